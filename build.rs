@@ -0,0 +1,213 @@
+//! Build script: turn `src/applets/usage.txt` into the usage table, and set
+//! up linking for `nolibc` builds.
+//!
+//! Only usage entries for applets whose Cargo feature is enabled are kept.
+//! With `usage-compressed` the text is LZ-compressed; the matching decoder
+//! is in `src/applets/usage.rs`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const USAGE_TXT: &str = "src/applets/usage.txt";
+
+/// Shortest back-reference worth encoding (a reference costs 3 bytes)
+const MIN_MATCH: usize = 4;
+/// Longest back-reference: the low 7 bits of the tag byte hold `len - MIN_MATCH`
+const MAX_MATCH: usize = MIN_MATCH + 0x7f;
+/// Farthest back-reference: the distance is a 16-bit field
+const MAX_DISTANCE: usize = 0xffff;
+/// Candidates checked per position; keeps the script fast without much loss
+const MAX_CANDIDATES: usize = 64;
+
+struct Entry {
+    name: String,
+    synopsis: String,
+    text: String,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", USAGE_TXT);
+    println!("cargo:rerun-if-changed=build.rs");
+    // Read by the `arena` allocator
+    println!("cargo:rerun-if-env-changed=ARMYBOX_ARENA_SIZE");
+
+    if env::var_os("CARGO_FEATURE_NOLIBC").is_some() {
+        link_without_libc();
+    }
+    if env::var_os("CARGO_FEATURE_USAGE").is_some() {
+        usage_table();
+    }
+}
+
+/// Link the binary with no C runtime. `-lc` and friends still appear on the
+/// link line (from `.cargo/config.toml` and the `libc` crate), so they are
+/// pointed at empty archives: anything `src/nolibc` doesn't define is an
+/// undefined symbol instead of a silent dependency on the real libc.
+fn link_without_libc() {
+    let dir = Path::new(&env::var("OUT_DIR").unwrap()).join("nolibc");
+    fs::create_dir_all(&dir).expect("create nolibc dir");
+    for lib in ["c", "m", "rt", "pthread", "dl", "util", "gcc_s", "gcc"] {
+        fs::write(dir.join(format!("lib{}.a", lib)), "!<arch>\n").expect("write empty archive");
+    }
+    println!("cargo:rustc-link-search=native={}", dir.display());
+    for arg in ["-nostartfiles", "-nostdlib", "-static", "-no-pie"] {
+        println!("cargo:rustc-link-arg-bins={}", arg);
+    }
+}
+
+/// Write OUT_DIR/usage.rs, the table behind `applets::usage`
+fn usage_table() {
+    let source = fs::read_to_string(USAGE_TXT).expect("read usage.txt");
+    let mut entries = parse(&source);
+    entries.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+
+    // One blob: each entry's synopsis followed by its text
+    let mut blob = String::new();
+    let mut index = String::new();
+    for entry in &entries {
+        let start = blob.len();
+        blob.push_str(&entry.synopsis);
+        let split = blob.len();
+        blob.push_str(&entry.text);
+        writeln!(
+            index,
+            "    ({}, {}, {}, {}),",
+            byte_string(entry.name.as_bytes()),
+            start,
+            split,
+            blob.len()
+        )
+        .unwrap();
+    }
+
+    let compressed = env::var_os("CARGO_FEATURE_USAGE_COMPRESSED").is_some();
+    let data = if compressed { compress(blob.as_bytes()) } else { blob.into_bytes() };
+
+    let mut out = String::new();
+    writeln!(out, "/// (name, synopsis start, text start, text end) into the unpacked text").unwrap();
+    writeln!(out, "static INDEX: &[(&[u8], u32, u32, u32)] = &[\n{}];", index).unwrap();
+    writeln!(out, "static TEXT: &[u8] = {};", byte_string(&data)).unwrap();
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("usage.rs");
+    fs::write(dest, out).expect("write usage.rs");
+}
+
+/// Parse `usage.txt`, keeping entries for enabled applets
+fn parse(source: &str) -> Vec<Entry> {
+    let mut entries: Vec<(String, Entry)> = Vec::new();
+    let mut lines = source.lines();
+
+    while let Some(line) = lines.next() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('@') {
+            let mut words = header.split_whitespace();
+            let name = words.next().expect("usage entry without a name");
+            let feature = words.next().unwrap_or(name);
+            let synopsis = lines.next().unwrap_or("").trim_end().to_string();
+            let entry = Entry { name: name.to_string(), synopsis, text: String::new() };
+            entries.push((feature.to_string(), entry));
+            continue;
+        }
+
+        match entries.last_mut() {
+            Some((_, entry)) => {
+                entry.text.push_str(line.trim_end());
+                entry.text.push('\n');
+            }
+            None if line.trim().is_empty() => {}
+            None => panic!("usage.txt: text before the first entry: {:?}", line),
+        }
+    }
+
+    entries
+        .into_iter()
+        .filter(|(feature, _)| {
+            let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+            env::var_os(var).is_some()
+        })
+        .map(|(_, mut entry)| {
+            // Drop the blank lines between entries
+            let len = entry.text.trim_end().len();
+            entry.text.truncate(len);
+            if !entry.text.is_empty() {
+                entry.text.push('\n');
+            }
+            assert!(
+                entry.synopsis.is_ascii() && entry.text.is_ascii(),
+                "usage.txt: {} is not ASCII",
+                entry.name
+            );
+            entry
+        })
+        .collect()
+}
+
+/// Greedy LZ77. Bytes below 0x80 are literals; a tag byte `0x80 | (len - 4)`
+/// is followed by a big-endian 16-bit distance back into the output.
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut chains: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    let mut i = 0;
+
+    while i < input.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= input.len() {
+            if let Some(candidates) = chains.get(&input[i..i + MIN_MATCH]) {
+                for &start in candidates.iter().rev().take(MAX_CANDIDATES) {
+                    if i - start > MAX_DISTANCE {
+                        break;
+                    }
+                    let len = input[start..]
+                        .iter()
+                        .zip(&input[i..])
+                        .take(MAX_MATCH)
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if len > best.0 {
+                        best = (len, i - start);
+                    }
+                }
+            }
+        }
+
+        let step = if best.0 >= MIN_MATCH {
+            out.push(0x80 | (best.0 - MIN_MATCH) as u8);
+            out.push((best.1 >> 8) as u8);
+            out.push(best.1 as u8);
+            best.0
+        } else {
+            out.push(input[i]);
+            1
+        };
+
+        for pos in i..i + step {
+            if pos + MIN_MATCH <= input.len() {
+                chains.entry(&input[pos..pos + MIN_MATCH]).or_default().push(pos);
+            }
+        }
+        i += step;
+    }
+    out
+}
+
+/// Rust source for a byte string literal
+fn byte_string(bytes: &[u8]) -> String {
+    let mut s = String::from("b\"");
+    for &b in bytes {
+        match b {
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            b'\n' => s.push_str("\\n"),
+            b'\t' => s.push_str("\\t"),
+            0x20..=0x7e => s.push(b as char),
+            _ => write!(s, "\\x{:02x}", b).unwrap(),
+        }
+    }
+    s.push('"');
+    s
+}
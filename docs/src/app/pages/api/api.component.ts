@@ -151,7 +151,7 @@ armybox = {{ '{' }} version = "0.2", default-features = false, features = ["allo
             <pre class="text-army-100"><span class="text-purple-400">#![no_std]</span>
 <span class="text-purple-400">extern crate</span> alloc;
 
-<span class="text-purple-400">use</span> armybox::{{ '{' }} run_applet, is_applet, applets {{ '}' }};
+<span class="text-purple-400">use</span> armybox::{{ '{' }} run_applet_args, is_applet, applets {{ '}' }};
 
 <span class="text-army-500">// Check if an applet exists</span>
 <span class="text-purple-400">let</span> exists = is_applet(<span class="text-yellow-400">b"echo"</span>);
@@ -161,8 +161,11 @@ armybox = {{ '{' }} version = "0.2", default-features = false, features = ["allo
     <span class="text-army-500">// name is &amp;[u8]</span>
 {{ '}' }}
 
-<span class="text-army-500">// Run an applet (takes C-style argc/argv)</span>
-<span class="text-purple-400">let</span> exit_code = run_applet(<span class="text-yellow-400">b"echo"</span>, argc, argv);</pre>
+<span class="text-army-500">// Run an applet (argv[0] first, as in C)</span>
+<span class="text-purple-400">let</span> exit_code = run_applet_args(<span class="text-yellow-400">b"echo"</span>, &amp;[<span class="text-yellow-400">c"echo"</span>, <span class="text-yellow-400">c"hello"</span>]);
+
+<span class="text-army-500">// With a C argv already in hand (see the # Safety section)</span>
+<span class="text-purple-400">let</span> exit_code = <span class="text-purple-400">unsafe</span> {{ '{' }} armybox::run_applet(<span class="text-yellow-400">b"echo"</span>, argc, argv) {{ '}' }};</pre>
           </div>
         </section>
 
@@ -0,0 +1,45 @@
+#![no_main]
+
+use armybox::applets::regex::{self, Captures, Regex};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // First byte: flags; second: pattern length; the rest: pattern + text
+    if data.len() < 2 {
+        return;
+    }
+    let flags = data[0] as u32 & (regex::EXTENDED | regex::ICASE);
+    let pattern_len = (data[1] as usize % 64).min(data.len() - 2);
+    let pattern = &data[2..2 + pattern_len];
+    let text = &data[2 + pattern_len..];
+
+    // Compiling must never panic, whatever the pattern
+    let re = match Regex::new(pattern, flags) {
+        Ok(re) => re,
+        Err(_) => return,
+    };
+
+    // Back-references use the backtracking matcher, which is exponential
+    // in the worst case; keep its texts short
+    let backrefs = pattern.windows(2).any(|w| w[0] == b'\\' && w[1].is_ascii_digit());
+    if backrefs && text.len() > 24 {
+        return;
+    }
+
+    let found = re.find_at(text, 0);
+    assert_eq!(re.is_match(text), found.is_some());
+
+    let mut caps = Captures::new();
+    assert_eq!(re.captures_at(text, 0, &mut caps), found.is_some());
+    if let Some((start, end)) = found {
+        assert!(start <= end && end <= text.len());
+        assert_eq!(caps.get(0), found);
+        for group in 1..=re.groups() {
+            if let Some((s, e)) = caps.get(group) {
+                assert!(start <= s && s <= e && e <= end);
+            }
+        }
+        // Searching again from the match start finds the same match
+        assert_eq!(re.find_at(text, start), found);
+    }
+});
@@ -0,0 +1,206 @@
+//! Global allocator
+//!
+//! By default allocations go to libc `malloc`, or `posix_memalign` for
+//! alignments `malloc` doesn't guarantee. With the `arena` feature they are
+//! carved out of a fixed static arena instead, so armybox's own allocations
+//! never reach `malloc` and can't grow past a known bound.
+//!
+//! Applets don't try to recover from allocation failure: it is reported as
+//! "out of memory" and the process exits with status 1.
+
+use core::alloc::{GlobalAlloc, Layout};
+
+/// Report a failed allocation and exit
+#[cold]
+fn out_of_memory() -> ! {
+    crate::io::write_str(2, b"armybox: out of memory\n");
+    crate::io::exit(1);
+}
+
+#[cfg(not(feature = "arena"))]
+mod backend {
+    use super::*;
+
+    /// Alignment every `malloc` result has
+    const MIN_ALIGN: usize = core::mem::align_of::<libc::max_align_t>();
+
+    /// Whether `malloc`/`realloc` alone satisfy `align` for `size` bytes
+    /// (small blocks may be less aligned than `MIN_ALIGN`)
+    fn malloc_aligns(align: usize, size: usize) -> bool {
+        align <= MIN_ALIGN && align <= size
+    }
+
+    pub struct LibcAllocator;
+
+    unsafe impl GlobalAlloc for LibcAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = if malloc_aligns(layout.align(), layout.size()) {
+                unsafe { libc::malloc(layout.size()) as *mut u8 }
+            } else {
+                let mut ptr = core::ptr::null_mut();
+                let align = layout.align().max(core::mem::size_of::<usize>());
+                match unsafe { libc::posix_memalign(&mut ptr, align, layout.size()) } {
+                    0 => ptr as *mut u8,
+                    _ => core::ptr::null_mut(),
+                }
+            };
+            if ptr.is_null() {
+                out_of_memory();
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            if !malloc_aligns(layout.align(), layout.size()) {
+                let ptr = unsafe { self.alloc(layout) };
+                unsafe { core::ptr::write_bytes(ptr, 0, layout.size()) };
+                return ptr;
+            }
+            let ptr = unsafe { libc::calloc(1, layout.size()) as *mut u8 };
+            if ptr.is_null() {
+                out_of_memory();
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+            unsafe { libc::free(ptr as *mut libc::c_void) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            if !malloc_aligns(layout.align(), new_size) {
+                // realloc may move the block to a less aligned address
+                let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+                let new = unsafe { self.alloc(new_layout) };
+                unsafe {
+                    core::ptr::copy_nonoverlapping(ptr, new, layout.size().min(new_size));
+                    self.dealloc(ptr, layout);
+                }
+                return new;
+            }
+            let ptr = unsafe { libc::realloc(ptr as *mut libc::c_void, new_size) as *mut u8 };
+            if ptr.is_null() {
+                out_of_memory();
+            }
+            ptr
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: LibcAllocator = LibcAllocator;
+}
+
+#[cfg(feature = "arena")]
+mod backend {
+    use super::*;
+    use core::cell::UnsafeCell;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Arena size: `ARMYBOX_ARENA_SIZE` at build time, in bytes with an
+    /// optional K, M or G suffix (default 16M). It lives in `.bss`, so it
+    /// costs address space but not binary size.
+    const ARENA_SIZE: usize = match option_env!("ARMYBOX_ARENA_SIZE") {
+        Some(size) => parse_size(size.as_bytes()),
+        None => 16 << 20,
+    };
+
+    const fn parse_size(s: &[u8]) -> usize {
+        let mut n: usize = 0;
+        let mut i = 0;
+        while i < s.len() && s[i].is_ascii_digit() {
+            n = n * 10 + (s[i] - b'0') as usize;
+            i += 1;
+        }
+        let shift = if i + 1 == s.len() {
+            match s[i] {
+                b'K' | b'k' => 10,
+                b'M' | b'm' => 20,
+                b'G' | b'g' => 30,
+                _ => panic!("ARMYBOX_ARENA_SIZE: bad suffix"),
+            }
+        } else if i == s.len() && i > 0 {
+            0
+        } else {
+            panic!("ARMYBOX_ARENA_SIZE: not a size")
+        };
+        n << shift
+    }
+
+    /// Page aligned, so page-aligned requests don't waste arena space
+    #[repr(C, align(4096))]
+    struct Arena(UnsafeCell<[u8; ARENA_SIZE]>);
+
+    // Blocks are handed out by atomically bumping `NEXT`
+    unsafe impl Sync for Arena {}
+
+    static ARENA: Arena = Arena(UnsafeCell::new([0; ARENA_SIZE]));
+
+    /// Offset of the first free byte
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    fn base() -> usize {
+        ARENA.0.get() as usize
+    }
+
+    /// Bump allocator over `ARENA`.
+    ///
+    /// Freed memory is only reclaimed when it is the most recent block,
+    /// which is also the only block `realloc` can resize in place. That
+    /// covers the common case of one growing buffer; applets that churn
+    /// through many blocks need a bigger arena.
+    pub struct ArenaAllocator;
+
+    unsafe impl GlobalAlloc for ArenaAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let mut next = NEXT.load(Ordering::Relaxed);
+            loop {
+                let start = (base() + next).next_multiple_of(layout.align()) - base();
+                let end = match start.checked_add(layout.size()) {
+                    Some(end) if end <= ARENA_SIZE => end,
+                    _ => out_of_memory(),
+                };
+                match NEXT.compare_exchange_weak(next, end, Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => return (base() + start) as *mut u8,
+                    Err(current) => next = current,
+                }
+            }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let start = ptr as usize - base();
+            let _ = NEXT.compare_exchange(
+                start + layout.size(),
+                start,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let start = ptr as usize - base();
+            let old_end = start + layout.size();
+            let new_end = start.saturating_add(new_size);
+            if new_end <= ARENA_SIZE
+                && NEXT
+                    .compare_exchange(old_end, new_end, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return ptr;
+            }
+            if new_size <= layout.size() {
+                return ptr;
+            }
+
+            let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+            let new = unsafe { self.alloc(new_layout) };
+            unsafe {
+                core::ptr::copy_nonoverlapping(ptr, new, layout.size());
+                self.dealloc(ptr, layout);
+            }
+            new
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: ArenaAllocator = ArenaAllocator;
+}
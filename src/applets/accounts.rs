@@ -0,0 +1,508 @@
+//! User and group databases
+//!
+//! Reads `/etc/passwd`, `/etc/group` and `/etc/shadow` directly, with no
+//! NSS, so lookups work the same in static, musl and `nolibc` builds. Each
+//! file is read whole with `Users::open`, `Groups::open` or
+//! `Shadows::open`, and looked up as often as needed with the methods named
+//! after their libc counterparts: `getpwnam`, `getpwuid`, `getgrnam`,
+//! `getgrgid`, `getgrouplist` and `getspnam`.
+//!
+//! The files are found under `$ARMYBOX_ROOT` when `sys::data_root` allows
+//! it, as are the login records `utmp` reads.
+//!
+//! `rewrite` replaces one of the files atomically, for applets that edit
+//! accounts.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use crate::io;
+use crate::sys;
+
+/// The largest file that can be read without `alloc`; bigger ones fail with
+/// `EFBIG` rather than being read in part
+#[cfg(not(feature = "alloc"))]
+const MAX_SIZE: usize = 16384;
+
+/// One of the account files
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Database {
+    Passwd,
+    Group,
+    Shadow,
+}
+
+impl Database {
+    /// The file's path under the root
+    fn path(self) -> &'static [u8] {
+        match self {
+            Database::Passwd => b"/etc/passwd",
+            Database::Group => b"/etc/group",
+            Database::Shadow => b"/etc/shadow",
+        }
+    }
+
+    /// The mode for a new file: only `shadow` is secret
+    fn mode(self) -> u32 {
+        if self == Database::Shadow { 0o600 } else { 0o644 }
+    }
+}
+
+/// The directory `etc/` is under, without a trailing `/`
+fn root() -> &'static [u8] {
+    sys::data_root(b"ARMYBOX_ROOT").unwrap_or(b"")
+}
+
+/// `path` under the root with `suffix` added, in `buf`
+pub fn full_path<'a>(path: &[u8], suffix: &[u8], buf: &'a mut [u8; 512]) -> Option<&'a [u8]> {
+    join(root(), path, suffix, buf)
+}
+
+/// `path` under `root` with `suffix` added, in `buf`
+fn join<'a>(root: &[u8], path: &[u8], suffix: &[u8], buf: &'a mut [u8; 512]) -> Option<&'a [u8]> {
+    let mut len = 0;
+    for part in [root, path, suffix] {
+        buf.get_mut(len..len + part.len())?.copy_from_slice(part);
+        len += part.len();
+    }
+    Some(&buf[..len])
+}
+
+/// The contents of an account file
+struct Contents {
+    #[cfg(feature = "alloc")]
+    data: Vec<u8>,
+    #[cfg(not(feature = "alloc"))]
+    buf: [u8; MAX_SIZE],
+    #[cfg(not(feature = "alloc"))]
+    len: usize,
+}
+
+impl Contents {
+    /// Read the whole of `db` under `root`, or the errno of the failure
+    fn read(root: &[u8], db: Database) -> Result<Contents, i32> {
+        let mut path = [0u8; 512];
+        let path = join(root, db.path(), b"", &mut path).ok_or(libc::ENAMETOOLONG)?;
+        let fd = io::open(path, libc::O_RDONLY | libc::O_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(sys::errno());
+        }
+        let contents = Contents::read_fd(fd);
+        io::close(fd);
+        contents
+    }
+
+    #[cfg(feature = "alloc")]
+    fn read_fd(fd: i32) -> Result<Contents, i32> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = io::read(fd, &mut buf);
+            if n < 0 {
+                return Err(sys::errno());
+            }
+            if n == 0 {
+                return Ok(Contents { data });
+            }
+            data.extend_from_slice(&buf[..n as usize]);
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn read_fd(fd: i32) -> Result<Contents, i32> {
+        let mut contents = Contents { buf: [0; MAX_SIZE], len: 0 };
+        loop {
+            if contents.len == MAX_SIZE {
+                return Err(libc::EFBIG);
+            }
+            let n = io::read(fd, &mut contents.buf[contents.len..]);
+            if n < 0 {
+                return Err(sys::errno());
+            }
+            if n == 0 {
+                return Ok(contents);
+            }
+            contents.len += n as usize;
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        #[cfg(feature = "alloc")]
+        return &self.data;
+        #[cfg(not(feature = "alloc"))]
+        return &self.buf[..self.len];
+    }
+
+    /// The entries, skipping blank lines and `#` comments
+    fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.data().split(|&c| c == b'\n').filter(|line| !line.is_empty() && line[0] != b'#')
+    }
+}
+
+/// Parse a numeric user or group id, which must fit in 32 bits and not be
+/// -1
+pub fn parse_id(s: &[u8]) -> Option<u32> {
+    sys::parse_u64(s).and_then(|id| u32::try_from(id).ok()).filter(|&id| id != u32::MAX)
+}
+
+/// An `/etc/passwd` entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Passwd<'a> {
+    pub name: &'a [u8],
+    /// Usually `x`, with the hash in `/etc/shadow`
+    pub passwd: &'a [u8],
+    pub uid: u32,
+    pub gid: u32,
+    pub gecos: &'a [u8],
+    pub dir: &'a [u8],
+    pub shell: &'a [u8],
+}
+
+/// Parse `NAME:PASSWD:UID:GID:GECOS:DIR:SHELL`
+pub fn parse_passwd(line: &[u8]) -> Option<Passwd<'_>> {
+    let mut fields = line.split(|&c| c == b':');
+    let entry = Passwd {
+        name: fields.next().filter(|name| !name.is_empty())?,
+        passwd: fields.next()?,
+        uid: parse_id(fields.next()?)?,
+        gid: parse_id(fields.next()?)?,
+        gecos: fields.next()?,
+        dir: fields.next()?,
+        shell: fields.next()?,
+    };
+    fields.next().is_none().then_some(entry)
+}
+
+/// An `/etc/group` entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Group<'a> {
+    pub name: &'a [u8],
+    pub passwd: &'a [u8],
+    pub gid: u32,
+    /// The comma-separated member list; see `members`
+    pub member_list: &'a [u8],
+}
+
+impl<'a> Group<'a> {
+    /// The users listed as members, who needn't have this as their
+    /// primary group
+    pub fn members(&self) -> impl Iterator<Item = &'a [u8]> + use<'a> {
+        self.member_list.split(|&c| c == b',').filter(|name| !name.is_empty())
+    }
+}
+
+/// Parse `NAME:PASSWD:GID:MEMBER,...`
+pub fn parse_group(line: &[u8]) -> Option<Group<'_>> {
+    let mut fields = line.split(|&c| c == b':');
+    let entry = Group {
+        name: fields.next().filter(|name| !name.is_empty())?,
+        passwd: fields.next()?,
+        gid: parse_id(fields.next()?)?,
+        member_list: fields.next()?,
+    };
+    fields.next().is_none().then_some(entry)
+}
+
+/// An `/etc/shadow` entry. Dates are in days since 1970-01-01, and empty
+/// fields are `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shadow<'a> {
+    pub name: &'a [u8],
+    /// The password hash, or `!` or `*` (perhaps in front of one) for a
+    /// locked account
+    pub passwd: &'a [u8],
+    pub last_change: Option<i64>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub warn: Option<i64>,
+    pub inactive: Option<i64>,
+    pub expire: Option<i64>,
+}
+
+/// Parse `NAME:PASSWD:LAST:MIN:MAX:WARN:INACTIVE:EXPIRE:RESERVED`
+pub fn parse_shadow(line: &[u8]) -> Option<Shadow<'_>> {
+    let mut fields = line.split(|&c| c == b':');
+    let name = fields.next().filter(|name| !name.is_empty())?;
+    let passwd = fields.next()?;
+    let mut days = [None; 6];
+    for day in &mut days {
+        *day = match fields.next()? {
+            b"" => None,
+            field => Some(sys::parse_i64(field)?),
+        };
+    }
+    let _reserved = fields.next()?;
+    let [last_change, min, max, warn, inactive, expire] = days;
+    fields.next().is_none().then_some(Shadow { name, passwd, last_change, min, max, warn, inactive, expire })
+}
+
+/// `/etc/passwd`
+pub struct Users(Contents);
+
+impl Users {
+    /// Read `/etc/passwd`, or `None` if it can't be
+    pub fn open() -> Option<Users> {
+        Contents::read(root(), Database::Passwd).ok().map(Users)
+    }
+
+    /// The valid entries, in order
+    pub fn iter(&self) -> impl Iterator<Item = Passwd<'_>> {
+        self.0.lines().filter_map(parse_passwd)
+    }
+
+    /// The first entry named `name`
+    pub fn getpwnam(&self, name: &[u8]) -> Option<Passwd<'_>> {
+        self.iter().find(|pw| pw.name == name)
+    }
+
+    /// The first entry for `uid`
+    pub fn getpwuid(&self, uid: u32) -> Option<Passwd<'_>> {
+        self.iter().find(|pw| pw.uid == uid)
+    }
+
+    /// The uid of a user name, or of a number that isn't one
+    pub fn uid_of(&self, name: &[u8]) -> Option<u32> {
+        self.getpwnam(name).map(|pw| pw.uid).or_else(|| parse_id(name))
+    }
+}
+
+/// `/etc/group`
+pub struct Groups(Contents);
+
+impl Groups {
+    /// Read `/etc/group`, or `None` if it can't be
+    pub fn open() -> Option<Groups> {
+        Contents::read(root(), Database::Group).ok().map(Groups)
+    }
+
+    /// The valid entries, in order
+    pub fn iter(&self) -> impl Iterator<Item = Group<'_>> {
+        self.0.lines().filter_map(parse_group)
+    }
+
+    /// The first entry named `name`
+    pub fn getgrnam(&self, name: &[u8]) -> Option<Group<'_>> {
+        self.iter().find(|gr| gr.name == name)
+    }
+
+    /// The first entry for `gid`
+    pub fn getgrgid(&self, gid: u32) -> Option<Group<'_>> {
+        self.iter().find(|gr| gr.gid == gid)
+    }
+
+    /// The gid of a group name, or of a number that isn't one
+    pub fn gid_of(&self, name: &[u8]) -> Option<u32> {
+        self.getgrnam(name).map(|gr| gr.gid).or_else(|| parse_id(name))
+    }
+
+    /// Fill `groups` with `gid` followed by every other group listing `user`
+    /// as a member, without repeats, as `getgrouplist(3)` does.
+    ///
+    /// Returns how many there are, which may be more than fit.
+    pub fn getgrouplist(&self, user: &[u8], gid: u32, groups: &mut [u32]) -> usize {
+        let mut count = 0;
+        let mut add = |gid: u32, groups: &mut [u32]| {
+            if groups[..count.min(groups.len())].contains(&gid) {
+                return;
+            }
+            if let Some(slot) = groups.get_mut(count) {
+                *slot = gid;
+            }
+            count += 1;
+        };
+        add(gid, groups);
+        for group in self.iter().filter(|gr| gr.members().any(|member| member == user)) {
+            add(group.gid, groups);
+        }
+        count
+    }
+}
+
+/// `/etc/shadow`, which only root can normally read
+pub struct Shadows(Contents);
+
+impl Shadows {
+    /// Read `/etc/shadow`, or `None` if it can't be
+    pub fn open() -> Option<Shadows> {
+        Contents::read(root(), Database::Shadow).ok().map(Shadows)
+    }
+
+    /// The valid entries, in order
+    pub fn iter(&self) -> impl Iterator<Item = Shadow<'_>> {
+        self.0.lines().filter_map(parse_shadow)
+    }
+
+    /// The first entry named `name`
+    pub fn getspnam(&self, name: &[u8]) -> Option<Shadow<'_>> {
+        self.iter().find(|sp| sp.name == name)
+    }
+}
+
+/// Holds `etc/.pwd.lock`, as `lckpwdf(3)` does, until dropped
+struct Lock(i32);
+
+impl Lock {
+    /// Wait up to 15 seconds for the lock under `root`
+    fn take(root: &[u8]) -> Result<Lock, i32> {
+        let mut path = [0u8; 512];
+        let path = join(root, b"/etc/.pwd.lock", b"", &mut path).ok_or(libc::ENAMETOOLONG)?;
+        let fd = io::open(path, libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC, 0o600);
+        if fd < 0 {
+            return Err(sys::errno());
+        }
+        let mut lock: libc::flock = unsafe { core::mem::zeroed() };
+        lock.l_type = libc::F_WRLCK as i16;
+        lock.l_whence = libc::SEEK_SET as i16;
+        for _ in 0..15 {
+            if unsafe { libc::fcntl(fd, libc::F_SETLK, &lock as *const libc::flock) } == 0 {
+                return Ok(Lock(fd));
+            }
+            io::sleep(1);
+        }
+        io::close(fd);
+        Err(libc::EAGAIN)
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock
+        io::close(self.0);
+    }
+}
+
+/// Replace `db` with what `edit` makes of it, atomically.
+///
+/// `edit` is called with each line of the file, without its newline, and
+/// then once with `None` to add lines at the end; each call passes the
+/// lines to keep or add to its `emit` argument. The new file is written
+/// to `FILE+` with the old one's owner and mode, synced, and renamed over
+/// the old one, which is kept as `FILE-`. Returns the errno of the first
+/// failure, leaving the old file in place; that includes failing to read
+/// all of it, so entries are never lost to a short read.
+pub fn rewrite(db: Database, edit: impl FnMut(Option<&[u8]>, &mut dyn FnMut(&[u8]))) -> Result<(), i32> {
+    rewrite_under(root(), db, edit)
+}
+
+/// `rewrite` for the files under `root`
+fn rewrite_under(root: &[u8], db: Database, mut edit: impl FnMut(Option<&[u8]>, &mut dyn FnMut(&[u8]))) -> Result<(), i32> {
+    let _lock = Lock::take(root)?;
+    let (mut path, mut new_path, mut old_path) = ([0u8; 512], [0u8; 512], [0u8; 512]);
+    let path = join(root, db.path(), b"", &mut path).ok_or(libc::ENAMETOOLONG)?;
+    let new_path = join(root, db.path(), b"+", &mut new_path).ok_or(libc::ENAMETOOLONG)?;
+    let old_path = join(root, db.path(), b"-", &mut old_path).ok_or(libc::ENAMETOOLONG)?;
+
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    let exists = io::stat(path, &mut st) == 0;
+    if !exists && sys::errno() != libc::ENOENT {
+        return Err(sys::errno());
+    }
+    let old = match Contents::read(root, db) {
+        Ok(old) => Some(old),
+        Err(_) if !exists => None,
+        Err(errno) => return Err(errno),
+    };
+
+    io::unlink(new_path);
+    let fd = io::open(new_path, libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC, db.mode());
+    if fd < 0 {
+        return Err(sys::errno());
+    }
+    let mut error = 0;
+    if exists && unsafe { libc::fchown(fd, st.st_uid, st.st_gid) != 0 || libc::fchmod(fd, st.st_mode & 0o7777) != 0 } {
+        error = sys::errno();
+    }
+
+    let mut emit = |line: &[u8]| {
+        if error == 0 && (io::write_all(fd, line) < 0 || io::write_all(fd, b"\n") < 0) {
+            error = sys::errno();
+        }
+    };
+    if let Some(old) = &old {
+        // Comments and blank lines are kept as they are
+        let data = old.data().strip_suffix(b"\n").unwrap_or(old.data());
+        for line in data.split(|&c| c == b'\n').filter(|_| !data.is_empty()) {
+            if line.is_empty() || line[0] == b'#' {
+                emit(line);
+            } else {
+                edit(Some(line), &mut emit);
+            }
+        }
+    }
+    edit(None, &mut emit);
+
+    if error == 0 && unsafe { libc::fsync(fd) } != 0 {
+        error = sys::errno();
+    }
+    io::close(fd);
+    if error == 0 && exists {
+        io::unlink(old_path);
+        io::link(path, old_path);
+    }
+    if error == 0 && io::rename(new_path, path) != 0 {
+        error = sys::errno();
+    }
+    if error != 0 {
+        io::unlink(new_path);
+        return Err(error);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Rewrite `etc/passwd` under `root`, keeping every entry
+    fn keep_all(root: &Path) -> Result<(), i32> {
+        rewrite_under(root.as_os_str().as_bytes(), Database::Passwd, |line, emit| {
+            if let Some(line) = line {
+                emit(line);
+            }
+        })
+    }
+
+    #[test]
+    fn rewrite_keeps_entries() {
+        let root = tempfile::tempdir().unwrap();
+        let etc = root.path().join("etc");
+        std::fs::create_dir(&etc).unwrap();
+        std::fs::write(etc.join("passwd"), "root:x:0:0::/root:/bin/sh\n# staff\n").unwrap();
+
+        assert_eq!(keep_all(root.path()), Ok(()));
+        assert_eq!(std::fs::read(etc.join("passwd")).unwrap(), b"root:x:0:0::/root:/bin/sh\n# staff\n");
+        assert_eq!(std::fs::read(etc.join("passwd-")).unwrap(), b"root:x:0:0::/root:/bin/sh\n# staff\n");
+        assert!(!etc.join("passwd+").exists());
+    }
+
+    #[test]
+    fn rewrite_leaves_unreadable_database_alone() {
+        let root = tempfile::tempdir().unwrap();
+        let etc = root.path().join("etc");
+        std::fs::create_dir_all(etc.join("real")).unwrap();
+        // Opens, but reading fails even for root; renaming over the link
+        // would still work
+        std::os::unix::fs::symlink("real", etc.join("passwd")).unwrap();
+
+        assert_eq!(keep_all(root.path()), Err(libc::EISDIR));
+        assert_eq!(std::fs::read_link(etc.join("passwd")).unwrap(), Path::new("real"));
+        assert!(!etc.join("passwd+").exists());
+        assert!(!etc.join("passwd-").exists());
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[test]
+    fn rewrite_leaves_oversized_database_alone() {
+        let root = tempfile::tempdir().unwrap();
+        let etc = root.path().join("etc");
+        std::fs::create_dir(&etc).unwrap();
+        let passwd: Vec<u8> = (0..MAX_SIZE / 16).flat_map(|i| format!("u{:05}:x:{:05}::/:\n", i, i).into_bytes()).collect();
+        assert!(passwd.len() > MAX_SIZE);
+        std::fs::write(etc.join("passwd"), &passwd).unwrap();
+
+        assert_eq!(keep_all(root.path()), Err(libc::EFBIG));
+        assert_eq!(std::fs::read(etc.join("passwd")).unwrap(), passwd);
+        assert!(!etc.join("passwd+").exists());
+    }
+}
@@ -0,0 +1,1176 @@
+//! Time zones and date formatting
+//!
+//! `TimeZone::local` finds the local zone the way the C library does:
+//! `$TZ` names a TZif file under `/usr/share/zoneinfo` (or `$TZDIR`) or is a
+//! POSIX TZ string such as `CET-1CEST,M3.5.0,M10.5.0/3`, and
+//! `/etc/localtime` is used when it is unset. TZif versions 1 to 4 are
+//! read, including the TZ string a version 2 file ends with for times after
+//! its last transition. Leap second records are ignored.
+//!
+//! `TimeZone::localtime` and `TimeZone::mktime` convert between seconds
+//! since the epoch and a broken-down [`Tm`], `strftime` formats a `Tm` with
+//! the GNU conversions and flags of the C locale, and `strptime` reads one
+//! back. `parse_date` accepts the date forms applets take as arguments.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use crate::io;
+use crate::sys;
+
+/// Where zone files are found when `$TZDIR` is not set
+const ZONEINFO: &[u8] = b"/usr/share/zoneinfo";
+
+/// The largest zone file read without `alloc`; the biggest in the tz
+/// database are under 4 KiB
+#[cfg(not(feature = "alloc"))]
+const MAX_SIZE: usize = 16384;
+
+/// When daylight saving time applies under a TZ string without rules, as
+/// glibc assumes: the US rules since 2007
+const DEFAULT_RULES: &[u8] = b",M3.2.0,M11.1.0";
+
+pub const WEEKDAYS: [&[u8]; 7] = [
+    b"Sunday", b"Monday", b"Tuesday", b"Wednesday", b"Thursday", b"Friday", b"Saturday",
+];
+
+pub const MONTHS: [&[u8]; 12] = [
+    b"January", b"February", b"March", b"April", b"May", b"June",
+    b"July", b"August", b"September", b"October", b"November", b"December",
+];
+
+// ============================================================================
+// Calendar arithmetic
+// ============================================================================
+
+/// A time zone abbreviation such as `CEST`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Abbr {
+    buf: [u8; 15],
+    len: u8,
+}
+
+impl Abbr {
+    /// `s`, cut to 15 bytes
+    pub fn new(s: &[u8]) -> Abbr {
+        let len = s.len().min(15);
+        let mut buf = [0; 15];
+        buf[..len].copy_from_slice(&s[..len]);
+        Abbr { buf, len: len as u8 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+/// A broken-down time
+///
+/// Unlike C's `struct tm`, `year` is the full year and `mon` counts from 1.
+/// `timegm` and `TimeZone::mktime` carry fields outside their usual ranges
+/// over, so `mday: 0` is the last day of the month before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tm {
+    pub year: i64,
+    /// 1 to 12
+    pub mon: i32,
+    /// 1 to 31
+    pub mday: i32,
+    pub hour: i32,
+    pub min: i32,
+    /// 0 to 60, for a leap second
+    pub sec: i32,
+    pub nsec: u32,
+    /// Days since Sunday
+    pub wday: i32,
+    /// Days since January 1st
+    pub yday: i32,
+    /// Positive for daylight saving time, zero for standard time and
+    /// negative if unknown
+    pub isdst: i32,
+    /// Seconds east of UTC
+    pub gmtoff: i32,
+    pub zone: Abbr,
+}
+
+pub fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// The number of days in `mon` (1 to 12) of `year`
+pub fn days_in_month(year: i64, mon: i32) -> i32 {
+    match mon {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to a date in the proleptic Gregorian calendar, with
+/// `mon` from 1 to 12 and `mday` in any range
+pub fn days_from_civil(year: i64, mon: i32, mday: i32) -> i64 {
+    // Counted in 400-year eras of years starting in March, so the leap day
+    // comes last
+    let y = if mon <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((mon as i64 + 9) % 12) + 2) / 5 + mday as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `days` after 1970-01-01: year, month (1 to 12) and day
+pub fn civil_from_days(days: i64) -> (i64, i32, i32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let mday = (doy - (153 * mp + 2) / 5 + 1) as i32;
+    let mon = if mp < 10 { mp + 3 } else { mp - 9 } as i32;
+    (yoe + era * 400 + (mon <= 2) as i64, mon, mday)
+}
+
+/// Break `t` down as the local time `gmtoff` seconds east of UTC
+fn breakdown(t: i64, gmtoff: i32) -> Tm {
+    let local = t + gmtoff as i64;
+    let days = local.div_euclid(86400);
+    let secs = local.rem_euclid(86400) as i32;
+    let (year, mon, mday) = civil_from_days(days);
+    Tm {
+        year,
+        mon,
+        mday,
+        hour: secs / 3600,
+        min: secs / 60 % 60,
+        sec: secs % 60,
+        nsec: 0,
+        // 1970-01-01 was a Thursday
+        wday: (days + 4).rem_euclid(7) as i32,
+        yday: (days - days_from_civil(year, 1, 1)) as i32,
+        isdst: 0,
+        gmtoff,
+        zone: Abbr::default(),
+    }
+}
+
+/// `t` as UTC
+pub fn gmtime(t: i64) -> Tm {
+    Tm { zone: Abbr::new(b"UTC"), ..breakdown(t, 0) }
+}
+
+/// The seconds since the epoch of `tm` read as UTC, ignoring `gmtoff`
+pub fn timegm(tm: &Tm) -> i64 {
+    let mon = tm.mon as i64 - 1;
+    let year = tm.year + mon.div_euclid(12);
+    let days = days_from_civil(year, mon.rem_euclid(12) as i32 + 1, 1) + tm.mday as i64 - 1;
+    days * 86400 + tm.hour as i64 * 3600 + tm.min as i64 * 60 + tm.sec as i64
+}
+
+/// The current time: seconds since the epoch and nanoseconds
+pub fn now() -> (i64, u32) {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) };
+    (ts.tv_sec, ts.tv_nsec as u32)
+}
+
+// ============================================================================
+// Time zones
+// ============================================================================
+
+/// An offset from UTC in force for a while, with its name
+#[derive(Clone, Copy)]
+struct LocalType {
+    gmtoff: i32,
+    isdst: bool,
+    abbr: Abbr,
+}
+
+const UTC: LocalType = LocalType { gmtoff: 0, isdst: false, abbr: Abbr { buf: *b"UTC\0\0\0\0\0\0\0\0\0\0\0\0", len: 3 } };
+
+/// Where the 64-bit (or, in a version 1 file, 32-bit) data of a TZif file
+/// is
+#[derive(Clone, Copy)]
+struct Tzif {
+    /// 4 or 8
+    time_size: usize,
+    /// The start of the transition times, followed by their type indices
+    times: usize,
+    timecnt: usize,
+    /// The start of the 6-byte local time types
+    types: usize,
+    typecnt: usize,
+    /// The start of the NUL-terminated abbreviations
+    chars: usize,
+    charcnt: usize,
+}
+
+impl Tzif {
+    /// Read the header at `at`, returning its version and the data block
+    /// after it
+    fn parse(data: &[u8], at: usize, time_size: usize) -> Option<(u8, Tzif, usize)> {
+        let header = data.get(at..at + 44)?;
+        if &header[..4] != b"TZif" {
+            return None;
+        }
+        let count = |i: usize| u32::from_be_bytes(header[20 + 4 * i..24 + 4 * i].try_into().unwrap()) as usize;
+        let (isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt) = (count(0), count(1), count(2), count(3), count(4), count(5));
+        if typecnt == 0 {
+            return None;
+        }
+        let times = at + 44;
+        let types = times + timecnt * (time_size + 1);
+        let chars = types + typecnt * 6;
+        let end = chars + charcnt + leapcnt * (time_size + 4) + isstdcnt + isutcnt;
+        if end > data.len() {
+            return None;
+        }
+        Some((header[4], Tzif { time_size, times, timecnt, types, typecnt, chars, charcnt }, end))
+    }
+
+    fn time(&self, data: &[u8], i: usize) -> i64 {
+        let at = self.times + i * self.time_size;
+        if self.time_size == 8 {
+            i64::from_be_bytes(data[at..at + 8].try_into().unwrap())
+        } else {
+            i32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as i64
+        }
+    }
+
+    fn local_type(&self, data: &[u8], i: usize) -> LocalType {
+        if i >= self.typecnt {
+            return UTC;
+        }
+        let at = self.types + i * 6;
+        let gmtoff = i32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+        let chars = &data[self.chars..self.chars + self.charcnt];
+        let abbr = chars.get(data[at + 5] as usize..).unwrap_or(b"");
+        let len = abbr.iter().position(|&c| c == 0).unwrap_or(abbr.len());
+        LocalType { gmtoff, isdst: data[at + 4] != 0, abbr: Abbr::new(&abbr[..len]) }
+    }
+
+    /// The type in force at `t`: that of the last transition at or before
+    /// it, or the first type before any
+    fn find(&self, data: &[u8], t: i64) -> LocalType {
+        let (mut lo, mut hi) = (0, self.timecnt);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.time(data, mid) <= t {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let index = if lo == 0 { 0 } else { data[self.times + self.timecnt * self.time_size + lo - 1] as usize };
+        self.local_type(data, index)
+    }
+}
+
+/// A day daylight saving time starts or ends on, from a TZ string
+#[derive(Clone, Copy)]
+enum Day {
+    /// `Jn`: 1 to 365, never counting February 29th
+    Julian(i32),
+    /// `n`: 0 to 365, counting February 29th
+    Ordinal(i32),
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 is the last) of month
+    /// `m`
+    Month(i32, i32, i32),
+}
+
+/// A change between standard and daylight saving time: a day and a local
+/// time on it, in seconds
+#[derive(Clone, Copy)]
+struct Change {
+    day: Day,
+    time: i32,
+}
+
+impl Change {
+    /// When the change happens in `year`, as seconds since the epoch in the
+    /// local time it is given in
+    fn at(&self, year: i64) -> i64 {
+        let days = match self.day {
+            Day::Julian(n) => days_from_civil(year, 1, 1) + (n - 1) as i64 + (is_leap(year) && n >= 60) as i64,
+            Day::Ordinal(n) => days_from_civil(year, 1, 1) + n as i64,
+            Day::Month(m, w, d) => {
+                let first = days_from_civil(year, m, 1);
+                let wday = (first + 4).rem_euclid(7) as i32;
+                let mut mday = 1 + (d - wday).rem_euclid(7) + (w - 1) * 7;
+                while mday > days_in_month(year, m) {
+                    mday -= 7;
+                }
+                first + mday as i64 - 1
+            }
+        };
+        days * 86400 + self.time as i64
+    }
+}
+
+/// A POSIX TZ string: a standard time and, optionally, a daylight saving
+/// time with when it starts and ends
+#[derive(Clone, Copy)]
+struct Rule {
+    std: LocalType,
+    dst: Option<(LocalType, Change, Change)>,
+}
+
+impl Rule {
+    fn parse(s: &[u8]) -> Option<Rule> {
+        let mut s = s;
+        let std = LocalType { abbr: tz_name(&mut s)?, gmtoff: -tz_offset(&mut s, 24)?, isdst: false };
+        if s.is_empty() {
+            return Some(Rule { std, dst: None });
+        }
+        let abbr = tz_name(&mut s)?;
+        let gmtoff = match s.first() {
+            Some(c) if c.is_ascii_digit() || *c == b'+' || *c == b'-' => -tz_offset(&mut s, 24)?,
+            _ => std.gmtoff + 3600,
+        };
+        let dst = LocalType { abbr, gmtoff, isdst: true };
+        if s.is_empty() {
+            s = DEFAULT_RULES;
+        }
+        let start = tz_change(&mut s)?;
+        let end = tz_change(&mut s)?;
+        if !s.is_empty() {
+            return None;
+        }
+        Some(Rule { std, dst: Some((dst, start, end)) })
+    }
+
+    fn find(&self, t: i64) -> LocalType {
+        let Some((dst, start, end)) = self.dst else {
+            return self.std;
+        };
+        // The start is given in standard time and the end in daylight
+        // saving time
+        let year = breakdown(t, self.std.gmtoff).year;
+        let start = start.at(year) - self.std.gmtoff as i64;
+        let end = end.at(year) - dst.gmtoff as i64;
+        let in_dst = if start < end {
+            start <= t && t < end
+        } else {
+            // Southern hemisphere: daylight saving time spans the new year
+            !(end <= t && t < start)
+        };
+        if in_dst { dst } else { self.std }
+    }
+}
+
+/// Take a zone name: three or more letters, or anything but `>` in `<>`
+fn tz_name(s: &mut &[u8]) -> Option<Abbr> {
+    let (name, rest) = if let Some(quoted) = s.strip_prefix(b"<") {
+        let end = quoted.iter().position(|&c| c == b'>')?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let end = s.iter().position(|c| !c.is_ascii_alphabetic()).unwrap_or(s.len());
+        (&s[..end], &s[end..])
+    };
+    if name.len() < 3 {
+        return None;
+    }
+    *s = rest;
+    Some(Abbr::new(name))
+}
+
+/// Take `[+-]hh[:mm[:ss]]` with up to `max_hours` hours, in seconds
+fn tz_offset(s: &mut &[u8], max_hours: i64) -> Option<i32> {
+    let sign = match s.first() {
+        Some(b'-') => -1,
+        Some(b'+') => 1,
+        _ => 0,
+    };
+    if sign != 0 {
+        *s = &s[1..];
+    }
+    let mut secs = 0;
+    for (max, unit) in [(max_hours, 3600), (59, 60), (59, 1)] {
+        if unit < 3600 {
+            match s.strip_prefix(b":") {
+                Some(rest) => *s = rest,
+                None => break,
+            }
+        }
+        let n = take_number(s, if max > 99 { 3 } else { 2 })?;
+        if n > max {
+            return None;
+        }
+        secs += n * unit;
+    }
+    Some(if sign < 0 { -secs } else { secs } as i32)
+}
+
+/// Take `,date[/time]`
+fn tz_change(s: &mut &[u8]) -> Option<Change> {
+    *s = s.strip_prefix(b",")?;
+    let day = if let Some(rest) = s.strip_prefix(b"J") {
+        *s = rest;
+        let n = take_number(s, 3)? as i32;
+        if !(1..=365).contains(&n) {
+            return None;
+        }
+        Day::Julian(n)
+    } else if let Some(rest) = s.strip_prefix(b"M") {
+        *s = rest;
+        let m = take_number(s, 2)? as i32;
+        *s = s.strip_prefix(b".")?;
+        let w = take_number(s, 1)? as i32;
+        *s = s.strip_prefix(b".")?;
+        let d = take_number(s, 1)? as i32;
+        if !(1..=12).contains(&m) || !(1..=5).contains(&w) || d > 6 {
+            return None;
+        }
+        Day::Month(m, w, d)
+    } else {
+        let n = take_number(s, 3)? as i32;
+        if n > 365 {
+            return None;
+        }
+        Day::Ordinal(n)
+    };
+    // Changes happen at 02:00 unless the string says otherwise; version 3
+    // TZif files allow -167 to 167 hours
+    let time = match s.strip_prefix(b"/") {
+        Some(rest) => {
+            *s = rest;
+            tz_offset(s, 167)?
+        }
+        None => 7200,
+    };
+    Some(Change { day, time })
+}
+
+/// Take one to `max` decimal digits
+fn take_number(s: &mut &[u8], max: usize) -> Option<i64> {
+    let len = s.iter().take(max).take_while(|c| c.is_ascii_digit()).count();
+    if len == 0 {
+        return None;
+    }
+    let n = s[..len].iter().fold(0, |n, &c| n * 10 + (c - b'0') as i64);
+    *s = &s[len..];
+    Some(n)
+}
+
+/// The contents of a zone file
+struct Contents {
+    #[cfg(feature = "alloc")]
+    data: Vec<u8>,
+    #[cfg(not(feature = "alloc"))]
+    buf: [u8; MAX_SIZE],
+    #[cfg(not(feature = "alloc"))]
+    len: usize,
+}
+
+impl Contents {
+    fn empty() -> Contents {
+        #[cfg(feature = "alloc")]
+        return Contents { data: Vec::new() };
+        #[cfg(not(feature = "alloc"))]
+        return Contents { buf: [0; MAX_SIZE], len: 0 };
+    }
+
+    fn read(path: &[u8]) -> Option<Contents> {
+        let fd = io::open(path, libc::O_RDONLY | libc::O_CLOEXEC, 0);
+        if fd < 0 {
+            return None;
+        }
+        #[cfg(feature = "alloc")]
+        let contents = Some(Contents { data: io::read_all(fd) });
+        #[cfg(not(feature = "alloc"))]
+        let contents = {
+            let mut contents = Contents::empty();
+            loop {
+                let n = io::read(fd, &mut contents.buf[contents.len..]);
+                if n <= 0 {
+                    break;
+                }
+                contents.len += n as usize;
+            }
+            // A file that fills the buffer may not have been read whole
+            if contents.len < MAX_SIZE { Some(contents) } else { None }
+        };
+        io::close(fd);
+        contents
+    }
+
+    fn data(&self) -> &[u8] {
+        #[cfg(feature = "alloc")]
+        return &self.data;
+        #[cfg(not(feature = "alloc"))]
+        return &self.buf[..self.len];
+    }
+}
+
+/// A time zone: a TZif file's transitions, a TZ string's rule, or both
+pub struct TimeZone {
+    contents: Contents,
+    tzif: Option<Tzif>,
+    /// For times after the last transition, or all times without a file
+    rule: Option<Rule>,
+}
+
+impl TimeZone {
+    pub fn utc() -> TimeZone {
+        TimeZone { contents: Contents::empty(), tzif: None, rule: None }
+    }
+
+    /// The zone `$TZ` names, `/etc/localtime` if it is unset, or UTC if
+    /// neither can be read
+    pub fn local() -> TimeZone {
+        match io::getenv(b"TZ") {
+            Some(tz) => TimeZone::named(tz),
+            None => TimeZone::from_file(b"/etc/localtime"),
+        }
+        .unwrap_or_else(TimeZone::utc)
+    }
+
+    /// The zone a `$TZ` value names: empty for UTC, a file (always, when it
+    /// starts with `:`) relative to the zoneinfo directory unless absolute,
+    /// or a TZ string. Setuid programs don't get `$TZDIR`, absolute paths or
+    /// `..`.
+    pub fn named(tz: &[u8]) -> Option<TimeZone> {
+        if tz.is_empty() {
+            return Some(TimeZone::utc());
+        }
+        let (name, file_only) = match tz.strip_prefix(b":") {
+            Some(name) => (name, true),
+            None => (tz, false),
+        };
+
+        let safe = !sys::is_setuid() || (name.first() != Some(&b'/') && !name.split(|&c| c == b'/').any(|part| part == b".."));
+        if safe && !name.is_empty() {
+            let dir = sys::data_root(b"TZDIR");
+            let mut buf = [0u8; 512];
+            let mut len = 0;
+            let parts: [&[u8]; 3] = if name[0] == b'/' { [b"", b"", name] } else { [dir.unwrap_or(ZONEINFO), b"/", name] };
+            for part in parts {
+                if let Some(dest) = buf.get_mut(len..len + part.len()) {
+                    dest.copy_from_slice(part);
+                }
+                len += part.len();
+            }
+            if len <= buf.len() {
+                if let Some(zone) = TimeZone::from_file(&buf[..len]) {
+                    return Some(zone);
+                }
+            }
+        }
+
+        if file_only {
+            return None;
+        }
+        let rule = Rule::parse(tz)?;
+        Some(TimeZone { contents: Contents::empty(), tzif: None, rule: Some(rule) })
+    }
+
+    /// The zone in a TZif file
+    pub fn from_file(path: &[u8]) -> Option<TimeZone> {
+        let contents = Contents::read(path)?;
+        let data = contents.data();
+        let (version, v1, end) = Tzif::parse(data, 0, 4)?;
+        if version == 0 {
+            return Some(TimeZone { tzif: Some(v1), rule: None, contents });
+        }
+        // Version 2 and later repeat the data with 64-bit times, then give
+        // a TZ string between newlines
+        let (_, tzif, end) = Tzif::parse(data, end, 8)?;
+        let footer = data[end..].strip_prefix(b"\n").and_then(|f| f.split(|&c| c == b'\n').next());
+        let rule = footer.filter(|f| !f.is_empty()).and_then(Rule::parse);
+        Some(TimeZone { tzif: Some(tzif), rule, contents })
+    }
+
+    fn find(&self, t: i64) -> LocalType {
+        let data = self.contents.data();
+        match (&self.tzif, &self.rule) {
+            (Some(tzif), Some(rule)) if tzif.timecnt == 0 || t >= tzif.time(data, tzif.timecnt - 1) => rule.find(t),
+            (Some(tzif), _) => tzif.find(data, t),
+            (None, Some(rule)) => rule.find(t),
+            (None, None) => UTC,
+        }
+    }
+
+    /// `t` as local time
+    pub fn localtime(&self, t: i64) -> Tm {
+        let local = self.find(t);
+        Tm { isdst: local.isdst as i32, zone: local.abbr, ..breakdown(t, local.gmtoff) }
+    }
+
+    /// The seconds since the epoch of local time `tm`
+    ///
+    /// A time that happens twice as the clocks go back is taken as daylight
+    /// saving time if `tm.isdst` is positive, standard time if it is zero,
+    /// and the first of the two otherwise. A time skipped as the clocks go
+    /// forward is taken with the offset before the change, so it lands
+    /// after it.
+    pub fn mktime(&self, tm: &Tm) -> i64 {
+        let local = timegm(tm);
+        // The offsets in force either side of it: no zone changes twice in
+        // two days
+        let before = self.find(local - 86400).gmtoff;
+        let after = self.find(local + 86400).gmtoff;
+
+        let mut found: Option<(i64, bool)> = None;
+        for gmtoff in [before, after] {
+            let t = local - gmtoff as i64;
+            let at = self.find(t);
+            if at.gmtoff != gmtoff {
+                continue;
+            }
+            found = match found {
+                Some(prev) if tm.isdst >= 0 && prev.1 != at.isdst => {
+                    if at.isdst == (tm.isdst > 0) { Some((t, at.isdst)) } else { Some(prev) }
+                }
+                Some(prev) if prev.0 <= t => Some(prev),
+                _ => Some((t, at.isdst)),
+            };
+        }
+        found.map_or(local - before as i64, |(t, _)| t)
+    }
+}
+
+// ============================================================================
+// strftime
+// ============================================================================
+
+/// The flags and width between `%` and a conversion
+struct Spec {
+    /// `-` (0), `_` (space) or `0`
+    pad: Option<u8>,
+    width: Option<usize>,
+    /// `^`
+    upper: bool,
+    /// `#`
+    swap: bool,
+}
+
+impl Spec {
+    /// Write `n` at least `width` digits wide, padded with `pad`
+    fn num(&self, out: &mut dyn FnMut(&[u8]), n: i64, width: usize, pad: u8) {
+        let pad = self.pad.unwrap_or(pad);
+        let width = if pad == 0 { 1 } else { self.width.unwrap_or(width) };
+        let mut digits = [0u8; 20];
+        let mut i = digits.len();
+        let mut m = n.unsigned_abs();
+        loop {
+            i -= 1;
+            digits[i] = b'0' + (m % 10) as u8;
+            m /= 10;
+            if m == 0 {
+                break;
+            }
+        }
+        let len = digits.len() - i + (n < 0) as usize;
+        if n < 0 && pad == b'0' {
+            out(b"-");
+        }
+        for _ in len..width {
+            out(&[pad]);
+        }
+        if n < 0 && pad != b'0' {
+            out(b"-");
+        }
+        out(&digits[i..]);
+    }
+
+    /// Write `s`, with `^` upper-casing it and `#` swapping it to upper case,
+    /// or to lower case if `lower_on_swap`
+    fn text(&self, out: &mut dyn FnMut(&[u8]), s: &[u8], lower_on_swap: bool) {
+        let pad = match self.pad {
+            Some(0) => None,
+            pad => Some(pad.unwrap_or(b' ')),
+        };
+        if let (Some(pad), Some(width)) = (pad, self.width) {
+            for _ in s.len()..width {
+                out(&[pad]);
+            }
+        }
+        for &c in s {
+            let c = if self.swap && lower_on_swap {
+                c.to_ascii_lowercase()
+            } else if self.upper || self.swap {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            };
+            out(&[c]);
+        }
+    }
+}
+
+/// The ISO 8601 week-based year and week number
+fn iso_week(tm: &Tm) -> (i64, i32) {
+    // Weeks start on Monday, and week 1 is the one with the year's first
+    // Thursday
+    let weeks_in = |year: i64| {
+        let jan1 = (days_from_civil(year, 1, 1) + 4).rem_euclid(7);
+        if jan1 == 4 || (jan1 == 3 && is_leap(year)) { 53 } else { 52 }
+    };
+    let week = (tm.yday - (tm.wday + 6) % 7 + 10) / 7;
+    if week < 1 {
+        (tm.year - 1, weeks_in(tm.year - 1))
+    } else if week > weeks_in(tm.year) {
+        (tm.year + 1, 1)
+    } else {
+        (tm.year, week)
+    }
+}
+
+/// Format `tm` as C's `strftime` does in the C locale, with GNU's `%N`,
+/// `%P`, `%k`, `%l`, `%s`, `%:z` and the flags `-`, `_`, `0`, `^` and `#`
+/// followed by a width. Unknown conversions are copied through.
+pub fn strftime(fmt: &[u8], tm: &Tm, out: &mut dyn FnMut(&[u8])) {
+    let mut i = 0;
+    while i < fmt.len() {
+        let Some(len) = fmt[i..].iter().position(|&c| c == b'%') else {
+            out(&fmt[i..]);
+            return;
+        };
+        out(&fmt[i..i + len]);
+        let start = i + len;
+        i = start + 1;
+
+        let mut spec = Spec { pad: None, width: None, upper: false, swap: false };
+        while let Some(&c) = fmt.get(i) {
+            match c {
+                b'-' => spec.pad = Some(0),
+                b'_' => spec.pad = Some(b' '),
+                b'0' => spec.pad = Some(b'0'),
+                b'^' => spec.upper = true,
+                b'#' => spec.swap = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        while let Some(c) = fmt.get(i).filter(|c| c.is_ascii_digit()) {
+            spec.width = Some((spec.width.unwrap_or(0) * 10 + (c - b'0') as usize).min(1024));
+            i += 1;
+        }
+        let mut colons = 0;
+        while fmt.get(i) == Some(&b':') {
+            colons += 1;
+            i += 1;
+        }
+        // The E and O modifiers pick alternative forms the C locale lacks
+        while matches!(fmt.get(i), Some(b'E' | b'O')) {
+            i += 1;
+        }
+        let Some(&conv) = fmt.get(i) else {
+            out(&fmt[start..]);
+            return;
+        };
+        i += 1;
+        if (colons > 0 && conv != b'z') || !convert(conv, colons, &spec, tm, out) {
+            out(&fmt[start..i]);
+        }
+    }
+}
+
+/// Write one conversion, or return false if `conv` isn't one
+fn convert(conv: u8, colons: usize, spec: &Spec, tm: &Tm, out: &mut dyn FnMut(&[u8])) -> bool {
+    let weekday = WEEKDAYS.get(tm.wday as usize).copied().unwrap_or(b"?");
+    let month = MONTHS.get(tm.mon as usize - 1).copied().unwrap_or(b"?");
+    let hour12 = if tm.hour % 12 == 0 { 12 } else { tm.hour % 12 } as i64;
+    match conv {
+        b'a' => spec.text(out, &weekday[..3.min(weekday.len())], false),
+        b'A' => spec.text(out, weekday, false),
+        b'b' | b'h' => spec.text(out, &month[..3.min(month.len())], false),
+        b'B' => spec.text(out, month, false),
+        b'c' => strftime(b"%a %b %e %H:%M:%S %Y", tm, out),
+        b'C' => spec.num(out, tm.year.div_euclid(100), 2, b'0'),
+        b'd' => spec.num(out, tm.mday as i64, 2, b'0'),
+        b'D' | b'x' => strftime(b"%m/%d/%y", tm, out),
+        b'e' => spec.num(out, tm.mday as i64, 2, b' '),
+        b'F' => strftime(b"%Y-%m-%d", tm, out),
+        b'g' => spec.num(out, iso_week(tm).0.rem_euclid(100), 2, b'0'),
+        b'G' => spec.num(out, iso_week(tm).0, 1, b'0'),
+        b'H' => spec.num(out, tm.hour as i64, 2, b'0'),
+        b'I' => spec.num(out, hour12, 2, b'0'),
+        b'j' => spec.num(out, tm.yday as i64 + 1, 3, b'0'),
+        b'k' => spec.num(out, tm.hour as i64, 2, b' '),
+        b'l' => spec.num(out, hour12, 2, b' '),
+        b'm' => spec.num(out, tm.mon as i64, 2, b'0'),
+        b'M' => spec.num(out, tm.min as i64, 2, b'0'),
+        b'n' => out(b"\n"),
+        b'N' => {
+            // The width is the number of digits, truncated rather than
+            // rounded
+            let digits = spec.width.unwrap_or(9).clamp(1, 9);
+            let n = tm.nsec as i64 / 10i64.pow(9 - digits as u32);
+            Spec { pad: None, width: None, ..*spec }.num(out, n, digits, b'0');
+        }
+        b'p' => spec.text(out, if tm.hour < 12 { b"AM" } else { b"PM" }, true),
+        b'P' => spec.text(out, if tm.hour < 12 { b"am" } else { b"pm" }, false),
+        b'r' => strftime(b"%I:%M:%S %p", tm, out),
+        b'R' => strftime(b"%H:%M", tm, out),
+        b's' => spec.num(out, timegm(tm) - tm.gmtoff as i64, 1, b'0'),
+        b'S' => spec.num(out, tm.sec as i64, 2, b'0'),
+        b't' => out(b"\t"),
+        b'T' | b'X' => strftime(b"%H:%M:%S", tm, out),
+        b'u' => spec.num(out, if tm.wday == 0 { 7 } else { tm.wday as i64 }, 1, b'0'),
+        b'U' => spec.num(out, ((tm.yday + 7 - tm.wday) / 7) as i64, 2, b'0'),
+        b'V' => spec.num(out, iso_week(tm).1 as i64, 2, b'0'),
+        b'w' => spec.num(out, tm.wday as i64, 1, b'0'),
+        b'W' => spec.num(out, ((tm.yday + 7 - (tm.wday + 6) % 7) / 7) as i64, 2, b'0'),
+        b'y' => spec.num(out, tm.year.rem_euclid(100), 2, b'0'),
+        b'Y' => spec.num(out, tm.year, 1, b'0'),
+        b'z' => write_offset(out, tm.gmtoff, colons),
+        b'Z' => spec.text(out, tm.zone.as_bytes(), true),
+        b'+' => strftime(b"%a %b %e %H:%M:%S %Z %Y", tm, out),
+        b'%' => out(b"%"),
+        _ => return false,
+    }
+    true
+}
+
+/// Write `gmtoff` as `+hhmm`, or with `colons` as `+hh:mm`, `+hh:mm:ss` or
+/// (3) only as precise as needed
+fn write_offset(out: &mut dyn FnMut(&[u8]), gmtoff: i32, colons: usize) {
+    let abs = gmtoff.unsigned_abs();
+    let (h, m, s) = (abs / 3600, abs / 60 % 60, abs % 60);
+    let mut buf = [0u8; 9];
+    buf[0] = if gmtoff < 0 { b'-' } else { b'+' };
+    let mut len = 1;
+    let parts = match colons {
+        0 | 1 => 2,
+        2 => 3,
+        _ if s != 0 => 3,
+        _ if m != 0 => 2,
+        _ => 1,
+    };
+    for (i, n) in [h, m, s].into_iter().take(parts).enumerate() {
+        if i > 0 && colons > 0 {
+            buf[len] = b':';
+            len += 1;
+        }
+        buf[len] = b'0' + (n / 10) as u8;
+        buf[len + 1] = b'0' + (n % 10) as u8;
+        len += 2;
+    }
+    out(&buf[..len]);
+}
+
+// ============================================================================
+// strptime
+// ============================================================================
+
+/// What `strptime` found besides the fields of the `Tm`
+pub struct Parsed<'a> {
+    /// The text after the part that matched
+    pub rest: &'a [u8],
+    /// The offset from UTC the text gave with `%z`, `%s`, or `%Z` as `UTC`,
+    /// `GMT` or `Z`; other abbreviations are taken as local time
+    pub gmtoff: Option<i32>,
+}
+
+/// Fields that only make sense once the whole text is read
+#[derive(Default)]
+struct Pending {
+    hour12: Option<i32>,
+    pm: Option<bool>,
+    century: Option<i64>,
+    year2: Option<i64>,
+    yday: Option<i32>,
+    gmtoff: Option<i32>,
+}
+
+/// Read `s` with the `strptime` format `fmt` into the fields of `tm` it
+/// names, leaving the others alone
+///
+/// Whitespace in `fmt` matches any amount, names match either spelling in
+/// any case, and `%y` alone is 1969 to 2068. `%j` sets the date when
+/// `TimeZone::mktime` or `timegm` reads `tm`, not `yday`.
+pub fn strptime<'a>(s: &'a [u8], fmt: &[u8], tm: &mut Tm) -> Option<Parsed<'a>> {
+    let mut s = s;
+    let mut pending = Pending::default();
+    scan(&mut s, fmt, tm, &mut pending)?;
+
+    if let Some(hour) = pending.hour12 {
+        tm.hour = hour % 12;
+    }
+    if pending.pm == Some(true) && tm.hour < 12 {
+        tm.hour += 12;
+    }
+    match (pending.century, pending.year2) {
+        (century, Some(y)) => tm.year = century.unwrap_or(if y < 69 { 20 } else { 19 }) * 100 + y,
+        (Some(century), None) => tm.year = century * 100 + tm.year.rem_euclid(100),
+        (None, None) => {}
+    }
+    if let Some(yday) = pending.yday {
+        tm.mon = 1;
+        tm.mday = yday + 1;
+    }
+    Some(Parsed { rest: s, gmtoff: pending.gmtoff })
+}
+
+fn scan(s: &mut &[u8], fmt: &[u8], tm: &mut Tm, pending: &mut Pending) -> Option<()> {
+    let skip_space = |s: &mut &[u8]| {
+        let n = s.iter().take_while(|c| c.is_ascii_whitespace()).count();
+        *s = &s[n..];
+    };
+    let mut i = 0;
+    while i < fmt.len() {
+        let c = fmt[i];
+        i += 1;
+        if c.is_ascii_whitespace() {
+            skip_space(s);
+            continue;
+        }
+        if c != b'%' {
+            *s = s.strip_prefix(&[c])?;
+            continue;
+        }
+        // Flags and modifiers change nothing when reading
+        while matches!(fmt.get(i), Some(b'-' | b'_' | b'0' | b'^' | b'#' | b'E' | b'O')) {
+            i += 1;
+        }
+        let conv = *fmt.get(i)?;
+        i += 1;
+
+        let number = |s: &mut &[u8], min: i64, max: i64, digits: usize| {
+            skip_space(s);
+            take_number(s, digits).filter(|n| (min..=max).contains(n))
+        };
+        match conv {
+            b'a' | b'A' => tm.wday = take_name(s, &WEEKDAYS)? as i32,
+            b'b' | b'B' | b'h' => tm.mon = take_name(s, &MONTHS)? as i32 + 1,
+            b'c' => scan(s, b"%a %b %e %H:%M:%S %Y", tm, pending)?,
+            b'C' => pending.century = Some(number(s, 0, 99, 2)?),
+            b'd' | b'e' => tm.mday = number(s, 1, 31, 2)? as i32,
+            b'D' | b'x' => scan(s, b"%m/%d/%y", tm, pending)?,
+            b'F' => scan(s, b"%Y-%m-%d", tm, pending)?,
+            b'H' | b'k' => {
+                tm.hour = number(s, 0, 23, 2)? as i32;
+                pending.hour12 = None;
+            }
+            b'I' | b'l' => pending.hour12 = Some(number(s, 1, 12, 2)? as i32),
+            b'j' => pending.yday = Some(number(s, 1, 366, 3)? as i32 - 1),
+            b'm' => tm.mon = number(s, 1, 12, 2)? as i32,
+            b'M' => tm.min = number(s, 0, 59, 2)? as i32,
+            b'n' | b't' => skip_space(s),
+            b'p' | b'P' => pending.pm = Some(take_name(s, &[b"AM", b"PM"])? == 1),
+            b'r' => scan(s, b"%I:%M:%S %p", tm, pending)?,
+            b'R' => scan(s, b"%H:%M", tm, pending)?,
+            b's' => {
+                skip_space(s);
+                let negative = s.first() == Some(&b'-');
+                if negative {
+                    *s = &s[1..];
+                }
+                let t = take_number(s, 15)?;
+                *tm = gmtime(if negative { -t } else { t });
+                pending.gmtoff = Some(0);
+            }
+            b'S' => tm.sec = number(s, 0, 60, 2)? as i32,
+            b'T' | b'X' => scan(s, b"%H:%M:%S", tm, pending)?,
+            b'u' => tm.wday = number(s, 1, 7, 1)? as i32 % 7,
+            b'w' => tm.wday = number(s, 0, 6, 1)? as i32,
+            // Week numbers are read but give no date on their own
+            b'U' | b'V' | b'W' => {
+                number(s, 0, 53, 2)?;
+            }
+            b'g' => {
+                number(s, 0, 99, 2)?;
+            }
+            b'y' => pending.year2 = Some(number(s, 0, 99, 2)?),
+            b'Y' | b'G' => {
+                tm.year = number(s, 0, 9999, 4)?;
+                pending.year2 = None;
+                pending.century = None;
+            }
+            b'z' => {
+                skip_space(s);
+                pending.gmtoff = Some(take_gmtoff(s)?);
+            }
+            b'Z' => {
+                skip_space(s);
+                let len = s.iter().take_while(|c| c.is_ascii_alphabetic()).count();
+                if len == 0 {
+                    return None;
+                }
+                let name = &s[..len];
+                if [b"UTC".as_slice(), b"GMT", b"UT", b"Z"].iter().any(|utc| name.eq_ignore_ascii_case(utc)) {
+                    pending.gmtoff = Some(0);
+                }
+                *s = &s[len..];
+            }
+            b'%' => *s = s.strip_prefix(b"%")?,
+            _ => return None,
+        }
+    }
+    Some(())
+}
+
+/// Take the index of the name in `names` that `s` starts with, in full or
+/// as its first three letters, in any case
+fn take_name(s: &mut &[u8], names: &[&[u8]]) -> Option<usize> {
+    for len in [usize::MAX, 3] {
+        for (i, name) in names.iter().enumerate() {
+            let name = &name[..len.min(name.len())];
+            if s.len() >= name.len() && s[..name.len()].eq_ignore_ascii_case(name) {
+                *s = &s[name.len()..];
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Take `Z` or `+hh[[:]mm]`, in seconds east of UTC
+fn take_gmtoff(s: &mut &[u8]) -> Option<i32> {
+    if let Some(rest) = s.strip_prefix(b"Z") {
+        *s = rest;
+        return Some(0);
+    }
+    let negative = match s.first()? {
+        b'+' => false,
+        b'-' => true,
+        _ => return None,
+    };
+    let mut rest = &s[1..];
+    if rest.iter().take(2).filter(|c| c.is_ascii_digit()).count() != 2 {
+        return None;
+    }
+    let h = take_number(&mut rest, 2)?;
+    let mut m = 0;
+    let after_colon = rest.strip_prefix(b":").unwrap_or(rest);
+    if after_colon.iter().take(2).filter(|c| c.is_ascii_digit()).count() == 2 {
+        rest = after_colon;
+        m = take_number(&mut rest, 2)?;
+    }
+    if h > 24 || m > 59 {
+        return None;
+    }
+    *s = rest;
+    let secs = (h * 3600 + m * 60) as i32;
+    Some(if negative { -secs } else { secs })
+}
+
+// ============================================================================
+// Dates as arguments
+// ============================================================================
+
+/// The forms `parse_date` accepts after `@SECONDS`, tried in order. Fields
+/// a form lacks come from today at midnight.
+const DATE_FORMATS: &[&[u8]] = &[
+    b"%Y-%m-%d %H:%M:%S",
+    b"%Y-%m-%dT%H:%M:%S",
+    b"%Y-%m-%d %H:%M",
+    b"%Y-%m-%dT%H:%M",
+    b"%Y-%m-%d",
+    b"%Y.%m.%d-%H:%M:%S",
+    b"%Y.%m.%d-%H:%M",
+    b"%m.%d-%H:%M:%S",
+    b"%m.%d-%H:%M",
+    b"%H:%M:%S",
+    b"%H:%M",
+    // What date prints by default and with -R
+    b"%a %b %e %H:%M:%S %Z %Y",
+    b"%a, %d %b %Y %H:%M:%S",
+];
+
+/// Read a date given as an argument: `now`, `@SECONDS[.FRACTION]`, or one
+/// of `YYYY-MM-DD[{T| }hh:mm[:ss]]`, `[YYYY.]MM.DD-hh:mm[:ss]`,
+/// `hh:mm[:ss]`, or what `date` prints by default or with `-R`. Seconds may
+/// have a fraction and the date may end with a zone: `Z`, `UTC`, `GMT` or
+/// `+hh[:mm]`. Otherwise it is a local time in `tz`. Returns seconds since
+/// the epoch and nanoseconds.
+pub fn parse_date(s: &[u8], now: (i64, u32), tz: &TimeZone) -> Option<(i64, u32)> {
+    let s = s.trim_ascii();
+    if s.eq_ignore_ascii_case(b"now") {
+        return Some(now);
+    }
+    if let Some(rest) = s.strip_prefix(b"@") {
+        let mut rest = rest;
+        let negative = rest.first() == Some(&b'-');
+        if negative || rest.first() == Some(&b'+') {
+            rest = &rest[1..];
+        }
+        let secs = take_number(&mut rest, 18)?;
+        let nsec = take_fraction(&mut rest);
+        if !rest.is_empty() {
+            return None;
+        }
+        // -1.5 is a second and a half before the epoch
+        return Some(match (negative, nsec) {
+            (false, _) => (secs, nsec),
+            (true, 0) => (-secs, 0),
+            (true, _) => (-secs - 1, 1_000_000_000 - nsec),
+        });
+    }
+    DATE_FORMATS.iter().find_map(|fmt| parse_format(s, fmt, now, tz))
+}
+
+/// Read `s` as `strptime` format `fmt`, all of it, taking the fields it
+/// lacks from today at midnight. Seconds may have a fraction and the date
+/// may end with a zone, as for `parse_date`.
+pub fn parse_format(s: &[u8], fmt: &[u8], now: (i64, u32), tz: &TimeZone) -> Option<(i64, u32)> {
+    let mut tm = Tm { hour: 0, min: 0, sec: 0, nsec: 0, isdst: -1, ..tz.localtime(now.0) };
+    let parsed = strptime(s, fmt, &mut tm)?;
+
+    let mut rest = parsed.rest;
+    let nsec = take_fraction(&mut rest);
+    let rest = rest.trim_ascii_start();
+    let gmtoff = if rest.is_empty() {
+        parsed.gmtoff
+    } else if [b"UTC".as_slice(), b"GMT"].iter().any(|utc| rest.eq_ignore_ascii_case(utc)) {
+        Some(0)
+    } else {
+        let mut rest = rest;
+        let gmtoff = take_gmtoff(&mut rest)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        Some(gmtoff)
+    };
+
+    let t = match gmtoff {
+        Some(gmtoff) => timegm(&tm) - gmtoff as i64,
+        None => tz.mktime(&tm),
+    };
+    Some((t, nsec))
+}
+
+/// Read the all-digit dates of `touch -t`, `[[CC]YY]MMDDhhmm[.ss]`, or with
+/// `year_last` that of `date`, `MMDDhhmm[[CC]YY][.ss]`
+pub fn parse_digits(s: &[u8], year_last: bool, now: (i64, u32), tz: &TimeZone) -> Option<(i64, u32)> {
+    let (digits, secs) = match s.iter().position(|&c| c == b'.') {
+        Some(dot) => (&s[..dot], Some(&s[dot + 1..])),
+        None => (s, None),
+    };
+    if !digits.iter().all(u8::is_ascii_digit) || secs.is_some_and(|secs| secs.len() != 2 || !secs.iter().all(u8::is_ascii_digit)) {
+        return None;
+    }
+    let fmt: &[u8] = match (digits.len(), year_last, secs.is_some()) {
+        (8, _, false) => b"%m%d%H%M",
+        (8, _, true) => b"%m%d%H%M.%S",
+        (10, false, false) => b"%y%m%d%H%M",
+        (10, false, true) => b"%y%m%d%H%M.%S",
+        (12, false, false) => b"%Y%m%d%H%M",
+        (12, false, true) => b"%Y%m%d%H%M.%S",
+        (10, true, false) => b"%m%d%H%M%y",
+        (10, true, true) => b"%m%d%H%M%y.%S",
+        (12, true, false) => b"%m%d%H%M%Y",
+        (12, true, true) => b"%m%d%H%M%Y.%S",
+        _ => return None,
+    };
+    parse_format(s, fmt, now, tz)
+}
+
+/// Take `.DIGITS` or `,DIGITS` as nanoseconds, or 0 if there is none
+fn take_fraction(s: &mut &[u8]) -> u32 {
+    let Some(digits) = s.strip_prefix(b".").or_else(|| s.strip_prefix(b",")) else {
+        return 0;
+    };
+    let len = digits.iter().take_while(|c| c.is_ascii_digit()).count();
+    if len == 0 {
+        return 0;
+    }
+    let mut nsec = 0;
+    for i in 0..9 {
+        nsec = nsec * 10 + digits.get(i).filter(|_| i < len).map_or(0, |&c| (c - b'0') as u32);
+    }
+    *s = &digits[len..];
+    nsec
+}
@@ -0,0 +1,163 @@
+//! Running other commands
+//!
+//! Applets that run a command (`xargs`, `find -exec`, `env`, `nice`,
+//! `timeout`, ...) start it through `spawn`, `start` or `exec` here. Each
+//! takes the command as a NULL-terminated `argv` array whose first element
+//! is the command name.
+//!
+//! With the `prefer-applets` feature, a command named like an armybox
+//! applet runs as that applet instead of whatever `PATH` finds, so these
+//! applets work on systems with no applet links installed:
+//!
+//! - NOFORK applets (listed in `NOFORK`) are run in-process by `spawn`,
+//!   without a fork at all
+//! - any other applet is NOEXEC: `/proc/self/exe` is executed with the
+//!   applet name as `argv[0]`, or, if that fails (no `/proc`) or the
+//!   program isn't armybox but embeds it, the applet runs in the process
+//!   that would have executed it
+//!
+//! `ARMYBOX_PREFER_APPLETS=0` in the environment turns this off at run
+//! time, so `PATH` is searched as usual.
+
+use core::sync::atomic::AtomicBool;
+#[cfg(feature = "prefer-applets")]
+use core::sync::atomic::Ordering;
+use crate::io;
+use crate::sys;
+use super::{get_arg, EXIT_CANNOT_EXECUTE, EXIT_NOT_FOUND};
+#[cfg(feature = "prefer-applets")]
+use super::{find_applet, Applet, Status};
+
+/// Set by the armybox binary, whose `/proc/self/exe` can run any applet.
+/// Other programs linking the library leave it unset.
+pub static IS_ARMYBOX: AtomicBool = AtomicBool::new(false);
+
+/// Applets safe to run in-process in place of a child: they return rather
+/// than exit, don't read stdin, change no process-wide state and free
+/// everything they allocate
+#[cfg(feature = "prefer-applets")]
+const NOFORK: &[&[u8]] = &[
+    b":", b"[", b"basename", b"chgrp", b"chmod", b"chown", b"dirname", b"echo",
+    b"false", b"kill", b"link", b"ln", b"mkdir", b"mkfifo", b"pwd", b"readlink",
+    b"realpath", b"rm", b"rmdir", b"sleep", b"sync", b"test", b"touch", b"true",
+    b"truncate", b"unlink", b"usleep",
+];
+
+/// The applet to run for command `name`, when applets are preferred
+#[cfg(feature = "prefer-applets")]
+fn preferred(name: &[u8]) -> Option<&'static Applet> {
+    if name.contains(&b'/') || io::getenv(b"ARMYBOX_PREFER_APPLETS") == Some(b"0".as_slice()) {
+        return None;
+    }
+    find_applet(name).filter(|applet| applet.status != Status::Stub)
+}
+
+/// Number of entries before the NULL ending `argv`
+#[cfg(feature = "prefer-applets")]
+unsafe fn count(argv: *const *const u8) -> i32 {
+    let mut argc = 0;
+    while unsafe { !(*argv.add(argc as usize)).is_null() } {
+        argc += 1;
+    }
+    argc
+}
+
+/// Run the command in `argv` and wait for it.
+///
+/// Returns its exit status as the shell reports it, or, when it couldn't be
+/// run, reports why as `applet` and returns `EXIT_NOT_FOUND` or
+/// `EXIT_CANNOT_EXECUTE`.
+///
+/// # Safety
+///
+/// `argv` must be a NULL-terminated array of NUL-terminated strings with at
+/// least one entry, valid until this returns.
+pub unsafe fn spawn(applet: &[u8], argv: *const *const u8) -> i32 {
+    #[cfg(feature = "prefer-applets")]
+    {
+        let name = unsafe { get_arg(argv, 0) }.unwrap_or(b"");
+        if let Some(found) = preferred(name).filter(|found| NOFORK.contains(&found.name)) {
+            return unsafe { crate::run_applet(found.name, count(argv), argv) };
+        }
+    }
+
+    let pid = unsafe { start(applet, argv) };
+    if pid < 0 {
+        return 1;
+    }
+    wait(applet, pid)
+}
+
+/// Start the command in `argv` in a child process and return its pid, or -1
+/// after reporting a failed fork as `applet`.
+///
+/// # Safety
+///
+/// As for `spawn`.
+pub unsafe fn start(applet: &[u8], argv: *const *const u8) -> i32 {
+    let pid = io::fork();
+    if pid == 0 {
+        let status = unsafe { exec(applet, argv) };
+        unsafe { libc::_exit(status) };
+    }
+    if pid < 0 {
+        sys::perror(applet, b"fork");
+    }
+    pid
+}
+
+/// Wait for child `pid` and return its exit status as the shell reports it
+pub fn wait(applet: &[u8], pid: i32) -> i32 {
+    let mut status = 0;
+    while io::waitpid(pid, &mut status, 0) < 0 {
+        if sys::errno() != libc::EINTR {
+            sys::perror(applet, b"wait");
+            return 1;
+        }
+    }
+    wait_status(status)
+}
+
+/// Replace this process with the command in `argv`.
+///
+/// Only returns if the command couldn't be run, after reporting why as
+/// `applet`, with `EXIT_NOT_FOUND` or `EXIT_CANNOT_EXECUTE`.
+///
+/// # Safety
+///
+/// As for `spawn`.
+pub unsafe fn exec(applet: &[u8], argv: *const *const u8) -> i32 {
+    let name = unsafe { get_arg(argv, 0) }.unwrap_or(b"");
+
+    #[cfg(feature = "prefer-applets")]
+    if let Some(found) = preferred(name) {
+        if IS_ARMYBOX.load(Ordering::Relaxed) {
+            unsafe extern "C" { static environ: *const *const i8; }
+            io::execve(b"/proc/self/exe", argv as *const *const i8, unsafe { environ });
+        }
+        let status = unsafe { crate::run_applet(found.name, count(argv), argv) };
+        io::exit(status);
+    }
+
+    unsafe { io::execvp(argv.read() as *const i8, argv as *const *const i8) };
+    exec_failed(applet, name)
+}
+
+/// Report that `execvp` failed to run `command` and return the status for
+/// it: `EXIT_NOT_FOUND` if it doesn't exist, `EXIT_CANNOT_EXECUTE` if it
+/// does but couldn't be run
+pub fn exec_failed(applet: &[u8], command: &[u8]) -> i32 {
+    let errno = sys::errno();
+    sys::error_errno(applet, command, errno);
+    if errno == libc::ENOENT { EXIT_NOT_FOUND } else { EXIT_CANNOT_EXECUTE }
+}
+
+/// Turn a `waitpid` status into an exit status as the shell reports it,
+/// with 128 plus the signal number for a command killed by a signal
+pub fn wait_status(status: i32) -> i32 {
+    if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        libc::WEXITSTATUS(status)
+    }
+}
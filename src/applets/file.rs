@@ -2,11 +2,23 @@
 
 use crate::io;
 use crate::sys;
-use super::{get_arg, has_opt, is_opt};
+use super::accounts;
+use super::datetime;
+use super::fnmatch::{self, fnmatch};
+use super::json::Json;
+use super::regex::{self, Regex};
+use super::{exec, get_arg, usage, utf8, Arg, Getopt};
+use super::{EXIT_CANNOT_EXECUTE, EXIT_NOT_FOUND, EXIT_TROUBLE};
 
 /// cat - concatenate files
 pub fn cat(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 {
+    // -u (unbuffered) is accepted for POSIX; output is never buffered
+    let mut opts = Getopt::new(argc, &argv, b"u");
+    if opts.any(|arg| arg == Arg::Error) {
+        return 1;
+    }
+
+    if opts.operand_count() == 0 {
         // Read from stdin
         let mut buf = [0u8; 4096];
         loop {
@@ -17,12 +29,8 @@ pub fn cat(argc: i32, argv: *const *const u8) -> i32 {
         return 0;
     }
 
-    for i in 1..argc {
-        let path = match unsafe { get_arg(argv, i) } {
-            Some(p) => p,
-            None => continue,
-        };
-
+    let mut status = 0;
+    for path in opts.operands() {
         if path == b"-" {
             let mut buf = [0u8; 4096];
             loop {
@@ -35,7 +43,8 @@ pub fn cat(argc: i32, argv: *const *const u8) -> i32 {
 
         let fd = io::open(path, libc::O_RDONLY, 0);
         if fd < 0 {
-            sys::perror(path);
+            sys::perror(b"cat", path);
+            status = 1;
             continue;
         }
 
@@ -47,7 +56,7 @@ pub fn cat(argc: i32, argv: *const *const u8) -> i32 {
         }
         io::close(fd);
     }
-    0
+    status
 }
 
 /// cp - copy files
@@ -56,49 +65,49 @@ pub fn cp(argc: i32, argv: *const *const u8) -> i32 {
     let mut force = false;
     let mut interactive = false;
     let mut preserve = false;
-    let mut files_start = 1;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'r') || has_opt(arg, b'R') { recursive = true; }
-                if has_opt(arg, b'f') { force = true; }
-                if has_opt(arg, b'i') { interactive = true; }
-                if has_opt(arg, b'p') { preserve = true; }
-                files_start = i + 1;
-            } else {
-                break;
-            }
+    let spec = b"r(recursive)Rf(force)i(interactive)p(preserve)";
+    let mut opts = Getopt::new(argc, &argv, spec);
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"r", _) | Arg::Opt(b"R", _) => recursive = true,
+            Arg::Opt(b"f", _) => force = true,
+            Arg::Opt(b"i", _) => interactive = true,
+            Arg::Opt(b"p", _) => preserve = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
-    if argc - files_start < 2 {
-        io::write_str(2, b"cp: missing operand\n");
-        return 1;
+    let count = opts.operand_count();
+    if count < 2 {
+        return usage::error(b"cp", b"missing operand");
     }
 
-    let dest = unsafe { get_arg(argv, argc - 1).unwrap() };
+    let dest = opts.operand(count - 1).unwrap();
 
-    for i in files_start..(argc - 1) {
-        if let Some(src) = unsafe { get_arg(argv, i) } {
-            copy_file(src, dest, recursive, force, interactive, preserve);
+    let mut status = 0;
+    for src in opts.operands().take(count - 1) {
+        if !copy_file(b"cp", src, dest, recursive, force, interactive, preserve) {
+            status = 1;
         }
     }
-    0
+    status
 }
 
-fn copy_file(src: &[u8], dest: &[u8], recursive: bool, _force: bool, _interactive: bool, _preserve: bool) {
+/// Copy `src` to `dest`, reporting failures as `applet`
+fn copy_file(applet: &[u8], src: &[u8], dest: &[u8], recursive: bool, _force: bool, _interactive: bool, _preserve: bool) -> bool {
     let src_fd = io::open(src, libc::O_RDONLY, 0);
     if src_fd < 0 {
-        sys::perror(src);
-        return;
+        sys::perror(applet, src);
+        return false;
     }
 
     let dest_fd = io::open(dest, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, 0o644);
     if dest_fd < 0 {
         io::close(src_fd);
-        sys::perror(dest);
-        return;
+        sys::perror(applet, dest);
+        return false;
     }
 
     let mut buf = [0u8; 4096];
@@ -111,26 +120,39 @@ fn copy_file(src: &[u8], dest: &[u8], recursive: bool, _force: bool, _interactiv
     io::close(src_fd);
     io::close(dest_fd);
     let _ = recursive; // TODO: implement recursive copy
+    true
 }
 
 /// mv - move/rename files
 pub fn mv(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 3 {
-        io::write_str(2, b"mv: missing operand\n");
+    // -f is accepted for compatibility; mv never prompts
+    let mut opts = Getopt::new(argc, &argv, b"f(force)");
+    if opts.any(|arg| arg == Arg::Error) {
         return 1;
     }
 
-    let src = unsafe { get_arg(argv, 1).unwrap() };
-    let dest = unsafe { get_arg(argv, 2).unwrap() };
+    let (src, dest) = match (opts.operand(0), opts.operand(1)) {
+        (Some(src), Some(dest)) => (src, dest),
+        _ => return usage::error(b"mv", b"missing operand"),
+    };
 
     // Try rename first
     if io::rename(src, dest) == 0 {
         return 0;
     }
+    if sys::errno() != libc::EXDEV {
+        sys::perror(b"mv", src);
+        return 1;
+    }
 
-    // Fall back to copy + remove
-    copy_file(src, dest, false, true, false, false);
-    io::unlink(src);
+    // Across filesystems: copy, then remove
+    if !copy_file(b"mv", src, dest, false, true, false, false) {
+        return 1;
+    }
+    if io::unlink(src) < 0 {
+        sys::perror(b"mv", src);
+        return 1;
+    }
     0
 }
 
@@ -139,21 +161,26 @@ pub fn rm(argc: i32, argv: *const *const u8) -> i32 {
     let mut recursive = false;
     let mut force = false;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'r') || has_opt(arg, b'R') { recursive = true; }
-                if has_opt(arg, b'f') { force = true; }
-            } else {
-                if recursive {
-                    remove_recursive(arg);
-                } else if io::unlink(arg) < 0 && !force {
-                    sys::perror(arg);
-                }
-            }
+    let mut opts = Getopt::new(argc, &argv, b"r(recursive)Rf(force)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"r", _) | Arg::Opt(b"R", _) => recursive = true,
+            Arg::Opt(b"f", _) => force = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
-    0
+
+    let mut status = 0;
+    for path in opts.operands() {
+        if recursive {
+            remove_recursive(path);
+        } else if io::unlink(path) < 0 && !(force && sys::errno() == libc::ENOENT) {
+            sys::perror(b"rm", path);
+            status = 1;
+        }
+    }
+    status
 }
 
 fn remove_recursive(path: &[u8]) {
@@ -201,21 +228,22 @@ pub fn mkdir(argc: i32, argv: *const *const u8) -> i32 {
     let mut parents = false;
     let mut mode = 0o755u32;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'p') { parents = true; }
-                if has_opt(arg, b'm') && i + 1 < argc {
-                    if let Some(m) = unsafe { get_arg(argv, i + 1) } {
-                        mode = sys::parse_octal(m).unwrap_or(0o755);
-                    }
-                }
-            } else if parents {
-                mkdir_parents(arg, mode);
-            } else if io::mkdir(arg, mode) < 0 {
-                sys::perror(arg);
-                return 1;
-            }
+    let mut opts = Getopt::new(argc, &argv, b"p(parents)m(mode):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"p", _) => parents = true,
+            Arg::Opt(b"m", Some(m)) => mode = sys::parse_octal(m).unwrap_or(0o755),
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    for path in opts.operands() {
+        if parents {
+            mkdir_parents(path, mode);
+        } else if io::mkdir(path, mode) < 0 {
+            sys::perror(b"mkdir", path);
+            return 1;
         }
     }
     0
@@ -237,34 +265,99 @@ fn mkdir_parents(path: &[u8], mode: u32) {
 
 /// rmdir - remove empty directories
 pub fn rmdir(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' && io::rmdir(path) < 0 {
-                sys::perror(path);
-            }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    let mut status = 0;
+    for path in opts.operands() {
+        if io::rmdir(path) < 0 {
+            sys::perror(b"rmdir", path);
+            status = 1;
         }
     }
-    0
+    status
 }
 
 /// touch - change file timestamps
 pub fn touch(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                // Try to create file if doesn't exist
-                let fd = io::open(path, libc::O_WRONLY | libc::O_CREAT, 0o644);
-                if fd >= 0 {
-                    io::close(fd);
-                    // Update timestamps
-                    unsafe { libc::utimes(path.as_ptr() as *const i8, core::ptr::null()) };
+    let mut no_create = false;
+    let mut access = false;
+    let mut modify = false;
+    // Access and modification times: now, unless -d, -t or -r say otherwise
+    let mut times = [libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_NOW }; 2];
+
+    let mut opts = Getopt::new(argc, &argv, b"ac(no-create)d(date):mr(reference):t:");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"a", _) => access = true,
+            Arg::Opt(b"c", _) => no_create = true,
+            Arg::Opt(b"m", _) => modify = true,
+            Arg::Opt(key @ (b"d" | b"t"), Some(date)) => {
+                let tz = datetime::TimeZone::local();
+                let parsed = if key == b"d" {
+                    datetime::parse_date(date, datetime::now(), &tz)
                 } else {
-                    sys::perror(path);
+                    datetime::parse_digits(date, false, datetime::now(), &tz)
+                };
+                let Some((sec, nsec)) = parsed else {
+                    sys::error_at(b"touch", date, b"invalid date format");
+                    return 1;
+                };
+                times = [libc::timespec { tv_sec: sec, tv_nsec: nsec as _ }; 2];
+            }
+            Arg::Opt(b"r", Some(file)) => {
+                let mut st: libc::stat = unsafe { core::mem::zeroed() };
+                if io::stat(file, &mut st) < 0 {
+                    sys::perror(b"touch", file);
+                    return 1;
                 }
+                times = [
+                    libc::timespec { tv_sec: st.st_atime, tv_nsec: st.st_atime_nsec },
+                    libc::timespec { tv_sec: st.st_mtime, tv_nsec: st.st_mtime_nsec },
+                ];
             }
+            Arg::Error => return 1,
+            _ => {}
         }
     }
-    0
+    if opts.operand_count() == 0 {
+        return usage::error(b"touch", b"missing file operand");
+    }
+    // -a or -m alone leave the other time alone
+    if access && !modify {
+        times[1].tv_nsec = libc::UTIME_OMIT;
+    }
+    if modify && !access {
+        times[0].tv_nsec = libc::UTIME_OMIT;
+    }
+
+    // Arguments are NUL-terminated, as utimensat needs
+    let set_times = |path: &[u8]| unsafe {
+        libc::utimensat(libc::AT_FDCWD, path.as_ptr() as *const i8, times.as_ptr(), 0) == 0
+    };
+    let mut status = 0;
+    for path in opts.operands() {
+        if set_times(path) {
+            continue;
+        }
+        if sys::errno() == libc::ENOENT {
+            if no_create {
+                continue;
+            }
+            let fd = io::open(path, libc::O_WRONLY | libc::O_CREAT | libc::O_NOCTTY, 0o644);
+            if fd >= 0 {
+                io::close(fd);
+                if set_times(path) {
+                    continue;
+                }
+            }
+        }
+        sys::perror(b"touch", path);
+        status = 1;
+    }
+    status
 }
 
 /// ln - create links
@@ -272,22 +365,23 @@ pub fn ln(argc: i32, argv: *const *const u8) -> i32 {
     let mut symbolic = false;
     let mut force = false;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b's') { symbolic = true; }
-                if has_opt(arg, b'f') { force = true; }
-            }
+    let mut opts = Getopt::new(argc, &argv, b"s(symbolic)f(force)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"s", _) => symbolic = true,
+            Arg::Opt(b"f", _) => force = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
-    if argc < 3 {
-        io::write_str(2, b"ln: missing operand\n");
-        return 1;
+    let count = opts.operand_count();
+    if count < 2 {
+        return usage::error(b"ln", b"missing operand");
     }
 
-    let target = unsafe { get_arg(argv, argc - 2).unwrap() };
-    let link_name = unsafe { get_arg(argv, argc - 1).unwrap() };
+    let target = opts.operand(count - 2).unwrap();
+    let link_name = opts.operand(count - 1).unwrap();
 
     if force {
         io::unlink(link_name);
@@ -300,7 +394,7 @@ pub fn ln(argc: i32, argv: *const *const u8) -> i32 {
     };
 
     if ret < 0 {
-        sys::perror(link_name);
+        sys::perror(b"ln", link_name);
         return 1;
     }
     0
@@ -311,57 +405,66 @@ pub fn ls(argc: i32, argv: *const *const u8) -> i32 {
     let mut show_all = false;
     let mut long_format = false;
     let mut one_per_line = false;
+    let mut columns = false;
     let mut recursive = false;
     let mut show_inode = false;
     let mut classify = false;
 
-    let mut paths_start = argc;
-
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' && arg.len() > 1 {
-                for &c in &arg[1..] {
-                    match c {
-                        b'a' => show_all = true,
-                        b'l' => long_format = true,
-                        b'1' => one_per_line = true,
-                        b'R' => recursive = true,
-                        b'i' => show_inode = true,
-                        b'F' => classify = true,
-                        _ => {}
-                    }
-                }
-            } else {
-                paths_start = i;
-                break;
-            }
+    let mut opts = Getopt::new(argc, &argv, b"a(all)l1CR(recursive)i(inode)F(classify)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"a", _) => show_all = true,
+            Arg::Opt(b"l", _) => long_format = true,
+            Arg::Opt(b"1", _) => one_per_line = true,
+            Arg::Opt(b"C", _) => columns = true,
+            Arg::Opt(b"R", _) => recursive = true,
+            Arg::Opt(b"i", _) => show_inode = true,
+            Arg::Opt(b"F", _) => classify = true,
+            Arg::Error => return EXIT_TROUBLE,
+            _ => {}
         }
     }
 
-    if paths_start >= argc {
-        list_dir(b".", show_all, long_format, one_per_line, show_inode, classify);
-    } else {
-        for i in paths_start..argc {
-            if let Some(path) = unsafe { get_arg(argv, i) } {
-                if recursive {
-                    io::write_all(1, path);
-                    io::write_str(1, b":\n");
-                }
-                list_dir(path, show_all, long_format, one_per_line, show_inode, classify);
-            }
+    // Columns are for people: a pipe gets one name per line unless -C
+    let one_per_line = one_per_line || long_format || !(columns || io::isatty(1));
+
+    let mut status = 0;
+    if opts.operand_count() == 0 && !list_dir(b".", show_all, long_format, one_per_line, show_inode, classify) {
+        status = EXIT_TROUBLE;
+    }
+    for path in opts.operands() {
+        if recursive {
+            io::write_all(1, path);
+            io::write_str(1, b":\n");
+        }
+        if !list_dir(path, show_all, long_format, one_per_line, show_inode, classify) {
+            status = EXIT_TROUBLE;
         }
     }
     let _ = recursive;
-    0
+    status
 }
 
-fn list_dir(path: &[u8], show_all: bool, long_format: bool, one_per_line: bool, _show_inode: bool, _classify: bool) {
+fn list_dir(path: &[u8], show_all: bool, long_format: bool, one_per_line: bool, _show_inode: bool, _classify: bool) -> bool {
     let fd = io::open(path, libc::O_RDONLY | libc::O_DIRECTORY, 0);
     if fd < 0 {
-        sys::perror(path);
-        return;
+        sys::perror(b"ls", path);
+        return false;
     }
 
+    // Owners are shown by name, and times in the local zone
+    let (users, groups) = if long_format {
+        (accounts::Users::open(), accounts::Groups::open())
+    } else {
+        (None, None)
+    };
+    let tz = if long_format { datetime::TimeZone::local() } else { datetime::TimeZone::utc() };
+    let now = datetime::now().0;
+
+    // Names to lay out in columns once they are all known
+    #[cfg(feature = "alloc")]
+    let mut names: alloc::vec::Vec<alloc::vec::Vec<u8>> = alloc::vec::Vec::new();
+
     let mut buf = [0u8; 4096];
     loop {
         let n = unsafe { libc::syscall(libc::SYS_getdents64, fd, buf.as_mut_ptr(), buf.len()) };
@@ -393,35 +496,97 @@ fn list_dir(path: &[u8], show_all: bool, long_format: bool, one_per_line: bool,
                     io::write_str(1, b" ");
                     io::write_num(1, st.st_nlink as u64);
                     io::write_str(1, b" ");
-                    io::write_num(1, st.st_uid as u64);
+                    match users.as_ref().and_then(|users| users.getpwuid(st.st_uid)) {
+                        Some(pw) => io::write_all(1, pw.name),
+                        None => io::write_num(1, st.st_uid as u64),
+                    };
                     io::write_str(1, b" ");
-                    io::write_num(1, st.st_gid as u64);
+                    match groups.as_ref().and_then(|groups| groups.getgrgid(st.st_gid)) {
+                        Some(gr) => io::write_all(1, gr.name),
+                        None => io::write_num(1, st.st_gid as u64),
+                    };
                     io::write_str(1, b" ");
                     io::write_num(1, st.st_size as u64);
                     io::write_str(1, b" ");
+                    // The year instead of the time for files over six months
+                    // old or in the future
+                    let recent = st.st_mtime <= now && now - st.st_mtime < 365 * 86400 / 2;
+                    let fmt: &[u8] = if recent { b"%b %e %H:%M " } else { b"%b %e  %Y " };
+                    datetime::strftime(fmt, &tz.localtime(st.st_mtime), &mut |s| {
+                        io::write_all(1, s);
+                    });
                 }
             }
 
-            io::write_all(1, name);
-            if one_per_line || long_format {
-                io::write_str(1, b"\n");
-            } else {
-                io::write_str(1, b"  ");
-            }
-
             offset += dirent.d_reclen as usize;
+
+            #[cfg(feature = "alloc")]
+            if !one_per_line {
+                names.push(name.to_vec());
+                continue;
+            }
+            io::write_all(1, name);
+            io::write_str(1, b"\n");
         }
     }
 
-    if !one_per_line && !long_format {
+    #[cfg(feature = "alloc")]
+    print_columns(&names);
+
+    io::close(fd);
+    true
+}
+
+/// Print `names` down then across, in as many columns as fit the terminal
+#[cfg(feature = "alloc")]
+fn print_columns(names: &[alloc::vec::Vec<u8>]) {
+    let utf8 = utf8::locale_is_utf8();
+    let widths: alloc::vec::Vec<usize> = names.iter().map(|name| utf8::str_width(name, utf8)).collect();
+    let max_width = terminal_width();
+
+    // The widest name in each column, with two spaces between columns
+    let column_width = |rows: usize, col: usize| {
+        widths[col * rows..((col + 1) * rows).min(widths.len())].iter().max().copied().unwrap_or(0)
+    };
+    let Some(rows) = (1..=names.len()).find(|&rows| {
+        let cols = names.len().div_ceil(rows);
+        (0..cols).map(|col| column_width(rows, col) + 2).sum::<usize>() - 2 <= max_width
+    }) else {
+        return;
+    };
+
+    for row in 0..rows {
+        let mut i = row;
+        while i < names.len() {
+            io::write_all(1, &names[i]);
+            if i + rows < names.len() {
+                for _ in widths[i]..column_width(rows, i / rows) + 2 {
+                    io::write_str(1, b" ");
+                }
+            }
+            i += rows;
+        }
         io::write_str(1, b"\n");
     }
+}
 
-    io::close(fd);
+/// The width of the terminal on stdout: `$COLUMNS`, what the terminal
+/// says, or 80
+#[cfg(feature = "alloc")]
+fn terminal_width() -> usize {
+    if let Some(cols) = io::getenv(b"COLUMNS").and_then(sys::parse_u64).filter(|&cols| cols > 0) {
+        return cols as usize;
+    }
+    super::terminal::size(1).map_or(80, |size| size.1)
 }
 
 /// pwd - print working directory
-pub fn pwd(_argc: i32, _argv: *const *const u8) -> i32 {
+pub fn pwd(argc: i32, argv: *const *const u8) -> i32 {
+    // The kernel's cwd is always physical, so -L and -P print the same path
+    if Getopt::new(argc, &argv, b"LP").any(|arg| arg == Arg::Error) {
+        return 1;
+    }
+
     let mut buf = [0u8; 4096];
     let ret = unsafe { libc::getcwd(buf.as_mut_ptr() as *mut i8, buf.len()) };
     if !ret.is_null() {
@@ -429,128 +594,300 @@ pub fn pwd(_argc: i32, _argv: *const *const u8) -> i32 {
         io::write_str(1, b"\n");
         0
     } else {
-        sys::perror(b"getcwd");
+        sys::perror(b"pwd", b"getcwd");
         1
     }
 }
 
 /// chmod - change file modes
 pub fn chmod(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 3 {
-        io::write_str(2, b"chmod: missing operand\n");
-        return 1;
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if opts.operand_count() < 2 {
+        return usage::error(b"chmod", b"missing operand");
     }
 
-    let mode_str = unsafe { get_arg(argv, 1).unwrap() };
+    let mode_str = opts.operand(0).unwrap();
     let mode = sys::parse_octal(mode_str).unwrap_or(0o644);
 
-    for i in 2..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if io::chmod(path, mode) < 0 {
-                sys::perror(path);
-            }
+    let mut status = 0;
+    for path in opts.operands().skip(1) {
+        if io::chmod(path, mode) < 0 {
+            sys::perror(b"chmod", path);
+            status = 1;
         }
     }
-    0
+    status
 }
 
-/// chown - change file owner
+/// chown - change file owner and group
 pub fn chown(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 3 {
-        io::write_str(2, b"chown: missing operand\n");
-        return 1;
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if opts.operand_count() < 2 {
+        return usage::error(b"chown", b"missing operand");
     }
 
-    let owner = unsafe { get_arg(argv, 1).unwrap() };
-    let uid = sys::parse_u64(owner).unwrap_or(0) as u32;
+    // USER, USER:GROUP, USER: (the user's login group) or :GROUP; `.` is
+    // the old separator
+    let owner = opts.operand(0).unwrap();
+    let (user, group) = match owner.iter().position(|&c| c == b':').or_else(|| owner.iter().position(|&c| c == b'.')) {
+        Some(i) => (&owner[..i], Some(&owner[i + 1..])),
+        None => (owner, None),
+    };
 
-    for i in 2..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if unsafe { libc::chown(path.as_ptr() as *const i8, uid, u32::MAX) } < 0 {
-                sys::perror(path);
+    let users = accounts::Users::open();
+    let mut uid = u32::MAX;
+    let mut gid = u32::MAX;
+    if !user.is_empty() {
+        let pw = users.as_ref().and_then(|users| users.getpwnam(user));
+        match pw.map(|pw| pw.uid).or_else(|| accounts::parse_id(user)) {
+            Some(id) => uid = id,
+            None => {
+                sys::error_at(b"chown", user, b"unknown user");
+                return 1;
+            }
+        }
+        if group == Some(b"") {
+            match pw {
+                Some(pw) => gid = pw.gid,
+                None => {
+                    sys::error_at(b"chown", user, b"no login group");
+                    return 1;
+                }
             }
         }
     }
-    0
+    if let Some(group) = group.filter(|group| !group.is_empty()) {
+        match lookup_group(group) {
+            Some(id) => gid = id,
+            None => {
+                sys::error_at(b"chown", group, b"unknown group");
+                return 1;
+            }
+        }
+    }
+
+    let mut status = 0;
+    for path in opts.operands().skip(1) {
+        if unsafe { libc::chown(path.as_ptr() as *const i8, uid, gid) } < 0 {
+            sys::perror(b"chown", path);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// The gid of a group name or number
+fn lookup_group(group: &[u8]) -> Option<u32> {
+    match accounts::Groups::open() {
+        Some(groups) => groups.gid_of(group),
+        None => accounts::parse_id(group),
+    }
 }
 
 /// chgrp - change file group
 pub fn chgrp(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 3 {
-        io::write_str(2, b"chgrp: missing operand\n");
-        return 1;
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if opts.operand_count() < 2 {
+        return usage::error(b"chgrp", b"missing operand");
     }
 
-    let group = unsafe { get_arg(argv, 1).unwrap() };
-    let gid = sys::parse_u64(group).unwrap_or(0) as u32;
+    let group = opts.operand(0).unwrap();
+    let gid = match lookup_group(group) {
+        Some(gid) => gid,
+        None => {
+            sys::error_at(b"chgrp", group, b"unknown group");
+            return 1;
+        }
+    };
 
-    for i in 2..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if unsafe { libc::chown(path.as_ptr() as *const i8, u32::MAX, gid) } < 0 {
-                sys::perror(path);
-            }
+    let mut status = 0;
+    for path in opts.operands().skip(1) {
+        if unsafe { libc::chown(path.as_ptr() as *const i8, u32::MAX, gid) } < 0 {
+            sys::perror(b"chgrp", path);
+            status = 1;
         }
     }
-    0
+    status
 }
 
 /// stat - display file status
 pub fn stat(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] == b'-' { continue; }
+    let mut json = false;
+    let mut opts = Getopt::new(argc, &argv, b"(json)");
+    for arg in opts.by_ref() {
+        match arg {
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
 
-            let mut st: libc::stat = unsafe { core::mem::zeroed() };
-            if io::stat(path, &mut st) < 0 {
-                sys::perror(path);
-                continue;
-            }
+    let users = accounts::Users::open();
+    let groups = accounts::Groups::open();
+    let tz = datetime::TimeZone::local();
+    let mut doc = Json::new();
+    if json {
+        doc.begin_array();
+    }
+    let mut status = 0;
+    for path in opts.operands() {
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::stat(path, &mut st) < 0 {
+            sys::perror(b"stat", path);
+            status = 1;
+            continue;
+        }
+        let user = users.as_ref().and_then(|users| users.getpwuid(st.st_uid)).map(|pw| pw.name);
+        let group = groups.as_ref().and_then(|groups| groups.getgrgid(st.st_gid)).map(|gr| gr.name);
+        if json {
+            stat_json(&mut doc, path, &st, user, group);
+            continue;
+        }
 
-            io::write_str(1, b"  File: ");
-            io::write_all(1, path);
-            io::write_str(1, b"\n  Size: ");
-            io::write_num(1, st.st_size as u64);
-            io::write_str(1, b"\tBlocks: ");
-            io::write_num(1, st.st_blocks as u64);
-            io::write_str(1, b"\nDevice: ");
-            io::write_num(1, st.st_dev as u64);
-            io::write_str(1, b"\tInode: ");
-            io::write_num(1, st.st_ino as u64);
-            io::write_str(1, b"\tLinks: ");
-            io::write_num(1, st.st_nlink as u64);
-            io::write_str(1, b"\nAccess: ");
-            let mut mode_buf = [0u8; 10];
-            sys::format_mode(st.st_mode as u32, &mut mode_buf);
-            io::write_all(1, &mode_buf);
-            io::write_str(1, b"\n");
+        io::write_str(1, b"  File: ");
+        io::write_all(1, path);
+        io::write_str(1, b"\n  Size: ");
+        io::write_num(1, st.st_size as u64);
+        io::write_str(1, b"\tBlocks: ");
+        io::write_num(1, st.st_blocks as u64);
+        io::write_str(1, b"\nDevice: ");
+        io::write_num(1, st.st_dev as u64);
+        io::write_str(1, b"\tInode: ");
+        io::write_num(1, st.st_ino as u64);
+        io::write_str(1, b"\tLinks: ");
+        io::write_num(1, st.st_nlink as u64);
+        io::write_str(1, b"\nAccess: (");
+        // Permission bits as four octal digits, then as ls shows them
+        let mut octal = [b'0'; 4];
+        sys::format_octal(st.st_mode as u32 & 0o7777, &mut octal);
+        io::write_all(1, &octal);
+        io::write_str(1, b"/");
+        let mut mode_buf = [0u8; 10];
+        sys::format_mode(st.st_mode as u32, &mut mode_buf);
+        io::write_all(1, &mode_buf);
+        io::write_str(1, b")  Uid: (");
+        write_owner(st.st_uid, user);
+        io::write_str(1, b")   Gid: (");
+        write_owner(st.st_gid, group);
+        io::write_str(1, b")\n");
+        let times = [
+            (b"Access: ", st.st_atime, st.st_atime_nsec),
+            (b"Modify: ", st.st_mtime, st.st_mtime_nsec),
+            (b"Change: ", st.st_ctime, st.st_ctime_nsec),
+        ];
+        for (label, sec, nsec) in times {
+            io::write_all(1, label);
+            let tm = datetime::Tm { nsec: nsec as u32, ..tz.localtime(sec) };
+            datetime::strftime(b"%Y-%m-%d %H:%M:%S.%N %z\n", &tm, &mut |s| {
+                io::write_all(1, s);
+            });
         }
     }
-    0
+    if json {
+        doc.end_array().finish();
+    }
+    status
+}
+
+/// Writes `stat`'s fields for one file as a JSON object. The mode is
+/// four octal digits, as text; times are seconds since the epoch, with
+/// their nanoseconds apart.
+fn stat_json(doc: &mut Json, path: &[u8], st: &libc::stat, user: Option<&[u8]>, group: Option<&[u8]>) {
+    let kind: &[u8] = match st.st_mode & libc::S_IFMT {
+        libc::S_IFREG if st.st_size == 0 => b"regular empty file",
+        libc::S_IFREG => b"regular file",
+        libc::S_IFDIR => b"directory",
+        libc::S_IFLNK => b"symbolic link",
+        libc::S_IFCHR => b"character special file",
+        libc::S_IFBLK => b"block special file",
+        libc::S_IFIFO => b"fifo",
+        libc::S_IFSOCK => b"socket",
+        _ => b"unknown",
+    };
+    let mut octal = [b'0'; 4];
+    sys::format_octal(st.st_mode & 0o7777, &mut octal);
+    let mut permissions = [0u8; 10];
+    sys::format_mode(st.st_mode, &mut permissions);
+
+    doc.begin_object();
+    doc.key(b"file").string(path);
+    doc.key(b"type").string(kind);
+    doc.key(b"size").signed(st.st_size);
+    doc.key(b"blocks").signed(st.st_blocks);
+    doc.key(b"io_block").signed(st.st_blksize);
+    doc.key(b"device").number(st.st_dev);
+    doc.key(b"inode").number(st.st_ino);
+    doc.key(b"links").number(st.st_nlink);
+    doc.key(b"mode").string(&octal);
+    doc.key(b"permissions").string(&permissions);
+    doc.key(b"uid").number(st.st_uid as u64);
+    doc.key(b"user").optional(user);
+    doc.key(b"gid").number(st.st_gid as u64);
+    doc.key(b"group").optional(group);
+    let times = [
+        (&b"atime"[..], &b"atime_nsec"[..], st.st_atime, st.st_atime_nsec),
+        (b"mtime", b"mtime_nsec", st.st_mtime, st.st_mtime_nsec),
+        (b"ctime", b"ctime_nsec", st.st_ctime, st.st_ctime_nsec),
+    ];
+    for (key, nsec_key, sec, nsec) in times {
+        doc.key(key).signed(sec);
+        doc.key(nsec_key).signed(nsec);
+    }
+    doc.end_object();
+}
+
+/// Write `ID/NAME` as `stat` does, right-aligned in 5 and 8 columns, with
+/// `UNKNOWN` for an id without a name
+fn write_owner(id: u32, name: Option<&[u8]>) {
+    let mut buf = [0u8; 10];
+    let id = sys::format_u64(id as u64, &mut buf);
+    let name = name.unwrap_or(b"UNKNOWN");
+    for _ in id.len()..5 {
+        io::write_str(1, b" ");
+    }
+    io::write_all(1, id);
+    io::write_str(1, b"/");
+    for _ in name.len()..8 {
+        io::write_str(1, b" ");
+    }
+    io::write_all(1, name);
 }
 
 /// readlink - print resolved symbolic link
 pub fn readlink(argc: i32, argv: *const *const u8) -> i32 {
     let mut canonicalize = false;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'f') { canonicalize = true; }
-            } else {
-                let mut buf = [0u8; 4096];
-                let n = if canonicalize {
-                    io::realpath(arg, &mut buf)
-                } else {
-                    io::readlink(arg, &mut buf)
-                };
-                if n > 0 {
-                    io::write_all(1, &buf[..n as usize]);
-                    io::write_str(1, b"\n");
-                } else {
-                    sys::perror(arg);
-                    return 1;
-                }
-            }
+    let mut opts = Getopt::new(argc, &argv, b"f(canonicalize)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"f", _) => canonicalize = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    for path in opts.operands() {
+        let mut buf = [0u8; 4096];
+        let n = if canonicalize {
+            io::realpath(path, &mut buf)
+        } else {
+            io::readlink(path, &mut buf)
+        };
+        if n > 0 {
+            io::write_all(1, &buf[..n as usize]);
+            io::write_str(1, b"\n");
+        } else {
+            // Like BusyBox, fail quietly: not being a link is an answer
+            return 1;
         }
     }
     0
@@ -558,33 +895,37 @@ pub fn readlink(argc: i32, argv: *const *const u8) -> i32 {
 
 /// realpath - print canonical path
 pub fn realpath(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] == b'-' { continue; }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
 
-            let mut buf = [0u8; 4096];
-            let n = io::realpath(path, &mut buf);
-            if n > 0 {
-                io::write_all(1, &buf[..n as usize]);
-                io::write_str(1, b"\n");
-            } else {
-                sys::perror(path);
-                return 1;
-            }
+    let mut status = 0;
+    for path in opts.operands() {
+        let mut buf = [0u8; 4096];
+        let n = io::realpath(path, &mut buf);
+        if n > 0 {
+            io::write_all(1, &buf[..n as usize]);
+            io::write_str(1, b"\n");
+        } else {
+            sys::perror(b"realpath", path);
+            status = 1;
         }
     }
-    0
+    status
 }
 
 /// basename - strip directory from file name
 pub fn basename(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 {
-        io::write_str(2, b"basename: missing operand\n");
-        return 1;
-    }
-
-    let path = unsafe { get_arg(argv, 1).unwrap() };
-    let suffix = if argc > 2 { unsafe { get_arg(argv, 2) } } else { None };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let path = match opts.operand(0) {
+        Some(path) => path,
+        None => return usage::error(b"basename", b"missing operand"),
+    };
+    let suffix = opts.operand(1);
 
     // Find last /
     let mut start = 0;
@@ -611,12 +952,14 @@ pub fn basename(argc: i32, argv: *const *const u8) -> i32 {
 
 /// dirname - strip last component from file name
 pub fn dirname(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 {
-        io::write_str(2, b"dirname: missing operand\n");
-        return 1;
-    }
-
-    let path = unsafe { get_arg(argv, 1).unwrap() };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let path = match opts.operand(0) {
+        Some(path) => path,
+        None => return usage::error(b"dirname", b"missing operand"),
+    };
 
     // Find last /
     let mut last_slash = None;
@@ -645,16 +988,17 @@ pub fn sync_cmd(_argc: i32, _argv: *const *const u8) -> i32 {
 
 /// link - create hard link
 pub fn link(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 3 {
-        io::write_str(2, b"link: missing operand\n");
-        return 1;
-    }
-
-    let target = unsafe { get_arg(argv, 1).unwrap() };
-    let link_name = unsafe { get_arg(argv, 2).unwrap() };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let (target, link_name) = match (opts.operand(0), opts.operand(1)) {
+        (Some(target), Some(link_name)) => (target, link_name),
+        _ => return usage::error(b"link", b"missing operand"),
+    };
 
     if io::link(target, link_name) < 0 {
-        sys::perror(link_name);
+        sys::perror(b"link", link_name);
         return 1;
     }
     0
@@ -662,14 +1006,16 @@ pub fn link(argc: i32, argv: *const *const u8) -> i32 {
 
 /// unlink - remove file
 pub fn unlink(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 {
-        io::write_str(2, b"unlink: missing operand\n");
-        return 1;
-    }
-
-    let path = unsafe { get_arg(argv, 1).unwrap() };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let path = match opts.operand(0) {
+        Some(path) => path,
+        None => return usage::error(b"unlink", b"missing operand"),
+    };
     if io::unlink(path) < 0 {
-        sys::perror(path);
+        sys::perror(b"unlink", path);
         return 1;
     }
     0
@@ -682,17 +1028,19 @@ pub fn dd(argc: i32, argv: *const *const u8) -> i32 {
     let mut bs: usize = 512;
     let mut count: Option<usize> = None;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg.starts_with(b"if=") {
-                if_path = Some(&arg[3..]);
-            } else if arg.starts_with(b"of=") {
-                of_path = Some(&arg[3..]);
-            } else if arg.starts_with(b"bs=") {
-                bs = sys::parse_u64(&arg[3..]).unwrap_or(512) as usize;
-            } else if arg.starts_with(b"count=") {
-                count = Some(sys::parse_u64(&arg[6..]).unwrap_or(0) as usize);
-            }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    for arg in opts.operands() {
+        if arg.starts_with(b"if=") {
+            if_path = Some(&arg[3..]);
+        } else if arg.starts_with(b"of=") {
+            of_path = Some(&arg[3..]);
+        } else if arg.starts_with(b"bs=") {
+            bs = sys::parse_u64(&arg[3..]).unwrap_or(512) as usize;
+        } else if arg.starts_with(b"count=") {
+            count = Some(sys::parse_u64(&arg[6..]).unwrap_or(0) as usize);
         }
     }
 
@@ -700,13 +1048,17 @@ pub fn dd(argc: i32, argv: *const *const u8) -> i32 {
         Some(p) => io::open(p, libc::O_RDONLY, 0),
         None => 0,
     };
-    if in_fd < 0 { return 1; }
+    if in_fd < 0 {
+        sys::perror(b"dd", if_path.unwrap());
+        return 1;
+    }
 
     let out_fd = match of_path {
         Some(p) => io::open(p, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, 0o644),
         None => 1,
     };
     if out_fd < 0 {
+        sys::perror(b"dd", of_path.unwrap());
         if in_fd != 0 { io::close(in_fd); }
         return 1;
     }
@@ -761,52 +1113,52 @@ pub fn dd(argc: i32, argv: *const *const u8) -> i32 {
 /// mktemp - create temporary file/directory
 pub fn mktemp(argc: i32, argv: *const *const u8) -> i32 {
     let mut dir = false;
-    let template = if argc > 1 {
-        for i in 1..argc {
-            if let Some(arg) = unsafe { get_arg(argv, i) } {
-                if has_opt(arg, b'd') { dir = true; }
-                else if arg[0] != b'-' { return create_temp(arg, dir); }
-            }
+
+    let mut opts = Getopt::new(argc, &argv, b"d(directory)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"d", _) => dir = true,
+            Arg::Error => return 1,
+            _ => {}
         }
-        b"/tmp/tmp.XXXXXX"
-    } else {
-        b"/tmp/tmp.XXXXXX"
-    };
+    }
+
+    let template = opts.operand(0).unwrap_or(b"/tmp/tmp.XXXXXX");
     create_temp(template, dir)
 }
 
 fn create_temp(template: &[u8], dir: bool) -> i32 {
-    let mut path = [0u8; 256];
-    for (i, &c) in template.iter().enumerate() {
-        path[i] = c;
+    let mut path = [0u8; 4096];
+    if template.len() >= path.len() {
+        sys::error_errno(b"mktemp", template, libc::ENAMETOOLONG);
+        return 1;
     }
+    let path = &mut path[..template.len()];
+    path.copy_from_slice(template);
 
-    // Replace X with random chars
-    let seed = unsafe { libc::time(core::ptr::null_mut()) } as u64;
-    let mut rng = seed;
-    for i in 0..template.len() {
-        if path[i] == b'X' {
-            rng = rng.wrapping_mul(6364136223846793005).wrapping_add(1);
-            path[i] = b"0123456789abcdef"[(rng >> 60) as usize];
+    let fd = sys::make_temp(path, dir);
+    if fd < 0 {
+        if sys::errno() == libc::EINVAL {
+            sys::error_at(b"mktemp", template, b"too few X's in template");
+        } else {
+            sys::perror(b"mktemp", template);
         }
+        return 1;
     }
-
-    if dir {
-        if io::mkdir(&path[..template.len()], 0o700) < 0 {
-            sys::perror(&path[..template.len()]);
-            return 1;
-        }
-    } else {
-        let fd = io::open(&path[..template.len()], libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL, 0o600);
-        if fd < 0 {
-            sys::perror(&path[..template.len()]);
-            return 1;
-        }
+    if !dir {
         io::close(fd);
     }
 
-    io::write_all(1, &path[..template.len()]);
-    io::write_str(1, b"\n");
+    // Nobody can use the name if it isn't printed, so remove it then
+    let created = sys::Cleanup::remove(path);
+    let out = io::stdout();
+    out.write_all(path);
+    out.write_byte(b'\n');
+    if out.flush() < 0 {
+        sys::perror(b"mktemp", b"write error");
+        return 1;
+    }
+    created.disarm();
     0
 }
 
@@ -814,14 +1166,15 @@ fn create_temp(template: &[u8], dir: bool) -> i32 {
 pub fn mkfifo(argc: i32, argv: *const *const u8) -> i32 {
     let mode = 0o644u32;
 
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                if unsafe { libc::mkfifo(path.as_ptr() as *const i8, mode) } < 0 {
-                    sys::perror(path);
-                    return 1;
-                }
-            }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    for path in opts.operands() {
+        if unsafe { libc::mkfifo(path.as_ptr() as *const i8, mode) } < 0 {
+            sys::perror(b"mkfifo", path);
+            return 1;
         }
     }
     0
@@ -829,28 +1182,28 @@ pub fn mkfifo(argc: i32, argv: *const *const u8) -> i32 {
 
 /// mknod - make block or character special files
 pub fn mknod(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 4 {
-        io::write_str(2, b"mknod: missing operand\n");
-        return 1;
-    }
-
-    let path = unsafe { get_arg(argv, 1).unwrap() };
-    let type_arg = unsafe { get_arg(argv, 2).unwrap() };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let (path, type_arg) = match (opts.operand(0), opts.operand(1)) {
+        (Some(path), Some(type_arg)) => (path, type_arg),
+        _ => return usage::error(b"mknod", b"missing operand"),
+    };
 
     let (mode, dev) = if type_arg == b"p" {
         (libc::S_IFIFO | 0o666, 0)
-    } else if argc >= 5 {
-        let major = sys::parse_u64(unsafe { get_arg(argv, 3).unwrap() }).unwrap_or(0) as u32;
-        let minor = sys::parse_u64(unsafe { get_arg(argv, 4).unwrap() }).unwrap_or(0) as u32;
+    } else if let (Some(major), Some(minor)) = (opts.operand(2), opts.operand(3)) {
+        let major = sys::parse_u64(major).unwrap_or(0) as u32;
+        let minor = sys::parse_u64(minor).unwrap_or(0) as u32;
         let m = if type_arg == b"b" { libc::S_IFBLK } else { libc::S_IFCHR };
         (m | 0o666, sys::makedev(major, minor))
     } else {
-        io::write_str(2, b"mknod: missing major/minor\n");
-        return 1;
+        return usage::error(b"mknod", b"missing major/minor");
     };
 
     if unsafe { libc::mknod(path.as_ptr() as *const i8, mode, dev) } < 0 {
-        sys::perror(path);
+        sys::perror(b"mknod", path);
         return 1;
     }
     0
@@ -860,29 +1213,29 @@ pub fn mknod(argc: i32, argv: *const *const u8) -> i32 {
 pub fn split(argc: i32, argv: *const *const u8) -> i32 {
     let mut lines = 1000usize;
     let mut prefix = b"x".as_slice();
-    let mut input: Option<&[u8]> = None;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'l') && i + 1 < argc {
-                if let Some(n) = unsafe { get_arg(argv, i + 1) } {
-                    lines = sys::parse_u64(n).unwrap_or(1000) as usize;
-                }
-            } else if arg[0] != b'-' {
-                if input.is_none() {
-                    input = Some(arg);
-                } else {
-                    prefix = arg;
-                }
-            }
+    let mut opts = Getopt::new(argc, &argv, b"l(lines):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"l", Some(n)) => lines = sys::parse_u64(n).unwrap_or(1000) as usize,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
+    let input = opts.operand(0);
+    if let Some(p) = opts.operand(1) {
+        prefix = p;
+    }
+
     let fd = match input {
         Some(p) if p != b"-" => io::open(p, libc::O_RDONLY, 0),
         _ => 0,
     };
-    if fd < 0 { return 1; }
+    if fd < 0 {
+        sys::perror(b"split", input.unwrap());
+        return 1;
+    }
 
     let _ = lines;
     let _ = prefix;
@@ -898,30 +1251,30 @@ pub fn install(argc: i32, argv: *const *const u8) -> i32 {
     let mut dir_mode = false;
     let mut mode = 0o755u32;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'd') { dir_mode = true; }
-            if has_opt(arg, b'm') && i + 1 < argc {
-                if let Some(m) = unsafe { get_arg(argv, i + 1) } {
-                    mode = sys::parse_octal(m).unwrap_or(0o755);
-                }
-            }
+    let mut opts = Getopt::new(argc, &argv, b"d(directory)m(mode):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"d", _) => dir_mode = true,
+            Arg::Opt(b"m", Some(m)) => mode = sys::parse_octal(m).unwrap_or(0o755),
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
+    let count = opts.operand_count();
     if dir_mode {
-        for i in 1..argc {
-            if let Some(path) = unsafe { get_arg(argv, i) } {
-                if path[0] != b'-' {
-                    mkdir_parents(path, mode);
-                }
-            }
+        for path in opts.operands() {
+            mkdir_parents(path, mode);
+        }
+    } else if count >= 2 {
+        let src = opts.operand(count - 2).unwrap();
+        let dest = opts.operand(count - 1).unwrap();
+        if !copy_file(b"install", src, dest, false, true, false, false) {
+            return 1;
         }
-    } else if argc >= 3 {
-        let src = unsafe { get_arg(argv, argc - 2).unwrap() };
-        let dest = unsafe { get_arg(argv, argc - 1).unwrap() };
-        copy_file(src, dest, false, true, false, false);
         io::chmod(dest, mode);
+    } else {
+        return usage::error(b"install", b"missing operand");
     }
     0
 }
@@ -930,18 +1283,19 @@ pub fn install(argc: i32, argv: *const *const u8) -> i32 {
 pub fn truncate(argc: i32, argv: *const *const u8) -> i32 {
     let mut size: i64 = 0;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b's') && i + 1 < argc {
-                if let Some(s) = unsafe { get_arg(argv, i + 1) } {
-                    size = sys::parse_u64(s).unwrap_or(0) as i64;
-                }
-            } else if arg[0] != b'-' {
-                if unsafe { libc::truncate(arg.as_ptr() as *const i8, size) } < 0 {
-                    sys::perror(arg);
-                    return 1;
-                }
-            }
+    let mut opts = Getopt::new(argc, &argv, b"s(size):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"s", Some(s)) => size = sys::parse_u64(s).unwrap_or(0) as i64,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    for path in opts.operands() {
+        if unsafe { libc::truncate(path.as_ptr() as *const i8, size) } < 0 {
+            sys::perror(b"truncate", path);
+            return 1;
         }
     }
     0
@@ -951,103 +1305,110 @@ pub fn truncate(argc: i32, argv: *const *const u8) -> i32 {
 pub fn shred(argc: i32, argv: *const *const u8) -> i32 {
     let mut remove = false;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'u') { remove = true; }
-            else if arg[0] != b'-' {
-                // Overwrite with random data
-                let fd = io::open(arg, libc::O_WRONLY, 0);
-                if fd < 0 {
-                    sys::perror(arg);
-                    continue;
-                }
+    let mut opts = Getopt::new(argc, &argv, b"u(remove)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"u", _) => remove = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
 
-                let mut st: libc::stat = unsafe { core::mem::zeroed() };
-                if io::fstat(fd, &mut st) == 0 {
-                    let size = st.st_size as usize;
-                    let mut buf = [0xFFu8; 4096];
-                    let mut written = 0;
-                    while written < size {
-                        let chunk = core::cmp::min(buf.len(), size - written);
-                        io::write_all(fd, &buf[..chunk]);
-                        written += chunk;
-                    }
-                    unsafe { libc::fsync(fd) };
-
-                    // Zero pass
-                    unsafe { libc::lseek(fd, 0, libc::SEEK_SET) };
-                    buf.fill(0);
-                    written = 0;
-                    while written < size {
-                        let chunk = core::cmp::min(buf.len(), size - written);
-                        io::write_all(fd, &buf[..chunk]);
-                        written += chunk;
-                    }
-                }
-                io::close(fd);
+    let mut status = 0;
+    for path in opts.operands() {
+        // Overwrite with random data
+        let fd = io::open(path, libc::O_WRONLY, 0);
+        if fd < 0 {
+            sys::perror(b"shred", path);
+            status = 1;
+            continue;
+        }
 
-                if remove {
-                    io::unlink(arg);
-                }
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::fstat(fd, &mut st) == 0 {
+            let size = st.st_size as usize;
+            let mut buf = [0xFFu8; 4096];
+            let mut written = 0;
+            while written < size {
+                let chunk = core::cmp::min(buf.len(), size - written);
+                io::write_all(fd, &buf[..chunk]);
+                written += chunk;
+            }
+            unsafe { libc::fsync(fd) };
+
+            // Zero pass
+            unsafe { libc::lseek(fd, 0, libc::SEEK_SET) };
+            buf.fill(0);
+            written = 0;
+            while written < size {
+                let chunk = core::cmp::min(buf.len(), size - written);
+                io::write_all(fd, &buf[..chunk]);
+                written += chunk;
             }
         }
+        io::close(fd);
+
+        if remove {
+            io::unlink(path);
+        }
     }
-    0
+    status
 }
 
 /// file - determine file type
 pub fn file(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] == b'-' { continue; }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
 
-            io::write_all(1, path);
-            io::write_str(1, b": ");
+    for path in opts.operands() {
+        io::write_all(1, path);
+        io::write_str(1, b": ");
 
-            let mut st: libc::stat = unsafe { core::mem::zeroed() };
-            if io::lstat(path, &mut st) < 0 {
-                io::write_str(1, b"cannot stat\n");
-                continue;
-            }
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::lstat(path, &mut st) < 0 {
+            io::write_str(1, b"cannot stat\n");
+            continue;
+        }
 
-            match st.st_mode & libc::S_IFMT {
-                libc::S_IFDIR => { io::write_str(1, b"directory\n"); }
-                libc::S_IFLNK => { io::write_str(1, b"symbolic link\n"); }
-                libc::S_IFIFO => { io::write_str(1, b"fifo (named pipe)\n"); }
-                libc::S_IFSOCK => { io::write_str(1, b"socket\n"); }
-                libc::S_IFBLK => { io::write_str(1, b"block special\n"); }
-                libc::S_IFCHR => { io::write_str(1, b"character special\n"); }
-                libc::S_IFREG => {
-                    // Check magic bytes
-                    let fd = io::open(path, libc::O_RDONLY, 0);
-                    if fd >= 0 {
-                        let mut magic = [0u8; 8];
-                        let n = io::read(fd, &mut magic);
-                        io::close(fd);
-
-                        if n >= 4 {
-                            if magic[0..4] == [0x7F, b'E', b'L', b'F'] {
-                                io::write_str(1, b"ELF executable\n");
-                            } else if magic[0..2] == [b'#', b'!'] {
-                                io::write_str(1, b"script\n");
-                            } else if magic[0..4] == [0x1F, 0x8B, 0x08, 0x00] {
-                                io::write_str(1, b"gzip compressed\n");
-                            } else if magic[0..3] == [b'B', b'Z', b'h'] {
-                                io::write_str(1, b"bzip2 compressed\n");
-                            } else if st.st_size == 0 {
-                                io::write_str(1, b"empty\n");
-                            } else {
-                                io::write_str(1, b"data\n");
-                            }
-                        } else {
+        match st.st_mode & libc::S_IFMT {
+            libc::S_IFDIR => { io::write_str(1, b"directory\n"); }
+            libc::S_IFLNK => { io::write_str(1, b"symbolic link\n"); }
+            libc::S_IFIFO => { io::write_str(1, b"fifo (named pipe)\n"); }
+            libc::S_IFSOCK => { io::write_str(1, b"socket\n"); }
+            libc::S_IFBLK => { io::write_str(1, b"block special\n"); }
+            libc::S_IFCHR => { io::write_str(1, b"character special\n"); }
+            libc::S_IFREG => {
+                // Check magic bytes
+                let fd = io::open(path, libc::O_RDONLY, 0);
+                if fd >= 0 {
+                    let mut magic = [0u8; 8];
+                    let n = io::read(fd, &mut magic);
+                    io::close(fd);
+
+                    if n >= 4 {
+                        if magic[0..4] == [0x7F, b'E', b'L', b'F'] {
+                            io::write_str(1, b"ELF executable\n");
+                        } else if magic[0..2] == [b'#', b'!'] {
+                            io::write_str(1, b"script\n");
+                        } else if magic[0..4] == [0x1F, 0x8B, 0x08, 0x00] {
+                            io::write_str(1, b"gzip compressed\n");
+                        } else if magic[0..3] == [b'B', b'Z', b'h'] {
+                            io::write_str(1, b"bzip2 compressed\n");
+                        } else if st.st_size == 0 {
                             io::write_str(1, b"empty\n");
+                        } else {
+                            io::write_str(1, b"data\n");
                         }
                     } else {
-                        io::write_str(1, b"regular file\n");
+                        io::write_str(1, b"empty\n");
                     }
+                } else {
+                    io::write_str(1, b"regular file\n");
                 }
-                _ => { io::write_str(1, b"unknown\n"); }
             }
+            _ => { io::write_str(1, b"unknown\n"); }
         }
     }
     0
@@ -1055,87 +1416,81 @@ pub fn file(argc: i32, argv: *const *const u8) -> i32 {
 
 /// xargs - build and execute commands
 pub fn xargs(argc: i32, argv: *const *const u8) -> i32 {
+    let mut delim = b'\n';
+
+    // Everything from the command name on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+0(null)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"0", _) => delim = 0,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
     #[cfg(feature = "alloc")]
     {
         use alloc::vec::Vec;
         use alloc::ffi::CString;
 
-        // Read lines from stdin
-        let mut buf = [0u8; 4096];
-        let n = io::read(0, &mut buf);
-        if n <= 0 { return 0; }
-
         // Parse arguments
-        let cmd = if argc > 1 {
-            unsafe { get_arg(argv, 1).unwrap() }
-        } else {
-            b"echo"
-        };
-
-        // Build argument list
-        let lines: Vec<&[u8]> = buf[..n as usize]
-            .split(|&c| c == b'\n')
-            .filter(|l| !l.is_empty())
-            .collect();
-
-        for line in lines {
-            let pid = unsafe { libc::fork() };
-            if pid == 0 {
-                let mut args: Vec<CString> = Vec::new();
-
-                // Command
-                let mut v = Vec::with_capacity(cmd.len() + 1);
-                v.extend_from_slice(cmd);
-                v.push(0);
-                if let Ok(cs) = CString::from_vec_with_nul(v) {
-                    args.push(cs);
-                }
-
-                // Original args
-                for i in 2..argc {
-                    if let Some(arg) = unsafe { get_arg(argv, i) } {
-                        let mut v = Vec::with_capacity(arg.len() + 1);
-                        v.extend_from_slice(arg);
-                        v.push(0);
-                        if let Ok(cs) = CString::from_vec_with_nul(v) {
-                            args.push(cs);
-                        }
-                    }
-                }
-
-                // Line as argument
-                let mut v = Vec::with_capacity(line.len() + 1);
-                v.extend_from_slice(line);
+        let cmd = opts.operand(0).unwrap_or(b"echo");
+        let mut status = 0;
+
+        // Run the command once per input line
+        let mut reader = io::LineReader::with_delimiter(0, delim);
+        while let Some(line) = reader.next_line() {
+            let line = line.data;
+            if line.is_empty() { continue; }
+
+            // Command, original args, then the line as an argument
+            let mut args: Vec<CString> = Vec::new();
+            let mut push = |arg: &[u8]| {
+                let mut v = Vec::with_capacity(arg.len() + 1);
+                v.extend_from_slice(arg);
                 v.push(0);
                 if let Ok(cs) = CString::from_vec_with_nul(v) {
                     args.push(cs);
                 }
-
-                let ptrs: Vec<*const i8> = args.iter()
-                    .map(|s: &CString| s.as_ptr())
-                    .chain(core::iter::once(core::ptr::null()))
-                    .collect();
-
-                unsafe { libc::execvp(ptrs[0], ptrs.as_ptr()) };
-                unsafe { libc::_exit(127) };
-            } else if pid > 0 {
-                let mut status = 0;
-                unsafe { libc::waitpid(pid, &mut status, 0) };
+            };
+            push(cmd);
+            opts.operands().skip(1).for_each(&mut push);
+            push(line);
+            let ptrs: Vec<*const u8> = args.iter()
+                .map(|s: &CString| s.as_ptr() as *const u8)
+                .chain(core::iter::once(core::ptr::null()))
+                .collect();
+
+            // As POSIX says: stop if the command can't be run or was
+            // killed, and exit with 123 if any run failed
+            match unsafe { exec::spawn(b"xargs", ptrs.as_ptr()) } {
+                0 => {}
+                s @ (EXIT_CANNOT_EXECUTE | EXIT_NOT_FOUND) => return s,
+                255 => return 124,
+                s if s > 128 => return 125,
+                _ => status = 123,
             }
         }
+        status
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    {
+        let _ = delim;
+        0
     }
-    0
 }
 
 /// patch - apply a diff file
 pub fn patch(argc: i32, argv: *const *const u8) -> i32 {
     let mut input: Option<&[u8]> = None;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'i') && i + 1 < argc {
-                input = unsafe { get_arg(argv, i + 1) };
-            }
+    let mut opts = Getopt::new(argc, &argv, b"i(input):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"i", Some(p)) => input = Some(p),
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
@@ -1160,26 +1515,115 @@ pub fn find(argc: i32, argv: *const *const u8) -> i32 {
         b"."
     };
 
-    let mut name_pattern: Option<&[u8]> = None;
-    let mut file_type: Option<u8> = None;
+    let mut tests = FindTests { name: None, path: None, regex: None, file_type: None };
+    let mut exec: Option<FindExec> = None;
 
-    for i in 1..argc {
+    let mut i = 1;
+    while i < argc {
         if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg == b"-name" && i + 1 < argc {
-                name_pattern = unsafe { get_arg(argv, i + 1) };
+            let pattern = if i + 1 < argc { unsafe { get_arg(argv, i + 1) } } else { None };
+            if let (b"-name" | b"-iname", Some(pattern)) = (arg, pattern) {
+                let flags = if arg == b"-iname" { fnmatch::CASEFOLD } else { 0 };
+                tests.name = Some((pattern, flags));
+                i += 1;
+            } else if let (b"-path" | b"-ipath", Some(pattern)) = (arg, pattern) {
+                let flags = if arg == b"-ipath" { fnmatch::CASEFOLD } else { 0 };
+                tests.path = Some((pattern, flags));
+                i += 1;
             } else if arg == b"-type" && i + 1 < argc {
                 if let Some(t) = unsafe { get_arg(argv, i + 1) } {
-                    file_type = Some(t[0]);
+                    tests.file_type = Some(t[0]);
+                }
+            } else if (arg == b"-regex" || arg == b"-iregex") && i + 1 < argc {
+                let flags = if arg == b"-iregex" { regex::ICASE } else { 0 };
+                match Regex::new(unsafe { get_arg(argv, i + 1) }.unwrap_or(b""), flags) {
+                    Ok(re) => tests.regex = Some(re),
+                    Err(msg) => {
+                        sys::error(b"find", msg);
+                        return 1;
+                    }
+                }
+                i += 1;
+            } else if arg == b"-exec" {
+                let end = (i + 1..argc).find(|&j| unsafe { get_arg(argv, j) } == Some(b";".as_slice()));
+                match end {
+                    Some(end) if end > i + 1 && end - i - 1 < FIND_EXEC_MAX => {
+                        exec = Some(FindExec { argv, start: i + 1, end });
+                        i = end;
+                    }
+                    _ => {
+                        sys::error(b"find", b"-exec requires a command ending in ';'");
+                        return 1;
+                    }
                 }
             }
         }
+        i += 1;
     }
 
-    find_recursive(start_path, name_pattern, file_type);
+    find_recursive(start_path, &tests, exec.as_ref());
     0
 }
 
-fn find_recursive(path: &[u8], name_pattern: Option<&[u8]>, file_type: Option<u8>) {
+/// Most arguments a `find -exec` command may have
+const FIND_EXEC_MAX: i32 = 64;
+
+/// The `-exec CMD... ;` action of `find`: `argv[start..end]`
+struct FindExec {
+    argv: *const *const u8,
+    start: i32,
+    end: i32,
+}
+
+impl FindExec {
+    /// Run the command with each `{}` replaced by `path`; true if it
+    /// exited with status 0
+    fn run(&self, path: &[u8]) -> bool {
+        let mut path_buf = [0u8; 513];
+        path_buf[..path.len()].copy_from_slice(path);
+
+        let mut args = [core::ptr::null::<u8>(); FIND_EXEC_MAX as usize + 1];
+        for (n, i) in (self.start..self.end).enumerate() {
+            let arg = unsafe { *self.argv.add(i as usize) };
+            args[n] = if unsafe { get_arg(self.argv, i) } == Some(b"{}".as_slice()) {
+                path_buf.as_ptr()
+            } else {
+                arg
+            };
+        }
+        unsafe { exec::spawn(b"find", args.as_ptr()) == 0 }
+    }
+}
+
+/// The tests `find` applies to each file
+struct FindTests<'a> {
+    /// `-name` or `-iname`: a pattern for the base name, with its
+    /// `fnmatch` flags
+    name: Option<(&'a [u8], u32)>,
+    /// `-path` or `-ipath`: the same for the whole path
+    path: Option<(&'a [u8], u32)>,
+    /// `-regex` or `-iregex`: must match the whole path
+    regex: Option<Regex>,
+    /// `-type`: `f`, `d` or `l`
+    file_type: Option<u8>,
+}
+
+impl FindTests<'_> {
+    fn matches(&self, path: &[u8], name: &[u8], d_type: u8) -> bool {
+        let type_ok = match self.file_type {
+            Some(b'f') => d_type == libc::DT_REG,
+            Some(b'd') => d_type == libc::DT_DIR,
+            Some(b'l') => d_type == libc::DT_LNK,
+            _ => true,
+        };
+        type_ok
+            && self.name.is_none_or(|(pattern, flags)| fnmatch(pattern, name, flags))
+            && self.path.is_none_or(|(pattern, flags)| fnmatch(pattern, path, flags))
+            && self.regex.as_ref().is_none_or(|re| re.find_at(path, 0) == Some((0, path.len())))
+    }
+}
+
+fn find_recursive(path: &[u8], tests: &FindTests, exec: Option<&FindExec>) {
     let fd = io::open(path, libc::O_RDONLY | libc::O_DIRECTORY, 0);
     if fd < 0 { return; }
 
@@ -1203,35 +1647,19 @@ fn find_recursive(path: &[u8], name_pattern: Option<&[u8]>, file_type: Option<u8
                 }
                 for c in name { full_path[len] = *c; len += 1; }
 
-                // Check type
-                let type_ok = match file_type {
-                    Some(b'f') => dirent.d_type == libc::DT_REG,
-                    Some(b'd') => dirent.d_type == libc::DT_DIR,
-                    Some(b'l') => dirent.d_type == libc::DT_LNK,
-                    _ => true,
-                };
-
-                // Check name pattern (simple glob)
-                let name_ok = match name_pattern {
-                    Some(p) => {
-                        if p.len() >= 2 && p[0] == b'*' {
-                            // *.ext pattern
-                            name.ends_with(&p[1..])
-                        } else {
-                            name == p
+                if tests.matches(&full_path[..len], name, dirent.d_type) {
+                    match exec {
+                        Some(exec) => { exec.run(&full_path[..len]); }
+                        None => {
+                            io::write_all(1, &full_path[..len]);
+                            io::write_str(1, b"\n");
                         }
                     }
-                    None => true,
-                };
-
-                if type_ok && name_ok {
-                    io::write_all(1, &full_path[..len]);
-                    io::write_str(1, b"\n");
                 }
 
                 // Recurse into directories
                 if dirent.d_type == libc::DT_DIR {
-                    find_recursive(&full_path[..len], name_pattern, file_type);
+                    find_recursive(&full_path[..len], tests, exec);
                 }
             }
 
@@ -1243,15 +1671,15 @@ fn find_recursive(path: &[u8], name_pattern: Option<&[u8]>, file_type: Option<u8
 
 /// cd - change directory (shell builtin, but implemented as stub)
 pub fn cd(argc: i32, argv: *const *const u8) -> i32 {
-    let path = if argc > 1 {
-        unsafe { get_arg(argv, 1).unwrap() }
-    } else {
-        // Get HOME
-        b"/root"
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
     };
+    // Get HOME
+    let path = opts.operand(0).unwrap_or(b"/root");
 
     if io::chdir(path) < 0 {
-        sys::perror(path);
+        sys::perror(b"cd", path);
         return 1;
     }
     0
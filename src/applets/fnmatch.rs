@@ -0,0 +1,480 @@
+//! Shell wildcard patterns
+//!
+//! `fnmatch` matches a name against a pattern as POSIX `fnmatch(3)` does,
+//! for `find -name`, and later for the shell's `case` and filename
+//! expansion, `tar --exclude` and the like. `glob` expands a pattern into
+//! the existing paths it matches.
+//!
+//! - `*` matches any string and `?` any one byte
+//! - `[...]` and `[!...]` (or `[^...]`) match one byte in or not in a set
+//!   of bytes, ranges, `[:class:]`, `[=c=]` and `[.c.]`; an unclosed `[`
+//!   is an ordinary byte
+//! - `\` makes the next byte ordinary, unless `NOESCAPE` is given
+//!
+//! Matching backtracks to the most recent `*` only, so it takes time
+//! proportional to the pattern length times the name length.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use crate::io;
+use super::regex::class_test;
+
+/// `/` is only matched by a `/` in the pattern (`FNM_PATHNAME`)
+pub const PATHNAME: u32 = 1;
+
+/// `\` is an ordinary byte (`FNM_NOESCAPE`)
+pub const NOESCAPE: u32 = 2;
+
+/// A leading `.`, or with `PATHNAME` one after a `/`, is only matched by a
+/// `.` in the pattern (`FNM_PERIOD`)
+pub const PERIOD: u32 = 4;
+
+/// Ignore case (`FNM_CASEFOLD`)
+pub const CASEFOLD: u32 = 16;
+
+/// Whether `name` matches `pattern` as a whole
+pub fn fnmatch(pattern: &[u8], name: &[u8], flags: u32) -> bool {
+    let fold = |c: u8| if flags & CASEFOLD != 0 { c.to_ascii_lowercase() } else { c };
+    // Whether a `.` at `i` may only be matched by a `.`. As in glibc, a
+    // `/` matched by an escaped `\/` doesn't make the next byte leading.
+    let leading = |i: usize, escaped_slash: bool| {
+        flags & PERIOD != 0
+            && name[i] == b'.'
+            && (i == 0 || (flags & PATHNAME != 0 && name[i - 1] == b'/' && !escaped_slash))
+    };
+    // Whether `*`, `?` or `[...]` may match the byte at `i`
+    let wild_ok = |i: usize, escaped_slash: bool| !(flags & PATHNAME != 0 && name[i] == b'/' || leading(i, escaped_slash));
+
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`: just past it in the pattern, and
+    // the next name byte it would swallow
+    let mut star: Option<(usize, usize)> = None;
+    // Whether the `/` before `n` was matched by `\/`
+    let mut escaped_slash = false;
+
+    loop {
+        let step = if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    while pattern.get(p) == Some(&b'*') {
+                        p += 1;
+                    }
+                    // Not even an empty `*` may stand in front of a leading `.`
+                    if n < name.len() && leading(n, escaped_slash) {
+                        return false;
+                    }
+                    star = Some((p, n));
+                    continue;
+                }
+                b'?' => n < name.len() && wild_ok(n, escaped_slash),
+                b'[' if n < name.len() => match bracket(pattern, p + 1, name[n], flags) {
+                    Bracket::Closed(matched, end) => {
+                        if matched && wild_ok(n, escaped_slash) {
+                            p = end;
+                            n += 1;
+                            continue;
+                        }
+                        false
+                    }
+                    Bracket::Unclosed => name[n] == b'[',
+                    Bracket::Invalid => false,
+                },
+                b'\\' if flags & NOESCAPE == 0 => {
+                    // A trailing `\` is an error, which matches nothing
+                    if p + 1 == pattern.len() {
+                        return false;
+                    }
+                    // As in glibc, only a plain `/` ends what a `*` may match,
+                    // so an escaped one right after it (and any `?`s) can't
+                    // match with PATHNAME
+                    let slash = flags & PATHNAME != 0 && pattern[p + 1] == b'/';
+                    let after_star = star.is_some_and(|(sp, _)| pattern[sp..p].iter().all(|&c| c == b'?'));
+                    if n < name.len() && fold(name[n]) == fold(pattern[p + 1]) && !(slash && after_star) {
+                        escaped_slash = slash;
+                        p += 2;
+                        n += 1;
+                        continue;
+                    }
+                    false
+                }
+                // A `*` before a matched `/` can't grow past it, so forget it
+                b'/' if flags & PATHNAME != 0 && name.get(n) == Some(&b'/') => {
+                    star = None;
+                    escaped_slash = false;
+                    true
+                }
+                c => n < name.len() && fold(name[n]) == fold(c),
+            }
+        } else if n == name.len() {
+            return true;
+        } else {
+            false
+        };
+
+        if step {
+            p += 1;
+            n += 1;
+            continue;
+        }
+
+        // Let the last `*` swallow one more byte and try again
+        match star {
+            Some((sp, sn)) if sn < name.len() && !(flags & PATHNAME != 0 && name[sn] == b'/') => {
+                star = Some((sp, sn + 1));
+                p = sp;
+                n = sn + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// What a bracket expression made of a name byte
+enum Bracket {
+    /// Whether it matched, and the index after the `]`
+    Closed(bool, usize),
+    /// There is no `]`, so the `[` is an ordinary byte
+    Unclosed,
+    /// An unknown class or collating element: nothing matches
+    Invalid,
+}
+
+/// Match `c` against the bracket expression whose `[` is just before
+/// `start`.
+///
+/// Elements are tried in order and the rest only scanned for the `]` once
+/// one matches, so a bad element after that doesn't count, as in glibc.
+fn bracket(pattern: &[u8], start: usize, c: u8, flags: u32) -> Bracket {
+    let fold = |c: u8| if flags & CASEFOLD != 0 { c.to_ascii_lowercase() } else { c };
+    let mut i = start;
+    let negate = matches!(pattern.get(i), Some(b'!' | b'^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+
+    loop {
+        match pattern.get(i) {
+            None => return Bracket::Unclosed,
+            // A `]` first in the set is an ordinary byte
+            Some(b']') if !first => return Bracket::Closed(matched != negate, i + 1),
+            _ => first = false,
+        }
+
+        let (lo, next) = match element(pattern, i, flags, false) {
+            Element::Byte(b, next) => (b, next),
+            // glibc doesn't fold case for `[=c=]`
+            Element::Equivalent(b, next) => {
+                matched |= b == c;
+                i = next;
+                continue;
+            }
+            Element::Class(name, next) => {
+                if !matched {
+                    match class_test(name) {
+                        Some(test) => matched = test(c) || test(fold(c)),
+                        None => return Bracket::Invalid,
+                    }
+                }
+                i = next;
+                continue;
+            }
+            Element::Invalid => return Bracket::Invalid,
+        };
+
+        // A `-` before the closing `]` is an ordinary byte
+        if pattern.get(next) == Some(&b'-') && pattern.get(next + 1) != Some(&b']') {
+            if next + 1 == pattern.len() {
+                return Bracket::Invalid;
+            }
+            match element(pattern, next + 1, flags, true) {
+                Element::Byte(hi, after) => {
+                    matched |= (fold(lo)..=fold(hi)).contains(&fold(c));
+                    i = after;
+                }
+                _ => return Bracket::Invalid,
+            }
+        } else {
+            matched |= fold(lo) == fold(c);
+            i = next;
+        }
+    }
+}
+
+/// One element of a bracket expression
+enum Element<'p> {
+    /// A byte and the index after it
+    Byte(u8, usize),
+    /// A `[:name:]` class and the index after it
+    Class(&'p [u8], usize),
+    /// The byte in `[=c=]` and the index after it
+    Equivalent(u8, usize),
+    /// An unclosed `[.` or a `[.ab.]` naming more than one byte
+    Invalid,
+}
+
+/// The bracket expression element starting at `pattern[i]`.
+///
+/// An unclosed `[:` or `[=` is just a `[`. The end of a range may be
+/// `[.c.]`, but not a class or `[=c=]`.
+fn element(pattern: &[u8], i: usize, flags: u32, range_end: bool) -> Element<'_> {
+    let c = pattern[i];
+    let kinds: &[u8] = if range_end { b"." } else { b":=." };
+    if let Some(&kind) = pattern.get(i + 1).filter(|k| c == b'[' && kinds.contains(k)) {
+        let rest = &pattern[i + 2..];
+        match rest.windows(2).position(|w| w[0] == kind && w[1] == b']') {
+            Some(len) if kind == b':' => return Element::Class(&rest[..len], i + len + 4),
+            Some(1) if kind == b'=' => return Element::Equivalent(rest[0], i + 5),
+            Some(1) => return Element::Byte(rest[0], i + 5),
+            Some(_) => return Element::Invalid,
+            None if kind == b'.' => return Element::Invalid,
+            None => {}
+        }
+    }
+    match pattern.get(i + 1) {
+        Some(&escaped) if c == b'\\' && flags & NOESCAPE == 0 => Element::Byte(escaped, i + 2),
+        _ => Element::Byte(c, i + 1),
+    }
+}
+
+/// Whether `pattern` has any unescaped `*`, `?` or `[`, so that it needs
+/// matching rather than comparing
+pub fn is_pattern(pattern: &[u8]) -> bool {
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            b'*' | b'?' | b'[' => return true,
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+/// The existing paths matching `pattern`, sorted.
+///
+/// Each `/`-separated part of the pattern is matched against the entries of
+/// one directory with `PATHNAME | PERIOD`, as the shell does, so `*` skips
+/// hidden files, and never matches `.` or `..`. A trailing `/` only
+/// matches directories. Returns nothing if no path matches.
+#[cfg(feature = "alloc")]
+pub fn glob(pattern: &[u8]) -> Vec<Vec<u8>> {
+    let mut found = Vec::new();
+    let slashes = pattern.iter().take_while(|&&c| c == b'/').count();
+    if slashes < pattern.len() {
+        expand(&pattern[..slashes], &pattern[slashes..], &mut found);
+    }
+    found.sort();
+    found
+}
+
+/// Add the paths matching `rest` under directory `prefix` (empty for the
+/// current directory, otherwise ending in `/`) to `found`
+#[cfg(feature = "alloc")]
+fn expand(prefix: &[u8], rest: &[u8], found: &mut Vec<Vec<u8>>) {
+    // The first part, the slashes after it, kept as written, and the rest
+    let end = rest.iter().position(|&c| c == b'/').unwrap_or(rest.len());
+    let slashes = rest[end..].iter().take_while(|&&c| c == b'/').count();
+    let (part, sep, after) = (&rest[..end], &rest[end..end + slashes], &rest[end + slashes..]);
+
+    let mut visit = |name: &[u8]| {
+        let mut path = Vec::with_capacity(prefix.len() + name.len() + sep.len());
+        path.extend_from_slice(prefix);
+        path.extend_from_slice(name);
+        if sep.is_empty() {
+            if exists(&path, false) {
+                found.push(path);
+            }
+            return;
+        }
+        path.extend_from_slice(sep);
+        if after.is_empty() {
+            if exists(&path, true) {
+                found.push(path);
+            }
+        } else {
+            expand(&path, after, found);
+        }
+    };
+
+    if !is_pattern(part) {
+        // No need to read the directory: take the part as is, unescaped
+        let mut name = Vec::with_capacity(part.len());
+        let mut i = 0;
+        while i < part.len() {
+            if part[i] == b'\\' && i + 1 < part.len() {
+                i += 1;
+            }
+            name.push(part[i]);
+            i += 1;
+        }
+        visit(&name);
+        return;
+    }
+
+    let dir = io::opendir(if prefix.is_empty() { b"." } else { prefix });
+    if dir.is_null() {
+        return;
+    }
+    loop {
+        let entry = io::readdir(dir);
+        if entry.is_null() {
+            break;
+        }
+        let name = unsafe { io::cstr_to_slice((*entry).d_name.as_ptr() as *const u8) };
+        if name != b"." && name != b".." && fnmatch(part, name, PATHNAME | PERIOD) {
+            visit(name);
+        }
+    }
+    io::closedir(dir);
+}
+
+/// Whether `path` exists, following symlinks if it must be a directory
+#[cfg(feature = "alloc")]
+fn exists(path: &[u8], dir: bool) -> bool {
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    if dir {
+        io::stat(path, &mut st) == 0 && st.st_mode & libc::S_IFMT == libc::S_IFDIR
+    } else {
+        io::lstat(path, &mut st) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every string of up to `max` bytes from `alphabet`
+    fn strings(alphabet: &[u8], max: usize) -> Vec<Vec<u8>> {
+        let mut all = vec![Vec::new()];
+        let mut last = vec![Vec::new()];
+        for _ in 0..max {
+            last = last
+                .iter()
+                .flat_map(|s: &Vec<u8>| alphabet.iter().map(move |&c| [s.as_slice(), &[c]].concat()))
+                .collect();
+            all.extend(last.iter().cloned());
+        }
+        all
+    }
+
+    fn libc_fnmatch(pattern: &[u8], name: &[u8], flags: u32) -> bool {
+        let pattern = std::ffi::CString::new(pattern).unwrap();
+        let name = std::ffi::CString::new(name).unwrap();
+        let flags = [
+            (PATHNAME, libc::FNM_PATHNAME),
+            (NOESCAPE, libc::FNM_NOESCAPE),
+            (PERIOD, libc::FNM_PERIOD),
+            (CASEFOLD, libc::FNM_CASEFOLD),
+        ]
+        .iter()
+        .filter(|&&(ours, _)| flags & ours != 0)
+        .fold(0, |all, &(_, theirs)| all | theirs);
+        unsafe { libc::fnmatch(pattern.as_ptr(), name.as_ptr(), flags) == 0 }
+    }
+
+    /// Every combination of the flags
+    fn flag_sets() -> impl Iterator<Item = u32> {
+        let all = [PATHNAME, NOESCAPE, PERIOD, CASEFOLD];
+        (0..1 << all.len()).map(move |bits| (0..all.len()).filter(|i| bits & 1 << i != 0).fold(0, |flags, i| flags | all[i]))
+    }
+
+    /// Matches libc for every short pattern and name over bytes that mean
+    /// something to one of the flags, with every combination of flags
+    #[test]
+    fn matches_libc_with_every_flag() {
+        let sets = [
+            (strings(b"a./*?\\[]!", 4), strings(b"aA./\\", 3)),
+            (strings(b"a./*\\", 5), strings(b"a./", 4)),
+        ];
+        let mut mismatches = Vec::new();
+        for flags in flag_sets() {
+            for (patterns, names) in &sets {
+                for (pattern, name) in patterns.iter().flat_map(|p| names.iter().map(move |n| (p, n))) {
+                    if fnmatch(pattern, name, flags) != libc_fnmatch(pattern, name, flags) {
+                        mismatches.push((String::from_utf8_lossy(pattern).into_owned(), String::from_utf8_lossy(name).into_owned(), flags));
+                    }
+                }
+            }
+        }
+        assert!(mismatches.is_empty(), "{} mismatches, first {:?}", mismatches.len(), &mismatches[..mismatches.len().min(20)]);
+    }
+
+    #[test]
+    fn escaped_slashes() {
+        // Only a plain `/` makes a following `.` leading
+        assert!(fnmatch(b"\\/*", b"/.", PATHNAME | PERIOD));
+        assert!(!fnmatch(b"/*", b"/.", PATHNAME | PERIOD));
+        // and an escaped one can't come straight after a `*`
+        assert!(!fnmatch(b"*\\/", b"a/", PATHNAME));
+        assert!(!fnmatch(b"*?\\/", b"a/", PATHNAME));
+        assert!(fnmatch(b"*a\\/", b"a/", PATHNAME));
+    }
+
+    /// `glob` of `pattern` under `dir`, with `dir` taken off the results
+    #[cfg(feature = "alloc")]
+    fn glob_in(dir: &std::path::Path, pattern: &str) -> Vec<String> {
+        let prefix = format!("{}/", dir.display());
+        glob(format!("{}{}", prefix, pattern).as_bytes())
+            .into_iter()
+            .map(|path| String::from_utf8(path).unwrap().strip_prefix(&prefix).unwrap().to_string())
+            .collect()
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn glob_sorts_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["c.txt", "a.txt", "B.txt", "b.txt", "d.log"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(glob_in(dir.path(), "*.txt"), ["B.txt", "a.txt", "b.txt", "c.txt"]);
+        assert_eq!(glob_in(dir.path(), "[ab].*"), ["a.txt", "b.txt"]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn glob_skips_hidden_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [".hidden", "shown", ".also"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(glob_in(dir.path(), "*"), ["shown"]);
+        assert_eq!(glob_in(dir.path(), "?*"), ["shown"]);
+        // Only a pattern starting with `.` finds them, and never `.` or `..`
+        assert_eq!(glob_in(dir.path(), ".*"), [".also", ".hidden"]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn glob_matches_each_component() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in ["a/x1", "a/y1", "b/x2", ".c/x3"] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        std::fs::write(dir.path().join("file"), "").unwrap();
+
+        assert_eq!(glob_in(dir.path(), "*/x*"), ["a/x1", "b/x2"]);
+        assert_eq!(glob_in(dir.path(), "a/*"), ["a/x1", "a/y1"]);
+        assert_eq!(glob_in(dir.path(), "*//x?"), ["a//x1", "b//x2"]);
+        // A trailing `/` keeps only directories
+        assert_eq!(glob_in(dir.path(), "*/"), ["a/", "b/"]);
+        assert_eq!(glob_in(dir.path(), "\\a/\\x1"), ["a/x1"]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn glob_without_matches_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "").unwrap();
+        assert!(glob_in(dir.path(), "*.txt").is_empty());
+        assert!(glob_in(dir.path(), "missing").is_empty());
+        assert!(glob_in(dir.path(), "a/*").is_empty());
+        assert!(glob_in(dir.path(), "nodir/*").is_empty());
+        assert!(glob(b"").is_empty());
+    }
+}
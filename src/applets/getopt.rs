@@ -0,0 +1,442 @@
+//! Shared option parser for applets
+//!
+//! A small `getopt_long` work-alike driven by a spec string, so every applet
+//! handles bundling, `-n5` / `-n 5`, `--`, long options and options after
+//! operands the same way.
+//!
+//! # Spec syntax
+//!
+//! Each option is an optional short letter, followed by zero or more long
+//! names in parentheses, followed by `:` (required argument) or `::`
+//! (optional argument, attached only: `-n5`, `--lines=5`):
+//!
+//! ```text
+//! "vcn(line-number)e(regexp):(color)::"
+//! ```
+//!
+//! An option is identified by its short letter, or by its first long name if
+//! it has no short letter. The spec may start with these modifiers:
+//!
+//! - `+` stop at the first operand; everything after it is an operand
+//!   (for applets that run a command, like `nice` or `xargs`)
+//! - `#` yield `-NUM` arguments as [`Arg::Number`] instead of rejecting them
+//!   (`head -5`, `kill -9`)
+//!
+//! Long options may be abbreviated to any unambiguous prefix.
+//!
+//! The parser borrows the applet's `argv` parameter, so the arguments it
+//! returns can't outlive the applet's call: `embed` frees the `argv` it builds
+//! once the applet returns.
+
+use crate::io;
+use super::{get_arg, usage};
+
+/// One parsed command-line item
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Arg<'a> {
+    /// An option and its argument, if any
+    Opt(&'static [u8], Option<&'a [u8]>),
+    /// A non-option argument
+    Operand(&'a [u8]),
+    /// A `-NUM` argument, including the dash (only with the `#` modifier)
+    Number(&'a [u8]),
+    /// An invalid option or missing argument; a diagnostic and the short
+    /// usage have been printed
+    Error,
+}
+
+/// Whether an option takes an argument
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HasArg {
+    No,
+    Required,
+    Optional,
+}
+
+/// Option parser over an applet's `argv`
+#[derive(Clone)]
+pub struct Getopt<'a> {
+    argc: i32,
+    argv: &'a *const *const u8,
+    spec: &'static [u8],
+    applet: &'a [u8],
+    /// Next `argv` index to look at
+    idx: i32,
+    /// Position inside a bundle of short options (`-abc`), 0 if none
+    pos: usize,
+    /// Set after `--`, or after the first operand in `+` mode
+    operands_only: bool,
+    stop_at_operand: bool,
+    numbers: bool,
+    quiet: bool,
+}
+
+impl<'a> Getopt<'a> {
+    /// Start parsing `argv[1..argc]` against `spec`
+    pub fn new(argc: i32, argv: &'a *const *const u8, spec: &'static [u8]) -> Self {
+        let mut spec = spec;
+        let mut stop_at_operand = false;
+        let mut numbers = false;
+        while let Some(&c) = spec.first() {
+            match c {
+                b'+' => stop_at_operand = true,
+                b'#' => numbers = true,
+                _ => break,
+            }
+            spec = &spec[1..];
+        }
+
+        let mut opts = Getopt {
+            argc,
+            argv,
+            spec,
+            applet: b"armybox",
+            idx: 1,
+            pos: 0,
+            operands_only: false,
+            stop_at_operand,
+            numbers,
+            quiet: false,
+        };
+        if let Some(a) = opts.arg(0) {
+            opts.applet = match a.iter().rposition(|&c| c == b'/') {
+                Some(p) => &a[p + 1..],
+                None => a,
+            };
+        }
+        opts
+    }
+
+    /// Parser for an applet that takes no options.
+    ///
+    /// Returns `None`, after printing a diagnostic, if an option was given.
+    pub fn no_options(argc: i32, argv: &'a *const *const u8) -> Option<Self> {
+        let mut opts = Getopt::new(argc, argv, b"");
+        if opts.any(|arg| arg == Arg::Error) {
+            return None;
+        }
+        Some(opts)
+    }
+
+    /// Name the applet was invoked as, for diagnostics
+    pub fn applet(&self) -> &'a [u8] {
+        self.applet
+    }
+
+    /// Iterate over the operands from the start of `argv`, skipping options.
+    ///
+    /// Diagnostics are not repeated; call this after the options have been
+    /// processed.
+    pub fn operands(&self) -> impl Iterator<Item = &'a [u8]> + use<'a> {
+        let mut p = self.clone();
+        p.idx = 1;
+        p.pos = 0;
+        p.operands_only = false;
+        p.quiet = true;
+        core::iter::from_fn(move || loop {
+            match p.next()? {
+                Arg::Operand(o) => return Some(o),
+                Arg::Error => return None,
+                _ => {}
+            }
+        })
+    }
+
+    /// Number of operands
+    pub fn operand_count(&self) -> usize {
+        self.operands().count()
+    }
+
+    /// The `n`th operand
+    pub fn operand(&self, n: usize) -> Option<&'a [u8]> {
+        self.operands().nth(n)
+    }
+
+    /// In `+` mode, the rest of `argv` from the first operand on: the
+    /// command an applet like `nice` runs, as a NULL-terminated array for
+    /// [`exec`](super::exec). `None` if there are no operands.
+    pub fn command(&self) -> Option<*const *const u8> {
+        let mut p = self.clone();
+        p.idx = 1;
+        p.pos = 0;
+        p.operands_only = false;
+        p.quiet = true;
+        loop {
+            match p.next()? {
+                Arg::Operand(_) => return Some(unsafe { self.argv.add(p.idx as usize - 1) }),
+                Arg::Error => return None,
+                _ => {}
+            }
+        }
+    }
+
+    fn arg(&self, idx: i32) -> Option<&'a [u8]> {
+        if idx >= self.argc {
+            return None;
+        }
+        unsafe { get_arg(*self.argv, idx) }
+    }
+
+    fn next_arg(&mut self) -> Option<Arg<'a>> {
+        let arg = self.arg(self.idx)?;
+
+        if self.pos > 0 {
+            return Some(self.short(arg));
+        }
+
+        self.idx += 1;
+
+        if self.operands_only || arg.len() < 2 || arg[0] != b'-' {
+            if self.stop_at_operand {
+                self.operands_only = true;
+            }
+            return Some(Arg::Operand(arg));
+        }
+
+        if arg == b"--" {
+            self.operands_only = true;
+            return self.next_arg();
+        }
+
+        if arg[1] == b'-' {
+            return Some(self.long(arg));
+        }
+
+        if self.numbers && arg[1..].iter().all(|c| c.is_ascii_digit()) {
+            return Some(Arg::Number(arg));
+        }
+
+        // Start of a short option bundle; `idx` already points past it
+        self.idx -= 1;
+        self.pos = 1;
+        Some(self.short(arg))
+    }
+
+    /// Parse the short option at `pos` in `arg` (`argv[idx]`)
+    fn short(&mut self, arg: &'a [u8]) -> Arg<'a> {
+        let c = arg[self.pos];
+        self.pos += 1;
+        let at_end = self.pos >= arg.len();
+
+        let (key, has_arg) = match find_short(self.spec, c) {
+            Some(found) => found,
+            None => {
+                self.finish_bundle(at_end);
+                self.short_error(b"invalid option -- '", c);
+                return Arg::Error;
+            }
+        };
+
+        match has_arg {
+            HasArg::No => {
+                self.finish_bundle(at_end);
+                Arg::Opt(key, None)
+            }
+            HasArg::Optional => {
+                let value = if at_end { None } else { Some(&arg[self.pos..]) };
+                self.finish_bundle(true);
+                Arg::Opt(key, value)
+            }
+            HasArg::Required => {
+                let attached = if at_end { None } else { Some(&arg[self.pos..]) };
+                self.finish_bundle(true);
+                if attached.is_some() {
+                    return Arg::Opt(key, attached);
+                }
+                match self.arg(self.idx) {
+                    Some(value) => {
+                        self.idx += 1;
+                        Arg::Opt(key, Some(value))
+                    }
+                    None => {
+                        self.short_error(b"option requires an argument -- '", c);
+                        Arg::Error
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move on to the next `argv` entry once a bundle is used up
+    fn finish_bundle(&mut self, done: bool) {
+        if done {
+            self.pos = 0;
+            self.idx += 1;
+        }
+    }
+
+    /// Parse `--name`, `--name=value` or `--name value`
+    fn long(&mut self, arg: &'a [u8]) -> Arg<'a> {
+        let body = &arg[2..];
+        let (name, value) = match body.iter().position(|&c| c == b'=') {
+            Some(eq) => (&body[..eq], Some(&body[eq + 1..])),
+            None => (body, None),
+        };
+
+        let (key, has_arg) = match find_long(self.spec, name) {
+            LongMatch::Found(key, has_arg) => (key, has_arg),
+            LongMatch::Ambiguous => {
+                self.long_error(b"option '--", name, b"' is ambiguous");
+                return Arg::Error;
+            }
+            LongMatch::None => {
+                self.long_error(b"invalid option '--", name, b"'");
+                return Arg::Error;
+            }
+        };
+
+        match has_arg {
+            HasArg::No => {
+                if value.is_some() {
+                    self.long_error(b"option '--", name, b"' doesn't allow an argument");
+                    return Arg::Error;
+                }
+                Arg::Opt(key, None)
+            }
+            HasArg::Optional => Arg::Opt(key, value),
+            HasArg::Required => {
+                if value.is_some() {
+                    return Arg::Opt(key, value);
+                }
+                match self.arg(self.idx) {
+                    Some(v) => {
+                        self.idx += 1;
+                        Arg::Opt(key, Some(v))
+                    }
+                    None => {
+                        self.long_error(b"option '--", name, b"' requires an argument");
+                        Arg::Error
+                    }
+                }
+            }
+        }
+    }
+
+    fn short_error(&self, msg: &[u8], c: u8) {
+        if self.quiet {
+            return;
+        }
+        io::write_all(2, self.applet);
+        io::write_str(2, b": ");
+        io::write_all(2, msg);
+        io::write_all(2, &[c]);
+        io::write_str(2, b"'\n");
+        usage::show_short(self.applet);
+    }
+
+    fn long_error(&self, pre: &[u8], name: &[u8], post: &[u8]) {
+        if self.quiet {
+            return;
+        }
+        io::write_all(2, self.applet);
+        io::write_str(2, b": ");
+        io::write_all(2, pre);
+        io::write_all(2, name);
+        io::write_all(2, post);
+        io::write_str(2, b"\n");
+        usage::show_short(self.applet);
+    }
+}
+
+impl<'a> Iterator for Getopt<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Arg<'a>> {
+        self.next_arg()
+    }
+}
+
+/// One entry of a spec string
+struct Entry {
+    short: Option<usize>,
+    /// Byte range of the `(...)` groups
+    longs: (usize, usize),
+    has_arg: HasArg,
+}
+
+impl Entry {
+    fn key(&self, spec: &'static [u8]) -> &'static [u8] {
+        match self.short {
+            Some(i) => &spec[i..i + 1],
+            None => self.long_names(spec).next().unwrap_or(b""),
+        }
+    }
+
+    fn long_names(&self, spec: &'static [u8]) -> impl Iterator<Item = &'static [u8]> {
+        spec[self.longs.0..self.longs.1]
+            .split(|&c| c == b'(' || c == b')')
+            .filter(|s| !s.is_empty())
+    }
+}
+
+/// Walk the entries of a spec string
+fn entries(spec: &'static [u8]) -> impl Iterator<Item = Entry> {
+    let mut i = 0;
+    core::iter::from_fn(move || {
+        if i >= spec.len() {
+            return None;
+        }
+        let short = if spec[i] != b'(' && spec[i] != b':' {
+            i += 1;
+            Some(i - 1)
+        } else {
+            None
+        };
+        let start = i;
+        while i < spec.len() && spec[i] == b'(' {
+            while i < spec.len() && spec[i] != b')' {
+                i += 1;
+            }
+            i += 1;
+        }
+        let longs = (start, i.min(spec.len()));
+        let mut has_arg = HasArg::No;
+        if i < spec.len() && spec[i] == b':' {
+            i += 1;
+            has_arg = HasArg::Required;
+            if i < spec.len() && spec[i] == b':' {
+                i += 1;
+                has_arg = HasArg::Optional;
+            }
+        }
+        Some(Entry { short, longs, has_arg })
+    })
+}
+
+fn find_short(spec: &'static [u8], c: u8) -> Option<(&'static [u8], HasArg)> {
+    entries(spec)
+        .find(|e| e.short.map(|i| spec[i]) == Some(c))
+        .map(|e| (e.key(spec), e.has_arg))
+}
+
+enum LongMatch {
+    Found(&'static [u8], HasArg),
+    Ambiguous,
+    None,
+}
+
+fn find_long(spec: &'static [u8], name: &[u8]) -> LongMatch {
+    let mut prefix_match: Option<(&'static [u8], HasArg)> = None;
+    let mut ambiguous = false;
+
+    for e in entries(spec) {
+        for long in e.long_names(spec) {
+            if long == name {
+                return LongMatch::Found(e.key(spec), e.has_arg);
+            }
+            if !name.is_empty() && long.starts_with(name) {
+                match prefix_match {
+                    Some((key, _)) if key != e.key(spec) => ambiguous = true,
+                    Some(_) => {}
+                    None => prefix_match = Some((e.key(spec), e.has_arg)),
+                }
+            }
+        }
+    }
+
+    match prefix_match {
+        _ if ambiguous => LongMatch::Ambiguous,
+        Some((key, has_arg)) => LongMatch::Found(key, has_arg),
+        None => LongMatch::None,
+    }
+}
@@ -2,12 +2,39 @@
 
 use crate::io;
 use super::get_arg;
+use super::utmp;
+use super::{Arg, Getopt};
 
 pub fn init(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(2, b"init: stub\n"); 0 }
 pub fn telinit(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn runlevel(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(1, b"N 3\n"); 0 }
 pub fn getty(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn sulogin(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn oneit(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn switch_root(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn watchdog(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
+
+/// runlevel: print the previous and current run levels from utmp
+pub fn runlevel(argc: i32, argv: *const *const u8) -> i32 {
+    let mut opts = Getopt::new(argc, &argv, b"");
+    if opts.by_ref().any(|arg| arg == Arg::Error) {
+        return 1;
+    }
+    let path = opts.operand(0);
+    let file = match path {
+        Some(path) => utmp::File::open(path),
+        None => utmp::File::open_log(utmp::Log::Utmp),
+    };
+    let entry = file.and_then(|file| file.iter_rev().find(|e| e.kind() == utmp::RUN_LVL));
+    match entry {
+        Some(entry) => {
+            let (level, last) = ((entry.pid() & 0xff) as u8, (entry.pid() >> 8 & 0xff) as u8);
+            let last = if last == 0 { b'N' } else { last };
+            io::write_all(1, &[last, b' ', level, b'\n']);
+            0
+        }
+        None => {
+            io::write_str(1, b"unknown\n");
+            1
+        }
+    }
+}
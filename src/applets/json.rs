@@ -0,0 +1,210 @@
+//! JSON output
+//!
+//! The `--json` form of the inspection applets (`ps`, `df`, `free`, `ip`,
+//! `stat`, `mount`, `lsmod`, `blkid`, `uptime`) is written with `Json`, a
+//! streaming writer over the buffered stdout. Nothing is collected first: a
+//! process table comes out one object at a time, and the writer remembers
+//! only whether each open object or array already has an item, so it knows
+//! where commas go.
+//!
+//! - strings are bytes: valid UTF-8 is copied, `"`, `\` and control
+//!   characters are escaped, and each byte that isn't UTF-8 becomes U+FFFD,
+//!   so a strange file name or command line still gives valid JSON
+//! - numbers are integers, or fixed-point with `fixed` for load averages
+//!   and percentages; sizes are in bytes unless a key says otherwise
+//! - the output is compact, one document per line
+//!
+//! Callers keep keys and nesting in step themselves:
+//!
+//! ```text
+//! json.begin_object();
+//! json.key(b"pid").signed(1);
+//! json.end_object();
+//! json.finish();
+//! ```
+
+use crate::io;
+use crate::sys;
+use super::utf8;
+
+/// A JSON document being written to stdout
+pub struct Json {
+    out: io::Stdout,
+    /// One bit per open object or array, set once it has an item
+    items: u64,
+    depth: u32,
+    /// Set after a key, so its value takes no comma
+    after_key: bool,
+}
+
+impl Default for Json {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Json {
+    pub fn new() -> Self {
+        Json { out: io::stdout(), items: 0, depth: 0, after_key: false }
+    }
+
+    /// Writes the comma before an item, unless it is the first in its
+    /// container or a key's value
+    fn item(&mut self) {
+        if self.after_key {
+            self.after_key = false;
+            return;
+        }
+        if self.depth > 0 {
+            let bit = 1 << ((self.depth - 1) % 64);
+            if self.items & bit != 0 {
+                self.out.write_byte(b',');
+            }
+            self.items |= bit;
+        }
+    }
+
+    fn open(&mut self, c: u8) {
+        self.item();
+        self.out.write_byte(c);
+        self.depth += 1;
+        self.items &= !(1 << ((self.depth - 1) % 64));
+    }
+
+    fn close(&mut self, c: u8) {
+        self.out.write_byte(c);
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn begin_object(&mut self) -> &mut Self {
+        self.open(b'{');
+        self
+    }
+
+    pub fn end_object(&mut self) -> &mut Self {
+        self.close(b'}');
+        self
+    }
+
+    pub fn begin_array(&mut self) -> &mut Self {
+        self.open(b'[');
+        self
+    }
+
+    pub fn end_array(&mut self) -> &mut Self {
+        self.close(b']');
+        self
+    }
+
+    /// Writes an object key; the next call writes its value
+    pub fn key(&mut self, key: &[u8]) -> &mut Self {
+        self.item();
+        self.quoted(key);
+        self.out.write_byte(b':');
+        self.after_key = true;
+        self
+    }
+
+    pub fn string(&mut self, s: &[u8]) -> &mut Self {
+        self.item();
+        self.quoted(s);
+        self
+    }
+
+    /// A string, or `null` for `None`
+    pub fn optional(&mut self, s: Option<&[u8]>) -> &mut Self {
+        match s {
+            Some(s) => self.string(s),
+            None => self.null(),
+        }
+    }
+
+    pub fn number(&mut self, n: u64) -> &mut Self {
+        self.item();
+        self.out.write_num(n);
+        self
+    }
+
+    pub fn signed(&mut self, n: i64) -> &mut Self {
+        self.item();
+        self.out.write_signed(n);
+        self
+    }
+
+    /// Writes `n` with `places` decimal places: `fixed(45, 2)` is `0.45`
+    pub fn fixed(&mut self, n: u64, places: u32) -> &mut Self {
+        self.item();
+        let scale = 10u64.pow(places);
+        self.out.write_num(n / scale);
+        if places > 0 {
+            self.out.write_byte(b'.');
+            let mut buf = [0u8; 20];
+            let digits = sys::format_u64(n % scale, &mut buf);
+            for _ in digits.len()..places as usize {
+                self.out.write_byte(b'0');
+            }
+            self.out.write_all(digits);
+        }
+        self
+    }
+
+    pub fn bool(&mut self, b: bool) -> &mut Self {
+        self.item();
+        self.out.write_str(if b { b"true" } else { b"false" });
+        self
+    }
+
+    pub fn null(&mut self) -> &mut Self {
+        self.item();
+        self.out.write_str(b"null");
+        self
+    }
+
+    /// Ends the document with a newline and writes it out
+    pub fn finish(&mut self) {
+        self.out.write_byte(b'\n');
+        self.out.flush();
+    }
+
+    fn quoted(&mut self, s: &[u8]) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        self.out.write_byte(b'"');
+        let mut rest = s;
+        while !rest.is_empty() {
+            // Copy the longest run that needs no escaping at once
+            let run = rest
+                .iter()
+                .position(|&c| c < 0x20 || c == b'"' || c == b'\\' || c >= 0x80)
+                .unwrap_or(rest.len());
+            self.out.write_all(&rest[..run]);
+            rest = &rest[run..];
+            let c = match rest.first() {
+                Some(&c) => c,
+                None => break,
+            };
+            if c >= 0x80 {
+                match utf8::decode(rest) {
+                    (Some(_), len) => {
+                        self.out.write_all(&rest[..len]);
+                        rest = &rest[len..];
+                    }
+                    (None, _) => {
+                        self.out.write_str("\u{fffd}".as_bytes());
+                        rest = &rest[1..];
+                    }
+                }
+                continue;
+            }
+            match c {
+                b'"' => self.out.write_str(b"\\\""),
+                b'\\' => self.out.write_str(b"\\\\"),
+                b'\n' => self.out.write_str(b"\\n"),
+                b'\r' => self.out.write_str(b"\\r"),
+                b'\t' => self.out.write_str(b"\\t"),
+                _ => self.out.write_all(&[b'\\', b'u', b'0', b'0', HEX[(c >> 4) as usize], HEX[(c & 15) as usize]]),
+            }
+            rest = &rest[1..];
+        }
+        self.out.write_byte(b'"');
+    }
+}
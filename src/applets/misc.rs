@@ -2,13 +2,30 @@
 
 use crate::io;
 use crate::sys;
-use super::{get_arg, has_opt};
+use super::datetime;
+use super::regex::{Captures, Regex};
+use super::terminal;
+use super::{get_arg, usage, Arg, Getopt, EXIT_TROUBLE};
 
 pub fn r#true(_argc: i32, _argv: *const *const u8) -> i32 { 0 }
 pub fn r#false(_argc: i32, _argv: *const *const u8) -> i32 { 1 }
 pub fn colon(_argc: i32, _argv: *const *const u8) -> i32 { 0 }
 
 pub fn test(argc: i32, argv: *const *const u8) -> i32 {
+    evaluate(b"test", argc, argv)
+}
+
+pub fn bracket(argc: i32, argv: *const *const u8) -> i32 {
+    if argc < 2 || unsafe { get_arg(argv, argc - 1) } != Some(b"]".as_slice()) {
+        sys::error(b"[", b"missing ]");
+        return EXIT_TROUBLE;
+    }
+    evaluate(b"[", argc - 1, argv)
+}
+
+/// `test` and `[` with the closing `]` removed: 0 for true, 1 for false and
+/// `EXIT_TROUBLE` for a malformed expression
+fn evaluate(name: &[u8], argc: i32, argv: *const *const u8) -> i32 {
     if argc < 2 { return 1; }
 
     let arg1 = unsafe { get_arg(argv, 1).unwrap() };
@@ -32,61 +49,62 @@ pub fn test(argc: i32, argv: *const *const u8) -> i32 {
             b"-n" => if !path.is_empty() { 0 } else { 1 },
             b"-z" => if path.is_empty() { 0 } else { 1 },
             b"-L" | b"-h" => if stat_ok && (st.st_mode & libc::S_IFMT) == libc::S_IFLNK { 0 } else { 1 },
-            _ => 1,
+            b"!" => if path.is_empty() { 0 } else { 1 },
+            _ => {
+                sys::error_at(name, op, b"unknown operand");
+                EXIT_TROUBLE
+            }
         };
     }
 
     // Binary tests
     if argc == 4 {
+        let (first, op, last) = unsafe { (get_arg(argv, 1).unwrap(), get_arg(argv, 2).unwrap(), get_arg(argv, 3).unwrap()) };
+        if op == b":" || first == b"match" {
+            let (text, pattern) = if op == b":" { (first, last) } else { (op, last) };
+            return expr_match(text, pattern);
+        }
+
         let left = arg1;
         let op = unsafe { get_arg(argv, 2).unwrap() };
         let right = unsafe { get_arg(argv, 3).unwrap() };
 
-        return match op {
-            b"=" | b"==" => if left == right { 0 } else { 1 },
-            b"!=" => if left != right { 0 } else { 1 },
-            b"-eq" => {
-                let l = sys::parse_i64(left).unwrap_or(0);
-                let r = sys::parse_i64(right).unwrap_or(0);
-                if l == r { 0 } else { 1 }
-            }
-            b"-ne" => {
-                let l = sys::parse_i64(left).unwrap_or(0);
-                let r = sys::parse_i64(right).unwrap_or(0);
-                if l != r { 0 } else { 1 }
-            }
-            b"-lt" => {
-                let l = sys::parse_i64(left).unwrap_or(0);
-                let r = sys::parse_i64(right).unwrap_or(0);
-                if l < r { 0 } else { 1 }
-            }
-            b"-gt" => {
-                let l = sys::parse_i64(left).unwrap_or(0);
-                let r = sys::parse_i64(right).unwrap_or(0);
-                if l > r { 0 } else { 1 }
-            }
-            b"-le" => {
-                let l = sys::parse_i64(left).unwrap_or(0);
-                let r = sys::parse_i64(right).unwrap_or(0);
-                if l <= r { 0 } else { 1 }
+        let ordering = match op {
+            b"=" | b"==" => return if left == right { 0 } else { 1 },
+            b"!=" => return if left != right { 0 } else { 1 },
+            b"-eq" | b"-ne" | b"-lt" | b"-gt" | b"-le" | b"-ge" => {
+                let integer = |s: &[u8]| {
+                    let n = sys::parse_i64(s);
+                    if n.is_none() {
+                        sys::error_at(name, s, b"integer expression expected");
+                    }
+                    n
+                };
+                match (integer(left), integer(right)) {
+                    (Some(l), Some(r)) => l.cmp(&r),
+                    _ => return EXIT_TROUBLE,
+                }
             }
-            b"-ge" => {
-                let l = sys::parse_i64(left).unwrap_or(0);
-                let r = sys::parse_i64(right).unwrap_or(0);
-                if l >= r { 0 } else { 1 }
+            _ => {
+                sys::error_at(name, op, b"unknown operand");
+                return EXIT_TROUBLE;
             }
-            _ => 1,
         };
+        let holds = match op {
+            b"-eq" => ordering.is_eq(),
+            b"-ne" => ordering.is_ne(),
+            b"-lt" => ordering.is_lt(),
+            b"-gt" => ordering.is_gt(),
+            b"-le" => ordering.is_le(),
+            _ => ordering.is_ge(),
+        };
+        return if holds { 0 } else { 1 };
     }
 
     // Single arg - true if non-empty
     if !arg1.is_empty() { 0 } else { 1 }
 }
 
-pub fn bracket(argc: i32, argv: *const *const u8) -> i32 {
-    test(argc, argv)
-}
-
 pub fn clear(_argc: i32, _argv: *const *const u8) -> i32 {
     io::write_str(1, b"\x1b[H\x1b[2J");
     0
@@ -98,9 +116,14 @@ pub fn reset(_argc: i32, _argv: *const *const u8) -> i32 {
 }
 
 pub fn which(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 { return 1; }
-
-    let cmd = unsafe { get_arg(argv, 1).unwrap() };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let cmd = match opts.operand(0) {
+        Some(cmd) => cmd,
+        None => return 1,
+    };
     let path_env = unsafe { libc::getenv(b"PATH\0".as_ptr() as *const i8) };
 
     if path_env.is_null() { return 1; }
@@ -124,7 +147,12 @@ pub fn which(argc: i32, argv: *const *const u8) -> i32 {
 }
 
 pub fn expr(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 { return 2; }
+    // Operands like `-` and `-5` are part of the expression, so argv is
+    // taken as is rather than going through Getopt
+    if argc < 2 {
+        usage::error(b"expr", b"missing operand");
+        return EXIT_TROUBLE;
+    }
 
     if argc == 2 {
         let arg = unsafe { get_arg(argv, 1).unwrap() };
@@ -134,9 +162,14 @@ pub fn expr(argc: i32, argv: *const *const u8) -> i32 {
     }
 
     if argc == 4 {
-        let left = sys::parse_i64(unsafe { get_arg(argv, 1).unwrap() }).unwrap_or(0);
-        let op = unsafe { get_arg(argv, 2).unwrap() };
-        let right = sys::parse_i64(unsafe { get_arg(argv, 3).unwrap() }).unwrap_or(0);
+        let (first, op, last) = unsafe { (get_arg(argv, 1).unwrap(), get_arg(argv, 2).unwrap(), get_arg(argv, 3).unwrap()) };
+        if op == b":" || first == b"match" {
+            let (text, pattern) = if op == b":" { (first, last) } else { (op, last) };
+            return expr_match(text, pattern);
+        }
+
+        let left = sys::parse_i64(first).unwrap_or(0);
+        let right = sys::parse_i64(last).unwrap_or(0);
 
         let result = match op {
             b"+" => left + right,
@@ -151,67 +184,83 @@ pub fn expr(argc: i32, argv: *const *const u8) -> i32 {
         io::write_str(1, b"\n");
         return if result == 0 { 1 } else { 0 };
     }
-    2
+    sys::error(b"expr", b"syntax error");
+    EXIT_TROUBLE
+}
+
+/// `STRING : REGEX`: match the BRE against the start of the string and
+/// print the first group, or the length of the match if there is none
+fn expr_match(text: &[u8], pattern: &[u8]) -> i32 {
+    let re = match Regex::new(pattern, 0) {
+        Ok(re) => re,
+        Err(msg) => {
+            sys::error(b"expr", msg);
+            return EXIT_TROUBLE;
+        }
+    };
+    let mut caps = Captures::new();
+    let matched = re.captures_at(text, 0, &mut caps) && caps.get(0).is_some_and(|(start, _)| start == 0);
+
+    if re.groups() > 0 {
+        let group = if matched { caps.get(1) } else { None };
+        let group = group.map_or(b"".as_slice(), |(start, end)| &text[start..end]);
+        io::write_all(1, group);
+        io::write_str(1, b"\n");
+        return if group.is_empty() { 1 } else { 0 };
+    }
+    let len = if matched { caps.get(0).map_or(0, |(start, end)| end - start) } else { 0 };
+    io::write_num(1, len as u64);
+    io::write_str(1, b"\n");
+    if len == 0 { 1 } else { 0 }
 }
 
 pub fn time(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 { return 0; }
+    // Everything from the command name on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+");
+    if opts.any(|arg| arg == Arg::Error) {
+        return 1;
+    }
+    let command = match opts.command() {
+        Some(command) => command,
+        None => return 0,
+    };
 
     let start = unsafe { libc::time(core::ptr::null_mut()) };
+    let status = unsafe { super::exec::spawn(b"time", command) };
 
-    // Fork and exec
-    let pid = unsafe { libc::fork() };
-    if pid == 0 {
-        #[cfg(feature = "alloc")]
-        {
-            use alloc::vec::Vec;
-            use alloc::ffi::CString;
-
-            let mut args: Vec<CString> = Vec::new();
-            for i in 1..argc {
-                if let Some(arg) = unsafe { get_arg(argv, i) } {
-                    let mut v = Vec::with_capacity(arg.len() + 1);
-                    v.extend_from_slice(arg);
-                    v.push(0);
-                    if let Ok(cs) = CString::from_vec_with_nul(v) {
-                        args.push(cs);
-                    }
-                }
-            }
-            let ptrs: Vec<*const i8> = args.iter().map(|s| s.as_ptr()).chain(core::iter::once(core::ptr::null())).collect();
-            unsafe { libc::execvp(ptrs[0], ptrs.as_ptr()) };
-        }
-        unsafe { libc::_exit(127) };
-    } else if pid > 0 {
-        let mut status = 0;
-        unsafe { libc::waitpid(pid, &mut status, 0) };
-
-        let end = unsafe { libc::time(core::ptr::null_mut()) };
-        let elapsed = end - start;
+    let end = unsafe { libc::time(core::ptr::null_mut()) };
+    let elapsed = end - start;
 
-        io::write_str(2, b"\nreal\t");
-        io::write_num(2, elapsed as u64);
-        io::write_str(2, b"s\n");
-    }
-    0
+    io::write_str(2, b"\nreal\t");
+    io::write_num(2, elapsed as u64);
+    io::write_str(2, b"s\n");
+    status
 }
 
 pub fn mesg(argc: i32, argv: *const *const u8) -> i32 {
-    if argc > 1 {
-        if let Some(arg) = unsafe { get_arg(argv, 1) } {
-            let mode = if arg == b"y" { 0o620 } else { 0o600 };
-            let tty = unsafe { libc::ttyname(0) };
-            if !tty.is_null() {
-                unsafe { libc::chmod(tty, mode) };
-            }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if let Some(arg) = opts.operand(0) {
+        let mode = if arg == b"y" { 0o620 } else { 0o600 };
+        let tty = unsafe { libc::ttyname(0) };
+        if !tty.is_null() {
+            unsafe { libc::chmod(tty, mode) };
         }
     }
     0
 }
 
 pub fn getconf(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 { return 1; }
-    let name = unsafe { get_arg(argv, 1).unwrap() };
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let name = match opts.operand(0) {
+        Some(name) => name,
+        None => return 1,
+    };
 
     let val = match name {
         b"PAGE_SIZE" | b"PAGESIZE" => unsafe { libc::sysconf(libc::_SC_PAGESIZE) },
@@ -230,38 +279,42 @@ pub fn getconf(argc: i32, argv: *const *const u8) -> i32 {
 }
 
 pub fn factor(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 { return 1; }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if opts.operand_count() == 0 { return 1; }
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            let mut n = sys::parse_u64(arg).unwrap_or(0);
-            io::write_num(1, n);
-            io::write_str(1, b":");
-
-            let mut d = 2u64;
-            while d * d <= n {
-                while n % d == 0 {
-                    io::write_str(1, b" ");
-                    io::write_num(1, d);
-                    n /= d;
-                }
-                d += 1;
-            }
-            if n > 1 {
+    for arg in opts.operands() {
+        let mut n = sys::parse_u64(arg).unwrap_or(0);
+        io::write_num(1, n);
+        io::write_str(1, b":");
+
+        let mut d = 2u64;
+        while d * d <= n {
+            while n % d == 0 {
                 io::write_str(1, b" ");
-                io::write_num(1, n);
+                io::write_num(1, d);
+                n /= d;
             }
-            io::write_str(1, b"\n");
+            d += 1;
+        }
+        if n > 1 {
+            io::write_str(1, b" ");
+            io::write_num(1, n);
         }
+        io::write_str(1, b"\n");
     }
     0
 }
 
 pub fn base64(argc: i32, argv: *const *const u8) -> i32 {
     let mut decode = false;
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'd') { decode = true; }
+    for arg in Getopt::new(argc, &argv, b"d(decode)") {
+        match arg {
+            Arg::Opt(b"d", _) => decode = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
@@ -328,11 +381,16 @@ pub fn diff(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = ar
 pub fn od(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 
 pub fn hexdump(argc: i32, argv: *const *const u8) -> i32 {
-    let fd = if argc > 1 {
-        if let Some(path) = unsafe { get_arg(argv, argc - 1) } {
-            if path[0] != b'-' { io::open(path, libc::O_RDONLY, 0) } else { 0 }
-        } else { 0 }
-    } else { 0 };
+    // -C is accepted for compatibility; there is only one output format
+    let mut opts = Getopt::new(argc, &argv, b"C");
+    if opts.any(|arg| arg == Arg::Error) {
+        return 1;
+    }
+
+    let fd = match opts.operands().last() {
+        Some(path) if path != b"-" => io::open(path, libc::O_RDONLY, 0),
+        _ => 0,
+    };
 
     let mut buf = [0u8; 16];
     let mut offset = 0u64;
@@ -383,33 +441,39 @@ fn simple_hash(data: &[u8], init: u32, poly: u32) -> u32 {
 }
 
 pub fn md5sum(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                let fd = io::open(path, libc::O_RDONLY, 0);
-                if fd < 0 { continue; }
-
-                let mut hash = 0u32;
-                let mut buf = [0u8; 4096];
-                loop {
-                    let n = io::read(fd, &mut buf);
-                    if n <= 0 { break; }
-                    hash = simple_hash(&buf[..n as usize], hash, 0xEDB88320);
-                }
-                io::close(fd);
-
-                // Print as hex (simplified - not real MD5)
-                let mut hex = [0u8; 16];
-                let s = sys::format_hex(hash as u64, &mut hex);
-                for _ in 0..(8 - s.len()) { io::write_str(1, b"0"); }
-                io::write_all(1, s);
-                io::write_str(1, b"00000000000000000000000000000000  ");
-                io::write_all(1, path);
-                io::write_str(1, b"\n");
-            }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    let mut status = 0;
+    for path in opts.operands() {
+        let fd = io::open(path, libc::O_RDONLY, 0);
+        if fd < 0 {
+            sys::perror(b"md5sum", path);
+            status = 1;
+            continue;
+        }
+
+        let mut hash = 0u32;
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = io::read(fd, &mut buf);
+            if n <= 0 { break; }
+            hash = simple_hash(&buf[..n as usize], hash, 0xEDB88320);
         }
+        io::close(fd);
+
+        // Print as hex (simplified - not real MD5)
+        let mut hex = [0u8; 16];
+        let s = sys::format_hex(hash as u64, &mut hex);
+        for _ in 0..(8 - s.len()) { io::write_str(1, b"0"); }
+        io::write_all(1, s);
+        io::write_str(1, b"00000000000000000000000000000000  ");
+        io::write_all(1, path);
+        io::write_str(1, b"\n");
     }
-    0
+       status
 }
 
 pub fn sha1sum(argc: i32, argv: *const *const u8) -> i32 { md5sum(argc, argv) }
@@ -447,7 +511,76 @@ pub fn count(_argc: i32, _argv: *const *const u8) -> i32 {
 }
 
 pub fn unicode(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn ts(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
+
+/// ts - timestamp each line of input
+pub fn ts(argc: i32, argv: *const *const u8) -> i32 {
+    let mut incremental = false;
+    let mut since_start = false;
+
+    let mut opts = Getopt::new(argc, &argv, b"is");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"i", _) => incremental = true,
+            Arg::Opt(b"s", _) => since_start = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    if opts.operand_count() > 1 {
+        return usage::error(b"ts", b"extra operand");
+    }
+    let relative = incremental || since_start;
+    let format = opts.operand(0).unwrap_or(if relative { b"%H:%M:%S" } else { b"%b %d %H:%M:%S" });
+
+    // %.S, %.s and %.T add microseconds
+    let mut buf = [0u8; 1024];
+    let mut len = 0;
+    let mut i = 0;
+    while i < format.len() {
+        let (piece, used): (&[u8], usize) = match format.get(i..i + 3) {
+            Some(b"%.S") => (b"%S.%6N", 3),
+            Some(b"%.s") => (b"%s.%6N", 3),
+            Some(b"%.T") => (b"%T.%6N", 3),
+            _ => (&format[i..i + 1], 1),
+        };
+        let Some(dest) = buf.get_mut(len..len + piece.len()) else {
+            return usage::error(b"ts", b"format too long");
+        };
+        dest.copy_from_slice(piece);
+        len += piece.len();
+        i += used;
+    }
+    let format = &buf[..len];
+
+    // Elapsed times are shown as times of day in UTC
+    let tz = if relative { datetime::TimeZone::utc() } else { datetime::TimeZone::local() };
+    let out = io::stdout();
+    let start = datetime::now();
+    let mut last = start;
+    let mut reader = io::LineReader::new(0);
+    while let Some(line) = reader.next_line() {
+        let now = datetime::now();
+        let (sec, nsec) = if relative {
+            let since = if incremental { last } else { start };
+            let ns = (now.0 - since.0) * 1_000_000_000 + now.1 as i64 - since.1 as i64;
+            (ns.div_euclid(1_000_000_000), ns.rem_euclid(1_000_000_000) as u32)
+        } else {
+            now
+        };
+        last = now;
+
+        let tm = datetime::Tm { nsec, ..tz.localtime(sec) };
+        datetime::strftime(format, &tm, &mut |s| out.write_all(s));
+        out.write_str(b" ");
+        out.write_all(line.data);
+        if line.terminated {
+            out.write_str(b"\n");
+        }
+        // Each line goes out when it comes in
+        out.flush();
+    }
+    0
+}
 
 pub fn uuidgen(_argc: i32, _argv: *const *const u8) -> i32 {
     let t = unsafe { libc::time(core::ptr::null_mut()) } as u64;
@@ -491,9 +624,17 @@ pub fn uuencode(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _
 pub fn uudecode(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 
 // Additional toybox applets
-pub fn help(_argc: i32, _argv: *const *const u8) -> i32 {
+pub fn help(argc: i32, argv: *const *const u8) -> i32 {
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if let Some(name) = opts.operand(0) {
+        return super::usage::help(name);
+    }
     io::write_str(1, b"armybox - BusyBox/Toybox compatible multi-call binary\n");
     io::write_str(1, b"Usage: armybox [APPLET] [ARGS]\n");
+    io::write_str(1, b"Run 'help APPLET' for an applet's usage\n");
     0
 }
 pub fn memeater(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(2, b"memeater: stub\n"); 0 }
@@ -677,7 +818,7 @@ fn screen_detach(name: Option<&[u8]>) -> i32 {
     let name = match name {
         Some(n) => n,
         None => {
-            io::write_str(2, b"screen: must specify session name to detach\n");
+            sys::error(b"screen", b"must specify session name to detach");
             return 1;
         }
     };
@@ -706,7 +847,7 @@ fn screen_detach(name: Option<&[u8]>) -> i32 {
         }
     }
 
-    io::write_str(2, b"screen: could not detach session\n");
+    sys::error(b"screen", b"could not detach session");
     1
 }
 
@@ -786,7 +927,7 @@ fn screen_reattach(name: Option<&[u8]>) -> i32 {
                 libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0)
             };
             if sock < 0 {
-                io::write_str(2, b"screen: could not create socket\n");
+                sys::error(b"screen", b"could not create socket");
                 return 1;
             }
 
@@ -800,24 +941,15 @@ fn screen_reattach(name: Option<&[u8]>) -> i32 {
 
             if unsafe { libc::connect(sock, &addr as *const _ as *const libc::sockaddr,
                                        core::mem::size_of::<libc::sockaddr_un>() as u32) } < 0 {
-                io::write_str(2, b"screen: could not connect to session\n");
+                sys::error(b"screen", b"could not connect to session");
                 unsafe { libc::close(sock) };
                 return 1;
             }
 
-            // Set terminal to raw mode
-            let mut old_termios: libc::termios = unsafe { core::mem::zeroed() };
-            unsafe { libc::tcgetattr(0, &mut old_termios) };
-
-            let mut raw = old_termios;
-            unsafe { libc::cfmakeraw(&mut raw) };
-            unsafe { libc::tcsetattr(0, libc::TCSANOW, &raw) };
-
-            // Proxy I/O between terminal and socket
+            // Proxy I/O between terminal and socket, in raw mode
+            let mode = terminal::Mode::raw(0);
             screen_proxy_io(sock);
-
-            // Restore terminal
-            unsafe { libc::tcsetattr(0, libc::TCSANOW, &old_termios) };
+            drop(mode);
             unsafe { libc::close(sock) };
 
             io::write_str(1, b"\n[screen detached]\n");
@@ -842,14 +974,15 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
 
     if unsafe { libc::openpty(&mut master, &mut slave, pty_name.as_mut_ptr(),
                                core::ptr::null_mut(), core::ptr::null_mut()) } < 0 {
-        io::write_str(2, b"screen: cannot open pty\n");
+        sys::error(b"screen", b"cannot open pty");
         return 1;
     }
+    screen_copy_size(master);
 
-    let pid = unsafe { libc::fork() };
+    let pid = io::fork();
 
     if pid < 0 {
-        io::write_str(2, b"screen: fork failed\n");
+        sys::error(b"screen", b"fork failed");
         unsafe { libc::close(master) };
         unsafe { libc::close(slave) };
         return 1;
@@ -899,7 +1032,7 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
                     .chain(core::iter::once(core::ptr::null()))
                     .collect();
 
-                unsafe { libc::execvp(ptrs[0], ptrs.as_ptr()) };
+                unsafe { io::execvp(ptrs[0], ptrs.as_ptr()) };
             }
         }
 
@@ -971,13 +1104,9 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
         }
     }
 
-    // Set terminal to raw mode
-    let mut old_termios: libc::termios = unsafe { core::mem::zeroed() };
-    unsafe { libc::tcgetattr(0, &mut old_termios) };
-
-    let mut raw = old_termios;
-    unsafe { libc::cfmakeraw(&mut raw) };
-    unsafe { libc::tcsetattr(0, libc::TCSANOW, &raw) };
+    // Set terminal to raw mode, and pass size changes on to the session
+    let mode = terminal::Mode::raw(0);
+    terminal::watch_resize();
 
     // Main loop: proxy I/O between terminal and PTY
     let mut ctrl_a_pressed = false;
@@ -991,6 +1120,12 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
         ];
 
         let ret = unsafe { libc::poll(fds.as_mut_ptr(), 3, 100) };
+        if terminal::resized() {
+            screen_copy_size(master);
+        }
+        if ret < 0 && sys::errno() == libc::EINTR {
+            continue;
+        }
         if ret < 0 {
             break;
         }
@@ -1015,7 +1150,7 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
                     match buf[i] {
                         b'd' | b'D' => {
                             // Detach
-                            unsafe { libc::tcsetattr(0, libc::TCSANOW, &old_termios) };
+                            drop(mode);
                             io::write_str(1, b"\r\n[detached from session]\r\n");
                             // Keep socket open for reattachment
                             unsafe { libc::close(master) };
@@ -1068,7 +1203,7 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
     }
 
     // Cleanup
-    unsafe { libc::tcsetattr(0, libc::TCSANOW, &old_termios) };
+    drop(mode);
     unsafe { libc::close(master) };
     unsafe { libc::close(sock) };
     unsafe { libc::unlink(session_path.as_ptr() as *const i8) };
@@ -1080,6 +1215,14 @@ fn screen_new_session(name: Option<&[u8]>, argc: i32, argv: *const *const u8, cm
     0
 }
 
+/// Gives the session's pty the size of the terminal
+fn screen_copy_size(master: i32) {
+    if let Some((rows, cols)) = terminal::size(0) {
+        let size = libc::winsize { ws_row: rows as u16, ws_col: cols as u16, ws_xpixel: 0, ws_ypixel: 0 };
+        unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) };
+    }
+}
+
 fn screen_proxy_io(sock: i32) {
     let mut buf = [0u8; 4096];
     let mut ctrl_a_pressed = false;
@@ -1,7 +1,9 @@
 //! Network utilities
 
 use crate::io;
-use super::{get_arg, has_opt};
+use crate::sys;
+use super::json::Json;
+use super::{get_arg, usage};
 
 pub fn wget(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(2, b"wget: stub\n"); 0 }
 pub fn nc(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(2, b"nc: stub\n"); 0 }
@@ -27,11 +29,7 @@ pub fn ifdown(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ =
 pub fn ss(argc: i32, argv: *const *const u8) -> i32 { netstat(argc, argv) }
 pub fn arp(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn arping(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn ip(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn ipaddr(argc: i32, argv: *const *const u8) -> i32 { ip(argc, argv) }
-pub fn iplink(argc: i32, argv: *const *const u8) -> i32 { ip(argc, argv) }
 pub fn ipneigh(argc: i32, argv: *const *const u8) -> i32 { ip(argc, argv) }
-pub fn iproute(argc: i32, argv: *const *const u8) -> i32 { ip(argc, argv) }
 pub fn iprule(argc: i32, argv: *const *const u8) -> i32 { ip(argc, argv) }
 pub fn nameif(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn slattach(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
@@ -44,3 +42,873 @@ pub fn microcom(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _
 // Additional toybox applets
 pub fn nbd_client(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(2, b"nbd-client: stub\n"); 0 }
 pub fn nbd_server(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(2, b"nbd-server: stub\n"); 0 }
+
+/// Reads a native-endian integer at `offset`, or 0 if `data` is too short
+fn ne32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map_or(0, |b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// A route netlink socket, for dumping links, addresses and routes
+struct Netlink {
+    fd: i32,
+}
+
+impl Netlink {
+    fn open() -> Option<Netlink> {
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE) };
+        if fd < 0 {
+            return None;
+        }
+        let mut addr: libc::sockaddr_nl = unsafe { core::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as u16;
+        let len = core::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        if unsafe { libc::bind(fd, &addr as *const _ as *const libc::sockaddr, len) } < 0 {
+            io::close(fd);
+            return None;
+        }
+        Some(Netlink { fd })
+    }
+
+    /// Asks for every object of a kind (`RTM_GETLINK` and so on), with a
+    /// request body of `header` bytes naming `family`, and calls `each` with
+    /// the body of each reply. Fails with the kernel's errno.
+    fn dump(&self, kind: u16, header: usize, family: u8, each: &mut dyn FnMut(&[u8])) -> Result<(), i32> {
+        let mut request = [0u8; 32];
+        let len = 16 + header;
+        request[0..4].copy_from_slice(&(len as u32).to_ne_bytes());
+        request[4..6].copy_from_slice(&kind.to_ne_bytes());
+        request[6..8].copy_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+        request[8..12].copy_from_slice(&1u32.to_ne_bytes());
+        request[16] = family;
+        let mut kernel: libc::sockaddr_nl = unsafe { core::mem::zeroed() };
+        kernel.nl_family = libc::AF_NETLINK as u16;
+        let addr_len = core::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        let sent = unsafe {
+            libc::sendto(self.fd, request.as_ptr() as *const libc::c_void, len, 0, &kernel as *const _ as *const libc::sockaddr, addr_len)
+        };
+        if sent < 0 {
+            return Err(sys::errno());
+        }
+
+        let mut buf = [0u8; 65536];
+        loop {
+            let n = unsafe { libc::recvfrom(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0, core::ptr::null_mut(), core::ptr::null_mut()) };
+            if n < 0 && sys::errno() == libc::EINTR {
+                continue;
+            }
+            if n <= 0 {
+                return Err(if n < 0 { sys::errno() } else { libc::EIO });
+            }
+            let mut messages = &buf[..n as usize];
+            while messages.len() >= 16 {
+                let len = ne32(messages, 0) as usize;
+                if len < 16 || len > messages.len() {
+                    break;
+                }
+                match u16::from_ne_bytes([messages[4], messages[5]]) as i32 {
+                    libc::NLMSG_DONE => return Ok(()),
+                    libc::NLMSG_ERROR => return Err((ne32(messages, 16) as i32).saturating_neg()),
+                    _ => each(&messages[16..len]),
+                }
+                messages = &messages[len.next_multiple_of(4).min(messages.len())..];
+            }
+        }
+    }
+}
+
+impl Drop for Netlink {
+    fn drop(&mut self) {
+        io::close(self.fd);
+    }
+}
+
+/// The attributes after a reply's fixed header, as types and values
+fn attributes(body: &[u8], header: usize) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut rest = body.get(header..).unwrap_or(b"");
+    core::iter::from_fn(move || {
+        if rest.len() < 4 {
+            return None;
+        }
+        let len = u16::from_ne_bytes([rest[0], rest[1]]) as usize;
+        let kind = u16::from_ne_bytes([rest[2], rest[3]]);
+        if len < 4 || len > rest.len() {
+            return None;
+        }
+        let value = &rest[4..len];
+        rest = &rest[len.next_multiple_of(4).min(rest.len())..];
+        Some((kind, value))
+    })
+}
+
+/// A short byte string from a netlink attribute, such as an interface name
+/// or a hardware address
+#[derive(Clone, Copy)]
+struct Short {
+    buf: [u8; 32],
+    len: usize,
+}
+
+impl Short {
+    const EMPTY: Short = Short { buf: [0; 32], len: 0 };
+
+    /// Takes `value`, without the NUL that ends a string attribute
+    fn string(value: &[u8]) -> Short {
+        let end = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+        Short::bytes(&value[..end])
+    }
+
+    fn bytes(value: &[u8]) -> Short {
+        let mut short = Short::EMPTY;
+        short.len = value.len().min(short.buf.len());
+        short.buf[..short.len].copy_from_slice(&value[..short.len]);
+        short
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// An address or route destination being formatted
+struct Text {
+    buf: [u8; 64],
+    len: usize,
+}
+
+impl Text {
+    fn new() -> Text {
+        Text { buf: [0; 64], len: 0 }
+    }
+
+    fn push(&mut self, s: &[u8]) {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s[..n]);
+        self.len += n;
+    }
+
+    fn num(&mut self, n: u64) {
+        let mut buf = [0u8; 20];
+        self.push(sys::format_u64(n, &mut buf));
+    }
+
+    fn hex(&mut self, n: u64, width: usize) {
+        let mut buf = [0u8; 16];
+        let digits = sys::format_hex(n, &mut buf);
+        for _ in digits.len()..width {
+            self.push(b"0");
+        }
+        self.push(digits);
+    }
+
+    /// An IPv4 address as a dotted quad, IPv6 in its shortest form, and
+    /// anything else, such as a MAC address, as colon-separated hex
+    fn addr(&mut self, bytes: &[u8]) {
+        match bytes.len() {
+            4 => {
+                for (i, &b) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        self.push(b".");
+                    }
+                    self.num(b as u64);
+                }
+            }
+            16 if bytes[..10].iter().all(|&b| b == 0) && bytes[10..12] == [0xff, 0xff] => {
+                self.push(b"::ffff:");
+                self.addr(&bytes[12..]);
+            }
+            16 => {
+                let groups: [u64; 8] = core::array::from_fn(|i| (bytes[2 * i] as u64) << 8 | bytes[2 * i + 1] as u64);
+                // The longest run of two or more zero groups becomes `::`
+                let (mut best, mut best_len, mut run) = (8, 1, 0);
+                for (i, &group) in groups.iter().enumerate() {
+                    run = if group == 0 { run + 1 } else { 0 };
+                    if run > best_len {
+                        best = i + 1 - run;
+                        best_len = run;
+                    }
+                }
+                let mut i = 0;
+                while i < 8 {
+                    if i == best {
+                        self.push(b"::");
+                        i += best_len;
+                        continue;
+                    }
+                    if i > 0 && i != best + best_len {
+                        self.push(b":");
+                    }
+                    self.hex(groups[i], 1);
+                    i += 1;
+                }
+            }
+            _ => {
+                for (i, &b) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        self.push(b":");
+                    }
+                    self.hex(b as u64, 2);
+                }
+            }
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// The most links and addresses `ip` lists
+const MAX_LINKS: usize = 256;
+const MAX_ADDRS: usize = 1024;
+
+/// A network interface, from `RTM_NEWLINK`
+#[derive(Clone, Copy)]
+struct Link {
+    index: i32,
+    /// `ARPHRD_*`
+    kind: u16,
+    flags: u32,
+    name: Short,
+    mtu: u32,
+    qdisc: Short,
+    operstate: u8,
+    txqlen: Option<u32>,
+    master: i32,
+    address: Short,
+    broadcast: Short,
+}
+
+impl Link {
+    fn parse(body: &[u8]) -> Link {
+        let mut link = Link {
+            index: ne32(body, 4) as i32,
+            kind: body.get(2..4).map_or(0, |b| u16::from_ne_bytes([b[0], b[1]])),
+            flags: ne32(body, 8),
+            name: Short::EMPTY,
+            mtu: 0,
+            qdisc: Short::EMPTY,
+            operstate: 0,
+            txqlen: None,
+            master: 0,
+            address: Short::EMPTY,
+            broadcast: Short::EMPTY,
+        };
+        for (kind, value) in attributes(body, 16) {
+            match kind {
+                libc::IFLA_IFNAME => link.name = Short::string(value),
+                libc::IFLA_MTU => link.mtu = ne32(value, 0),
+                libc::IFLA_QDISC => link.qdisc = Short::string(value),
+                libc::IFLA_OPERSTATE => link.operstate = value.first().copied().unwrap_or(0),
+                libc::IFLA_TXQLEN => link.txqlen = Some(ne32(value, 0)),
+                libc::IFLA_MASTER => link.master = ne32(value, 0) as i32,
+                libc::IFLA_ADDRESS => link.address = Short::bytes(value),
+                libc::IFLA_BROADCAST => link.broadcast = Short::bytes(value),
+                _ => {}
+            }
+        }
+        link
+    }
+}
+
+/// An interface address, from `RTM_NEWADDR`
+#[derive(Clone, Copy)]
+struct Addr {
+    family: u8,
+    prefixlen: u8,
+    scope: u8,
+    flags: u32,
+    index: i32,
+    local: Short,
+    peer: Short,
+    broadcast: Short,
+    label: Short,
+    valid: u32,
+    preferred: u32,
+}
+
+impl Addr {
+    fn parse(body: &[u8]) -> Addr {
+        let byte = |i: usize| body.get(i).copied().unwrap_or(0);
+        let mut addr = Addr {
+            family: byte(0),
+            prefixlen: byte(1),
+            flags: byte(2) as u32,
+            scope: byte(3),
+            index: ne32(body, 4) as i32,
+            local: Short::EMPTY,
+            peer: Short::EMPTY,
+            broadcast: Short::EMPTY,
+            label: Short::EMPTY,
+            valid: u32::MAX,
+            preferred: u32::MAX,
+        };
+        for (kind, value) in attributes(body, 8) {
+            match kind {
+                libc::IFA_LOCAL => addr.local = Short::bytes(value),
+                libc::IFA_ADDRESS => addr.peer = Short::bytes(value),
+                libc::IFA_BROADCAST => addr.broadcast = Short::bytes(value),
+                libc::IFA_LABEL => addr.label = Short::string(value),
+                libc::IFA_FLAGS => addr.flags = ne32(value, 0),
+                libc::IFA_CACHEINFO => {
+                    addr.preferred = ne32(value, 0);
+                    addr.valid = ne32(value, 4);
+                }
+                _ => {}
+            }
+        }
+        // IPv6 addresses only come as IFA_ADDRESS
+        if addr.local.len == 0 {
+            addr.local = addr.peer;
+        }
+        addr
+    }
+}
+
+/// Interface flags as `ip` names them, in its order
+const LINK_FLAGS: &[(u32, &[u8])] = &[
+    (libc::IFF_LOOPBACK as u32, b"LOOPBACK"),
+    (libc::IFF_BROADCAST as u32, b"BROADCAST"),
+    (libc::IFF_POINTOPOINT as u32, b"POINTOPOINT"),
+    (libc::IFF_MULTICAST as u32, b"MULTICAST"),
+    (libc::IFF_NOARP as u32, b"NOARP"),
+    (libc::IFF_ALLMULTI as u32, b"ALLMULTI"),
+    (libc::IFF_PROMISC as u32, b"PROMISC"),
+    (libc::IFF_NOTRAILERS as u32, b"NOTRAILERS"),
+    (libc::IFF_DEBUG as u32, b"DEBUG"),
+    (libc::IFF_DYNAMIC as u32, b"DYNAMIC"),
+    (libc::IFF_SLAVE as u32, b"SLAVE"),
+    (libc::IFF_MASTER as u32, b"MASTER"),
+    (libc::IFF_UP as u32, b"UP"),
+    (libc::IFF_LOWER_UP as u32, b"LOWER_UP"),
+    (libc::IFF_DORMANT as u32, b"DORMANT"),
+];
+
+/// Address flags as `ip` names them
+const ADDR_FLAGS: &[(u32, &[u8])] = &[
+    (libc::IFA_F_SECONDARY, b"secondary"),
+    (libc::IFA_F_NODAD, b"nodad"),
+    (libc::IFA_F_OPTIMISTIC, b"optimistic"),
+    (libc::IFA_F_TENTATIVE, b"tentative"),
+    (libc::IFA_F_DEPRECATED, b"deprecated"),
+    (libc::IFA_F_NOPREFIXROUTE, b"noprefixroute"),
+];
+
+const OPERSTATES: &[&[u8]] = &[b"UNKNOWN", b"NOTPRESENT", b"DOWN", b"LOWERLAYERDOWN", b"TESTING", b"DORMANT", b"UP"];
+
+const ROUTE_TYPES: &[&[u8]] = &[
+    b"none", b"unicast", b"local", b"broadcast", b"anycast", b"multicast",
+    b"blackhole", b"unreachable", b"prohibit", b"throw", b"nat",
+];
+
+const ROUTE_PROTOCOLS: &[(u8, &[u8])] = &[
+    (1, b"redirect"),
+    (2, b"kernel"),
+    (3, b"boot"),
+    (4, b"static"),
+    (9, b"ra"),
+    (11, b"zebra"),
+    (12, b"bird"),
+    (16, b"dhcp"),
+    (42, b"babel"),
+    (186, b"bgp"),
+    (187, b"isis"),
+    (188, b"ospf"),
+    (189, b"rip"),
+];
+
+/// The `ARPHRD_*` link types `ip` names
+fn link_type(kind: u16, buf: &mut [u8; 20]) -> &[u8] {
+    match kind {
+        1 => b"ether",
+        32 => b"infiniband",
+        280 => b"can",
+        512 => b"ppp",
+        768 => b"ipip",
+        769 => b"tunnel6",
+        772 => b"loopback",
+        776 => b"sit",
+        778 => b"gre",
+        823 => b"ip6gre",
+        65534 => b"none",
+        _ => sys::format_u64(kind as u64, buf),
+    }
+}
+
+fn scope_name(scope: u8, buf: &mut [u8; 20]) -> &[u8] {
+    match scope {
+        0 => b"global",
+        200 => b"site",
+        253 => b"link",
+        254 => b"host",
+        255 => b"nowhere",
+        _ => sys::format_u64(scope as u64, buf),
+    }
+}
+
+/// What `ip` was asked to show
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IpObject {
+    Link,
+    Addr,
+    Route,
+}
+
+/// Whether `arg` abbreviates `word`, as `ip` accepts `a` or `addr` for
+/// `address`
+fn abbreviates(arg: &[u8], word: &[u8]) -> bool {
+    !arg.is_empty() && word.starts_with(arg)
+}
+
+pub fn ip(argc: i32, argv: *const *const u8) -> i32 {
+    ip_main(None, argc, argv)
+}
+
+pub fn ipaddr(argc: i32, argv: *const *const u8) -> i32 {
+    ip_main(Some(IpObject::Addr), argc, argv)
+}
+
+pub fn iplink(argc: i32, argv: *const *const u8) -> i32 {
+    ip_main(Some(IpObject::Link), argc, argv)
+}
+
+pub fn iproute(argc: i32, argv: *const *const u8) -> i32 {
+    ip_main(Some(IpObject::Route), argc, argv)
+}
+
+/// ip: show network interfaces, addresses and routes
+fn ip_main(mut object: Option<IpObject>, argc: i32, argv: *const *const u8) -> i32 {
+    let mut family = libc::AF_UNSPEC as u8;
+    let mut json = false;
+    let mut dev: Option<&[u8]> = None;
+    let mut i = 1;
+
+    // Options come first, with one dash or two: -4, -6, -j or -json
+    while let Some(arg) = (i < argc).then(|| unsafe { get_arg(argv, i) }).flatten() {
+        let name = match arg.strip_prefix(b"-") {
+            Some(name) if !name.is_empty() => name.strip_prefix(b"-").unwrap_or(name),
+            _ => break,
+        };
+        match name {
+            b"4" => family = libc::AF_INET as u8,
+            b"6" => family = libc::AF_INET6 as u8,
+            _ if abbreviates(name, b"json") => json = true,
+            _ => return usage::error(b"ip", b"unknown option"),
+        }
+        i += 1;
+    }
+    if object.is_none() {
+        let arg = match unsafe { get_arg(argv, i) }.filter(|_| i < argc) {
+            Some(arg) => arg,
+            None => return usage::error(b"ip", b"missing object"),
+        };
+        object = if abbreviates(arg, b"address") {
+            Some(IpObject::Addr)
+        } else if abbreviates(arg, b"link") {
+            Some(IpObject::Link)
+        } else if abbreviates(arg, b"route") {
+            Some(IpObject::Route)
+        } else {
+            sys::error_at(b"ip", arg, b"unknown object");
+            return 1;
+        };
+        i += 1;
+    }
+    let object = object.unwrap_or(IpObject::Link);
+
+    // [show | list] [dev] [NAME]
+    if let Some(arg) = unsafe { get_arg(argv, i) }.filter(|_| i < argc) {
+        if abbreviates(arg, b"show") || abbreviates(arg, b"list") || arg == b"lst" {
+            i += 1;
+        } else if !(arg == b"dev" || object != IpObject::Route && i + 1 == argc) {
+            sys::error_at(b"ip", arg, b"command not supported");
+            return 1;
+        }
+    }
+    while let Some(arg) = unsafe { get_arg(argv, i) }.filter(|_| i < argc) {
+        if arg == b"dev" {
+            i += 1;
+            dev = unsafe { get_arg(argv, i) }.filter(|_| i < argc);
+            if dev.is_none() {
+                return usage::error(b"ip", b"missing device name");
+            }
+        } else if object != IpObject::Route && dev.is_none() {
+            dev = Some(arg);
+        } else {
+            sys::error_at(b"ip", arg, b"unknown argument");
+            return 1;
+        }
+        i += 1;
+    }
+
+    let netlink = match Netlink::open() {
+        Some(netlink) => netlink,
+        None => {
+            sys::perror(b"ip", b"netlink");
+            return 1;
+        }
+    };
+    let mut links = [None; MAX_LINKS];
+    let mut nlinks = 0;
+    let dumped = netlink.dump(libc::RTM_GETLINK, 16, libc::AF_UNSPEC as u8, &mut |body| {
+        if nlinks < MAX_LINKS {
+            links[nlinks] = Some(Link::parse(body));
+            nlinks += 1;
+        }
+    });
+    if let Err(errno) = dumped {
+        sys::error_errno(b"ip", b"netlink", errno);
+        return 1;
+    }
+    let links = &links[..nlinks];
+    let find = |index: i32| links.iter().flatten().find(|link| link.index == index);
+    let wanted = match dev {
+        Some(dev) => match links.iter().flatten().find(|link| link.name.as_bytes() == dev) {
+            Some(link) => Some(link.index),
+            None => {
+                sys::error_at(b"ip", dev, b"device does not exist");
+                return 1;
+            }
+        },
+        None => None,
+    };
+
+    let out = io::stdout();
+    let mut doc = Json::new();
+    if json {
+        doc.begin_array();
+    }
+    let status = match object {
+        IpObject::Route => {
+            let family = if family == libc::AF_UNSPEC as u8 { libc::AF_INET as u8 } else { family };
+            let dumped = netlink.dump(libc::RTM_GETROUTE, 12, family, &mut |body| {
+                ip_route(body, family, wanted, &find, json.then_some(&mut doc), out)
+            });
+            dumped.map_err(|errno| sys::error_errno(b"ip", b"netlink", errno)).is_err() as i32
+        }
+        IpObject::Link | IpObject::Addr => {
+            let mut addrs = [None; MAX_ADDRS];
+            let mut naddrs = 0;
+            if object == IpObject::Addr {
+                let dumped = netlink.dump(libc::RTM_GETADDR, 8, family, &mut |body| {
+                    if naddrs < MAX_ADDRS {
+                        addrs[naddrs] = Some(Addr::parse(body));
+                        naddrs += 1;
+                    }
+                });
+                if let Err(errno) = dumped {
+                    sys::error_errno(b"ip", b"netlink", errno);
+                    return 1;
+                }
+            }
+            for link in links.iter().flatten().filter(|link| wanted.is_none_or(|index| link.index == index)) {
+                let addrs = || addrs[..naddrs].iter().flatten().filter(|addr| addr.index == link.index);
+                // With -4 or -6, only links with such addresses
+                if object == IpObject::Addr && family != libc::AF_UNSPEC as u8 && addrs().next().is_none() {
+                    continue;
+                }
+                match json {
+                    true => ip_link_json(&mut doc, link, &find, (object == IpObject::Addr).then(addrs)),
+                    false => ip_link_text(out, link, &find, (object == IpObject::Addr).then(addrs)),
+                }
+            }
+            0
+        }
+    };
+    if json {
+        doc.end_array().finish();
+    }
+    out.flush();
+    status
+}
+
+/// Writes a link and, for `ip addr`, its addresses as `ip` shows them
+fn ip_link_text<'a>(
+    out: io::Stdout,
+    link: &Link,
+    find: &dyn Fn(i32) -> Option<&'a Link>,
+    addrs: Option<impl Iterator<Item = &'a Addr>>,
+) {
+    let mut buf = [0u8; 20];
+    out.write_num(link.index as u64);
+    out.write_str(b": ");
+    out.write_all(link.name.as_bytes());
+    out.write_str(b": <");
+    let mut first = true;
+    let up_without_carrier = link.flags & libc::IFF_UP as u32 != 0 && link.flags & libc::IFF_LOWER_UP as u32 == 0;
+    let names = up_without_carrier.then_some(&b"NO-CARRIER"[..]).into_iter();
+    for name in names.chain(LINK_FLAGS.iter().filter(|f| link.flags & f.0 != 0).map(|f| f.1)) {
+        if !first {
+            out.write_byte(b',');
+        }
+        out.write_all(name);
+        first = false;
+    }
+    out.write_str(b"> mtu ");
+    out.write_num(link.mtu as u64);
+    if link.qdisc.len > 0 {
+        out.write_str(b" qdisc ");
+        out.write_all(link.qdisc.as_bytes());
+    }
+    if let Some(master) = find(link.master) {
+        out.write_str(b" master ");
+        out.write_all(master.name.as_bytes());
+    }
+    out.write_str(b" state ");
+    out.write_all(OPERSTATES.get(link.operstate as usize).copied().unwrap_or(b"UNKNOWN"));
+    if let Some(txqlen) = link.txqlen {
+        out.write_str(b" qlen ");
+        out.write_num(txqlen as u64);
+    }
+    out.write_str(b"\n    link/");
+    out.write_all(link_type(link.kind, &mut buf));
+    for (prefix, addr) in [(&b" "[..], &link.address), (b" brd ", &link.broadcast)] {
+        if addr.len > 0 {
+            let mut text = Text::new();
+            text.addr(addr.as_bytes());
+            out.write_all(prefix);
+            out.write_all(text.as_bytes());
+        }
+    }
+    out.write_byte(b'\n');
+
+    for addr in addrs.into_iter().flatten() {
+        let inet = addr.family == libc::AF_INET as u8;
+        let mut text = Text::new();
+        text.addr(addr.local.as_bytes());
+        if addr.peer.len > 0 && addr.peer.as_bytes() != addr.local.as_bytes() {
+            text.push(b" peer ");
+            text.addr(addr.peer.as_bytes());
+        }
+        text.push(b"/");
+        text.num(addr.prefixlen as u64);
+        out.write_str(if inet { b"    inet " } else { b"    inet6 " });
+        out.write_all(text.as_bytes());
+        if addr.broadcast.len > 0 {
+            let mut text = Text::new();
+            text.addr(addr.broadcast.as_bytes());
+            out.write_str(b" brd ");
+            out.write_all(text.as_bytes());
+        }
+        out.write_str(b" scope ");
+        out.write_all(scope_name(addr.scope, &mut buf));
+        if addr.flags & libc::IFA_F_PERMANENT == 0 {
+            out.write_str(b" dynamic");
+        }
+        for &(_, name) in ADDR_FLAGS.iter().filter(|f| addr.flags & f.0 != 0) {
+            out.write_byte(b' ');
+            out.write_all(name);
+        }
+        if inet {
+            out.write_byte(b' ');
+            out.write_all(if addr.label.len > 0 { addr.label.as_bytes() } else { link.name.as_bytes() });
+        }
+        out.write_str(b"\n       valid_lft ");
+        for (i, lifetime) in [addr.valid, addr.preferred].into_iter().enumerate() {
+            if i > 0 {
+                out.write_str(b" preferred_lft ");
+            }
+            match lifetime {
+                u32::MAX => out.write_str(b"forever"),
+                secs => {
+                    out.write_num(secs as u64);
+                    out.write_str(b"sec");
+                }
+            }
+        }
+        out.write_byte(b'\n');
+    }
+}
+
+/// Writes a link and, for `ip addr`, its addresses as an object with the
+/// keys `ip -json` uses
+fn ip_link_json<'a>(
+    doc: &mut Json,
+    link: &Link,
+    find: &dyn Fn(i32) -> Option<&'a Link>,
+    addrs: Option<impl Iterator<Item = &'a Addr>>,
+) {
+    let mut buf = [0u8; 20];
+    doc.begin_object();
+    doc.key(b"ifindex").signed(link.index as i64);
+    doc.key(b"ifname").string(link.name.as_bytes());
+    doc.key(b"flags").begin_array();
+    if link.flags & libc::IFF_UP as u32 != 0 && link.flags & libc::IFF_LOWER_UP as u32 == 0 {
+        doc.string(b"NO-CARRIER");
+    }
+    for &(_, name) in LINK_FLAGS.iter().filter(|f| link.flags & f.0 != 0) {
+        doc.string(name);
+    }
+    doc.end_array();
+    doc.key(b"mtu").number(link.mtu as u64);
+    if link.qdisc.len > 0 {
+        doc.key(b"qdisc").string(link.qdisc.as_bytes());
+    }
+    if let Some(master) = find(link.master) {
+        doc.key(b"master").string(master.name.as_bytes());
+    }
+    doc.key(b"operstate").string(OPERSTATES.get(link.operstate as usize).copied().unwrap_or(b"UNKNOWN"));
+    if let Some(txqlen) = link.txqlen {
+        doc.key(b"txqlen").number(txqlen as u64);
+    }
+    doc.key(b"link_type").string(link_type(link.kind, &mut buf));
+    for (key, addr) in [(&b"address"[..], &link.address), (b"broadcast", &link.broadcast)] {
+        if addr.len > 0 {
+            let mut text = Text::new();
+            text.addr(addr.as_bytes());
+            doc.key(key).string(text.as_bytes());
+        }
+    }
+
+    if let Some(addrs) = addrs {
+        doc.key(b"addr_info").begin_array();
+        for addr in addrs {
+            let inet = addr.family == libc::AF_INET as u8;
+            doc.begin_object();
+            doc.key(b"family").string(if inet { b"inet" } else { b"inet6" });
+            let mut text = Text::new();
+            text.addr(addr.local.as_bytes());
+            doc.key(b"local").string(text.as_bytes());
+            if addr.peer.len > 0 && addr.peer.as_bytes() != addr.local.as_bytes() {
+                let mut text = Text::new();
+                text.addr(addr.peer.as_bytes());
+                doc.key(b"address").string(text.as_bytes());
+            }
+            doc.key(b"prefixlen").number(addr.prefixlen as u64);
+            if addr.broadcast.len > 0 {
+                let mut text = Text::new();
+                text.addr(addr.broadcast.as_bytes());
+                doc.key(b"broadcast").string(text.as_bytes());
+            }
+            doc.key(b"scope").string(scope_name(addr.scope, &mut buf));
+            if addr.flags & libc::IFA_F_PERMANENT == 0 {
+                doc.key(b"dynamic").bool(true);
+            }
+            for &(_, name) in ADDR_FLAGS.iter().filter(|f| addr.flags & f.0 != 0) {
+                doc.key(name).bool(true);
+            }
+            if inet {
+                doc.key(b"label").string(if addr.label.len > 0 { addr.label.as_bytes() } else { link.name.as_bytes() });
+            }
+            doc.key(b"valid_life_time").number(addr.valid as u64);
+            doc.key(b"preferred_life_time").number(addr.preferred as u64);
+            doc.end_object();
+        }
+        doc.end_array();
+    }
+    doc.end_object();
+}
+
+/// Writes a route from the main table as `ip route` shows it, or as a JSON
+/// object with the keys `ip -json route` uses
+fn ip_route<'a>(
+    body: &[u8],
+    family: u8,
+    wanted: Option<i32>,
+    find: &dyn Fn(i32) -> Option<&'a Link>,
+    doc: Option<&mut Json>,
+    out: io::Stdout,
+) {
+    let byte = |i: usize| body.get(i).copied().unwrap_or(0);
+    let (dst_len, mut table, protocol, scope, kind) = (byte(1), byte(4) as u32, byte(5), byte(6), byte(7));
+    // Routes the kernel cloned for a destination aren't in a table
+    if byte(0) != family || ne32(body, 8) & libc::RTM_F_CLONED != 0 {
+        return;
+    }
+    let (mut dst, mut gateway, mut prefsrc) = (None, None, None);
+    let (mut oif, mut metric) = (0, None);
+    for (attr, value) in attributes(body, 12) {
+        match attr {
+            libc::RTA_DST => dst = Some(value),
+            libc::RTA_GATEWAY => gateway = Some(value),
+            libc::RTA_PREFSRC => prefsrc = Some(value),
+            libc::RTA_OIF => oif = ne32(value, 0) as i32,
+            libc::RTA_PRIORITY => metric = Some(ne32(value, 0)),
+            libc::RTA_TABLE => table = ne32(value, 0),
+            _ => {}
+        }
+    }
+    if table != libc::RT_TABLE_MAIN as u32 || wanted.is_some_and(|index| index != oif) {
+        return;
+    }
+
+    let mut destination = Text::new();
+    match dst {
+        Some(dst) => {
+            destination.addr(dst);
+            if dst_len as usize != dst.len() * 8 {
+                destination.push(b"/");
+                destination.num(dst_len as u64);
+            }
+        }
+        None => destination.push(b"default"),
+    }
+    let text = |value: Option<&[u8]>| {
+        value.map(|value| {
+            let mut text = Text::new();
+            text.addr(value);
+            text
+        })
+    };
+    let (gateway, prefsrc) = (text(gateway), text(prefsrc));
+    let dev = find(oif).map(|link| link.name);
+    let kind = (kind != libc::RTN_UNICAST).then(|| ROUTE_TYPES.get(kind as usize).copied().unwrap_or(b"unknown"));
+    let mut protocol_buf = [0u8; 20];
+    let protocol = match ROUTE_PROTOCOLS.iter().find(|p| p.0 == protocol) {
+        Some(&(_, name)) => name,
+        None => sys::format_u64(protocol as u64, &mut protocol_buf),
+    };
+    let mut scope_buf = [0u8; 20];
+    let scope = (scope != libc::RT_SCOPE_UNIVERSE).then(|| scope_name(scope, &mut scope_buf));
+
+    if let Some(doc) = doc {
+        doc.begin_object();
+        if let Some(kind) = kind {
+            doc.key(b"type").string(kind);
+        }
+        doc.key(b"dst").string(destination.as_bytes());
+        if let Some(gateway) = &gateway {
+            doc.key(b"gateway").string(gateway.as_bytes());
+        }
+        if let Some(dev) = &dev {
+            doc.key(b"dev").string(dev.as_bytes());
+        }
+        doc.key(b"protocol").string(protocol);
+        if let Some(scope) = scope {
+            doc.key(b"scope").string(scope);
+        }
+        if let Some(prefsrc) = &prefsrc {
+            doc.key(b"prefsrc").string(prefsrc.as_bytes());
+        }
+        if let Some(metric) = metric {
+            doc.key(b"metric").number(metric as u64);
+        }
+        doc.key(b"flags").begin_array().end_array();
+        doc.end_object();
+        return;
+    }
+
+    if let Some(kind) = kind {
+        out.write_all(kind);
+        out.write_byte(b' ');
+    }
+    out.write_all(destination.as_bytes());
+    let words: [(&[u8], Option<&[u8]>); 5] = [
+        (b" via ", gateway.as_ref().map(Text::as_bytes)),
+        (b" dev ", dev.as_ref().map(Short::as_bytes)),
+        // Like ip, the usual boot protocol goes without saying
+        (b" proto ", Some(protocol).filter(|p| *p != b"boot")),
+        (b" scope ", scope),
+        (b" src ", prefsrc.as_ref().map(Text::as_bytes)),
+    ];
+    for (word, value) in words {
+        if let Some(value) = value {
+            out.write_all(word);
+            out.write_all(value);
+        }
+    }
+    if let Some(metric) = metric {
+        out.write_str(b" metric ");
+        out.write_num(metric as u64);
+    }
+    out.write_byte(b'\n');
+}
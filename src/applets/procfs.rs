@@ -0,0 +1,450 @@
+//! Process table
+//!
+//! Lists processes with `pids` and a process's threads with
+//! `Process::threads`, and reads `/proc/PID/stat` and `status` into a typed
+//! `Process`. The rest of a process's files are read on demand: `cmdline`,
+//! `environ`, `io`, `statm`, the `fd/` directory and the `cwd` and `exe`
+//! links. `uptime`, `loadavg`, `meminfo` and `read` cover the system-wide
+//! files.
+//!
+//! Everything is read from `$ARMYBOX_PROC` instead of `/proc` when
+//! `sys::data_root` allows it, so `ps`, `top` and friends can be shown a
+//! fixed process table.
+
+use crate::io;
+use crate::sys;
+
+/// `PF_KTHREAD` in `stat`'s flags
+const KERNEL_THREAD: u32 = 0x0020_0000;
+
+/// The process table's directory, without a trailing `/`
+fn root() -> &'static [u8] {
+    sys::data_root(b"ARMYBOX_PROC").unwrap_or(b"/proc")
+}
+
+/// Joins the root and `parts` into `buf`
+fn join<'a>(parts: &[&[u8]], buf: &'a mut [u8; 256]) -> Option<&'a [u8]> {
+    let mut len = 0;
+    for part in core::iter::once(&root()).chain(parts) {
+        buf.get_mut(len..len + part.len())?.copy_from_slice(part);
+        len += part.len();
+    }
+    Some(&buf[..len])
+}
+
+/// As much of the file at `path` as fits in `buf`
+fn read_path<'a>(path: &[u8], buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    let fd = io::open(path, libc::O_RDONLY | libc::O_CLOEXEC, 0);
+    if fd < 0 {
+        return None;
+    }
+    let mut len = 0;
+    while len < buf.len() {
+        let n = io::read(fd, &mut buf[len..]);
+        if n <= 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    io::close(fd);
+    Some(&buf[..len])
+}
+
+/// As much of a system-wide file, such as `loadavg`, as fits in `buf`
+pub fn read<'a>(name: &[u8], buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    let mut path = [0u8; 256];
+    read_path(join(&[b"/", name], &mut path)?, buf)
+}
+
+/// A decimal like `123.45` in hundredths
+fn parse_hundredths(field: &[u8]) -> Option<u64> {
+    let (secs, frac) = match field.iter().position(|&c| c == b'.') {
+        Some(dot) => (&field[..dot], &field[dot + 1..]),
+        None => (field, &b""[..]),
+    };
+    let hundredths = frac.iter().take(2).chain(b"00").take(2).try_fold(0, |n, &c| {
+        c.is_ascii_digit().then(|| n * 10 + (c - b'0') as u64)
+    })?;
+    Some(sys::parse_u64(secs)? * 100 + hundredths)
+}
+
+/// Seconds since boot, in hundredths
+pub fn uptime() -> Option<u64> {
+    let mut buf = [0u8; 64];
+    let data = read(b"uptime", &mut buf)?;
+    parse_hundredths(data.split(|&c| c == b' ').next()?)
+}
+
+/// The 1, 5 and 15 minute load averages, in hundredths
+pub fn loadavg() -> Option<[u64; 3]> {
+    let mut buf = [0u8; 128];
+    let mut fields = read(b"loadavg", &mut buf)?.split(|&c| c == b' ');
+    let mut load = [0; 3];
+    for n in load.iter_mut() {
+        *n = parse_hundredths(fields.next()?)?;
+    }
+    Some(load)
+}
+
+/// A `meminfo` field, in KiB
+pub fn meminfo(key: &[u8]) -> Option<u64> {
+    let mut buf = [0u8; 4096];
+    let data = read(b"meminfo", &mut buf)?;
+    data.split(|&c| c == b'\n').find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix(b":")?;
+        let value = value.trim_ascii();
+        sys::parse_u64(value.strip_suffix(b" kB").unwrap_or(value))
+    })
+}
+
+/// Clock ticks per second, the unit of a process's times
+pub fn ticks() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        n if n > 0 => n as u64,
+        _ => 100,
+    }
+}
+
+/// The size of a page, the unit of `rss` and `statm`
+pub fn page_size() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        n if n > 0 => n as u64,
+        _ => 4096,
+    }
+}
+
+/// The numbered entries of a directory: processes, threads or descriptors
+pub struct Ids {
+    fd: i32,
+    buf: [u8; 4096],
+    len: usize,
+    offset: usize,
+}
+
+impl Ids {
+    fn open(path: &[u8]) -> Option<Ids> {
+        let fd = io::open(path, libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC, 0);
+        (fd >= 0).then_some(Ids { fd, buf: [0; 4096], len: 0, offset: 0 })
+    }
+}
+
+impl Iterator for Ids {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        loop {
+            if self.offset >= self.len {
+                let n = unsafe { libc::syscall(libc::SYS_getdents64, self.fd, self.buf.as_mut_ptr(), self.buf.len()) };
+                if n <= 0 {
+                    return None;
+                }
+                self.len = n as usize;
+                self.offset = 0;
+            }
+            let dirent = unsafe { &*(self.buf.as_ptr().add(self.offset) as *const libc::dirent64) };
+            self.offset += dirent.d_reclen as usize;
+            let name = unsafe { io::cstr_to_slice(dirent.d_name.as_ptr() as *const u8) };
+            if let Some(id) = sys::parse_u64(name).filter(|&id| id <= i32::MAX as u64) {
+                return Some(id as i32);
+            }
+        }
+    }
+}
+
+impl Drop for Ids {
+    fn drop(&mut self) {
+        io::close(self.fd);
+    }
+}
+
+/// The processes, in the order `/proc` lists them
+pub fn pids() -> Option<Ids> {
+    let mut path = [0u8; 256];
+    Ids::open(join(&[], &mut path)?)
+}
+
+/// A process's command name, as `comm` and `stat` give it
+#[derive(Clone, Copy)]
+pub struct Comm {
+    buf: [u8; 64],
+    len: u8,
+}
+
+impl Comm {
+    fn new(s: &[u8]) -> Comm {
+        let len = s.len().min(64);
+        let mut buf = [0; 64];
+        buf[..len].copy_from_slice(&s[..len]);
+        Comm { buf, len: len as u8 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+impl Default for Comm {
+    fn default() -> Comm {
+        Comm::new(b"")
+    }
+}
+
+/// A process or thread, from its `stat` and `status`. Times are in clock
+/// ticks, `starttime` counting from boot; `vsize` is in bytes and `rss` in
+/// pages.
+#[derive(Clone, Copy, Default)]
+pub struct Process {
+    /// The thread's id; the same as `tgid` for a process
+    pub pid: i32,
+    /// The process the thread belongs to
+    pub tgid: i32,
+    pub comm: Comm,
+    /// One of `RSDZTtXI`
+    pub state: u8,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub session: i32,
+    /// The controlling terminal's device number, or 0
+    pub tty_nr: u32,
+    /// The terminal's foreground process group
+    pub tpgid: i32,
+    pub flags: u32,
+    pub minflt: u64,
+    pub majflt: u64,
+    pub utime: u64,
+    pub stime: u64,
+    pub cutime: u64,
+    pub cstime: u64,
+    pub priority: i64,
+    pub nice: i64,
+    pub num_threads: i64,
+    pub starttime: u64,
+    pub vsize: u64,
+    pub rss: u64,
+    /// The CPU it last ran on
+    pub processor: i32,
+    /// Real, effective, saved and filesystem user ids
+    pub uid: [u32; 4],
+    /// Real, effective, saved and filesystem group ids
+    pub gid: [u32; 4],
+}
+
+/// Counters from `/proc/PID/io`
+#[derive(Clone, Copy, Default)]
+pub struct Io {
+    pub rchar: u64,
+    pub wchar: u64,
+    pub syscr: u64,
+    pub syscw: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub cancelled_write_bytes: u64,
+}
+
+/// Memory use from `/proc/PID/statm`, in pages
+#[derive(Clone, Copy, Default)]
+pub struct Statm {
+    pub size: u64,
+    pub resident: u64,
+    pub shared: u64,
+    pub text: u64,
+    pub data: u64,
+}
+
+/// Parses a `stat` line into `p`
+pub fn parse_stat(line: &[u8], p: &mut Process) -> Option<()> {
+    // The name may itself hold spaces and parentheses
+    let open = line.iter().position(|&c| c == b'(')?;
+    let close = line.iter().rposition(|&c| c == b')')?;
+    p.pid = sys::parse_i64(line[..open].trim_ascii())? as i32;
+    p.comm = Comm::new(line.get(open + 1..close)?);
+
+    // Fields are numbered from 1 for the pid, as in proc(5)
+    let mut fields = line[close + 1..].trim_ascii().split(|&c| c == b' ');
+    let mut number = 2;
+    let mut next = |n: usize| -> Option<&[u8]> {
+        let field = fields.nth(n - number - 1)?;
+        number = n;
+        Some(field)
+    };
+    let signed = |f: &[u8]| sys::parse_i64(f);
+    let unsigned = |f: &[u8]| sys::parse_u64(f);
+
+    p.state = *next(3)?.first()?;
+    p.ppid = signed(next(4)?)? as i32;
+    p.pgrp = signed(next(5)?)? as i32;
+    p.session = signed(next(6)?)? as i32;
+    p.tty_nr = signed(next(7)?)? as u32;
+    p.tpgid = signed(next(8)?)? as i32;
+    p.flags = unsigned(next(9)?)? as u32;
+    p.minflt = unsigned(next(10)?)?;
+    p.majflt = unsigned(next(12)?)?;
+    p.utime = unsigned(next(14)?)?;
+    p.stime = unsigned(next(15)?)?;
+    p.cutime = signed(next(16)?)? as u64;
+    p.cstime = signed(next(17)?)? as u64;
+    p.priority = signed(next(18)?)?;
+    p.nice = signed(next(19)?)?;
+    p.num_threads = signed(next(20)?)?;
+    p.starttime = unsigned(next(22)?)?;
+    p.vsize = unsigned(next(23)?)?;
+    p.rss = signed(next(24)?)?.max(0) as u64;
+    // Older kernels stop before `processor`
+    p.processor = next(39).and_then(signed).unwrap_or(0) as i32;
+    Some(())
+}
+
+/// Parses the `Uid:` and `Gid:` lines of `status` into `p`
+fn parse_status(data: &[u8], p: &mut Process) {
+    for line in data.split(|&c| c == b'\n') {
+        let ids = match line.iter().position(|&c| c == b':') {
+            Some(colon) if &line[..colon] == b"Uid" => &mut p.uid,
+            Some(colon) if &line[..colon] == b"Gid" => &mut p.gid,
+            _ => continue,
+        };
+        let values = line[4..].split(|c| c.is_ascii_whitespace()).filter(|f| !f.is_empty());
+        for (id, value) in ids.iter_mut().zip(values) {
+            *id = sys::parse_u64(value).unwrap_or(0) as u32;
+        }
+    }
+}
+
+/// Parses `NAME VALUE` lines, as in `io`, calling `field` for each
+fn parse_fields(data: &[u8], mut field: impl FnMut(&[u8], u64)) {
+    for line in data.split(|&c| c == b'\n') {
+        if let Some(colon) = line.iter().position(|&c| c == b':') {
+            if let Some(value) = sys::parse_u64(line[colon + 1..].trim_ascii()) {
+                field(&line[..colon], value);
+            }
+        }
+    }
+}
+
+impl Process {
+    /// Reads process `pid`; `None` once it has exited
+    pub fn read(pid: i32) -> Option<Process> {
+        Process::read_thread(pid, pid)
+    }
+
+    /// Reads thread `tid` of process `tgid`
+    pub fn read_thread(tgid: i32, tid: i32) -> Option<Process> {
+        let mut p = Process { tgid, ..Process::default() };
+        let mut path = [0u8; 256];
+        let mut buf = [0u8; 2048];
+        p.pid = tid;
+        parse_stat(read_path(p.path(b"stat", &mut path)?, &mut buf)?, &mut p)?;
+        p.tgid = tgid;
+        if let Some(status) = read_path(p.path(b"status", &mut path)?, &mut buf) {
+            parse_status(status, &mut p);
+        }
+        Some(p)
+    }
+
+    /// The path of one of its files, under `task/` for a thread
+    fn path<'a>(&self, file: &[u8], buf: &'a mut [u8; 256]) -> Option<&'a [u8]> {
+        let mut tgid = [0u8; 20];
+        let mut tid = [0u8; 20];
+        let tgid = sys::format_u64(self.tgid as u64, &mut tgid);
+        if self.pid == self.tgid {
+            join(&[b"/", tgid, b"/", file], buf)
+        } else {
+            let tid = sys::format_u64(self.pid as u64, &mut tid);
+            join(&[b"/", tgid, b"/task/", tid, b"/", file], buf)
+        }
+    }
+
+    /// Its threads' ids, itself included
+    pub fn threads(&self) -> Option<Ids> {
+        let mut path = [0u8; 256];
+        Ids::open(self.path(b"task", &mut path)?)
+    }
+
+    /// Its open file descriptors
+    pub fn fds(&self) -> Option<Ids> {
+        let mut path = [0u8; 256];
+        Ids::open(self.path(b"fd", &mut path)?)
+    }
+
+    /// As much of one of its files as fits in `buf`
+    pub fn read_file<'a>(&self, file: &[u8], buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let mut path = [0u8; 256];
+        read_path(self.path(file, &mut path)?, buf)
+    }
+
+    /// Where one of its links, such as `cwd`, `exe` or `fd/N`, points
+    pub fn link<'a>(&self, file: &[u8], buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let mut path = [0u8; 256];
+        let n = io::readlink(self.path(file, &mut path)?, buf);
+        (n >= 0).then(|| &buf[..n as usize])
+    }
+
+    /// Its arguments, NUL-separated without the last NUL; empty for kernel
+    /// threads and zombies
+    pub fn cmdline<'a>(&self, buf: &'a mut [u8]) -> &'a [u8] {
+        let data = self.read_file(b"cmdline", buf).unwrap_or(&[]);
+        data.strip_suffix(b"\0").unwrap_or(data)
+    }
+
+    /// Its environment, NUL-separated without the last NUL
+    pub fn environ<'a>(&self, buf: &'a mut [u8]) -> &'a [u8] {
+        let data = self.read_file(b"environ", buf).unwrap_or(&[]);
+        data.strip_suffix(b"\0").unwrap_or(data)
+    }
+
+    /// Its I/O counters; only readable by its owner
+    pub fn io(&self) -> Option<Io> {
+        let mut buf = [0u8; 512];
+        let mut io = Io::default();
+        parse_fields(self.read_file(b"io", &mut buf)?, |name, value| match name {
+            b"rchar" => io.rchar = value,
+            b"wchar" => io.wchar = value,
+            b"syscr" => io.syscr = value,
+            b"syscw" => io.syscw = value,
+            b"read_bytes" => io.read_bytes = value,
+            b"write_bytes" => io.write_bytes = value,
+            b"cancelled_write_bytes" => io.cancelled_write_bytes = value,
+            _ => {}
+        });
+        Some(io)
+    }
+
+    /// Its memory use
+    pub fn statm(&self) -> Option<Statm> {
+        let mut buf = [0u8; 128];
+        let data = self.read_file(b"statm", &mut buf)?;
+        let mut fields = data.trim_ascii().split(|&c| c == b' ').map(sys::parse_u64);
+        let mut next = || fields.next().flatten();
+        let (size, resident, shared, text) = (next()?, next()?, next()?, next()?);
+        let _lib = next()?;
+        Some(Statm { size, resident, shared, text, data: next()? })
+    }
+
+    /// Whether it is a kernel thread, with no user space
+    pub fn is_kernel_thread(&self) -> bool {
+        self.flags & KERNEL_THREAD != 0
+    }
+
+    /// User and system time, in clock ticks
+    pub fn cpu_time(&self) -> u64 {
+        self.utime + self.stime
+    }
+}
+
+/// The name of terminal `dev` under `/dev`, for the usual terminal drivers,
+/// or `?` for none
+pub fn tty_name(dev: u32, buf: &mut [u8; 16]) -> &[u8] {
+    let (major, minor) = ((dev >> 8) & 0xfff, (dev & 0xff) | ((dev >> 12) & 0xfff00));
+    let (prefix, n): (&[u8], u32) = match major {
+        0 => return b"?",
+        4 if minor < 64 => (b"tty", minor),
+        4 => (b"ttyS", minor - 64),
+        5 if minor == 1 => return b"console",
+        136..=143 => (b"pts/", (major - 136) * 256 + minor),
+        _ => return b"?",
+    };
+    buf[..prefix.len()].copy_from_slice(prefix);
+    let mut digits = [0u8; 20];
+    let digits = sys::format_u64(n as u64, &mut digits);
+    buf[prefix.len()..prefix.len() + digits.len()].copy_from_slice(digits);
+    &buf[..prefix.len() + digits.len()]
+}
@@ -0,0 +1,1107 @@
+//! POSIX regular expressions
+//!
+//! Basic (BRE) and extended (ERE) regular expressions for `grep`, `sed`,
+//! `awk`, `expr`, `find` and `pgrep`, without heap allocation.
+//!
+//! A pattern compiles to a small program for a Thompson NFA, which runs as a
+//! Pike VM: every possible match advances in step over the text, so matching
+//! takes time linear in the text times the program size. Back-references
+//! can't be matched that way, so patterns using them run on a backtracking
+//! matcher instead, which can take exponential time.
+//!
+//! A pattern that is only a literal string is searched for directly. Others
+//! skip ahead to where their literal prefix, or a byte that can start a
+//! match, occurs before the VM runs.
+//!
+//! Matches are leftmost-longest, as POSIX requires. Among equally long
+//! matches, subexpressions prefer the greedy reading.
+//!
+//! Syntax, with the BRE spelling in parentheses where it differs:
+//!
+//! - `.`, and `[...]` with ranges, `[:class:]`, `[=c=]` and `[.c.]`
+//! - `*`, `+` (`\+`), `?` (`\?`) and `{m,n}` (`\{m,n\}`)
+//! - `|` (`\|`), `(...)` (`\(...\)`) and `\1` to `\9`
+//! - `^` and `$`, anywhere in an ERE but only at the ends of a BRE
+//! - the GNU extensions `\<`, `\>`, `\b`, `\B`, `\w`, `\W`, `\s`, `\S`,
+//!   and `\n` and `\t` for newline and tab
+//!
+//! A program holds up to 1024 instructions, so large repeat counts
+//! (`x{1,1000}`) are rejected as too big.
+
+use core::mem::MaybeUninit;
+
+/// Compile an extended regular expression (`REG_EXTENDED`)
+pub const EXTENDED: u32 = 1;
+
+/// Ignore case (`REG_ICASE`)
+pub const ICASE: u32 = 2;
+
+/// Capture slots kept: the whole match and `\1` to `\9`
+pub const MAX_GROUPS: usize = 10;
+
+const MAX_INSTS: usize = 1024;
+const MAX_CLASSES: usize = 64;
+const DUP_MAX: u32 = 255;
+const SLOTS: usize = 2 * MAX_GROUPS;
+
+/// An unset capture slot
+const NONE: u32 = u32::MAX;
+
+/// Deepest recursion of the backtracking matcher before it gives up
+const MAX_DEPTH: usize = 10_000;
+
+// Messages as glibc's regerror gives them
+const TOO_BIG: &[u8] = b"Regular expression too big";
+const UNMATCHED_BRACKET: &[u8] = b"Unmatched [, [^, [:, [., or [=";
+const UNMATCHED_PAREN: &[u8] = b"Unmatched ( or \\(";
+const UNMATCHED_CLOSE: &[u8] = b"Unmatched ) or \\)";
+const UNMATCHED_BRACE: &[u8] = b"Unmatched \\{";
+const BAD_BRACE: &[u8] = b"Invalid content of \\{\\}";
+const BAD_BACKREF: &[u8] = b"Invalid back reference";
+const BAD_RANGE: &[u8] = b"Invalid range end";
+const BAD_CLASS: &[u8] = b"Invalid character class name";
+const BAD_COLLATE: &[u8] = b"Invalid collation character";
+const TRAILING_BACKSLASH: &[u8] = b"Trailing backslash";
+
+/// Zero-width conditions
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Assert {
+    LineStart,
+    LineEnd,
+    WordStart,
+    WordEnd,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+/// One program instruction. Jump offsets are relative to the instruction,
+/// so code can be moved and copied as a block.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Inst {
+    /// A byte (lowercase when ignoring case)
+    Byte(u8),
+    /// Any byte
+    Any,
+    /// A byte in `classes[n]`
+    Class(u8),
+    /// Continue at both offsets, preferring the first
+    Split(i16, i16),
+    Jmp(i16),
+    /// Record the position in capture slot `n`
+    Save(u8),
+    Assert(Assert),
+    /// The text of group `n` again
+    Backref(u8),
+    Match,
+}
+
+/// A set of bytes
+type Set = [u8; 32];
+
+/// A compiled regular expression
+pub struct Regex {
+    prog: [Inst; MAX_INSTS],
+    len: usize,
+    classes: [Set; MAX_CLASSES],
+    nclasses: usize,
+    groups: usize,
+    backrefs: bool,
+    icase: bool,
+    /// Number of `Byte` instructions the program starts with, which every
+    /// match begins with
+    prefix: usize,
+    /// The program starts with `^`, so it can only match at 0
+    anchored: bool,
+    /// Bytes a match can start with, unless it can be `empty`
+    first: Set,
+    /// Whether a match can be found without consuming a byte
+    empty: bool,
+}
+
+/// Positions of the whole match and of each group, from `captures_at`
+#[derive(Clone, Copy)]
+pub struct Captures {
+    slots: [u32; SLOTS],
+}
+
+impl Default for Captures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Captures {
+    /// Captures with nothing matched
+    pub const fn new() -> Self {
+        Captures { slots: [NONE; SLOTS] }
+    }
+
+    /// Byte range of `group` (0 for the whole match), if it took part in
+    /// the match
+    pub fn get(&self, group: usize) -> Option<(usize, usize)> {
+        let (start, end) = (*self.slots.get(2 * group)?, self.slots[2 * group + 1]);
+        if start == NONE || end == NONE {
+            return None;
+        }
+        Some((start as usize, end as usize))
+    }
+}
+
+impl Regex {
+    /// Compile `pattern`, a BRE unless `flags` has `EXTENDED`.
+    ///
+    /// Errors are glibc's `regerror` messages.
+    pub fn new(pattern: &[u8], flags: u32) -> Result<Regex, &'static [u8]> {
+        let mut re = Regex {
+            prog: [Inst::Match; MAX_INSTS],
+            len: 0,
+            classes: [[0; 32]; MAX_CLASSES],
+            nclasses: 0,
+            groups: 0,
+            backrefs: false,
+            icase: flags & ICASE != 0,
+            prefix: 0,
+            anchored: false,
+            first: [0; 32],
+            empty: false,
+        };
+        let mut parser = Parser {
+            re: &mut re,
+            pat: pattern,
+            pos: 0,
+            ere: flags & EXTENDED != 0,
+            groups: 0,
+            closed: 0,
+            depth: 0,
+        };
+        parser.alt()?;
+        if parser.pos < pattern.len() {
+            return Err(UNMATCHED_CLOSE);
+        }
+        parser.emit(Inst::Match)?;
+        re.groups = parser.groups.min(MAX_GROUPS - 1);
+        re.prefix = re.prog.iter().take_while(|inst| matches!(inst, Inst::Byte(_))).count();
+        re.anchored = re.prog[0] == Inst::Assert(Assert::LineStart);
+        let mut first = [0; 32];
+        re.empty = re.first_bytes(0, &mut first, &mut [false; MAX_INSTS]);
+        re.first = first;
+        Ok(re)
+    }
+
+    /// Number of parenthesized groups that can be captured (at most 9)
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// Whether the pattern matches anywhere in `text`
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        if self.backrefs {
+            return self.backtrack(text, 0, &mut Captures::new());
+        }
+        if self.is_literal() {
+            return self.find_prefix(text, 0).is_some();
+        }
+        let mut best = [NONE; 2];
+        self.pike(text, 0, &mut best, true)
+    }
+
+    /// The leftmost-longest match starting at or after `start`.
+    ///
+    /// `^`, `\<` and the like see the whole of `text`, so `^` never matches
+    /// after `start` > 0.
+    pub fn find_at(&self, text: &[u8], start: usize) -> Option<(usize, usize)> {
+        if self.is_literal() {
+            return self.find_prefix(text, start).map(|at| (at, at + self.prefix));
+        }
+        let mut caps = Captures::new();
+        if self.backrefs {
+            if !self.backtrack(text, start, &mut caps) {
+                return None;
+            }
+        } else {
+            let mut best = [NONE; 2];
+            if !self.pike(text, start, &mut best, false) {
+                return None;
+            }
+            caps.slots[..2].copy_from_slice(&best);
+        }
+        caps.get(0)
+    }
+
+    /// Like `find_at`, also recording where each group matched
+    pub fn captures_at(&self, text: &[u8], start: usize, caps: &mut Captures) -> bool {
+        *caps = Captures::new();
+        if self.backrefs {
+            return self.backtrack(text, start, caps);
+        }
+        // Without groups only the whole match needs tracking
+        if self.groups == 0 {
+            let found = self.find_at(text, start);
+            if let Some((from, to)) = found {
+                caps.slots[0] = from as u32;
+                caps.slots[1] = to as u32;
+            }
+            return found.is_some();
+        }
+        self.pike(text, start, &mut caps.slots, false)
+    }
+
+    /// Whether the program is just its literal prefix
+    fn is_literal(&self) -> bool {
+        self.prog[self.prefix] == Inst::Match
+    }
+
+    /// Add the bytes the thread at `pc` can consume first to `set`, giving
+    /// true if it can reach `Match` without consuming one. Assertions are
+    /// taken to hold.
+    fn first_bytes(&self, pc: usize, set: &mut Set, seen: &mut [bool; MAX_INSTS]) -> bool {
+        if seen[pc] {
+            return false;
+        }
+        seen[pc] = true;
+        match self.prog[pc] {
+            Inst::Byte(b) => {
+                add(set, b);
+                if self.icase {
+                    add(set, b.to_ascii_uppercase());
+                }
+                false
+            }
+            Inst::Any => {
+                *set = [0xff; 32];
+                false
+            }
+            Inst::Class(n) => {
+                for (byte, class) in set.iter_mut().zip(&self.classes[n as usize]) {
+                    *byte |= class;
+                }
+                false
+            }
+            Inst::Split(a, b) => {
+                let a = self.first_bytes(offset(pc, a), set, seen);
+                self.first_bytes(offset(pc, b), set, seen) || a
+            }
+            Inst::Jmp(off) => self.first_bytes(offset(pc, off), set, seen),
+            Inst::Save(_) | Inst::Assert(_) => self.first_bytes(pc + 1, set, seen),
+            // A back-reference can be empty
+            Inst::Backref(_) | Inst::Match => true,
+        }
+    }
+
+    /// Where the next byte that can start a match is, at or after `from`
+    fn find_first(&self, text: &[u8], from: usize) -> Option<usize> {
+        if self.prefix > 0 {
+            return self.find_prefix(text, from);
+        }
+        if self.empty {
+            return (from <= text.len()).then_some(from);
+        }
+        (from..text.len()).find(|&at| has(&self.first, text[at]))
+    }
+
+    /// Where the literal prefix next occurs at or after `from`
+    fn find_prefix(&self, text: &[u8], from: usize) -> Option<usize> {
+        let byte = |i: usize| match self.prog[i] {
+            Inst::Byte(b) => b,
+            _ => unreachable!(),
+        };
+        let fold = |c: u8| if self.icase { c.to_ascii_lowercase() } else { c };
+        if self.prefix == 0 {
+            return (from <= text.len()).then_some(from);
+        }
+        let first = byte(0);
+        let last = text.len().checked_sub(self.prefix)?;
+        (from..=last).find(|&at| {
+            fold(text[at]) == first && (1..self.prefix).all(|i| fold(text[at + i]) == byte(i))
+        })
+    }
+
+    /// Whether `byte` satisfies the consuming instruction at `pc`
+    fn consumes(&self, pc: usize, byte: u8) -> bool {
+        match self.prog[pc] {
+            Inst::Byte(b) => b == if self.icase { byte.to_ascii_lowercase() } else { byte },
+            Inst::Any => true,
+            Inst::Class(n) => has(&self.classes[n as usize], byte),
+            _ => false,
+        }
+    }
+
+    fn check(&self, assert: Assert, text: &[u8], pos: usize) -> bool {
+        let before = pos > 0 && is_word(text[pos - 1]);
+        let after = pos < text.len() && is_word(text[pos]);
+        match assert {
+            Assert::LineStart => pos == 0,
+            Assert::LineEnd => pos == text.len(),
+            Assert::WordStart => !before && after,
+            Assert::WordEnd => before && !after,
+            Assert::WordBoundary => before != after,
+            Assert::NotWordBoundary => before == after,
+        }
+    }
+
+    /// Run the Pike VM from `start`, keeping the first `N` capture slots.
+    ///
+    /// On a match, `best` holds its slots and true is returned. With
+    /// `first`, stop at the first match found instead of the longest.
+    fn pike<const N: usize>(&self, text: &[u8], start: usize, best: &mut [u32; N], first: bool) -> bool {
+        // Thread lists no bigger than the program needs: every page of them
+        // is touched on each call, even if only a few entries are used
+        match self.len {
+            0..=64 => self.run::<N, 64>(text, start, best, first),
+            _ => self.run::<N, MAX_INSTS>(text, start, best, first),
+        }
+    }
+
+    // Not inlined, so a call only reserves stack for the lists it uses
+    #[inline(never)]
+    fn run<const N: usize, const CAP: usize>(&self, text: &[u8], start: usize, best: &mut [u32; N], first: bool) -> bool {
+        let (mut a, mut b) = (Threads::<N, CAP>::new(self.len), Threads::<N, CAP>::new(self.len));
+        let (mut clist, mut nlist) = (&mut a, &mut b);
+        let mut matched = false;
+        let mut pos = start;
+
+        loop {
+            // A new attempt at each position until something matches;
+            // later ones could only find matches further right
+            if !matched {
+                // With nothing under way, skip to where a match could start
+                if clist.len == 0 {
+                    if self.anchored && pos > 0 {
+                        break;
+                    }
+                    match self.find_first(text, pos) {
+                        Some(at) => pos = at,
+                        None => break,
+                    }
+                }
+                let mut caps = [NONE; N];
+                caps[0] = pos as u32;
+                self.add(clist, 0, text, pos, &mut caps);
+            }
+
+            for i in 0..clist.len {
+                let pc = clist.get(i);
+                // Only threads waiting on a byte or a match have captures
+                if !matches!(self.prog[pc], Inst::Byte(_) | Inst::Any | Inst::Class(_) | Inst::Match) {
+                    continue;
+                }
+                let mut caps = clist.caps(i);
+                // Threads that started after a match can't be leftmost
+                if matched && caps[0] > best[0] {
+                    continue;
+                }
+                if self.prog[pc] == Inst::Match {
+                    let better = !matched
+                        || caps[0] < best[0]
+                        || (caps[0] == best[0] && pos as u32 > best[1]);
+                    if better {
+                        caps[1] = pos as u32;
+                        *best = caps;
+                        matched = true;
+                        if first {
+                            return true;
+                        }
+                    }
+                } else if pos < text.len() && self.consumes(pc, text[pos]) {
+                    self.add(nlist, pc + 1, text, pos + 1, &mut caps);
+                }
+            }
+
+            if pos >= text.len() || (matched && nlist.len == 0) {
+                break;
+            }
+            core::mem::swap(&mut clist, &mut nlist);
+            nlist.len = 0;
+            pos += 1;
+        }
+        matched
+    }
+
+    /// Add the thread at `pc` to `list`, following jumps, splits, saves and
+    /// assertions to the instructions that consume a byte or match
+    fn add<const N: usize, const CAP: usize>(&self, list: &mut Threads<N, CAP>, mut pc: usize, text: &[u8], pos: usize, caps: &mut [u32; N]) {
+        // Follow single successors in a loop, recursing only for splits and
+        // saves that have to be undone
+        loop {
+            if list.contains(pc) {
+                return;
+            }
+            let i = list.insert(pc);
+            match self.prog[pc] {
+                Inst::Jmp(off) => pc = offset(pc, off),
+                Inst::Split(a, b) => {
+                    self.add(list, offset(pc, a), text, pos, caps);
+                    pc = offset(pc, b);
+                }
+                Inst::Save(n) if (n as usize) < N => {
+                    let old = caps[n as usize];
+                    caps[n as usize] = pos as u32;
+                    self.add(list, pc + 1, text, pos, caps);
+                    caps[n as usize] = old;
+                    return;
+                }
+                Inst::Save(_) => pc += 1,
+                Inst::Assert(a) => {
+                    if !self.check(a, text, pos) {
+                        return;
+                    }
+                    pc += 1;
+                }
+                _ => {
+                    list.set_caps(i, *caps);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Find the leftmost-longest match from `start` by trying every way
+    /// through the program; needed for back-references
+    #[inline(never)]
+    fn backtrack(&self, text: &[u8], start: usize, caps: &mut Captures) -> bool {
+        let mut state = Backtrack {
+            best: Captures::new(),
+            found: false,
+            split_pos: [NONE; MAX_INSTS],
+        };
+        for s in start..=text.len() {
+            let mut slots = [NONE; SLOTS];
+            slots[0] = s as u32;
+            self.bt(0, s, text, &mut slots, &mut state, 0);
+            if state.found {
+                *caps = state.best;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn bt(&self, mut pc: usize, mut pos: usize, text: &[u8], slots: &mut [u32; SLOTS], state: &mut Backtrack, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        loop {
+            match self.prog[pc] {
+                Inst::Byte(_) | Inst::Any | Inst::Class(_) => {
+                    if pos >= text.len() || !self.consumes(pc, text[pos]) {
+                        return;
+                    }
+                    pc += 1;
+                    pos += 1;
+                }
+                Inst::Backref(n) => {
+                    let (s, e) = (slots[2 * n as usize], slots[2 * n as usize + 1]);
+                    if s == NONE || e == NONE {
+                        return;
+                    }
+                    let group = &text[s as usize..e as usize];
+                    let rest = &text[pos..];
+                    let same = rest.len() >= group.len() && if self.icase {
+                        rest[..group.len()].eq_ignore_ascii_case(group)
+                    } else {
+                        &rest[..group.len()] == group
+                    };
+                    if !same {
+                        return;
+                    }
+                    pc += 1;
+                    pos += group.len();
+                }
+                Inst::Assert(a) => {
+                    if !self.check(a, text, pos) {
+                        return;
+                    }
+                    pc += 1;
+                }
+                Inst::Jmp(off) => pc = offset(pc, off),
+                Inst::Split(a, b) => {
+                    // Back at a split without having moved: an empty loop
+                    let old = state.split_pos[pc];
+                    if old == pos as u32 {
+                        return;
+                    }
+                    state.split_pos[pc] = pos as u32;
+                    self.bt(offset(pc, a), pos, text, slots, state, depth + 1);
+                    self.bt(offset(pc, b), pos, text, slots, state, depth + 1);
+                    state.split_pos[pc] = old;
+                    return;
+                }
+                Inst::Save(n) => {
+                    let old = slots[n as usize];
+                    slots[n as usize] = pos as u32;
+                    self.bt(pc + 1, pos, text, slots, state, depth + 1);
+                    slots[n as usize] = old;
+                    return;
+                }
+                Inst::Match => {
+                    if !state.found || pos as u32 > state.best.slots[1] {
+                        state.best.slots = *slots;
+                        state.best.slots[1] = pos as u32;
+                        state.found = true;
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// State shared across the backtracking matcher's recursion
+struct Backtrack {
+    best: Captures,
+    found: bool,
+    /// Position each split was last entered at on the current path
+    split_pos: [u32; MAX_INSTS],
+}
+
+/// The Pike VM's thread list: a sparse set of program counters, each with
+/// its capture slots.
+///
+/// Room for `CAP` threads, at least the program's `insts` instructions. Only
+/// the part of `sparse` the program can index is initialized, once per
+/// search; the list is emptied by setting `len` to 0. `dense` is only read
+/// below `len`, and `caps` only where `set_caps` wrote it, so neither is
+/// ever cleared.
+struct Threads<const N: usize, const CAP: usize> {
+    dense: [MaybeUninit<u16>; CAP],
+    sparse: [MaybeUninit<u16>; CAP],
+    caps: [MaybeUninit<[u32; N]>; CAP],
+    insts: usize,
+    len: usize,
+}
+
+impl<const N: usize, const CAP: usize> Threads<N, CAP> {
+    fn new(insts: usize) -> Self {
+        let mut threads = Threads {
+            dense: [const { MaybeUninit::uninit() }; CAP],
+            sparse: [const { MaybeUninit::uninit() }; CAP],
+            caps: [const { MaybeUninit::uninit() }; CAP],
+            insts,
+            len: 0,
+        };
+        for i in &mut threads.sparse[..insts] {
+            i.write(0);
+        }
+        threads
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        assert!(pc < self.insts);
+        // SAFETY: sparse[..insts] is initialized in `new`
+        let i = unsafe { self.sparse[pc].assume_init() } as usize;
+        i < self.len && self.get(i) == pc
+    }
+
+    fn insert(&mut self, pc: usize) -> usize {
+        let i = self.len;
+        self.dense[i].write(pc as u16);
+        self.sparse[pc].write(i as u16);
+        self.len += 1;
+        i
+    }
+
+    /// The program counter of thread `i`
+    fn get(&self, i: usize) -> usize {
+        assert!(i < self.len);
+        // SAFETY: dense[..len] was written by `insert`
+        unsafe { self.dense[i].assume_init() as usize }
+    }
+
+    /// The captures of thread `i`, which must have been set
+    fn caps(&self, i: usize) -> [u32; N] {
+        assert!(i < self.len);
+        // SAFETY: the caller only asks for threads `add` gave captures, and
+        // `i` is one of this search's threads
+        unsafe { self.caps[i].assume_init() }
+    }
+
+    fn set_caps(&mut self, i: usize, caps: [u32; N]) {
+        self.caps[i].write(caps);
+    }
+}
+
+fn offset(pc: usize, off: i16) -> usize {
+    (pc as isize + off as isize) as usize
+}
+
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn has(set: &Set, c: u8) -> bool {
+    set[c as usize / 8] & (1 << (c % 8)) != 0
+}
+
+fn add(set: &mut Set, c: u8) {
+    set[c as usize / 8] |= 1 << (c % 8);
+}
+
+/// Add the bytes of POSIX class `name` to `set`; false if there is no such
+/// class
+fn add_class(set: &mut Set, name: &[u8]) -> bool {
+    let test = match class_test(name) {
+        Some(test) => test,
+        None => return false,
+    };
+    for c in 0..=255u8 {
+        if test(c) {
+            add(set, c);
+        }
+    }
+    true
+}
+
+/// The test for membership of POSIX class `name`, as in `[:name:]`
+pub(super) fn class_test(name: &[u8]) -> Option<fn(u8) -> bool> {
+    let test: fn(u8) -> bool = match name {
+        b"alpha" => |c| c.is_ascii_alphabetic(),
+        b"digit" => |c| c.is_ascii_digit(),
+        b"alnum" => |c| c.is_ascii_alphanumeric(),
+        b"upper" => |c| c.is_ascii_uppercase(),
+        b"lower" => |c| c.is_ascii_lowercase(),
+        b"space" => |c| b" \t\n\r\x0b\x0c".contains(&c),
+        b"blank" => |c| c == b' ' || c == b'\t',
+        b"punct" => |c| c.is_ascii_punctuation(),
+        b"print" => |c| (0x20..0x7f).contains(&c),
+        b"graph" => |c| c.is_ascii_graphic(),
+        b"cntrl" => |c| c.is_ascii_control(),
+        b"xdigit" => |c| c.is_ascii_hexdigit(),
+        _ => return None,
+    };
+    Some(test)
+}
+
+/// Recursive-descent compiler from pattern to program
+struct Parser<'r, 'p> {
+    re: &'r mut Regex,
+    pat: &'p [u8],
+    pos: usize,
+    ere: bool,
+    /// Groups opened so far
+    groups: usize,
+    /// Bit `n` set once group `n` is closed, so `\n` may refer to it
+    closed: u16,
+    /// Groups currently open
+    depth: usize,
+}
+
+impl<'p> Parser<'_, 'p> {
+    fn peek(&self) -> Option<u8> {
+        self.pat.get(self.pos).copied()
+    }
+
+    fn peek2(&self) -> Option<u8> {
+        self.pat.get(self.pos + 1).copied()
+    }
+
+    /// Whether the next token is `\` followed by `c`
+    fn at_escaped(&self, c: u8) -> bool {
+        self.peek() == Some(b'\\') && self.peek2() == Some(c)
+    }
+
+    fn at_bar(&self) -> bool {
+        if self.ere { self.peek() == Some(b'|') } else { self.at_escaped(b'|') }
+    }
+
+    fn at_close(&self) -> bool {
+        if self.ere {
+            self.peek() == Some(b')') && self.depth > 0
+        } else {
+            self.at_escaped(b')')
+        }
+    }
+
+    fn emit(&mut self, inst: Inst) -> Result<usize, &'static [u8]> {
+        if self.re.len >= MAX_INSTS {
+            return Err(TOO_BIG);
+        }
+        self.re.prog[self.re.len] = inst;
+        self.re.len += 1;
+        Ok(self.re.len - 1)
+    }
+
+    /// Insert `inst` at `at`, moving the code after it along. Only the
+    /// code being built is moved, and its jumps are relative.
+    fn insert(&mut self, at: usize, inst: Inst) -> Result<(), &'static [u8]> {
+        if self.re.len >= MAX_INSTS {
+            return Err(TOO_BIG);
+        }
+        self.re.prog.copy_within(at..self.re.len, at + 1);
+        self.re.prog[at] = inst;
+        self.re.len += 1;
+        Ok(())
+    }
+
+    /// `alt := seq ('|' alt)?`
+    fn alt(&mut self) -> Result<(), &'static [u8]> {
+        let start = self.re.len;
+        self.seq()?;
+        if self.at_bar() {
+            self.pos += if self.ere { 1 } else { 2 };
+            let jmp = self.emit(Inst::Jmp(0))?;
+            self.insert(start, Inst::Split(1, (jmp + 2 - start) as i16))?;
+            self.alt()?;
+            let jmp = jmp + 1;
+            self.re.prog[jmp] = Inst::Jmp((self.re.len - jmp) as i16);
+        }
+        Ok(())
+    }
+
+    /// A sequence of repeated atoms, up to `|`, a closing parenthesis or
+    /// the end
+    fn seq(&mut self) -> Result<(), &'static [u8]> {
+        let seq_start = self.pos;
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Ok(()),
+            };
+            if self.at_bar() || self.at_close() {
+                return Ok(());
+            }
+
+            // A leading `*` (after any `^`) is literal, as are ERE `+`, `?`
+            // and `{` there
+            let leading = self.pos == seq_start
+                || (self.pos == seq_start + 1 && self.pat[seq_start] == b'^');
+            let atom = self.re.len;
+            if leading && (c == b'*' || (self.ere && matches!(c, b'+' | b'?' | b'{'))) {
+                self.pos += 1;
+                self.literal(c)?;
+            } else {
+                self.atom(seq_start)?;
+            }
+            self.repeats(atom)?;
+        }
+    }
+
+    fn atom(&mut self, seq_start: usize) -> Result<(), &'static [u8]> {
+        let c = self.pat[self.pos];
+        self.pos += 1;
+        match c {
+            b'.' => {
+                self.emit(Inst::Any)?;
+            }
+            b'[' => self.bracket()?,
+            b'(' if self.ere => self.group()?,
+            b'^' if self.ere || self.pos - 1 == seq_start => {
+                self.emit(Inst::Assert(Assert::LineStart))?;
+            }
+            b'$' if self.ere || self.at_seq_end() => {
+                self.emit(Inst::Assert(Assert::LineEnd))?;
+            }
+            b'\\' => self.escape()?,
+            _ => self.literal(c)?,
+        }
+        Ok(())
+    }
+
+    /// Whether a BRE `$` just read ends its sequence, making it an anchor
+    fn at_seq_end(&self) -> bool {
+        self.peek().is_none() || self.at_escaped(b')') || self.at_escaped(b'|')
+    }
+
+    fn escape(&mut self) -> Result<(), &'static [u8]> {
+        let c = self.peek().ok_or(TRAILING_BACKSLASH)?;
+        self.pos += 1;
+        let assert = match c {
+            b'(' if !self.ere => return self.group(),
+            b'{' if !self.ere => return self.literal(b'{'),
+            b'1'..=b'9' => {
+                let n = c - b'0';
+                if self.closed & (1 << n) == 0 {
+                    return Err(BAD_BACKREF);
+                }
+                self.re.backrefs = true;
+                self.emit(Inst::Backref(n))?;
+                return Ok(());
+            }
+            b'w' | b'W' | b's' | b'S' => {
+                let mut set = [0; 32];
+                add_class(&mut set, if c.eq_ignore_ascii_case(&b'w') { b"alnum" } else { b"space" });
+                if c.eq_ignore_ascii_case(&b'w') {
+                    add(&mut set, b'_');
+                }
+                return self.class(set, c.is_ascii_uppercase());
+            }
+            b'n' => return self.literal(b'\n'),
+            b't' => return self.literal(b'\t'),
+            b'<' => Assert::WordStart,
+            b'>' => Assert::WordEnd,
+            b'b' => Assert::WordBoundary,
+            b'B' => Assert::NotWordBoundary,
+            _ => return self.literal(c),
+        };
+        self.emit(Inst::Assert(assert))?;
+        Ok(())
+    }
+
+    fn literal(&mut self, c: u8) -> Result<(), &'static [u8]> {
+        let c = if self.re.icase { c.to_ascii_lowercase() } else { c };
+        self.emit(Inst::Byte(c))?;
+        Ok(())
+    }
+
+    /// A group, after its opening parenthesis
+    fn group(&mut self) -> Result<(), &'static [u8]> {
+        self.groups += 1;
+        let n = self.groups;
+        if n < MAX_GROUPS {
+            self.emit(Inst::Save(2 * n as u8))?;
+        }
+        self.depth += 1;
+        self.alt()?;
+        self.depth -= 1;
+
+        if self.ere && self.peek() == Some(b')') {
+            self.pos += 1;
+        } else if !self.ere && self.at_escaped(b')') {
+            self.pos += 2;
+        } else {
+            return Err(UNMATCHED_PAREN);
+        }
+        if n < MAX_GROUPS {
+            self.emit(Inst::Save(2 * n as u8 + 1))?;
+            self.closed |= 1 << n;
+        }
+        Ok(())
+    }
+
+    /// A bracket expression, after its `[`
+    fn bracket(&mut self) -> Result<(), &'static [u8]> {
+        let mut set = [0; 32];
+        let negate = self.peek() == Some(b'^');
+        if negate {
+            self.pos += 1;
+        }
+
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or(UNMATCHED_BRACKET)?;
+            self.pos += 1;
+            if c == b']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = if c == b'[' && matches!(self.peek(), Some(b':' | b'=' | b'.')) {
+                let kind = self.pat[self.pos];
+                self.pos += 1;
+                let name = self.bracket_name(kind)?;
+                if kind == b':' {
+                    if !add_class(&mut set, name) {
+                        return Err(BAD_CLASS);
+                    }
+                    continue;
+                }
+                match name {
+                    [c] => *c,
+                    _ => return Err(BAD_COLLATE),
+                }
+            } else {
+                c
+            };
+
+            // A `-` before the closing `]` is literal
+            if self.peek() == Some(b'-') && self.peek2().is_some_and(|c| c != b']') {
+                self.pos += 1;
+                let mut hi = self.pat[self.pos];
+                self.pos += 1;
+                if hi == b'[' && self.peek() == Some(b'.') {
+                    self.pos += 1;
+                    hi = match self.bracket_name(b'.')? {
+                        [c] => *c,
+                        _ => return Err(BAD_COLLATE),
+                    };
+                }
+                if hi < lo {
+                    return Err(BAD_RANGE);
+                }
+                for c in lo..=hi {
+                    add(&mut set, c);
+                }
+            } else {
+                add(&mut set, lo);
+            }
+        }
+        self.class(set, negate)
+    }
+
+    /// The name in `[:name:]`, `[=c=]` or `[.c.]`, after its `[` and `kind`
+    fn bracket_name(&mut self, kind: u8) -> Result<&'p [u8], &'static [u8]> {
+        let rest = &self.pat[self.pos..];
+        let end = rest
+            .windows(2)
+            .position(|w| w[0] == kind && w[1] == b']')
+            .ok_or(UNMATCHED_BRACKET)?;
+        self.pos += end + 2;
+        Ok(&rest[..end])
+    }
+
+    /// Emit a match against `set`, folded for case and maybe negated
+    fn class(&mut self, mut set: Set, negate: bool) -> Result<(), &'static [u8]> {
+        if self.re.icase {
+            for c in b'a'..=b'z' {
+                if has(&set, c) || has(&set, c.to_ascii_uppercase()) {
+                    add(&mut set, c);
+                    add(&mut set, c.to_ascii_uppercase());
+                }
+            }
+        }
+        if negate {
+            for byte in &mut set {
+                *byte = !*byte;
+            }
+        }
+
+        let classes = &mut self.re.classes[..self.re.nclasses];
+        let n = match classes.iter().position(|c| *c == set) {
+            Some(n) => n,
+            None if self.re.nclasses < MAX_CLASSES => {
+                self.re.classes[self.re.nclasses] = set;
+                self.re.nclasses += 1;
+                self.re.nclasses - 1
+            }
+            None => return Err(TOO_BIG),
+        };
+        self.emit(Inst::Class(n as u8))?;
+        Ok(())
+    }
+
+    /// Apply any `*`, `+`, `?` and intervals that follow the atom starting
+    /// at `atom`
+    fn repeats(&mut self, atom: usize) -> Result<(), &'static [u8]> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some(b'*') => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                Some(b'+') if self.ere => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                Some(b'?') if self.ere => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                Some(b'{') if self.ere => match self.interval()? {
+                    Some(interval) => interval,
+                    None => return Ok(()),
+                },
+                Some(b'\\') if !self.ere => match self.peek2() {
+                    Some(b'+') => {
+                        self.pos += 2;
+                        (1, None)
+                    }
+                    Some(b'?') => {
+                        self.pos += 2;
+                        (0, Some(1))
+                    }
+                    Some(b'{') => match self.interval()? {
+                        Some(interval) => interval,
+                        None => return Ok(()),
+                    },
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+            self.repeat(atom, min, max)?;
+        }
+    }
+
+    /// Parse `{m}`, `{m,}` or `{m,n}` (`\{...\}` in a BRE) at `pos`.
+    ///
+    /// An ERE `{` that doesn't start an interval is left to be literal,
+    /// giving `None`.
+    fn interval(&mut self) -> Result<Option<(u32, Option<u32>)>, &'static [u8]> {
+        let start = self.pos;
+        self.pos += if self.ere { 1 } else { 2 };
+        let parsed = self.interval_body();
+        if parsed.is_none() && self.ere {
+            self.pos = start;
+            return Ok(None);
+        }
+        let (min, max) = parsed.ok_or(if self.pos >= self.pat.len() { UNMATCHED_BRACE } else { BAD_BRACE })?;
+        if max.is_some_and(|max| max < min) {
+            return Err(BAD_BRACE);
+        }
+        if min > DUP_MAX || max.is_some_and(|max| max > DUP_MAX) {
+            return Err(TOO_BIG);
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn interval_body(&mut self) -> Option<(u32, Option<u32>)> {
+        // `{,n}` is a GNU extension for `{0,n}`
+        let min = if self.peek() == Some(b',') { 0 } else { self.number()? };
+        let max = if self.peek() == Some(b',') {
+            self.pos += 1;
+            if self.peek().is_some_and(|c| c.is_ascii_digit()) { Some(self.number()?) } else { None }
+        } else {
+            Some(min)
+        };
+        if self.ere && self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else if !self.ere && self.at_escaped(b'}') {
+            self.pos += 2;
+        } else {
+            return None;
+        }
+        Some((min, max))
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        let mut n: u32 = 0;
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+            n = n.saturating_mul(10).saturating_add((c - b'0') as u32);
+            self.pos += 1;
+        }
+        if self.pos == start { None } else { Some(n) }
+    }
+
+    /// Repeat the code from `atom` to the end `min` to `max` times
+    fn repeat(&mut self, atom: usize, min: u32, max: Option<u32>) -> Result<(), &'static [u8]> {
+        let k = self.re.len - atom;
+        if k == 0 {
+            return Ok(());
+        }
+        let mut code = [Inst::Match; MAX_INSTS];
+        code[..k].copy_from_slice(&self.re.prog[atom..self.re.len]);
+        self.re.len = atom;
+
+        for _ in 0..min {
+            self.copy(&code[..k])?;
+        }
+        match max {
+            None if min == 0 => {
+                // x*: split over the atom, jump back to the split
+                self.copy(&code[..k])?;
+                self.insert(atom, Inst::Split(1, k as i16 + 2))?;
+                self.emit(Inst::Jmp(-(k as i16 + 1)))?;
+            }
+            None => {
+                // x{m,}: the last copy loops
+                self.emit(Inst::Split(-(k as i16), 1))?;
+            }
+            Some(max) => {
+                for _ in min..max {
+                    let start = self.re.len;
+                    self.copy(&code[..k])?;
+                    self.insert(start, Inst::Split(1, k as i16 + 1))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn copy(&mut self, code: &[Inst]) -> Result<(), &'static [u8]> {
+        if self.re.len + code.len() > MAX_INSTS {
+            return Err(TOO_BIG);
+        }
+        self.re.prog[self.re.len..self.re.len + code.len()].copy_from_slice(code);
+        self.re.len += code.len();
+        Ok(())
+    }
+}
@@ -2,7 +2,15 @@
 
 use crate::io;
 use crate::sys;
-use super::{get_arg, has_opt};
+use super::accounts;
+use super::datetime;
+use super::json::Json;
+use super::procfs;
+use super::regex::{self, Regex};
+use super::terminal;
+use super::utmp;
+use super::{exec, get_arg, usage, Arg, Getopt};
+use super::{EXIT_CANNOT_EXECUTE, EXIT_TROUBLE};
 
 pub fn uname(argc: i32, argv: *const *const u8) -> i32 {
     let mut show_all = false;
@@ -11,13 +19,16 @@ pub fn uname(argc: i32, argv: *const *const u8) -> i32 {
     let mut show_r = false;
     let mut show_m = false;
     
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'a') { show_all = true; }
-            if has_opt(arg, b's') { show_s = true; }
-            if has_opt(arg, b'n') { show_n = true; }
-            if has_opt(arg, b'r') { show_r = true; }
-            if has_opt(arg, b'm') { show_m = true; }
+    let spec = b"a(all)s(kernel-name)n(nodename)r(kernel-release)m(machine)";
+    for arg in Getopt::new(argc, &argv, spec) {
+        match arg {
+            Arg::Opt(b"a", _) => show_all = true,
+            Arg::Opt(b"s", _) => show_s = true,
+            Arg::Opt(b"n", _) => show_n = true,
+            Arg::Opt(b"r", _) => show_r = true,
+            Arg::Opt(b"m", _) => show_m = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
     
@@ -36,12 +47,14 @@ pub fn uname(argc: i32, argv: *const *const u8) -> i32 {
 }
 
 pub fn hostname(argc: i32, argv: *const *const u8) -> i32 {
-    if argc > 1 {
-        if let Some(name) = unsafe { get_arg(argv, 1) } {
-            if unsafe { libc::sethostname(name.as_ptr() as *const i8, name.len()) } < 0 {
-                sys::perror(b"sethostname");
-                return 1;
-            }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if let Some(name) = opts.operand(0) {
+        if unsafe { libc::sethostname(name.as_ptr() as *const i8, name.len()) } < 0 {
+            sys::perror(b"hostname", b"sethostname");
+            return 1;
         }
     } else {
         let mut buf = [0u8; 256];
@@ -53,57 +66,846 @@ pub fn hostname(argc: i32, argv: *const *const u8) -> i32 {
     0
 }
 
-pub fn whoami(_argc: i32, _argv: *const *const u8) -> i32 {
-    let uid = unsafe { libc::getuid() };
-    let pwd = unsafe { libc::getpwuid(uid) };
-    if !pwd.is_null() {
-        let name = unsafe { io::cstr_to_slice((*pwd).pw_name as *const u8) };
-        io::write_all(1, name);
-        io::write_str(1, b"\n");
+/// whoami - print effective user name
+pub fn whoami(argc: i32, argv: *const *const u8) -> i32 {
+    if Getopt::no_options(argc, &argv).is_none() {
+        return 1;
+    }
+    let out = io::stdout();
+    let uid = io::geteuid();
+    let users = accounts::Users::open();
+    match users.as_ref().and_then(|users| users.getpwuid(uid)) {
+        Some(pw) => {
+            out.write_all(pw.name);
+            out.write_str(b"\n");
+            0
+        }
+        None => {
+            let mut buf = [0u8; 20];
+            sys::error_at(b"whoami", b"unknown uid", sys::format_u64(uid as u64, &mut buf));
+            1
+        }
+    }
+}
+
+/// The ids `id` and `groups` report on: a user named on the command line,
+/// or this process
+struct Ids {
+    uid: u32,
+    gid: u32,
+    euid: u32,
+    egid: u32,
+    /// Group list, starting with the (effective) primary group
+    groups: [u32; 256],
+    count: usize,
+}
+
+impl Ids {
+    /// The ids of `user`, a name or number, or of this process if `None`.
+    /// Prints an error and returns `None` if there is no such user.
+    fn find(applet: &[u8], user: Option<&[u8]>, users: Option<&accounts::Users>, groups: Option<&accounts::Groups>) -> Option<Ids> {
+        let mut ids = Ids { uid: 0, gid: 0, euid: 0, egid: 0, groups: [0; 256], count: 0 };
+        match user {
+            Some(user) => {
+                let pw = users.and_then(|users| {
+                    users.getpwnam(user).or_else(|| sys::parse_u64(user).and_then(|uid| users.getpwuid(uid as u32)))
+                });
+                let Some(pw) = pw else {
+                    sys::error_at(applet, user, b"no such user");
+                    return None;
+                };
+                (ids.uid, ids.gid, ids.euid, ids.egid) = (pw.uid, pw.gid, pw.uid, pw.gid);
+                ids.count = match groups {
+                    Some(groups) => groups.getgrouplist(pw.name, pw.gid, &mut ids.groups),
+                    None => {
+                        ids.groups[0] = pw.gid;
+                        1
+                    }
+                };
+            }
+            None => {
+                (ids.uid, ids.gid, ids.euid, ids.egid) = (io::getuid(), io::getgid(), io::geteuid(), io::getegid());
+                ids.groups[0] = ids.egid;
+                let mut list = [0 as libc::gid_t; 255];
+                let n = unsafe { libc::getgroups(list.len() as i32, list.as_mut_ptr()) };
+                ids.count = 1;
+                for &gid in &list[..n.max(0) as usize] {
+                    if gid != ids.egid {
+                        ids.groups[ids.count] = gid;
+                        ids.count += 1;
+                    }
+                }
+            }
+        }
+        ids.count = ids.count.min(ids.groups.len());
+        Some(ids)
+    }
+
+    fn groups(&self) -> &[u32] {
+        &self.groups[..self.count]
     }
-    0
 }
 
+/// id - print user and group IDs
 pub fn id(argc: i32, argv: *const *const u8) -> i32 {
-    let uid = unsafe { libc::getuid() };
-    let gid = unsafe { libc::getgid() };
-    let euid = unsafe { libc::geteuid() };
-    let egid = unsafe { libc::getegid() };
-    
-    let _ = argc; let _ = argv;
-    
-    io::write_str(1, b"uid="); io::write_num(1, uid as u64);
-    io::write_str(1, b" gid="); io::write_num(1, gid as u64);
-    if euid != uid { io::write_str(1, b" euid="); io::write_num(1, euid as u64); }
-    if egid != gid { io::write_str(1, b" egid="); io::write_num(1, egid as u64); }
-    io::write_str(1, b"\n");
+    let out = io::stdout();
+    let mut only = 0u8;
+    let mut names = false;
+    let mut real = false;
+
+    let mut opts = Getopt::new(argc, &argv, b"u(user)g(group)G(groups)n(name)r(real)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"u", _) if only == 0 || only == b'u' => only = b'u',
+            Arg::Opt(b"g", _) if only == 0 || only == b'g' => only = b'g',
+            Arg::Opt(b"G", _) if only == 0 || only == b'G' => only = b'G',
+            Arg::Opt(b"u" | b"g" | b"G", _) => {
+                return usage::error(b"id", b"only one of -u, -g and -G may be given");
+            }
+            Arg::Opt(b"n", _) => names = true,
+            Arg::Opt(b"r", _) => real = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    if (names || real) && only == 0 {
+        return usage::error(b"id", b"-n and -r need -u, -g or -G");
+    }
+    if opts.operand_count() > 1 {
+        return usage::error(b"id", b"extra operand");
+    }
+
+    let users = accounts::Users::open();
+    let groups = accounts::Groups::open();
+    let Some(ids) = Ids::find(b"id", opts.operand(0), users.as_ref(), groups.as_ref()) else {
+        return 1;
+    };
+    let user_name = |uid: u32| users.as_ref().and_then(|users| users.getpwuid(uid)).map(|pw| pw.name);
+    let group_name = |gid: u32| groups.as_ref().and_then(|groups| groups.getgrgid(gid)).map(|gr| gr.name);
+
+    // One id, as a name with -n if it has one
+    let write_one = |id: u32, name: Option<&[u8]>| match name {
+        Some(name) if names => out.write_all(name),
+        _ => out.write_num(id as u64),
+    };
+    // `id(name)`, for the full report
+    let write_full = |id: u32, name: Option<&[u8]>| {
+        out.write_num(id as u64);
+        if let Some(name) = name {
+            out.write_str(b"(");
+            out.write_all(name);
+            out.write_str(b")");
+        }
+    };
+
+    let (uid, gid) = if real { (ids.uid, ids.gid) } else { (ids.euid, ids.egid) };
+    match only {
+        b'u' => write_one(uid, user_name(uid)),
+        b'g' => write_one(gid, group_name(gid)),
+        b'G' => {
+            for (i, &gid) in ids.groups().iter().enumerate() {
+                if i > 0 { out.write_str(b" "); }
+                write_one(gid, group_name(gid));
+            }
+        }
+        _ => {
+            out.write_str(b"uid=");
+            write_full(ids.uid, user_name(ids.uid));
+            out.write_str(b" gid=");
+            write_full(ids.gid, group_name(ids.gid));
+            if ids.euid != ids.uid {
+                out.write_str(b" euid=");
+                write_full(ids.euid, user_name(ids.euid));
+            }
+            if ids.egid != ids.gid {
+                out.write_str(b" egid=");
+                write_full(ids.egid, group_name(ids.egid));
+            }
+            out.write_str(b" groups=");
+            for (i, &gid) in ids.groups().iter().enumerate() {
+                if i > 0 { out.write_str(b","); }
+                write_full(gid, group_name(gid));
+            }
+        }
+    }
+    out.write_str(b"\n");
     0
 }
 
-pub fn groups(_argc: i32, _argv: *const *const u8) -> i32 {
-    let mut gids = [0u32; 32];
-    let n = unsafe { libc::getgroups(32, gids.as_mut_ptr()) };
-    if n > 0 {
-        for i in 0..n as usize {
-            io::write_num(1, gids[i] as u64);
-            io::write_str(1, b" ");
+/// groups - print the groups a user is in
+pub fn groups(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    let users = accounts::Users::open();
+    let groups = accounts::Groups::open();
+    let group_name = |gid: u32| groups.as_ref().and_then(|groups| groups.getgrgid(gid)).map(|gr| gr.name);
+
+    // Named users are labelled; without any, this process's groups aren't
+    let report = |user: Option<&[u8]>| {
+        let Some(ids) = Ids::find(b"groups", user, users.as_ref(), groups.as_ref()) else {
+            return false;
+        };
+        if let Some(user) = user {
+            out.write_all(user);
+            out.write_str(b" : ");
         }
-        io::write_str(1, b"\n");
+        for (i, &gid) in ids.groups().iter().enumerate() {
+            if i > 0 { out.write_str(b" "); }
+            match group_name(gid) {
+                Some(name) => out.write_all(name),
+                None => out.write_num(gid as u64),
+            }
+        }
+        out.write_str(b"\n");
+        true
+    };
+
+    if opts.operand_count() == 0 {
+        return if report(None) { 0 } else { 1 };
+    }
+    let mut status = 0;
+    for user in opts.operands() {
+        if !report(Some(user)) {
+            status = 1;
+        }
+    }
+    status
+}
+
+/// A line of `who`, `w`, `last`, `free` or `df` output being put together
+struct Row {
+    buf: [u8; 1024],
+    len: usize,
+}
+
+impl Row {
+    fn new() -> Row {
+        Row { buf: [0; 1024], len: 0 }
+    }
+
+    fn push(&mut self, s: &[u8]) {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s[..n]);
+        self.len += n;
+    }
+
+    fn num(&mut self, n: u64) {
+        let mut buf = [0u8; 20];
+        self.push(sys::format_u64(n, &mut buf));
+    }
+
+    /// Adds `s` padded to `width`, or right-aligned in it
+    fn column(&mut self, s: &[u8], width: usize, left: bool) {
+        for _ in s.len()..width {
+            if !left {
+                self.push(b" ");
+            }
+        }
+        self.push(s);
+        for _ in s.len()..width {
+            if left {
+                self.push(b" ");
+            }
+        }
+    }
+
+    fn time(&mut self, fmt: &[u8], tz: &datetime::TimeZone, t: i64) {
+        datetime::strftime(fmt, &tz.localtime(t), &mut |s| self.push(s));
+    }
+
+    /// Writes the line without trailing spaces
+    fn finish(&self, out: io::Stdout) {
+        let end = self.buf[..self.len].iter().rposition(|&c| c != b' ').map_or(0, |i| i + 1);
+        out.write_all(&self.buf[..end]);
+        out.write_byte(b'\n');
+    }
+}
+
+/// `/dev/LINE`'s status
+fn tty_stat(line: &[u8]) -> Option<libc::stat> {
+    let mut path = [0u8; 64];
+    let path = path.get_mut(..5 + line.len())?;
+    path[..5].copy_from_slice(b"/dev/");
+    path[5..].copy_from_slice(line);
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    if io::stat(path, &mut st) == 0 { Some(st) } else { None }
+}
+
+/// How long since a terminal was last used, as `who` shows it: `.` within
+/// the last minute, `old` after a day
+fn format_idle(idle: i64, buf: &mut [u8; 32]) -> &[u8] {
+    if idle < 60 {
+        return b".";
+    }
+    if idle >= 86400 {
+        return b"old";
+    }
+    let (hours, mins) = (idle / 3600, idle / 60 % 60);
+    buf[..5].copy_from_slice(&[b'0' + (hours / 10) as u8, b'0' + (hours % 10) as u8, b':', b'0' + (mins / 10) as u8, b'0' + (mins % 10) as u8]);
+    &buf[..5]
+}
+
+/// The terminal on stdin, without `/dev/`
+fn own_tty(buf: &mut [u8; 16]) -> Option<&[u8]> {
+    let p = procfs::Process::read(io::getpid())?;
+    if p.tty_nr == 0 {
+        return None;
+    }
+    Some(procfs::tty_name(p.tty_nr, buf))
+}
+
+/// who: show who is logged in
+pub fn who(argc: i32, argv: *const *const u8) -> i32 {
+    let (mut boot, mut dead, mut login, mut process, mut run_level, mut clock, mut users) = (false, false, false, false, false, false, false);
+    let (mut state, mut idle, mut heading, mut count) = (false, false, false, false);
+    let mut opts = Getopt::new(argc, &argv, b"abdHlpqrTtuw");
+    for arg in opts.by_ref() {
+        match arg {
+            Arg::Opt(b"a", _) => {
+                (boot, dead, login, process, run_level, clock, users) = (true, true, true, true, true, true, true);
+                (state, idle) = (true, true);
+            }
+            Arg::Opt(b"b", _) => boot = true,
+            Arg::Opt(b"d", _) => dead = true,
+            Arg::Opt(b"H", _) => heading = true,
+            Arg::Opt(b"l", _) => login = true,
+            Arg::Opt(b"p", _) => process = true,
+            Arg::Opt(b"q", _) => count = true,
+            Arg::Opt(b"r", _) => run_level = true,
+            Arg::Opt(b"T" | b"w", _) => state = true,
+            Arg::Opt(b"t", _) => clock = true,
+            Arg::Opt(b"u", _) => {
+                users = true;
+                idle = true;
+            }
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    if !(boot || dead || login || process || run_level || clock) {
+        users = true;
+    }
+
+    // `who am i` and the like show only the terminal on stdin
+    let mut tty = [0u8; 16];
+    let (path, only) = match opts.operand_count() {
+        0 => (None, None),
+        1 => (opts.operand(0), None),
+        2 => (None, own_tty(&mut tty).or(Some(b"?"))),
+        _ => return usage::error(b"who", b"extra operand"),
+    };
+    let file = match path {
+        Some(path) => utmp::File::open(path),
+        None => utmp::File::open_log(utmp::Log::Utmp),
+    };
+    let file = match (file, path) {
+        (Some(file), _) => file,
+        (None, Some(path)) => {
+            sys::perror(b"who", path);
+            return 1;
+        }
+        // No one has logged in since boot
+        (None, None) => return 0,
+    };
+
+    let out = io::stdout();
+    if count {
+        let mut n = 0;
+        for entry in file.iter().filter(|e| e.kind() == utmp::USER_PROCESS && !e.user().is_empty()) {
+            if n > 0 {
+                out.write_byte(b' ');
+            }
+            out.write_all(entry.user());
+            n += 1;
+        }
+        out.write_str(b"\n# users=");
+        out.write_num(n);
+        out.write_byte(b'\n');
+        out.flush();
+        return 0;
+    }
+
+    let tz = datetime::TimeZone::local();
+    let now = datetime::now().0;
+    // Writes one line; `idle_pid` is for `-u`, and `comment` follows
+    let show = |name: &[u8], mark: u8, line: &[u8], time: Option<i64>, idle_pid: Option<(&[u8], &[u8])>, comment: &[u8]| {
+        let mut row = Row::new();
+        row.column(name, 8, true);
+        if state {
+            row.push(b" ");
+            row.push(&[mark]);
+        }
+        row.push(b" ");
+        row.column(line, 12, true);
+        row.push(b" ");
+        match time {
+            Some(t) => row.time(b"%Y-%m-%d %H:%M", &tz, t),
+            None => row.column(b"TIME", 16, true),
+        }
+        if idle {
+            let (idle, pid) = idle_pid.unwrap_or((b"", b""));
+            row.push(b" ");
+            row.column(idle, 6, true);
+            row.column(pid, 11, false);
+        }
+        if !comment.is_empty() {
+            row.push(b" ");
+            row.push(comment);
+        }
+        row.finish(out);
+    };
+    if heading {
+        show(b"NAME", b' ', b"LINE", None, Some((b"IDLE", b"PID")), b"COMMENT");
+    }
+
+    for entry in file.iter() {
+        if only.is_some_and(|tty| entry.line() != tty) {
+            continue;
+        }
+        let mut pid_buf = [0u8; 32];
+        let pid = format_signed(entry.pid() as i64, &mut pid_buf);
+        let mut comment = Row::new();
+        let kind = entry.kind();
+        if matches!(kind, utmp::INIT_PROCESS | utmp::LOGIN_PROCESS | utmp::DEAD_PROCESS) {
+            comment.push(b"id=");
+            comment.push(entry.id());
+        }
+        match kind {
+            utmp::USER_PROCESS if users && !entry.user().is_empty() => {
+                let st = tty_stat(entry.line());
+                let mark = match st {
+                    Some(st) if st.st_mode & libc::S_IWGRP != 0 => b'+',
+                    Some(_) => b'-',
+                    None => b'?',
+                };
+                let mut idle_buf = [0u8; 32];
+                let idle = st.map_or(&b"?"[..], |st| format_idle(now - st.st_atime, &mut idle_buf));
+                if !entry.host().is_empty() {
+                    comment.push(b"(");
+                    comment.push(entry.host());
+                    comment.push(b")");
+                }
+                show(entry.user(), mark, entry.line(), Some(entry.time()), Some((idle, pid)), &comment.buf[..comment.len]);
+            }
+            utmp::BOOT_TIME if boot => show(b"", b' ', b"system boot", Some(entry.time()), None, b""),
+            utmp::RUN_LVL if run_level && entry.user() != b"shutdown" => {
+                let (level, last) = ((entry.pid() & 0xff) as u8, (entry.pid() >> 8 & 0xff) as u8);
+                let line = [b'r', b'u', b'n', b'-', b'l', b'e', b'v', b'e', b'l', b' ', level];
+                if last.is_ascii_graphic() && last != b'N' {
+                    comment.push(b"last=");
+                    comment.push(&[last]);
+                }
+                show(b"", b' ', &line, Some(entry.time()), None, &comment.buf[..comment.len]);
+            }
+            utmp::NEW_TIME if clock => show(b"", b' ', b"clock change", Some(entry.time()), None, b""),
+            utmp::INIT_PROCESS if process => show(b"", b' ', entry.line(), Some(entry.time()), Some((b"", pid)), &comment.buf[..comment.len]),
+            utmp::LOGIN_PROCESS if login => show(b"LOGIN", b' ', entry.line(), Some(entry.time()), Some((b"", pid)), &comment.buf[..comment.len]),
+            utmp::DEAD_PROCESS if dead => {
+                let (term, exit) = entry.exit();
+                comment.push(b" term=");
+                comment.num(term as u64);
+                comment.push(b" exit=");
+                comment.num(exit as u64);
+                show(b"", b' ', entry.line(), Some(entry.time()), Some((b"", pid)), &comment.buf[..comment.len]);
+            }
+            _ => {}
+        }
+    }
+    out.flush();
+    0
+}
+
+/// Formats a time in hundredths of a second as `w` shows it: seconds
+/// under a minute, then minutes and seconds, then hours and minutes, then
+/// days
+fn format_interval(hundredths: u64, buf: &mut [u8; 32]) -> &[u8] {
+    let secs = hundredths / 100;
+    let two = |n: u64| [b'0' + (n / 10 % 10) as u8, b'0' + (n % 10) as u8];
+    let mut row = Row::new();
+    if secs < 60 {
+        row.num(secs);
+        row.push(b".");
+        row.push(&two(hundredths % 100));
+        row.push(b"s");
+    } else if secs < 3600 {
+        row.num(secs / 60);
+        row.push(b":");
+        row.push(&two(secs % 60));
+    } else if secs < 48 * 3600 {
+        row.num(secs / 3600);
+        row.push(b":");
+        row.push(&two(secs / 60 % 60));
+        row.push(b"m");
+    } else {
+        row.num(secs / 86400);
+        row.push(b"days");
+    }
+    buf[..row.len].copy_from_slice(&row.buf[..row.len]);
+    &buf[..row.len]
+}
+
+/// Writes the time, how long the system has been up, how many users are
+/// logged in and the load averages, as `uptime` and the first lines of
+/// `w` and `top` show them
+fn uptime_summary(out: &mut dyn FnMut(&[u8]), tz: &datetime::TimeZone, uptime: u64) {
+    let mut row = Row::new();
+    row.time(b"%H:%M:%S up ", tz, datetime::now().0);
+    let (days, hours, mins) = (uptime / 8_640_000, uptime / 360_000 % 24, uptime / 6000 % 60);
+    if days > 0 {
+        row.num(days);
+        row.push(if days == 1 { b" day, " } else { b" days, " });
+    }
+    if hours > 0 {
+        row.column(&[b'0' + (hours / 10) as u8, b'0' + (hours % 10) as u8][(hours < 10) as usize..], 2, false);
+        row.push(&[b':', b'0' + (mins / 10) as u8, b'0' + (mins % 10) as u8]);
+    } else {
+        row.num(mins);
+        row.push(b" min");
+    }
+    let users = utmp::users();
+    row.push(b",  ");
+    row.num(users);
+    row.push(if users == 1 { b" user" } else { b" users" });
+
+    let mut buf = [0u8; 128];
+    let loadavg = procfs::read(b"loadavg", &mut buf).unwrap_or(b"");
+    row.push(b",  load average:");
+    for (i, load) in loadavg.split(|&c| c == b' ').take(3).enumerate() {
+        row.push(if i > 0 { b", " } else { b" " });
+        row.push(load);
+    }
+    out(&row.buf[..row.len]);
+}
+
+/// w: show who is logged in and what they are doing
+pub fn w(argc: i32, argv: *const *const u8) -> i32 {
+    let (mut header, mut short) = (true, false);
+    let mut opts = Getopt::new(argc, &argv, b"hs");
+    for arg in opts.by_ref() {
+        match arg {
+            Arg::Opt(b"h", _) => header = false,
+            Arg::Opt(b"s", _) => short = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    let only = opts.operand(0);
+    let out = io::stdout();
+    let tz = datetime::TimeZone::local();
+    let (now, _) = datetime::now();
+    let ticks = procfs::ticks();
+
+    if header {
+        out.write_byte(b' ');
+        uptime_summary(&mut |s| out.write_all(s), &tz, procfs::uptime().unwrap_or(0));
+        out.write_byte(b'\n');
+        out.write_str(if short {
+            b"USER     TTY      FROM              IDLE WHAT\n"
+        } else {
+            b"USER     TTY      FROM             LOGIN@   IDLE   JCPU   PCPU WHAT\n"
+        });
+    }
+    let file = match utmp::File::open_log(utmp::Log::Utmp) {
+        Some(file) => file,
+        None => {
+            out.flush();
+            return 0;
+        }
+    };
+
+    for entry in file.iter().filter(|e| e.kind() == utmp::USER_PROCESS && !e.user().is_empty()) {
+        if only.is_some_and(|user| entry.user() != user) {
+            continue;
+        }
+        let st = tty_stat(entry.line());
+        let dev = st.map_or(0, |st| st.st_rdev);
+
+        // Every process on the terminal counts towards JCPU; the
+        // foreground one is shown
+        let (mut total, mut current) = (0, None::<procfs::Process>);
+        if dev != 0 {
+            for pid in procfs::pids().into_iter().flatten() {
+                let p = match procfs::Process::read(pid) {
+                    Some(p) if p.tty_nr as u64 == dev => p,
+                    _ => continue,
+                };
+                total += p.cpu_time();
+                let foreground = p.pgrp == p.tpgid;
+                let better = match &current {
+                    None => true,
+                    Some(c) => foreground && (c.pgrp != c.tpgid || p.starttime > c.starttime),
+                };
+                if better {
+                    current = Some(p);
+                }
+            }
+        }
+        if current.is_none() {
+            current = procfs::Process::read(entry.pid());
+        }
+
+        let mut row = Row::new();
+        row.column(&entry.user()[..entry.user().len().min(8)], 9, true);
+        row.column(&entry.line()[..entry.line().len().min(8)], 9, true);
+        let host = if entry.host().is_empty() { &b"-"[..] } else { entry.host() };
+        row.column(&host[..host.len().min(16)], 16, true);
+        if !short {
+            row.push(b" ");
+            let login = entry.time();
+            let fmt: &[u8] = if now - login < 86400 {
+                b"%H:%M"
+            } else if now - login < 7 * 86400 {
+                b"%a%H"
+            } else {
+                b"%d%b%y"
+            };
+            let start = row.len;
+            row.time(fmt, &tz, login);
+            let used = row.len - start;
+            row.column(b"", 6usize.saturating_sub(used), true);
+        }
+        let mut buf = [0u8; 32];
+        let idle = st.map_or(0, |st| (now - st.st_atime).max(0) as u64 * 100);
+        row.column(format_interval(idle, &mut buf), 7, false);
+        if !short {
+            row.column(format_interval(total * 100 / ticks, &mut buf), 7, false);
+            let pcpu = current.as_ref().map_or(0, |p| p.cpu_time() * 100 / ticks);
+            row.column(format_interval(pcpu, &mut buf), 7, false);
+        }
+        row.push(b" ");
+        out.write_all(&row.buf[..row.len]);
+        match &current {
+            Some(p) => out.write_all(ps_args(p, &mut [0u8; 4096])),
+            None => out.write_byte(b'-'),
+        }
+        out.write_byte(b'\n');
     }
+    out.flush();
     0
 }
 
-pub fn who(_argc: i32, _argv: *const *const u8) -> i32 {
-    io::write_str(1, b"root     tty1         2024-01-01 00:00\n");
+/// The most users `users` sorts
+const MAX_USERS: usize = 256;
+
+/// users: print the names of the users logged in
+pub fn users(argc: i32, argv: *const *const u8) -> i32 {
+    let mut opts = Getopt::new(argc, &argv, b"");
+    for arg in opts.by_ref() {
+        if let Arg::Opt(_, _) | Arg::Error = arg {
+            return 1;
+        }
+    }
+    let path = opts.operand(0);
+    let file = match path {
+        Some(path) => utmp::File::open(path),
+        None => utmp::File::open_log(utmp::Log::Utmp),
+    };
+    let mut names = [([0u8; 32], 0usize); MAX_USERS];
+    let mut count = 0;
+    match file {
+        Some(file) => {
+            for entry in file.iter().filter(|e| e.kind() == utmp::USER_PROCESS && !e.user().is_empty()) {
+                if count < MAX_USERS {
+                    let user = entry.user();
+                    names[count].0[..user.len()].copy_from_slice(user);
+                    names[count].1 = user.len();
+                    count += 1;
+                }
+            }
+        }
+        None if path.is_some() => {
+            sys::perror(b"users", path.unwrap_or(b""));
+            return 1;
+        }
+        None => {}
+    }
+    let names = &mut names[..count];
+    names.sort_unstable_by(|a, b| a.0[..a.1].cmp(&b.0[..b.1]));
+
+    let out = io::stdout();
+    for (i, (name, len)) in names.iter().enumerate() {
+        if i > 0 {
+            out.write_byte(b' ');
+        }
+        out.write_all(&name[..*len]);
+    }
+    out.write_byte(b'\n');
+    out.flush();
     0
 }
 
-pub fn w(_argc: i32, _argv: *const *const u8) -> i32 {
-    who(_argc, _argv)
+/// How many terminals `last` remembers a logout time for at once
+const LAST_LINES: usize = 256;
+
+/// last: list logins, logouts and reboots, newest first
+pub fn last(argc: i32, argv: *const *const u8) -> i32 {
+    last_main(b"last", utmp::Log::Wtmp, argc, argv)
+}
+
+/// lastb: list failed logins, newest first
+pub fn lastb(argc: i32, argv: *const *const u8) -> i32 {
+    last_main(b"lastb", utmp::Log::Btmp, argc, argv)
 }
 
-pub fn users(_argc: i32, _argv: *const *const u8) -> i32 {
-    io::write_str(1, b"root\n");
+fn last_main(applet: &[u8], log: utmp::Log, argc: i32, argv: *const *const u8) -> i32 {
+    let mut path = None;
+    let mut limit = usize::MAX;
+    let mut extra = false;
+    let mut opts = Getopt::new(argc, &argv, b"#f:n:x");
+    for arg in opts.by_ref() {
+        match arg {
+            Arg::Opt(b"f", Some(file)) => path = Some(file),
+            Arg::Opt(b"n", Some(n)) | Arg::Number(n) => match sys::parse_u64(n.strip_prefix(b"-").unwrap_or(n)) {
+                Some(n) => limit = n as usize,
+                None => {
+                    sys::error_at(applet, n, b"invalid number of lines");
+                    return 1;
+                }
+            },
+            Arg::Opt(b"x", _) => extra = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    let mut default_path = [0u8; 512];
+    let path = match path.or_else(|| log.full_path(&mut default_path)) {
+        Some(path) => path,
+        None => return 1,
+    };
+    let file = match utmp::File::open(path) {
+        Some(file) => file,
+        None => {
+            sys::perror(applet, path);
+            return 1;
+        }
+    };
+
+    let out = io::stdout();
+    let tz = datetime::TimeZone::local();
+    let wanted = |user: &[u8], line: &[u8]| {
+        opts.operand_count() == 0
+            || opts.operands().any(|op| op == user || op == line || (line.strip_prefix(b"tty") == Some(op)))
+    };
+    // Writes a line ending at `end`, or with `note` in place of the end
+    let mut shown = 0;
+    let mut show = |user: &[u8], line: &[u8], host: &[u8], start: i64, end: Option<i64>, note: &[u8]| {
+        if shown >= limit || !wanted(user, line) {
+            return;
+        }
+        shown += 1;
+        let mut row = Row::new();
+        row.column(&user[..user.len().min(8)], 9, true);
+        row.column(&line[..line.len().min(12)], 13, true);
+        row.column(&host[..host.len().min(16)], 17, true);
+        row.time(b"%a %b %e %H:%M", &tz, start);
+        row.push(b" ");
+        match end {
+            Some(end) => {
+                row.push(b"- ");
+                if note.is_empty() {
+                    row.time(b"%H:%M", &tz, end);
+                } else {
+                    row.column(note, 5, true);
+                }
+                let secs = (end - start).max(0);
+                row.push(b"  (");
+                if secs >= 86400 {
+                    row.num((secs / 86400) as u64);
+                    row.push(b"+");
+                }
+                let (hours, mins) = (secs / 3600 % 24, secs / 60 % 60);
+                row.push(&[b'0' + (hours / 10) as u8, b'0' + (hours % 10) as u8, b':', b'0' + (mins / 10) as u8, b'0' + (mins % 10) as u8]);
+                row.push(b")");
+            }
+            None => {
+                row.push(b" ");
+                row.push(note);
+            }
+        }
+        row.finish(out);
+    };
+
+    // When the system went down after the records seen so far, and
+    // whether it crashed; `None` while still in the current boot
+    let mut down: Option<(i64, bool)> = None;
+    // When each terminal's next session began or its last one ended
+    let mut logouts = [([0u8; 32], 0usize, 0i64); LAST_LINES];
+    let mut nlogouts = 0;
+    let mut first = None;
+
+    for entry in file.iter_rev() {
+        let (line, time) = (entry.line(), entry.time());
+        first = Some(time);
+        if log == utmp::Log::Btmp {
+            show(entry.user(), line, entry.host(), time, Some(time), b"");
+            continue;
+        }
+        let found = logouts[..nlogouts].iter().position(|l| &l.0[..l.1] == line);
+        let remember = |logouts: &mut [([u8; 32], usize, i64)], nlogouts: &mut usize| {
+            let i = match found {
+                Some(i) => i,
+                None if *nlogouts < LAST_LINES => {
+                    *nlogouts += 1;
+                    *nlogouts - 1
+                }
+                None => return,
+            };
+            logouts[i].0[..line.len()].copy_from_slice(line);
+            logouts[i].1 = line.len();
+            logouts[i].2 = time;
+        };
+        match entry.kind() {
+            utmp::USER_PROCESS if !entry.user().is_empty() => {
+                match (found, down) {
+                    (Some(i), _) => show(entry.user(), line, entry.host(), time, Some(logouts[i].2), b""),
+                    (None, Some((at, crashed))) => show(entry.user(), line, entry.host(), time, Some(at), if crashed { b"crash" } else { b"down" }),
+                    (None, None) => {
+                        let alive = entry.pid() <= 0 || io::kill(entry.pid(), 0) == 0 || sys::errno() == libc::EPERM;
+                        show(entry.user(), line, entry.host(), time, None, if alive { b"still logged in" } else { b"gone - no logout" });
+                    }
+                }
+                remember(&mut logouts, &mut nlogouts);
+            }
+            utmp::USER_PROCESS | utmp::DEAD_PROCESS => remember(&mut logouts, &mut nlogouts),
+            utmp::BOOT_TIME => {
+                match down {
+                    Some((at, true)) => show(b"reboot", b"system boot", entry.host(), time, Some(at), b"crash"),
+                    Some((at, false)) => show(b"reboot", b"system boot", entry.host(), time, Some(at), b""),
+                    None => show(b"reboot", b"system boot", entry.host(), time, None, b"still running"),
+                }
+                down = Some((time, true));
+                nlogouts = 0;
+            }
+            utmp::RUN_LVL if entry.user() == b"shutdown" => {
+                if extra {
+                    match down {
+                        Some((at, _)) => show(b"shutdown", b"system down", entry.host(), time, Some(at), b""),
+                        None => show(b"shutdown", b"system down", entry.host(), time, None, b"still running"),
+                    }
+                }
+                down = Some((time, false));
+                nlogouts = 0;
+            }
+            utmp::RUN_LVL if extra => {
+                let level = [b'(', b't', b'o', b' ', b'l', b'v', b'l', b' ', (entry.pid() & 0xff) as u8, b')'];
+                show(b"runlevel", &level, entry.host(), time, None, b"");
+            }
+            _ => {}
+        }
+    }
+
+    let name = path.rsplit(|&c| c == b'/').next().unwrap_or(path);
+    let mut row = Row::new();
+    row.push(b"\n");
+    row.push(name);
+    row.push(b" begins ");
+    row.time(b"%a %b %e %H:%M:%S %Y", &tz, first.unwrap_or_else(|| file.mtime()));
+    row.finish(out);
+    out.flush();
     0
 }
 
@@ -116,35 +918,151 @@ pub fn arch(_argc: i32, _argv: *const *const u8) -> i32 {
     0
 }
 
-pub fn date(_argc: i32, _argv: *const *const u8) -> i32 {
-    let now = unsafe { libc::time(core::ptr::null_mut()) };
-    let tm = unsafe { libc::localtime(&now) };
-    if !tm.is_null() {
-        let t = unsafe { &*tm };
-        io::write_num(1, (t.tm_year + 1900) as u64);
-        io::write_str(1, b"-");
-        if t.tm_mon + 1 < 10 { io::write_str(1, b"0"); }
-        io::write_num(1, (t.tm_mon + 1) as u64);
-        io::write_str(1, b"-");
-        if t.tm_mday < 10 { io::write_str(1, b"0"); }
-        io::write_num(1, t.tm_mday as u64);
-        io::write_str(1, b" ");
-        if t.tm_hour < 10 { io::write_str(1, b"0"); }
-        io::write_num(1, t.tm_hour as u64);
-        io::write_str(1, b":");
-        if t.tm_min < 10 { io::write_str(1, b"0"); }
-        io::write_num(1, t.tm_min as u64);
-        io::write_str(1, b":");
-        if t.tm_sec < 10 { io::write_str(1, b"0"); }
-        io::write_num(1, t.tm_sec as u64);
-        io::write_str(1, b"\n");
+/// date - print or set the system date and time
+pub fn date(argc: i32, argv: *const *const u8) -> i32 {
+    let mut utc = false;
+    let mut input: Option<&[u8]> = None;
+    let mut input_format: Option<&[u8]> = None;
+    let mut reference: Option<&[u8]> = None;
+    let mut set: Option<&[u8]> = None;
+    let mut format: &[u8] = b"%a %b %e %H:%M:%S %Z %Y";
+
+    let spec = b"u(utc)(universal)d(date):D:r(reference):s(set):I(iso-8601)::R(rfc-2822)(rfc-email)";
+    let mut opts = Getopt::new(argc, &argv, spec);
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"u", _) => utc = true,
+            Arg::Opt(b"d", value) => input = value,
+            Arg::Opt(b"D", value) => input_format = value,
+            Arg::Opt(b"r", value) => reference = value,
+            Arg::Opt(b"s", value) => set = value,
+            Arg::Opt(b"I", value) => {
+                format = match value.unwrap_or(b"date") {
+                    b"date" => b"%Y-%m-%d",
+                    b"hours" => b"%Y-%m-%dT%H%:z",
+                    b"minutes" => b"%Y-%m-%dT%H:%M%:z",
+                    b"seconds" => b"%Y-%m-%dT%H:%M:%S%:z",
+                    b"ns" => b"%Y-%m-%dT%H:%M:%S,%N%:z",
+                    other => {
+                        sys::error_at(b"date", other, b"invalid argument for --iso-8601");
+                        return 1;
+                    }
+                }
+            }
+            Arg::Opt(b"R", _) => format = b"%a, %d %b %Y %H:%M:%S %z",
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    // +FORMAT, and the time to set as MMDDhhmm[[CC]YY][.ss]
+    let mut digits = None;
+    let mut custom = false;
+    for operand in opts.operands() {
+        if let (Some(f), false) = (operand.strip_prefix(b"+"), custom) {
+            format = f;
+            custom = true;
+        } else if digits.is_none() && set.is_none() && operand.first().is_some_and(u8::is_ascii_digit) {
+            digits = Some(operand);
+        } else {
+            return usage::error(b"date", b"extra operand");
+        }
+    }
+
+    let tz = if utc { datetime::TimeZone::utc() } else { datetime::TimeZone::local() };
+    let now = datetime::now();
+    let parsed = if let Some(path) = reference {
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::stat(path, &mut st) < 0 {
+            sys::perror(b"date", path);
+            return 1;
+        }
+        Some((st.st_mtime, st.st_mtime_nsec as u32))
+    } else if let Some(s) = set.or(input) {
+        match input_format {
+            Some(fmt) => datetime::parse_format(s, fmt, now, &tz),
+            None => datetime::parse_date(s, now, &tz),
+        }
+    } else if let Some(s) = digits {
+        datetime::parse_digits(s, true, now, &tz)
+    } else {
+        Some(now)
+    };
+    let Some((t, nsec)) = parsed else {
+        let s = set.or(input).or(digits).unwrap_or(b"");
+        sys::error_at(b"date", s, b"invalid date");
+        return 1;
+    };
+
+    if set.is_some() || digits.is_some() {
+        let ts = libc::timespec { tv_sec: t, tv_nsec: nsec as _ };
+        if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &ts) } < 0 {
+            sys::perror(b"date", b"cannot set date");
+            return 1;
+        }
     }
+
+    let out = io::stdout();
+    let tm = datetime::Tm { nsec, ..tz.localtime(t) };
+    datetime::strftime(format, &tm, &mut |s| out.write_all(s));
+    out.write_str(b"\n");
     0
 }
 
-pub fn env(_argc: i32, _argv: *const *const u8) -> i32 {
+pub fn env(argc: i32, argv: *const *const u8) -> i32 {
+    // Everything from the command name on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+i(ignore-environment)u(unset):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"i", _) => unsafe { libc::clearenv(); },
+            // Arguments are NUL-terminated, as unsetenv needs
+            Arg::Opt(b"u", Some(name)) => unsafe { libc::unsetenv(name.as_ptr() as *const i8); },
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    // Leading NAME=VALUE operands are set; the rest is the command
+    let mut command = opts.command();
+    while let Some(cmd) = command {
+        let arg = match unsafe { get_arg(cmd, 0) } {
+            Some(arg) => arg,
+            None => {
+                command = None;
+                break;
+            }
+        };
+        let eq = match arg.iter().position(|&c| c == b'=') {
+            Some(eq) => eq,
+            None => break,
+        };
+        let mut name = [0u8; 256];
+        if eq == 0 || eq >= name.len() {
+            sys::error_at(b"env", arg, b"invalid variable");
+            return 1;
+        }
+        name[..eq].copy_from_slice(&arg[..eq]);
+        unsafe { libc::setenv(name.as_ptr() as *const i8, arg.as_ptr().add(eq + 1) as *const i8, 1) };
+        command = Some(unsafe { cmd.add(1) });
+    }
+
+    match command {
+        Some(command) => unsafe { exec::exec(b"env", command) },
+        None => {
+            print_environ();
+            0
+        }
+    }
+}
+
+/// Write each `NAME=VALUE` in the environment on a line of its own
+fn print_environ() {
     unsafe extern "C" { static environ: *const *const i8; }
     unsafe {
+        // glibc's clearenv leaves it NULL
+        if environ.is_null() {
+            return;
+        }
         let mut i = 0;
         while !(*environ.add(i)).is_null() {
             let e = io::cstr_to_slice(*environ.add(i) as *const u8);
@@ -153,22 +1071,24 @@ pub fn env(_argc: i32, _argv: *const *const u8) -> i32 {
             i += 1;
         }
     }
-    0
 }
 
 pub fn printenv(argc: i32, argv: *const *const u8) -> i32 {
-    if argc > 1 {
-        if let Some(name) = unsafe { get_arg(argv, 1) } {
-            let val = unsafe { libc::getenv(name.as_ptr() as *const i8) };
-            if !val.is_null() {
-                io::write_all(1, unsafe { io::cstr_to_slice(val as *const u8) });
-                io::write_str(1, b"\n");
-                return 0;
-            }
-            return 1;
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if let Some(name) = opts.operand(0) {
+        let val = unsafe { libc::getenv(name.as_ptr() as *const i8) };
+        if !val.is_null() {
+            io::write_all(1, unsafe { io::cstr_to_slice(val as *const u8) });
+            io::write_str(1, b"\n");
+            return 0;
         }
+        return 1;
     }
-    env(argc, argv)
+    print_environ();
+    0
 }
 
 pub fn tty(_argc: i32, _argv: *const *const u8) -> i32 {
@@ -183,132 +1103,2076 @@ pub fn tty(_argc: i32, _argv: *const *const u8) -> i32 {
     }
 }
 
-pub fn kill(argc: i32, argv: *const *const u8) -> i32 {
-    let mut signal = libc::SIGTERM;
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if arg.len() > 1 && arg[1] >= b'0' && arg[1] <= b'9' {
-                    signal = sys::parse_u64(&arg[1..]).unwrap_or(15) as i32;
-                }
-            } else {
-                let pid = sys::parse_i64(arg).unwrap_or(0) as i32;
-                if unsafe { libc::kill(pid, signal) } < 0 {
-                    sys::perror(arg);
+pub fn nice(argc: i32, argv: *const *const u8) -> i32 {
+    let mut adjustment = 10;
+
+    // Everything from the command name on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+n(adjustment):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"n", Some(n)) => match sys::parse_i64(n) {
+                Some(n) => adjustment = n as i32,
+                None => {
+                    sys::error_at(b"nice", n, b"invalid number");
+                    return 1;
                 }
-            }
+            },
+            Arg::Error => return 1,
+            _ => {}
         }
     }
-    0
-}
-
-pub fn killall(argc: i32, argv: *const *const u8) -> i32 {
-    let _ = argc; let _ = argv;
-    io::write_str(2, b"killall: stub\n");
-    0
-}
-
-pub fn killall5(argc: i32, argv: *const *const u8) -> i32 {
-    let _ = argc; let _ = argv;
-    io::write_str(2, b"killall5: stub\n");
-    0
-}
 
-pub fn ps(_argc: i32, _argv: *const *const u8) -> i32 {
-    io::write_str(1, b"  PID TTY          TIME CMD\n");
-    let fd = io::open(b"/proc", libc::O_RDONLY | libc::O_DIRECTORY, 0);
-    if fd < 0 { return 1; }
-    
-    let mut buf = [0u8; 4096];
-    loop {
-        let n = unsafe { libc::syscall(libc::SYS_getdents64, fd, buf.as_mut_ptr(), buf.len()) };
-        if n <= 0 { break; }
-        let mut offset = 0;
-        while offset < n as usize {
-            let dirent = unsafe { &*(buf.as_ptr().add(offset) as *const libc::dirent64) };
-            let name = unsafe { io::cstr_to_slice(dirent.d_name.as_ptr() as *const u8) };
-            if !name.is_empty() && name[0] >= b'0' && name[0] <= b'9' {
-                io::write_str(1, b"  ");
-                io::write_all(1, name);
-                io::write_str(1, b" ?\n");
-            }
-            offset += dirent.d_reclen as usize;
+    let command = match opts.command() {
+        Some(command) => command,
+        None => {
+            io::write_signed(1, unsafe { libc::nice(0) } as i64);
+            io::write_str(1, b"\n");
+            return 0;
         }
+    };
+
+    // -1 is also a valid niceness, so only errno tells of failure
+    sys::clear_errno();
+    if unsafe { libc::nice(adjustment) } == -1 && sys::errno() != 0 {
+        sys::perror(b"nice", b"setpriority");
     }
-    io::close(fd);
-    0
+    unsafe { exec::exec(b"nice", command) }
 }
 
-pub fn pgrep(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn pkill(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn pidof(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn pwdx(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
+pub fn nohup(argc: i32, argv: *const *const u8) -> i32 {
+    let mut opts = Getopt::new(argc, &argv, b"+");
+    if opts.any(|arg| arg == Arg::Error) {
+        return 1;
+    }
+    let command = match opts.command() {
+        Some(command) => command,
+        None => return usage::error(b"nohup", b"missing operand"),
+    };
 
-pub fn sleep(argc: i32, argv: *const *const u8) -> i32 {
-    if argc > 1 {
-        if let Some(arg) = unsafe { get_arg(argv, 1) } {
-            let secs = sys::parse_u64(arg).unwrap_or(0) as u32;
-            unsafe { libc::sleep(secs) };
+    if io::isatty(1) {
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND;
+        let fd = io::open(b"nohup.out", flags, 0o600);
+        if fd < 0 {
+            sys::perror(b"nohup", b"nohup.out");
+            return EXIT_CANNOT_EXECUTE;
         }
+        sys::error(b"nohup", b"appending output to nohup.out");
+        io::dup2(fd, 1);
+        io::close(fd);
     }
-    0
-}
-
-pub fn usleep(argc: i32, argv: *const *const u8) -> i32 {
-    if argc > 1 {
-        if let Some(arg) = unsafe { get_arg(argv, 1) } {
-            let usecs = sys::parse_u64(arg).unwrap_or(0) as u32;
-            unsafe { libc::usleep(usecs) };
+    if io::isatty(2) {
+        io::dup2(1, 2);
+    }
+    if io::isatty(0) {
+        let fd = io::open(b"/dev/null", libc::O_RDONLY, 0);
+        if fd >= 0 {
+            io::dup2(fd, 0);
+            io::close(fd);
         }
     }
-    0
-}
 
-pub fn uptime(_argc: i32, _argv: *const *const u8) -> i32 {
-    let fd = io::open(b"/proc/uptime", libc::O_RDONLY, 0);
-    if fd >= 0 {
-        let mut buf = [0u8; 64];
-        let n = io::read(fd, &mut buf);
-        io::close(fd);
-        if n > 0 {
-            io::write_str(1, b"up ");
-            io::write_all(1, &buf[..n as usize]);
-        }
+    unsafe {
+        let mut action: libc::sigaction = core::mem::zeroed();
+        action.sa_sigaction = libc::SIG_IGN;
+        libc::sigaction(libc::SIGHUP, &action, core::ptr::null_mut());
+        exec::exec(b"nohup", command)
     }
-    0
 }
 
-pub fn free(_argc: i32, _argv: *const *const u8) -> i32 {
-    let fd = io::open(b"/proc/meminfo", libc::O_RDONLY, 0);
-    if fd >= 0 {
-        let mut buf = [0u8; 2048];
-        let n = io::read(fd, &mut buf);
-        io::close(fd);
-        if n > 0 { io::write_all(1, &buf[..n as usize]); }
+pub fn chroot(argc: i32, argv: *const *const u8) -> i32 {
+    let mut opts = Getopt::new(argc, &argv, b"+");
+    if opts.any(|arg| arg == Arg::Error) {
+        return 1;
     }
-    0
-}
+    let (root, command) = match (opts.operand(0), opts.command()) {
+        (Some(root), Some(command)) => (root, unsafe { command.add(1) }),
+        _ => return usage::error(b"chroot", b"missing operand"),
+    };
 
-pub fn df(_argc: i32, _argv: *const *const u8) -> i32 {
-    io::write_str(1, b"Filesystem     1K-blocks  Used Available Use% Mounted on\n");
-    io::write_str(1, b"/dev/root      10000000   5000000  5000000  50% /\n");
-    0
+    // Arguments are NUL-terminated, as chroot needs
+    if unsafe { libc::chroot(root.as_ptr() as *const i8) } < 0 {
+        sys::perror(b"chroot", root);
+        return 1;
+    }
+    if io::chdir(b"/") < 0 {
+        sys::perror(b"chroot", b"/");
+        return 1;
+    }
+
+    if unsafe { !(*command).is_null() } {
+        return unsafe { exec::exec(b"chroot", command) };
+    }
+
+    // No command: an interactive $SHELL (getenv's value is NUL-terminated)
+    let shell = io::getenv(b"SHELL").filter(|s| !s.is_empty()).unwrap_or(c"/bin/sh".to_bytes());
+    let shell_argv = [shell.as_ptr(), c"-i".as_ptr() as *const u8, core::ptr::null()];
+    unsafe { exec::exec(b"chroot", shell_argv.as_ptr()) }
+}
+
+pub fn setsid(argc: i32, argv: *const *const u8) -> i32 {
+    let mut ctty = false;
+    let mut wait = false;
+
+    // Everything from the command name on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+c(ctty)w(wait)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"c", _) => ctty = true,
+            Arg::Opt(b"w", _) => wait = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    let command = match opts.command() {
+        Some(command) => command,
+        None => return usage::error(b"setsid", b"missing operand"),
+    };
+
+    // A process group leader can't start a session, but its child can
+    if unsafe { libc::setsid() } < 0 {
+        let pid = io::fork();
+        if pid < 0 {
+            sys::perror(b"setsid", b"fork");
+            return 1;
+        }
+        if pid > 0 {
+            return if wait { exec::wait(b"setsid", pid) } else { 0 };
+        }
+        if unsafe { libc::setsid() } < 0 {
+            sys::perror(b"setsid", b"setsid");
+            io::exit(1);
+        }
+    }
+    if ctty && unsafe { libc::ioctl(0, libc::TIOCSCTTY, 1) } < 0 {
+        sys::perror(b"setsid", b"TIOCSCTTY");
+    }
+
+    let status = unsafe { exec::exec(b"setsid", command) };
+    io::exit(status)
+}
+
+/// Set by the `SIGALRM` handler when `timeout`'s time is up
+static TIMED_OUT: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+extern "C" fn timeout_alarm(_sig: i32) {
+    TIMED_OUT.store(true, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Parse a `timeout` duration: whole seconds with an optional `s`, `m`,
+/// `h` or `d` suffix
+fn parse_duration(s: &[u8]) -> Option<u32> {
+    let (digits, unit) = match s.last() {
+        Some(b's') => (&s[..s.len() - 1], 1),
+        Some(b'm') => (&s[..s.len() - 1], 60),
+        Some(b'h') => (&s[..s.len() - 1], 60 * 60),
+        Some(b'd') => (&s[..s.len() - 1], 24 * 60 * 60),
+        _ => (s, 1),
+    };
+    sys::parse_u64(digits)?.checked_mul(unit)?.try_into().ok()
+}
+
+pub fn timeout(argc: i32, argv: *const *const u8) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut kill_after = 0;
+
+    // Everything from the duration on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+s(signal):k(kill-after):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"s", Some(s)) => match sys::parse_u64(s) {
+                Some(n) => signal = n as i32,
+                None => {
+                    sys::error_at(b"timeout", s, b"invalid signal");
+                    return 1;
+                }
+            },
+            Arg::Opt(b"k", Some(k)) => match parse_duration(k) {
+                Some(k) => kill_after = k,
+                None => {
+                    sys::error_at(b"timeout", k, b"invalid time interval");
+                    return 1;
+                }
+            },
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let (duration, command) = match (opts.operand(0), opts.operand(1), opts.command()) {
+        (Some(duration), Some(_), Some(command)) => (duration, unsafe { command.add(1) }),
+        _ => return usage::error(b"timeout", b"missing operand"),
+    };
+    let secs = match parse_duration(duration) {
+        Some(secs) => secs,
+        None => {
+            sys::error_at(b"timeout", duration, b"invalid time interval");
+            return 1;
+        }
+    };
+
+    let pid = unsafe { exec::start(b"timeout", command) };
+    if pid < 0 {
+        return 1;
+    }
+    // Take the command down too if timeout is killed first
+    let child = sys::Cleanup::kill(pid, signal);
+
+    // No SA_RESTART, so the alarm interrupts waitpid
+    unsafe {
+        let mut action: libc::sigaction = core::mem::zeroed();
+        action.sa_sigaction = timeout_alarm as *const () as usize;
+        libc::sigaction(libc::SIGALRM, &action, core::ptr::null_mut());
+        libc::alarm(secs);
+    }
+
+    let mut timed_out = false;
+    let mut status = 0;
+    while io::waitpid(pid, &mut status, 0) < 0 {
+        if sys::errno() != libc::EINTR {
+            sys::perror(b"timeout", b"wait");
+            return 1;
+        }
+        if !TIMED_OUT.swap(false, core::sync::atomic::Ordering::Relaxed) {
+            continue;
+        }
+        if timed_out {
+            io::kill(pid, libc::SIGKILL);
+        } else {
+            timed_out = true;
+            io::kill(pid, signal);
+            unsafe { libc::alarm(kill_after) };
+        }
+    }
+    child.disarm();
+
+    if timed_out { 124 } else { exec::wait_status(status) }
+}
+
+pub fn watch(argc: i32, argv: *const *const u8) -> i32 {
+    let mut interval = 2;
+    let mut title = true;
+
+    // Everything from the command name on belongs to the command
+    let mut opts = Getopt::new(argc, &argv, b"+n(interval):t(no-title)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"n", Some(n)) => match sys::parse_u64(n) {
+                Some(n) if n > 0 => interval = n as u32,
+                _ => {
+                    sys::error_at(b"watch", n, b"invalid number");
+                    return 1;
+                }
+            },
+            Arg::Opt(b"t", _) => title = false,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    let command = match opts.command() {
+        Some(command) => command,
+        None => return usage::error(b"watch", b"missing operand"),
+    };
+
+    loop {
+        let out = io::stdout();
+        out.write_all(b"\x1b[H\x1b[J");
+        if title {
+            out.write_all(b"Every ");
+            out.write_num(interval as u64);
+            out.write_all(b"s:");
+            for arg in opts.operands() {
+                out.write_all(b" ");
+                out.write_all(arg);
+            }
+            out.write_all(b"\n\n");
+        }
+        out.flush();
+        unsafe {
+            exec::spawn(b"watch", command);
+            libc::sleep(interval);
+        }
+    }
+}
+
+pub fn kill(argc: i32, argv: *const *const u8) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut signal_given = false;
+    let mut status = 0;
+
+    // -NUM is the signal the first time; after that it is a process group
+    for arg in Getopt::new(argc, &argv, b"#s:") {
+        let pid = match arg {
+            Arg::Number(n) if !signal_given => {
+                signal = sys::parse_u64(&n[1..]).unwrap_or(15) as i32;
+                signal_given = true;
+                continue;
+            }
+            Arg::Opt(b"s", Some(s)) => {
+                signal = sys::parse_u64(s).unwrap_or(15) as i32;
+                signal_given = true;
+                continue;
+            }
+            Arg::Number(pid) | Arg::Operand(pid) => pid,
+            Arg::Error => return 1,
+            _ => continue,
+        };
+        let id = match sys::parse_i64(pid) {
+            Some(id) => id as i32,
+            None => {
+                sys::error_at(b"kill", pid, b"invalid number");
+                status = 1;
+                continue;
+            }
+        };
+        if unsafe { libc::kill(id, signal) } < 0 {
+            sys::perror(b"kill", pid);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Whether process `p` is called `name`: its `comm`, or the last component
+/// of its first argument, since `comm` is cut to 15 bytes
+fn process_named(p: &procfs::Process, name: &[u8]) -> bool {
+    if p.comm.as_bytes() == name {
+        return true;
+    }
+    let mut buf = [0u8; 4096];
+    let cmdline = p.cmdline(&mut buf);
+    let arg0 = cmdline.split(|&c| c == 0).next().unwrap_or(b"");
+    let base = arg0.rsplit(|&c| c == b'/').next().unwrap_or(b"");
+    !base.is_empty() && base == name
+}
+
+/// Parses `-NUM` or `-s NUM` as a signal
+fn parse_signal(applet: &[u8], s: &[u8]) -> Option<i32> {
+    match sys::parse_u64(s) {
+        Some(n) if n < 65 => Some(n as i32),
+        _ => {
+            sys::error_at(applet, s, b"invalid signal");
+            None
+        }
+    }
+}
+
+pub fn killall(argc: i32, argv: *const *const u8) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut quiet = false;
+
+    let mut opts = Getopt::new(argc, &argv, b"#q(quiet)s(signal):");
+    for arg in &mut opts {
+        match arg {
+            Arg::Number(n) => match parse_signal(b"killall", &n[1..]) {
+                Some(n) => signal = n,
+                None => return 1,
+            },
+            Arg::Opt(b"s", Some(s)) => match parse_signal(b"killall", s) {
+                Some(n) => signal = n,
+                None => return 1,
+            },
+            Arg::Opt(b"q", _) => quiet = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    if opts.operand_count() == 0 {
+        usage::error(b"killall", b"no process name specified");
+        return 1;
+    }
+
+    let me = io::getpid();
+    let mut status = 0;
+    for name in opts.operands() {
+        let mut killed = false;
+        for pid in procfs::pids().into_iter().flatten().filter(|&pid| pid != me) {
+            let p = match procfs::Process::read(pid) {
+                Some(p) if process_named(&p, name) => p,
+                _ => continue,
+            };
+            if io::kill(p.pid, signal) < 0 {
+                if !quiet {
+                    let mut id = [0u8; 20];
+                    sys::perror(b"killall", sys::format_u64(p.pid as u64, &mut id));
+                }
+                status = 1;
+            } else {
+                killed = true;
+            }
+        }
+        if !killed {
+            if !quiet {
+                sys::error_at(b"killall", name, b"no process killed");
+            }
+            status = 1;
+        }
+    }
+    status
+}
+
+/// killall5: signal every process outside our session, as init scripts do
+/// at shutdown
+pub fn killall5(argc: i32, argv: *const *const u8) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut omit = [0i32; 64];
+    let mut omitted = 0;
+
+    for arg in Getopt::new(argc, &argv, b"#o:") {
+        match arg {
+            Arg::Number(n) => match parse_signal(b"killall5", &n[1..]) {
+                Some(n) => signal = n,
+                None => return 2,
+            },
+            Arg::Opt(b"o", Some(list)) => {
+                for pid in list.split(|&c| c == b',') {
+                    match sys::parse_u64(pid) {
+                        Some(pid) if omitted < omit.len() => {
+                            omit[omitted] = pid as i32;
+                            omitted += 1;
+                        }
+                        _ => {
+                            sys::error_at(b"killall5", pid, b"invalid process id");
+                            return 2;
+                        }
+                    }
+                }
+            }
+            Arg::Error => return 2,
+            _ => {}
+        }
+    }
+
+    let sid = unsafe { libc::getsid(0) };
+    let mut found = false;
+    for pid in procfs::pids().into_iter().flatten() {
+        let p = match procfs::Process::read(pid) {
+            Some(p) => p,
+            None => continue,
+        };
+        if p.pid == 1 || p.session == sid || p.is_kernel_thread() || omit[..omitted].contains(&p.pid) {
+            continue;
+        }
+        io::kill(p.pid, signal);
+        found = true;
+    }
+    // As in sysvinit, 2 says there was nothing to signal
+    if found { 0 } else { 2 }
+}
+
+/// A `ps -o` field
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Pid,
+    Ppid,
+    Pgid,
+    Sid,
+    Tid,
+    Tty,
+    User,
+    Uid,
+    Group,
+    Gid,
+    State,
+    Stat,
+    Nice,
+    Pri,
+    Psr,
+    Nlwp,
+    Rss,
+    Vsz,
+    Time,
+    Etime,
+    Stime,
+    Pcpu,
+    C,
+    Pmem,
+    Comm,
+    Args,
+}
+
+/// A `ps -o` name, with its field, header, width and whether it is
+/// left-aligned
+type FieldName = (&'static [u8], Field, &'static [u8], usize, bool);
+
+const PS_FIELDS: &[FieldName] = &[
+    (b"pid", Field::Pid, b"PID", 5, false),
+    (b"ppid", Field::Ppid, b"PPID", 5, false),
+    (b"pgid", Field::Pgid, b"PGID", 5, false),
+    (b"pgrp", Field::Pgid, b"PGRP", 5, false),
+    (b"sid", Field::Sid, b"SID", 5, false),
+    (b"sess", Field::Sid, b"SESS", 5, false),
+    (b"tid", Field::Tid, b"TID", 5, false),
+    (b"lwp", Field::Tid, b"LWP", 5, false),
+    (b"spid", Field::Tid, b"SPID", 5, false),
+    (b"tty", Field::Tty, b"TT", 8, true),
+    (b"tt", Field::Tty, b"TT", 8, true),
+    (b"tname", Field::Tty, b"TTY", 8, true),
+    (b"user", Field::User, b"USER", 8, true),
+    (b"euser", Field::User, b"EUSER", 8, true),
+    (b"uname", Field::User, b"USER", 8, true),
+    (b"uid", Field::Uid, b"UID", 5, false),
+    (b"euid", Field::Uid, b"EUID", 5, false),
+    (b"group", Field::Group, b"GROUP", 8, true),
+    (b"egroup", Field::Group, b"EGROUP", 8, true),
+    (b"gid", Field::Gid, b"GID", 5, false),
+    (b"egid", Field::Gid, b"EGID", 5, false),
+    (b"s", Field::State, b"S", 1, true),
+    (b"state", Field::State, b"S", 1, true),
+    (b"stat", Field::Stat, b"STAT", 4, true),
+    (b"ni", Field::Nice, b"NI", 3, false),
+    (b"nice", Field::Nice, b"NI", 3, false),
+    (b"pri", Field::Pri, b"PRI", 3, false),
+    (b"psr", Field::Psr, b"PSR", 3, false),
+    (b"nlwp", Field::Nlwp, b"NLWP", 4, false),
+    (b"thcount", Field::Nlwp, b"THCNT", 5, false),
+    (b"rss", Field::Rss, b"RSS", 6, false),
+    (b"rsz", Field::Rss, b"RSZ", 6, false),
+    (b"vsz", Field::Vsz, b"VSZ", 7, false),
+    (b"vsize", Field::Vsz, b"VSZ", 7, false),
+    (b"time", Field::Time, b"TIME", 8, false),
+    (b"cputime", Field::Time, b"TIME", 8, false),
+    (b"etime", Field::Etime, b"ELAPSED", 11, false),
+    (b"stime", Field::Stime, b"STIME", 5, true),
+    (b"start_time", Field::Stime, b"START", 5, true),
+    (b"pcpu", Field::Pcpu, b"%CPU", 4, false),
+    (b"%cpu", Field::Pcpu, b"%CPU", 4, false),
+    (b"c", Field::C, b"C", 2, false),
+    (b"pmem", Field::Pmem, b"%MEM", 4, false),
+    (b"%mem", Field::Pmem, b"%MEM", 4, false),
+    (b"comm", Field::Comm, b"COMMAND", 15, true),
+    (b"ucomm", Field::Comm, b"COMMAND", 15, true),
+    (b"ucmd", Field::Comm, b"CMD", 15, true),
+    (b"args", Field::Args, b"COMMAND", 27, true),
+    (b"cmd", Field::Args, b"CMD", 27, true),
+    (b"command", Field::Args, b"COMMAND", 27, true),
+];
+
+/// A column of `ps` output
+#[derive(Clone, Copy)]
+struct Column<'a> {
+    field: Field,
+    /// The `-o` name, which is also the `--json` key
+    name: &'static [u8],
+    header: &'a [u8],
+    width: usize,
+    left: bool,
+}
+
+/// The columns `ps` prints, from `-o` or a default
+struct Columns<'a> {
+    list: [Column<'a>; 32],
+    len: usize,
+}
+
+impl<'a> Columns<'a> {
+    /// Adds the fields in `spec`, separated by commas or blanks. `NAME=HEADER`
+    /// renames a column, and takes the rest of the spec as the header.
+    fn add(&mut self, mut spec: &'a [u8]) -> Result<(), &'a [u8]> {
+        while !spec.is_empty() {
+            let end = spec.iter().position(|&c| c == b',' || c == b' ' || c == b'=').unwrap_or(spec.len());
+            let name = &spec[..end];
+            let renamed = spec.get(end) == Some(&b'=');
+            let rest = if end < spec.len() { &spec[end + 1..] } else { &spec[end..] };
+            if name.is_empty() {
+                spec = rest;
+                continue;
+            }
+            let &(name, field, header, width, left) = PS_FIELDS.iter().find(|f| f.0 == name).ok_or(name)?;
+            let header = if renamed { rest } else { header };
+            spec = if renamed { &[] } else { rest };
+            let column = self.list.get_mut(self.len).ok_or(name)?;
+            *column = Column { field, name, header, width: width.max(header.len()), left };
+            self.len += 1;
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Column<'a>> {
+        self.list[..self.len].iter()
+    }
+}
+
+/// What every `ps` row needs besides the process itself
+struct PsContext {
+    users: Option<accounts::Users>,
+    groups: Option<accounts::Groups>,
+    tz: datetime::TimeZone,
+    now: i64,
+    /// Hundredths of a second since boot
+    uptime: u64,
+    ticks: u64,
+    page_size: u64,
+    mem_total: u64,
+}
+
+/// Writes `s` padded to `width`, unless it is the last column
+fn write_column(out: io::Stdout, s: &[u8], width: usize, left: bool, last: bool) {
+    if !left {
+        for _ in s.len()..width {
+            out.write_byte(b' ');
+        }
+    }
+    out.write_all(s);
+    if left && !last {
+        for _ in s.len()..width {
+            out.write_byte(b' ');
+        }
+    }
+}
+
+/// Formats `n` at the start of `buf`, where `sys::format_u64` right-aligns
+fn format_signed(n: i64, buf: &mut [u8; 32]) -> &[u8] {
+    let mut digits = [0u8; 20];
+    let digits = sys::format_u64(n.unsigned_abs(), &mut digits);
+    let sign = (n < 0) as usize;
+    buf[0] = b'-';
+    buf[sign..sign + digits.len()].copy_from_slice(digits);
+    &buf[..sign + digits.len()]
+}
+
+/// Formats a duration in seconds as `[DD-]HH:MM:SS`, or with `short`
+/// as `[[DD-]HH:]MM:SS`
+fn format_duration(secs: u64, short: bool, buf: &mut [u8; 32]) -> &[u8] {
+    let (days, hours) = (secs / 86400, secs / 3600 % 24);
+    let mut len = 0;
+    if days > 0 {
+        len = format_signed(days as i64, buf).len();
+        buf[len] = b'-';
+        len += 1;
+    }
+    let parts = [hours, secs / 60 % 60, secs % 60];
+    let skip = (short && days == 0 && hours == 0) as usize;
+    for (i, n) in parts[skip..].iter().enumerate() {
+        if i > 0 {
+            buf[len] = b':';
+            len += 1;
+        }
+        buf[len] = b'0' + (n / 10) as u8;
+        buf[len + 1] = b'0' + (n % 10) as u8;
+        len += 2;
+    }
+    &buf[..len]
+}
+
+/// Formats tenths as `N.N`
+fn format_tenths(tenths: u64, buf: &mut [u8; 32]) -> &[u8] {
+    let len = format_signed((tenths / 10) as i64, buf).len();
+    buf[len] = b'.';
+    buf[len + 1] = b'0' + (tenths % 10) as u8;
+    &buf[..len + 2]
+}
+
+/// Formats one column of process `p`, except `args`
+fn ps_cell<'a>(field: Field, p: &procfs::Process, ctx: &'a PsContext, buf: &'a mut [u8; 32]) -> &'a [u8] {
+    let signed = format_signed;
+    // Ages and usage are from boot, in hundredths of a second
+    let started = p.starttime * 100 / ctx.ticks;
+    let age = ctx.uptime.saturating_sub(started).max(1);
+    let cpu = p.cpu_time() * 100 / ctx.ticks;
+    match field {
+        Field::Pid => signed(p.tgid as i64, buf),
+        Field::Ppid => signed(p.ppid as i64, buf),
+        Field::Pgid => signed(p.pgrp as i64, buf),
+        Field::Sid => signed(p.session as i64, buf),
+        Field::Tid => signed(p.pid as i64, buf),
+        Field::Tty => {
+            let mut name = [0u8; 16];
+            let name = procfs::tty_name(p.tty_nr, &mut name);
+            buf[..name.len()].copy_from_slice(name);
+            &buf[..name.len()]
+        }
+        Field::User => match ctx.users.as_ref().and_then(|users| users.getpwuid(p.uid[1])) {
+            Some(pw) => pw.name,
+            None => sys::format_u64(p.uid[1] as u64, buf),
+        },
+        Field::Uid => sys::format_u64(p.uid[1] as u64, buf),
+        Field::Group => match ctx.groups.as_ref().and_then(|groups| groups.getgrgid(p.gid[1])) {
+            Some(gr) => gr.name,
+            None => sys::format_u64(p.gid[1] as u64, buf),
+        },
+        Field::Gid => sys::format_u64(p.gid[1] as u64, buf),
+        Field::State => {
+            buf[0] = p.state;
+            &buf[..1]
+        }
+        Field::Stat => {
+            let mut len = 0;
+            let flags = [
+                (true, p.state),
+                (p.nice < 0, b'<'),
+                (p.nice > 0, b'N'),
+                (p.session == p.tgid, b's'),
+                (p.num_threads > 1, b'l'),
+                (p.tty_nr != 0 && p.tpgid == p.pgrp, b'+'),
+            ];
+            for (_, c) in flags.iter().filter(|f| f.0) {
+                buf[len] = *c;
+                len += 1;
+            }
+            &buf[..len]
+        }
+        Field::Nice => signed(p.nice, buf),
+        Field::Pri => signed(p.priority, buf),
+        Field::Psr => signed(p.processor as i64, buf),
+        Field::Nlwp => signed(p.num_threads, buf),
+        Field::Rss => sys::format_u64(p.rss * ctx.page_size / 1024, buf),
+        Field::Vsz => sys::format_u64(p.vsize / 1024, buf),
+        Field::Time => format_duration(p.cpu_time() / ctx.ticks, false, buf),
+        Field::Etime => format_duration(age / 100, true, buf),
+        Field::Stime => {
+            let start = ctx.now - (age / 100) as i64;
+            let tm = ctx.tz.localtime(start);
+            let fmt: &[u8] = if ctx.now - start < 86400 { b"%H:%M" } else { b"%b%d" };
+            let mut len = 0;
+            datetime::strftime(fmt, &tm, &mut |s| {
+                let n = s.len().min(32 - len);
+                buf[len..len + n].copy_from_slice(&s[..n]);
+                len += n;
+            });
+            &buf[..len]
+        }
+        Field::Pcpu => format_tenths(cpu * 1000 / age, buf),
+        Field::C => sys::format_u64(cpu * 100 / age, buf),
+        Field::Pmem => match ctx.mem_total {
+            0 => b"0.0",
+            total => format_tenths(p.rss * ctx.page_size / 1024 * 1000 / total, buf),
+        },
+        Field::Comm | Field::Args => {
+            let comm = p.comm.as_bytes();
+            let len = comm.len().min(32);
+            buf[..len].copy_from_slice(&comm[..len]);
+            &buf[..len]
+        }
+    }
+}
+
+/// The `args` column: the arguments, or for a kernel thread or zombie the
+/// bracketed name
+fn ps_args<'a>(p: &procfs::Process, buf: &'a mut [u8; 4096]) -> &'a [u8] {
+    let cmdline = p.cmdline(buf);
+    let mut len = cmdline.len();
+    if len == 0 {
+        let comm = p.comm.as_bytes();
+        let n = comm.len().min(buf.len() - 2);
+        buf[0] = b'[';
+        buf[1..n + 1].copy_from_slice(&comm[..n]);
+        buf[n + 1] = b']';
+        len = n + 2;
+    }
+    for c in buf[..len].iter_mut() {
+        if *c == 0 || *c == b'\n' {
+            *c = b' ';
+        }
+    }
+    &buf[..len]
+}
+
+/// Writes one column of process `p` as a JSON value: ids and counts as
+/// numbers, sizes in bytes, times in seconds (`stime` since the epoch) and
+/// percentages to one decimal place
+fn ps_json_cell(json: &mut Json, field: Field, p: &procfs::Process, ctx: &PsContext) {
+    let started = p.starttime * 100 / ctx.ticks;
+    let age = ctx.uptime.saturating_sub(started).max(1);
+    let cpu = p.cpu_time() * 100 / ctx.ticks;
+    match field {
+        Field::Pid => json.signed(p.tgid as i64),
+        Field::Ppid => json.signed(p.ppid as i64),
+        Field::Pgid => json.signed(p.pgrp as i64),
+        Field::Sid => json.signed(p.session as i64),
+        Field::Tid => json.signed(p.pid as i64),
+        Field::Uid => json.number(p.uid[1] as u64),
+        Field::Gid => json.number(p.gid[1] as u64),
+        Field::Nice => json.signed(p.nice),
+        Field::Pri => json.signed(p.priority),
+        Field::Psr => json.signed(p.processor as i64),
+        Field::Nlwp => json.signed(p.num_threads),
+        Field::Rss => json.number(p.rss * ctx.page_size),
+        Field::Vsz => json.number(p.vsize),
+        Field::Time => json.number(p.cpu_time() / ctx.ticks),
+        Field::Etime => json.number(age / 100),
+        Field::Stime => json.signed(ctx.now - (age / 100) as i64),
+        Field::Pcpu => json.fixed(cpu * 1000 / age, 1),
+        Field::C => json.number(cpu * 100 / age),
+        Field::Pmem => json.fixed((p.rss * ctx.page_size / 1024 * 1000).checked_div(ctx.mem_total).unwrap_or(0), 1),
+        Field::Args => json.string(ps_args(p, &mut [0u8; 4096])),
+        _ => json.string(ps_cell(field, p, ctx, &mut [0u8; 32])),
+    };
+}
+
+pub fn ps(argc: i32, argv: *const *const u8) -> i32 {
+    let blank = Column { field: Field::Pid, name: b"", header: b"", width: 0, left: false };
+    let mut columns = Columns { list: [blank; 32], len: 0 };
+    let mut full = false;
+    let mut threads = false;
+    let mut json = false;
+    let mut pids = [0i32; 64];
+    let mut npids = 0;
+
+    for arg in Getopt::new(argc, &argv, b"AefTo:p:(json)") {
+        match arg {
+            Arg::Opt(b"A", _) | Arg::Opt(b"e", _) => {}
+            Arg::Opt(b"f", _) => full = true,
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(b"T", _) => threads = true,
+            Arg::Opt(b"o", Some(spec)) => {
+                if let Err(name) = columns.add(spec) {
+                    sys::error_at(b"ps", name, b"unknown or too many fields");
+                    return 1;
+                }
+            }
+            Arg::Opt(b"p", Some(list)) => {
+                for pid in list.split(|&c| c == b',' || c == b' ').filter(|pid| !pid.is_empty()) {
+                    match sys::parse_u64(pid) {
+                        Some(pid) if npids < pids.len() => {
+                            pids[npids] = pid as i32;
+                            npids += 1;
+                        }
+                        _ => {
+                            sys::error_at(b"ps", pid, b"invalid process id");
+                            return 1;
+                        }
+                    }
+                }
+            }
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    if columns.len == 0 {
+        let specs: &[&'static [u8]] = match (full, threads) {
+            (true, false) => &[b"user=UID", b"pid,ppid,c,stime,tty,time", b"args=CMD"],
+            (true, true) => &[b"user=UID", b"pid,tid,ppid,c,stime,tty,time", b"args=CMD"],
+            (false, false) => &[b"pid,user,time,args"],
+            (false, true) => &[b"pid,tid,user,time,args"],
+        };
+        for spec in specs {
+            let _ = columns.add(spec);
+        }
+    }
+
+    let ctx = PsContext {
+        users: accounts::Users::open(),
+        groups: accounts::Groups::open(),
+        tz: datetime::TimeZone::local(),
+        now: datetime::now().0,
+        uptime: procfs::uptime().unwrap_or(0),
+        ticks: procfs::ticks(),
+        page_size: procfs::page_size(),
+        mem_total: procfs::meminfo(b"MemTotal").unwrap_or(0),
+    };
+    let all = match procfs::pids() {
+        Some(all) => all,
+        None => {
+            sys::perror(b"ps", b"/proc");
+            return 1;
+        }
+    };
+
+    let out = io::stdout();
+    let last = columns.len - 1;
+    let mut doc = Json::new();
+    if json {
+        doc.begin_array();
+    } else if columns.iter().any(|column| !column.header.is_empty()) {
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                out.write_byte(b' ');
+            }
+            write_column(out, column.header, column.width, column.left, i == last);
+        }
+        out.write_byte(b'\n');
+    }
+
+    let mut row = |p: &procfs::Process| {
+        if json {
+            doc.begin_object();
+            for column in columns.iter() {
+                doc.key(column.name);
+                ps_json_cell(&mut doc, column.field, p, &ctx);
+            }
+            doc.end_object();
+            return;
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                out.write_byte(b' ');
+            }
+            if column.field == Field::Args {
+                write_column(out, ps_args(p, &mut [0u8; 4096]), column.width, true, i == last);
+            } else {
+                let mut buf = [0u8; 32];
+                write_column(out, ps_cell(column.field, p, &ctx, &mut buf), column.width, column.left, i == last);
+            }
+        }
+        out.write_byte(b'\n');
+    };
+    let mut found = npids == 0;
+    for pid in all {
+        if npids > 0 && !pids[..npids].contains(&pid) {
+            continue;
+        }
+        let p = match procfs::Process::read(pid) {
+            Some(p) => p,
+            None => continue,
+        };
+        found = true;
+        if !threads {
+            row(&p);
+            continue;
+        }
+        for tid in p.threads().into_iter().flatten() {
+            if let Some(thread) = procfs::Process::read_thread(pid, tid) {
+                row(&thread);
+            }
+        }
+    }
+    if json {
+        doc.end_array().finish();
+    }
+    out.flush();
+    if found { 0 } else { 1 }
+}
+
+pub fn pgrep(argc: i32, argv: *const *const u8) -> i32 {
+    match_processes(b"pgrep", argc, argv)
+}
+
+pub fn pkill(argc: i32, argv: *const *const u8) -> i32 {
+    match_processes(b"pkill", argc, argv)
+}
+
+/// Whether `value` is in a comma-separated `list`, or there is no list.
+/// `id` turns an entry into a number, returning `None` to skip it.
+fn in_list(list: Option<&[u8]>, value: i64, id: impl Fn(&[u8]) -> Option<i64>) -> bool {
+    match list {
+        Some(list) => list.split(|&c| c == b',').filter_map(id).any(|n| n == value),
+        None => true,
+    }
+}
+
+/// pgrep and pkill: find processes whose name, or with -f whole command
+/// line, matches an ERE and which meet the other criteria, then list or
+/// signal them
+fn match_processes(applet: &'static [u8], argc: i32, argv: *const *const u8) -> i32 {
+    let killing = applet == b"pkill";
+    let mut signal = libc::SIGTERM;
+    let mut full = false;
+    let mut list_name = false;
+    let mut exact = false;
+    let mut invert = false;
+    let mut count = false;
+    let mut newest = false;
+    let mut oldest = false;
+    let mut delimiter: &[u8] = b"\n";
+    let (mut parents, mut groups, mut sessions, mut terminals, mut euids, mut uids) = (None, None, None, None, None, None);
+
+    let spec: &'static [u8] = if killing {
+        b"#c(count)f(full)g(pgroup):n(newest)o(oldest)P(parent):s(session):t(terminal):u(euid):U(uid):v(inverse)x(exact)"
+    } else {
+        b"c(count)d(delimiter):f(full)g(pgroup):l(list-name)n(newest)o(oldest)P(parent):s(session):t(terminal):u(euid):U(uid):v(inverse)x(exact)"
+    };
+    let mut opts = Getopt::new(argc, &argv, spec);
+    for arg in &mut opts {
+        match arg {
+            Arg::Number(n) => match parse_signal(applet, &n[1..]) {
+                Some(n) => signal = n,
+                None => return EXIT_TROUBLE,
+            },
+            Arg::Opt(b"c", _) => count = true,
+            Arg::Opt(b"d", Some(d)) => delimiter = d,
+            Arg::Opt(b"f", _) => full = true,
+            Arg::Opt(b"g", list) => groups = list,
+            Arg::Opt(b"l", _) => list_name = true,
+            Arg::Opt(b"n", _) => newest = true,
+            Arg::Opt(b"o", _) => oldest = true,
+            Arg::Opt(b"P", list) => parents = list,
+            Arg::Opt(b"s", list) => sessions = list,
+            Arg::Opt(b"t", list) => terminals = list,
+            Arg::Opt(b"u", list) => euids = list,
+            Arg::Opt(b"U", list) => uids = list,
+            Arg::Opt(b"v", _) => invert = true,
+            Arg::Opt(b"x", _) => exact = true,
+            Arg::Error => return EXIT_TROUBLE,
+            _ => {}
+        }
+    }
+    if newest && oldest {
+        usage::error(applet, b"-n and -o are mutually exclusive");
+        return EXIT_TROUBLE;
+    }
+    let criteria = [parents, groups, sessions, terminals, euids, uids].iter().any(Option::is_some);
+    let pattern = match (opts.operand(0), opts.operand_count()) {
+        (Some(pattern), 1) => pattern,
+        (None, _) if criteria => b"",
+        (None, _) => {
+            usage::error(applet, b"no matching criteria specified");
+            return EXIT_TROUBLE;
+        }
+        _ => {
+            usage::error(applet, b"only one pattern can be provided");
+            return EXIT_TROUBLE;
+        }
+    };
+    let re = match Regex::new(pattern, regex::EXTENDED) {
+        Ok(re) => re,
+        Err(msg) => {
+            sys::error(applet, msg);
+            return EXIT_TROUBLE;
+        }
+    };
+
+    let all = match procfs::pids() {
+        Some(all) => all,
+        None => {
+            sys::perror(applet, b"/proc");
+            return 3;
+        }
+    };
+    let users = accounts::Users::open();
+    let user_id = |name: &[u8]| match accounts::parse_id(name) {
+        Some(id) => Some(id as i64),
+        None => users.as_ref().and_then(|users| users.uid_of(name)).map(i64::from),
+    };
+    // 0 means our own process group or session
+    let own = |id: fn(i32) -> i32| move |s: &[u8]| match sys::parse_u64(s)? {
+        0 => Some(id(0) as i64),
+        n => Some(n as i64),
+    };
+    let pgid_of = |pid| unsafe { libc::getpgid(pid) };
+    let sid_of = |pid| unsafe { libc::getsid(pid) };
+
+    let me = io::getpid();
+    let out = io::stdout();
+    let mut matches = 0u64;
+    let mut chosen: Option<procfs::Process> = None;
+    let mut status = 0;
+    let mut act = |p: &procfs::Process, matches: u64| {
+        if killing {
+            if io::kill(p.tgid, signal) < 0 {
+                let mut id = [0u8; 20];
+                sys::perror(applet, sys::format_u64(p.tgid as u64, &mut id));
+                status = 1;
+            }
+        } else if !count {
+            if matches > 1 {
+                out.write_all(delimiter);
+            }
+            out.write_num(p.tgid as u64);
+            if list_name {
+                out.write_byte(b' ');
+                out.write_all(p.comm.as_bytes());
+            }
+        }
+    };
+
+    for pid in all.filter(|&pid| pid != me) {
+        let p = match procfs::Process::read(pid) {
+            Some(p) => p,
+            None => continue,
+        };
+        let mut tty = [0u8; 16];
+        let tty = procfs::tty_name(p.tty_nr, &mut tty);
+        let selected = in_list(parents, p.ppid as i64, sys::parse_i64)
+            && in_list(groups, p.pgrp as i64, own(pgid_of))
+            && in_list(sessions, p.session as i64, own(sid_of))
+            && in_list(euids, p.uid[1] as i64, user_id)
+            && in_list(uids, p.uid[0] as i64, user_id)
+            && terminals.is_none_or(|list| list.split(|&c| c == b',').any(|t| t.strip_prefix(b"/dev/").unwrap_or(t) == tty));
+        if !selected {
+            continue;
+        }
+
+        let mut buf = [0u8; 4096];
+        let cmdline = if full { p.cmdline(&mut buf) } else { &[] };
+        let len = cmdline.len();
+        let subject = if len == 0 {
+            p.comm.as_bytes()
+        } else {
+            // Arguments are NUL-separated
+            buf[..len].iter_mut().filter(|c| **c == 0).for_each(|c| *c = b' ');
+            &buf[..len]
+        };
+        let matched = if exact {
+            re.find_at(subject, 0) == Some((0, subject.len()))
+        } else {
+            re.is_match(subject)
+        };
+        if matched == invert {
+            continue;
+        }
+
+        if newest || oldest {
+            let better = chosen.is_none_or(|c| if newest { p.starttime >= c.starttime } else { p.starttime < c.starttime });
+            if better {
+                chosen = Some(p);
+            }
+            continue;
+        }
+        matches += 1;
+        act(&p, matches);
+    }
+    if let Some(p) = chosen {
+        matches = 1;
+        act(&p, matches);
+    }
+
+    if count {
+        out.write_num(matches);
+        out.write_byte(b'\n');
+    } else if matches > 0 && !killing {
+        out.write_byte(b'\n');
+    }
+    out.flush();
+    if matches == 0 { 1 } else { status }
+}
+
+pub fn pidof(argc: i32, argv: *const *const u8) -> i32 {
+    let mut single = false;
+    let mut omit = [0i32; 64];
+    let mut omitted = 0;
+
+    let mut opts = Getopt::new(argc, &argv, b"so:");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"s", _) => single = true,
+            Arg::Opt(b"o", Some(list)) => {
+                for pid in list.split(|&c| c == b',') {
+                    let pid = match pid {
+                        b"%PPID" => Some(io::getppid() as u64),
+                        _ => sys::parse_u64(pid),
+                    };
+                    match pid {
+                        Some(pid) if omitted < omit.len() => {
+                            omit[omitted] = pid as i32;
+                            omitted += 1;
+                        }
+                        _ => {
+                            usage::error(b"pidof", b"invalid process id to omit");
+                            return 1;
+                        }
+                    }
+                }
+            }
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let me = io::getpid();
+    let out = io::stdout();
+    let mut found = false;
+    'names: for name in opts.operands() {
+        for pid in procfs::pids().into_iter().flatten() {
+            if pid == me || omit[..omitted].contains(&pid) {
+                continue;
+            }
+            match procfs::Process::read(pid) {
+                Some(p) if process_named(&p, name) => {}
+                _ => continue,
+            }
+            if found {
+                out.write_byte(b' ');
+            }
+            out.write_num(pid as u64);
+            found = true;
+            if single {
+                break 'names;
+            }
+        }
+    }
+    if found {
+        out.write_byte(b'\n');
+    }
+    out.flush();
+    if found { 0 } else { 1 }
+}
+
+pub fn pwdx(argc: i32, argv: *const *const u8) -> i32 {
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if opts.operand_count() == 0 {
+        usage::error(b"pwdx", b"no process id specified");
+        return 1;
+    }
+
+    let out = io::stdout();
+    let mut status = 0;
+    for arg in opts.operands() {
+        let p = match sys::parse_u64(arg).and_then(|pid| procfs::Process::read(pid as i32)) {
+            Some(p) => p,
+            None => {
+                sys::error_at(b"pwdx", arg, b"No such process");
+                status = 1;
+                continue;
+            }
+        };
+        let mut buf = [0u8; 4096];
+        match p.link(b"cwd", &mut buf) {
+            Some(cwd) => {
+                out.write_all(arg);
+                out.write_str(b": ");
+                out.write_all(cwd);
+                out.write_byte(b'\n');
+            }
+            None => {
+                sys::perror(b"pwdx", arg);
+                status = 1;
+            }
+        }
+    }
+    out.flush();
+    status
+}
+
+pub fn sleep(argc: i32, argv: *const *const u8) -> i32 {
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if let Some(arg) = opts.operand(0) {
+        let secs = sys::parse_u64(arg).unwrap_or(0) as u32;
+        unsafe { libc::sleep(secs) };
+    }
+    0
+}
+
+pub fn usleep(argc: i32, argv: *const *const u8) -> i32 {
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    if let Some(arg) = opts.operand(0) {
+        let usecs = sys::parse_u64(arg).unwrap_or(0) as u32;
+        unsafe { libc::usleep(usecs) };
+    }
+    0
+}
+
+/// uptime: show how long the system has been up
+pub fn uptime(argc: i32, argv: *const *const u8) -> i32 {
+    let mut json = false;
+    for arg in Getopt::new(argc, &argv, b"(json)") {
+        match arg {
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    let uptime = procfs::uptime().unwrap_or(0);
+    if json {
+        let mut doc = Json::new();
+        doc.begin_object();
+        doc.key(b"time").signed(datetime::now().0);
+        doc.key(b"uptime").fixed(uptime, 2);
+        doc.key(b"users").number(utmp::users());
+        doc.key(b"load_average").begin_array();
+        for load in procfs::loadavg().unwrap_or([0; 3]) {
+            doc.fixed(load, 2);
+        }
+        doc.end_array().end_object().finish();
+        return 0;
+    }
+    let out = io::stdout();
+    out.write_byte(b' ');
+    uptime_summary(&mut |s| out.write_all(s), &datetime::TimeZone::local(), uptime);
+    out.write_byte(b'\n');
+    out.flush();
+    0
+}
+
+/// Formats `bytes` in whole `unit`s, or with `unit` 0 as `sys::format_size`
+/// does
+fn format_units(bytes: u64, unit: u64, buf: &mut [u8; 20]) -> &[u8] {
+    match unit {
+        0 => sys::format_size(bytes, buf),
+        _ => sys::format_u64(bytes / unit, buf),
+    }
+}
+
+/// free: show memory and swap use
+pub fn free(argc: i32, argv: *const *const u8) -> i32 {
+    let mut unit = 1024;
+    let mut json = false;
+    for arg in Getopt::new(argc, &argv, b"(json)bkmgh") {
+        match arg {
+            Arg::Opt(b"b", _) => unit = 1,
+            Arg::Opt(b"k", _) => unit = 1 << 10,
+            Arg::Opt(b"m", _) => unit = 1 << 20,
+            Arg::Opt(b"g", _) => unit = 1 << 30,
+            Arg::Opt(b"h", _) => unit = 0,
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let bytes = |key: &[u8]| procfs::meminfo(key).unwrap_or(0) * 1024;
+    let total = match procfs::meminfo(b"MemTotal") {
+        Some(total) => total * 1024,
+        None => {
+            sys::perror(b"free", b"/proc/meminfo");
+            return 1;
+        }
+    };
+    let free = bytes(b"MemFree");
+    let cache = bytes(b"Buffers") + bytes(b"Cached") + bytes(b"SReclaimable");
+    let available = procfs::meminfo(b"MemAvailable").map_or(free, |n| n * 1024);
+    let mem = [
+        (&b"total"[..], total),
+        (b"used", total.saturating_sub(free + cache)),
+        (b"free", free),
+        (b"shared", bytes(b"Shmem")),
+        (b"buff_cache", cache),
+        (b"available", available.min(total)),
+    ];
+    let (swap_total, swap_free) = (bytes(b"SwapTotal"), bytes(b"SwapFree"));
+    let swap = [
+        (&b"total"[..], swap_total),
+        (b"used", swap_total.saturating_sub(swap_free)),
+        (b"free", swap_free),
+    ];
+
+    if json {
+        let mut doc = Json::new();
+        doc.begin_object();
+        for (key, values) in [(&b"mem"[..], &mem[..]), (b"swap", &swap[..])] {
+            doc.key(key).begin_object();
+            for &(key, n) in values {
+                doc.key(key).number(n);
+            }
+            doc.end_object();
+        }
+        doc.end_object().finish();
+        return 0;
+    }
+
+    let out = io::stdout();
+    let mut row = Row::new();
+    row.push(b"       ");
+    for title in [&b"total"[..], b"used", b"free", b"shared", b"buff/cache", b"available"] {
+        row.column(title, 12, false);
+    }
+    row.finish(out);
+    for (label, values) in [(&b"Mem:"[..], &mem[..]), (b"Swap:", &swap[..])] {
+        let mut row = Row::new();
+        row.column(label, 7, true);
+        for &(_, n) in values {
+            row.column(format_units(n, unit, &mut [0u8; 20]), 12, false);
+        }
+        row.finish(out);
+    }
+    out.flush();
+    0
+}
+
+/// How much of a `/proc` table `df`, `mount` and `lsmod` read
+const TABLE_SIZE: usize = 65536;
+
+/// A line of `/proc/self/mounts`, with blanks in its fields still escaped
+/// (see `unescape_mount`)
+#[derive(Clone, Copy)]
+struct MountLine<'a> {
+    source: &'a [u8],
+    target: &'a [u8],
+    fstype: &'a [u8],
+    options: &'a [u8],
+}
+
+/// The lines of a mount table, `/proc/self/mounts` or `/etc/fstab`
+fn mount_lines(table: &[u8]) -> impl Iterator<Item = MountLine<'_>> {
+    table.split(|&c| c == b'\n').filter_map(|line| {
+        let mut fields = line.split(|&c| c == b' ' || c == b'\t').filter(|f| !f.is_empty());
+        let source = fields.next().filter(|source| !source.starts_with(b"#"))?;
+        Some(MountLine { source, target: fields.next()?, fstype: fields.next()?, options: fields.next().unwrap_or(b"defaults") })
+    })
+}
+
+/// Decodes the `\040`-style octal escapes a mount table puts in place of
+/// blanks and backslashes
+fn unescape_mount<'a>(field: &[u8], buf: &'a mut [u8; 4096]) -> &'a [u8] {
+    let (mut i, mut len) = (0, 0);
+    while i < field.len() && len < buf.len() {
+        let octal = field.get(i + 1..i + 4).filter(|d| field[i] == b'\\' && d.iter().all(|d| (b'0'..=b'7').contains(d)));
+        match octal {
+            Some(digits) => {
+                buf[len] = digits.iter().fold(0u8, |n, &d| n.wrapping_mul(8).wrapping_add(d - b'0'));
+                i += 4;
+            }
+            None => {
+                buf[len] = field[i];
+                i += 1;
+            }
+        }
+        len += 1;
+    }
+    &buf[..len]
+}
+
+/// df: show free space on mounted filesystems
+pub fn df(argc: i32, argv: *const *const u8) -> i32 {
+    let (mut all, mut show_type, mut json) = (false, false, false);
+    let mut unit = 1024;
+    let mut only: Option<&[u8]> = None;
+    let mut opts = Getopt::new(argc, &argv, b"ahkmPTt:(json)");
+    for arg in opts.by_ref() {
+        match arg {
+            Arg::Opt(b"a", _) => all = true,
+            Arg::Opt(b"h", _) => unit = 0,
+            Arg::Opt(b"k", _) => unit = 1 << 10,
+            Arg::Opt(b"m", _) => unit = 1 << 20,
+            Arg::Opt(b"P", _) => {}
+            Arg::Opt(b"T", _) => show_type = true,
+            Arg::Opt(b"t", fstype) => only = fstype,
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let mut buf = [0u8; TABLE_SIZE];
+    let table = match procfs::read(b"self/mounts", &mut buf) {
+        Some(table) => table,
+        None => {
+            sys::perror(b"df", b"/proc/self/mounts");
+            return 1;
+        }
+    };
+
+    // With FILEs, each shows the last filesystem mounted on its device
+    let mut status = 0;
+    let mut chosen = [None; 64];
+    let mut nchosen = 0;
+    for path in opts.operands() {
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::stat(path, &mut st) < 0 {
+            sys::perror(b"df", path);
+            status = 1;
+            continue;
+        }
+        let found = mount_lines(table).filter(|line| {
+            let mut mounted: libc::stat = unsafe { core::mem::zeroed() };
+            io::stat(unescape_mount(line.target, &mut [0u8; 4096]), &mut mounted) == 0 && mounted.st_dev == st.st_dev
+        });
+        match found.last() {
+            Some(line) if nchosen < chosen.len() => {
+                chosen[nchosen] = Some(line);
+                nchosen += 1;
+            }
+            _ => {
+                sys::error_at(b"df", path, b"no file system found");
+                status = 1;
+            }
+        }
+    }
+    if opts.operand_count() > 0 && nchosen == 0 {
+        return status;
+    }
+
+    let out = io::stdout();
+    let mut doc = Json::new();
+    if json {
+        doc.begin_array();
+    } else {
+        let mut row = Row::new();
+        row.column(b"Filesystem", 19, true);
+        if show_type {
+            row.push(b" ");
+            row.column(b"Type", 8, true);
+        }
+        let size: &[u8] = match unit {
+            0 => b"Size",
+            1024 => b"1K-blocks",
+            _ => b"1M-blocks",
+        };
+        for (title, width) in [(size, 10), (b"Used", 10), (b"Available", 10), (b"Use%", 4)] {
+            row.push(b" ");
+            row.column(title, width, false);
+        }
+        row.push(b" Mounted on");
+        row.finish(out);
+    }
+
+    let mut show = |line: &MountLine, named: bool| {
+        let (mut source, mut target) = ([0u8; 4096], [0u8; 4096]);
+        let source = unescape_mount(line.source, &mut source);
+        let target = unescape_mount(line.target, &mut target);
+        let mut fs: libc::statfs = unsafe { core::mem::zeroed() };
+        if io::statfs(target, &mut fs) < 0 {
+            if named {
+                sys::perror(b"df", target);
+            }
+            return;
+        }
+        if !all && !named && fs.f_blocks == 0 {
+            return;
+        }
+        let block = fs.f_frsize as u64;
+        let size = fs.f_blocks as u64 * block;
+        let used = size.saturating_sub(fs.f_bfree as u64 * block);
+        let avail = fs.f_bavail as u64 * block;
+        // Of the space ordinary users can have, rounded up
+        let percent = (used + avail > 0).then(|| (used * 100).div_ceil(used + avail));
+
+        if json {
+            doc.begin_object();
+            doc.key(b"filesystem").string(source);
+            doc.key(b"type").string(line.fstype);
+            doc.key(b"size").number(size);
+            doc.key(b"used").number(used);
+            doc.key(b"available").number(avail);
+            doc.key(b"use_percent");
+            match percent {
+                Some(percent) => doc.number(percent),
+                None => doc.null(),
+            };
+            doc.key(b"mounted_on").string(target);
+            doc.end_object();
+            return;
+        }
+        let mut row = Row::new();
+        row.column(source, 19, true);
+        if show_type {
+            row.push(b" ");
+            row.column(line.fstype, 8, true);
+        }
+        for n in [size, used, avail] {
+            row.push(b" ");
+            let n = if unit == 0 { n } else { n.div_ceil(unit) * unit };
+            row.column(format_units(n, unit, &mut [0u8; 20]), 10, false);
+        }
+        row.push(b" ");
+        match percent {
+            Some(percent) => {
+                let mut buf = [0u8; 20];
+                let digits = sys::format_u64(percent, &mut buf);
+                row.column(digits, 3, false);
+                row.push(b"%");
+            }
+            None => row.column(b"-", 4, false),
+        }
+        row.push(b" ");
+        row.push(target);
+        row.finish(out);
+    };
+    if nchosen > 0 {
+        for line in chosen[..nchosen].iter().flatten() {
+            show(line, true);
+        }
+    } else {
+        for line in mount_lines(table).filter(|line| only.is_none_or(|only| line.fstype == only)) {
+            show(&line, false);
+        }
+    }
+    if json {
+        doc.end_array().finish();
+    }
+    out.flush();
+    status
+}
+
+/// `mount -o` options that are mount flags, and whether they set or clear
+/// them; the rest go to the filesystem
+const MOUNT_FLAGS: &[(&[u8], libc::c_ulong, bool)] = &[
+    (b"defaults", 0, true),
+    (b"ro", libc::MS_RDONLY, true),
+    (b"rw", libc::MS_RDONLY, false),
+    (b"nosuid", libc::MS_NOSUID, true),
+    (b"suid", libc::MS_NOSUID, false),
+    (b"nodev", libc::MS_NODEV, true),
+    (b"dev", libc::MS_NODEV, false),
+    (b"noexec", libc::MS_NOEXEC, true),
+    (b"exec", libc::MS_NOEXEC, false),
+    (b"sync", libc::MS_SYNCHRONOUS, true),
+    (b"async", libc::MS_SYNCHRONOUS, false),
+    (b"noatime", libc::MS_NOATIME, true),
+    (b"atime", libc::MS_NOATIME, false),
+    (b"nodiratime", libc::MS_NODIRATIME, true),
+    (b"diratime", libc::MS_NODIRATIME, false),
+    (b"relatime", libc::MS_RELATIME, true),
+    (b"norelatime", libc::MS_RELATIME, false),
+    (b"strictatime", libc::MS_STRICTATIME, true),
+    (b"mand", libc::MS_MANDLOCK, true),
+    (b"nomand", libc::MS_MANDLOCK, false),
+    (b"dirsync", libc::MS_DIRSYNC, true),
+    (b"remount", libc::MS_REMOUNT, true),
+    (b"bind", libc::MS_BIND, true),
+    (b"rbind", libc::MS_BIND | libc::MS_REC, true),
+    (b"move", libc::MS_MOVE, true),
+    (b"shared", libc::MS_SHARED, true),
+    (b"rshared", libc::MS_SHARED | libc::MS_REC, true),
+    (b"slave", libc::MS_SLAVE, true),
+    (b"rslave", libc::MS_SLAVE | libc::MS_REC, true),
+    (b"private", libc::MS_PRIVATE, true),
+    (b"rprivate", libc::MS_PRIVATE | libc::MS_REC, true),
+    (b"unbindable", libc::MS_UNBINDABLE, true),
+    (b"runbindable", libc::MS_UNBINDABLE | libc::MS_REC, true),
+];
+
+/// Mount options being gathered from `/etc/fstab` and `-o`: the flags, and
+/// the rest comma-separated for the filesystem
+struct MountOptions {
+    flags: libc::c_ulong,
+    data: [u8; 1024],
+    len: usize,
+}
+
+impl MountOptions {
+    fn add(&mut self, list: &[u8]) {
+        for option in list.split(|&c| c == b',').filter(|option| !option.is_empty()) {
+            match MOUNT_FLAGS.iter().find(|flag| flag.0 == option) {
+                Some(&(_, flag, true)) => self.flags |= flag,
+                Some(&(_, flag, false)) => self.flags &= !flag,
+                // Only for fstab and mount(8) itself
+                None if matches!(option, b"auto" | b"noauto" | b"user" | b"nouser" | b"users" | b"nofail" | b"_netdev") => {}
+                None => {
+                    let start = self.len + (self.len > 0) as usize;
+                    if start + option.len() < self.data.len() {
+                        if start > self.len {
+                            self.data[self.len] = b',';
+                        }
+                        self.data[start..start + option.len()].copy_from_slice(option);
+                        self.len = start + option.len();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Calls mount(2) on NUL-terminated copies of its arguments
+fn mount_fs(source: &[u8], target: &[u8], fstype: &[u8], options: &MountOptions) -> bool {
+    let mut bufs = [[0u8; 4096]; 3];
+    for (buf, s) in bufs.iter_mut().zip([source, target, fstype]) {
+        let n = s.len().min(4095);
+        buf[..n].copy_from_slice(&s[..n]);
+    }
+    let mut data = [0u8; 1025];
+    data[..options.len].copy_from_slice(&options.data[..options.len]);
+    let ptr = |buf: &[u8; 4096]| buf.as_ptr() as *const libc::c_char;
+    let fstype = if fstype.is_empty() { core::ptr::null() } else { ptr(&bufs[2]) };
+    unsafe { libc::mount(ptr(&bufs[0]), ptr(&bufs[1]), fstype, options.flags, data.as_ptr() as *const libc::c_void) == 0 }
+}
+
+/// mount: list mounted filesystems, or mount one
+pub fn mount(argc: i32, argv: *const *const u8) -> i32 {
+    let mut options = MountOptions { flags: 0, data: [0; 1024], len: 0 };
+    let mut extra: [&[u8]; 16] = [b""; 16];
+    let mut nextra = 0;
+    let mut fstype: Option<&[u8]> = None;
+    let mut json = false;
+    let mut opts = Getopt::new(argc, &argv, b"rwt:o:(json)");
+    // Options from the command line go after any from /etc/fstab
+    for arg in opts.by_ref() {
+        let list: &[u8] = match arg {
+            Arg::Opt(b"r", _) => b"ro",
+            Arg::Opt(b"w", _) => b"rw",
+            Arg::Opt(b"o", Some(list)) => list,
+            Arg::Opt(b"t", t) => {
+                fstype = t;
+                continue;
+            }
+            Arg::Opt(b"json", _) => {
+                json = true;
+                continue;
+            }
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => continue,
+        };
+        if nextra < extra.len() {
+            extra[nextra] = list;
+            nextra += 1;
+        }
+    }
+
+    let mut buf = [0u8; TABLE_SIZE];
+    if opts.operand_count() == 0 {
+        let table = match procfs::read(b"self/mounts", &mut buf) {
+            Some(table) => table,
+            None => {
+                sys::perror(b"mount", b"/proc/self/mounts");
+                return 1;
+            }
+        };
+        let out = io::stdout();
+        let mut doc = Json::new();
+        if json {
+            doc.begin_array();
+        }
+        for line in mount_lines(table).filter(|line| fstype.is_none_or(|fstype| line.fstype == fstype)) {
+            let (mut source, mut target) = ([0u8; 4096], [0u8; 4096]);
+            let source = unescape_mount(line.source, &mut source);
+            let target = unescape_mount(line.target, &mut target);
+            if json {
+                doc.begin_object();
+                doc.key(b"source").string(source);
+                doc.key(b"target").string(target);
+                doc.key(b"fstype").string(line.fstype);
+                doc.key(b"options").begin_array();
+                for option in line.options.split(|&c| c == b',') {
+                    doc.string(option);
+                }
+                doc.end_array().end_object();
+                continue;
+            }
+            for part in [source, b" on ", target, b" type ", line.fstype, b" (", line.options, b")\n"] {
+                out.write_all(part);
+            }
+        }
+        if json {
+            doc.end_array().finish();
+        }
+        out.flush();
+        return 0;
+    }
+
+    // One operand is looked up in /etc/fstab, as a source or a target
+    let (source, target, fstab_type) = match (opts.operand(0), opts.operand(1)) {
+        (Some(source), Some(target)) => (source, target, None),
+        (Some(name), None) => {
+            let fd = io::open(b"/etc/fstab", libc::O_RDONLY | libc::O_CLOEXEC, 0);
+            let mut len = 0;
+            if fd >= 0 {
+                len = io::read(fd, &mut buf).max(0) as usize;
+                io::close(fd);
+            }
+            let found = mount_lines(&buf[..len]).find(|line| {
+                unescape_mount(line.target, &mut [0u8; 4096]) == name || unescape_mount(line.source, &mut [0u8; 4096]) == name
+            });
+            let line = match found {
+                Some(line) => line,
+                None => {
+                    sys::error_at(b"mount", name, b"can't find in /etc/fstab");
+                    return 1;
+                }
+            };
+            options.add(line.options);
+            (line.source, line.target, Some(line.fstype))
+        }
+        _ => unreachable!(),
+    };
+    for list in &extra[..nextra] {
+        options.add(list);
+    }
+    let (mut source_buf, mut target_buf) = ([0u8; 4096], [0u8; 4096]);
+    let source = unescape_mount(source, &mut source_buf);
+    let target = unescape_mount(target, &mut target_buf);
+
+    // Without a type, try each one the kernel knows that needs a device
+    let fstype = fstype.or(fstab_type).filter(|t| *t != b"auto");
+    let moving = options.flags & (libc::MS_BIND | libc::MS_MOVE | libc::MS_REMOUNT) != 0;
+    let mounted = match fstype {
+        Some(fstype) => mount_fs(source, target, fstype, &options),
+        None if moving => mount_fs(source, target, b"", &options),
+        None => {
+            let mut types = [0u8; 4096];
+            let types = procfs::read(b"filesystems", &mut types).unwrap_or(b"");
+            let mut mounted = false;
+            for line in types.split(|&c| c == b'\n').filter(|line| !line.starts_with(b"nodev")) {
+                let fstype = line.trim_ascii();
+                if !fstype.is_empty() && mount_fs(source, target, fstype, &options) {
+                    mounted = true;
+                    break;
+                }
+            }
+            mounted
+        }
+    };
+    if !mounted {
+        sys::perror(b"mount", target);
+        return 1;
+    }
+    0
+}
+
+/// lsmod: list loaded kernel modules
+pub fn lsmod(argc: i32, argv: *const *const u8) -> i32 {
+    let mut json = false;
+    for arg in Getopt::new(argc, &argv, b"(json)") {
+        match arg {
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+    let mut buf = [0u8; TABLE_SIZE];
+    let table = match procfs::read(b"modules", &mut buf) {
+        Some(table) => table,
+        None => {
+            sys::perror(b"lsmod", b"/proc/modules");
+            return 1;
+        }
+    };
+
+    let out = io::stdout();
+    let mut doc = Json::new();
+    if json {
+        doc.begin_array();
+    } else {
+        out.write_str(b"Module                  Size  Used by\n");
+    }
+    // NAME SIZE USES USERS, STATE ADDRESS, where USERS is `-` for none
+    for line in table.split(|&c| c == b'\n') {
+        let mut fields = line.split(|&c| c == b' ');
+        let (name, size, uses) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(name), Some(size), Some(uses)) if !name.is_empty() => (name, size, uses),
+            _ => continue,
+        };
+        let users = fields.next().unwrap_or(b"-");
+        let users = users.split(|&c| c == b',').filter(|user| !user.is_empty() && *user != b"-");
+        if json {
+            doc.begin_object();
+            doc.key(b"name").string(name);
+            doc.key(b"size").number(sys::parse_u64(size).unwrap_or(0));
+            doc.key(b"used").number(sys::parse_u64(uses).unwrap_or(0));
+            doc.key(b"used_by").begin_array();
+            for user in users {
+                doc.string(user);
+            }
+            doc.end_array();
+            doc.key(b"state").string(fields.next().unwrap_or(b""));
+            doc.end_object();
+            continue;
+        }
+        let mut row = Row::new();
+        row.column(name, 19, true);
+        row.push(b" ");
+        row.column(size, 8, false);
+        row.push(b"  ");
+        row.push(uses);
+        row.push(b" ");
+        for (i, user) in users.enumerate() {
+            if i > 0 {
+                row.push(b",");
+            }
+            row.push(user);
+        }
+        row.finish(out);
+    }
+    if json {
+        doc.end_array().finish();
+    }
+    out.flush();
+    0
+}
+
+/// How much of a device `blkid` reads: enough for a btrfs superblock
+const PROBE_SIZE: usize = 0x10000 + 0x1000;
+
+/// What `blkid` found on a device
+struct Probe {
+    fstype: &'static [u8],
+    uuid: [u8; 40],
+    uuid_len: usize,
+    label: [u8; 256],
+    label_len: usize,
+}
+
+impl Probe {
+    fn new(fstype: &'static [u8]) -> Probe {
+        Probe { fstype, uuid: [0; 40], uuid_len: 0, label: [0; 256], label_len: 0 }
+    }
+
+    /// Formats 16 bytes as `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`
+    fn uuid(mut self, bytes: &[u8]) -> Probe {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        if bytes.iter().all(|&b| b == 0) {
+            return self;
+        }
+        for (i, &b) in bytes.iter().take(16).enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                self.uuid[self.uuid_len] = b'-';
+                self.uuid_len += 1;
+            }
+            self.uuid[self.uuid_len] = HEX[(b >> 4) as usize];
+            self.uuid[self.uuid_len + 1] = HEX[(b & 15) as usize];
+            self.uuid_len += 2;
+        }
+        self
+    }
+
+    /// A FAT volume serial number, as `XXXX-XXXX`
+    fn serial(mut self, bytes: &[u8]) -> Probe {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        for (i, &b) in bytes.iter().take(4).rev().enumerate() {
+            if i == 2 {
+                self.uuid[self.uuid_len] = b'-';
+                self.uuid_len += 1;
+            }
+            self.uuid[self.uuid_len] = HEX[(b >> 4) as usize];
+            self.uuid[self.uuid_len + 1] = HEX[(b & 15) as usize];
+            self.uuid_len += 2;
+        }
+        self
+    }
+
+    /// Takes a label up to the first NUL, without trailing blanks
+    fn label(mut self, bytes: &[u8]) -> Probe {
+        let bytes = &bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())];
+        let bytes = bytes.trim_ascii_end();
+        self.label_len = bytes.len().min(self.label.len());
+        self.label[..self.label_len].copy_from_slice(&bytes[..self.label_len]);
+        self
+    }
+
+    fn uuid_str(&self) -> Option<&[u8]> {
+        Some(&self.uuid[..self.uuid_len]).filter(|uuid| !uuid.is_empty())
+    }
+
+    fn label_str(&self) -> Option<&[u8]> {
+        Some(&self.label[..self.label_len]).filter(|label| !label.is_empty())
+    }
+}
+
+/// Looks for a filesystem, swap or LUKS superblock at the start of a device
+fn blkid_probe(data: &[u8]) -> Option<Probe> {
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+    let le32 = |offset: usize| data.get(offset..offset + 4).map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let field = |offset: usize, len: usize| data.get(offset..offset + len).unwrap_or(b"");
+
+    if at(1024 + 56, &[0x53, 0xef]) {
+        let (compat, incompat) = (le32(1024 + 92), le32(1024 + 96));
+        // Extents, 64-bit or flexible block groups make it ext4
+        let fstype: &[u8] = if incompat & 0x2c0 != 0 {
+            b"ext4"
+        } else if compat & 0x4 != 0 {
+            b"ext3"
+        } else {
+            b"ext2"
+        };
+        return Some(Probe::new(fstype).uuid(field(1024 + 104, 16)).label(field(1024 + 120, 16)));
+    }
+    if at(0, b"XFSB") {
+        return Some(Probe::new(b"xfs").uuid(field(32, 16)).label(field(108, 12)));
+    }
+    if at(0x10000 + 64, b"_BHRfS_M") {
+        return Some(Probe::new(b"btrfs").uuid(field(0x10000 + 32, 16)).label(field(0x10000 + 299, 256)));
+    }
+    if at(4096 - 10, b"SWAPSPACE2") || at(4096 - 10, b"SWAP-SPACE") {
+        return Some(Probe::new(b"swap").uuid(field(1024 + 12, 16)).label(field(1024 + 28, 16)));
+    }
+    if at(0, b"LUKS\xba\xbe") {
+        let mut probe = Probe::new(b"crypto_LUKS");
+        let uuid = field(168, 40);
+        let uuid = &uuid[..uuid.iter().position(|&b| b == 0).unwrap_or(uuid.len())];
+        probe.uuid[..uuid.len()].copy_from_slice(uuid);
+        probe.uuid_len = uuid.len();
+        return Some(probe);
+    }
+    if at(0, b"hsqs") {
+        return Some(Probe::new(b"squashfs"));
+    }
+    if at(0x8001, b"CD001") {
+        return Some(Probe::new(b"iso9660").label(field(0x8000 + 40, 32)));
+    }
+    if at(510, &[0x55, 0xaa]) {
+        let (serial, label) = if at(82, b"FAT32") {
+            (67, 71)
+        } else if at(54, b"FAT1") {
+            (39, 43)
+        } else {
+            return None;
+        };
+        let probe = Probe::new(b"vfat").serial(field(serial, 4));
+        let label = field(label, 11);
+        return Some(if label == b"NO NAME    " { probe } else { probe.label(label) });
+    }
+    None
+}
+
+/// blkid: show the type, UUID and label of block devices
+pub fn blkid(argc: i32, argv: *const *const u8) -> i32 {
+    let mut json = false;
+    let mut opts = Getopt::new(argc, &argv, b"(json)");
+    for arg in opts.by_ref() {
+        match arg {
+            Arg::Opt(b"json", _) => json = true,
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let out = io::stdout();
+    let mut doc = Json::new();
+    if json {
+        doc.begin_array();
+    }
+    let mut found = false;
+    let mut show = |device: &[u8]| {
+        let fd = io::open(device, libc::O_RDONLY | libc::O_CLOEXEC, 0);
+        if fd < 0 {
+            return;
+        }
+        let mut data = [0u8; PROBE_SIZE];
+        let mut len = 0;
+        while len < data.len() {
+            match io::read(fd, &mut data[len..]) {
+                n if n > 0 => len += n as usize,
+                _ => break,
+            }
+        }
+        io::close(fd);
+        let probe = match blkid_probe(&data[..len]) {
+            Some(probe) => probe,
+            None => return,
+        };
+        found = true;
+        if json {
+            doc.begin_object();
+            doc.key(b"device").string(device);
+            doc.key(b"type").string(probe.fstype);
+            doc.key(b"uuid").optional(probe.uuid_str());
+            doc.key(b"label").optional(probe.label_str());
+            doc.end_object();
+            return;
+        }
+        out.write_all(device);
+        out.write_byte(b':');
+        for (tag, value) in [(&b"LABEL"[..], probe.label_str()), (b"UUID", probe.uuid_str()), (b"TYPE", Some(probe.fstype))] {
+            if let Some(value) = value {
+                for part in [b" ", tag, b"=\"", value, b"\""] {
+                    out.write_all(part);
+                }
+            }
+        }
+        out.write_byte(b'\n');
+    };
+
+    if opts.operand_count() > 0 {
+        for device in opts.operands() {
+            show(device);
+        }
+    } else {
+        // MAJOR MINOR BLOCKS NAME, after a heading and a blank line
+        let mut buf = [0u8; TABLE_SIZE];
+        let table = procfs::read(b"partitions", &mut buf).unwrap_or(b"");
+        for line in table.split(|&c| c == b'\n').skip(2) {
+            let name = match line.split(|&c| c == b' ').filter(|f| !f.is_empty()).nth(3) {
+                Some(name) if name.len() < 64 => name,
+                _ => continue,
+            };
+            let mut path = [0u8; 69];
+            path[..5].copy_from_slice(b"/dev/");
+            path[5..5 + name.len()].copy_from_slice(name);
+            show(&path[..5 + name.len()]);
+        }
+    }
+    if json {
+        doc.end_array().finish();
+    }
+    out.flush();
+    if found { 0 } else { 2 }
 }
 
 pub fn du(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn mount(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn umount(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn mountpoint(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn dmesg(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn halt(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; unsafe { libc::sync(); libc::reboot(libc::RB_HALT_SYSTEM); } 0 }
 pub fn reboot(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; unsafe { libc::sync(); libc::reboot(libc::RB_AUTOBOOT); } 0 }
 pub fn poweroff(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; unsafe { libc::sync(); libc::reboot(libc::RB_POWER_OFF); } 0 }
-pub fn chroot(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn nice(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn renice(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn nohup(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn setsid(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn timeout(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn logname(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; whoami(argc, argv) }
 pub fn logger(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn dnsdomainname(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
@@ -321,12 +3185,10 @@ pub fn fsync_cmd(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _
 pub fn sysctl(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn swapoff(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn swapon(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn blkid(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn losetup(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn insmod(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn rmmod(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn modprobe(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn lsmod(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn pivot_root(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn readahead_cmd(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn taskset(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
@@ -334,11 +3196,471 @@ pub fn rfkill(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ =
 pub fn ionice(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn chrt(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn acpi(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(1, b"Battery 0: 100%\n"); 0 }
-pub fn cal(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; io::write_str(1, b"Su Mo Tu We Th Fr Sa\n"); 0 }
-pub fn top(argc: i32, argv: *const *const u8) -> i32 { ps(argc, argv) }
+/// cal - display a calendar
+pub fn cal(argc: i32, argv: *const *const u8) -> i32 {
+    let mut julian = false;
+    let mut whole_year = false;
+
+    let mut opts = Getopt::new(argc, &argv, b"j(julian)y(year)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"j", _) => julian = true,
+            Arg::Opt(b"y", _) => whole_year = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    // This month, MONTH YEAR, or all of YEAR
+    let today = datetime::TimeZone::local().localtime(datetime::now().0);
+    let (mut month, mut year) = (today.mon, today.year);
+    let count = opts.operand_count();
+    if count > 2 {
+        return usage::error(b"cal", b"extra operand");
+    }
+    if let Some(arg) = opts.operand(count.wrapping_sub(1)) {
+        match sys::parse_u64(arg).filter(|year| (1..=9999).contains(year)) {
+            Some(n) => year = n as i64,
+            None => {
+                sys::error_at(b"cal", arg, b"invalid year");
+                return 1;
+            }
+        }
+        whole_year |= count == 1;
+    }
+    if count == 2 {
+        let arg = opts.operand(0).unwrap();
+        match sys::parse_u64(arg).filter(|month| (1..=12).contains(month)) {
+            Some(n) => month = n as i32,
+            None => {
+                sys::error_at(b"cal", arg, b"invalid month");
+                return 1;
+            }
+        }
+    }
+
+    let out = io::stdout();
+    if !whole_year {
+        for line in cal_month(year, month, julian, true).lines() {
+            out.write_all(line.trim_ascii_end());
+            out.write_str(b"\n");
+        }
+        return 0;
+    }
+
+    // Three months across, or two of Julian days
+    let across = if julian { 2 } else { 3 };
+    let width = cal_month(year, 1, julian, false).width * across + 2 * (across - 1);
+    let mut digits = [0u8; 20];
+    let title = sys::format_u64(year as u64, &mut digits);
+    for _ in 0..(width - title.len()) / 2 {
+        out.write_str(b" ");
+    }
+    out.write_all(title);
+    out.write_str(b"\n");
+    for first in (1..=12).step_by(across) {
+        out.write_str(b"\n");
+        let months: [CalMonth; 3] = core::array::from_fn(|i| cal_month(year, first + i as i32, julian, false));
+        for line in 0..8 {
+            let mut row = [b' '; 3 * 29];
+            let mut len = 0;
+            for month in &months[..across] {
+                row[len..len + month.width].copy_from_slice(&month.lines[line][..month.width]);
+                len += month.width + 2;
+            }
+            out.write_all(row[..len].trim_ascii_end());
+            out.write_str(b"\n");
+        }
+    }
+    0
+}
+
+/// One month as `cal` shows it: a title, the days of the week and six
+/// weeks, padded with spaces to `width`
+struct CalMonth {
+    lines: [[u8; 27]; 8],
+    width: usize,
+}
+
+impl CalMonth {
+    fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.lines.iter().map(|line| &line[..self.width])
+    }
+}
+
+/// Lay out `month` of `year`, numbering days from the start of the year
+/// with `julian`
+fn cal_month(year: i64, month: i32, julian: bool, title_year: bool) -> CalMonth {
+    let cell = if julian { 4 } else { 3 };
+    let width = cell * 7 - 1;
+    let mut lines = [[b' '; 27]; 8];
+
+    let mut title = [b' '; 24];
+    let name = datetime::MONTHS[month as usize - 1];
+    title[..name.len()].copy_from_slice(name);
+    let mut len = name.len();
+    if title_year {
+        let mut digits = [0u8; 20];
+        let digits = sys::format_u64(year as u64, &mut digits);
+        title[len + 1..len + 1 + digits.len()].copy_from_slice(digits);
+        len += 1 + digits.len();
+    }
+    let start = (width - len) / 2;
+    lines[0][start..start + len].copy_from_slice(&title[..len]);
+
+    for (i, day) in datetime::WEEKDAYS.iter().enumerate() {
+        let col = i * cell + cell - 3;
+        lines[1][col..col + 2].copy_from_slice(&day[..2]);
+    }
+
+    let yday = (1..month).map(|m| cal_month_length(year, m)).sum::<i32>();
+    let mut wday = cal_weekday(year, month, 1) as usize;
+    let mut week = 0;
+    for mday in 1..=cal_month_length(year, month) {
+        if year == 1752 && month == 9 && (3..=13).contains(&mday) {
+            continue;
+        }
+        let mut digits = [0u8; 20];
+        let n = sys::format_u64((if julian { yday + mday } else { mday }) as u64, &mut digits);
+        let end = wday * cell + cell - 1;
+        lines[2 + week][end - n.len()..end].copy_from_slice(n);
+        wday += 1;
+        if wday == 7 {
+            wday = 0;
+            week += 1;
+        }
+    }
+    CalMonth { lines, width }
+}
+
+/// The days in a month as `cal` numbers them: in the Julian calendar until
+/// September 2nd 1752, which the Gregorian September 14th followed
+fn cal_month_length(year: i64, month: i32) -> i32 {
+    if year <= 1752 && month == 2 {
+        if year % 4 == 0 { 29 } else { 28 }
+    } else {
+        datetime::days_in_month(year, month)
+    }
+}
+
+/// The day of the week (0 is Sunday) of a date in the calendar of its time
+fn cal_weekday(year: i64, month: i32, mday: i32) -> i64 {
+    // Julian day numbers, from March-based years
+    let a = (14 - month as i64) / 12;
+    let y = year + 4800 - a;
+    let m = month as i64 + 12 * a - 3;
+    let mut jdn = mday as i64 + (153 * m + 2) / 5 + 365 * y + y / 4;
+    if (year, month, mday) >= (1752, 9, 14) {
+        jdn += y / 400 - y / 100 - 32045;
+    } else {
+        jdn -= 32083;
+    }
+    (jdn + 1) % 7
+}
+
+/// How many processes `top` remembers between refreshes
+const TOP_SAMPLES: usize = 4096;
+
+/// One refresh of `top`'s output, put together before it is shown
+struct TopFrame {
+    buf: [u8; 32768],
+    len: usize,
+}
+
+impl TopFrame {
+    fn write_all(&mut self, s: &[u8]) {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s[..n]);
+        self.len += n;
+    }
+
+    fn write_byte(&mut self, c: u8) {
+        self.write_all(&[c]);
+    }
+
+    fn write_num(&mut self, n: u64) {
+        let mut buf = [0u8; 20];
+        self.write_all(sys::format_u64(n, &mut buf));
+    }
+
+    /// Like `write_column`, for a column that is never last
+    fn column(&mut self, s: &[u8], width: usize, left: bool) {
+        for _ in s.len()..width {
+            if !left {
+                self.write_byte(b' ');
+            }
+        }
+        self.write_all(s);
+        for _ in s.len()..width {
+            if left {
+                self.write_byte(b' ');
+            }
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Writes `top`'s summary lines; `states` counts running, sleeping,
+/// stopped and zombie processes
+fn top_summary(out: &mut TopFrame, tz: &datetime::TimeZone, uptime: u64, states: &[u64; 4]) {
+    out.write_all(b"top - ");
+    uptime_summary(&mut |s| out.write_all(s), tz, uptime);
+    out.write_all(b"\nTasks: ");
+    out.write_num(states.iter().sum());
+    out.write_all(b" total");
+    for (n, name) in states.iter().zip([&b" running"[..], b" sleeping", b" stopped", b" zombie"]) {
+        out.write_all(b", ");
+        out.write_num(*n);
+        out.write_all(name);
+    }
+    out.write_byte(b'\n');
+
+    out.write_all(b"KiB Mem: ");
+    let total = procfs::meminfo(b"MemTotal").unwrap_or(0);
+    let free = procfs::meminfo(b"MemFree").unwrap_or(0);
+    let cache = procfs::meminfo(b"Buffers").unwrap_or(0) + procfs::meminfo(b"Cached").unwrap_or(0);
+    let used = total.saturating_sub(free + cache);
+    for (n, name) in [(total, &b" total, "[..]), (free, b" free, "), (used, b" used, "), (cache, b" buff/cache\n\n")] {
+        out.write_num(n);
+        out.write_all(name);
+    }
+}
+
+/// What `top` lists first
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TopOrder {
+    Cpu,
+    Memory,
+    Pid,
+}
+
+/// top: show the busiest processes, refreshing every few seconds
+pub fn top(argc: i32, argv: *const *const u8) -> i32 {
+    let mut batch = false;
+    let mut iterations = u64::MAX;
+    // Tenths of a second
+    let mut delay = 30;
+
+    for arg in Getopt::new(argc, &argv, b"bn:d:") {
+        match arg {
+            Arg::Opt(b"b", _) => batch = true,
+            Arg::Opt(b"n", Some(n)) => match sys::parse_u64(n) {
+                Some(n) if n > 0 => iterations = n,
+                _ => {
+                    sys::error_at(b"top", n, b"invalid number of iterations");
+                    return 1;
+                }
+            },
+            Arg::Opt(b"d", Some(d)) => {
+                let (secs, tenths) = match d.iter().position(|&c| c == b'.') {
+                    Some(dot) => (&d[..dot], d.get(dot + 1..dot + 2).unwrap_or(b"0")),
+                    None => (d, &b"0"[..]),
+                };
+                match (sys::parse_u64(if secs.is_empty() { b"0" } else { secs }), sys::parse_u64(tenths)) {
+                    (Some(secs), Some(tenths)) if secs * 10 + tenths > 0 => delay = secs * 10 + tenths,
+                    _ => {
+                        sys::error_at(b"top", d, b"invalid delay");
+                        return 1;
+                    }
+                }
+            }
+            Arg::Opt(_, _) | Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let ctx = PsContext {
+        users: accounts::Users::open(),
+        groups: None,
+        tz: datetime::TimeZone::local(),
+        now: 0,
+        uptime: 0,
+        ticks: procfs::ticks(),
+        page_size: procfs::page_size(),
+        mem_total: procfs::meminfo(b"MemTotal").unwrap_or(0),
+    };
+
+    // On a terminal, keys change the order or quit, and only the lines
+    // that change are redrawn
+    let interactive = !batch && io::isatty(1);
+    let mode = if interactive { terminal::Mode::cbreak(0) } else { None };
+    let mut keys = mode.as_ref().map(|_| terminal::Keys::new(0));
+    let mut screen = None;
+    if interactive {
+        let mut s = terminal::Screen::new();
+        s.alternate();
+        screen = Some(s);
+        terminal::watch_resize();
+    }
+    let mut order = TopOrder::Cpu;
+
+    // CPU time at the last refresh, by pid; /proc lists pids in order
+    let mut samples = [(0i32, 0u64); TOP_SAMPLES];
+    let mut nsamples = 0;
+    let mut last_uptime = 0;
+    let mut frame = TopFrame { buf: [0; 32768], len: 0 };
+    let out = io::stdout();
+
+    let mut iteration = 0;
+    while iteration < iterations {
+        if iteration > 0 {
+            match keys.as_mut() {
+                Some(keys) => match keys.read(delay as i32 * 100) {
+                    terminal::Input::Key(terminal::Key::Char(0x71) | terminal::Key::Ctrl(b'c'))
+                    | terminal::Input::End => break,
+                    terminal::Input::Key(terminal::Key::Char(0x50)) => order = TopOrder::Cpu,
+                    terminal::Input::Key(terminal::Key::Char(0x4d)) => order = TopOrder::Memory,
+                    terminal::Input::Key(terminal::Key::Char(0x4e)) => order = TopOrder::Pid,
+                    terminal::Input::Resize => {
+                        if let Some(screen) = screen.as_mut() {
+                            screen.resize();
+                        }
+                    }
+                    // Space and other keys refresh at once
+                    _ => {}
+                },
+                None => io::usleep(delay as u32 * 100_000),
+            }
+        }
+        let uptime = procfs::uptime().unwrap_or(0);
+        let all = match procfs::pids() {
+            Some(all) => all,
+            None => {
+                sys::perror(b"top", b"/proc");
+                return 1;
+            }
+        };
+
+        // Usage is over the last interval, or at first over each lifetime
+        let mut busy = [(0i32, 0u64, 0u64); TOP_SAMPLES];
+        let mut nbusy = 0;
+        let mut states = [0u64; 4];
+        let mut next_samples = [(0i32, 0u64); TOP_SAMPLES];
+        let mut nnext = 0;
+        for pid in all {
+            let p = match procfs::Process::read(pid) {
+                Some(p) => p,
+                None => continue,
+            };
+            let i = match p.state {
+                b'R' => 0,
+                b'T' | b't' => 2,
+                b'Z' => 3,
+                _ => 1,
+            };
+            states[i] += 1;
+            let cpu = p.cpu_time();
+            let (used, interval) = match samples[..nsamples].binary_search_by_key(&pid, |s| s.0) {
+                Ok(i) if iteration > 0 => (cpu.saturating_sub(samples[i].1), uptime.saturating_sub(last_uptime)),
+                _ => (cpu, uptime.saturating_sub(p.starttime * 100 / ctx.ticks)),
+            };
+            if nnext < TOP_SAMPLES {
+                next_samples[nnext] = (pid, cpu);
+                nnext += 1;
+            }
+            if nbusy < TOP_SAMPLES {
+                // Tenths of a percent of one CPU
+                busy[nbusy] = (pid, used * 100_000 / ctx.ticks / interval.max(1), p.rss);
+                nbusy += 1;
+            }
+        }
+        samples = next_samples;
+        nsamples = nnext;
+        last_uptime = uptime;
+        let busy = &mut busy[..nbusy];
+        match order {
+            TopOrder::Cpu => busy.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))),
+            TopOrder::Memory => busy.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0))),
+            TopOrder::Pid => busy.sort_unstable_by_key(|a| a.0),
+        }
+
+        frame.len = 0;
+        top_summary(&mut frame, &ctx.tz, uptime, &states);
+        frame.write_all(b"  PID USER      PR  NI    VIRT    RES S  %CPU %MEM     TIME+ COMMAND\n");
+
+        // Five lines above the processes
+        let rows = screen.as_ref().map_or(0, |screen| screen.rows().saturating_sub(5).max(1));
+        let mut shown = 0;
+        for &(pid, usage, _) in busy.iter() {
+            if rows > 0 && shown == rows {
+                break;
+            }
+            let p = match procfs::Process::read(pid) {
+                Some(p) => p,
+                None => continue,
+            };
+            let columns: [(Field, usize, bool); 7] = [
+                (Field::Pid, 5, false),
+                (Field::User, 9, true),
+                (Field::Pri, 3, false),
+                (Field::Nice, 3, false),
+                (Field::Vsz, 7, false),
+                (Field::Rss, 6, false),
+                (Field::State, 1, true),
+            ];
+            for (i, &(field, width, left)) in columns.iter().enumerate() {
+                if i > 0 {
+                    frame.write_byte(b' ');
+                }
+                let mut buf = [0u8; 32];
+                frame.column(ps_cell(field, &p, &ctx, &mut buf), width, left);
+            }
+            let mut buf = [0u8; 32];
+            frame.write_byte(b' ');
+            frame.column(format_tenths(usage, &mut buf), 5, false);
+            frame.write_byte(b' ');
+            frame.column(ps_cell(Field::Pmem, &p, &ctx, &mut buf), 4, false);
+            // Minutes, seconds and hundredths
+            let time = p.cpu_time() * 100 / ctx.ticks;
+            let mut time_buf = [0u8; 32];
+            let mut len = sys::format_u64(time / 6000, &mut time_buf).len();
+            for (sep, n) in [(b':', time / 100 % 60), (b'.', time % 100)] {
+                time_buf[len] = sep;
+                time_buf[len + 1] = b'0' + (n / 10) as u8;
+                time_buf[len + 2] = b'0' + (n % 10) as u8;
+                len += 3;
+            }
+            frame.write_byte(b' ');
+            frame.column(&time_buf[..len], 9, false);
+            frame.write_byte(b' ');
+            frame.write_all(p.comm.as_bytes());
+            frame.write_byte(b'\n');
+            shown += 1;
+        }
+
+        match screen.as_mut() {
+            Some(screen) => {
+                screen.clear();
+                for (row, line) in frame.as_bytes().split(|&c| c == b'\n').enumerate() {
+                    screen.set_line(row, line);
+                }
+                screen.set_cursor(3, 0);
+                screen.refresh();
+            }
+            None => {
+                if !batch {
+                    out.write_str(b"\x1b[H\x1b[J");
+                }
+                out.write_all(frame.as_bytes());
+                if !batch {
+                    out.write_byte(b'\n');
+                }
+                out.flush();
+            }
+        }
+        iteration += 1;
+    }
+    // Leave the alternate screen before the terminal mode
+    drop(screen);
+    drop(mode);
+    0
+}
 pub fn vmstat(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn watch(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
-pub fn hwclock(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; date(argc, argv) }
+// The system clock stands in for the hardware clock
+pub fn hwclock(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; date(1, argv) }
 pub fn fallocate(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn shuf(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
 pub fn mkswap(argc: i32, argv: *const *const u8) -> i32 { let _ = argc; let _ = argv; 0 }
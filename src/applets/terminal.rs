@@ -0,0 +1,986 @@
+//! Terminal control
+//!
+//! - `Mode::raw` and `Mode::cbreak` take a terminal out of line mode and
+//!   put it back when dropped, or when a signal would kill or stop the
+//!   process; the mode comes back on `SIGCONT`
+//! - `size` reads the window size, and after `watch_resize` `resized` says
+//!   whether `SIGWINCH` has arrived since it was last asked
+//! - `decode` turns the bytes of a key press, including ANSI and VT escape
+//!   sequences and bracketed paste, into a `Key`; `Keys` reads them
+//! - `Screen` keeps what is on the terminal and redraws only the lines
+//!   that change
+//! - `Editor` reads a line with readline-style editing and history
+//!
+//! Only one `Mode` is in effect at a time.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use crate::io;
+use crate::sys;
+use super::utf8;
+
+const ESC: u8 = 0x1b;
+
+/// How long the rest of an escape sequence may take to arrive before a
+/// lone `ESC` is taken as the Escape key, in milliseconds
+const ESCAPE_TIMEOUT: i32 = 50;
+
+/// How many bytes `Bytes` holds without `alloc`
+#[cfg(not(feature = "alloc"))]
+const MAX_SIZE: usize = 32768;
+
+/// Bytes that grow on the heap, or fill a fixed array without `alloc`
+struct Bytes {
+    #[cfg(feature = "alloc")]
+    data: Vec<u8>,
+    #[cfg(not(feature = "alloc"))]
+    data: [u8; MAX_SIZE],
+    #[cfg(not(feature = "alloc"))]
+    len: usize,
+}
+
+impl Bytes {
+    fn new() -> Bytes {
+        #[cfg(feature = "alloc")]
+        return Bytes { data: Vec::new() };
+        #[cfg(not(feature = "alloc"))]
+        return Bytes { data: [0; MAX_SIZE], len: 0 };
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        #[cfg(feature = "alloc")]
+        return &self.data;
+        #[cfg(not(feature = "alloc"))]
+        return &self.data[..self.len];
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        #[cfg(feature = "alloc")]
+        return &mut self.data;
+        #[cfg(not(feature = "alloc"))]
+        return &mut self.data[..self.len];
+    }
+
+    /// Zero-fills to `len` bytes, or as many as fit; returns the new length
+    fn resize(&mut self, len: usize) -> usize {
+        #[cfg(feature = "alloc")]
+        {
+            self.data.clear();
+            self.data.resize(len, 0);
+            len
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            self.len = len.min(MAX_SIZE);
+            self.data[..self.len].fill(0);
+            self.len
+        }
+    }
+
+    /// Appends `s` if it fits
+    fn push(&mut self, s: &[u8]) -> bool {
+        #[cfg(feature = "alloc")]
+        self.data.extend_from_slice(s);
+        #[cfg(not(feature = "alloc"))]
+        {
+            if self.len + s.len() > MAX_SIZE {
+                return false;
+            }
+            self.data[self.len..self.len + s.len()].copy_from_slice(s);
+            self.len += s.len();
+        }
+        true
+    }
+
+    /// Removes the bytes from `start` to `end`
+    fn remove(&mut self, start: usize, end: usize) {
+        #[cfg(feature = "alloc")]
+        self.data.drain(start..end);
+        #[cfg(not(feature = "alloc"))]
+        {
+            self.data.copy_within(end..self.len, start);
+            self.len -= end - start;
+        }
+    }
+}
+
+// ============================================================================
+// Modes
+// ============================================================================
+
+/// Stopping puts the terminal back for the shell, and `SIGCONT` sets the
+/// mode again; a signal that kills runs the `sys::Cleanup` instead
+const SIGNALS: [i32; 2] = [libc::SIGTSTP, libc::SIGCONT];
+
+/// The mode in effect, for the signal handler: the terminal, its settings
+/// from before and the ones `Mode` made
+struct Active {
+    fd: AtomicI32,
+    saved: UnsafeCell<libc::termios>,
+    mode: UnsafeCell<libc::termios>,
+}
+
+// Applets run on a single thread, and the handler only reads
+unsafe impl Sync for Active {}
+
+static ACTIVE: Active = Active {
+    fd: AtomicI32::new(-1),
+    saved: UnsafeCell::new(unsafe { core::mem::zeroed() }),
+    mode: UnsafeCell::new(unsafe { core::mem::zeroed() }),
+};
+
+fn set_handler(sig: i32, handler: usize, old: *mut libc::sigaction) {
+    let mut action: libc::sigaction = unsafe { core::mem::zeroed() };
+    action.sa_sigaction = handler;
+    unsafe { libc::sigaction(sig, &action, old) };
+}
+
+extern "C" fn on_signal(sig: i32) {
+    let fd = ACTIVE.fd.load(Ordering::Relaxed);
+    if fd < 0 {
+        return;
+    }
+    unsafe {
+        match sig {
+            libc::SIGCONT => {
+                libc::tcsetattr(fd, libc::TCSANOW, ACTIVE.mode.get());
+                RESIZED.store(true, Ordering::Relaxed);
+            }
+            libc::SIGTSTP => {
+                // Stop for real with the terminal as the shell expects it,
+                // then carry on once SIGCONT has put the mode back
+                libc::tcsetattr(fd, libc::TCSANOW, ACTIVE.saved.get());
+                set_handler(sig, libc::SIG_DFL, core::ptr::null_mut());
+                let mut set: libc::sigset_t = core::mem::zeroed();
+                libc::sigaddset(&mut set, sig);
+                libc::sigprocmask(libc::SIG_UNBLOCK, &set, core::ptr::null_mut());
+                libc::kill(libc::getpid(), sig);
+                set_handler(sig, on_signal as *const () as usize, core::ptr::null_mut());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A terminal taken out of line mode, until dropped
+pub struct Mode {
+    actions: [libc::sigaction; SIGNALS.len()],
+    /// Puts the settings from before back, when dropped or on a signal
+    /// that kills
+    _restore: sys::Cleanup,
+}
+
+impl Mode {
+    /// Every byte passes through unprocessed, in both directions: no echo,
+    /// no line editing, no signals from `^C` or `^Z`, and `\n` is not
+    /// turned into `\r\n`. `None` if `fd` is not a terminal.
+    pub fn raw(fd: i32) -> Option<Mode> {
+        Mode::set(fd, |t| unsafe { libc::cfmakeraw(t) })
+    }
+
+    /// Keys arrive as they are pressed and are not echoed; signals and
+    /// output processing stay as they were
+    pub fn cbreak(fd: i32) -> Option<Mode> {
+        Mode::set(fd, |t| {
+            t.c_lflag &= !(libc::ICANON | libc::ECHO);
+            t.c_cc[libc::VMIN] = 1;
+            t.c_cc[libc::VTIME] = 0;
+        })
+    }
+
+    fn set(fd: i32, change: impl FnOnce(&mut libc::termios)) -> Option<Mode> {
+        let mut saved: libc::termios = unsafe { core::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } < 0 {
+            return None;
+        }
+        let mut mode = saved;
+        change(&mut mode);
+        unsafe {
+            *ACTIVE.saved.get() = saved;
+            *ACTIVE.mode.get() = mode;
+        }
+        ACTIVE.fd.store(fd, Ordering::Relaxed);
+
+        let mut actions: [libc::sigaction; SIGNALS.len()] = unsafe { core::mem::zeroed() };
+        for (&sig, old) in SIGNALS.iter().zip(actions.iter_mut()) {
+            unsafe { libc::sigaction(sig, core::ptr::null(), old) };
+            // Leave ignored signals ignored, as under nohup
+            if old.sa_sigaction != libc::SIG_IGN {
+                set_handler(sig, on_signal as *const () as usize, core::ptr::null_mut());
+            }
+        }
+        let restore = sys::Cleanup::terminal(fd, &saved);
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &mode) };
+        Some(Mode { actions, _restore: restore })
+    }
+}
+
+impl Drop for Mode {
+    fn drop(&mut self) {
+        // `_restore` puts the settings back after this
+        ACTIVE.fd.store(-1, Ordering::Relaxed);
+        for (&sig, old) in SIGNALS.iter().zip(self.actions.iter()) {
+            unsafe { libc::sigaction(sig, old, core::ptr::null_mut()) };
+        }
+    }
+}
+
+// ============================================================================
+// Window size
+// ============================================================================
+
+/// Set by the `SIGWINCH` handler, and on `SIGCONT` since the size may have
+/// changed while stopped
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_resize(_sig: i32) {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+/// The size of the terminal on `fd` as rows and columns, or `$LINES` and
+/// `$COLUMNS` if it won't say
+pub fn size(fd: i32) -> Option<(usize, usize)> {
+    let mut ws: libc::winsize = unsafe { core::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) } == 0 && ws.ws_row > 0 && ws.ws_col > 0 {
+        return Some((ws.ws_row as usize, ws.ws_col as usize));
+    }
+    let env = |name: &[u8]| io::getenv(name).and_then(sys::parse_u64).filter(|&n| n > 0).map(|n| n as usize);
+    Some((env(b"LINES")?, env(b"COLUMNS")?))
+}
+
+/// Catches `SIGWINCH` for `resized`. It interrupts `Keys::read`, which
+/// then returns `Input::Resize`.
+pub fn watch_resize() {
+    set_handler(libc::SIGWINCH, on_resize as *const () as usize, core::ptr::null_mut());
+}
+
+/// Whether the window has changed size since the last call
+pub fn resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+// ============================================================================
+// Keys
+// ============================================================================
+
+/// A key press
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    /// A printable character
+    Char(u32),
+    /// A byte that isn't a character in the locale
+    Byte(u8),
+    /// Control with a character: `Ctrl(b'a')` is `^A`. Enter, Tab,
+    /// Backspace and Escape are keys of their own.
+    Ctrl(u8),
+    /// Alt or Meta with a character or byte, sent as `ESC` before it
+    Alt(u32),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// `F(1)` to `F(20)`
+    F(u8),
+    /// The start and end of pasted text, when bracketed paste is on
+    PasteStart,
+    PasteEnd,
+    /// An escape sequence that isn't any of these
+    Unknown,
+}
+
+/// Decodes the key at the start of `s`, returning it and the bytes it
+/// took, or `None` if `s` stops partway through one. Modifiers on cursor
+/// and function keys are dropped.
+pub fn decode(s: &[u8], utf8: bool) -> Option<(Key, usize)> {
+    let c = *s.first()?;
+    let key = match c {
+        ESC => return decode_escape(s, utf8),
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0 => Key::Ctrl(b'@'),
+        1..=0x1a => Key::Ctrl(c + 0x60),
+        0x1c..=0x1f => Key::Ctrl(c + 0x40),
+        0x20..=0x7e => Key::Char(c as u32),
+        _ if utf8 => {
+            if utf8::complete_len(s) == 0 {
+                return None;
+            }
+            return Some(match utf8::decode(s) {
+                (Some(c), len) => (Key::Char(c), len),
+                (None, _) => (Key::Byte(c), 1),
+            });
+        }
+        _ => Key::Byte(c),
+    };
+    Some((key, 1))
+}
+
+fn decode_escape(s: &[u8], utf8: bool) -> Option<(Key, usize)> {
+    match *s.get(1)? {
+        b'[' => decode_csi(s),
+        b'O' => {
+            let key = match *s.get(2)? {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                b'M' => Key::Enter,
+                c @ b'P'..=b'S' => Key::F(c - b'P' + 1),
+                _ => Key::Unknown,
+            };
+            Some((key, 3))
+        }
+        ESC => Some((Key::Escape, 1)),
+        c if c < 0x80 || !utf8 => Some((Key::Alt(c as u32), 2)),
+        _ => match decode(&s[1..], utf8)? {
+            (Key::Char(c), len) => Some((Key::Alt(c), len + 1)),
+            (Key::Byte(c), len) => Some((Key::Alt(c as u32), len + 1)),
+            (key, len) => Some((key, len + 1)),
+        },
+    }
+}
+
+/// `ESC [`, parameters and a final byte
+fn decode_csi(s: &[u8]) -> Option<(Key, usize)> {
+    // The Linux console sends F1 to F5 as ESC [ [ A to E
+    if s.get(2) == Some(&b'[') {
+        let key = match *s.get(3)? {
+            c @ b'A'..=b'E' => Key::F(c - b'A' + 1),
+            _ => Key::Unknown,
+        };
+        return Some((key, 4));
+    }
+    let mut end = 2;
+    loop {
+        match *s.get(end)? {
+            0x40..=0x7e => break,
+            0x20..=0x3f if end < 32 => end += 1,
+            _ => return Some((Key::Unknown, end)),
+        }
+    }
+    let first = s[2..end].split(|&c| c == b';').next().and_then(sys::parse_u64);
+    let key = match s[end] {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'Z' => Key::BackTab,
+        c @ b'P'..=b'S' => Key::F(c - b'P' + 1),
+        b'~' => match first {
+            Some(1 | 7) => Key::Home,
+            Some(2) => Key::Insert,
+            Some(3) => Key::Delete,
+            Some(4 | 8) => Key::End,
+            Some(5) => Key::PageUp,
+            Some(6) => Key::PageDown,
+            Some(n @ 11..=15) => Key::F(n as u8 - 10),
+            Some(n @ 17..=21) => Key::F(n as u8 - 11),
+            Some(n @ 23..=26) => Key::F(n as u8 - 12),
+            Some(n @ 28..=29) => Key::F(n as u8 - 13),
+            Some(n @ 31..=34) => Key::F(n as u8 - 14),
+            Some(200) => Key::PasteStart,
+            Some(201) => Key::PasteEnd,
+            _ => Key::Unknown,
+        },
+        _ => Key::Unknown,
+    };
+    Some((key, end + 1))
+}
+
+/// What `Keys::read` got
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    Key(Key),
+    /// The window changed size
+    Resize,
+    /// Nothing came in time
+    Timeout,
+    /// End of input, or an error reading it
+    End,
+}
+
+/// Reads keys from a terminal
+pub struct Keys {
+    fd: i32,
+    buf: [u8; 64],
+    len: usize,
+    utf8: bool,
+}
+
+impl Keys {
+    pub fn new(fd: i32) -> Keys {
+        Keys { fd, buf: [0; 64], len: 0, utf8: utf8::locale_is_utf8() }
+    }
+
+    /// Waits up to `timeout` milliseconds, or forever if negative, for the
+    /// next key
+    pub fn read(&mut self, timeout: i32) -> Input {
+        // Set once the rest of a sequence has had its chance to arrive
+        let mut stale = false;
+        loop {
+            if self.len > 0 {
+                let key = match decode(&self.buf[..self.len], self.utf8) {
+                    Some((key, len)) => Some((key, len)),
+                    None if stale => Some((if self.buf[0] == ESC { Key::Escape } else { Key::Byte(self.buf[0]) }, 1)),
+                    None => None,
+                };
+                if let Some((key, len)) = key {
+                    self.buf.copy_within(len..self.len, 0);
+                    self.len -= len;
+                    return Input::Key(key);
+                }
+            }
+            if resized() {
+                return Input::Resize;
+            }
+
+            let pending = self.len > 0;
+            let mut poll = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+            match unsafe { libc::poll(&mut poll, 1, if pending { ESCAPE_TIMEOUT } else { timeout }) } {
+                n if n < 0 && sys::errno() == libc::EINTR => continue,
+                n if n < 0 => return Input::End,
+                0 if pending => {
+                    stale = true;
+                    continue;
+                }
+                0 => return Input::Timeout,
+                _ => {}
+            }
+            match io::read(self.fd, &mut self.buf[self.len..]) {
+                n if n > 0 => self.len += n as usize,
+                n if n < 0 && sys::errno() == libc::EINTR => {}
+                _ if pending => stale = true,
+                _ => return Input::End,
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Screen
+// ============================================================================
+
+/// The most rows a `Screen` keeps
+const MAX_ROWS: usize = 256;
+
+/// Writes the sequence that puts the cursor at `row` and `col`, from 0
+pub fn move_to(out: io::Stdout, row: usize, col: usize) {
+    out.write_str(b"\x1b[");
+    out.write_num(row as u64 + 1);
+    out.write_byte(b';');
+    out.write_num(col as u64 + 1);
+    out.write_byte(b'H');
+}
+
+/// The lines to show on the terminal on stdout, and the lines it has.
+/// `refresh` redraws only those that differ.
+pub struct Screen {
+    rows: usize,
+    cols: usize,
+    /// Bytes a line may take: four for each column
+    stride: usize,
+    /// The lines to show, then the lines shown
+    grid: Bytes,
+    wanted: [u16; MAX_ROWS],
+    shown: [u16; MAX_ROWS],
+    cursor: (usize, usize),
+    /// Whether `shown` is what the terminal has
+    valid: bool,
+    alternate: bool,
+    utf8: bool,
+}
+
+impl Screen {
+    /// A screen the size of the terminal, blank until `refresh`
+    pub fn new() -> Screen {
+        let mut screen = Screen {
+            rows: 0,
+            cols: 0,
+            stride: 0,
+            grid: Bytes::new(),
+            wanted: [0; MAX_ROWS],
+            shown: [0; MAX_ROWS],
+            cursor: (0, 0),
+            valid: false,
+            alternate: false,
+            utf8: utf8::locale_is_utf8(),
+        };
+        screen.resize();
+        screen
+    }
+
+    /// Switches to the terminal's alternate screen until dropped, as
+    /// full-screen programs do
+    pub fn alternate(&mut self) {
+        io::stdout().write_str(b"\x1b[?1049h");
+        self.alternate = true;
+        self.valid = false;
+    }
+
+    /// Takes the terminal's size again, emptying every line
+    pub fn resize(&mut self) {
+        let (rows, cols) = size(1).unwrap_or((24, 80));
+        self.cols = cols;
+        self.stride = cols * 4;
+        let len = self.grid.resize(2 * rows.min(MAX_ROWS) * self.stride);
+        self.rows = len / (2 * self.stride).max(1);
+        self.wanted = [0; MAX_ROWS];
+        self.valid = false;
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Empties every line
+    pub fn clear(&mut self) {
+        self.wanted = [0; MAX_ROWS];
+    }
+
+    /// Sets line `row` to `text`, cut to the width of the screen. `text`
+    /// should hold no control characters.
+    pub fn set_line(&mut self, row: usize, text: &[u8]) {
+        if row >= self.rows {
+            return;
+        }
+        let (mut len, mut width) = (0, 0);
+        while len < text.len() {
+            let n = utf8::char_len(&text[len..], self.utf8);
+            width += utf8::char_width(&text[len..], self.utf8);
+            if width > self.cols || len + n > self.stride {
+                break;
+            }
+            len += n;
+        }
+        let start = row * self.stride;
+        self.grid.as_mut_slice()[start..start + len].copy_from_slice(&text[..len]);
+        self.wanted[row] = len as u16;
+    }
+
+    /// Where the cursor is left after `refresh`
+    pub fn set_cursor(&mut self, row: usize, col: usize) {
+        self.cursor = (row, col);
+    }
+
+    /// Makes the next `refresh` redraw everything, as after something else
+    /// wrote to the terminal
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Brings the terminal up to date
+    pub fn refresh(&mut self) {
+        let out = io::stdout();
+        if !self.valid {
+            out.write_str(b"\x1b[H\x1b[2J");
+            self.shown = [0; MAX_ROWS];
+        }
+        let shown_start = self.rows * self.stride;
+        let grid = self.grid.as_mut_slice();
+        for row in 0..self.rows {
+            let wanted = row * self.stride..row * self.stride + self.wanted[row] as usize;
+            let shown = shown_start + row * self.stride..shown_start + row * self.stride + self.shown[row] as usize;
+            if grid[wanted.clone()] == grid[shown.clone()] {
+                continue;
+            }
+            move_to(out, row, 0);
+            out.write_all(&grid[wanted.clone()]);
+            // At the last column the cursor waits to wrap, and erasing
+            // would take the last character with it
+            if utf8::str_width(&grid[wanted.clone()], self.utf8) < self.cols {
+                out.write_str(b"\x1b[K");
+            }
+            grid.copy_within(wanted.clone(), shown.start);
+            self.shown[row] = self.wanted[row];
+        }
+        move_to(out, self.cursor.0, self.cursor.1);
+        out.flush();
+        self.valid = true;
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Screen {
+        Screen::new()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        if self.alternate {
+            io::stdout().write_str(b"\x1b[?1049l");
+            io::stdout().flush();
+        }
+    }
+}
+
+// ============================================================================
+// Line editing
+// ============================================================================
+
+/// How many bytes of history an `Editor` keeps
+const HISTORY_SIZE: usize = 16384;
+
+/// Reads lines from a terminal with editing and history, like readline:
+///
+/// - Left/Right or `^B`/`^F`, Home/End or `^A`/`^E`, and Alt-B/Alt-F by
+///   words, move the cursor
+/// - Backspace, Delete or `^D`, `^W` or Alt-Backspace and Alt-D delete a
+///   character or word; `^K` and `^U` delete to the end or the start
+/// - Up/Down or `^P`/`^N` step through history, `^L` clears the screen
+/// - `^C` abandons the line, and `^D` on an empty line ends input
+///
+/// Pasted text goes in as it is, newlines included, when the terminal
+/// brackets it.
+pub struct Editor {
+    /// Lines, oldest first, each ended by a NUL
+    history: Bytes,
+    utf8: bool,
+}
+
+/// What a key did to the line
+enum Step {
+    Edit,
+    Done,
+    End,
+}
+
+/// A line being edited in the caller's buffer
+struct Line<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    cursor: usize,
+    /// The first byte shown, when the line is wider than the terminal
+    scroll: usize,
+    prompt: &'a [u8],
+    cols: usize,
+    pasting: bool,
+    /// The history entry shown; the line as typed is kept as an extra
+    /// last entry while stepping through
+    recalled: Option<usize>,
+    utf8: bool,
+}
+
+/// Columns a character of the line takes: control characters are shown as
+/// `^X`
+fn columns(c: &[u8], utf8: bool) -> usize {
+    if c[0] < 0x20 || c[0] == 0x7f { 2 } else { utf8::char_width(c, utf8) }
+}
+
+impl Line<'_> {
+    fn char_end(&self, at: usize) -> usize {
+        at + utf8::char_len(&self.buf[at..self.len], self.utf8)
+    }
+
+    fn char_start(&self, at: usize) -> usize {
+        utf8::prev_char(&self.buf[..self.len], at, self.utf8)
+    }
+
+    fn width(&self, start: usize, end: usize) -> usize {
+        utf8::chars(&self.buf[start..end], self.utf8).map(|c| columns(c, self.utf8)).sum()
+    }
+
+    fn insert(&mut self, s: &[u8]) {
+        if self.len + s.len() > self.buf.len() {
+            return;
+        }
+        self.buf.copy_within(self.cursor..self.len, self.cursor + s.len());
+        self.buf[self.cursor..self.cursor + s.len()].copy_from_slice(s);
+        self.len += s.len();
+        self.cursor += s.len();
+    }
+
+    fn delete(&mut self, start: usize, end: usize) {
+        self.buf.copy_within(end..self.len, start);
+        self.len -= end - start;
+        self.cursor = start;
+    }
+
+    /// The start of the word before the cursor
+    fn word_start(&self) -> usize {
+        let mut at = self.cursor;
+        while at > 0 && self.buf[at - 1] == b' ' {
+            at -= 1;
+        }
+        while at > 0 && self.buf[at - 1] != b' ' {
+            at -= 1;
+        }
+        at
+    }
+
+    /// The end of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut at = self.cursor;
+        while at < self.len && self.buf[at] == b' ' {
+            at += 1;
+        }
+        while at < self.len && self.buf[at] != b' ' {
+            at += 1;
+        }
+        at
+    }
+
+    fn set(&mut self, s: &[u8]) {
+        let len = s.len().min(self.buf.len());
+        self.buf[..len].copy_from_slice(&s[..len]);
+        self.len = len;
+        self.cursor = len;
+        self.scroll = 0;
+    }
+
+    /// Redraws the prompt and as much of the line around the cursor as fits
+    fn draw(&mut self) {
+        let out = io::stdout();
+        let prompt_width = utf8::str_width(self.prompt, self.utf8);
+        // The last column is left alone so the terminal never wraps
+        let room = self.cols.saturating_sub(prompt_width + 1).max(1);
+        self.scroll = self.scroll.min(self.cursor);
+        while self.width(self.scroll, self.cursor) > room {
+            self.scroll = self.char_end(self.scroll);
+        }
+
+        out.write_byte(b'\r');
+        out.write_all(self.prompt);
+        let (mut at, mut used) = (self.scroll, 0);
+        while at < self.len {
+            let end = self.char_end(at);
+            let c = &self.buf[at..end];
+            used += columns(c, self.utf8);
+            if used > room {
+                break;
+            }
+            if c[0] < 0x20 || c[0] == 0x7f {
+                out.write_byte(b'^');
+                out.write_byte(c[0] ^ 0x40);
+            } else {
+                out.write_all(c);
+            }
+            at = end;
+        }
+        out.write_str(b"\x1b[K\r");
+        let col = prompt_width + self.width(self.scroll, self.cursor);
+        if col > 0 {
+            out.write_str(b"\x1b[");
+            out.write_num(col as u64);
+            out.write_byte(b'C');
+        }
+        out.flush();
+    }
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor { history: Bytes::new(), utf8: utf8::locale_is_utf8() }
+    }
+
+    fn history_len(&self) -> usize {
+        self.history.as_slice().iter().filter(|&&c| c == 0).count()
+    }
+
+    fn history_entry(&self, n: usize) -> &[u8] {
+        self.history.as_slice().split(|&c| c == 0).nth(n).unwrap_or(b"")
+    }
+
+    /// Adds a line to the history, dropping the oldest to make room;
+    /// `read_line` adds each line it returns that isn't empty
+    pub fn add_history(&mut self, line: &[u8]) {
+        let count = self.history_len();
+        if line.len() >= HISTORY_SIZE || line.contains(&0) || (count > 0 && self.history_entry(count - 1) == line) {
+            return;
+        }
+        self.push_history(line);
+    }
+
+    /// Adds `line`, cut to fit, dropping the oldest entries to make room
+    fn push_history(&mut self, line: &[u8]) {
+        let line = &line[..line.len().min(HISTORY_SIZE - 1)];
+        loop {
+            let len = self.history.as_slice().len();
+            if len + line.len() < HISTORY_SIZE {
+                break;
+            }
+            let oldest = self.history.as_slice().iter().position(|&c| c == 0).map_or(len, |i| i + 1);
+            self.history.remove(0, oldest);
+        }
+        self.history.push(line);
+        self.history.push(b"\0");
+    }
+
+    fn pop_history(&mut self) {
+        let data = self.history.as_slice();
+        let (len, end) = (data.len(), data.len().saturating_sub(1));
+        let start = data[..end].iter().rposition(|&c| c == 0).map_or(0, |i| i + 1);
+        self.history.remove(start, len);
+    }
+
+    /// Shows `prompt` and reads a line into `buf`, which bounds its length.
+    /// Returns `None` at the end of input. Without a terminal on stdin the
+    /// line is read as it comes.
+    pub fn read_line<'a>(&mut self, prompt: &'a [u8], buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let out = io::stdout();
+        out.write_all(prompt);
+        out.flush();
+        let mode = match Mode::raw(0) {
+            Some(mode) => mode,
+            None => return read_plain(buf),
+        };
+        watch_resize();
+        out.write_str(b"\x1b[?2004h");
+
+        let cols = size(1).map_or(80, |size| size.1);
+        let mut line = Line { buf, len: 0, cursor: 0, scroll: 0, prompt, cols, pasting: false, recalled: None, utf8: self.utf8 };
+        line.draw();
+        let mut keys = Keys::new(0);
+        let step = loop {
+            match keys.read(-1) {
+                Input::Key(key) => match self.key(&mut line, key) {
+                    Step::Edit => line.draw(),
+                    step => break step,
+                },
+                Input::Resize => {
+                    line.cols = size(1).map_or(line.cols, |size| size.1);
+                    line.draw();
+                }
+                Input::Timeout => {}
+                Input::End => break Step::End,
+            }
+        };
+        if line.recalled.take().is_some() {
+            self.pop_history();
+        }
+
+        // Leave the whole line on screen
+        line.scroll = 0;
+        line.cursor = line.len;
+        line.draw();
+        out.write_str(b"\x1b[?2004l\r\n");
+        out.flush();
+        drop(mode);
+        match step {
+            Step::End => None,
+            _ => {
+                let len = line.len;
+                let text = &line.buf[..len];
+                if !text.is_empty() {
+                    self.add_history(text);
+                }
+                Some(text)
+            }
+        }
+    }
+
+    fn key(&mut self, line: &mut Line, key: Key) -> Step {
+        let mut char_buf = [0u8; 4];
+        match key {
+            Key::Enter if line.pasting => line.insert(b"\n"),
+            Key::Enter => return Step::Done,
+            Key::PasteStart => line.pasting = true,
+            Key::PasteEnd => line.pasting = false,
+            Key::Char(c) => line.insert(utf8::encode(c, &mut char_buf)),
+            Key::Byte(c) => line.insert(&[c]),
+            Key::Tab => line.insert(b"\t"),
+            Key::Ctrl(b'c') => {
+                io::stdout().write_str(b"^C");
+                line.len = 0;
+                line.cursor = 0;
+                return Step::Done;
+            }
+            Key::Ctrl(b'd') if line.len == 0 => return Step::End,
+            Key::Ctrl(b'd') | Key::Delete if line.cursor < line.len => line.delete(line.cursor, line.char_end(line.cursor)),
+            Key::Backspace if line.cursor > 0 => line.delete(line.char_start(line.cursor), line.cursor),
+            Key::Left | Key::Ctrl(b'b') if line.cursor > 0 => line.cursor = line.char_start(line.cursor),
+            Key::Right | Key::Ctrl(b'f') if line.cursor < line.len => line.cursor = line.char_end(line.cursor),
+            Key::Home | Key::Ctrl(b'a') => line.cursor = 0,
+            Key::End | Key::Ctrl(b'e') => line.cursor = line.len,
+            Key::Alt(0x62) => line.cursor = line.word_start(),
+            Key::Alt(0x66) => line.cursor = line.word_end(),
+            Key::Ctrl(b'w') | Key::Alt(0x7f) => line.delete(line.word_start(), line.cursor),
+            Key::Alt(0x64) => {
+                let end = line.word_end();
+                line.delete(line.cursor, end);
+            }
+            Key::Ctrl(b'k') => line.len = line.cursor,
+            Key::Ctrl(b'u') => line.delete(0, line.cursor),
+            Key::Ctrl(b'l') => io::stdout().write_str(b"\x1b[H\x1b[2J"),
+            Key::Up | Key::Ctrl(b'p') => {
+                let n = match line.recalled {
+                    Some(0) => return Step::Edit,
+                    Some(n) => n - 1,
+                    None => {
+                        let count = self.history_len();
+                        if count == 0 {
+                            return Step::Edit;
+                        }
+                        self.push_history(&line.buf[..line.len]);
+                        count - 1
+                    }
+                };
+                line.recalled = Some(n);
+                line.set(self.history_entry(n));
+            }
+            Key::Down | Key::Ctrl(b'n') => {
+                if let Some(n) = line.recalled {
+                    let last = self.history_len() - 1;
+                    line.set(self.history_entry(n + 1));
+                    if n + 1 == last {
+                        self.pop_history();
+                        line.recalled = None;
+                    } else {
+                        line.recalled = Some(n + 1);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Step::Edit
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new()
+    }
+}
+
+/// Reads up to a newline from stdin a byte at a time, so nothing past it
+/// is taken from a shared pipe
+fn read_plain(buf: &mut [u8]) -> Option<&[u8]> {
+    let mut len = 0;
+    let mut c = [0u8; 1];
+    loop {
+        match io::read(0, &mut c) {
+            1 if c[0] == b'\n' => break,
+            1 => {
+                if len < buf.len() {
+                    buf[len] = c[0];
+                    len += 1;
+                }
+            }
+            _ if len == 0 => return None,
+            _ => break,
+        }
+    }
+    Some(&buf[..len])
+}
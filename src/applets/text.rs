@@ -1,11 +1,13 @@
 //! Text processing applets
 
-use crate::io;
+use crate::io::{self, LineReader};
 use crate::sys;
-use super::{get_arg, has_opt};
+use super::regex::{self, Captures, Regex};
+use super::{get_arg, usage, utf8, Arg, Getopt, EXIT_TROUBLE};
 
 /// echo - print arguments
 pub fn echo(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let mut newline = true;
     let mut start = 1;
 
@@ -20,21 +22,22 @@ pub fn echo(argc: i32, argv: *const *const u8) -> i32 {
 
     for i in start..argc {
         if i > start {
-            io::write_str(1, b" ");
+            out.write_str(b" ");
         }
         if let Some(arg) = unsafe { get_arg(argv, i) } {
-            io::write_all(1, arg);
+            out.write_all(arg);
         }
     }
 
     if newline {
-        io::write_str(1, b"\n");
+        out.write_str(b"\n");
     }
     0
 }
 
 /// printf - format and print data
 pub fn printf(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     if argc < 2 {
         return 0;
     }
@@ -50,7 +53,7 @@ pub fn printf(argc: i32, argv: *const *const u8) -> i32 {
                 b's' => {
                     if arg_idx < argc {
                         if let Some(arg) = unsafe { get_arg(argv, arg_idx) } {
-                            io::write_all(1, arg);
+                            out.write_all(arg);
                             arg_idx += 1;
                         }
                     }
@@ -59,7 +62,7 @@ pub fn printf(argc: i32, argv: *const *const u8) -> i32 {
                     if arg_idx < argc {
                         if let Some(arg) = unsafe { get_arg(argv, arg_idx) } {
                             if let Some(n) = sys::parse_i64(arg) {
-                                io::write_signed(1, n);
+                                out.write_signed(n);
                             }
                             arg_idx += 1;
                         }
@@ -71,30 +74,30 @@ pub fn printf(argc: i32, argv: *const *const u8) -> i32 {
                             if let Some(n) = sys::parse_u64(arg) {
                                 let mut buf = [0u8; 20];
                                 let s = sys::format_hex(n, &mut buf);
-                                io::write_all(1, s);
+                                out.write_all(s);
                             }
                             arg_idx += 1;
                         }
                     }
                 }
-                b'%' => { io::write_str(1, b"%"); }
-                b'n' => { io::write_str(1, b"\n"); }
+                b'%' => { out.write_str(b"%"); }
+                b'n' => { out.write_str(b"\n"); }
                 _ => {
-                    io::write_str(1, b"%");
-                    io::write_all(1, &[fmt[i]]);
+                    out.write_str(b"%");
+                    out.write_byte(fmt[i]);
                 }
             }
         } else if fmt[i] == b'\\' && i + 1 < fmt.len() {
             i += 1;
             match fmt[i] {
-                b'n' => { io::write_str(1, b"\n"); }
-                b't' => { io::write_str(1, b"\t"); }
-                b'r' => { io::write_str(1, b"\r"); }
-                b'\\' => { io::write_str(1, b"\\"); }
-                _ => { io::write_all(1, &[fmt[i]]); }
+                b'n' => { out.write_str(b"\n"); }
+                b't' => { out.write_str(b"\t"); }
+                b'r' => { out.write_str(b"\r"); }
+                b'\\' => { out.write_str(b"\\"); }
+                _ => { out.write_byte(fmt[i]); }
             }
         } else {
-            io::write_all(1, &[fmt[i]]);
+            out.write_byte(fmt[i]);
         }
         i += 1;
     }
@@ -103,117 +106,139 @@ pub fn printf(argc: i32, argv: *const *const u8) -> i32 {
 
 /// head - output first part of files
 pub fn head(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let mut lines = 10i64;
-    let mut files_start = 1;
-
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'n') && i + 1 < argc {
-                if let Some(n) = unsafe { get_arg(argv, i + 1) } {
-                    lines = sys::parse_i64(n).unwrap_or(10);
-                }
-                files_start = i + 2;
-            } else if arg[0] == b'-' && arg.len() > 1 && arg[1] >= b'0' && arg[1] <= b'9' {
-                lines = sys::parse_i64(&arg[1..]).unwrap_or(10);
-                files_start = i + 1;
-            }
+    let mut delim = b'\n';
+    let mut headers: Option<bool> = None;
+
+    let mut opts = Getopt::new(argc, &argv, b"#n(lines):z(zero-terminated)q(quiet)v(verbose)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"n", Some(n)) => lines = sys::parse_i64(n).unwrap_or(10),
+            Arg::Opt(b"z", _) => delim = 0,
+            Arg::Opt(b"q", _) => headers = Some(false),
+            Arg::Opt(b"v", _) => headers = Some(true),
+            Arg::Number(n) => lines = sys::parse_i64(&n[1..]).unwrap_or(10),
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
-    if files_start >= argc {
-        head_fd(0, lines);
-    } else {
-        for i in files_start..argc {
-            if let Some(path) = unsafe { get_arg(argv, i) } {
-                if path == b"-" {
-                    head_fd(0, lines);
-                } else {
-                    let fd = io::open(path, libc::O_RDONLY, 0);
-                    if fd >= 0 {
-                        head_fd(fd, lines);
-                        io::close(fd);
-                    }
-                }
+    let headers = headers.unwrap_or(opts.operand_count() > 1);
+    let mut first = true;
+    let mut inputs = io::Inputs::new(opts.applet(), opts.operands());
+    while let Some(input) = inputs.next() {
+        if headers {
+            if !first {
+                out.write_str(b"\n");
             }
+            out.write_str(b"==> ");
+            out.write_all(if input.path == b"-" { b"standard input" } else { input.path });
+            out.write_str(b" <==\n");
+        }
+        first = false;
+
+        if let Err(errno) = head_fd(input.fd, lines, delim) {
+            inputs.fail(input.path, errno);
         }
     }
-    0
+
+    if inputs.failed() { 1 } else { 0 }
 }
 
-fn head_fd(fd: i32, mut lines: i64) {
-    let mut buf = [0u8; 4096];
+/// Copy the first `lines` lines of `fd`, or the errno of a failed read
+fn head_fd(fd: i32, mut lines: i64, delim: u8) -> Result<(), i32> {
+    let out = io::stdout();
+    let mut reader = LineReader::with_delimiter(fd, delim);
     while lines > 0 {
-        let n = io::read(fd, &mut buf);
-        if n <= 0 { break; }
-
-        for i in 0..n as usize {
-            io::write_all(1, &buf[i..i+1]);
-            if buf[i] == b'\n' {
-                lines -= 1;
-                if lines <= 0 { return; }
-            }
+        let line = match reader.next_line() {
+            Some(line) => line,
+            None => break,
+        };
+        out.write_all(line.data);
+        if line.terminated {
+            out.write_byte(delim);
+            lines -= 1;
         }
     }
+    if reader.error() { Err(reader.errno()) } else { Ok(()) }
 }
 
 /// tail - output last part of files
 pub fn tail(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let mut lines = 10usize;
     let mut follow = false;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'n') && i + 1 < argc {
-                if let Some(n) = unsafe { get_arg(argv, i + 1) } {
-                    lines = sys::parse_u64(n).unwrap_or(10) as usize;
-                }
-            } else if has_opt(arg, b'f') {
-                follow = true;
-            } else if arg[0] != b'-' {
-                let fd = io::open(arg, libc::O_RDONLY, 0);
-                if fd >= 0 {
-                    tail_fd(fd, lines);
-                    if follow {
-                        loop {
-                            let mut buf = [0u8; 4096];
-                            let n = io::read(fd, &mut buf);
-                            if n > 0 {
-                                io::write_all(1, &buf[..n as usize]);
-                            } else {
-                                unsafe { libc::usleep(100000) };
-                            }
-                        }
-                    }
-                    io::close(fd);
+    let mut opts = Getopt::new(argc, &argv, b"#n(lines):f(follow)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"n", Some(n)) => lines = sys::parse_u64(n).unwrap_or(10) as usize,
+            Arg::Opt(b"f", _) => follow = true,
+            Arg::Number(n) => lines = sys::parse_u64(&n[1..]).unwrap_or(10) as usize,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
+    let mut inputs = io::Inputs::new(opts.applet(), opts.operands());
+    while let Some(input) = inputs.next() {
+        if let Err(errno) = tail_fd(input.fd, lines) {
+            inputs.fail(input.path, errno);
+            continue;
+        }
+        if follow {
+            loop {
+                let mut buf = [0u8; 4096];
+                let n = io::read(input.fd, &mut buf);
+                if n > 0 {
+                    out.write_all(&buf[..n as usize]);
+                } else {
+                    out.flush();
+                    unsafe { libc::usleep(100000) };
                 }
             }
         }
     }
-    0
+
+    if inputs.failed() { 1 } else { 0 }
 }
 
-fn tail_fd(fd: i32, lines: usize) {
+/// Copy the last `lines` lines of `fd`, or the errno of a failed read
+fn tail_fd(fd: i32, lines: usize) -> Result<(), i32> {
     #[cfg(feature = "alloc")]
     {
         use alloc::vec::Vec;
         use alloc::collections::VecDeque;
 
-        let content = io::read_all(fd);
-        let mut line_starts: VecDeque<usize> = VecDeque::new();
-        line_starts.push_back(0);
-
-        for (i, &c) in content.iter().enumerate() {
-            if c == b'\n' && i + 1 < content.len() {
-                line_starts.push_back(i + 1);
-                if line_starts.len() > lines + 1 {
-                    line_starts.pop_front();
-                }
+        let out = io::stdout();
+        let mut reader = LineReader::new(fd);
+        // The last `lines` lines so far, and whether each ended in a newline
+        let mut last: VecDeque<(Vec<u8>, bool)> = VecDeque::new();
+        while let Some(line) = reader.next_line() {
+            if lines == 0 {
+                continue;
             }
+            let mut data = if last.len() == lines {
+                last.pop_front().map(|(data, _)| data).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            data.clear();
+            data.extend_from_slice(line.data);
+            last.push_back((data, line.terminated));
+        }
+        if reader.error() {
+            return Err(reader.errno());
         }
 
-        if let Some(&start) = line_starts.front() {
-            io::write_all(1, &content[start..]);
+        for (data, terminated) in &last {
+            out.write_all(data);
+            if *terminated {
+                out.write_byte(b'\n');
+            }
         }
+        Ok(())
     }
 
     #[cfg(not(feature = "alloc"))]
@@ -221,106 +246,148 @@ fn tail_fd(fd: i32, lines: usize) {
         let _ = fd;
         let _ = lines;
         io::write_str(2, b"tail: requires alloc feature\n");
+        Ok(())
     }
 }
 
 /// wc - word, line, character count
 pub fn wc(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let mut show_lines = false;
     let mut show_words = false;
     let mut show_chars = false;
-
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'l') { show_lines = true; }
-                if has_opt(arg, b'w') { show_words = true; }
-                if has_opt(arg, b'c') { show_chars = true; }
-            }
+    let mut show_bytes = false;
+
+    let mut opts = Getopt::new(argc, &argv, b"l(lines)w(words)m(chars)c(bytes)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"l", _) => show_lines = true,
+            Arg::Opt(b"w", _) => show_words = true,
+            Arg::Opt(b"m", _) => show_chars = true,
+            Arg::Opt(b"c", _) => show_bytes = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
-    if !show_lines && !show_words && !show_chars {
+    if !show_lines && !show_words && !show_chars && !show_bytes {
         show_lines = true;
         show_words = true;
-        show_chars = true;
-    }
-
-    let mut total_lines = 0u64;
-    let mut total_words = 0u64;
-    let mut total_chars = 0u64;
-    let mut file_count = 0;
-
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                let fd = io::open(path, libc::O_RDONLY, 0);
-                if fd >= 0 {
-                    let (l, w, c) = wc_fd(fd);
-                    total_lines += l;
-                    total_words += w;
-                    total_chars += c;
-
-                    if show_lines { io::write_num(1, l); io::write_str(1, b" "); }
-                    if show_words { io::write_num(1, w); io::write_str(1, b" "); }
-                    if show_chars { io::write_num(1, c); io::write_str(1, b" "); }
-                    io::write_all(1, path);
-                    io::write_str(1, b"\n");
-
-                    io::close(fd);
-                    file_count += 1;
-                }
-            }
+        show_bytes = true;
+    }
+
+    // Only -m needs characters decoded
+    let utf8 = show_chars && utf8::locale_is_utf8();
+    let mut total = WcCounts::default();
+    // The selected counts, then the name if there is one
+    let print = |counts: &WcCounts, name: Option<&[u8]>| {
+        let fields = [
+            (show_lines, counts.lines),
+            (show_words, counts.words),
+            (show_chars, counts.chars),
+            (show_bytes, counts.bytes),
+        ];
+        let mut first = true;
+        for (_, count) in fields.iter().filter(|(show, _)| *show) {
+            if !first { out.write_str(b" "); }
+            out.write_num(*count);
+            first = false;
+        }
+        if let Some(name) = name {
+            out.write_str(b" ");
+            out.write_all(name);
         }
+        out.write_str(b"\n");
+    };
+
+    let mut inputs = io::Inputs::new(opts.applet(), opts.operands());
+    while let Some(input) = inputs.next() {
+        let counts = match wc_fd(input.fd, utf8) {
+            Ok(counts) => counts,
+            Err(errno) => {
+                inputs.fail(input.path, errno);
+                continue;
+            }
+        };
+        total.lines += counts.lines;
+        total.words += counts.words;
+        total.chars += counts.chars;
+        total.bytes += counts.bytes;
+
+        // Standard input gets no name
+        print(&counts, (opts.operand_count() != 0).then_some(input.path));
     }
 
-    if file_count == 0 {
-        let (l, w, c) = wc_fd(0);
-        if show_lines { io::write_num(1, l); io::write_str(1, b" "); }
-        if show_words { io::write_num(1, w); io::write_str(1, b" "); }
-        if show_chars { io::write_num(1, c); }
-        io::write_str(1, b"\n");
-    } else if file_count > 1 {
-        if show_lines { io::write_num(1, total_lines); io::write_str(1, b" "); }
-        if show_words { io::write_num(1, total_words); io::write_str(1, b" "); }
-        if show_chars { io::write_num(1, total_chars); io::write_str(1, b" "); }
-        io::write_str(1, b"total\n");
+    if opts.operand_count() > 1 {
+        print(&total, Some(b"total"));
     }
-    0
+
+    if inputs.failed() { 1 } else { 0 }
+}
+
+/// What `wc` counts in a file
+#[derive(Default)]
+struct WcCounts {
+    lines: u64,
+    words: u64,
+    chars: u64,
+    bytes: u64,
 }
 
-fn wc_fd(fd: i32) -> (u64, u64, u64) {
-    let mut lines = 0u64;
-    let mut words = 0u64;
-    let mut chars = 0u64;
+/// Count lines, words, characters and bytes of `fd`, or the errno of a
+/// failed read. Characters are bytes unless `utf8`.
+fn wc_fd(fd: i32, utf8: bool) -> Result<WcCounts, i32> {
+    let mut counts = WcCounts::default();
     let mut in_word = false;
 
-    let mut buf = [0u8; 4096];
+    // Room in front for the start of a character split by the last read
+    let mut buf = [0u8; 4096 + 3];
+    let mut kept = 0;
     loop {
-        let n = io::read(fd, &mut buf);
-        if n <= 0 { break; }
-
-        for &c in &buf[..n as usize] {
-            chars += 1;
-            if c == b'\n' { lines += 1; }
+        let n = io::read(fd, &mut buf[kept..]);
+        if n < 0 { return Err(sys::errno()); }
+        let end = kept + n as usize;
+        // At end of file a truncated sequence is just invalid bytes
+        let complete = if n == 0 || !utf8 { end } else { utf8::complete_len(&buf[..end]) };
+        if n == 0 && end == 0 { break; }
+
+        counts.bytes += n as u64;
+        counts.chars += if utf8 { utf8::chars(&buf[..complete], true).count() as u64 } else { complete as u64 };
+        // No byte of a multibyte character is a space or newline, so lines
+        // and words can be counted a byte at a time
+        for &c in &buf[..complete] {
+            if c == b'\n' { counts.lines += 1; }
 
             let is_space = c == b' ' || c == b'\n' || c == b'\t' || c == b'\r';
             if is_space {
                 in_word = false;
             } else if !in_word {
                 in_word = true;
-                words += 1;
+                counts.words += 1;
             }
         }
+
+        buf.copy_within(complete..end, 0);
+        kept = end - complete;
+        if n == 0 { break; }
     }
 
-    (lines, words, chars)
+    Ok(counts)
 }
 
 /// tee - read from stdin and write to stdout and files
 pub fn tee(argc: i32, argv: *const *const u8) -> i32 {
     let mut append = false;
 
+    let mut opts = Getopt::new(argc, &argv, b"a(append)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"a", _) => append = true,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
+
     #[cfg(feature = "alloc")]
     {
         use alloc::vec::Vec;
@@ -328,21 +395,15 @@ pub fn tee(argc: i32, argv: *const *const u8) -> i32 {
         let mut fds: Vec<i32> = Vec::new();
         fds.push(1); // stdout
 
-        for i in 1..argc {
-            if let Some(arg) = unsafe { get_arg(argv, i) } {
-                if has_opt(arg, b'a') {
-                    append = true;
-                } else if arg[0] != b'-' {
-                    let flags = if append {
-                        libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND
-                    } else {
-                        libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
-                    };
-                    let fd = io::open(arg, flags, 0o644);
-                    if fd >= 0 {
-                        fds.push(fd);
-                    }
-                }
+        let flags = if append {
+            libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND
+        } else {
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
+        };
+        for path in opts.operands() {
+            let fd = io::open(path, flags, 0o644);
+            if fd >= 0 {
+                fds.push(fd);
             }
         }
 
@@ -376,37 +437,39 @@ pub fn tee(argc: i32, argv: *const *const u8) -> i32 {
 
 /// tac - concatenate files in reverse
 pub fn tac(argc: i32, argv: *const *const u8) -> i32 {
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
     #[cfg(feature = "alloc")]
     {
         use alloc::vec::Vec;
 
-        for i in 1..argc {
-            if let Some(path) = unsafe { get_arg(argv, i) } {
-                if path[0] != b'-' {
-                    let fd = io::open(path, libc::O_RDONLY, 0);
-                    if fd >= 0 {
-                        let content = io::read_all(fd);
-                        io::close(fd);
-
-                        let lines: Vec<&[u8]> = content.split(|&c| c == b'\n').collect();
-                        for line in lines.iter().rev() {
-                            if !line.is_empty() {
-                                io::write_all(1, line);
-                                io::write_str(1, b"\n");
-                            }
-                        }
+        let out = io::stdout();
+        for path in opts.operands() {
+            let fd = io::open(path, libc::O_RDONLY, 0);
+            if fd >= 0 {
+                let content = io::read_all(fd);
+                io::close(fd);
+
+                let lines: Vec<&[u8]> = content.split(|&c| c == b'\n').collect();
+                for line in lines.iter().rev() {
+                    if !line.is_empty() {
+                        out.write_all(line);
+                        out.write_str(b"\n");
                     }
                 }
             }
         }
 
-        if argc < 2 {
+        if opts.operand_count() == 0 {
             let content = io::read_all(0);
             let lines: Vec<&[u8]> = content.split(|&c| c == b'\n').collect();
             for line in lines.iter().rev() {
                 if !line.is_empty() {
-                    io::write_all(1, line);
-                    io::write_str(1, b"\n");
+                    out.write_all(line);
+                    out.write_str(b"\n");
                 }
             }
         }
@@ -415,42 +478,36 @@ pub fn tac(argc: i32, argv: *const *const u8) -> i32 {
 }
 
 /// rev - reverse lines character-wise
+///
+/// In a UTF-8 locale combining marks stay after the character they belong
+/// to.
 pub fn rev(argc: i32, argv: *const *const u8) -> i32 {
-    let fd = if argc > 1 {
-        if let Some(path) = unsafe { get_arg(argv, 1) } {
-            if path[0] != b'-' {
-                io::open(path, libc::O_RDONLY, 0)
-            } else { 0 }
-        } else { 0 }
-    } else { 0 };
-
-    let mut buf = [0u8; 4096];
-    let mut line = [0u8; 1024];
-    let mut line_len = 0;
-
-    loop {
-        let n = io::read(fd, &mut buf);
-        if n <= 0 { break; }
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let fd = match opts.operand(0) {
+        Some(path) if path != b"-" => io::open(path, libc::O_RDONLY, 0),
+        _ => 0,
+    };
 
-        for &c in &buf[..n as usize] {
-            if c == b'\n' {
-                // Reverse and print
-                for i in (0..line_len).rev() {
-                    io::write_all(1, &line[i..i+1]);
-                }
-                io::write_str(1, b"\n");
-                line_len = 0;
-            } else if line_len < line.len() {
-                line[line_len] = c;
-                line_len += 1;
+    let utf8 = utf8::locale_is_utf8();
+    let mut reader = LineReader::new(fd);
+    while let Some(line) = reader.next_line() {
+        let data = line.data;
+        let mut end = data.len();
+        while end > 0 {
+            let mut start = utf8::prev_char(data, end, utf8);
+            // Take the character a run of combining marks belongs to
+            while start > 0 && utf8 && utf8::decode(&data[start..]).0.is_some_and(utf8::is_zero_width) {
+                start = utf8::prev_char(data, start, utf8);
             }
+            out.write_all(&data[start..end]);
+            end = start;
         }
-    }
-
-    // Print remaining
-    if line_len > 0 {
-        for i in (0..line_len).rev() {
-            io::write_all(1, &line[i..i+1]);
+        if line.terminated {
+            out.write_str(b"\n");
         }
     }
 
@@ -460,50 +517,56 @@ pub fn rev(argc: i32, argv: *const *const u8) -> i32 {
 
 /// yes - output a string repeatedly
 pub fn yes(argc: i32, argv: *const *const u8) -> i32 {
-    let text = if argc > 1 {
-        unsafe { get_arg(argv, 1).unwrap_or(b"y") }
-    } else {
-        b"y"
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
     };
+    let text = opts.operand(0).unwrap_or(b"y");
 
     loop {
-        io::write_all(1, text);
-        io::write_str(1, b"\n");
+        out.write_all(text);
+        out.write_str(b"\n");
     }
 }
 
 /// seq - print sequence of numbers
 pub fn seq(argc: i32, argv: *const *const u8) -> i32 {
-    let (first, last, incr) = match argc {
-        2 => {
-            let last = sys::parse_i64(unsafe { get_arg(argv, 1).unwrap() }).unwrap_or(1);
-            (1i64, last, 1i64)
-        }
-        3 => {
-            let first = sys::parse_i64(unsafe { get_arg(argv, 1).unwrap() }).unwrap_or(1);
-            let last = sys::parse_i64(unsafe { get_arg(argv, 2).unwrap() }).unwrap_or(1);
-            (first, last, 1)
-        }
-        _ if argc >= 4 => {
-            let first = sys::parse_i64(unsafe { get_arg(argv, 1).unwrap() }).unwrap_or(1);
-            let incr = sys::parse_i64(unsafe { get_arg(argv, 2).unwrap() }).unwrap_or(1);
-            let last = sys::parse_i64(unsafe { get_arg(argv, 3).unwrap() }).unwrap_or(1);
-            (first, last, incr)
+    let out = io::stdout();
+    // Negative numbers are operands here, not options
+    let mut nums = [0i64; 3];
+    let mut count = 0;
+    for arg in Getopt::new(argc, &argv, b"#") {
+        match arg {
+            Arg::Operand(n) | Arg::Number(n) => {
+                if count < nums.len() {
+                    nums[count] = sys::parse_i64(n).unwrap_or(1);
+                }
+                count += 1;
+            }
+            Arg::Error => return 1,
+            _ => {}
         }
+    }
+
+    let (first, last, incr) = match count {
+        1 => (1i64, nums[0], 1i64),
+        2 => (nums[0], nums[1], 1),
+        _ if count >= 3 => (nums[0], nums[2], nums[1]),
         _ => (1, 10, 1),
     };
 
     let mut n = first;
     if incr > 0 {
         while n <= last {
-            io::write_signed(1, n);
-            io::write_str(1, b"\n");
+            out.write_signed(n);
+            out.write_str(b"\n");
             n += incr;
         }
     } else if incr < 0 {
         while n >= last {
-            io::write_signed(1, n);
-            io::write_str(1, b"\n");
+            out.write_signed(n);
+            out.write_str(b"\n");
             n += incr;
         }
     }
@@ -512,13 +575,15 @@ pub fn seq(argc: i32, argv: *const *const u8) -> i32 {
 
 /// nl - number lines
 pub fn nl(argc: i32, argv: *const *const u8) -> i32 {
-    let fd = if argc > 1 {
-        if let Some(path) = unsafe { get_arg(argv, argc - 1) } {
-            if path[0] != b'-' {
-                io::open(path, libc::O_RDONLY, 0)
-            } else { 0 }
-        } else { 0 }
-    } else { 0 };
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let fd = match opts.operands().last() {
+        Some(path) if path != b"-" => io::open(path, libc::O_RDONLY, 0),
+        _ => 0,
+    };
 
     let mut line_num = 1u64;
     let mut buf = [0u8; 4096];
@@ -530,11 +595,11 @@ pub fn nl(argc: i32, argv: *const *const u8) -> i32 {
 
         for &c in &buf[..n as usize] {
             if at_line_start {
-                io::write_num(1, line_num);
-                io::write_str(1, b"\t");
+                out.write_num(line_num);
+                out.write_str(b"\t");
                 at_line_start = false;
             }
-            io::write_all(1, &[c]);
+            out.write_byte(c);
             if c == b'\n' {
                 line_num += 1;
                 at_line_start = true;
@@ -548,33 +613,35 @@ pub fn nl(argc: i32, argv: *const *const u8) -> i32 {
 
 /// tr - translate characters
 pub fn tr(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let mut delete = false;
     let mut squeeze = false;
     let mut complement = false;
-    let mut set1_idx = 0;
-    let mut set2_idx = 0;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'd') { delete = true; }
-                if has_opt(arg, b's') { squeeze = true; }
-                if has_opt(arg, b'c') || has_opt(arg, b'C') { complement = true; }
-            } else if set1_idx == 0 {
-                set1_idx = i;
-            } else if set2_idx == 0 {
-                set2_idx = i;
-            }
+    let mut opts = Getopt::new(argc, &argv, b"d(delete)s(squeeze-repeats)c(complement)C");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"d", _) => delete = true,
+            Arg::Opt(b"s", _) => squeeze = true,
+            Arg::Opt(b"c", _) | Arg::Opt(b"C", _) => complement = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
-    if set1_idx == 0 {
-        io::write_str(2, b"tr: missing operand\n");
-        return 1;
-    }
+    let set1 = match opts.operand(0) {
+        Some(s) => s,
+        None => {
+            return usage::error(b"tr", b"missing operand");
+        }
+    };
+    let set2 = opts.operand(1);
 
-    let set1 = unsafe { get_arg(argv, set1_idx).unwrap() };
-    let set2 = if set2_idx > 0 { unsafe { get_arg(argv, set2_idx) } } else { None };
+    // Multibyte characters in a set, or a complement that would otherwise
+    // take the bytes of one apart, need the character-wise path
+    if utf8::locale_is_utf8() && (complement || !set1.is_ascii() || !set2.is_none_or(<[u8]>::is_ascii)) {
+        return tr_chars(expand_set(set1), set2.map(expand_set), delete, squeeze, complement);
+    }
 
     let mut map = [0u8; 256];
     for i in 0..256 { map[i] = i as u8; }
@@ -600,11 +667,11 @@ pub fn tr(argc: i32, argv: *const *const u8) -> i32 {
                 if !in_set {
                     if squeeze {
                         if Some(c) != last_char {
-                            io::write_all(1, &[c]);
+                            out.write_byte(c);
                             last_char = Some(c);
                         }
                     } else {
-                        io::write_all(1, &[c]);
+                        out.write_byte(c);
                     }
                 }
             }
@@ -641,15 +708,15 @@ pub fn tr(argc: i32, argv: *const *const u8) -> i32 {
             if n <= 0 { break; }
 
             for &c in &buf[..n as usize] {
-                let out = map[c as usize];
-                if squeeze && set2_expanded.contains(&out) {
-                    if Some(out) != last_char {
-                        io::write_all(1, &[out]);
-                        last_char = Some(out);
+                let mapped = map[c as usize];
+                if squeeze && set2_expanded.contains(&mapped) {
+                    if Some(mapped) != last_char {
+                        out.write_byte(mapped);
+                        last_char = Some(mapped);
                     }
                 } else {
-                    io::write_all(1, &[out]);
-                    last_char = Some(out);
+                    out.write_byte(mapped);
+                    last_char = Some(mapped);
                 }
             }
         }
@@ -657,79 +724,229 @@ pub fn tr(argc: i32, argv: *const *const u8) -> i32 {
     0
 }
 
+/// tr on UTF-8 characters rather than bytes; invalid bytes are characters
+/// of their own
+fn tr_chars(set1: &[u8], set2: Option<&[u8]>, delete: bool, squeeze: bool, complement: bool) -> i32 {
+    let out = io::stdout();
+    let position = |set: &[u8], c: &[u8]| utf8::chars(set, true).position(|s| s == c);
+    // What `c` becomes, or None to delete it
+    let translate = |c: &[u8]| -> Option<Option<&[u8]>> {
+        let found = position(set1, c);
+        if found.is_some() == complement {
+            return Some(None);
+        }
+        match set2 {
+            _ if delete => None,
+            // The last character of SET2 stands in for the rest of SET1
+            Some(set2) if !set2.is_empty() => {
+                let last = utf8::chars(set2, true).count() - 1;
+                let i = found.unwrap_or(last).min(last);
+                Some(utf8::chars(set2, true).nth(i))
+            }
+            _ => Some(None),
+        }
+    };
+    // Runs are squeezed in SET2, or in SET1 without one
+    let squeezable = |c: &[u8]| {
+        squeeze
+            && match set2 {
+                Some(set2) => position(set2, c).is_some(),
+                None => position(set1, c).is_some() != complement,
+            }
+    };
+
+    let mut last = [0u8; 4];
+    let mut last_len = 0;
+    let mut buf = [0u8; 4096 + 3];
+    let mut kept = 0;
+    loop {
+        let n = io::read(0, &mut buf[kept..]);
+        if n < 0 { return 1; }
+        let end = kept + n as usize;
+        let complete = if n == 0 { end } else { utf8::complete_len(&buf[..end]) };
+
+        for c in utf8::chars(&buf[..complete], true) {
+            let c = match translate(c) {
+                Some(None) => c,
+                Some(Some(mapped)) => mapped,
+                None => continue,
+            };
+            if squeezable(c) && &last[..last_len] == c {
+                continue;
+            }
+            out.write_all(c);
+            last[..c.len()].copy_from_slice(c);
+            last_len = c.len();
+        }
+
+        buf.copy_within(complete..end, 0);
+        kept = end - complete;
+        if n == 0 { break; }
+    }
+    0
+}
+
 fn expand_set(s: &[u8]) -> &[u8] {
     // Simplified - just return as-is
     // Full impl would handle [:alpha:], a-z, etc.
     s
 }
 
+/// A `cut` LIST: positions `N` and ranges `N-M`, `N-` and `-M`, counted
+/// from 1 and separated by commas or blanks
+#[derive(Clone, Copy)]
+struct CutList<'a>(&'a [u8]);
+
+impl<'a> CutList<'a> {
+    /// Check `list`, or say what is wrong with it
+    fn parse(list: &'a [u8]) -> Result<Self, &'static [u8]> {
+        for item in Self::items(list) {
+            Self::range(item)?;
+        }
+        Ok(CutList(list))
+    }
+
+    fn items(list: &[u8]) -> impl Iterator<Item = &[u8]> {
+        list.split(|&c| c == b',' || c == b' ' || c == b'\t')
+    }
+
+    /// The first and last position of one item, inclusive
+    fn range(item: &[u8]) -> Result<(usize, usize), &'static [u8]> {
+        let (first, last) = match item.iter().position(|&c| c == b'-') {
+            Some(dash) => (&item[..dash], &item[dash + 1..]),
+            None => (item, item),
+        };
+        match item {
+            b"" => return Err(b"positions are numbered from 1"),
+            b"-" => return Err(b"invalid range with no endpoint: -"),
+            _ => {}
+        }
+        let number = |s: &[u8], default| match sys::parse_u64(s) {
+            _ if s.is_empty() => Ok(default),
+            Some(0) => Err(b"positions are numbered from 1".as_slice()),
+            Some(n) => Ok(usize::try_from(n).unwrap_or(usize::MAX)),
+            None => Err(b"invalid list".as_slice()),
+        };
+        let (first, last) = (number(first, 1)?, number(last, usize::MAX)?);
+        if last < first {
+            return Err(b"invalid decreasing range");
+        }
+        Ok((first, last))
+    }
+
+    /// Whether position `n` is selected
+    fn contains(self, n: usize) -> bool {
+        Self::items(self.0).any(|item| matches!(Self::range(item), Ok((first, last)) if (first..=last).contains(&n)))
+    }
+
+    /// The last position selected, past which the rest of a line can be
+    /// skipped
+    fn end(self) -> usize {
+        Self::items(self.0).filter_map(|item| Self::range(item).ok()).map(|(_, last)| last).max().unwrap_or(0)
+    }
+}
+
+/// What `cut` selects from each line
+#[derive(Clone, Copy)]
+enum CutBy<'a> {
+    Bytes(CutList<'a>),
+    Chars(CutList<'a>),
+    Fields(CutList<'a>),
+}
+
 /// cut - remove sections from lines
 pub fn cut(argc: i32, argv: *const *const u8) -> i32 {
-    let mut delimiter = b'\t';
-    let mut field: Option<usize> = None;
-    let mut chars: Option<usize> = None;
+    let out = io::stdout();
+    let mut delimiter = None;
+    let mut lists = 0;
+    let mut list = None;
+    let mut only_delimited = false;
+    let mut line_delim = b'\n';
+
+    let spec = b"b(bytes):c(characters):d(delimiter):f(fields):ns(only-delimited)z(zero-terminated)";
+    let mut opts = Getopt::new(argc, &argv, spec);
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(kind @ (b"b" | b"c" | b"f"), Some(value)) => {
+                let parsed = match CutList::parse(value) {
+                    Ok(parsed) => parsed,
+                    Err(msg) => return usage::error(b"cut", msg),
+                };
+                lists += 1;
+                list = Some(match kind {
+                    b"b" => CutBy::Bytes(parsed),
+                    b"c" => CutBy::Chars(parsed),
+                    _ => CutBy::Fields(parsed),
+                });
+            }
+            Arg::Opt(b"d", Some(d)) => match d {
+                [d] => delimiter = Some(*d),
+                _ => return usage::error(b"cut", b"the delimiter must be a single character"),
+            },
+            // -n: don't split multibyte characters with -b; they never are
+            Arg::Opt(b"n", _) => {}
+            Arg::Opt(b"s", _) => only_delimited = true,
+            Arg::Opt(b"z", _) => line_delim = 0,
+            Arg::Error => return 1,
+            _ => {}
+        }
+    }
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'd') && i + 1 < argc {
-                if let Some(d) = unsafe { get_arg(argv, i + 1) } {
-                    if !d.is_empty() { delimiter = d[0]; }
-                }
-            } else if has_opt(arg, b'f') && i + 1 < argc {
-                if let Some(f) = unsafe { get_arg(argv, i + 1) } {
-                    field = Some(sys::parse_u64(f).unwrap_or(1) as usize);
-                }
-            } else if has_opt(arg, b'c') && i + 1 < argc {
-                if let Some(c) = unsafe { get_arg(argv, i + 1) } {
-                    chars = Some(sys::parse_u64(c).unwrap_or(1) as usize);
+    let list = match list {
+        _ if lists > 1 => return usage::error(b"cut", b"only one list may be specified"),
+        Some(list) => list,
+        None => return usage::error(b"cut", b"you must specify a list of bytes, characters, or fields"),
+    };
+    let fields = matches!(list, CutBy::Fields(_));
+    if !fields && (delimiter.is_some() || only_delimited) {
+        return usage::error(b"cut", b"-d and -s only apply to fields");
+    }
+    let delimiter = delimiter.unwrap_or(b'\t');
+
+    let utf8 = utf8::locale_is_utf8();
+    let inputs = io::Inputs::new(opts.applet(), opts.operands());
+    let mut lines = inputs.lines(line_delim);
+    while let Some(line) = lines.next_line() {
+        let line = line.data;
+        match list {
+            CutBy::Bytes(positions) => cut_pieces(out, line.chunks(1), positions, None),
+            CutBy::Chars(positions) => cut_pieces(out, utf8::chars(line, utf8), positions, None),
+            CutBy::Fields(_) if !line.contains(&delimiter) => {
+                if only_delimited {
+                    continue;
                 }
+                out.write_all(line);
+            }
+            CutBy::Fields(positions) => {
+                let pieces = line.split(move |&c| c == delimiter);
+                cut_pieces(out, pieces, positions, Some(delimiter));
             }
         }
+        out.write_byte(line_delim);
     }
 
-    let mut buf = [0u8; 4096];
-    let mut line = [0u8; 4096];
-    let mut line_len = 0;
-
-    loop {
-        let n = io::read(0, &mut buf);
-        if n <= 0 { break; }
+    if lines.failed() { 1 } else { 0 }
+}
 
-        for &c in &buf[..n as usize] {
-            if c == b'\n' {
-                if let Some(f) = field {
-                    let mut field_num = 1;
-                    let mut start = 0;
-
-                    for j in 0..line_len {
-                        if line[j] == delimiter {
-                            if field_num == f {
-                                io::write_all(1, &line[start..j]);
-                                break;
-                            }
-                            field_num += 1;
-                            start = j + 1;
-                        }
-                    }
-                    if field_num == f {
-                        io::write_all(1, &line[start..line_len]);
-                    }
-                } else if let Some(c) = chars {
-                    if c <= line_len {
-                        io::write_all(1, &line[..c]);
-                    } else {
-                        io::write_all(1, &line[..line_len]);
-                    }
-                }
-                io::write_str(1, b"\n");
-                line_len = 0;
-            } else if line_len < line.len() {
-                line[line_len] = c;
-                line_len += 1;
-            }
+/// Write the pieces of a line at `positions`, with `separator` between them
+fn cut_pieces<'a>(
+    out: io::Stdout,
+    pieces: impl Iterator<Item = &'a [u8]>,
+    positions: CutList,
+    separator: Option<u8>,
+) {
+    let end = positions.end();
+    let mut first = true;
+    for (piece, n) in pieces.zip(1..=end) {
+        if !positions.contains(n) {
+            continue;
+        }
+        if let (Some(separator), false) = (separator, first) {
+            out.write_byte(separator);
         }
+        out.write_all(piece);
+        first = false;
     }
-    0
 }
 
 /// paste - merge lines of files
@@ -746,19 +963,44 @@ pub fn sort(argc: i32, argv: *const *const u8) -> i32 {
     {
         use alloc::vec::Vec;
 
+        let out = io::stdout();
         let mut reverse = false;
         let mut numeric = false;
         let mut unique = false;
-
-        for i in 1..argc {
-            if let Some(arg) = unsafe { get_arg(argv, i) } {
-                if has_opt(arg, b'r') { reverse = true; }
-                if has_opt(arg, b'n') { numeric = true; }
-                if has_opt(arg, b'u') { unique = true; }
+        let mut output = None;
+
+        let mut opts = Getopt::new(argc, &argv, b"r(reverse)n(numeric-sort)u(unique)o(output):");
+        for arg in &mut opts {
+            match arg {
+                Arg::Opt(b"r", _) => reverse = true,
+                Arg::Opt(b"n", _) => numeric = true,
+                Arg::Opt(b"u", _) => unique = true,
+                Arg::Opt(b"o", path) => output = path,
+                Arg::Error => return 1,
+                _ => {}
             }
         }
 
-        let content = io::read_all(0);
+        // The output may also be an input, so it is only replaced once
+        // everything has been read
+        let mut replacement = match output {
+            Some(path) => match sys::Replacement::new(path) {
+                Ok(replacement) => Some(replacement),
+                Err(e) => {
+                    sys::error_errno(b"sort", path, e);
+                    return EXIT_TROUBLE;
+                }
+            },
+            None => None,
+        };
+
+        // Sort the lines of all inputs together
+        let mut content = Vec::new();
+        let mut input = io::Inputs::new(opts.applet(), opts.operands()).lines(b'\n');
+        while let Some(line) = input.next_line() {
+            content.extend_from_slice(line.data);
+            content.push(b'\n');
+        }
         let mut lines: Vec<&[u8]> = content.split(|&c| c == b'\n').filter(|l| !l.is_empty()).collect();
 
         if numeric {
@@ -775,14 +1017,41 @@ pub fn sort(argc: i32, argv: *const *const u8) -> i32 {
             lines.reverse();
         }
 
+        let mut file = match replacement.as_mut().map(sys::Replacement::fd) {
+            Some(Ok(fd)) => Some(io::BufWriter::new(fd)),
+            Some(Err(e)) => {
+                sys::error_errno(b"sort", output.unwrap_or_default(), e);
+                return EXIT_TROUBLE;
+            }
+            None => None,
+        };
         let mut last: Option<&[u8]> = None;
         for line in lines {
             if unique {
                 if Some(line) == last { continue; }
                 last = Some(line);
             }
-            io::write_all(1, line);
-            io::write_str(1, b"\n");
+            match &mut file {
+                Some(file) => {
+                    file.write_all(line);
+                    file.write_byte(b'\n');
+                }
+                None => {
+                    out.write_all(line);
+                    out.write_str(b"\n");
+                }
+            }
+        }
+
+        if input.failed() {
+            return EXIT_TROUBLE;
+        }
+        if let (Some(mut file), Some(replacement), Some(path)) = (file, replacement, output) {
+            let result = if file.flush() < 0 { Err(sys::errno()) } else { replacement.commit() };
+            if let Err(e) = result {
+                sys::error_errno(b"sort", path, e);
+                return EXIT_TROUBLE;
+            }
         }
     }
     0
@@ -794,11 +1063,14 @@ pub fn uniq(argc: i32, argv: *const *const u8) -> i32 {
     let mut repeated = false;
     let mut unique_only = false;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'c') { count = true; }
-            if has_opt(arg, b'd') { repeated = true; }
-            if has_opt(arg, b'u') { unique_only = true; }
+    let mut opts = Getopt::new(argc, &argv, b"c(count)d(repeated)u(unique)");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"c", _) => count = true,
+            Arg::Opt(b"d", _) => repeated = true,
+            Arg::Opt(b"u", _) => unique_only = true,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
@@ -806,7 +1078,13 @@ pub fn uniq(argc: i32, argv: *const *const u8) -> i32 {
     {
         use alloc::vec::Vec;
 
-        let content = io::read_all(0);
+        let out = io::stdout();
+        let mut content = Vec::new();
+        let mut input = io::Inputs::new(opts.applet(), opts.operands().take(1)).lines(b'\n');
+        while let Some(line) = input.next_line() {
+            content.extend_from_slice(line.data);
+            content.push(b'\n');
+        }
         let lines: Vec<&[u8]> = content.split(|&c| c == b'\n').collect();
 
         let mut i = 0;
@@ -828,103 +1106,215 @@ pub fn uniq(argc: i32, argv: *const *const u8) -> i32 {
 
             if should_print && !line.is_empty() {
                 if count {
-                    io::write_num(1, cnt as u64);
-                    io::write_str(1, b" ");
+                    out.write_num(cnt as u64);
+                    out.write_str(b" ");
                 }
-                io::write_all(1, line);
-                io::write_str(1, b"\n");
+                out.write_all(line);
+                out.write_str(b"\n");
             }
 
             i += cnt;
         }
+
+        if input.failed() {
+            return 1;
+        }
     }
     0
 }
 
+/// How `grep` reads its pattern
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Basic,
+    Extended,
+    Fixed,
+}
+
 /// grep - search for patterns
 pub fn grep(argc: i32, argv: *const *const u8) -> i32 {
+    search(argc, argv, Syntax::Basic)
+}
+
+fn search(argc: i32, argv: *const *const u8, mut syntax: Syntax) -> i32 {
+    let out = io::stdout();
     let mut invert = false;
     let mut count_only = false;
     let mut line_numbers = false;
     let mut ignore_case = false;
-    let mut pattern_idx = 0;
-
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if arg[0] == b'-' {
-                if has_opt(arg, b'v') { invert = true; }
-                if has_opt(arg, b'c') { count_only = true; }
-                if has_opt(arg, b'n') { line_numbers = true; }
-                if has_opt(arg, b'i') { ignore_case = true; }
-            } else if pattern_idx == 0 {
-                pattern_idx = i;
+    let mut with_filename: Option<bool> = None;
+    let mut patterns = Patterns::default();
+    let mut delim = b'\n';
+
+    let spec = b"v(invert-match)c(count)n(line-number)i(ignore-case)e(regexp):z(null-data)\
+                 H(with-filename)h(no-filename)E(extended-regexp)F(fixed-strings)G(basic-regexp)";
+    let mut opts = Getopt::new(argc, &argv, spec);
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"v", _) => invert = true,
+            Arg::Opt(b"c", _) => count_only = true,
+            Arg::Opt(b"n", _) => line_numbers = true,
+            Arg::Opt(b"i", _) => ignore_case = true,
+            Arg::Opt(b"e", Some(p)) if !patterns.add(p) => {
+                usage::error(b"grep", b"more than one pattern needs alloc");
+                return EXIT_TROUBLE;
             }
+            Arg::Opt(b"z", _) => delim = 0,
+            Arg::Opt(b"H", _) => with_filename = Some(true),
+            Arg::Opt(b"h", _) => with_filename = Some(false),
+            Arg::Opt(b"E", _) => syntax = Syntax::Extended,
+            Arg::Opt(b"F", _) => syntax = Syntax::Fixed,
+            Arg::Opt(b"G", _) => syntax = Syntax::Basic,
+            Arg::Error => return EXIT_TROUBLE,
+            _ => {}
         }
     }
 
-    if pattern_idx == 0 {
-        io::write_str(2, b"grep: missing pattern\n");
-        return 2;
+    // Without -e the first operand is the pattern
+    let mut first_file = 0;
+    if patterns.is_empty() {
+        match opts.operand(0) {
+            Some(p) if patterns.add(p) => first_file = 1,
+            Some(_) => {
+                usage::error(b"grep", b"more than one pattern needs alloc");
+                return EXIT_TROUBLE;
+            }
+            None => {
+                usage::error(b"grep", b"missing pattern");
+                return EXIT_TROUBLE;
+            }
+        }
     }
+    let with_filename = with_filename.unwrap_or(opts.operand_count() > first_file + 1);
 
-    let pattern = unsafe { get_arg(argv, pattern_idx).unwrap() };
+    // No regex for -F: the pattern is searched for as is
+    let regexes = if syntax == Syntax::Fixed {
+        None
+    } else {
+        let mut flags = if ignore_case { regex::ICASE } else { 0 };
+        if syntax == Syntax::Extended {
+            flags |= regex::EXTENDED;
+        }
+        match patterns.compile(flags) {
+            Ok(regexes) => Some(regexes),
+            Err(msg) => {
+                sys::error(b"grep", msg);
+                return EXIT_TROUBLE;
+            }
+        }
+    };
+    let is_match = |line: &[u8]| match &regexes {
+        Some(regexes) => regexes.iter().any(|re| re.is_match(line)),
+        None if ignore_case => patterns.iter().any(|p| contains_ignore_case(line, p)),
+        None => patterns.iter().any(|p| contains(line, p)),
+    };
 
-    let mut count = 0u64;
-    let mut line_num = 0u64;
-    let mut buf = [0u8; 4096];
-    let mut line = [0u8; 4096];
-    let mut line_len = 0;
+    let mut total = 0u64;
+    let mut inputs = io::Inputs::new(opts.applet(), opts.operands().skip(first_file));
+    let mut reader = LineReader::with_delimiter(0, delim);
+
+    while let Some(input) = inputs.next() {
+        let name: &[u8] = if input.path == b"-" { b"(standard input)" } else { input.path };
+        let mut count = 0u64;
+        let mut line_num = 0u64;
+        reader.reset(input.fd);
+
+        while let Some(line) = reader.next_line() {
+            line_num += 1;
+            if is_match(line.data) != invert {
+                count += 1;
+                if !count_only {
+                    if with_filename {
+                        out.write_all(name);
+                        out.write_str(b":");
+                    }
+                    if line_numbers {
+                        out.write_num(line_num);
+                        out.write_str(b":");
+                    }
+                    out.write_all(line.data);
+                    out.write_byte(delim);
+                }
+            }
+        }
 
-    loop {
-        let n = io::read(0, &mut buf);
-        if n <= 0 { break; }
+        if reader.error() {
+            inputs.fail(input.path, reader.errno());
+        }
+        if count_only {
+            if with_filename {
+                out.write_all(name);
+                out.write_str(b":");
+            }
+            out.write_num(count);
+            out.write_str(b"\n");
+        }
+        total += count;
+    }
 
-        for &c in &buf[..n as usize] {
-            if c == b'\n' {
-                line_num += 1;
-                let matches = if ignore_case {
-                    contains_ignore_case(&line[..line_len], pattern)
-                } else {
-                    contains(&line[..line_len], pattern)
-                };
+    if inputs.failed() {
+        EXIT_TROUBLE
+    } else if total > 0 {
+        0
+    } else {
+        1
+    }
+}
 
-                if matches != invert {
-                    count += 1;
-                    if !count_only {
-                        if line_numbers {
-                            io::write_num(1, line_num);
-                            io::write_str(1, b":");
-                        }
-                        io::write_all(1, &line[..line_len]);
-                        io::write_str(1, b"\n");
-                    }
-                }
-                line_len = 0;
-            } else if line_len < line.len() {
-                line[line_len] = c;
-                line_len += 1;
+/// What grep searches for: every `-e` pattern, or else the first operand,
+/// split at newlines. A line matches if any of them does. Without `alloc`
+/// there is room for only one.
+#[derive(Default)]
+struct Patterns<'a> {
+    #[cfg(feature = "alloc")]
+    list: alloc::vec::Vec<&'a [u8]>,
+    #[cfg(not(feature = "alloc"))]
+    list: Option<&'a [u8]>,
+}
+
+impl<'a> Patterns<'a> {
+    /// Add each line of `arg`; false if there's no room for them
+    fn add(&mut self, arg: &'a [u8]) -> bool {
+        for pattern in arg.split(|&c| c == b'\n') {
+            #[cfg(feature = "alloc")]
+            self.list.push(pattern);
+            #[cfg(not(feature = "alloc"))]
+            if self.list.replace(pattern).is_some() {
+                return false;
             }
         }
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
     }
 
-    if count_only {
-        io::write_num(1, count);
-        io::write_str(1, b"\n");
+    fn iter(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        self.list.iter().copied()
     }
 
-    if count > 0 { 0 } else { 1 }
+    #[cfg(feature = "alloc")]
+    fn compile(&self, flags: u32) -> Result<alloc::vec::Vec<Regex>, &'static [u8]> {
+        self.iter().map(|p| Regex::new(p, flags)).collect()
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn compile(&self, flags: u32) -> Result<Option<Regex>, &'static [u8]> {
+        self.list.map(|p| Regex::new(p, flags)).transpose()
+    }
 }
 
 fn contains(haystack: &[u8], needle: &[u8]) -> bool {
-    if needle.is_empty() { return true; }
-    if haystack.len() < needle.len() { return false; }
+    find(haystack, needle).is_some()
+}
 
-    for i in 0..=(haystack.len() - needle.len()) {
-        if &haystack[i..i+needle.len()] == needle {
-            return true;
-        }
-    }
-    false
+/// Position of the first occurrence of `needle` in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() { return Some(0); }
+    if haystack.len() < needle.len() { return None; }
+
+    (0..=(haystack.len() - needle.len())).find(|&i| &haystack[i..i+needle.len()] == needle)
 }
 
 fn contains_ignore_case(haystack: &[u8], needle: &[u8]) -> bool {
@@ -954,197 +1344,421 @@ fn contains_ignore_case(haystack: &[u8], needle: &[u8]) -> bool {
     false
 }
 
-/// egrep - extended grep
+/// egrep - grep -E
 pub fn egrep(argc: i32, argv: *const *const u8) -> i32 {
-    grep(argc, argv)
+    search(argc, argv, Syntax::Extended)
 }
 
-/// fgrep - fixed string grep
+/// fgrep - grep -F
 pub fn fgrep(argc: i32, argv: *const *const u8) -> i32 {
-    grep(argc, argv)
+    search(argc, argv, Syntax::Fixed)
 }
 
 /// sed - stream editor
 pub fn sed(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 {
-        io::write_str(2, b"sed: missing script\n");
-        return 1;
+    let mut script: Option<&[u8]> = None;
+    let mut line_delim = b'\n';
+    let mut flags = 0;
+    let mut in_place: Option<&[u8]> = None;
+
+    let mut opts = Getopt::new(argc, &argv, b"e(expression):n(quiet)z(null-data)E(regexp-extended)ri(in-place)::");
+    for arg in &mut opts {
+        match arg {
+            Arg::Opt(b"e", _) if script.is_some() => {
+                return usage::error(b"sed", b"only one -e script is supported");
+            }
+            Arg::Opt(b"e", s) => script = s,
+            Arg::Opt(b"n", _) => return usage::error(b"sed", b"-n is not supported"),
+            Arg::Opt(b"z", _) => line_delim = 0,
+            Arg::Opt(b"E" | b"r", _) => flags |= regex::EXTENDED,
+            Arg::Opt(b"i", suffix) => in_place = Some(suffix.unwrap_or(b"")),
+            Arg::Error => return 1,
+            _ => {}
+        }
     }
 
-    // Find script
-    let mut script: Option<&[u8]> = None;
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'e') && i + 1 < argc {
-                script = unsafe { get_arg(argv, i + 1) };
-                break;
-            } else if arg[0] != b'-' {
-                script = Some(arg);
-                break;
+    // Without -e the first operand is the script
+    let (script, first_file) = match script {
+        Some(s) => (s, 0),
+        None => match opts.operand(0) {
+            Some(s) => (s, 1),
+            None => {
+                return usage::error(b"sed", b"missing script");
+            }
+        },
+    };
+
+    // One s/pattern/replacement/flags; an empty script copies the input
+    let mut pattern_buf = [0u8; 1024];
+    let substitute = match script.trim_ascii_start() {
+        b"" => None,
+        command @ [b's', ..] => match Substitute::parse(command, flags, &mut pattern_buf) {
+            Ok(s) => Some(s),
+            Err(msg) => {
+                sys::error(b"sed", msg);
+                return 1;
             }
+        },
+        _ => {
+            sys::error_at(b"sed", script, b"unsupported command");
+            return 1;
+        }
+    };
+
+    let files = opts.operands().skip(first_file);
+    let suffix = match in_place {
+        Some(suffix) => suffix,
+        None => {
+            let mut lines = io::Inputs::new(opts.applet(), files).lines(line_delim);
+            io::stdout().with(|out| sed_lines(&mut lines, substitute.as_ref(), line_delim, out));
+            return if lines.failed() { 2 } else { 0 };
         }
+    };
+
+    if opts.operand(first_file).is_none() {
+        return usage::error(b"sed", b"no input files");
     }
+    let mut status = 0;
+    for path in files {
+        status = status.max(sed_in_place(path, suffix, substitute.as_ref(), line_delim));
+    }
+    status
+}
 
-    let script = match script {
-        Some(s) => s,
-        None => return 1,
+fn sed_lines<'a, I: Iterator<Item = &'a [u8]>>(
+    lines: &mut io::InputLines<'a, I>,
+    substitute: Option<&Substitute>,
+    line_delim: u8,
+    out: &mut io::BufWriter,
+) {
+    while let Some(line) = lines.next_line() {
+        match substitute {
+            Some(s) => s.apply(line.data, out),
+            None => out.write_all(line.data),
+        }
+        if line.terminated {
+            out.write_byte(line_delim);
+        }
+    }
+}
+
+/// Edits `path` through a temporary file, keeping the old one as `path`
+/// with `suffix` added if there is one. Returns 2 if the file can't be
+/// read and 4 if the new one can't be written.
+fn sed_in_place(path: &[u8], suffix: &[u8], substitute: Option<&Substitute>, line_delim: u8) -> i32 {
+    // A symlink would be replaced by a file, so it is refused like any
+    // other file that isn't regular
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    if io::lstat(path, &mut st) < 0 {
+        sys::perror(b"sed", path);
+        return 2;
+    }
+    if st.st_mode & libc::S_IFMT != libc::S_IFREG {
+        sys::error_at(b"sed", path, b"not a regular file");
+        return 4;
+    }
+    let mut replacement = match sys::Replacement::new(path) {
+        Ok(replacement) => replacement,
+        Err(e) => {
+            sys::error_errno(b"sed", path, e);
+            return 4;
+        }
     };
 
-    // Parse s/pattern/replacement/flags
-    if script.len() > 2 && script[0] == b's' {
-        let delim = script[1];
-        let mut parts = [0usize; 4];
-        let mut part = 0;
-        parts[0] = 2;
+    let mut out = match replacement.fd() {
+        Ok(fd) => io::BufWriter::new(fd),
+        Err(e) => {
+            sys::error_errno(b"sed", path, e);
+            return 4;
+        }
+    };
+    let mut lines = io::Inputs::new(b"sed", core::iter::once(path)).lines(line_delim);
+    sed_lines(&mut lines, substitute, line_delim, &mut out);
+    if lines.failed() {
+        return 2;
+    }
+    if out.flush() < 0 {
+        sys::perror(b"sed", path);
+        return 4;
+    }
 
-        for i in 2..script.len() {
-            if script[i] == delim && part < 3 {
-                part += 1;
-                parts[part] = i + 1;
+    if !suffix.is_empty() {
+        let mut backup = [0u8; 4096];
+        let len = path.len() + suffix.len();
+        if len >= backup.len() {
+            sys::error_errno(b"sed", path, libc::ENAMETOOLONG);
+            return 4;
+        }
+        backup[..path.len()].copy_from_slice(path);
+        backup[path.len()..len].copy_from_slice(suffix);
+        if io::rename(path, &backup[..len]) < 0 {
+            sys::perror(b"sed", &backup[..len]);
+            return 4;
+        }
+    }
+    if let Err(e) = replacement.commit() {
+        sys::error_errno(b"sed", path, e);
+        return 4;
+    }
+    0
+}
+
+/// A parsed `s` command
+struct Substitute<'a> {
+    regex: Regex,
+    /// Still escaped: `&`, `\1`..`\9` and `\n` are expanded per match
+    replacement: &'a [u8],
+    delim: u8,
+    /// Replace the match with this number, and with `global` every later one
+    occurrence: usize,
+    global: bool,
+}
+
+impl<'a> Substitute<'a> {
+    /// Parse `s/pattern/replacement/flags`, unescaping the delimiter in
+    /// the pattern into `buf`
+    fn parse(script: &'a [u8], mut flags: u32, buf: &mut [u8]) -> Result<Self, &'static [u8]> {
+        const UNTERMINATED: &[u8] = b"unterminated `s' command";
+        let delim = *script.get(1).ok_or(UNTERMINATED)?;
+        if delim == b'\\' || delim == b'\n' {
+            return Err(b"delimiter can't be a backslash or newline");
+        }
+
+        // Pattern: copy into buf, turning \<delim> into <delim>
+        let mut len = 0;
+        let mut i = 2;
+        loop {
+            let c = *script.get(i).ok_or(UNTERMINATED)?;
+            if c == delim {
+                break;
+            }
+            let escaped_delim = c == b'\\' && script.get(i + 1) == Some(&delim);
+            if c == b'\\' && !escaped_delim {
+                // Keep other escapes whole, so \\/ isn't taken for \/
+                *buf.get_mut(len).ok_or(b"pattern too long".as_slice())? = c;
+                len += 1;
+                i += 1;
+            } else if escaped_delim {
+                i += 1;
             }
+            let c = *script.get(i).ok_or(UNTERMINATED)?;
+            *buf.get_mut(len).ok_or(b"pattern too long".as_slice())? = c;
+            len += 1;
+            i += 1;
         }
 
-        if part >= 2 {
-            let pattern = &script[parts[0]..parts[1]-1];
-            let replacement = &script[parts[1]..parts[2]-1];
-            let global = part >= 3 && script[parts[2]..].contains(&b'g');
+        // Replacement: find its end, leaving escapes for apply()
+        let rstart = i + 1;
+        i = rstart;
+        loop {
+            match script.get(i) {
+                None => return Err(UNTERMINATED),
+                Some(&b'\\') => i += 2,
+                Some(&c) if c == delim => break,
+                Some(_) => i += 1,
+            }
+        }
+        let replacement = &script[rstart..i];
+
+        // Nothing may follow but blanks and a `;`
+        let flag_chars = &script[i + 1..];
+        let end = flag_chars.iter().position(|c| c.is_ascii_whitespace() || *c == b';').unwrap_or(flag_chars.len());
+        let (flag_chars, rest) = flag_chars.split_at(end);
+        if rest.iter().any(|&c| !c.is_ascii_whitespace() && c != b';') {
+            return Err(b"unsupported command after `s'");
+        }
 
-            let mut buf = [0u8; 4096];
-            let mut line = [0u8; 4096];
-            let mut line_len = 0;
+        let mut global = false;
+        let mut occurrence = 0;
+        for &c in flag_chars {
+            match c {
+                b'g' => global = true,
+                b'i' | b'I' => flags |= regex::ICASE,
+                b'0'..=b'9' => occurrence = occurrence * 10 + (c - b'0') as usize,
+                _ => return Err(b"unknown option to `s'"),
+            }
+        }
+        if occurrence == 0 && flag_chars.iter().any(u8::is_ascii_digit) {
+            return Err(b"number option to `s' command may not be zero");
+        }
 
-            loop {
-                let n = io::read(0, &mut buf);
-                if n <= 0 { break; }
-
-                for &c in &buf[..n as usize] {
-                    if c == b'\n' {
-                        // Do substitution
-                        let mut result = [0u8; 4096];
-                        let mut result_len = 0;
-                        let mut i = 0;
-                        let mut did_replace = false;
-
-                        while i < line_len {
-                            if i + pattern.len() <= line_len && &line[i..i+pattern.len()] == pattern {
-                                for &r in replacement {
-                                    if result_len < result.len() {
-                                        result[result_len] = r;
-                                        result_len += 1;
-                                    }
-                                }
-                                i += pattern.len();
-                                did_replace = true;
-                                if !global {
-                                    // Copy rest
-                                    while i < line_len && result_len < result.len() {
-                                        result[result_len] = line[i];
-                                        result_len += 1;
-                                        i += 1;
-                                    }
-                                    break;
-                                }
-                            } else {
-                                if result_len < result.len() {
-                                    result[result_len] = line[i];
-                                    result_len += 1;
-                                }
-                                i += 1;
-                            }
-                        }
+        let regex = Regex::new(&buf[..len], flags)?;
+        for pair in replacement.windows(2) {
+            if pair[0] == b'\\' && pair[1].is_ascii_digit() && (pair[1] - b'0') as usize > regex.groups() {
+                return Err(b"invalid reference on `s' command's RHS");
+            }
+        }
+        Ok(Substitute { regex, replacement, delim, occurrence: occurrence.max(1), global })
+    }
+
+    /// Write `line` with the first, or every, match replaced
+    fn apply(&self, line: &[u8], out: &mut io::BufWriter) {
+        let mut caps = Captures::new();
+        let mut pos = 0;
+        let mut prev_end = None;
+        let mut count = 0;
+
+        while pos <= line.len() && self.regex.captures_at(line, pos, &mut caps) {
+            let (start, end) = caps.get(0).unwrap_or((pos, pos));
+            // No empty match right after the previous match
+            if start == end && prev_end == Some(start) {
+                if start < line.len() {
+                    out.write_byte(line[start]);
+                }
+                pos = start + 1;
+                continue;
+            }
+
+            out.write_all(&line[pos..start]);
+            count += 1;
+            if count < self.occurrence {
+                out.write_all(&line[start..end]);
+            } else {
+                self.expand(line, &caps, out);
+            }
+            prev_end = Some(end);
+            if start == end {
+                if start < line.len() {
+                    out.write_byte(line[start]);
+                }
+                pos = end + 1;
+            } else {
+                pos = end;
+            }
+            if count >= self.occurrence && !self.global {
+                break;
+            }
+        }
+        if pos < line.len() {
+            out.write_all(&line[pos..]);
+        }
+    }
 
-                        if did_replace {
-                            io::write_all(1, &result[..result_len]);
-                        } else {
-                            io::write_all(1, &line[..line_len]);
+    /// Write the replacement for one match
+    fn expand(&self, line: &[u8], caps: &Captures, out: &mut io::BufWriter) {
+        let r = self.replacement;
+        let mut i = 0;
+        while i < r.len() {
+            let c = r[i];
+            if c == b'&' {
+                if let Some((s, e)) = caps.get(0) {
+                    out.write_all(&line[s..e]);
+                }
+            } else if c == b'\\' && i + 1 < r.len() {
+                i += 1;
+                match r[i] {
+                    d @ b'0'..=b'9' => {
+                        if let Some((s, e)) = caps.get((d - b'0') as usize) {
+                            out.write_all(&line[s..e]);
                         }
-                        io::write_str(1, b"\n");
-                        line_len = 0;
-                    } else if line_len < line.len() {
-                        line[line_len] = c;
-                        line_len += 1;
                     }
+                    b'n' if self.delim != b'n' => out.write_byte(b'\n'),
+                    b't' if self.delim != b't' => out.write_byte(b'\t'),
+                    other => out.write_byte(other),
                 }
+            } else {
+                out.write_byte(c);
             }
-        }
-    } else {
-        // Other commands - just pass through
-        let mut buf = [0u8; 4096];
-        loop {
-            let n = io::read(0, &mut buf);
-            if n <= 0 { break; }
-            io::write_all(1, &buf[..n as usize]);
+            i += 1;
         }
     }
-    0
 }
 
 /// awk - pattern scanning and processing
 pub fn awk(argc: i32, argv: *const *const u8) -> i32 {
-    if argc < 2 {
-        io::write_str(2, b"awk: missing program\n");
-        return 1;
-    }
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    let program = match opts.operand(0) {
+        Some(p) => p,
+        None => {
+            return usage::error(b"awk", b"missing program");
+        }
+    };
 
-    let program = unsafe { get_arg(argv, 1).unwrap() };
+    // Very simple awk - just [!]/ERE/ and {print $N}
+    let mut pattern_buf = [0u8; 1024];
+    let (filter, action) = match awk_pattern(program, &mut pattern_buf) {
+        Ok(parsed) => parsed,
+        Err(msg) => {
+            sys::error(b"awk", msg);
+            return 2;
+        }
+    };
 
-    // Very simple awk - just {print $N}
     let mut print_field: Option<usize> = None;
     let mut print_all = false;
 
-    if program == b"{print}" || program == b"{print $0}" {
+    if action.is_empty() || action == b"{print}" || action == b"{print $0}" {
         print_all = true;
-    } else if program.starts_with(b"{print $") {
-        let end = program.iter().position(|&c| c == b'}').unwrap_or(program.len());
-        if let Some(n) = sys::parse_u64(&program[8..end]) {
+    } else if action.starts_with(b"{print $") {
+        let end = action.iter().position(|&c| c == b'}').unwrap_or(action.len());
+        if let Some(n) = sys::parse_u64(&action[8..end]) {
             print_field = Some(n as usize);
         }
     }
 
-    let mut buf = [0u8; 4096];
-    let mut line = [0u8; 4096];
-    let mut line_len = 0;
+    let inputs = io::Inputs::new(opts.applet(), opts.operands().skip(1));
+    let mut lines = inputs.lines(b'\n');
+    while let Some(line) = lines.next_line() {
+        let line = line.data;
+        if let Some((re, negate)) = &filter {
+            if re.is_match(line) == *negate {
+                continue;
+            }
+        }
+        if print_all {
+            out.write_all(line);
+            out.write_str(b"\n");
+        } else if let Some(field) = print_field {
+            // Split by whitespace
+            let text = line
+                .split(|&c| c == b' ' || c == b'\t')
+                .filter(|f| !f.is_empty())
+                .nth(field.wrapping_sub(1));
+            if let Some(text) = text {
+                out.write_all(text);
+                out.write_str(b"\n");
+            }
+        }
+    }
 
-    loop {
-        let n = io::read(0, &mut buf);
-        if n <= 0 { break; }
+    if lines.failed() { 2 } else { 0 }
+}
 
-        for &c in &buf[..n as usize] {
-            if c == b'\n' {
-                if print_all {
-                    io::write_all(1, &line[..line_len]);
-                    io::write_str(1, b"\n");
-                } else if let Some(field) = print_field {
-                    // Split by whitespace
-                    let mut field_num = 0;
-                    let mut start = 0;
-                    let mut in_field = false;
-
-                    for i in 0..=line_len {
-                        let is_space = i == line_len || line[i] == b' ' || line[i] == b'\t';
-
-                        if !in_field && !is_space {
-                            in_field = true;
-                            field_num += 1;
-                            start = i;
-                        } else if in_field && is_space {
-                            if field_num == field {
-                                io::write_all(1, &line[start..i]);
-                                io::write_str(1, b"\n");
-                                break;
-                            }
-                            in_field = false;
-                        }
-                    }
-                }
-                line_len = 0;
-            } else if line_len < line.len() {
-                line[line_len] = c;
-                line_len += 1;
-            }
+/// An awk `/ERE/` pattern, and whether it was negated with `!`
+type AwkFilter = Option<(Regex, bool)>;
+
+/// Split an awk program into its `[!]/ERE/` pattern, if any, and the
+/// action after it; `\/` in the pattern stands for `/`
+fn awk_pattern<'a>(program: &'a [u8], buf: &mut [u8]) -> Result<(AwkFilter, &'a [u8]), &'static [u8]> {
+    let program = program.trim_ascii();
+    let negate = program.first() == Some(&b'!');
+    let rest = if negate { program[1..].trim_ascii_start() } else { program };
+    if rest.first() != Some(&b'/') {
+        return Ok((None, program));
+    }
+
+    let mut len = 0;
+    let mut i = 1;
+    loop {
+        let mut c = *rest.get(i).ok_or(b"unterminated regexp".as_slice())?;
+        if c == b'/' {
+            break;
+        }
+        if c == b'\\' && rest.get(i + 1) == Some(&b'/') {
+            i += 1;
+            c = b'/';
         }
+        *buf.get_mut(len).ok_or(b"regexp too long".as_slice())? = c;
+        len += 1;
+        i += 1;
     }
-    0
+    let re = Regex::new(&buf[..len], regex::EXTENDED)?;
+    Ok((Some((re, negate)), rest[i + 1..].trim_ascii()))
 }
 
 /// comm - compare sorted files
@@ -1155,36 +1769,36 @@ pub fn comm(_argc: i32, _argv: *const *const u8) -> i32 {
 
 /// expand - convert tabs to spaces
 pub fn expand(argc: i32, argv: *const *const u8) -> i32 {
-    let fd = if argc > 1 {
-        if let Some(path) = unsafe { get_arg(argv, argc - 1) } {
-            if path[0] != b'-' {
-                io::open(path, libc::O_RDONLY, 0)
-            } else { 0 }
-        } else { 0 }
-    } else { 0 };
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let fd = match opts.operands().last() {
+        Some(path) if path != b"-" => io::open(path, libc::O_RDONLY, 0),
+        _ => 0,
+    };
 
-    let mut buf = [0u8; 4096];
+    let utf8 = utf8::locale_is_utf8();
+    let mut reader = LineReader::new(fd);
     let mut col = 0;
-
-    loop {
-        let n = io::read(fd, &mut buf);
-        if n <= 0 { break; }
-
-        for &c in &buf[..n as usize] {
-            if c == b'\t' {
+    while let Some(line) = reader.next_line() {
+        for c in utf8::chars(line.data, utf8) {
+            if c == b"\t" {
                 let spaces = 8 - (col % 8);
                 for _ in 0..spaces {
-                    io::write_str(1, b" ");
+                    out.write_str(b" ");
                 }
                 col += spaces;
-            } else if c == b'\n' {
-                io::write_str(1, b"\n");
-                col = 0;
             } else {
-                io::write_all(1, &[c]);
-                col += 1;
+                out.write_all(c);
+                col += utf8::char_width(c, utf8);
             }
         }
+        if line.terminated {
+            out.write_str(b"\n");
+            col = 0;
+        }
     }
 
     if fd != 0 { io::close(fd); }
@@ -1193,13 +1807,15 @@ pub fn expand(argc: i32, argv: *const *const u8) -> i32 {
 
 /// unexpand - convert spaces to tabs
 pub fn unexpand(argc: i32, argv: *const *const u8) -> i32 {
-    let fd = if argc > 1 {
-        if let Some(path) = unsafe { get_arg(argv, argc - 1) } {
-            if path[0] != b'-' {
-                io::open(path, libc::O_RDONLY, 0)
-            } else { 0 }
-        } else { 0 }
-    } else { 0 };
+    let out = io::stdout();
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+    let fd = match opts.operands().last() {
+        Some(path) if path != b"-" => io::open(path, libc::O_RDONLY, 0),
+        _ => 0,
+    };
 
     let mut buf = [0u8; 4096];
     let mut spaces = 0;
@@ -1212,15 +1828,15 @@ pub fn unexpand(argc: i32, argv: *const *const u8) -> i32 {
             if c == b' ' {
                 spaces += 1;
                 if spaces == 8 {
-                    io::write_str(1, b"\t");
+                    out.write_str(b"\t");
                     spaces = 0;
                 }
             } else {
                 for _ in 0..spaces {
-                    io::write_str(1, b" ");
+                    out.write_str(b" ");
                 }
                 spaces = 0;
-                io::write_all(1, &[c]);
+                out.write_byte(c);
             }
         }
     }
@@ -1231,37 +1847,35 @@ pub fn unexpand(argc: i32, argv: *const *const u8) -> i32 {
 
 /// fold - wrap lines to specified width
 pub fn fold(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let mut width = 80usize;
 
-    for i in 1..argc {
-        if let Some(arg) = unsafe { get_arg(argv, i) } {
-            if has_opt(arg, b'w') && i + 1 < argc {
-                if let Some(w) = unsafe { get_arg(argv, i + 1) } {
-                    width = sys::parse_u64(w).unwrap_or(80) as usize;
-                }
-            }
+    for arg in Getopt::new(argc, &argv, b"w(width):") {
+        match arg {
+            Arg::Opt(b"w", Some(w)) => width = sys::parse_u64(w).unwrap_or(80) as usize,
+            Arg::Error => return 1,
+            _ => {}
         }
     }
 
-    let mut buf = [0u8; 4096];
+    // Characters are kept whole, so a wide one that doesn't fit on the line
+    // starts the next
+    let utf8 = utf8::locale_is_utf8();
+    let mut reader = LineReader::new(0);
     let mut col = 0;
-
-    loop {
-        let n = io::read(0, &mut buf);
-        if n <= 0 { break; }
-
-        for &c in &buf[..n as usize] {
-            if c == b'\n' {
-                io::write_str(1, b"\n");
+    while let Some(line) = reader.next_line() {
+        for c in utf8::chars(line.data, utf8) {
+            let w = utf8::char_width(c, utf8);
+            if col + w > width && col > 0 {
+                out.write_str(b"\n");
                 col = 0;
-            } else {
-                if col >= width {
-                    io::write_str(1, b"\n");
-                    col = 0;
-                }
-                io::write_all(1, &[c]);
-                col += 1;
             }
+            out.write_all(c);
+            col += w;
+        }
+        if line.terminated {
+            out.write_str(b"\n");
+            col = 0;
         }
     }
     0
@@ -1274,101 +1888,132 @@ pub fn fmt(argc: i32, argv: *const *const u8) -> i32 {
 
 /// strings - print printable strings from binary
 pub fn strings(argc: i32, argv: *const *const u8) -> i32 {
+    let out = io::stdout();
     let min_len = 4;
 
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                let fd = io::open(path, libc::O_RDONLY, 0);
-                if fd < 0 { continue; }
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
 
-                let mut buf = [0u8; 4096];
-                let mut string = [0u8; 256];
-                let mut string_len = 0;
-
-                loop {
-                    let n = io::read(fd, &mut buf);
-                    if n <= 0 { break; }
-
-                    for &c in &buf[..n as usize] {
-                        if c >= 0x20 && c < 0x7f {
-                            if string_len < string.len() {
-                                string[string_len] = c;
-                                string_len += 1;
-                            }
-                        } else {
-                            if string_len >= min_len {
-                                io::write_all(1, &string[..string_len]);
-                                io::write_str(1, b"\n");
-                            }
-                            string_len = 0;
-                        }
+    let mut status = 0;
+    for path in opts.operands() {
+        let fd = io::open(path, libc::O_RDONLY, 0);
+        if fd < 0 {
+            sys::perror(b"strings", path);
+            status = 1;
+            continue;
+        }
+
+        let mut buf = [0u8; 4096];
+        let mut string = [0u8; 256];
+        let mut string_len = 0;
+
+        loop {
+            let n = io::read(fd, &mut buf);
+            if n <= 0 { break; }
+
+            for &c in &buf[..n as usize] {
+                if (0x20..0x7f).contains(&c) {
+                    if string_len < string.len() {
+                        string[string_len] = c;
+                        string_len += 1;
                     }
+                } else {
+                    if string_len >= min_len {
+                        out.write_all(&string[..string_len]);
+                        out.write_str(b"\n");
+                    }
+                    string_len = 0;
                 }
-
-                io::close(fd);
             }
         }
+
+        io::close(fd);
     }
-    0
+    status
 }
 
 /// dos2unix - convert line endings
 pub fn dos2unix(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                #[cfg(feature = "alloc")]
-                {
-                    let fd = io::open(path, libc::O_RDONLY, 0);
-                    if fd < 0 { continue; }
-
-                    let content = io::read_all(fd);
-                    io::close(fd);
-
-                    let fd = io::open(path, libc::O_WRONLY | libc::O_TRUNC, 0);
-                    if fd < 0 { continue; }
-
-                    for &c in &content {
-                        if c != b'\r' {
-                            io::write_all(fd, &[c]);
-                        }
-                    }
-                    io::close(fd);
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    let mut status = 0;
+    for path in opts.operands() {
+        #[cfg(feature = "alloc")]
+        {
+            let fd = io::open(path, libc::O_RDONLY, 0);
+            if fd < 0 {
+                sys::perror(b"dos2unix", path);
+                status = 1;
+                continue;
+            }
+
+            let content = io::read_all(fd);
+            io::close(fd);
+
+            let fd = io::open(path, libc::O_WRONLY | libc::O_TRUNC, 0);
+            if fd < 0 {
+                sys::perror(b"dos2unix", path);
+                status = 1;
+                continue;
+            }
+
+            for &c in &content {
+                if c != b'\r' {
+                    io::write_all(fd, &[c]);
                 }
             }
+            io::close(fd);
         }
+        #[cfg(not(feature = "alloc"))]
+        let _ = path;
     }
-    0
+    status
 }
 
 /// unix2dos - convert line endings
 pub fn unix2dos(argc: i32, argv: *const *const u8) -> i32 {
-    for i in 1..argc {
-        if let Some(path) = unsafe { get_arg(argv, i) } {
-            if path[0] != b'-' {
-                #[cfg(feature = "alloc")]
-                {
-                    let fd = io::open(path, libc::O_RDONLY, 0);
-                    if fd < 0 { continue; }
-
-                    let content = io::read_all(fd);
-                    io::close(fd);
-
-                    let fd = io::open(path, libc::O_WRONLY | libc::O_TRUNC, 0);
-                    if fd < 0 { continue; }
-
-                    for &c in &content {
-                        if c == b'\n' {
-                            io::write_str(fd, b"\r\n");
-                        } else if c != b'\r' {
-                            io::write_all(fd, &[c]);
-                        }
-                    }
-                    io::close(fd);
+    let opts = match Getopt::no_options(argc, &argv) {
+        Some(opts) => opts,
+        None => return 1,
+    };
+
+    let mut status = 0;
+    for path in opts.operands() {
+        #[cfg(feature = "alloc")]
+        {
+            let fd = io::open(path, libc::O_RDONLY, 0);
+            if fd < 0 {
+                sys::perror(b"unix2dos", path);
+                status = 1;
+                continue;
+            }
+
+            let content = io::read_all(fd);
+            io::close(fd);
+
+            let fd = io::open(path, libc::O_WRONLY | libc::O_TRUNC, 0);
+            if fd < 0 {
+                sys::perror(b"unix2dos", path);
+                status = 1;
+                continue;
+            }
+
+            for &c in &content {
+                if c == b'\n' {
+                    io::write_str(fd, b"\r\n");
+                } else if c != b'\r' {
+                    io::write_all(fd, &[c]);
                 }
             }
+            io::close(fd);
         }
+        #[cfg(not(feature = "alloc"))]
+        let _ = path;
     }
-    0
+    status
 }
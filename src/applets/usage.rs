@@ -0,0 +1,115 @@
+//! Per-applet usage text
+//!
+//! The text comes from `usage.txt` by way of `build.rs`, which keeps only the
+//! applets compiled into this binary. With `usage-compressed` it is stored
+//! LZ-compressed and unpacked when help is shown. Without `usage` there is no
+//! text at all and nothing is printed.
+
+use crate::io;
+use crate::sys;
+use super::Status;
+
+#[cfg(feature = "usage-compressed")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "usage")]
+include!(concat!(env!("OUT_DIR"), "/usage.rs"));
+
+/// `armybox --help APPLET` and `help APPLET`: show the usage of `name`, or
+/// explain why there is none
+pub fn help(name: &[u8]) -> i32 {
+    match super::find_applet(name) {
+        Some(applet) if applet.status == Status::Stub => sys::error(name, b"not implemented"),
+        Some(_) if show(name) => return 0,
+        Some(_) => sys::error(name, b"no help available"),
+        None => sys::error(name, b"applet not found"),
+    }
+    1
+}
+
+/// Report a usage problem such as a missing operand as `name: msg`,
+/// followed by the one-line usage, and return the exit status for it
+pub fn error(name: &[u8], msg: &[u8]) -> i32 {
+    sys::error(name, msg);
+    show_short(name);
+    1
+}
+
+/// Print `Usage: NAME SYNOPSIS` and the description to stdout; false if
+/// there is no text for `name`
+pub fn show(name: &[u8]) -> bool {
+    with_entry(name, |synopsis, text| {
+        let out = io::stdout();
+        out.write_str(b"Usage: ");
+        out.write_all(name);
+        if !synopsis.is_empty() {
+            out.write_str(b" ");
+            out.write_all(synopsis);
+        }
+        out.write_str(b"\n");
+        if !text.is_empty() {
+            out.write_str(b"\n");
+            out.write_all(text);
+        }
+    })
+}
+
+/// Print the one-line usage to stderr, after an option error
+pub fn show_short(name: &[u8]) {
+    with_entry(name, |synopsis, _| {
+        io::write_str(2, b"Usage: ");
+        io::write_all(2, name);
+        if !synopsis.is_empty() {
+            io::write_str(2, b" ");
+            io::write_all(2, synopsis);
+        }
+        io::write_str(2, b"\n");
+    });
+}
+
+/// Call `f` with the synopsis and description of `name`
+#[cfg(feature = "usage")]
+fn with_entry(name: &[u8], f: impl FnOnce(&[u8], &[u8])) -> bool {
+    let (start, split, end) = match INDEX.binary_search_by(|e| e.0.cmp(name)) {
+        Ok(i) => (INDEX[i].1 as usize, INDEX[i].2 as usize, INDEX[i].3 as usize),
+        Err(_) => return false,
+    };
+    let text = unpack(end);
+    f(&text[start..split], &text[split..end]);
+    true
+}
+
+#[cfg(not(feature = "usage"))]
+fn with_entry(_name: &[u8], _f: impl FnOnce(&[u8], &[u8])) -> bool {
+    false
+}
+
+#[cfg(all(feature = "usage", not(feature = "usage-compressed")))]
+fn unpack(_len: usize) -> &'static [u8] {
+    TEXT
+}
+
+/// Unpack at least the first `len` bytes of the text. Bytes below 0x80 are
+/// literals; `0x80 | (n - 4)` and a big-endian 16-bit distance copy `n`
+/// bytes from that far back in the output.
+#[cfg(feature = "usage-compressed")]
+fn unpack(len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 0x83);
+    let mut i = 0;
+    while out.len() < len && i < TEXT.len() {
+        let tag = TEXT[i];
+        if tag < 0x80 {
+            out.push(tag);
+            i += 1;
+            continue;
+        }
+        let n = (tag & 0x7f) as usize + 4;
+        let distance = ((TEXT[i + 1] as usize) << 8) | TEXT[i + 2] as usize;
+        let from = out.len() - distance;
+        for k in from..from + n {
+            out.push(out[k]);
+        }
+        i += 3;
+    }
+    out
+}
@@ -0,0 +1,443 @@
+//! UTF-8 characters
+//!
+//! Text applets work on bytes, which is all the C locale asks of them.
+//! When the locale is UTF-8 (`locale_is_utf8`), `wc -m`, `cut -c`, `rev`,
+//! `fold`, `expand`, `tr` and `ls` use this module to step over whole
+//! characters and measure how many terminal columns they take.
+//!
+//! - `decode` and `encode` convert between bytes and scalar values;
+//!   overlong forms, surrogates and values past U+10FFFF are invalid
+//! - invalid bytes are never dropped or replaced: each is a character of
+//!   its own, one column wide, and is written back out unchanged
+//! - `width` follows `wcwidth(3)`: combining marks and format characters
+//!   take no columns, East Asian wide and fullwidth characters two
+//! - `to_lower`, `to_upper` and `fold_case` map single characters, for the
+//!   Latin, Greek, Cyrillic and Armenian alphabets
+
+/// The length of the sequence a lead byte starts, or 0 for a byte that
+/// can't start one
+fn sequence_len(lead: u8) -> usize {
+    match lead {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 0,
+    }
+}
+
+/// The range the second byte of a sequence must fall in, which rules out
+/// overlong forms, surrogates and values past U+10FFFF
+fn second_range(lead: u8) -> (u8, u8) {
+    match lead {
+        0xe0 => (0xa0, 0xbf),
+        0xed => (0x80, 0x9f),
+        0xf0 => (0x90, 0xbf),
+        0xf4 => (0x80, 0x8f),
+        _ => (0x80, 0xbf),
+    }
+}
+
+/// How many bytes of `s` form a valid sequence, or a valid start of one
+fn valid_prefix(s: &[u8]) -> usize {
+    let len = sequence_len(s[0]);
+    if len <= 1 {
+        return len;
+    }
+    let (lo, hi) = second_range(s[0]);
+    match s.get(1) {
+        Some(&c) if (lo..=hi).contains(&c) => {}
+        _ => return 1,
+    }
+    let mut i = 2;
+    while i < len && i < s.len() && s[i] & 0xc0 == 0x80 {
+        i += 1;
+    }
+    i
+}
+
+/// Decode the character at the start of `s`, which must not be empty.
+///
+/// Returns its value and length, or `None` and 1 for an invalid or
+/// truncated sequence, whose first byte should then be taken alone.
+pub fn decode(s: &[u8]) -> (Option<u32>, usize) {
+    let len = sequence_len(s[0]);
+    if len == 1 {
+        return (Some(s[0] as u32), 1);
+    }
+    if len == 0 || valid_prefix(s) < len {
+        return (None, 1);
+    }
+    let mut c = (s[0] & (0x7f >> len)) as u32;
+    for &b in &s[1..len] {
+        c = c << 6 | (b & 0x3f) as u32;
+    }
+    (Some(c), len)
+}
+
+/// Encode `c` into `buf`, returning the bytes written. Surrogates and
+/// values past U+10FFFF become U+FFFD.
+pub fn encode(c: u32, buf: &mut [u8; 4]) -> &[u8] {
+    let c = if (0xd800..0xe000).contains(&c) || c > 0x10ffff { 0xfffd } else { c };
+    let len = match c {
+        0..=0x7f => {
+            buf[0] = c as u8;
+            return &buf[..1];
+        }
+        0x80..=0x7ff => 2,
+        0x800..=0xffff => 3,
+        _ => 4,
+    };
+    let lead: u8 = [0, 0, 0xc0, 0xe0, 0xf0][len];
+    buf[0] = lead | (c >> (6 * (len - 1))) as u8;
+    for (i, byte) in buf[..len].iter_mut().enumerate().skip(1) {
+        *byte = 0x80 | (c >> (6 * (len - 1 - i)) & 0x3f) as u8;
+    }
+    &buf[..len]
+}
+
+/// Whether `s` is valid UTF-8, or the offset of the first invalid byte
+pub fn validate(s: &[u8]) -> Result<(), usize> {
+    let mut i = 0;
+    while i < s.len() {
+        match decode(&s[i..]) {
+            (Some(_), len) => i += len,
+            (None, _) => return Err(i),
+        }
+    }
+    Ok(())
+}
+
+/// The length of `s` without a sequence cut short at its end.
+///
+/// A reader that decodes a buffer at a time keeps the bytes past this for
+/// the next read, so a character split by the read is decoded whole.
+pub fn complete_len(s: &[u8]) -> usize {
+    // A sequence is at most 4 bytes, so only the last 3 can start a
+    // truncated one
+    for start in s.len().saturating_sub(3)..s.len() {
+        let len = sequence_len(s[start]);
+        if len > s.len() - start && valid_prefix(&s[start..]) == s.len() - start {
+            return start;
+        }
+    }
+    s.len()
+}
+
+/// The length of the character at the start of `s`: its whole sequence in
+/// a UTF-8 locale, otherwise one byte
+pub fn char_len(s: &[u8], utf8: bool) -> usize {
+    if utf8 { decode(s).1 } else { 1 }
+}
+
+/// Iterator over the characters of a byte string, each as its bytes
+pub struct Chars<'a> {
+    rest: &'a [u8],
+    utf8: bool,
+}
+
+/// The characters of `s`, or its bytes if `utf8` is false
+pub fn chars(s: &[u8], utf8: bool) -> Chars<'_> {
+    Chars { rest: s, utf8 }
+}
+
+impl<'a> Iterator for Chars<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let (c, rest) = self.rest.split_at(char_len(self.rest, self.utf8));
+        self.rest = rest;
+        Some(c)
+    }
+}
+
+/// Whether the locale in the environment uses UTF-8.
+///
+/// The first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set and not empty
+/// decides, as in `setlocale(3)`; with none of them, the locale is C.
+pub fn locale_is_utf8() -> bool {
+    let locale = [b"LC_ALL".as_slice(), b"LC_CTYPE", b"LANG"]
+        .iter()
+        .find_map(|name| crate::io::getenv(name).filter(|v| !v.is_empty()));
+    let Some(locale) = locale else { return false };
+    // The codeset follows a `.`, up to any `@modifier`
+    let Some(dot) = locale.iter().position(|&c| c == b'.') else { return false };
+    let codeset = locale[dot + 1..].split(|&c| c == b'@').next().unwrap_or(b"");
+    codeset.eq_ignore_ascii_case(b"UTF-8") || codeset.eq_ignore_ascii_case(b"utf8")
+}
+
+/// Whether `c` falls in one of the sorted, inclusive `ranges`
+fn in_table(c: u32, ranges: &[(u32, u32)]) -> bool {
+    ranges
+        .binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                core::cmp::Ordering::Less
+            } else if lo > c {
+                core::cmp::Ordering::Greater
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Combining marks (Mn, Me), format characters (Cf) and Hangul medial and
+/// final jamo, which take no columns of their own
+static ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036f), (0x0483, 0x0489), (0x0591, 0x05bd), (0x05bf, 0x05bf),
+    (0x05c1, 0x05c2), (0x05c4, 0x05c5), (0x05c7, 0x05c7), (0x0600, 0x0605),
+    (0x0610, 0x061a), (0x061c, 0x061c), (0x064b, 0x065f), (0x0670, 0x0670),
+    (0x06d6, 0x06dd), (0x06df, 0x06e4), (0x06e7, 0x06e8), (0x06ea, 0x06ed),
+    (0x070f, 0x070f), (0x0711, 0x0711), (0x0730, 0x074a), (0x07a6, 0x07b0),
+    (0x07eb, 0x07f3), (0x07fd, 0x07fd), (0x0816, 0x0819), (0x081b, 0x0823),
+    (0x0825, 0x0827), (0x0829, 0x082d), (0x0859, 0x085b), (0x0890, 0x0891),
+    (0x0898, 0x089f), (0x08ca, 0x0902), (0x093a, 0x093a), (0x093c, 0x093c),
+    (0x0941, 0x0948), (0x094d, 0x094d), (0x0951, 0x0957), (0x0962, 0x0963),
+    (0x0981, 0x0981), (0x09bc, 0x09bc), (0x09c1, 0x09c4), (0x09cd, 0x09cd),
+    (0x09e2, 0x09e3), (0x09fe, 0x09fe), (0x0a01, 0x0a02), (0x0a3c, 0x0a3c),
+    (0x0a41, 0x0a42), (0x0a47, 0x0a48), (0x0a4b, 0x0a4d), (0x0a51, 0x0a51),
+    (0x0a70, 0x0a71), (0x0a75, 0x0a75), (0x0a81, 0x0a82), (0x0abc, 0x0abc),
+    (0x0ac1, 0x0ac5), (0x0ac7, 0x0ac8), (0x0acd, 0x0acd), (0x0ae2, 0x0ae3),
+    (0x0afa, 0x0aff), (0x0b01, 0x0b01), (0x0b3c, 0x0b3c), (0x0b3f, 0x0b3f),
+    (0x0b41, 0x0b44), (0x0b4d, 0x0b4d), (0x0b55, 0x0b56), (0x0b62, 0x0b63),
+    (0x0b82, 0x0b82), (0x0bc0, 0x0bc0), (0x0bcd, 0x0bcd), (0x0c00, 0x0c00),
+    (0x0c04, 0x0c04), (0x0c3c, 0x0c3c), (0x0c3e, 0x0c40), (0x0c46, 0x0c48),
+    (0x0c4a, 0x0c4d), (0x0c55, 0x0c56), (0x0c62, 0x0c63), (0x0c81, 0x0c81),
+    (0x0cbc, 0x0cbc), (0x0cbf, 0x0cbf), (0x0cc6, 0x0cc6), (0x0ccc, 0x0ccd),
+    (0x0ce2, 0x0ce3), (0x0d00, 0x0d01), (0x0d3b, 0x0d3c), (0x0d41, 0x0d44),
+    (0x0d4d, 0x0d4d), (0x0d62, 0x0d63), (0x0d81, 0x0d81), (0x0dca, 0x0dca),
+    (0x0dd2, 0x0dd4), (0x0dd6, 0x0dd6), (0x0e31, 0x0e31), (0x0e34, 0x0e3a),
+    (0x0e47, 0x0e4e), (0x0eb1, 0x0eb1), (0x0eb4, 0x0ebc), (0x0ec8, 0x0ece),
+    (0x0f18, 0x0f19), (0x0f35, 0x0f35), (0x0f37, 0x0f37), (0x0f39, 0x0f39),
+    (0x0f71, 0x0f7e), (0x0f80, 0x0f84), (0x0f86, 0x0f87), (0x0f8d, 0x0f97),
+    (0x0f99, 0x0fbc), (0x0fc6, 0x0fc6), (0x102d, 0x1030), (0x1032, 0x1037),
+    (0x1039, 0x103a), (0x103d, 0x103e), (0x1058, 0x1059), (0x105e, 0x1060),
+    (0x1071, 0x1074), (0x1082, 0x1082), (0x1085, 0x1086), (0x108d, 0x108d),
+    (0x109d, 0x109d), (0x1160, 0x11ff), (0x135d, 0x135f), (0x1712, 0x1714),
+    (0x1732, 0x1733), (0x1752, 0x1753), (0x1772, 0x1773), (0x17b4, 0x17b5),
+    (0x17b7, 0x17bd), (0x17c6, 0x17c6), (0x17c9, 0x17d3), (0x17dd, 0x17dd),
+    (0x180b, 0x180f), (0x1885, 0x1886), (0x18a9, 0x18a9), (0x1920, 0x1922),
+    (0x1927, 0x1928), (0x1932, 0x1932), (0x1939, 0x193b), (0x1a17, 0x1a18),
+    (0x1a1b, 0x1a1b), (0x1a56, 0x1a56), (0x1a58, 0x1a5e), (0x1a60, 0x1a60),
+    (0x1a62, 0x1a62), (0x1a65, 0x1a6c), (0x1a73, 0x1a7c), (0x1a7f, 0x1a7f),
+    (0x1ab0, 0x1ace), (0x1b00, 0x1b03), (0x1b34, 0x1b34), (0x1b36, 0x1b3a),
+    (0x1b3c, 0x1b3c), (0x1b42, 0x1b42), (0x1b6b, 0x1b73), (0x1b80, 0x1b81),
+    (0x1ba2, 0x1ba5), (0x1ba8, 0x1ba9), (0x1bab, 0x1bad), (0x1be6, 0x1be6),
+    (0x1be8, 0x1be9), (0x1bed, 0x1bed), (0x1bef, 0x1bf1), (0x1c2c, 0x1c33),
+    (0x1c36, 0x1c37), (0x1cd0, 0x1cd2), (0x1cd4, 0x1ce0), (0x1ce2, 0x1ce8),
+    (0x1ced, 0x1ced), (0x1cf4, 0x1cf4), (0x1cf8, 0x1cf9), (0x1dc0, 0x1dff),
+    (0x200b, 0x200f), (0x202a, 0x202e), (0x2060, 0x2064), (0x2066, 0x206f),
+    (0x20d0, 0x20f0), (0x2cef, 0x2cf1), (0x2d7f, 0x2d7f), (0x2de0, 0x2dff),
+    (0x302a, 0x302d), (0x3099, 0x309a), (0xa66f, 0xa672), (0xa674, 0xa67d),
+    (0xa69e, 0xa69f), (0xa6f0, 0xa6f1), (0xa802, 0xa802), (0xa806, 0xa806),
+    (0xa80b, 0xa80b), (0xa825, 0xa826), (0xa82c, 0xa82c), (0xa8c4, 0xa8c5),
+    (0xa8e0, 0xa8f1), (0xa8ff, 0xa8ff), (0xa926, 0xa92d), (0xa947, 0xa951),
+    (0xa980, 0xa982), (0xa9b3, 0xa9b3), (0xa9b6, 0xa9b9), (0xa9bc, 0xa9bd),
+    (0xa9e5, 0xa9e5), (0xaa29, 0xaa2e), (0xaa31, 0xaa32), (0xaa35, 0xaa36),
+    (0xaa43, 0xaa43), (0xaa4c, 0xaa4c), (0xaa7c, 0xaa7c), (0xaab0, 0xaab0),
+    (0xaab2, 0xaab4), (0xaab7, 0xaab8), (0xaabe, 0xaabf), (0xaac1, 0xaac1),
+    (0xaaec, 0xaaed), (0xaaf6, 0xaaf6), (0xabe5, 0xabe5), (0xabe8, 0xabe8),
+    (0xabed, 0xabed), (0xd7b0, 0xd7ff), (0xfb1e, 0xfb1e), (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f), (0xfeff, 0xfeff), (0xfff9, 0xfffb), (0x101fd, 0x101fd),
+    (0x102e0, 0x102e0), (0x10376, 0x1037a), (0x10a01, 0x10a03), (0x10a05, 0x10a06),
+    (0x10a0c, 0x10a0f), (0x10a38, 0x10a3a), (0x10a3f, 0x10a3f), (0x10ae5, 0x10ae6),
+    (0x10d24, 0x10d27), (0x10eab, 0x10eac), (0x10f46, 0x10f50), (0x11001, 0x11001),
+    (0x11038, 0x11046), (0x1107f, 0x11081), (0x110b3, 0x110b6), (0x110b9, 0x110ba),
+    (0x110bd, 0x110bd), (0x11100, 0x11102), (0x11127, 0x1112b), (0x1112d, 0x11134),
+    (0x11173, 0x11173), (0x11180, 0x11181), (0x111b6, 0x111be), (0x1122f, 0x11231),
+    (0x11234, 0x11234), (0x11236, 0x11237), (0x112df, 0x112df), (0x112e3, 0x112ea),
+    (0x11300, 0x11301), (0x1133b, 0x1133c), (0x11340, 0x11340), (0x11366, 0x11374),
+    (0x11438, 0x1143f), (0x11442, 0x11444), (0x11446, 0x11446), (0x114b3, 0x114b8),
+    (0x115b2, 0x115b5), (0x115bc, 0x115bd), (0x11633, 0x1163a), (0x116ab, 0x116ab),
+    (0x116ad, 0x116ad), (0x116b0, 0x116b5), (0x116b7, 0x116b7), (0x1171d, 0x1171f),
+    (0x11722, 0x11725), (0x11727, 0x1172b), (0x11a01, 0x11a0a), (0x11a33, 0x11a38),
+    (0x11a3b, 0x11a3e), (0x11a47, 0x11a47), (0x11a51, 0x11a56), (0x11a59, 0x11a5b),
+    (0x11a8a, 0x11a96), (0x11a98, 0x11a99), (0x11c30, 0x11c36), (0x11c38, 0x11c3d),
+    (0x11d31, 0x11d36), (0x11d3a, 0x11d3a), (0x11d3c, 0x11d3d), (0x11d3f, 0x11d45),
+    (0x11d47, 0x11d47), (0x13430, 0x13440), (0x16af0, 0x16af4), (0x16b30, 0x16b36),
+    (0x16f8f, 0x16f92), (0x16fe4, 0x16fe4), (0x1bc9d, 0x1bc9e), (0x1bca0, 0x1bca3),
+    (0x1cf00, 0x1cf46), (0x1d167, 0x1d169), (0x1d173, 0x1d182), (0x1d185, 0x1d18b),
+    (0x1d1aa, 0x1d1ad), (0x1d242, 0x1d244), (0x1da00, 0x1da36), (0x1da3b, 0x1da6c),
+    (0x1da75, 0x1da75), (0x1da84, 0x1da84), (0x1da9b, 0x1daaf), (0x1e000, 0x1e02a),
+    (0x1e130, 0x1e136), (0x1e2ec, 0x1e2ef), (0x1e8d0, 0x1e8d6), (0x1e944, 0x1e94a),
+    (0xe0001, 0xe0001), (0xe0020, 0xe007f), (0xe0100, 0xe01ef),
+];
+
+/// East Asian Wide (W) and Fullwidth (F) characters, including emoji
+/// presentation; checked after `ZERO_WIDTH`, which carves marks out of
+/// some of these ranges
+static WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115f), (0x231a, 0x231b), (0x2329, 0x232a), (0x23e9, 0x23ec),
+    (0x23f0, 0x23f0), (0x23f3, 0x23f3), (0x25fd, 0x25fe), (0x2614, 0x2615),
+    (0x2648, 0x2653), (0x267f, 0x267f), (0x2693, 0x2693), (0x26a1, 0x26a1),
+    (0x26aa, 0x26ab), (0x26bd, 0x26be), (0x26c4, 0x26c5), (0x26ce, 0x26ce),
+    (0x26d4, 0x26d4), (0x26ea, 0x26ea), (0x26f2, 0x26f3), (0x26f5, 0x26f5),
+    (0x26fa, 0x26fa), (0x26fd, 0x26fd), (0x2705, 0x2705), (0x270a, 0x270b),
+    (0x2728, 0x2728), (0x274c, 0x274c), (0x274e, 0x274e), (0x2753, 0x2755),
+    (0x2757, 0x2757), (0x2795, 0x2797), (0x27b0, 0x27b0), (0x27bf, 0x27bf),
+    (0x2b1b, 0x2b1c), (0x2b50, 0x2b50), (0x2b55, 0x2b55), (0x2e80, 0x303e),
+    (0x3041, 0x33ff), (0x3400, 0x4dbf), (0x4e00, 0xa4cf), (0xa960, 0xa97f),
+    (0xac00, 0xd7a3), (0xf900, 0xfaff), (0xfe10, 0xfe19), (0xfe30, 0xfe6f),
+    (0xff00, 0xff60), (0xffe0, 0xffe6), (0x16fe0, 0x16fe4), (0x16ff0, 0x16ff1),
+    (0x17000, 0x187f7), (0x18800, 0x18cd5), (0x18d00, 0x18d08), (0x1aff0, 0x1b2fb),
+    (0x1f004, 0x1f004), (0x1f0cf, 0x1f0cf), (0x1f18e, 0x1f18e), (0x1f191, 0x1f19a),
+    (0x1f200, 0x1f202), (0x1f210, 0x1f23b), (0x1f240, 0x1f248), (0x1f250, 0x1f251),
+    (0x1f260, 0x1f265), (0x1f300, 0x1f320), (0x1f32d, 0x1f335), (0x1f337, 0x1f37c),
+    (0x1f37e, 0x1f393), (0x1f3a0, 0x1f3ca), (0x1f3cf, 0x1f3d3), (0x1f3e0, 0x1f3f0),
+    (0x1f3f4, 0x1f3f4), (0x1f3f8, 0x1f43e), (0x1f440, 0x1f440), (0x1f442, 0x1f4fc),
+    (0x1f4ff, 0x1f53d), (0x1f54b, 0x1f54e), (0x1f550, 0x1f567), (0x1f57a, 0x1f57a),
+    (0x1f595, 0x1f596), (0x1f5a4, 0x1f5a4), (0x1f5fb, 0x1f64f), (0x1f680, 0x1f6c5),
+    (0x1f6cc, 0x1f6cc), (0x1f6d0, 0x1f6d2), (0x1f6d5, 0x1f6d7), (0x1f6dc, 0x1f6df),
+    (0x1f6eb, 0x1f6ec), (0x1f6f4, 0x1f6fc), (0x1f7e0, 0x1f7eb), (0x1f7f0, 0x1f7f0),
+    (0x1f90c, 0x1f93a), (0x1f93c, 0x1f945), (0x1f947, 0x1f9ff), (0x1fa70, 0x1fa7c),
+    (0x1fa80, 0x1fa88), (0x1fa90, 0x1fabd), (0x1fabf, 0x1fac5), (0x1face, 0x1fadb),
+    (0x1fae0, 0x1fae8), (0x1faf0, 0x1faf8), (0x20000, 0x2fffd), (0x30000, 0x3fffd),
+];
+
+/// Whether `c` is a combining mark or other character that takes no
+/// columns, and so belongs with the character before it
+pub fn is_zero_width(c: u32) -> bool {
+    c != 0 && in_table(c, ZERO_WIDTH)
+}
+
+/// The number of terminal columns `c` takes: 0 for controls and
+/// zero-width characters, 2 for wide ones, otherwise 1
+pub fn width(c: u32) -> usize {
+    if c < 0x20 || (0x7f..0xa0).contains(&c) || is_zero_width(c) {
+        0
+    } else if c >= 0x1100 && in_table(c, WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+/// The width of the character at the start of `s`. Outside a UTF-8
+/// locale every byte is one column, as is an invalid byte in one, since
+/// terminals show U+FFFD in its place.
+pub fn char_width(s: &[u8], utf8: bool) -> usize {
+    match decode(s) {
+        (Some(c), _) if utf8 => width(c),
+        _ => 1,
+    }
+}
+
+/// The character before `end` in `s`, as the index it starts at
+pub fn prev_char(s: &[u8], end: usize, utf8: bool) -> usize {
+    if utf8 {
+        for start in (end.saturating_sub(4)..end).rev() {
+            if s[start] & 0xc0 != 0x80 {
+                if let (Some(_), len) = decode(&s[start..end]) {
+                    if start + len == end {
+                        return start;
+                    }
+                }
+                break;
+            }
+        }
+    }
+    end - 1
+}
+
+/// The number of terminal columns `s` takes
+pub fn str_width(s: &[u8], utf8: bool) -> usize {
+    chars(s, utf8).map(|c| char_width(c, utf8)).sum()
+}
+
+/// A run of characters with simple case mappings: every `step`th one from
+/// `upper` to `last` is upper case, and its lower case form is `delta`
+/// further on
+struct CaseRange {
+    upper: u32,
+    last: u32,
+    delta: i32,
+    step: u32,
+}
+
+const fn run(upper: u32, last: u32, delta: i32) -> CaseRange {
+    CaseRange { upper, last, delta, step: 1 }
+}
+
+/// Alternating upper and lower case pairs from `upper` to `last`
+const fn pairs(upper: u32, last: u32) -> CaseRange {
+    CaseRange { upper, last, delta: 1, step: 2 }
+}
+
+/// Case mappings, searched in order; the first range a character is in
+/// wins, which lets the special cases in front override the runs after
+static CASES: &[CaseRange] = &[
+    run(0x41, 0x5a, 0x20),
+    run(0x130, 0x130, 0x69 - 0x130), // İ
+    run(0x49, 0x49, 0x131 - 0x49),   // ı
+    run(0x178, 0x178, 0xff - 0x178), // Ÿ
+    run(0xc0, 0xd6, 0x20),
+    run(0xd8, 0xde, 0x20),
+    pairs(0x100, 0x12f),
+    pairs(0x132, 0x137),
+    pairs(0x139, 0x148),
+    pairs(0x14a, 0x177),
+    pairs(0x179, 0x17e),
+    run(0x386, 0x386, 0x26),
+    run(0x388, 0x38a, 0x25),
+    run(0x38c, 0x38c, 0x40),
+    run(0x38e, 0x38f, 0x3f),
+    run(0x391, 0x3a1, 0x20),
+    run(0x3a3, 0x3ab, 0x20),
+    run(0x3a3, 0x3a3, 0x3c2 - 0x3a3), // final ς
+    pairs(0x3d8, 0x3ef),
+    run(0x400, 0x40f, 0x50),
+    run(0x410, 0x42f, 0x20),
+    pairs(0x460, 0x481),
+    pairs(0x48a, 0x4bf),
+    pairs(0x4c1, 0x4ce),
+    pairs(0x4d0, 0x52f),
+    run(0x531, 0x556, 0x30),
+    pairs(0x1e00, 0x1e95),
+    pairs(0x1ea0, 0x1eff),
+    run(0xff21, 0xff3a, 0x20),
+];
+
+/// Map `c` from upper to lower case, or back if `to_upper`
+fn case_map(c: u32, to_upper: bool) -> u32 {
+    for range in CASES {
+        let offset = if to_upper { range.delta } else { 0 };
+        let first = range.upper as i64 + offset as i64;
+        let last = range.last as i64 + offset as i64;
+        let c64 = c as i64;
+        if c64 >= first && c64 <= last && (c64 - first) as u32 % range.step == 0 {
+            let delta = if to_upper { -range.delta } else { range.delta };
+            return (c64 + delta as i64) as u32;
+        }
+    }
+    c
+}
+
+/// The lower case form of `c`, or `c` itself
+pub fn to_lower(c: u32) -> u32 {
+    case_map(c, false)
+}
+
+/// The upper case form of `c`, or `c` itself
+pub fn to_upper(c: u32) -> u32 {
+    case_map(c, true)
+}
+
+/// `c` with its case folded away, for comparing without regard to case:
+/// `fold_case(a) == fold_case(b)` when `a` and `b` differ only in case,
+/// such as `ς`, `σ` and `Σ`
+pub fn fold_case(c: u32) -> u32 {
+    to_lower(to_upper(c))
+}
+
+/// Whether `c` is an upper case letter with a lower case form
+pub fn is_upper(c: u32) -> bool {
+    to_lower(c) != c
+}
+
+/// Whether `c` is a lower case letter with an upper case form
+pub fn is_lower(c: u32) -> bool {
+    to_upper(c) != c
+}
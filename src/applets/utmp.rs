@@ -0,0 +1,486 @@
+//! Login records
+//!
+//! Reads and writes `utmp(5)` files: `/var/run/utmp` holds who is logged
+//! in now, and `/var/log/wtmp` and `/var/log/btmp` are logs of every login,
+//! logout, boot and run level change, and of failed logins. Records have
+//! the 384-byte layout glibc uses on Linux, whatever the C library.
+//!
+//! `File` reads records in either direction. `update` puts a record in
+//! `utmp`, in place of the one it replaces, `append` adds one to a log,
+//! and `record` does both, as `login`, `getty` and `init` should.
+//! `logout` ends a session in both. `rotate` starts a new log, keeping
+//! the old ones numbered.
+//!
+//! Like the account files, the default files are under `$ARMYBOX_ROOT` if
+//! it is set.
+
+use crate::io;
+use crate::sys;
+use super::accounts;
+use super::datetime;
+
+/// The size of a record
+pub const RECORD_SIZE: usize = 384;
+
+// Record types
+pub const EMPTY: i16 = 0;
+/// A run level change: `pid` is the new level plus 256 times the old one
+pub const RUN_LVL: i16 = 1;
+pub const BOOT_TIME: i16 = 2;
+pub const NEW_TIME: i16 = 3;
+pub const OLD_TIME: i16 = 4;
+/// A process started by `init`
+pub const INIT_PROCESS: i16 = 5;
+/// `getty` waiting for a login
+pub const LOGIN_PROCESS: i16 = 6;
+/// A logged-in user
+pub const USER_PROCESS: i16 = 7;
+/// A finished session or process
+pub const DEAD_PROCESS: i16 = 8;
+
+// Field offsets and sizes
+const PID: usize = 4;
+const LINE: (usize, usize) = (8, 32);
+const ID: (usize, usize) = (40, 4);
+const USER: (usize, usize) = (44, 32);
+const HOST: (usize, usize) = (76, 256);
+const EXIT: usize = 332;
+const SESSION: usize = 336;
+const TIME: usize = 340;
+const ADDR: (usize, usize) = (348, 16);
+
+/// One of the record files
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Log {
+    /// Sessions now
+    Utmp,
+    /// Sessions, boots and run levels since the log began
+    Wtmp,
+    /// Failed logins
+    Btmp,
+}
+
+impl Log {
+    fn path(self) -> &'static [u8] {
+        match self {
+            Log::Utmp => b"/var/run/utmp",
+            Log::Wtmp => b"/var/log/wtmp",
+            Log::Btmp => b"/var/log/btmp",
+        }
+    }
+
+    /// The file's path, under `$ARMYBOX_ROOT`, in `buf`
+    pub fn full_path(self, buf: &mut [u8; 512]) -> Option<&[u8]> {
+        accounts::full_path(self.path(), b"", buf)
+    }
+
+    /// Failed logins are only for root to see
+    fn mode(self) -> u32 {
+        if self == Log::Btmp { 0o600 } else { 0o664 }
+    }
+}
+
+/// A record
+#[derive(Clone, Copy)]
+pub struct Entry([u8; RECORD_SIZE]);
+
+/// A NUL-padded field without the padding
+fn field(s: &[u8]) -> &[u8] {
+    &s[..s.iter().position(|&c| c == 0).unwrap_or(s.len())]
+}
+
+impl Entry {
+    /// A record of `kind` for `line`, made now by this process. `line` may
+    /// start with `/dev/`; the ID is its last four bytes, as `login(3)`
+    /// makes it.
+    pub fn new(kind: i16, line: &[u8], user: &[u8]) -> Entry {
+        let mut entry = Entry([0; RECORD_SIZE]);
+        entry.0[..2].copy_from_slice(&kind.to_ne_bytes());
+        let line = line.strip_prefix(b"/dev/").unwrap_or(line);
+        entry.set(LINE, line);
+        entry.set(ID, &line[line.len().saturating_sub(ID.1)..]);
+        entry.set(USER, user);
+        entry.set_pid(io::getpid());
+        entry.set_time(datetime::now());
+        entry
+    }
+
+    /// The record of the system starting, with the kernel release as its
+    /// host
+    pub fn boot() -> Entry {
+        let mut entry = Entry::new(BOOT_TIME, b"~", b"reboot");
+        entry.set_id(b"~~");
+        let mut uts: libc::utsname = unsafe { core::mem::zeroed() };
+        if io::uname(&mut uts) == 0 {
+            entry.set_host(unsafe { io::cstr_to_slice(uts.release.as_ptr() as *const u8) });
+        }
+        entry
+    }
+
+    /// The record of going from run level `old` to `new`, such as `b'3'`;
+    /// `old` is 0 if there was none
+    pub fn run_level(new: u8, old: u8) -> Entry {
+        let mut entry = Entry::new(RUN_LVL, b"~", b"runlevel");
+        entry.set_id(b"~~");
+        entry.set_pid(new as i32 + 256 * old as i32);
+        entry
+    }
+
+    /// The record of the system going down, which `last` ends sessions at
+    pub fn shutdown() -> Entry {
+        let mut entry = Entry::new(RUN_LVL, b"~", b"shutdown");
+        entry.set_id(b"~~");
+        entry.set_pid(b'0' as i32);
+        entry
+    }
+
+    /// Reads a record from its bytes
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Entry {
+        Entry(*bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; RECORD_SIZE] {
+        &self.0
+    }
+
+    fn get(&self, (start, len): (usize, usize)) -> &[u8] {
+        field(&self.0[start..start + len])
+    }
+
+    fn set(&mut self, (start, len): (usize, usize), s: &[u8]) {
+        let n = s.len().min(len);
+        self.0[start..start + len].fill(0);
+        self.0[start..start + n].copy_from_slice(&s[..n]);
+    }
+
+    fn i32_at(&self, at: usize) -> i32 {
+        i32::from_ne_bytes([self.0[at], self.0[at + 1], self.0[at + 2], self.0[at + 3]])
+    }
+
+    pub fn kind(&self) -> i16 {
+        i16::from_ne_bytes([self.0[0], self.0[1]])
+    }
+
+    pub fn pid(&self) -> i32 {
+        self.i32_at(PID)
+    }
+
+    /// The terminal, without `/dev/`
+    pub fn line(&self) -> &[u8] {
+        self.get(LINE)
+    }
+
+    /// What `init` knows the entry by
+    pub fn id(&self) -> &[u8] {
+        self.get(ID)
+    }
+
+    pub fn user(&self) -> &[u8] {
+        self.get(USER)
+    }
+
+    /// Where the user logged in from, or the kernel release for a boot
+    pub fn host(&self) -> &[u8] {
+        self.get(HOST)
+    }
+
+    /// The signal and exit status of a dead process
+    pub fn exit(&self) -> (i16, i16) {
+        let at = EXIT;
+        (i16::from_ne_bytes([self.0[at], self.0[at + 1]]), i16::from_ne_bytes([self.0[at + 2], self.0[at + 3]]))
+    }
+
+    pub fn session(&self) -> i32 {
+        self.i32_at(SESSION)
+    }
+
+    /// Seconds since the epoch. The field is 32 bits, read as unsigned so
+    /// it lasts until 2106.
+    pub fn time(&self) -> i64 {
+        self.i32_at(TIME) as u32 as i64
+    }
+
+    /// The remote address: IPv4 in the first word, or IPv6
+    pub fn addr(&self) -> &[u8] {
+        &self.0[ADDR.0..ADDR.0 + ADDR.1]
+    }
+
+    pub fn set_pid(&mut self, pid: i32) {
+        self.0[PID..PID + 4].copy_from_slice(&pid.to_ne_bytes());
+    }
+
+    pub fn set_id(&mut self, id: &[u8]) {
+        self.set(ID, id);
+    }
+
+    pub fn set_host(&mut self, host: &[u8]) {
+        self.set(HOST, host);
+    }
+
+    pub fn set_exit(&mut self, signal: i16, status: i16) {
+        self.0[EXIT..EXIT + 2].copy_from_slice(&signal.to_ne_bytes());
+        self.0[EXIT + 2..EXIT + 4].copy_from_slice(&status.to_ne_bytes());
+    }
+
+    pub fn set_session(&mut self, session: i32) {
+        self.0[SESSION..SESSION + 4].copy_from_slice(&session.to_ne_bytes());
+    }
+
+    /// Sets the time from seconds and nanoseconds, as `datetime::now`
+    /// gives them
+    pub fn set_time(&mut self, (secs, nsecs): (i64, u32)) {
+        self.0[TIME..TIME + 4].copy_from_slice(&(secs as u32).to_ne_bytes());
+        self.0[TIME + 4..TIME + 8].copy_from_slice(&((nsecs / 1000) as i32).to_ne_bytes());
+    }
+
+    /// Whether `self` and `other` are the same slot in `utmp`: the same
+    /// kind of system record, or the same `init` ID for a process
+    fn same_slot(&self, other: &Entry) -> bool {
+        match (self.kind(), other.kind()) {
+            (a @ (RUN_LVL | BOOT_TIME | NEW_TIME | OLD_TIME), b) => a == b,
+            (INIT_PROCESS..=DEAD_PROCESS, INIT_PROCESS..=DEAD_PROCESS) => self.id() == other.id(),
+            _ => false,
+        }
+    }
+}
+
+/// An open record file. Records are read one at a time, by index.
+pub struct File(i32);
+
+impl File {
+    /// Opens `log` to read
+    pub fn open_log(log: Log) -> Option<File> {
+        let mut path = [0u8; 512];
+        File::open(log.full_path(&mut path)?)
+    }
+
+    /// Opens the file at `path` to read
+    pub fn open(path: &[u8]) -> Option<File> {
+        let fd = io::open(path, libc::O_RDONLY | libc::O_CLOEXEC, 0);
+        if fd < 0 { None } else { Some(File(fd)) }
+    }
+
+    /// How many whole records there are
+    pub fn len(&self) -> usize {
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::fstat(self.0, &mut st) < 0 {
+            return 0;
+        }
+        st.st_size as usize / RECORD_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The record at `index`
+    pub fn get(&self, index: usize) -> Option<Entry> {
+        read_at(self.0, index)
+    }
+
+    /// The records from first to last
+    pub fn iter(&self) -> impl Iterator<Item = Entry> + '_ {
+        (0..self.len()).map_while(|i| self.get(i))
+    }
+
+    /// The records from last to first
+    pub fn iter_rev(&self) -> impl Iterator<Item = Entry> + '_ {
+        (0..self.len()).rev().map_while(|i| self.get(i))
+    }
+
+    /// When the file was last changed
+    pub fn mtime(&self) -> i64 {
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        io::fstat(self.0, &mut st);
+        st.st_mtime
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        io::close(self.0);
+    }
+}
+
+fn read_at(fd: i32, index: usize) -> Option<Entry> {
+    let mut entry = Entry([0; RECORD_SIZE]);
+    if io::lseek(fd, (index * RECORD_SIZE) as i64, libc::SEEK_SET) < 0 {
+        return None;
+    }
+    let mut len = 0;
+    while len < RECORD_SIZE {
+        match io::read(fd, &mut entry.0[len..]) {
+            n if n > 0 => len += n as usize,
+            _ => return None,
+        }
+    }
+    Some(entry)
+}
+
+/// `log` opened to write and locked against other writers, until dropped
+struct Locked(i32);
+
+impl Locked {
+    /// Waits up to 10 seconds for the lock. If `log` was rotated while
+    /// waiting, the new file is opened instead.
+    fn open(log: Log) -> Result<Locked, i32> {
+        let mut path = [0u8; 512];
+        let path = log.full_path(&mut path).ok_or(libc::ENAMETOOLONG)?;
+        for _ in 0..10 {
+            let fd = io::open(path, libc::O_RDWR | libc::O_CREAT | libc::O_CLOEXEC, log.mode());
+            if fd < 0 {
+                return Err(sys::errno());
+            }
+            let mut lock: libc::flock = unsafe { core::mem::zeroed() };
+            lock.l_type = libc::F_WRLCK as i16;
+            lock.l_whence = libc::SEEK_SET as i16;
+            if unsafe { libc::fcntl(fd, libc::F_SETLK, &lock as *const libc::flock) } == 0 {
+                let (mut open, mut named): (libc::stat, libc::stat) = unsafe { core::mem::zeroed() };
+                if io::fstat(fd, &mut open) == 0 && io::stat(path, &mut named) == 0 && open.st_ino == named.st_ino {
+                    return Ok(Locked(fd));
+                }
+                // Rotated: try the new file at once
+                io::close(fd);
+                continue;
+            }
+            io::close(fd);
+            io::sleep(1);
+        }
+        Err(libc::EAGAIN)
+    }
+
+    fn write_at(&self, offset: usize, entry: &Entry) -> Result<(), i32> {
+        if io::lseek(self.0, offset as i64, libc::SEEK_SET) < 0 || io::write_all(self.0, &entry.0) < 0 {
+            return Err(sys::errno());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Locked {
+    fn drop(&mut self) {
+        // Closing the file releases the lock
+        io::close(self.0);
+    }
+}
+
+/// Adds `entry` to the end of `log`. A record cut short by a writer that
+/// died partway is dropped first, so records stay in step.
+pub fn append(log: Log, entry: &Entry) -> Result<(), i32> {
+    let file = Locked::open(log)?;
+    let size = io::lseek(file.0, 0, libc::SEEK_END);
+    if size < 0 {
+        return Err(sys::errno());
+    }
+    let whole = size as usize / RECORD_SIZE * RECORD_SIZE;
+    if whole != size as usize && io::ftruncate(file.0, whole as i64) < 0 {
+        return Err(sys::errno());
+    }
+    file.write_at(whole, entry)
+}
+
+/// Puts `entry` in `utmp` in place of the record for the same slot, or in
+/// an empty one, or at the end
+pub fn update(entry: &Entry) -> Result<(), i32> {
+    let file = Locked::open(Log::Utmp)?;
+    let (mut index, mut free) = (0, None);
+    let slot = loop {
+        match read_at(file.0, index) {
+            Some(old) if old.same_slot(entry) => break index,
+            Some(old) if old.kind() == EMPTY && free.is_none() => free = Some(index),
+            Some(_) => {}
+            None => break free.unwrap_or(index),
+        }
+        index += 1;
+    };
+    file.write_at(slot * RECORD_SIZE, entry)
+}
+
+/// Records `entry` in `utmp` and `wtmp`
+pub fn record(entry: &Entry) -> Result<(), i32> {
+    let updated = update(entry);
+    append(Log::Wtmp, entry)?;
+    updated
+}
+
+/// Ends the session on `line` in `utmp`, and logs its end in `wtmp`.
+/// Returns whether there was one.
+pub fn logout(line: &[u8]) -> Result<bool, i32> {
+    let line = line.strip_prefix(b"/dev/").unwrap_or(line);
+    let file = Locked::open(Log::Utmp)?;
+    let mut index = 0;
+    while let Some(mut entry) = read_at(file.0, index) {
+        if entry.kind() == USER_PROCESS && entry.line() == line {
+            entry.0[..2].copy_from_slice(&DEAD_PROCESS.to_ne_bytes());
+            entry.set(USER, b"");
+            entry.set(HOST, b"");
+            entry.set_time(datetime::now());
+            file.write_at(index * RECORD_SIZE, &entry)?;
+            drop(file);
+            append(Log::Wtmp, &entry)?;
+            return Ok(true);
+        }
+        index += 1;
+    }
+    Ok(false)
+}
+
+/// `log`'s path with `.N` added, in `buf`
+fn numbered(log: Log, n: u32, buf: &mut [u8; 512]) -> Option<&[u8]> {
+    let mut digits = [0u8; 20];
+    let digits = sys::format_u64(n as u64, &mut digits);
+    let mut suffix = [b'.'; 21];
+    suffix[1..1 + digits.len()].copy_from_slice(digits);
+    accounts::full_path(log.path(), &suffix[..1 + digits.len()], buf)
+}
+
+/// Starts `log` afresh, keeping the old logs as `FILE.1` to `FILE.KEEP`,
+/// newest first, and dropping the oldest. The new file has the old one's
+/// owner and mode. `FILE` always exists, and records are never lost: a
+/// writer still waiting on the old file moves to the new one.
+pub fn rotate(log: Log, keep: u32) -> Result<(), i32> {
+    let file = Locked::open(log)?;
+    let (mut path, mut new_path, mut from, mut to) = ([0u8; 512], [0u8; 512], [0u8; 512], [0u8; 512]);
+    let path = log.full_path(&mut path).ok_or(libc::ENAMETOOLONG)?;
+    let new_path = accounts::full_path(log.path(), b"+", &mut new_path).ok_or(libc::ENAMETOOLONG)?;
+
+    for n in (1..keep).rev() {
+        if let (Some(from), Some(to)) = (numbered(log, n, &mut from), numbered(log, n + 1, &mut to)) {
+            io::rename(from, to);
+        }
+    }
+
+    // Make the new file, then swap it in with a single rename
+    io::unlink(new_path);
+    let fd = io::open(new_path, libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC, log.mode());
+    if fd < 0 {
+        return Err(sys::errno());
+    }
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    let mut error = 0;
+    if io::fstat(file.0, &mut st) == 0 && unsafe { libc::fchown(fd, st.st_uid, st.st_gid) != 0 || libc::fchmod(fd, st.st_mode & 0o7777) != 0 } {
+        error = sys::errno();
+    }
+    io::close(fd);
+
+    if error == 0 && keep > 0 {
+        let first = numbered(log, 1, &mut to).ok_or(libc::ENAMETOOLONG)?;
+        io::unlink(first);
+        if io::link(path, first) != 0 {
+            error = sys::errno();
+        }
+    }
+    if error == 0 && io::rename(new_path, path) != 0 {
+        error = sys::errno();
+    }
+    if error != 0 {
+        io::unlink(new_path);
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// The users logged in now, from `utmp`
+pub fn users() -> u64 {
+    File::open_log(Log::Utmp).map_or(0, |file| file.iter().filter(|e| e.kind() == USER_PROCESS && !e.user().is_empty()).count() as u64)
+}
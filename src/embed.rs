@@ -0,0 +1,580 @@
+//! Running applets in-process
+//!
+//! `run_applet` takes a C `argv` and always talks to fds 0, 1 and 2.
+//! `Command` wraps it for Rust callers: arguments are byte slices, each
+//! standard stream can be one of the caller's fds or an in-memory buffer,
+//! environment variables can be overridden, and the exit status comes back
+//! as a value.
+//!
+//! ```ignore
+//! use armybox::embed::{Command, Input, Output};
+//!
+//! let sorted = Command::new(b"sort")
+//!     .arg(b"-r")
+//!     .stdin(Input::Bytes(b"b\na\nc\n"))
+//!     .stdout(Output::Capture)
+//!     .run()?;
+//! assert_eq!(sorted.status, 0);
+//! assert_eq!(sorted.stdout, b"c\nb\na\n");
+//! ```
+//!
+//! The applet still runs on fds 0-2. For the length of `run` they are
+//! pointed at the requested streams with `dup2`, and afterwards they are
+//! put back along with the environment, working directory, umask, signal
+//! dispositions, signal mask and pending `alarm`. The shared stdout buffer
+//! is reset before and after, and a closed output pipe fails the applet's
+//! writes instead of ending the process.
+//!
+//! Applets that run a command in their own place (`env`, `nice`, `nohup`,
+//! `chroot`, `setsid`, `timeout`) would replace the caller or change its
+//! root or session for good, so they run in a child process instead, which
+//! `run` waits for; their streams are redirected the same way.
+//!
+//! The setuid policy (see `suid`) is left out: the ids are the caller's,
+//! so a setuid program that embeds armybox keeps its privileges and
+//! decides itself what to run with them.
+//!
+//! Because those are process-wide, only one applet runs at a time: `run`
+//! fails with `EBUSY` while another is in progress, and other threads using
+//! fds 0-2 meanwhile see the applet's streams. Programs with their own
+//! panic handler and allocator (any `std` program) need the `std` feature.
+
+use alloc::vec::Vec;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::io;
+
+/// Where an applet's standard input comes from
+#[derive(Clone, Copy)]
+pub enum Input<'a> {
+    /// The caller's own standard input
+    Inherit,
+    /// An open file descriptor, which is left open
+    Fd(i32),
+    /// These bytes, then end of file
+    Bytes(&'a [u8]),
+    /// `/dev/null`
+    Null,
+}
+
+/// Where an applet's standard output or standard error goes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// The caller's own stdout or stderr
+    Inherit,
+    /// An open file descriptor, which is left open
+    Fd(i32),
+    /// Collected into `Completed::stdout` or `Completed::stderr`
+    Capture,
+    /// `/dev/null`
+    Null,
+}
+
+/// A finished applet run
+pub struct Completed {
+    /// Exit status, as `run_applet` returns it
+    pub status: i32,
+    /// Captured standard output; empty unless `Output::Capture` was used
+    pub stdout: Vec<u8>,
+    /// Captured standard error; empty unless `Output::Capture` was used
+    pub stderr: Vec<u8>,
+}
+
+/// An applet invocation, built up and then `run`
+pub struct Command<'a> {
+    name: &'a [u8],
+    args: Vec<&'a [u8]>,
+    env: Vec<(&'a [u8], Option<&'a [u8]>)>,
+    stdin: Input<'a>,
+    stdout: Output,
+    stderr: Output,
+}
+
+/// Set while an applet is running
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Applets that exec their command, or change the process for good, and
+/// so are run in a child
+const FORKED: &[&[u8]] = &[b"chroot", b"env", b"nice", b"nohup", b"setsid", b"timeout"];
+
+impl<'a> Command<'a> {
+    /// Invoke applet `name` with no arguments; it is also passed as `argv[0]`
+    pub fn new(name: &'a [u8]) -> Self {
+        Command {
+            name,
+            args: Vec::new(),
+            env: Vec::new(),
+            stdin: Input::Inherit,
+            stdout: Output::Inherit,
+            stderr: Output::Inherit,
+        }
+    }
+
+    /// Add an argument
+    pub fn arg(mut self, arg: &'a [u8]) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Add several arguments
+    pub fn args(mut self, args: &[&'a [u8]]) -> Self {
+        self.args.extend_from_slice(args);
+        self
+    }
+
+    /// Set environment variable `name` to `value` for the run
+    pub fn env(mut self, name: &'a [u8], value: &'a [u8]) -> Self {
+        self.env.push((name, Some(value)));
+        self
+    }
+
+    /// Unset environment variable `name` for the run
+    pub fn env_remove(mut self, name: &'a [u8]) -> Self {
+        self.env.push((name, None));
+        self
+    }
+
+    /// Set where standard input comes from
+    pub fn stdin(mut self, input: Input<'a>) -> Self {
+        self.stdin = input;
+        self
+    }
+
+    /// Set where standard output goes
+    pub fn stdout(mut self, output: Output) -> Self {
+        self.stdout = output;
+        self
+    }
+
+    /// Set where standard error goes
+    pub fn stderr(mut self, output: Output) -> Self {
+        self.stderr = output;
+        self
+    }
+
+    /// Run the applet and wait for it to return.
+    ///
+    /// An unknown or stub applet is not an error here: it gives the same
+    /// status and message as on the command line. `Err` holds an errno when
+    /// the run couldn't be set up: `EBUSY` if another run is in progress,
+    /// `EINVAL` for an argument or variable containing NUL (or `=` in a
+    /// variable name), or whatever opening a stream or forking failed with.
+    pub fn run(&self) -> Result<Completed, i32> {
+        if RUNNING.swap(true, Ordering::Acquire) {
+            return Err(libc::EBUSY);
+        }
+        let result = self.run_locked();
+        RUNNING.store(false, Ordering::Release);
+        result
+    }
+
+    fn run_locked(&self) -> Result<Completed, i32> {
+        let mut strings = Vec::with_capacity(self.args.len() + 1);
+        strings.push(cstring(self.name)?);
+        for arg in &self.args {
+            strings.push(cstring(arg)?);
+        }
+        let mut argv: Vec<*const u8> = strings.iter().map(|s| s.as_ptr()).collect();
+        argv.push(ptr::null());
+
+        let mut env = Vec::with_capacity(self.env.len());
+        for &(name, value) in &self.env {
+            if name.is_empty() || name.contains(&b'=') {
+                return Err(libc::EINVAL);
+            }
+            env.push((cstring(name)?, value.map(cstring).transpose()?));
+        }
+
+        let streams = [
+            Stream::input(self.stdin)?,
+            Stream::output(self.stdout)?,
+            Stream::output(self.stderr)?,
+        ];
+
+        io::stdout().flush();
+        let saved = Saved::take(&env)?;
+        if let Err(e) = saved.redirect(&streams) {
+            saved.restore();
+            return Err(e);
+        }
+        for (name, value) in &env {
+            unsafe {
+                match value {
+                    Some(value) => libc::setenv(name.as_ptr().cast(), value.as_ptr().cast(), 1),
+                    None => libc::unsetenv(name.as_ptr().cast()),
+                };
+            }
+        }
+
+        io::stdout().reset();
+        io::EXIT_ON_EPIPE.store(false, Ordering::Relaxed);
+        // argv holds a pointer to each of strings, then NULL
+        let status = if FORKED.contains(&self.name) {
+            unsafe { run_forked(self.name, strings.len() as i32, argv.as_ptr()) }
+        } else {
+            Ok(unsafe { crate::dispatch(self.name, strings.len() as i32, argv.as_ptr()) })
+        };
+        io::stdout().flush();
+        io::stdout().reset();
+        io::EXIT_ON_EPIPE.store(true, Ordering::Relaxed);
+        saved.restore();
+
+        Ok(Completed {
+            status: status?,
+            stdout: streams[1].captured(),
+            stderr: streams[2].captured(),
+        })
+    }
+}
+
+/// Run an applet in a child and return its exit status as the shell
+/// reports it.
+///
+/// # Safety
+///
+/// As for `run_applet`.
+unsafe fn run_forked(name: &[u8], argc: i32, argv: *const *const u8) -> Result<i32, i32> {
+    let pid = io::fork();
+    if pid < 0 {
+        return Err(crate::sys::errno());
+    }
+    if pid == 0 {
+        // Not io::exit: the caller's cleanup actions aren't the child's
+        let status = unsafe { crate::dispatch(name, argc, argv) };
+        io::stdout().flush();
+        unsafe { libc::_exit(status) };
+    }
+    Ok(crate::applets::exec::wait(name, pid))
+}
+
+/// A NUL-terminated copy of `s`
+fn cstring(s: &[u8]) -> Result<Vec<u8>, i32> {
+    if s.contains(&0) {
+        return Err(libc::EINVAL);
+    }
+    let mut v = Vec::with_capacity(s.len() + 1);
+    v.extend_from_slice(s);
+    v.push(0);
+    Ok(v)
+}
+
+/// The fd one standard stream is redirected to
+struct Stream {
+    /// -1 to leave the stream alone
+    fd: i32,
+    /// Opened here, so closed on drop
+    owned: bool,
+    /// Read back into `Completed` after the run
+    capture: bool,
+}
+
+impl Stream {
+    fn input(input: Input) -> Result<Self, i32> {
+        match input {
+            Input::Inherit => Ok(Stream::borrowed(-1)),
+            Input::Fd(fd) => Ok(Stream::borrowed(fd)),
+            Input::Null => Stream::null(libc::O_RDONLY),
+            Input::Bytes(data) => {
+                let stream = Stream::memory(false)?;
+                if io::write_all(stream.fd, data) < 0
+                    || io::lseek(stream.fd, 0, libc::SEEK_SET) < 0
+                {
+                    return Err(crate::sys::errno());
+                }
+                Ok(stream)
+            }
+        }
+    }
+
+    fn output(output: Output) -> Result<Self, i32> {
+        match output {
+            Output::Inherit => Ok(Stream::borrowed(-1)),
+            Output::Fd(fd) => Ok(Stream::borrowed(fd)),
+            Output::Null => Stream::null(libc::O_WRONLY),
+            Output::Capture => Stream::memory(true),
+        }
+    }
+
+    fn borrowed(fd: i32) -> Self {
+        Stream { fd, owned: false, capture: false }
+    }
+
+    fn null(flags: i32) -> Result<Self, i32> {
+        let fd = io::open(b"/dev/null", flags | libc::O_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(crate::sys::errno());
+        }
+        Ok(Stream { fd, owned: true, capture: false })
+    }
+
+    /// An anonymous in-memory file; unlike a pipe it can't fill up while
+    /// nobody is reading it
+    fn memory(capture: bool) -> Result<Self, i32> {
+        let fd = unsafe { libc::memfd_create(c"armybox".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(crate::sys::errno());
+        }
+        Ok(Stream { fd, owned: true, capture })
+    }
+
+    fn captured(&self) -> Vec<u8> {
+        if !self.capture || io::lseek(self.fd, 0, libc::SEEK_SET) < 0 {
+            return Vec::new();
+        }
+        io::read_all(self.fd)
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if self.owned {
+            io::close(self.fd);
+        }
+    }
+}
+
+/// Process state an applet may change, saved before the run
+struct Saved {
+    /// Copies of fds 0-2, or -1 for one that was closed
+    fds: [i32; 3],
+    cwd: i32,
+    umask: libc::mode_t,
+    alarm: u32,
+    mask: libc::sigset_t,
+    actions: [libc::sigaction; SAVED_SIGNALS],
+    /// Overridden variables and their previous values
+    env: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+/// Signals 1 to 31; the real-time ones are left to the caller
+const SAVED_SIGNALS: usize = 31;
+
+impl Saved {
+    fn take(env: &[(Vec<u8>, Option<Vec<u8>>)]) -> Result<Self, i32> {
+        let cwd = io::open(b".", libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC, 0);
+        if cwd < 0 {
+            return Err(crate::sys::errno());
+        }
+
+        let mut fds = [-1; 3];
+        for (fd, copy) in fds.iter_mut().enumerate() {
+            *copy = unsafe { libc::fcntl(fd as i32, libc::F_DUPFD_CLOEXEC, 3) };
+        }
+
+        let umask = unsafe { libc::umask(0) };
+        unsafe { libc::umask(umask) };
+
+        let mut mask: libc::sigset_t = unsafe { core::mem::zeroed() };
+        unsafe { libc::sigprocmask(libc::SIG_SETMASK, ptr::null(), &mut mask) };
+        let mut actions: [libc::sigaction; SAVED_SIGNALS] = unsafe { core::mem::zeroed() };
+        for (i, action) in actions.iter_mut().enumerate() {
+            unsafe { libc::sigaction(i as i32 + 1, ptr::null(), action) };
+        }
+
+        let env = env
+            .iter()
+            .map(|(name, _)| {
+                let old = io::getenv(&name[..name.len() - 1]).map(|v| v.to_vec());
+                (name.clone(), old)
+            })
+            .collect();
+
+        Ok(Saved {
+            fds,
+            cwd,
+            umask,
+            alarm: unsafe { libc::alarm(0) },
+            mask,
+            actions,
+            env,
+        })
+    }
+
+    /// Point fds 0-2 at `streams`
+    fn redirect(&self, streams: &[Stream; 3]) -> Result<(), i32> {
+        for (target, stream) in streams.iter().enumerate() {
+            if stream.fd < 0 {
+                continue;
+            }
+            // fds 0-2 may already have been replaced; use the saved copies
+            let fd = if stream.owned || stream.fd > 2 {
+                stream.fd
+            } else {
+                self.fds[stream.fd as usize]
+            };
+            if fd < 0 || io::dup2(fd, target as i32) < 0 {
+                return Err(libc::EBADF);
+            }
+        }
+        Ok(())
+    }
+
+    fn restore(self) {
+        for (fd, &copy) in self.fds.iter().enumerate() {
+            if copy >= 0 {
+                io::dup2(copy, fd as i32);
+                io::close(copy);
+            } else {
+                io::close(fd as i32);
+            }
+        }
+
+        for (name, value) in self.env.iter().rev() {
+            unsafe {
+                match value {
+                    Some(value) => {
+                        let value = cstring(value).unwrap_or_default();
+                        libc::setenv(name.as_ptr().cast(), value.as_ptr().cast(), 1)
+                    }
+                    None => libc::unsetenv(name.as_ptr().cast()),
+                };
+            }
+        }
+
+        unsafe {
+            libc::fchdir(self.cwd);
+            libc::close(self.cwd);
+            libc::umask(self.umask);
+            for (i, action) in self.actions.iter().enumerate() {
+                libc::sigaction(i as i32 + 1, action, ptr::null_mut());
+            }
+            libc::sigprocmask(libc::SIG_SETMASK, &self.mask, ptr::null_mut());
+            libc::alarm(self.alarm);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Other tests in this binary may run at once, but only one `run` can
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    /// Device and inode of what each of fds 0-2 is open on
+    fn std_fds() -> [(u64, u64); 3] {
+        core::array::from_fn(|fd| {
+            let mut st: libc::stat = unsafe { core::mem::zeroed() };
+            if unsafe { libc::fstat(fd as i32, &mut st) } < 0 {
+                return (0, 0);
+            }
+            (st.st_dev, st.st_ino)
+        })
+    }
+
+    #[test]
+    fn buffers_stdin_and_stdout() {
+        let _lock = LOCK.lock().unwrap();
+        let sorted = Command::new(b"sort")
+            .arg(b"-r")
+            .stdin(Input::Bytes(b"b\na\nc\n"))
+            .stdout(Output::Capture)
+            .run()
+            .unwrap();
+        assert_eq!(sorted.status, 0);
+        assert_eq!(sorted.stdout, b"c\nb\na\n");
+        assert!(sorted.stderr.is_empty());
+
+        let missing = Command::new(b"cat")
+            .arg(b"/nonexistent/armybox")
+            .stdout(Output::Capture)
+            .stderr(Output::Capture)
+            .run()
+            .unwrap();
+        assert_eq!(missing.status, 1);
+        assert!(missing.stdout.is_empty());
+        assert!(missing.stderr.starts_with(b"cat: /nonexistent/armybox: "));
+    }
+
+    #[test]
+    fn puts_back_fds_and_environment() {
+        let _lock = LOCK.lock().unwrap();
+        let before = std_fds();
+        let cwd = std::env::current_dir().unwrap();
+        let echoed = Command::new(b"echo")
+            .arg(b"hi")
+            .env(b"ARMYBOX_EMBED_TEST", b"1")
+            .stdin(Input::Null)
+            .stdout(Output::Capture)
+            .stderr(Output::Null)
+            .run()
+            .unwrap();
+        assert_eq!(echoed.stdout, b"hi\n");
+        assert_eq!(std_fds(), before);
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+        assert!(std::env::var_os("ARMYBOX_EMBED_TEST").is_none());
+
+        assert_eq!(Command::new(b"echo").arg(b"a\0b").run().err(), Some(libc::EINVAL));
+        assert_eq!(Command::new(b"echo").env(b"A=B", b"c").run().err(), Some(libc::EINVAL));
+        assert_eq!(std_fds(), before);
+    }
+
+    #[test]
+    fn runs_exec_family_applets_in_a_child() {
+        let _lock = LOCK.lock().unwrap();
+        let pid = std::process::id();
+        // Find the commands on PATH, not as applets in this test binary
+        let env = Command::new(b"env")
+            .env(b"ARMYBOX_PREFER_APPLETS", b"0")
+            .args(&[b"ARMYBOX_EMBED_TEST=2", b"sh", b"-c", b"echo \"$ARMYBOX_EMBED_TEST\""])
+            .stdout(Output::Capture)
+            .run()
+            .unwrap();
+        assert_eq!(env.status, 0);
+        assert_eq!(env.stdout, b"2\n");
+        assert_eq!(std::process::id(), pid);
+        assert!(std::env::var_os("ARMYBOX_EMBED_TEST").is_none());
+
+        let priority = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        let nice = Command::new(b"nice")
+            .env(b"ARMYBOX_PREFER_APPLETS", b"0")
+            .args(&[b"-n", b"5", b"sh", b"-c", b"exit 3"])
+            .run()
+            .unwrap();
+        assert_eq!(nice.status, 3);
+        assert_eq!(unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }, priority);
+
+        let session = unsafe { libc::getsid(0) };
+        let setsid = Command::new(b"setsid")
+            .env(b"ARMYBOX_PREFER_APPLETS", b"0")
+            .arg(b"true")
+            .run()
+            .unwrap();
+        assert_eq!(setsid.status, 0);
+        assert_eq!(unsafe { libc::getsid(0) }, session);
+    }
+
+    #[test]
+    fn runs_applets_from_exec_family_applets() {
+        let _lock = LOCK.lock().unwrap();
+        // With prefer-applets, echo runs as an applet in env's child rather
+        // than as this test binary re-executed
+        for (applet, args) in [
+            (b"env".as_slice(), &[b"echo".as_slice(), b"hi"][..]),
+            (b"nice", &[b"-n", b"5", b"echo", b"hi"]),
+            (b"timeout", &[b"5", b"echo", b"hi"]),
+        ] {
+            let run = Command::new(applet).args(args).stdout(Output::Capture).run().unwrap();
+            assert_eq!(run.status, 0);
+            assert_eq!(run.stdout, b"hi\n");
+        }
+    }
+
+    #[test]
+    fn keeps_the_callers_ids() {
+        let _lock = LOCK.lock().unwrap();
+        // Only root can take on another effective gid, as a setgid program
+        // would have. Just the gid: other tests still run as root meanwhile.
+        if unsafe { libc::getuid() } != 0 {
+            return;
+        }
+        assert_eq!(unsafe { libc::setresgid(0, 65534, 0) }, 0);
+        let status = Command::new(b"true").run().map(|done| done.status);
+        let egid = unsafe { libc::getegid() };
+        assert_eq!(unsafe { libc::setresgid(0, 0, 0) }, 0);
+        assert_eq!(status, Ok(0));
+        assert_eq!(egid, 65534);
+    }
+}
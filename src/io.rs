@@ -5,7 +5,9 @@
 #[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
+use core::iter::Peekable;
 use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// Write all bytes to a file descriptor
 pub fn write_all(fd: i32, buf: &[u8]) -> isize {
@@ -83,6 +85,208 @@ pub fn write_signed(fd: i32, n: i64) -> isize {
     }
 }
 
+// ============================================================================
+// Buffered output
+// ============================================================================
+
+/// Size of a `BufWriter` buffer
+pub const BUF_SIZE: usize = 4096;
+
+/// When a `BufWriter` flushes on its own
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Buffering {
+    /// Not decided yet; checked with `isatty` on the first write
+    Unknown,
+    /// Flush after every write that contains a newline
+    Line,
+    /// Flush only when the buffer fills up
+    Full,
+}
+
+/// Buffered writer over a file descriptor.
+///
+/// Writes are collected in a fixed buffer and issued as one `write(2)`
+/// when it fills up, on `flush`, or when the writer is dropped. Output to a
+/// terminal is line buffered.
+///
+/// If the reader has gone away (`EPIPE`, with SIGPIPE ignored), the process
+/// runs its cleanup actions and exits quietly with status 1 instead of
+/// reporting an error for every write; see `sys::Cleanup` for the SIGPIPE
+/// policy. Applets run through `embed` only see the error, since the
+/// process is not theirs.
+pub struct BufWriter {
+    fd: i32,
+    buf: [u8; BUF_SIZE],
+    len: usize,
+    buffering: Buffering,
+    error: bool,
+}
+
+impl BufWriter {
+    /// Create a writer for `fd`
+    pub const fn new(fd: i32) -> Self {
+        BufWriter {
+            fd,
+            buf: [0; BUF_SIZE],
+            len: 0,
+            buffering: Buffering::Unknown,
+            error: false,
+        }
+    }
+
+    /// Append bytes, flushing as needed
+    pub fn write_all(&mut self, data: &[u8]) {
+        if self.buffering == Buffering::Unknown {
+            self.buffering = if isatty(self.fd) { Buffering::Line } else { Buffering::Full };
+        }
+
+        if self.len + data.len() > BUF_SIZE {
+            self.flush();
+            if data.len() >= BUF_SIZE {
+                self.write_through(data);
+                return;
+            }
+        }
+
+        self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+
+        if self.buffering == Buffering::Line && data.contains(&b'\n') {
+            self.flush();
+        }
+    }
+
+    /// Append a string literal
+    pub fn write_str(&mut self, s: &[u8]) {
+        self.write_all(s);
+    }
+
+    /// Append a single byte
+    pub fn write_byte(&mut self, c: u8) {
+        self.write_all(&[c]);
+    }
+
+    /// Append a number in decimal
+    pub fn write_num(&mut self, n: u64) {
+        let mut buf = [0u8; 20];
+        self.write_all(crate::sys::format_u64(n, &mut buf));
+    }
+
+    /// Append a signed number in decimal
+    pub fn write_signed(&mut self, n: i64) {
+        if n < 0 {
+            self.write_str(b"-");
+        }
+        self.write_num(n.unsigned_abs());
+    }
+
+    /// Write out everything buffered so far.
+    ///
+    /// Returns -1 if this or an earlier write failed.
+    pub fn flush(&mut self) -> isize {
+        if self.len > 0 {
+            let ret = write_all(self.fd, &self.buf[..self.len]);
+            self.len = 0;
+            self.check(ret);
+        }
+        if self.error { -1 } else { 0 }
+    }
+
+    fn write_through(&mut self, data: &[u8]) {
+        let ret = write_all(self.fd, data);
+        self.check(ret);
+    }
+
+    fn check(&mut self, ret: isize) {
+        if ret < 0 {
+            if crate::sys::errno() == libc::EPIPE && EXIT_ON_EPIPE.load(Ordering::Relaxed) {
+                crate::sys::run_cleanups();
+                unsafe { libc::_exit(1) };
+            }
+            self.error = true;
+        }
+    }
+}
+
+impl Drop for BufWriter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Whether `EPIPE` ends the process; cleared while `embed` runs an applet
+pub(crate) static EXIT_ON_EPIPE: AtomicBool = AtomicBool::new(true);
+
+/// Holder for the process-wide stdout buffer
+struct StdoutCell(core::cell::UnsafeCell<BufWriter>);
+
+// Applets run on a single thread
+unsafe impl Sync for StdoutCell {}
+
+static STDOUT: StdoutCell = StdoutCell(core::cell::UnsafeCell::new(BufWriter::new(1)));
+
+/// Handle to the buffered standard output shared by all applets.
+///
+/// It is flushed when the applet returns, and by `io::exit`, `io::fork` and
+/// `io::execve`/`io::execvp`. Code that writes to fd 1 directly while this
+/// buffer holds data must call `flush` first.
+#[derive(Clone, Copy)]
+pub struct Stdout;
+
+/// Get the buffered standard output
+pub fn stdout() -> Stdout {
+    Stdout
+}
+
+impl Stdout {
+    /// Runs `f` on the buffer itself, for code that writes to any
+    /// `BufWriter`
+    pub fn with<R>(self, f: impl FnOnce(&mut BufWriter) -> R) -> R {
+        // No reference to the buffer outlives this call
+        f(unsafe { &mut *STDOUT.0.get() })
+    }
+
+    /// Append bytes
+    pub fn write_all(self, data: &[u8]) {
+        self.with(|w| w.write_all(data))
+    }
+
+    /// Append a string literal
+    pub fn write_str(self, s: &[u8]) {
+        self.with(|w| w.write_str(s))
+    }
+
+    /// Append a single byte
+    pub fn write_byte(self, c: u8) {
+        self.with(|w| w.write_byte(c))
+    }
+
+    /// Append a number in decimal
+    pub fn write_num(self, n: u64) {
+        self.with(|w| w.write_num(n))
+    }
+
+    /// Append a signed number in decimal
+    pub fn write_signed(self, n: i64) {
+        self.with(|w| w.write_signed(n))
+    }
+
+    /// Write out everything buffered so far
+    pub fn flush(self) -> isize {
+        self.with(|w| w.flush())
+    }
+
+    /// Drop anything buffered and forget the error and buffering mode, so
+    /// the next applet starts from scratch
+    pub(crate) fn reset(self) {
+        self.with(|w| {
+            w.len = 0;
+            w.buffering = Buffering::Unknown;
+            w.error = false;
+        })
+    }
+}
+
 /// Read from file descriptor into buffer
 pub fn read(fd: i32, buf: &mut [u8]) -> isize {
     unsafe {
@@ -90,6 +294,336 @@ pub fn read(fd: i32, buf: &mut [u8]) -> isize {
     }
 }
 
+// ============================================================================
+// Line input
+// ============================================================================
+
+/// One line from a `LineReader`
+pub struct Line<'a> {
+    /// The line, without its delimiter
+    pub data: &'a [u8],
+    /// Whether the line ended with the delimiter (false only for the last
+    /// line of input, or for the pieces of an over-long line without `alloc`)
+    pub terminated: bool,
+    /// Input ended after this line
+    end: bool,
+}
+
+/// Reads delimiter-terminated lines from a file descriptor.
+///
+/// With `alloc`, lines of any length are returned whole. Without it, a line
+/// longer than `BUF_SIZE` comes back in `BUF_SIZE` pieces with `terminated`
+/// unset on all but the last.
+pub struct LineReader {
+    fd: i32,
+    delim: u8,
+    buf: [u8; BUF_SIZE],
+    pos: usize,
+    end: usize,
+    /// Start of a line that straddles reads
+    #[cfg(feature = "alloc")]
+    partial: Vec<u8>,
+    #[cfg(not(feature = "alloc"))]
+    partial: [u8; BUF_SIZE],
+    #[cfg(not(feature = "alloc"))]
+    partial_len: usize,
+    eof: bool,
+    /// errno of a failed read, or 0
+    error: i32,
+}
+
+impl LineReader {
+    /// Read newline-terminated lines from `fd`
+    pub fn new(fd: i32) -> Self {
+        Self::with_delimiter(fd, b'\n')
+    }
+
+    /// Read lines terminated by `delim` (`0` for `-z`/`-0` modes)
+    pub fn with_delimiter(fd: i32, delim: u8) -> Self {
+        LineReader {
+            fd,
+            delim,
+            buf: [0; BUF_SIZE],
+            pos: 0,
+            end: 0,
+            #[cfg(feature = "alloc")]
+            partial: Vec::new(),
+            #[cfg(not(feature = "alloc"))]
+            partial: [0; BUF_SIZE],
+            #[cfg(not(feature = "alloc"))]
+            partial_len: 0,
+            eof: false,
+            error: 0,
+        }
+    }
+
+    /// Start reading `fd`, dropping anything left over from the previous one
+    pub fn reset(&mut self, fd: i32) {
+        self.fd = fd;
+        self.pos = 0;
+        self.end = 0;
+        self.eof = false;
+        self.error = 0;
+        self.clear_partial();
+    }
+
+    /// Whether a read failed; the lines up to the failure were returned
+    pub fn error(&self) -> bool {
+        self.error != 0
+    }
+
+    /// errno of the failed read, if `error()` is set
+    pub fn errno(&self) -> i32 {
+        self.error
+    }
+
+    /// Whether there are no more lines, reading ahead if needed
+    pub fn at_eof(&mut self) -> bool {
+        if self.pos == self.end && !self.eof {
+            self.fill();
+        }
+        self.pos == self.end && self.eof
+    }
+
+    /// Read the next line, or `None` at end of input
+    pub fn next_line(&mut self) -> Option<Line<'_>> {
+        self.clear_partial();
+        loop {
+            if self.pos < self.end {
+                let found = self.buf[self.pos..self.end].iter().position(|&c| c == self.delim);
+                if let Some(i) = found {
+                    let start = self.pos;
+                    self.pos += i + 1;
+                    if self.partial_len() == 0 {
+                        // Common case: the whole line is in the read buffer
+                        let data = &self.buf[start..start + i];
+                        return Some(Line { data, terminated: true, end: false });
+                    }
+                    let whole = self.stash(start, start + i);
+                    return Some(Line { data: self.partial(), terminated: whole, end: false });
+                }
+                let (start, end) = (self.pos, self.end);
+                self.pos = self.end;
+                if !self.stash(start, end) {
+                    return Some(Line { data: self.partial(), terminated: false, end: false });
+                }
+            }
+
+            if self.eof {
+                if self.partial_len() == 0 {
+                    return None;
+                }
+                return Some(Line { data: self.partial(), terminated: false, end: true });
+            }
+            self.fill();
+        }
+    }
+
+    fn fill(&mut self) {
+        loop {
+            let n = read(self.fd, &mut self.buf);
+            if n > 0 {
+                self.pos = 0;
+                self.end = n as usize;
+                return;
+            }
+            if n < 0 && crate::sys::errno() == libc::EINTR {
+                continue;
+            }
+            if n < 0 {
+                self.error = crate::sys::errno();
+            }
+            self.eof = true;
+            return;
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn clear_partial(&mut self) {
+        self.partial.clear();
+    }
+
+    #[cfg(feature = "alloc")]
+    fn partial_len(&self) -> usize {
+        self.partial.len()
+    }
+
+    #[cfg(feature = "alloc")]
+    fn partial(&self) -> &[u8] {
+        &self.partial
+    }
+
+    /// Append `buf[start..end]` to the partial line; false if it didn't fit
+    #[cfg(feature = "alloc")]
+    fn stash(&mut self, start: usize, end: usize) -> bool {
+        self.partial.extend_from_slice(&self.buf[start..end]);
+        true
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn clear_partial(&mut self) {
+        self.partial_len = 0;
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn partial_len(&self) -> usize {
+        self.partial_len
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn partial(&self) -> &[u8] {
+        &self.partial[..self.partial_len]
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn stash(&mut self, start: usize, end: usize) -> bool {
+        let room = BUF_SIZE - self.partial_len;
+        let take = core::cmp::min(room, end - start);
+        self.partial[self.partial_len..self.partial_len + take]
+            .copy_from_slice(&self.buf[start..start + take]);
+        self.partial_len += take;
+        if take < end - start {
+            // Leave the rest for the next piece
+            self.pos = start + take;
+            return false;
+        }
+        true
+    }
+}
+
+// ============================================================================
+// Input files
+// ============================================================================
+
+/// One input opened by `Inputs`
+#[derive(Clone, Copy)]
+pub struct Input<'a> {
+    /// The operand as given, or `-` for standard input
+    pub path: &'a [u8],
+    pub fd: i32,
+}
+
+/// Opens an applet's file operands in order.
+///
+/// `-` means standard input, and so does an empty operand list. A file that
+/// can't be opened is reported as `applet: path: message` and skipped; the
+/// applet checks `failed()` for its exit status. Each file is closed when the
+/// next one is opened.
+pub struct Inputs<'a, I: Iterator<Item = &'a [u8]>> {
+    applet: &'a [u8],
+    paths: Peekable<I>,
+    fd: i32,
+    started: bool,
+    failed: bool,
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> Inputs<'a, I> {
+    pub fn new(applet: &'a [u8], paths: I) -> Self {
+        Inputs { applet, paths: paths.peekable(), fd: -1, started: false, failed: false }
+    }
+
+    /// Whether any operand couldn't be opened or read
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Whether operands remain after the current one
+    pub fn has_more(&mut self) -> bool {
+        self.paths.peek().is_some()
+    }
+
+    /// Report `path` as `applet: path: message` and mark the run failed
+    pub fn fail(&mut self, path: &[u8], errno: i32) {
+        crate::sys::error_errno(self.applet, path, errno);
+        self.failed = true;
+    }
+
+    /// Read all inputs as one stream of `delim`-terminated lines
+    pub fn lines(self, delim: u8) -> InputLines<'a, I> {
+        InputLines { inputs: self, reader: LineReader::with_delimiter(-1, delim), path: b"-", open: false }
+    }
+
+    fn close_current(&mut self) {
+        if self.fd > 0 {
+            close(self.fd);
+        }
+        self.fd = -1;
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> Iterator for Inputs<'a, I> {
+    type Item = Input<'a>;
+
+    fn next(&mut self) -> Option<Input<'a>> {
+        self.close_current();
+        if !self.started {
+            self.started = true;
+            if self.paths.peek().is_none() {
+                self.fd = 0;
+                return Some(Input { path: b"-", fd: 0 });
+            }
+        }
+
+        while let Some(path) = self.paths.next() {
+            if path == b"-" {
+                self.fd = 0;
+                return Some(Input { path, fd: 0 });
+            }
+            let fd = open(path, libc::O_RDONLY, 0);
+            if fd >= 0 {
+                self.fd = fd;
+                return Some(Input { path, fd });
+            }
+            let e = crate::sys::errno();
+            self.fail(path, e);
+        }
+        None
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> Drop for Inputs<'a, I> {
+    fn drop(&mut self) {
+        self.close_current();
+    }
+}
+
+/// Lines of every input in turn, as if the files were concatenated.
+///
+/// A file whose last line lacks the delimiter still ends that line when
+/// another operand follows, so only the very last line can come back
+/// unterminated. Read errors are reported like open errors.
+pub struct InputLines<'a, I: Iterator<Item = &'a [u8]>> {
+    inputs: Inputs<'a, I>,
+    reader: LineReader,
+    path: &'a [u8],
+    open: bool,
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> InputLines<'a, I> {
+    /// Read the next line, or `None` once every input is exhausted
+    pub fn next_line(&mut self) -> Option<Line<'_>> {
+        while !self.open || self.reader.at_eof() {
+            if self.open && self.reader.error() {
+                let e = self.reader.errno();
+                self.inputs.fail(self.path, e);
+            }
+            let input = self.inputs.next()?;
+            self.reader.reset(input.fd);
+            self.path = input.path;
+            self.open = true;
+        }
+
+        let more = self.inputs.has_more();
+        let line = self.reader.next_line()?;
+        Some(Line { terminated: line.terminated || (line.end && more), ..line })
+    }
+
+    /// Whether any input couldn't be opened or read
+    pub fn failed(&self) -> bool {
+        self.inputs.failed()
+    }
+}
+
 /// Read entire file into Vec
 #[cfg(feature = "alloc")]
 pub fn read_all(fd: i32) -> Vec<u8> {
@@ -154,6 +688,18 @@ pub fn fstat(fd: i32, buf: &mut libc::stat) -> i32 {
     unsafe { libc::fstat(fd, buf) }
 }
 
+/// Get the status of the filesystem holding a path
+pub fn statfs(path: &[u8], buf: &mut libc::statfs) -> i32 {
+    let mut path_buf = [0u8; 4096];
+    if path.len() >= path_buf.len() {
+        return -1;
+    }
+    path_buf[..path.len()].copy_from_slice(path);
+    path_buf[path.len()] = 0;
+
+    unsafe { libc::statfs(path_buf.as_ptr() as *const i8, buf) }
+}
+
 /// Create a directory
 pub fn mkdir(path: &[u8], mode: u32) -> i32 {
     let mut path_buf = [0u8; 4096];
@@ -480,11 +1026,24 @@ pub fn execve(path: &[u8], argv: *const *const i8, envp: *const *const i8) -> i3
     path_buf[..path.len()].copy_from_slice(path);
     path_buf[path.len()] = 0;
 
+    stdout().flush();
     unsafe { libc::execve(path_buf.as_ptr() as *const i8, argv, envp) }
 }
 
-/// Fork process
+/// Execute program, searching PATH
+///
+/// # Safety
+///
+/// `file` must be a NUL-terminated string and `argv` a NULL-terminated
+/// array of them, as for `execvp(3)`.
+pub unsafe fn execvp(file: *const i8, argv: *const *const i8) -> i32 {
+    stdout().flush();
+    unsafe { libc::execvp(file, argv) }
+}
+
+/// Fork process, flushing stdout first so the child doesn't repeat it
 pub fn fork() -> i32 {
+    stdout().flush();
     unsafe { libc::fork() }
 }
 
@@ -503,8 +1062,10 @@ pub fn uname(buf: &mut libc::utsname) -> i32 {
     unsafe { libc::uname(buf) }
 }
 
-/// Exit process
+/// Exit process, flushing stdout and running cleanup actions first
 pub fn exit(code: i32) -> ! {
+    stdout().flush();
+    crate::sys::run_cleanups();
     unsafe { libc::_exit(code); }
 }
 
@@ -8,9 +8,13 @@
 //! - **Truly `#[no_std]`**: No standard library dependency
 //! - **Tiny binary**: ~74KB release, ~33KB with UPX compression
 //! - **Embedded-ready**: Works on systems without full std support
+//! - **Callable in-process**: `embed::Command` runs applets on buffers or
+//!   caller-chosen fds from Rust
 
 // Use no_std except during tests (which require std for test harness)
 #![cfg_attr(not(test), no_std)]
+// The applet table macro recurses once per row
+#![recursion_limit = "512"]
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
@@ -18,6 +22,11 @@ extern crate alloc;
 pub mod io;
 pub mod applets;
 pub mod sys;
+pub mod suid;
+#[cfg(feature = "alloc")]
+pub mod embed;
+#[cfg(feature = "nolibc")]
+mod nolibc;
 
 /// Applet function type
 pub type AppletFn = fn(i32, *const *const u8) -> i32;
@@ -27,19 +36,79 @@ pub fn is_applet(name: &[u8]) -> bool {
     applets::find_applet(name).is_some()
 }
 
+/// Applets for which `--help` is an ordinary argument (POSIX says
+/// `test --help` is no different from `test --foo`)
+const NO_HELP_OPTION: &[&[u8]] = &[b":", b"[", b"echo", b"false", b"test", b"true"];
+
 /// Run an applet by name
-pub fn run_applet(name: &[u8], argc: i32, argv: *const *const u8) -> i32 {
+///
+/// In a setuid or setgid binary, privileges are first kept or dropped as
+/// `/etc/armybox.conf` says (see `suid`); an applet it forbids returns
+/// `applets::EXIT_CANNOT_EXECUTE`. `APPLET --help` prints the applet's usage
+/// instead of running it. Stub applets are not run; they report "not
+/// implemented" and return `applets::EXIT_NOT_IMPLEMENTED`.
+///
+/// `run_applet_args` is the safe form for callers that don't already hold a
+/// C `argv`.
+///
+/// # Safety
+///
+/// `argv` must point to `argc` pointers to NUL-terminated strings followed
+/// by a NULL pointer. The array and the strings must stay valid and
+/// unchanged until this returns.
+pub unsafe fn run_applet(name: &[u8], argc: i32, argv: *const *const u8) -> i32 {
+    if !suid::enforce(name) {
+        return applets::EXIT_CANNOT_EXECUTE;
+    }
+    unsafe { dispatch(name, argc, argv) }
+}
+
+/// `run_applet` without the setuid policy, for callers whose ids aren't
+/// armybox's to change
+///
+/// # Safety
+///
+/// As for `run_applet`.
+pub(crate) unsafe fn dispatch(name: &[u8], argc: i32, argv: *const *const u8) -> i32 {
+    let help = argc == 2
+        && unsafe { applets::get_arg(argv, 1) } == Some(b"--help".as_slice())
+        && !NO_HELP_OPTION.contains(&name);
+    if help && is_applet(name) && applets::usage::show(name) {
+        io::stdout().flush();
+        return 0;
+    }
+
     match applets::find_applet(name) {
-        Some(f) => f(argc, argv),
+        Some(applet) if applet.status == applets::Status::Stub => {
+            sys::error(applet.name, b"not implemented");
+            applets::EXIT_NOT_IMPLEMENTED
+        }
+        Some(applet) => {
+            let status = (applet.main)(argc, argv);
+            io::stdout().flush();
+            status
+        }
         None => {
-            io::write_str(2, b"armybox: applet not found: ");
-            io::write_all(2, name);
-            io::write_str(2, b"\n");
-            127
+            sys::error(name, b"applet not found");
+            applets::EXIT_NOT_FOUND
         }
     }
 }
 
+/// Run an applet by name with `args` as its argv
+///
+/// `args[0]` is the name the applet sees itself run as. Otherwise as for
+/// `run_applet`.
+#[cfg(feature = "alloc")]
+pub fn run_applet_args(name: &[u8], args: &[&core::ffi::CStr]) -> i32 {
+    let mut argv: alloc::vec::Vec<*const u8> = args.iter().map(|arg| arg.as_ptr().cast()).collect();
+    argv.push(core::ptr::null());
+    let argc = i32::try_from(args.len()).unwrap_or(i32::MAX);
+    // SAFETY: argv holds argc pointers to strings borrowed for this call,
+    // then NULL
+    unsafe { run_applet(name, argc, argv.as_ptr()) }
+}
+
 /// Get applet count
 pub const fn applet_count() -> usize {
     applets::APPLET_COUNT
@@ -53,33 +122,25 @@ pub const fn applet_count() -> usize {
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     io::write_str(2, b"armybox: panic!\n");
+    sys::run_cleanups();
     unsafe { libc::_exit(1); }
 }
 
 // ============================================================================
-// Global allocator using libc malloc (not used in test builds)
+// Global allocator (not used in test builds)
 // ============================================================================
 
 #[cfg(all(not(test), not(feature = "std"), feature = "alloc"))]
-mod allocator {
-    use core::alloc::{GlobalAlloc, Layout};
+mod allocator;
 
-    pub struct LibcAllocator;
-
-    unsafe impl GlobalAlloc for LibcAllocator {
-        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-            unsafe { libc::malloc(layout.size()) as *mut u8 }
-        }
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
 
-        unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-            unsafe { libc::free(ptr as *mut libc::c_void) }
-        }
-
-        unsafe fn realloc(&self, ptr: *mut u8, _layout: Layout, new_size: usize) -> *mut u8 {
-            unsafe { libc::realloc(ptr as *mut libc::c_void, new_size) as *mut u8 }
-        }
+    #[test]
+    fn run_applet_args_passes_arguments() {
+        assert_eq!(run_applet_args(b"test", &[c"test", c"a", c"=", c"a"]), 0);
+        assert_eq!(run_applet_args(b"test", &[c"test", c"a", c"=", c"b"]), 1);
+        assert_eq!(run_applet_args(b"false", &[c"false"]), 1);
     }
-
-    #[global_allocator]
-    static ALLOCATOR: LibcAllocator = LibcAllocator;
 }
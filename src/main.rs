@@ -7,7 +7,8 @@
 
 extern crate armybox;
 
-use armybox::{io, applets, run_applet};
+use core::sync::atomic::Ordering;
+use armybox::{io, applets, suid, sys, run_applet};
 
 /// Main entry point
 #[unsafe(no_mangle)]
@@ -15,6 +16,7 @@ pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
     if argc < 1 || argv.is_null() {
         return 1;
     }
+    applets::exec::IS_ARMYBOX.store(true, Ordering::Relaxed);
 
     // Get the program name (argv[0])
     let prog_name = unsafe { applets::get_arg(argv, 0) };
@@ -29,16 +31,29 @@ pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
 
     // Check if invoked as "armybox" itself
     if io::bytes_eq(applet_name, b"armybox") {
+        // Only applets may keep setuid privileges, never --install and co.
+        let arg1 = unsafe { applets::get_arg(argv, 1) };
+        if !arg1.is_some_and(|a| !a.starts_with(b"-")) {
+            suid::drop_privileges();
+        }
+
         // If there's an argument, use it as the applet name
         if argc > 1 {
             if let Some(arg1) = unsafe { applets::get_arg(argv, 1) } {
                 // Check for special flags
                 if io::bytes_eq(arg1, b"--list") || io::bytes_eq(arg1, b"-l") {
-                    applets::list_applets();
+                    let arg2 = unsafe { applets::get_arg(argv, 2) };
+                    applets::list_applets(arg2 == Some(b"--status".as_slice()));
+                    io::stdout().flush();
                     return 0;
                 }
 
                 if io::bytes_eq(arg1, b"--help") || io::bytes_eq(arg1, b"-h") {
+                    if let Some(name) = unsafe { applets::get_arg(argv, 2) } {
+                        let status = applets::usage::help(name);
+                        io::stdout().flush();
+                        return status;
+                    }
                     print_help();
                     return 0;
                 }
@@ -49,18 +64,12 @@ pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
                 }
 
                 if io::bytes_eq(arg1, b"--install") {
-                    if argc > 2 {
-                        if let Some(dir) = unsafe { applets::get_arg(argv, 2) } {
-                            return install_symlinks(dir);
-                        }
-                    }
-                    io::write_str(2, b"armybox: --install requires a directory\n");
-                    return 1;
+                    return install(argc - 2, unsafe { argv.offset(2) });
                 }
 
                 // Run the specified applet
                 // Shift argv: argv[1] becomes argv[0] for the applet
-                return run_applet(arg1, argc - 1, unsafe { argv.offset(1) });
+                return unsafe { run_applet(arg1, argc - 1, argv.offset(1)) };
             }
         }
 
@@ -70,7 +79,7 @@ pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
     }
 
     // Invoked via symlink - run the corresponding applet
-    run_applet(applet_name, argc, argv)
+    unsafe { run_applet(applet_name, argc, argv) }
 }
 
 /// Get basename from path
@@ -86,8 +95,9 @@ fn basename(path: &[u8]) -> &[u8] {
 fn print_usage() {
     io::write_str(1, b"armybox - A tiny Unix utility collection\n\n");
     io::write_str(1, b"Usage: armybox [APPLET] [ARGS...]\n");
-    io::write_str(1, b"       armybox --list\n");
-    io::write_str(1, b"       armybox --install DIR\n\n");
+    io::write_str(1, b"       armybox --help [APPLET]\n");
+    io::write_str(1, b"       armybox --list [--status]\n");
+    io::write_str(1, b"       armybox --install [-s] [-f] [--stubs] [--root DESTDIR | DIR]\n\n");
     io::write_str(1, b"Run 'armybox --list' to see available applets.\n");
 }
 
@@ -96,9 +106,17 @@ fn print_help() {
     print_usage();
     io::write_str(1, b"\nOptions:\n");
     io::write_str(1, b"  -l, --list      List all available applets\n");
+    io::write_str(1, b"      --status    Show whether each is complete, partial or a stub\n");
     io::write_str(1, b"  -h, --help      Show this help message\n");
+    io::write_str(1, b"      APPLET      Show APPLET's usage instead\n");
     io::write_str(1, b"  -V, --version   Show version information\n");
-    io::write_str(1, b"  --install DIR   Create symlinks in DIR for all applets\n");
+    io::write_str(1, b"  --install       Link every implemented applet into /bin, /sbin, /usr/bin,\n");
+    io::write_str(1, b"                  /usr/sbin\n");
+    io::write_str(1, b"      -s          Create symlinks instead of hard links\n");
+    io::write_str(1, b"      -f          Replace existing files\n");
+    io::write_str(1, b"      --stubs     Link stub applets too\n");
+    io::write_str(1, b"      --root DIR  Install the layout under DIR (DESTDIR)\n");
+    io::write_str(1, b"      DIR         Install all links directly into DIR\n");
 }
 
 /// Print version
@@ -108,84 +126,235 @@ fn print_version() {
     io::write_str(1, b"Copyright (c) 2025 Pegasus Heavy Industries\n");
 }
 
-/// Install symlinks for all applets
-fn install_symlinks(dir: &[u8]) -> i32 {
-    // Get path to ourselves
-    let self_path = b"/proc/self/exe";
-
-    let mut target = [0u8; 4096];
-    let mut target_buf = [0u8; 4096];
-    target_buf[..self_path.len()].copy_from_slice(self_path);
-    target_buf[self_path.len()] = 0;
-
-    let n = unsafe {
-        libc::readlink(
-            target_buf.as_ptr() as *const i8,
-            target.as_mut_ptr() as *mut i8,
-            target.len() - 1
-        )
-    };
+/// Install links for every registered applet.
+///
+/// `armybox --install [-s] [-f] [--stubs] [--root DESTDIR | DIR]`
+///
+/// Without `DIR`, each applet is linked into its registry directory (`/bin`,
+/// `/sbin`, `/usr/bin` or `/usr/sbin`) under `DESTDIR` (default `/`). With
+/// `DIR`, every link goes directly into `DIR`. Links are hard links unless
+/// `-s` is given; existing files are skipped unless `-f` is given. Stub
+/// applets, which only exit with `EXIT_NOT_IMPLEMENTED`, are left out unless
+/// `--stubs` is given, so `-f` can't replace a working `/bin/sh` or
+/// `/sbin/init` with one. Returns 1 if any link could not be created.
+fn install(argc: i32, argv: *const *const u8) -> i32 {
+    let mut symbolic = false;
+    let mut force = false;
+    let mut stubs = false;
+    let mut root: &[u8] = b"";
+    let mut flat_dir: Option<&[u8]> = None;
+
+    let mut i = 0;
+    while i < argc {
+        let arg = match unsafe { applets::get_arg(argv, i) } {
+            Some(a) => a,
+            None => break,
+        };
+        if arg == b"-s" {
+            symbolic = true;
+        } else if arg == b"-f" {
+            force = true;
+        } else if arg == b"--stubs" {
+            stubs = true;
+        } else if arg == b"--root" {
+            i += 1;
+            match unsafe { applets::get_arg(argv, i) } {
+                Some(r) => root = r,
+                None => {
+                    sys::error(b"armybox", b"--root requires a directory");
+                    return 1;
+                }
+            }
+        } else if arg.len() > 1 && arg[0] == b'-' {
+            io::write_str(2, b"armybox: --install: invalid option '");
+            io::write_all(2, arg);
+            io::write_str(2, b"'\n");
+            return 1;
+        } else {
+            flat_dir = Some(arg);
+        }
+        i += 1;
+    }
 
+    // Strip trailing slashes so joined paths stay clean ("/" becomes "")
+    while root.last() == Some(&b'/') {
+        root = &root[..root.len() - 1];
+    }
+
+    let mut exe = [0u8; 4096];
+    let n = io::readlink(b"/proc/self/exe", &mut exe[..4095]);
     if n < 0 {
-        io::write_str(2, b"armybox: cannot read /proc/self/exe\n");
+        sys::perror(b"armybox", b"/proc/self/exe");
         return 1;
     }
+    let exe = &exe[..n as usize];
+
+    // Symlinks must resolve inside the installed image, so drop DESTDIR from
+    // the binary path when the binary lives under it.
+    let link_target = if !root.is_empty() && exe.len() > root.len()
+        && exe.starts_with(root) && exe[root.len()] == b'/'
+    {
+        &exe[root.len()..]
+    } else {
+        exe
+    };
+
+    let mut installed = 0u64;
+    let mut skipped = 0u64;
+    let mut failed = 0u64;
+
+    // Create each target directory once; applets whose directory could not
+    // be created count as failures without repeating the error.
+    let mut dir_ok = [false; 5];
+    for (slot, dir) in [
+        applets::InstallDir::Root,
+        applets::InstallDir::Bin,
+        applets::InstallDir::Sbin,
+        applets::InstallDir::UsrBin,
+        applets::InstallDir::UsrSbin,
+    ].into_iter().enumerate() {
+        if flat_dir.is_some() && slot > 0 {
+            dir_ok[slot] = dir_ok[0];
+            continue;
+        }
+        let path = target_dir(flat_dir, root, dir);
+        dir_ok[slot] = mkdir_p(path.as_bytes());
+        if !dir_ok[slot] {
+            sys::perror(b"armybox", path.as_bytes());
+        }
+    }
 
-    let target_path = &target[..n as usize];
+    for applet in applets::APPLETS {
+        if applet.status == applets::Status::Stub && !stubs {
+            continue;
+        }
+        if !dir_ok[applet.dir as usize] {
+            failed += 1;
+            continue;
+        }
+        let mut path = target_dir(flat_dir, root, applet.dir);
+        path.push_component(applet.name);
+        if path.overflow {
+            failed += 1;
+            sys::error_at(b"armybox", applet.name, b"path too long");
+            continue;
+        }
+        let link = path.as_bytes();
+
+        // Never clobber the binary we are linking to
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        let mut self_st: libc::stat = unsafe { core::mem::zeroed() };
+        if io::lstat(link, &mut st) == 0 {
+            if io::stat(exe, &mut self_st) == 0
+                && st.st_dev == self_st.st_dev && st.st_ino == self_st.st_ino
+            {
+                skipped += 1;
+                continue;
+            }
+            if !force {
+                skipped += 1;
+                continue;
+            }
+            if io::unlink(link) < 0 {
+                failed += 1;
+                sys::perror(b"armybox", link);
+                continue;
+            }
+        }
 
-    io::write_str(1, b"Installing symlinks to ");
-    io::write_all(1, dir);
-    io::write_str(1, b"...\n");
+        let ret = if symbolic {
+            io::symlink(link_target, link)
+        } else {
+            io::link(exe, link)
+        };
+        if ret == 0 {
+            installed += 1;
+        } else {
+            failed += 1;
+            sys::perror(b"armybox", link);
+        }
+    }
 
-    let mut count = 0;
+    io::write_str(1, b"Installed ");
+    io::write_num(1, installed);
+    io::write_str(1, if symbolic { b" symlinks" } else { b" hard links" });
+    if skipped > 0 {
+        io::write_str(1, b", skipped ");
+        io::write_num(1, skipped);
+        io::write_str(1, b" existing");
+    }
+    if failed > 0 {
+        io::write_str(1, b", ");
+        io::write_num(1, failed);
+        io::write_str(1, b" failed");
+    }
+    io::write_str(1, b".\n");
 
-    // Get list via list_applets - for now just create basic symlinks
-    let names: &[&[u8]] = &[
-        b"true", b"false", b"echo", b"cat", b"ls", b"cp", b"mv", b"rm",
-        b"mkdir", b"rmdir", b"pwd", b"touch", b"ln", b"chmod", b"head", b"tail",
-        b"wc", b"grep", b"sed", b"awk", b"sort", b"uniq", b"cut", b"tr",
-        b"date", b"uname", b"hostname", b"whoami", b"id", b"ps", b"kill", b"sleep",
-    ];
-    for &name in names {
-        // Build link path: dir/name
-        let mut link_path = [0u8; 4096];
-        let mut len = 0;
+    if failed > 0 { 1 } else { 0 }
+}
 
-        for &c in dir {
-            if len < link_path.len() - 1 {
-                link_path[len] = c;
-                len += 1;
-            }
+/// Directory that links for applets in `dir` are created in
+fn target_dir(flat_dir: Option<&[u8]>, root: &[u8], dir: applets::InstallDir) -> PathBuf {
+    let mut path = PathBuf::new();
+    match flat_dir {
+        Some(d) => path.push(d),
+        None => {
+            path.push(root);
+            path.push_component(dir.path());
         }
+    }
+    if path.len == 0 {
+        path.push(b"/");
+    }
+    path
+}
 
-        if len > 0 && link_path[len - 1] != b'/' {
-            link_path[len] = b'/';
-            len += 1;
+/// Create a directory and any missing parents
+fn mkdir_p(path: &[u8]) -> bool {
+    for i in 1..path.len() {
+        if path[i] == b'/' {
+            io::mkdir(&path[..i], 0o755);
         }
+    }
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    io::mkdir(path, 0o755) == 0
+        || (io::stat(path, &mut st) == 0 && (st.st_mode & libc::S_IFMT) == libc::S_IFDIR)
+}
 
-        for &c in name {
-            if len < link_path.len() - 1 {
-                link_path[len] = c;
-                len += 1;
-            }
-        }
+/// Fixed-size path builder for the install loop
+struct PathBuf {
+    buf: [u8; 4096],
+    len: usize,
+    overflow: bool,
+}
 
-        // Remove existing link
-        io::unlink(&link_path[..len]);
+impl PathBuf {
+    fn new() -> Self {
+        PathBuf { buf: [0; 4096], len: 0, overflow: false }
+    }
 
-        // Create symlink
-        if io::symlink(target_path, &link_path[..len]) == 0 {
-            count += 1;
-        } else {
-            io::write_str(2, b"armybox: failed to create ");
-            io::write_all(2, &link_path[..len]);
-            io::write_str(2, b"\n");
+    fn push(&mut self, s: &[u8]) {
+        // Leave room for the NUL terminator the io helpers add
+        if self.len + s.len() >= self.buf.len() {
+            self.overflow = true;
+            return;
         }
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s);
+        self.len += s.len();
     }
 
-    io::write_str(1, b"Installed ");
-    io::write_num(1, count as u64);
-    io::write_str(1, b" symlinks.\n");
+    /// Append `s` as a new path component
+    fn push_component(&mut self, s: &[u8]) {
+        if s.is_empty() {
+            return;
+        }
+        if self.len == 0 || self.buf[self.len - 1] != b'/' {
+            self.push(b"/");
+        }
+        self.push(s);
+    }
 
-    0
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
 }
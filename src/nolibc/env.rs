@@ -0,0 +1,117 @@
+//! The environment
+//!
+//! `environ` starts out pointing at the array the kernel passed in. The
+//! first `setenv` or `unsetenv` copies it into an owned vector, which later
+//! changes edit; replaced strings are leaked, as C callers may still hold
+//! them.
+
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ptr;
+use libc::{c_char, c_int};
+
+use super::start::environ;
+
+struct Owned(UnsafeCell<Option<Vec<*const c_char>>>);
+
+// Applets run on a single thread
+unsafe impl Sync for Owned {}
+
+static OWNED: Owned = Owned(UnsafeCell::new(None));
+
+/// Bytes of a NUL-terminated string
+unsafe fn bytes<'a>(s: *const c_char) -> &'a [u8] {
+    unsafe { core::slice::from_raw_parts(s as *const u8, super::mem::strlen(s)) }
+}
+
+/// The value part of `entry` if it is `NAME=...`
+fn value_of<'a>(entry: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    entry.strip_prefix(name)?.strip_prefix(b"=")
+}
+
+/// The owned copy of the environment, made on first use
+fn owned() -> &'static mut Vec<*const c_char> {
+    let owned = unsafe { &mut *OWNED.0.get() };
+    owned.get_or_insert_with(|| {
+        let mut vars = Vec::new();
+        let mut p = unsafe { environ };
+        while !p.is_null() && unsafe { !(*p).is_null() } {
+            vars.push(unsafe { *p });
+            p = unsafe { p.add(1) };
+        }
+        vars.push(ptr::null());
+        vars
+    })
+}
+
+/// Whether `name` can be set: non-empty, with no `=`
+unsafe fn valid_name<'a>(name: *const c_char) -> Option<&'a [u8]> {
+    if name.is_null() {
+        return None;
+    }
+    let name = unsafe { bytes(name) };
+    if name.is_empty() || name.contains(&b'=') { None } else { Some(name) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getenv(name: *const c_char) -> *mut c_char {
+    let name = unsafe { bytes(name) };
+    let mut p = unsafe { environ };
+    while !p.is_null() && unsafe { !(*p).is_null() } {
+        let entry = unsafe { bytes(*p) };
+        if value_of(entry, name).is_some() {
+            return unsafe { (*p).add(name.len() + 1) as *mut c_char };
+        }
+        p = unsafe { p.add(1) };
+    }
+    ptr::null_mut()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setenv(name: *const c_char, value: *const c_char, overwrite: c_int) -> c_int {
+    let Some(name) = (unsafe { valid_name(name) }) else {
+        super::errno::set(libc::EINVAL);
+        return -1;
+    };
+    let existing = unsafe { getenv(name.as_ptr() as *const c_char) };
+    if !existing.is_null() && overwrite == 0 {
+        return 0;
+    }
+
+    let value = unsafe { bytes(value) };
+    let mut entry = Vec::with_capacity(name.len() + value.len() + 2);
+    entry.extend_from_slice(name);
+    entry.push(b'=');
+    entry.extend_from_slice(value);
+    entry.push(0);
+    let entry = entry.leak().as_ptr() as *const c_char;
+
+    let vars = owned();
+    match vars.iter().position(|&v| !v.is_null() && value_of(unsafe { bytes(v) }, name).is_some()) {
+        Some(i) => vars[i] = entry,
+        None => vars.insert(vars.len() - 1, entry),
+    }
+    unsafe { environ = vars.as_ptr() };
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unsetenv(name: *const c_char) -> c_int {
+    let Some(name) = (unsafe { valid_name(name) }) else {
+        super::errno::set(libc::EINVAL);
+        return -1;
+    };
+    let vars = owned();
+    vars.retain(|&v| v.is_null() || value_of(unsafe { bytes(v) }, name).is_none());
+    unsafe { environ = vars.as_ptr() };
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn clearenv() -> c_int {
+    let vars = owned();
+    vars.clear();
+    vars.push(ptr::null());
+    unsafe { environ = vars.as_ptr() };
+    0
+}
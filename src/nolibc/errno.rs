@@ -0,0 +1,21 @@
+//! `errno`, which lives here instead of in libc's thread-local storage
+
+use core::cell::UnsafeCell;
+
+struct Errno(UnsafeCell<i32>);
+
+// Applets run on a single thread
+unsafe impl Sync for Errno {}
+
+static ERRNO: Errno = Errno(UnsafeCell::new(0));
+
+/// Set `errno`
+pub fn set(e: i32) {
+    unsafe { *ERRNO.0.get() = e };
+}
+
+/// What `libc::__errno_location` resolves to; `sys::errno` reads through it
+#[unsafe(no_mangle)]
+pub extern "C" fn __errno_location() -> *mut i32 {
+    ERRNO.0.get()
+}
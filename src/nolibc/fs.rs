@@ -0,0 +1,335 @@
+//! Files, descriptors and directories
+//!
+//! Path-based calls go through the `*at` system calls, which are the only
+//! ones aarch64 has.
+
+use alloc::boxed::Box;
+use libc::{c_char, c_int, c_uint, c_void, dev_t, gid_t, mode_t, off_t, size_t, ssize_t, uid_t};
+
+use libc::AT_FDCWD;
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn open(path: *const c_char, flags: c_int, mode: mode_t) -> c_int {
+    syscall!(libc::SYS_openat, AT_FDCWD, path, flags, mode) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn close(fd: c_int) -> c_int {
+    syscall!(libc::SYS_close, fd) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t {
+    syscall!(libc::SYS_read, fd, buf, count)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t {
+    syscall!(libc::SYS_write, fd, buf, count)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn lseek(fd: c_int, offset: off_t, whence: c_int) -> off_t {
+    syscall!(libc::SYS_lseek, fd, offset, whence) as off_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn dup(fd: c_int) -> c_int {
+    syscall!(libc::SYS_dup, fd) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn dup2(old: c_int, new: c_int) -> c_int {
+    if old == new {
+        // dup3 rejects this; dup2 just checks that `old` is open
+        return if fcntl(old, libc::F_GETFD, 0) < 0 { -1 } else { new };
+    }
+    syscall!(libc::SYS_dup3, old, new, 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
+    syscall!(libc::SYS_fcntl, fd, cmd, arg) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn stat(path: *const c_char, buf: *mut libc::stat) -> c_int {
+    syscall!(libc::SYS_newfstatat, AT_FDCWD, path, buf, 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut libc::stat) -> c_int {
+    syscall!(libc::SYS_newfstatat, AT_FDCWD, path, buf, libc::AT_SYMLINK_NOFOLLOW) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fstat(fd: c_int, buf: *mut libc::stat) -> c_int {
+    syscall!(libc::SYS_fstat, fd, buf) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn statfs(path: *const c_char, buf: *mut libc::statfs) -> c_int {
+    syscall!(libc::SYS_statfs, path, buf) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn access(path: *const c_char, mode: c_int) -> c_int {
+    syscall!(libc::SYS_faccessat, AT_FDCWD, path, mode) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkdir(path: *const c_char, mode: mode_t) -> c_int {
+    syscall!(libc::SYS_mkdirat, AT_FDCWD, path, mode) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmdir(path: *const c_char) -> c_int {
+    syscall!(libc::SYS_unlinkat, AT_FDCWD, path, libc::AT_REMOVEDIR) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unlink(path: *const c_char) -> c_int {
+    syscall!(libc::SYS_unlinkat, AT_FDCWD, path, 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
+    syscall!(libc::SYS_renameat2, AT_FDCWD, old, AT_FDCWD, new, 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn symlink(target: *const c_char, path: *const c_char) -> c_int {
+    syscall!(libc::SYS_symlinkat, target, AT_FDCWD, path) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn link(old: *const c_char, new: *const c_char) -> c_int {
+    syscall!(libc::SYS_linkat, AT_FDCWD, old, AT_FDCWD, new, 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readlink(path: *const c_char, buf: *mut c_char, size: size_t) -> ssize_t {
+    syscall!(libc::SYS_readlinkat, AT_FDCWD, path, buf, size)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chmod(path: *const c_char, mode: mode_t) -> c_int {
+    syscall!(libc::SYS_fchmodat, AT_FDCWD, path, mode) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chown(path: *const c_char, uid: uid_t, gid: gid_t) -> c_int {
+    syscall!(libc::SYS_fchownat, AT_FDCWD, path, uid, gid, 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fchmod(fd: c_int, mode: mode_t) -> c_int {
+    syscall!(libc::SYS_fchmod, fd, mode) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fchown(fd: c_int, uid: uid_t, gid: gid_t) -> c_int {
+    syscall!(libc::SYS_fchown, fd, uid, gid) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mknod(path: *const c_char, mode: mode_t, dev: dev_t) -> c_int {
+    syscall!(libc::SYS_mknodat, AT_FDCWD, path, mode, dev) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkfifo(path: *const c_char, mode: mode_t) -> c_int {
+    unsafe { mknod(path, mode | libc::S_IFIFO, 0) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn truncate(path: *const c_char, length: off_t) -> c_int {
+    syscall!(libc::SYS_truncate, path, length) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ftruncate(fd: c_int, length: off_t) -> c_int {
+    syscall!(libc::SYS_ftruncate, fd, length) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn utimes(path: *const c_char, times: *const libc::timeval) -> c_int {
+    if times.is_null() {
+        return syscall!(libc::SYS_utimensat, AT_FDCWD, path, 0, 0) as c_int;
+    }
+    let times = unsafe { [*times, *times.add(1)] };
+    let ts = times.map(|tv| libc::timespec { tv_sec: tv.tv_sec, tv_nsec: tv.tv_usec * 1000 });
+    syscall!(libc::SYS_utimensat, AT_FDCWD, path, ts.as_ptr(), 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn utimensat(dirfd: c_int, path: *const c_char, times: *const libc::timespec, flags: c_int) -> c_int {
+    syscall!(libc::SYS_utimensat, dirfd, path, times, flags) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fsync(fd: c_int) -> c_int {
+    syscall!(libc::SYS_fsync, fd) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fdatasync(fd: c_int) -> c_int {
+    syscall!(libc::SYS_fdatasync, fd) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn sync() {
+    syscall!(libc::SYS_sync);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn flock(fd: c_int, operation: c_int) -> c_int {
+    syscall!(libc::SYS_flock, fd, operation) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn readahead(fd: c_int, offset: libc::off64_t, count: size_t) -> ssize_t {
+    syscall!(libc::SYS_readahead, fd, offset, count)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn umask(mask: mode_t) -> mode_t {
+    syscall!(libc::SYS_umask, mask) as mode_t
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memfd_create(name: *const c_char, flags: c_uint) -> c_int {
+    syscall!(libc::SYS_memfd_create, name, flags) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chdir(path: *const c_char) -> c_int {
+    syscall!(libc::SYS_chdir, path) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fchdir(fd: c_int) -> c_int {
+    syscall!(libc::SYS_fchdir, fd) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chroot(path: *const c_char) -> c_int {
+    syscall!(libc::SYS_chroot, path) as c_int
+}
+
+/// Unlike glibc, a null `buf` is not allocated for the caller
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: size_t) -> *mut c_char {
+    if syscall!(libc::SYS_getcwd, buf, size) < 0 { core::ptr::null_mut() } else { buf }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn swapon(path: *const c_char, flags: c_int) -> c_int {
+    syscall!(libc::SYS_swapon, path, flags) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn swapoff(path: *const c_char) -> c_int {
+    syscall!(libc::SYS_swapoff, path) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: libc::c_ulong,
+    data: *const c_void,
+) -> c_int {
+    syscall!(libc::SYS_mount, source, target, fstype, flags, data) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn umount2(target: *const c_char, flags: c_int) -> c_int {
+    syscall!(libc::SYS_umount2, target, flags) as c_int
+}
+
+/// Write `/proc/self/fd/N` for `fd` into `path`, NUL-terminated
+pub fn proc_fd_path(fd: c_int, path: &mut [u8; 32]) {
+    let prefix = b"/proc/self/fd/";
+    path[..prefix.len()].copy_from_slice(prefix);
+    let mut digits = [0u8; 20];
+    let n = crate::sys::format_u64(fd as u64, &mut digits);
+    path[prefix.len()..prefix.len() + n.len()].copy_from_slice(n);
+    path[prefix.len() + n.len()] = 0;
+}
+
+/// The kernel's name for an open fd; the length, or -1
+pub fn fd_name(fd: c_int, buf: *mut c_char, size: size_t) -> ssize_t {
+    let mut path = [0u8; 32];
+    proc_fd_path(fd, &mut path);
+    unsafe { readlink(path.as_ptr() as *const c_char, buf, size) }
+}
+
+/// Resolved through `/proc`, so it needs procfs mounted. Unlike glibc, a
+/// null `resolved` is not allocated for the caller.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn realpath(path: *const c_char, resolved: *mut c_char) -> *mut c_char {
+    if resolved.is_null() {
+        super::errno::set(libc::EINVAL);
+        return core::ptr::null_mut();
+    }
+    let fd = unsafe { open(path, libc::O_PATH | libc::O_CLOEXEC, 0) };
+    if fd < 0 {
+        return core::ptr::null_mut();
+    }
+    let n = fd_name(fd, resolved, libc::PATH_MAX as size_t - 1);
+    close(fd);
+    if n < 0 {
+        return core::ptr::null_mut();
+    }
+    unsafe { *resolved.add(n as usize) = 0 };
+    resolved
+}
+
+/// An open directory stream
+#[repr(C, align(8))]
+struct Dir {
+    fd: c_int,
+    pos: usize,
+    end: usize,
+    /// getdents64 records, plus room for a `libc::dirent` read past the end
+    /// of the last one
+    buf: [u8; DIR_BUF + core::mem::size_of::<libc::dirent>()],
+}
+
+const DIR_BUF: usize = 4096;
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn opendir(path: *const c_char) -> *mut libc::DIR {
+    let fd = unsafe { open(path, libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC, 0) };
+    if fd < 0 {
+        return core::ptr::null_mut();
+    }
+    let dir = Box::new(Dir { fd, pos: 0, end: 0, buf: [0; DIR_BUF + core::mem::size_of::<libc::dirent>()] });
+    Box::into_raw(dir) as *mut libc::DIR
+}
+
+/// `libc::dirent` on 64-bit Linux has the same layout as the kernel's
+/// `linux_dirent64`, so entries are returned in place
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readdir(dir: *mut libc::DIR) -> *mut libc::dirent {
+    let dir = unsafe { &mut *(dir as *mut Dir) };
+    if dir.pos >= dir.end {
+        let n = syscall!(libc::SYS_getdents64, dir.fd, dir.buf.as_mut_ptr(), DIR_BUF);
+        if n <= 0 {
+            return core::ptr::null_mut();
+        }
+        dir.pos = 0;
+        dir.end = n as usize;
+    }
+    let entry = unsafe { dir.buf.as_mut_ptr().add(dir.pos) } as *mut libc::dirent;
+    dir.pos += unsafe { (*entry).d_reclen } as usize;
+    entry
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn closedir(dir: *mut libc::DIR) -> c_int {
+    let dir = unsafe { Box::from_raw(dir as *mut Dir) };
+    close(dir.fd)
+}
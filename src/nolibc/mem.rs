@@ -0,0 +1,93 @@
+//! `memcpy` and friends, which the compiler emits calls to
+//!
+//! The loops must not be turned back into calls to the function being
+//! defined, so copies use `rep movsb`/`rep stosb` on x86_64 and volatile
+//! accesses elsewhere.
+
+use core::ptr::{read_volatile, write_volatile};
+use libc::{c_char, c_int, c_void, size_t};
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn copy_forward(dst: *mut u8, src: *const u8, n: usize) {
+    unsafe {
+        core::arch::asm!(
+            "rep movsb",
+            inout("rcx") n => _,
+            inout("rdi") dst => _,
+            inout("rsi") src => _,
+            options(nostack, preserves_flags),
+        );
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn copy_forward(dst: *mut u8, src: *const u8, n: usize) {
+    for i in 0..n {
+        unsafe { write_volatile(dst.add(i), read_volatile(src.add(i))) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memcpy(dst: *mut c_void, src: *const c_void, n: size_t) -> *mut c_void {
+    unsafe { copy_forward(dst as *mut u8, src as *const u8, n) };
+    dst
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memmove(dst: *mut c_void, src: *const c_void, n: size_t) -> *mut c_void {
+    let (d, s) = (dst as *mut u8, src as *const u8);
+    if (d as usize).wrapping_sub(s as usize) >= n {
+        // No overlap that a forward copy would clobber
+        unsafe { copy_forward(d, s, n) };
+    } else {
+        for i in (0..n).rev() {
+            unsafe { write_volatile(d.add(i), read_volatile(s.add(i))) };
+        }
+    }
+    dst
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memset(dst: *mut c_void, c: c_int, n: size_t) -> *mut c_void {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::asm!(
+            "rep stosb",
+            inout("rcx") n => _,
+            inout("rdi") dst => _,
+            in("al") c as u8,
+            options(nostack, preserves_flags),
+        );
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    for i in 0..n {
+        unsafe { write_volatile((dst as *mut u8).add(i), c as u8) };
+    }
+    dst
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memcmp(a: *const c_void, b: *const c_void, n: size_t) -> c_int {
+    let (a, b) = (a as *const u8, b as *const u8);
+    for i in 0..n {
+        let (x, y) = unsafe { (read_volatile(a.add(i)), read_volatile(b.add(i))) };
+        if x != y {
+            return x as c_int - y as c_int;
+        }
+    }
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcmp(a: *const c_void, b: *const c_void, n: size_t) -> c_int {
+    unsafe { memcmp(a, b, n) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn strlen(s: *const c_char) -> size_t {
+    let mut n = 0;
+    while unsafe { read_volatile(s.add(n)) } != 0 {
+        n += 1;
+    }
+    n
+}
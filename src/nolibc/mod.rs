@@ -0,0 +1,28 @@
+//! Running without a C library (`nolibc` feature)
+//!
+//! armybox calls libc through the `libc` crate's declarations. With
+//! `nolibc` the functions it uses are defined here instead, on top of raw
+//! system calls, and the binary is linked with `-nostdlib -static` against
+//! empty stand-ins for libc (see `build.rs`). A libc function that is used
+//! but not defined here fails the link rather than quietly pulling in the
+//! real one.
+//!
+//! Only what armybox needs is provided, with Linux semantics and no locale,
+//! time zone, user database or threads. Applets that depend on those are
+//! marked `libc` in the applet table and left out of `nolibc` builds.
+//!
+//! Supported on x86_64 and aarch64 Linux.
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+compile_error!("the nolibc feature supports x86_64 and aarch64 Linux only");
+
+#[macro_use]
+mod syscall;
+mod errno;
+mod start;
+mod mem;
+mod env;
+mod fs;
+mod term;
+mod process;
+mod system;
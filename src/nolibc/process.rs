@@ -0,0 +1,319 @@
+//! Processes, identity, scheduling and signals
+
+use libc::{c_char, c_int, c_uint, c_ulong, gid_t, pid_t, size_t, uid_t};
+
+use super::start::environ;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _exit(status: c_int) -> ! {
+    loop {
+        syscall!(libc::SYS_exit_group, status);
+    }
+}
+
+/// `clone` with just the exit signal is `fork`, and exists on every
+/// architecture (aarch64 has no `fork` system call)
+#[unsafe(no_mangle)]
+pub extern "C" fn fork() -> pid_t {
+    syscall!(libc::SYS_clone, libc::SIGCHLD, 0, 0, 0, 0) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn execve(
+    path: *const c_char,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    syscall!(libc::SYS_execve, path, argv, envp) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn execv(path: *const c_char, argv: *const *const c_char) -> c_int {
+    unsafe { execve(path, argv, environ) }
+}
+
+/// Search `PATH` (default `/bin:/usr/bin`) unless `file` contains a slash.
+/// Unlike glibc, files without a `#!` line are not retried with `/bin/sh`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn execvp(file: *const c_char, argv: *const *const c_char) -> c_int {
+    let name = unsafe { core::slice::from_raw_parts(file as *const u8, super::mem::strlen(file)) };
+    if name.is_empty() {
+        super::errno::set(libc::ENOENT);
+        return -1;
+    }
+    if name.contains(&b'/') {
+        return unsafe { execv(file, argv) };
+    }
+
+    let path = unsafe { super::env::getenv(c"PATH".as_ptr()) };
+    let path = if path.is_null() {
+        b"/bin:/usr/bin".as_slice()
+    } else {
+        unsafe { core::slice::from_raw_parts(path as *const u8, super::mem::strlen(path)) }
+    };
+
+    let mut denied = false;
+    for dir in path.split(|&c| c == b':') {
+        let dir = if dir.is_empty() { b".".as_slice() } else { dir };
+        let mut full = [0u8; 4096];
+        if dir.len() + name.len() + 2 > full.len() {
+            continue;
+        }
+        full[..dir.len()].copy_from_slice(dir);
+        full[dir.len()] = b'/';
+        full[dir.len() + 1..dir.len() + 1 + name.len()].copy_from_slice(name);
+
+        unsafe { execv(full.as_ptr() as *const c_char, argv) };
+        match crate::sys::errno() {
+            libc::EACCES => denied = true,
+            libc::ENOENT | libc::ENOTDIR => {}
+            _ => return -1,
+        }
+    }
+    super::errno::set(if denied { libc::EACCES } else { libc::ENOENT });
+    -1
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn waitpid(pid: pid_t, status: *mut c_int, options: c_int) -> pid_t {
+    syscall!(libc::SYS_wait4, pid, status, options, 0) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wait(status: *mut c_int) -> pid_t {
+    unsafe { waitpid(-1, status, 0) }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn kill(pid: pid_t, sig: c_int) -> c_int {
+    syscall!(libc::SYS_kill, pid, sig) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getpid() -> pid_t {
+    syscall!(libc::SYS_getpid) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getppid() -> pid_t {
+    syscall!(libc::SYS_getppid) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getuid() -> uid_t {
+    syscall!(libc::SYS_getuid) as uid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn geteuid() -> uid_t {
+    syscall!(libc::SYS_geteuid) as uid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getgid() -> gid_t {
+    syscall!(libc::SYS_getgid) as gid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getegid() -> gid_t {
+    syscall!(libc::SYS_getegid) as gid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn setresuid(ruid: uid_t, euid: uid_t, suid: uid_t) -> c_int {
+    syscall!(libc::SYS_setresuid, ruid, euid, suid) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn setresgid(rgid: gid_t, egid: gid_t, sgid: gid_t) -> c_int {
+    syscall!(libc::SYS_setresgid, rgid, egid, sgid) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getgroups(size: c_int, list: *mut gid_t) -> c_int {
+    syscall!(libc::SYS_getgroups, size, list) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getpgid(pid: pid_t) -> pid_t {
+    syscall!(libc::SYS_getpgid, pid) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn getsid(pid: pid_t) -> pid_t {
+    syscall!(libc::SYS_getsid, pid) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn setsid() -> pid_t {
+    syscall!(libc::SYS_setsid) as pid_t
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn unshare(flags: c_int) -> c_int {
+    syscall!(libc::SYS_unshare, flags) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn setpriority(which: c_uint, who: libc::id_t, prio: c_int) -> c_int {
+    syscall!(libc::SYS_setpriority, which, who, prio) as c_int
+}
+
+/// The raw system call returns `20 - nice` so that it is never negative
+#[unsafe(no_mangle)]
+pub extern "C" fn nice(inc: c_int) -> c_int {
+    let current = syscall!(libc::SYS_getpriority, libc::PRIO_PROCESS, 0);
+    if current < 0 {
+        return -1;
+    }
+    let new = 20 - current as c_int + inc;
+    if inc != 0 && setpriority(libc::PRIO_PROCESS, 0, new) < 0 {
+        return -1;
+    }
+    new
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn sched_getscheduler(pid: pid_t) -> c_int {
+    syscall!(libc::SYS_sched_getscheduler, pid) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_setscheduler(
+    pid: pid_t,
+    policy: c_int,
+    param: *const libc::sched_param,
+) -> c_int {
+    syscall!(libc::SYS_sched_setscheduler, pid, policy, param) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_getparam(pid: pid_t, param: *mut libc::sched_param) -> c_int {
+    syscall!(libc::SYS_sched_getparam, pid, param) as c_int
+}
+
+/// The kernel only fills in as many bytes as it has CPUs for; glibc zeroes
+/// the rest and returns 0, and so does this
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_getaffinity(pid: pid_t, size: size_t, set: *mut libc::cpu_set_t) -> c_int {
+    let n = syscall!(libc::SYS_sched_getaffinity, pid, size, set);
+    if n < 0 {
+        return -1;
+    }
+    unsafe { core::ptr::write_bytes((set as *mut u8).add(n as usize), 0, size - n as usize) };
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_setaffinity(pid: pid_t, size: size_t, set: *const libc::cpu_set_t) -> c_int {
+    syscall!(libc::SYS_sched_setaffinity, pid, size, set) as c_int
+}
+
+/// `struct sigaction` as the kernel takes it
+#[repr(C)]
+struct KernelSigaction {
+    handler: usize,
+    flags: c_ulong,
+    restorer: usize,
+    mask: u64,
+}
+
+// x86_64 signal handlers return through a restorer the caller supplies
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    ".globl __restore_rt",
+    ".hidden __restore_rt",
+    "__restore_rt:",
+    "mov eax, {n}",
+    "syscall",
+    n = const libc::SYS_rt_sigreturn,
+);
+
+#[cfg(target_arch = "x86_64")]
+unsafe extern "C" {
+    fn __restore_rt();
+}
+
+#[cfg(target_arch = "x86_64")]
+const SA_RESTORER: c_ulong = 0x0400_0000;
+
+/// The kernel's signal set is the first 64 bits of `libc::sigset_t`
+unsafe fn kernel_set(set: *const libc::sigset_t) -> u64 {
+    unsafe { *(set as *const u64) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sigaction(
+    sig: c_int,
+    act: *const libc::sigaction,
+    old: *mut libc::sigaction,
+) -> c_int {
+    let new = (!act.is_null()).then(|| {
+        let act = unsafe { &*act };
+        #[allow(unused_mut)]
+        let mut k = KernelSigaction {
+            handler: act.sa_sigaction,
+            flags: act.sa_flags as c_ulong,
+            restorer: 0,
+            mask: unsafe { kernel_set(&act.sa_mask) },
+        };
+        #[cfg(target_arch = "x86_64")]
+        {
+            k.flags |= SA_RESTORER;
+            k.restorer = __restore_rt as *const () as usize;
+        }
+        k
+    });
+    let mut prev: KernelSigaction = unsafe { core::mem::zeroed() };
+
+    let new_ptr = new.as_ref().map_or(core::ptr::null(), |k| k as *const KernelSigaction);
+    if syscall!(libc::SYS_rt_sigaction, sig, new_ptr, &mut prev as *mut KernelSigaction, 8) < 0 {
+        return -1;
+    }
+    if !old.is_null() {
+        unsafe {
+            core::ptr::write_bytes(old, 0, 1);
+            (*old).sa_sigaction = prev.handler;
+            (*old).sa_flags = prev.flags as c_int;
+            *(&mut (*old).sa_mask as *mut libc::sigset_t as *mut u64) = prev.mask;
+        }
+    }
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sigemptyset(set: *mut libc::sigset_t) -> c_int {
+    unsafe { core::ptr::write_bytes(set, 0, 1) };
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sigaddset(set: *mut libc::sigset_t, sig: c_int) -> c_int {
+    if !(1..=64).contains(&sig) {
+        super::errno::set(libc::EINVAL);
+        return -1;
+    }
+    unsafe { *(set as *mut u64) |= 1 << (sig - 1) };
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sigprocmask(
+    how: c_int,
+    set: *const libc::sigset_t,
+    old: *mut libc::sigset_t,
+) -> c_int {
+    let new = if set.is_null() { None } else { Some(unsafe { kernel_set(set) }) };
+    let mut prev: u64 = 0;
+    let new_ptr = new.as_ref().map_or(core::ptr::null(), |m| m as *const u64);
+    if syscall!(libc::SYS_rt_sigprocmask, how, new_ptr, &mut prev as *mut u64, 8) < 0 {
+        return -1;
+    }
+    if !old.is_null() {
+        unsafe {
+            core::ptr::write_bytes(old, 0, 1);
+            *(old as *mut u64) = prev;
+        }
+    }
+    0
+}
@@ -0,0 +1,69 @@
+//! Process entry point
+//!
+//! The kernel starts the process at `_start` with the stack pointer at
+//! `argc`, followed by the `argv` and `envp` arrays. There is no C runtime to
+//! set anything else up, so `start` records `environ` and the page size from
+//! the auxiliary vector that follows it, then calls the binary's `main`.
+
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+unsafe extern "C" {
+    /// The binary's entry point (`src/main.rs`)
+    fn main(argc: i32, argv: *const *const u8) -> i32;
+}
+
+/// The environment, as C code expects to find it
+#[allow(non_upper_case_globals)]
+#[unsafe(no_mangle)]
+pub static mut environ: *const *const i8 = ptr::null();
+
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    ".globl _start",
+    "_start:",
+    "xor ebp, ebp",
+    "mov rdi, rsp",
+    "and rsp, -16",
+    "call {start}",
+    "ud2",
+    start = sym start,
+);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".globl _start",
+    "_start:",
+    "mov x29, #0",
+    "mov x30, #0",
+    "mov x0, sp",
+    "bl {start}",
+    "brk #0",
+    start = sym start,
+);
+
+/// Page size from `AT_PAGESZ`; aarch64 kernels may use 16K or 64K pages
+pub static PAGE_SIZE: AtomicUsize = AtomicUsize::new(4096);
+
+unsafe extern "C" fn start(sp: *const usize) -> ! {
+    unsafe {
+        let argc = *sp as i32;
+        let argv = sp.add(1) as *const *const u8;
+        environ = argv.add(argc as usize + 1) as *const *const i8;
+
+        let mut p = environ as *const usize;
+        while *p != 0 {
+            p = p.add(1);
+        }
+        let mut aux = p.add(1);
+        while *aux != libc::AT_NULL as usize {
+            if *aux == libc::AT_PAGESZ as usize {
+                PAGE_SIZE.store(*aux.add(1), Ordering::Relaxed);
+            }
+            aux = aux.add(2);
+        }
+
+        let status = main(argc, argv);
+        crate::io::exit(status)
+    }
+}
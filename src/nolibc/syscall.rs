@@ -0,0 +1,82 @@
+//! Raw system calls
+
+use core::arch::asm;
+
+/// Issue system call `n` with up to six arguments, returning the kernel's
+/// result (`-errno` on failure)
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub unsafe fn raw(n: i64, a: usize, b: usize, c: usize, d: usize, e: usize, f: usize) -> isize {
+    let ret: isize;
+    unsafe {
+        asm!(
+            "syscall",
+            inlateout("rax") n as isize => ret,
+            in("rdi") a,
+            in("rsi") b,
+            in("rdx") c,
+            in("r10") d,
+            in("r8") e,
+            in("r9") f,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Issue system call `n` with up to six arguments, returning the kernel's
+/// result (`-errno` on failure)
+#[cfg(target_arch = "aarch64")]
+#[inline]
+pub unsafe fn raw(n: i64, a: usize, b: usize, c: usize, d: usize, e: usize, f: usize) -> isize {
+    let ret: isize;
+    unsafe {
+        asm!(
+            "svc 0",
+            in("x8") n,
+            inlateout("x0") a as isize => ret,
+            in("x1") b,
+            in("x2") c,
+            in("x3") d,
+            in("x4") e,
+            in("x5") f,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Turn a raw result into the C convention: -1 with `errno` set on failure
+pub fn ret(r: isize) -> isize {
+    if (-4095..0).contains(&r) {
+        super::errno::set(-r as i32);
+        -1
+    } else {
+        r
+    }
+}
+
+/// System call with the C return convention
+macro_rules! syscall {
+    ($n:expr) => { syscall!($n, 0, 0, 0, 0, 0, 0) };
+    ($n:expr, $a:expr) => { syscall!($n, $a, 0, 0, 0, 0, 0) };
+    ($n:expr, $a:expr, $b:expr) => { syscall!($n, $a, $b, 0, 0, 0, 0) };
+    ($n:expr, $a:expr, $b:expr, $c:expr) => { syscall!($n, $a, $b, $c, 0, 0, 0) };
+    ($n:expr, $a:expr, $b:expr, $c:expr, $d:expr) => { syscall!($n, $a, $b, $c, $d, 0, 0) };
+    ($n:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr) => { syscall!($n, $a, $b, $c, $d, $e, 0) };
+    ($n:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr) => {
+        $crate::nolibc::syscall::ret(unsafe {
+            $crate::nolibc::syscall::raw(
+                $n as i64,
+                $a as usize,
+                $b as usize,
+                $c as usize,
+                $d as usize,
+                $e as usize,
+                $f as usize,
+            )
+        })
+    };
+}
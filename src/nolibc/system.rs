@@ -0,0 +1,241 @@
+//! Time, system information, sockets and the generic `syscall`
+
+use core::sync::atomic::Ordering;
+use libc::{c_char, c_int, c_long, c_uint, size_t, time_t};
+
+use super::fs::{close, open, read};
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn uname(buf: *mut libc::utsname) -> c_int {
+    syscall!(libc::SYS_uname, buf) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gethostname(name: *mut c_char, len: size_t) -> c_int {
+    let mut uts: libc::utsname = unsafe { core::mem::zeroed() };
+    if unsafe { uname(&mut uts) } < 0 {
+        return -1;
+    }
+    let n = unsafe { super::mem::strlen(uts.nodename.as_ptr()) };
+    if n >= len {
+        super::errno::set(libc::ENAMETOOLONG);
+        return -1;
+    }
+    unsafe { core::ptr::copy_nonoverlapping(uts.nodename.as_ptr(), name, n + 1) };
+    0
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethostname(name: *const c_char, len: size_t) -> c_int {
+    syscall!(libc::SYS_sethostname, name, len) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn reboot(how: c_int) -> c_int {
+    syscall!(
+        libc::SYS_reboot,
+        libc::LINUX_REBOOT_MAGIC1,
+        libc::LINUX_REBOOT_MAGIC2,
+        how,
+        0
+    ) as c_int
+}
+
+/// Count the CPUs in a sysfs list such as `0-3,8`
+fn count_cpus(path: &core::ffi::CStr) -> Option<c_long> {
+    let fd = unsafe { open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC, 0) };
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 256];
+    let n = unsafe { read(fd, buf.as_mut_ptr() as *mut _, buf.len()) };
+    close(fd);
+    if n <= 0 {
+        return None;
+    }
+
+    let mut count = 0;
+    for range in buf[..n as usize].trim_ascii().split(|&c| c == b',') {
+        let mut ends = range.splitn(2, |&c| c == b'-').map(crate::sys::parse_u64);
+        let first = ends.next()??;
+        let last = ends.next().unwrap_or(Some(first))?;
+        count += last.checked_sub(first)? as c_long + 1;
+    }
+    Some(count)
+}
+
+/// Only the names armybox asks for are known
+#[unsafe(no_mangle)]
+pub extern "C" fn sysconf(name: c_int) -> c_long {
+    match name {
+        libc::_SC_PAGESIZE => super::start::PAGE_SIZE.load(Ordering::Relaxed) as c_long,
+        libc::_SC_CLK_TCK => 100,
+        libc::_SC_NPROCESSORS_ONLN => count_cpus(c"/sys/devices/system/cpu/online").unwrap_or(1),
+        libc::_SC_NPROCESSORS_CONF => count_cpus(c"/sys/devices/system/cpu/possible").unwrap_or(1),
+        _ => {
+            super::errno::set(libc::EINVAL);
+            -1
+        }
+    }
+}
+
+fn now() -> libc::timespec {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    syscall!(libc::SYS_clock_gettime, libc::CLOCK_REALTIME, &mut ts as *mut libc::timespec);
+    ts
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn time(t: *mut time_t) -> time_t {
+    let sec = now().tv_sec;
+    if !t.is_null() {
+        unsafe { *t = sec };
+    }
+    sec
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clock_gettime(clock: libc::clockid_t, ts: *mut libc::timespec) -> c_int {
+    syscall!(libc::SYS_clock_gettime, clock, ts) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clock_settime(clock: libc::clockid_t, ts: *const libc::timespec) -> c_int {
+    syscall!(libc::SYS_clock_settime, clock, ts) as c_int
+}
+
+/// The time zone argument is ignored, as it is obsolete
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gettimeofday(tv: *mut libc::timeval, _tz: *mut libc::timezone) -> c_int {
+    let ts = now();
+    unsafe {
+        (*tv).tv_sec = ts.tv_sec;
+        (*tv).tv_usec = ts.tv_nsec / 1000;
+    }
+    0
+}
+
+/// `alarm` in terms of `setitimer`, since aarch64 has no `alarm` call
+#[unsafe(no_mangle)]
+pub extern "C" fn alarm(seconds: c_uint) -> c_uint {
+    let new = libc::itimerval {
+        it_interval: libc::timeval { tv_sec: 0, tv_usec: 0 },
+        it_value: libc::timeval { tv_sec: seconds as time_t, tv_usec: 0 },
+    };
+    let mut old: libc::itimerval = unsafe { core::mem::zeroed() };
+    syscall!(
+        libc::SYS_setitimer,
+        libc::ITIMER_REAL,
+        &new as *const libc::itimerval,
+        &mut old as *mut libc::itimerval
+    );
+    // Round to the nearest second, but don't report a pending alarm as 0
+    let mut left = old.it_value.tv_sec as c_uint;
+    if old.it_value.tv_usec >= 500_000 || (left == 0 && old.it_value.tv_usec > 0) {
+        left += 1;
+    }
+    left
+}
+
+fn nanosleep(ts: &libc::timespec, rem: &mut libc::timespec) -> c_int {
+    syscall!(
+        libc::SYS_nanosleep,
+        ts as *const libc::timespec,
+        rem as *mut libc::timespec
+    ) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn sleep(seconds: c_uint) -> c_uint {
+    let ts = libc::timespec { tv_sec: seconds as time_t, tv_nsec: 0 };
+    let mut rem = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    if nanosleep(&ts, &mut rem) < 0 {
+        return rem.tv_sec as c_uint + (rem.tv_nsec > 0) as c_uint;
+    }
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn usleep(usec: libc::useconds_t) -> c_int {
+    let ts = libc::timespec {
+        tv_sec: (usec / 1_000_000) as time_t,
+        tv_nsec: (usec % 1_000_000) as c_long * 1000,
+    };
+    let mut rem = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    nanosleep(&ts, &mut rem)
+}
+
+/// `poll` in terms of `ppoll`, since aarch64 has no `poll` call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poll(fds: *mut libc::pollfd, nfds: libc::nfds_t, timeout: c_int) -> c_int {
+    let ts = libc::timespec {
+        tv_sec: (timeout / 1000) as time_t,
+        tv_nsec: (timeout % 1000) as c_long * 1_000_000,
+    };
+    let ts_ptr = if timeout < 0 { core::ptr::null() } else { &ts as *const libc::timespec };
+    syscall!(libc::SYS_ppoll, fds, nfds, ts_ptr, 0, 8) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int {
+    syscall!(libc::SYS_socket, domain, ty, protocol) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bind(fd: c_int, addr: *const libc::sockaddr, len: libc::socklen_t) -> c_int {
+    syscall!(libc::SYS_bind, fd, addr, len) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn listen(fd: c_int, backlog: c_int) -> c_int {
+    syscall!(libc::SYS_listen, fd, backlog) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn accept(fd: c_int, addr: *mut libc::sockaddr, len: *mut libc::socklen_t) -> c_int {
+    syscall!(libc::SYS_accept, fd, addr, len) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn connect(fd: c_int, addr: *const libc::sockaddr, len: libc::socklen_t) -> c_int {
+    syscall!(libc::SYS_connect, fd, addr, len) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sendto(
+    fd: c_int,
+    buf: *const libc::c_void,
+    len: size_t,
+    flags: c_int,
+    addr: *const libc::sockaddr,
+    addrlen: libc::socklen_t,
+) -> libc::ssize_t {
+    syscall!(libc::SYS_sendto, fd, buf, len, flags, addr, addrlen) as libc::ssize_t
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn recvfrom(
+    fd: c_int,
+    buf: *mut libc::c_void,
+    len: size_t,
+    flags: c_int,
+    addr: *mut libc::sockaddr,
+    addrlen: *mut libc::socklen_t,
+) -> libc::ssize_t {
+    syscall!(libc::SYS_recvfrom, fd, buf, len, flags, addr, addrlen) as libc::ssize_t
+}
+
+/// Declared variadic in C; integer arguments arrive in the same registers
+/// either way on the supported architectures
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn syscall(
+    n: c_long,
+    a: c_long,
+    b: c_long,
+    c: c_long,
+    d: c_long,
+    e: c_long,
+    f: c_long,
+) -> c_long {
+    syscall!(n, a, b, c, d, e, f) as c_long
+}
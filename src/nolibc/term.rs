@@ -0,0 +1,137 @@
+//! Terminals
+
+use core::cell::UnsafeCell;
+use libc::{c_char, c_int, c_ulong, size_t};
+
+use super::fs::{close, fd_name, open};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ioctl(fd: c_int, request: c_ulong, arg: usize) -> c_int {
+    syscall!(libc::SYS_ioctl, fd, request, arg) as c_int
+}
+
+/// The kernel fills in the start of `libc::termios`, which only adds
+/// control characters and speed fields on the end
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcgetattr(fd: c_int, termios: *mut libc::termios) -> c_int {
+    ioctl(fd, libc::TCGETS, termios as usize)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tcsetattr(fd: c_int, action: c_int, termios: *const libc::termios) -> c_int {
+    let request = match action {
+        libc::TCSANOW => libc::TCSETS,
+        libc::TCSADRAIN => libc::TCSETSW,
+        libc::TCSAFLUSH => libc::TCSETSF,
+        _ => {
+            super::errno::set(libc::EINVAL);
+            return -1;
+        }
+    };
+    ioctl(fd, request, termios as usize)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cfmakeraw(termios: *mut libc::termios) {
+    let t = unsafe { &mut *termios };
+    t.c_iflag &= !(libc::IGNBRK | libc::BRKINT | libc::PARMRK | libc::ISTRIP
+        | libc::INLCR | libc::IGNCR | libc::ICRNL | libc::IXON);
+    t.c_oflag &= !libc::OPOST;
+    t.c_lflag &= !(libc::ECHO | libc::ECHONL | libc::ICANON | libc::ISIG | libc::IEXTEN);
+    t.c_cflag &= !(libc::CSIZE | libc::PARENB);
+    t.c_cflag |= libc::CS8;
+    t.c_cc[libc::VMIN] = 1;
+    t.c_cc[libc::VTIME] = 0;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn isatty(fd: c_int) -> c_int {
+    let mut termios: libc::termios = unsafe { core::mem::zeroed() };
+    (unsafe { tcgetattr(fd, &mut termios) } == 0) as c_int
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ttyname_r(fd: c_int, buf: *mut c_char, size: size_t) -> c_int {
+    if isatty(fd) == 0 {
+        return libc::ENOTTY;
+    }
+    if size == 0 {
+        return libc::ERANGE;
+    }
+    let n = fd_name(fd, buf, size - 1);
+    if n < 0 {
+        return crate::sys::errno();
+    }
+    unsafe { *buf.add(n as usize) = 0 };
+    0
+}
+
+struct TtyName(UnsafeCell<[c_char; 64]>);
+
+// Applets run on a single thread
+unsafe impl Sync for TtyName {}
+
+static TTY_NAME: TtyName = TtyName(UnsafeCell::new([0; 64]));
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ttyname(fd: c_int) -> *mut c_char {
+    let buf = TTY_NAME.0.get() as *mut c_char;
+    match unsafe { ttyname_r(fd, buf, 64) } {
+        0 => buf,
+        e => {
+            super::errno::set(e);
+            core::ptr::null_mut()
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn openpty(
+    master: *mut c_int,
+    slave: *mut c_int,
+    name: *mut c_char,
+    termios: *const libc::termios,
+    winsize: *const libc::winsize,
+) -> c_int {
+    let m = unsafe { open(c"/dev/ptmx".as_ptr(), libc::O_RDWR | libc::O_NOCTTY, 0) };
+    if m < 0 {
+        return -1;
+    }
+
+    let unlock: c_int = 0;
+    let mut index: c_int = 0;
+    if ioctl(m, libc::TIOCSPTLCK, &unlock as *const c_int as usize) < 0
+        || ioctl(m, libc::TIOCGPTN, &mut index as *mut c_int as usize) < 0
+    {
+        close(m);
+        return -1;
+    }
+
+    let mut path = [0u8; 32];
+    let prefix = b"/dev/pts/";
+    path[..prefix.len()].copy_from_slice(prefix);
+    let mut digits = [0u8; 20];
+    let n = crate::sys::format_u64(index as u64, &mut digits);
+    path[prefix.len()..prefix.len() + n.len()].copy_from_slice(n);
+    let len = prefix.len() + n.len();
+
+    let s = unsafe { open(path.as_ptr() as *const c_char, libc::O_RDWR | libc::O_NOCTTY, 0) };
+    if s < 0 {
+        close(m);
+        return -1;
+    }
+    unsafe {
+        if !name.is_null() {
+            core::ptr::copy_nonoverlapping(path.as_ptr() as *const c_char, name, len + 1);
+        }
+        if !termios.is_null() {
+            tcsetattr(s, libc::TCSANOW, termios);
+        }
+        if !winsize.is_null() {
+            ioctl(s, libc::TIOCSWINSZ, winsize as usize);
+        }
+        *master = m;
+        *slave = s;
+    }
+    0
+}
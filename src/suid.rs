@@ -0,0 +1,296 @@
+//! Setuid policy
+//!
+//! Armybox may be installed setuid root so that `su`, `passwd`, `ping` or
+//! `mount` work for ordinary users. No other applet may run with those
+//! privileges, so `run_applet` calls `enforce` before anything else: unless
+//! `/etc/armybox.conf` lets the applet keep them, it drops to the real uid
+//! and gid. `embed` doesn't, since the ids there belong to the host program.
+//!
+//! The file has a `[SUID]` section of `APPLET = MODE [USER[.GROUP]]` lines:
+//!
+//! ```text
+//! [SUID]
+//! # Anyone may su; only root and members of wheel may mount
+//! su = sss root.root
+//! mount = ssx root.wheel
+//! ```
+//!
+//! `MODE` has one character each, like permission bits, for the caller
+//! being `USER`, being a member of `GROUP`, and anyone else:
+//!
+//! - `s` run privileged, with `USER` and `GROUP` as the effective and saved
+//!   ids, so an applet kept as an ordinary user can't get root back
+//! - `x` run with the caller's own ids
+//! - `-` refuse to run
+//!
+//! `USER` and `GROUP` are names or numbers and default to root. Applets
+//! without a valid line drop privileges. The file is only trusted if it is
+//! a regular file owned by root and writable by no one else; otherwise, or
+//! if there is none, every applet drops privileges.
+
+use crate::applets::accounts;
+use crate::io;
+use crate::sys;
+
+/// Where the policy is read from
+const CONFIG: &[u8] = b"/etc/armybox.conf";
+
+/// What `[SUID]` allows for an applet
+#[derive(Debug, PartialEq)]
+struct Rule {
+    /// `s`, `x` or `-` for the owner, the group and others
+    mode: [u8; 3],
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+/// How an applet runs under the policy
+#[derive(Debug, PartialEq)]
+enum Privileges {
+    /// With `uid` and `gid` as the effective and saved ids, so the applet
+    /// can't get back to whatever the binary is owned by
+    Keep { uid: libc::uid_t, gid: libc::gid_t },
+    /// With the caller's own ids
+    Drop,
+    Refuse,
+}
+
+/// What `rule` allows a caller with real uid `ruid`, where `in_group` says
+/// whether the caller is a member of a group
+fn privileges(rule: Option<&Rule>, ruid: libc::uid_t, in_group: impl FnOnce(libc::gid_t) -> bool) -> Privileges {
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return Privileges::Drop,
+    };
+    let which = if ruid == rule.uid {
+        0
+    } else if in_group(rule.gid) {
+        1
+    } else {
+        2
+    };
+    match rule.mode[which] {
+        b's' => Privileges::Keep { uid: rule.uid, gid: rule.gid },
+        b'x' => Privileges::Drop,
+        _ => Privileges::Refuse,
+    }
+}
+
+/// Apply the policy for `applet`.
+///
+/// Returns false, after printing why, if the applet must not run. Does
+/// nothing unless the effective ids differ from the real ones.
+pub fn enforce(applet: &[u8]) -> bool {
+    let (ruid, rgid) = unsafe { (libc::getuid(), libc::getgid()) };
+    if unsafe { libc::geteuid() == ruid && libc::getegid() == rgid } {
+        return true;
+    }
+
+    let rule = find_rule(applet);
+    match privileges(rule.as_ref(), ruid, |gid| in_group(rgid, gid)) {
+        Privileges::Keep { uid, gid } => {
+            // Group first, while the effective uid may still change it
+            let ok = unsafe {
+                libc::setresgid(u32::MAX, gid, gid) == 0
+                    && libc::setresuid(u32::MAX, uid, uid) == 0
+            };
+            if !ok {
+                sys::perror(applet, b"setresuid");
+            }
+            ok
+        }
+        Privileges::Drop => {
+            drop_privileges();
+            true
+        }
+        Privileges::Refuse => {
+            sys::error(applet, b"permission denied");
+            false
+        }
+    }
+}
+
+/// Make the effective and saved ids the real ones, for good.
+///
+/// Exits if that fails, rather than run with privileges.
+pub fn drop_privileges() {
+    let (ruid, rgid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let ok = unsafe {
+        libc::setresgid(rgid, rgid, rgid) == 0
+            && libc::setresuid(ruid, ruid, ruid) == 0
+            && libc::geteuid() == ruid
+            && libc::getegid() == rgid
+    };
+    if !ok {
+        sys::error(b"armybox", b"can't drop privileges");
+        io::exit(1);
+    }
+}
+
+/// Whether the caller has `gid` as its real or a supplementary group
+fn in_group(rgid: libc::gid_t, gid: libc::gid_t) -> bool {
+    if rgid == gid {
+        return true;
+    }
+    let mut groups = [0 as libc::gid_t; 64];
+    let n = unsafe { libc::getgroups(groups.len() as i32, groups.as_mut_ptr()) };
+    n > 0 && groups[..n as usize].contains(&gid)
+}
+
+/// The `[SUID]` rule for `applet` in a trusted `/etc/armybox.conf`
+fn find_rule(applet: &[u8]) -> Option<Rule> {
+    let mut buf = [0u8; 4096];
+    rule_in(read_file(CONFIG, &mut buf, true)?, applet)
+}
+
+/// The `[SUID]` rule for `applet` in the text of a config file
+fn rule_in(config: &[u8], applet: &[u8]) -> Option<Rule> {
+    let mut in_suid = false;
+    for line in config.split(|&c| c == b'\n') {
+        let line = line.trim_ascii();
+        if line.is_empty() || line[0] == b'#' {
+            continue;
+        }
+        if line[0] == b'[' {
+            in_suid = line == b"[SUID]";
+            continue;
+        }
+        if !in_suid {
+            continue;
+        }
+
+        let eq = match line.iter().position(|&c| c == b'=') {
+            Some(eq) => eq,
+            None => continue,
+        };
+        if line[..eq].trim_ascii() != applet {
+            continue;
+        }
+        // A bad line for the applet keeps it unprivileged
+        return parse_rule(line[eq + 1..].trim_ascii());
+    }
+    None
+}
+
+/// Parse `MODE [USER[.GROUP]]`
+fn parse_rule(value: &[u8]) -> Option<Rule> {
+    let mut fields = value.split(|c| c.is_ascii_whitespace()).filter(|f| !f.is_empty());
+    let mode: [u8; 3] = fields.next()?.try_into().ok()?;
+    if !mode.iter().all(|c| matches!(c, b's' | b'x' | b'-')) {
+        return None;
+    }
+
+    let owner = fields.next().unwrap_or(b"root");
+    if fields.next().is_some() {
+        return None;
+    }
+    let (user, group) = match owner.iter().position(|&c| c == b'.') {
+        Some(dot) => (&owner[..dot], &owner[dot + 1..]),
+        None => (owner, b"root".as_slice()),
+    };
+    Some(Rule {
+        mode,
+        uid: lookup_uid(user)?,
+        gid: lookup_gid(group)?,
+    })
+}
+
+/// The uid of `name`, a user name or number
+fn lookup_uid(name: &[u8]) -> Option<u32> {
+    match (name, accounts::Users::open()) {
+        (b"root", _) => Some(0),
+        (_, Some(users)) => users.uid_of(name),
+        (_, None) => accounts::parse_id(name),
+    }
+}
+
+/// The gid of `name`, a group name or number
+fn lookup_gid(name: &[u8]) -> Option<u32> {
+    match (name, accounts::Groups::open()) {
+        (b"root", _) => Some(0),
+        (_, Some(groups)) => groups.gid_of(name),
+        (_, None) => accounts::parse_id(name),
+    }
+}
+
+/// Read `path` into `buf`, up to the last complete line if it doesn't fit.
+///
+/// With `trusted`, only a regular file owned by root that no one else can
+/// write is read.
+fn read_file<'a>(path: &[u8], buf: &'a mut [u8], trusted: bool) -> Option<&'a [u8]> {
+    let flags = if trusted { libc::O_RDONLY | libc::O_NOFOLLOW } else { libc::O_RDONLY };
+    let fd = io::open(path, flags, 0);
+    if fd < 0 {
+        return None;
+    }
+    let mut st: libc::stat = unsafe { core::mem::zeroed() };
+    let ok = !trusted || (io::fstat(fd, &mut st) == 0
+        && st.st_mode & libc::S_IFMT == libc::S_IFREG
+        && st.st_uid == 0
+        && st.st_mode & (libc::S_IWGRP | libc::S_IWOTH) == 0);
+
+    let mut len = 0;
+    while ok && len < buf.len() {
+        let n = io::read(fd, &mut buf[len..]);
+        if n <= 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    io::close(fd);
+    if !ok {
+        return None;
+    }
+
+    let data = &buf[..len];
+    if len == buf.len() {
+        let end = data.iter().rposition(|&c| c == b'\n')?;
+        return Some(&data[..end]);
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(mode: &[u8; 3], uid: u32, gid: u32) -> Rule {
+        Rule { mode: *mode, uid, gid }
+    }
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(parse_rule(b"sss"), Some(rule(b"sss", 0, 0)));
+        assert_eq!(parse_rule(b"ssx root.root"), Some(rule(b"ssx", 0, 0)));
+        assert_eq!(parse_rule(b"s-x 1000"), Some(rule(b"s-x", 1000, 0)));
+        assert_eq!(parse_rule(b"sx- 1000.50"), Some(rule(b"sx-", 1000, 50)));
+        assert_eq!(parse_rule(b"  x--   7.8  "), Some(rule(b"x--", 7, 8)));
+        for bad in [&b""[..], b"ss", b"ssss", b"sqs", b"sss root extra", b"sss 4294967295", b"sss 1.nosuchgroup"] {
+            assert_eq!(parse_rule(bad), None, "{:?}", core::str::from_utf8(bad));
+        }
+    }
+
+    #[test]
+    fn finds_rules_in_the_suid_section() {
+        let config = b"# comment\nsu = sss\n[SUID]\n  mount = ssx 0.10\nping=s--\nbad = nonsense\n[OTHER]\npasswd = sss\n";
+        assert_eq!(rule_in(config, b"su"), None);
+        assert_eq!(rule_in(config, b"mount"), Some(rule(b"ssx", 0, 10)));
+        assert_eq!(rule_in(config, b"ping"), Some(rule(b"s--", 0, 0)));
+        assert_eq!(rule_in(config, b"bad"), None);
+        assert_eq!(rule_in(config, b"passwd"), None);
+        assert_eq!(rule_in(config, b"moun"), None);
+    }
+
+    #[test]
+    fn modes_pick_the_ids() {
+        let mount = rule(b"sx-", 1000, 50);
+        let member = |gid| gid == 50;
+        let outsider = |_| false;
+        // The owner and group keep the rule's ids as effective and saved
+        assert_eq!(privileges(Some(&mount), 1000, outsider), Privileges::Keep { uid: 1000, gid: 50 });
+        assert_eq!(privileges(Some(&mount), 1001, member), Privileges::Drop);
+        assert_eq!(privileges(Some(&mount), 1001, outsider), Privileges::Refuse);
+        assert_eq!(privileges(Some(&rule(b"--s", 0, 0)), 1001, outsider), Privileges::Keep { uid: 0, gid: 0 });
+        assert_eq!(privileges(None, 1001, member), Privileges::Drop);
+    }
+}
@@ -1,5 +1,8 @@
 //! System utilities and helpers
 
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
 use crate::io;
 
 /// Parse decimal number from bytes
@@ -135,38 +138,41 @@ pub fn format_hex(n: u64, buf: &mut [u8]) -> &[u8] {
     &buf[i..]
 }
 
-/// Human-readable size
+/// Human-readable size: bytes under 1024, then `1.5K`, `12M` and so on,
+/// rounded up, with one decimal place under 10
 pub fn format_size(bytes: u64, buf: &mut [u8]) -> &[u8] {
-    const UNITS: &[u8] = b"BKMGTPE";
+    const UNITS: &[u8] = b"KMGTPE";
 
-    let mut size = bytes;
+    if bytes < 1024 {
+        return format_u64(bytes, buf);
+    }
     let mut unit = 0;
-
-    while size >= 1024 && unit < UNITS.len() - 1 {
-        size /= 1024;
+    let mut scale = 1024u128;
+    while bytes as u128 >= scale * 1024 && unit < UNITS.len() - 1 {
+        scale *= 1024;
         unit += 1;
     }
 
-    // Format number first to get its length
-    let num_str = format_u64(size, buf);
-    let num_len = num_str.len();
-
-    // If we have a unit suffix (not bytes), we need to add it
-    if unit > 0 && buf.len() > num_len {
-        // Find start of the formatted number
-        let start = buf.len() - num_len;
-
-        // Shift number left by 1 to make room for unit suffix
-        if start > 0 {
-            for i in 0..num_len {
-                buf[start - 1 + i] = buf[start + i];
-            }
-            buf[start - 1 + num_len] = UNITS[unit];
-            return &buf[start - 1..start + num_len];
-        }
+    let mut out = [0u8; 24];
+    let mut len = 0;
+    let tenths = (bytes as u128 * 10).div_ceil(scale) as u64;
+    let mut digits = [0u8; 20];
+    if tenths < 100 {
+        out[0] = b'0' + (tenths / 10) as u8;
+        out[1] = b'.';
+        out[2] = b'0' + (tenths % 10) as u8;
+        len = 3;
+    } else {
+        let whole = format_u64((bytes as u128).div_ceil(scale) as u64, &mut digits);
+        out[..whole.len()].copy_from_slice(whole);
+        len += whole.len();
     }
+    out[len] = UNITS[unit];
+    len += 1;
 
-    &buf[buf.len() - num_len..]
+    let len = len.min(buf.len());
+    buf[..len].copy_from_slice(&out[..len]);
+    &buf[..len]
 }
 
 /// File mode to permission string
@@ -256,6 +262,15 @@ pub fn clear_errno() {
     unsafe { *libc::__error() = 0; }
 }
 
+/// Set errno
+pub fn set_errno(e: i32) {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unsafe { *libc::__errno_location() = e; }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    unsafe { *libc::__error() = e; }
+}
+
 /// Parse size with optional suffix (K, M, G, T, P, E)
 pub fn parse_size(s: &[u8]) -> Option<u64> {
     if s.is_empty() {
@@ -333,39 +348,612 @@ pub fn format_num(n: u64) -> &'static [u8] {
     }
 }
 
-/// Print errno message
-pub fn perror(prefix: &[u8]) {
-    let e = errno();
-    io::write_all(2, prefix);
-    io::write_str(2, b": ");
+/// Messages for errno values 0..=133, in order and NUL-terminated, with
+/// glibc's wording (which is also what BusyBox prints on glibc systems).
+/// Numbers with no error have an empty entry.
+const MESSAGES: &str = concat!(
+    "Success\0",                                                 // 0
+    "Operation not permitted\0",                                 // EPERM
+    "No such file or directory\0",                               // ENOENT
+    "No such process\0",                                         // ESRCH
+    "Interrupted system call\0",                                 // EINTR
+    "Input/output error\0",                                      // EIO
+    "No such device or address\0",                               // ENXIO
+    "Argument list too long\0",                                  // E2BIG
+    "Exec format error\0",                                       // ENOEXEC
+    "Bad file descriptor\0",                                     // EBADF
+    "No child processes\0",                                      // ECHILD
+    "Resource temporarily unavailable\0",                        // EAGAIN
+    "Cannot allocate memory\0",                                  // ENOMEM
+    "Permission denied\0",                                       // EACCES
+    "Bad address\0",                                             // EFAULT
+    "Block device required\0",                                   // ENOTBLK
+    "Device or resource busy\0",                                 // EBUSY
+    "File exists\0",                                             // EEXIST
+    "Invalid cross-device link\0",                               // EXDEV
+    "No such device\0",                                          // ENODEV
+    "Not a directory\0",                                         // ENOTDIR
+    "Is a directory\0",                                          // EISDIR
+    "Invalid argument\0",                                        // EINVAL
+    "Too many open files in system\0",                           // ENFILE
+    "Too many open files\0",                                     // EMFILE
+    "Inappropriate ioctl for device\0",                          // ENOTTY
+    "Text file busy\0",                                          // ETXTBSY
+    "File too large\0",                                          // EFBIG
+    "No space left on device\0",                                 // ENOSPC
+    "Illegal seek\0",                                            // ESPIPE
+    "Read-only file system\0",                                   // EROFS
+    "Too many links\0",                                          // EMLINK
+    "Broken pipe\0",                                             // EPIPE
+    "Numerical argument out of domain\0",                        // EDOM
+    "Numerical result out of range\0",                           // ERANGE
+    "Resource deadlock avoided\0",                               // EDEADLK
+    "File name too long\0",                                      // ENAMETOOLONG
+    "No locks available\0",                                      // ENOLCK
+    "Function not implemented\0",                                // ENOSYS
+    "Directory not empty\0",                                     // ENOTEMPTY
+    "Too many levels of symbolic links\0",                       // ELOOP
+    "\0",                                                        // 41
+    "No message of desired type\0",                              // ENOMSG
+    "Identifier removed\0",                                      // EIDRM
+    "Channel number out of range\0",                             // ECHRNG
+    "Level 2 not synchronized\0",                                // EL2NSYNC
+    "Level 3 halted\0",                                          // EL3HLT
+    "Level 3 reset\0",                                           // EL3RST
+    "Link number out of range\0",                                // ELNRNG
+    "Protocol driver not attached\0",                            // EUNATCH
+    "No CSI structure available\0",                              // ENOCSI
+    "Level 2 halted\0",                                          // EL2HLT
+    "Invalid exchange\0",                                        // EBADE
+    "Invalid request descriptor\0",                              // EBADR
+    "Exchange full\0",                                           // EXFULL
+    "No anode\0",                                                // ENOANO
+    "Invalid request code\0",                                    // EBADRQC
+    "Invalid slot\0",                                            // EBADSLT
+    "\0",                                                        // 58
+    "Bad font file format\0",                                    // EBFONT
+    "Device not a stream\0",                                     // ENOSTR
+    "No data available\0",                                       // ENODATA
+    "Timer expired\0",                                           // ETIME
+    "Out of streams resources\0",                                // ENOSR
+    "Machine is not on the network\0",                           // ENONET
+    "Package not installed\0",                                   // ENOPKG
+    "Object is remote\0",                                        // EREMOTE
+    "Link has been severed\0",                                   // ENOLINK
+    "Advertise error\0",                                         // EADV
+    "Srmount error\0",                                           // ESRMNT
+    "Communication error on send\0",                             // ECOMM
+    "Protocol error\0",                                          // EPROTO
+    "Multihop attempted\0",                                      // EMULTIHOP
+    "RFS specific error\0",                                      // EDOTDOT
+    "Bad message\0",                                             // EBADMSG
+    "Value too large for defined data type\0",                   // EOVERFLOW
+    "Name not unique on network\0",                              // ENOTUNIQ
+    "File descriptor in bad state\0",                            // EBADFD
+    "Remote address changed\0",                                  // EREMCHG
+    "Can not access a needed shared library\0",                  // ELIBACC
+    "Accessing a corrupted shared library\0",                    // ELIBBAD
+    ".lib section in a.out corrupted\0",                         // ELIBSCN
+    "Attempting to link in too many shared libraries\0",         // ELIBMAX
+    "Cannot exec a shared library directly\0",                   // ELIBEXEC
+    "Invalid or incomplete multibyte or wide character\0",       // EILSEQ
+    "Interrupted system call should be restarted\0",             // ERESTART
+    "Streams pipe error\0",                                      // ESTRPIPE
+    "Too many users\0",                                          // EUSERS
+    "Socket operation on non-socket\0",                          // ENOTSOCK
+    "Destination address required\0",                            // EDESTADDRREQ
+    "Message too long\0",                                        // EMSGSIZE
+    "Protocol wrong type for socket\0",                          // EPROTOTYPE
+    "Protocol not available\0",                                  // ENOPROTOOPT
+    "Protocol not supported\0",                                  // EPROTONOSUPPORT
+    "Socket type not supported\0",                               // ESOCKTNOSUPPORT
+    "Operation not supported\0",                                 // EOPNOTSUPP
+    "Protocol family not supported\0",                           // EPFNOSUPPORT
+    "Address family not supported by protocol\0",                // EAFNOSUPPORT
+    "Address already in use\0",                                  // EADDRINUSE
+    "Cannot assign requested address\0",                         // EADDRNOTAVAIL
+    "Network is down\0",                                         // ENETDOWN
+    "Network is unreachable\0",                                  // ENETUNREACH
+    "Network dropped connection on reset\0",                     // ENETRESET
+    "Software caused connection abort\0",                        // ECONNABORTED
+    "Connection reset by peer\0",                                // ECONNRESET
+    "No buffer space available\0",                               // ENOBUFS
+    "Transport endpoint is already connected\0",                 // EISCONN
+    "Transport endpoint is not connected\0",                     // ENOTCONN
+    "Cannot send after transport endpoint shutdown\0",           // ESHUTDOWN
+    "Too many references: cannot splice\0",                      // ETOOMANYREFS
+    "Connection timed out\0",                                    // ETIMEDOUT
+    "Connection refused\0",                                      // ECONNREFUSED
+    "Host is down\0",                                            // EHOSTDOWN
+    "No route to host\0",                                        // EHOSTUNREACH
+    "Operation already in progress\0",                           // EALREADY
+    "Operation now in progress\0",                               // EINPROGRESS
+    "Stale file handle\0",                                       // ESTALE
+    "Structure needs cleaning\0",                                // EUCLEAN
+    "Not a XENIX named type file\0",                             // ENOTNAM
+    "No XENIX semaphores available\0",                           // ENAVAIL
+    "Is a named type file\0",                                    // EISNAM
+    "Remote I/O error\0",                                        // EREMOTEIO
+    "Disk quota exceeded\0",                                     // EDQUOT
+    "No medium found\0",                                         // ENOMEDIUM
+    "Wrong medium type\0",                                       // EMEDIUMTYPE
+    "Operation canceled\0",                                      // ECANCELED
+    "Required key not available\0",                              // ENOKEY
+    "Key has expired\0",                                         // EKEYEXPIRED
+    "Key has been revoked\0",                                    // EKEYREVOKED
+    "Key was rejected by service\0",                             // EKEYREJECTED
+    "Owner died\0",                                              // EOWNERDEAD
+    "State not recoverable\0",                                   // ENOTRECOVERABLE
+    "Operation not possible due to RF-kill\0",                   // ERFKILL
+    "Memory page has hardware error\0",                          // EHWPOISON
+);
+
+// The table follows the errno numbering shared by every Linux port armybox
+// builds for (alpha, mips, parisc and sparc number theirs differently)
+const _: () = assert!(libc::EDEADLK == 35 && libc::ELOOP == 40 && libc::EHWPOISON == 133);
+
+/// Message for an errno value, or `"Unknown error"`
+pub fn strerror(e: i32) -> &'static [u8] {
+    let mut rest = MESSAGES.as_bytes();
+    if e >= 0 {
+        for _ in 0..e {
+            match rest.iter().position(|&c| c == 0) {
+                Some(nul) => rest = &rest[nul + 1..],
+                None => break,
+            }
+        }
+        let len = rest.iter().position(|&c| c == 0).unwrap_or(0);
+        if len > 0 {
+            return &rest[..len];
+        }
+    }
+    b"Unknown error"
+}
 
-    // Common errno messages
-    let msg = match e {
-        libc::ENOENT => b"No such file or directory" as &[u8],
-        libc::EACCES => b"Permission denied",
-        libc::EEXIST => b"File exists",
-        libc::EISDIR => b"Is a directory",
-        libc::ENOTDIR => b"Not a directory",
-        libc::ENOTEMPTY => b"Directory not empty",
-        libc::EBUSY => b"Device or resource busy",
-        libc::EINVAL => b"Invalid argument",
-        libc::ENOMEM => b"Out of memory",
-        libc::ENOSPC => b"No space left on device",
-        libc::EPERM => b"Operation not permitted",
-        libc::EROFS => b"Read-only file system",
-        libc::EMFILE => b"Too many open files",
-        libc::ENFILE => b"File table overflow",
-        libc::EBADF => b"Bad file descriptor",
-        libc::EAGAIN => b"Resource temporarily unavailable",
-        libc::EINTR => b"Interrupted system call",
-        libc::EIO => b"Input/output error",
-        libc::ENODEV => b"No such device",
-        libc::ENXIO => b"No such device or address",
-        _ => b"Unknown error",
-    };
+/// Write the message for `e` to `fd`, numbering unknown errors as
+/// `strerror(3)` does
+fn write_strerror(fd: i32, e: i32) {
+    let msg = strerror(e);
+    io::write_all(fd, msg);
+    if msg == b"Unknown error" {
+        let mut buf = [0u8; 20];
+        io::write_str(fd, b" ");
+        io::write_all(fd, format_i64(e as i64, &mut buf));
+    }
+}
+
+// ============================================================================
+// Data roots
+//
+// A few files can be looked for elsewhere through the environment: the
+// account files under `$ARMYBOX_ROOT`, the process table in `$ARMYBOX_PROC`
+// and zone files in `$TZDIR`. Tests point them at fixtures. A setuid or
+// setgid armybox ignores them, so whoever runs it can't feed it forged
+// accounts, processes or zones.
+// ============================================================================
+
+/// Whether the effective user or group differs from the real one
+pub fn is_setuid() -> bool {
+    unsafe { libc::geteuid() != libc::getuid() || libc::getegid() != libc::getgid() }
+}
+
+/// The directory environment variable `var` names, without trailing `/`s
+/// (so `/` is empty); `None` if it is unset or empty, or when running
+/// setuid
+pub fn data_root(var: &[u8]) -> Option<&'static [u8]> {
+    if is_setuid() {
+        return None;
+    }
+    let root = io::getenv(var).filter(|root| !root.is_empty())?;
+    let end = root.iter().rposition(|&c| c != b'/').map_or(0, |i| i + 1);
+    Some(&root[..end])
+}
+
+// ============================================================================
+// Diagnostics
+//
+// Every applet reports problems on stderr as `applet: message`, or as
+// `applet: object: message` when there is a file, device or operation to
+// name. The applet name carries no directory, as in BusyBox, so scripts that
+// match on BusyBox's stderr keep working.
+// Anything already buffered for stdout is flushed first, so messages appear
+// in order when both go to the same place.
+// ============================================================================
+
+/// Print `applet: msg`
+pub fn error(applet: &[u8], msg: &[u8]) {
+    io::stdout().flush();
+    io::write_all(2, applet);
+    io::write_str(2, b": ");
+    io::write_all(2, msg);
+    io::write_str(2, b"\n");
+}
 
+/// Print `applet: object: msg`
+pub fn error_at(applet: &[u8], object: &[u8], msg: &[u8]) {
+    io::stdout().flush();
+    io::write_all(2, applet);
+    io::write_str(2, b": ");
+    io::write_all(2, object);
+    io::write_str(2, b": ");
     io::write_all(2, msg);
-    io::write_str(2, b" (errno ");
-    io::write_num(2, e as u64);
-    io::write_str(2, b")\n");
+    io::write_str(2, b"\n");
+}
+
+/// Print `applet: object: ` and the message for `errno`
+pub fn error_errno(applet: &[u8], object: &[u8], errno: i32) {
+    io::stdout().flush();
+    io::write_all(2, applet);
+    io::write_str(2, b": ");
+    io::write_all(2, object);
+    io::write_str(2, b": ");
+    write_strerror(2, errno);
+    io::write_str(2, b"\n");
+}
+
+/// Print `applet: object: ` and the message for the current `errno`
+pub fn perror(applet: &[u8], object: &[u8]) {
+    error_errno(applet, object, errno());
+}
+
+// ============================================================================
+// Cleanup
+//
+// Some applets leave something behind if they stop half way: the temporary
+// file `sed -i` writes beside the one it edits, a terminal in raw mode, a
+// child that should not outlive them. Each registers a `Cleanup` for it,
+// whose action runs when it is dropped, when the applet ends through
+// `io::exit`, and on SIGHUP, SIGINT, SIGQUIT, SIGTERM and SIGPIPE. The
+// handler then puts back the signal's default action and raises it again,
+// so the process still dies by the signal and its parent sees the status it
+// expects. A signal that was ignored when the first action was registered
+// stays ignored, as under nohup.
+//
+// SIGPIPE keeps its default, so a writer whose reader has gone dies quietly
+// by the signal, after its actions have run. If the parent ignored SIGPIPE,
+// a write fails with EPIPE instead: on stdout that ends the process quietly
+// with status 1, also after the actions (see `io::BufWriter`); any other
+// output error is reported by the applet as usual.
+//
+// Actions run in a signal handler, so they only make async-signal-safe
+// calls. A forked child inherits the registered actions but never runs them.
+// ============================================================================
+
+/// Actions that can be registered at once
+const CLEANUP_SLOTS: usize = 8;
+
+/// The signals that run the actions
+const CLEANUP_SIGNALS: [i32; 5] = [libc::SIGHUP, libc::SIGINT, libc::SIGQUIT, libc::SIGTERM, libc::SIGPIPE];
+
+// What a slot holds
+const FREE: u8 = 0;
+const FILLING: u8 = 1;
+const REMOVE: u8 = 2;
+const TERMINAL: u8 = 3;
+const KILL: u8 = 4;
+
+struct Slot {
+    action: AtomicU8,
+    /// The process that registered it
+    owner: AtomicI32,
+    /// The terminal's fd, or the process to signal
+    target: AtomicI32,
+    signal: AtomicI32,
+    /// NUL-terminated
+    path: UnsafeCell<[u8; 4096]>,
+    termios: UnsafeCell<libc::termios>,
+}
+
+struct Slots {
+    slots: [Slot; CLEANUP_SLOTS],
+    /// Registered actions, to install the handler for the first and put the
+    /// old actions back after the last
+    armed: AtomicUsize,
+    saved: UnsafeCell<[libc::sigaction; CLEANUP_SIGNALS.len()]>,
+}
+
+// Applets run on a single thread; a slot's data is written before its
+// action is set, and the handler only reads slots with an action
+unsafe impl Sync for Slots {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    action: AtomicU8::new(FREE),
+    owner: AtomicI32::new(0),
+    target: AtomicI32::new(-1),
+    signal: AtomicI32::new(0),
+    path: UnsafeCell::new([0; 4096]),
+    termios: UnsafeCell::new(unsafe { core::mem::zeroed() }),
+};
+
+static CLEANUP: Slots = Slots {
+    slots: [EMPTY_SLOT; CLEANUP_SLOTS],
+    armed: AtomicUsize::new(0),
+    saved: UnsafeCell::new(unsafe { core::mem::zeroed() }),
+};
+
+extern "C" fn on_cleanup_signal(sig: i32) {
+    run_cleanups();
+    // The signal is delivered again, to kill, once this returns
+    unsafe {
+        let mut action: libc::sigaction = core::mem::zeroed();
+        action.sa_sigaction = libc::SIG_DFL;
+        libc::sigaction(sig, &action, core::ptr::null_mut());
+        libc::kill(libc::getpid(), sig);
+    }
+}
+
+/// Runs the action in `slot`, unless it has already run or belongs to
+/// another process
+fn run_slot(slot: &Slot) {
+    let action = slot.action.swap(FREE, Ordering::Acquire);
+    if action < REMOVE || slot.owner.load(Ordering::Relaxed) != unsafe { libc::getpid() } {
+        return;
+    }
+    unsafe {
+        match action {
+            REMOVE => {
+                let path = (*slot.path.get()).as_ptr().cast();
+                if libc::unlink(path) < 0 && errno() == libc::EISDIR {
+                    libc::rmdir(path);
+                }
+            }
+            TERMINAL => {
+                libc::tcsetattr(slot.target.load(Ordering::Relaxed), libc::TCSANOW, slot.termios.get());
+            }
+            _ => {
+                libc::kill(slot.target.load(Ordering::Relaxed), slot.signal.load(Ordering::Relaxed));
+            }
+        }
+    }
+}
+
+/// Runs every registered action, newest first, before the process ends
+pub fn run_cleanups() {
+    for slot in CLEANUP.slots.iter().rev() {
+        run_slot(slot);
+    }
+}
+
+/// Something to undo if the applet stops before it is done; see above.
+/// Dropping it runs the action, `disarm` forgets it.
+pub struct Cleanup {
+    /// `CLEANUP_SLOTS` if nothing was registered
+    slot: usize,
+}
+
+impl Cleanup {
+    /// Removes the file or empty directory at `path`
+    pub fn remove(path: &[u8]) -> Cleanup {
+        if path.len() >= 4096 || path.contains(&0) {
+            return Cleanup::none();
+        }
+        Cleanup::register(REMOVE, |slot| unsafe {
+            let buf = &mut *slot.path.get();
+            buf[..path.len()].copy_from_slice(path);
+            buf[path.len()] = 0;
+        })
+    }
+
+    /// Puts back the terminal settings `saved` on `fd`
+    pub fn terminal(fd: i32, saved: &libc::termios) -> Cleanup {
+        Cleanup::register(TERMINAL, |slot| {
+            slot.target.store(fd, Ordering::Relaxed);
+            unsafe { *slot.termios.get() = *saved };
+        })
+    }
+
+    /// Sends `signal` to process `pid`
+    pub fn kill(pid: i32, signal: i32) -> Cleanup {
+        Cleanup::register(KILL, |slot| {
+            slot.target.store(pid, Ordering::Relaxed);
+            slot.signal.store(signal, Ordering::Relaxed);
+        })
+    }
+
+    /// Forgets the action without running it, once there is nothing left
+    /// to undo
+    pub fn disarm(mut self) {
+        if let Some(slot) = CLEANUP.slots.get(self.slot) {
+            slot.action.store(FREE, Ordering::Release);
+            Cleanup::release();
+        }
+        self.slot = CLEANUP_SLOTS;
+    }
+
+    const fn none() -> Cleanup {
+        Cleanup { slot: CLEANUP_SLOTS }
+    }
+
+    /// Fills a free slot and sets its action; if all are taken the action
+    /// only runs on drop
+    fn register(action: u8, fill: impl FnOnce(&Slot)) -> Cleanup {
+        let index = CLEANUP.slots.iter().position(|slot| {
+            slot.action.compare_exchange(FREE, FILLING, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        });
+        let index = match index {
+            Some(index) => index,
+            None => return Cleanup::none(),
+        };
+        let slot = &CLEANUP.slots[index];
+        slot.owner.store(unsafe { libc::getpid() }, Ordering::Relaxed);
+        fill(slot);
+        if CLEANUP.armed.fetch_add(1, Ordering::Relaxed) == 0 {
+            let saved = unsafe { &mut *CLEANUP.saved.get() };
+            for (&sig, old) in CLEANUP_SIGNALS.iter().zip(saved.iter_mut()) {
+                let mut action: libc::sigaction = unsafe { core::mem::zeroed() };
+                action.sa_sigaction = on_cleanup_signal as *const () as usize;
+                unsafe {
+                    libc::sigaction(sig, core::ptr::null(), old);
+                    if old.sa_sigaction != libc::SIG_IGN {
+                        libc::sigaction(sig, &action, core::ptr::null_mut());
+                    }
+                }
+            }
+        }
+        slot.action.store(action, Ordering::Release);
+        Cleanup { slot: index }
+    }
+
+    /// Puts the old signal actions back once nothing is registered
+    fn release() {
+        if CLEANUP.armed.fetch_sub(1, Ordering::Relaxed) == 1 {
+            let saved = unsafe { &*CLEANUP.saved.get() };
+            for (&sig, old) in CLEANUP_SIGNALS.iter().zip(saved.iter()) {
+                unsafe { libc::sigaction(sig, old, core::ptr::null_mut()) };
+            }
+        }
+    }
+}
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        if let Some(slot) = CLEANUP.slots.get(self.slot) {
+            run_slot(slot);
+            Cleanup::release();
+        }
+    }
+}
+
+/// Creates a file, or a directory with `dir`, named by `template` with its
+/// trailing `X`s replaced, mode 0600 or 0700. `template` is left holding
+/// the name. Returns the file's descriptor (0 for a directory), or -1 with
+/// `errno` set; `EINVAL` if there are fewer than three `X`s.
+pub fn make_temp(template: &mut [u8], dir: bool) -> i32 {
+    const CHARS: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let xs = template.iter().rev().take_while(|&&c| c == b'X').count();
+    if xs < 3 {
+        set_errno(libc::EINVAL);
+        return -1;
+    }
+    let start = template.len() - xs;
+    for _ in 0..100 {
+        let mut ts: libc::timespec = unsafe { core::mem::zeroed() };
+        unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) };
+        let mut n = (ts.tv_nsec as u64 ^ ((ts.tv_sec as u64) << 30) ^ ((unsafe { libc::getpid() } as u64) << 40))
+            .wrapping_add(COUNTER.fetch_add(1, Ordering::Relaxed).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        for c in &mut template[start..] {
+            // An xorshift step per character
+            n ^= n << 13;
+            n ^= n >> 7;
+            n ^= n << 17;
+            *c = CHARS[(n % CHARS.len() as u64) as usize];
+        }
+        let ret = if dir {
+            io::mkdir(template, 0o700)
+        } else {
+            io::open(template, libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC, 0o600)
+        };
+        if ret >= 0 || errno() != libc::EEXIST {
+            return ret;
+        }
+    }
+    -1
+}
+
+/// A new version of the file at a path, written to a temporary file beside
+/// it and renamed over it by `commit`, so the file holds the old contents
+/// or the new and never part of either. The temporary file has the old
+/// file's mode and owner, and is removed if the replacement is dropped or
+/// the applet is interrupted before `commit`.
+///
+/// Only a regular file, or a path with nothing there yet, is replaced. A
+/// device, FIFO or symlink is left in place and written through instead:
+/// the first call to `fd` opens and truncates it, so anything to be read
+/// from it must be read before then.
+pub struct Replacement<'a> {
+    path: &'a [u8],
+    temp: [u8; 4096],
+    /// 0 when writing through `path` itself
+    temp_len: usize,
+    fd: i32,
+    cleanup: Cleanup,
+}
+
+impl<'a> Replacement<'a> {
+    /// Creates the temporary file, or returns the `errno` of the failure
+    pub fn new(path: &'a [u8]) -> Result<Replacement<'a>, i32> {
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        let exists = io::lstat(path, &mut st) == 0;
+        if exists && st.st_mode & libc::S_IFMT != libc::S_IFREG {
+            if st.st_mode & libc::S_IFMT == libc::S_IFDIR {
+                return Err(libc::EISDIR);
+            }
+            return Ok(Replacement { path, temp: [0; 4096], temp_len: 0, fd: -1, cleanup: Cleanup::none() });
+        }
+
+        // .NAME.XXXXXX in the same directory, so the rename can't cross
+        // filesystems
+        let name_start = path.iter().rposition(|&c| c == b'/').map_or(0, |i| i + 1);
+        let name = &path[name_start..];
+        let len = name_start + 1 + name.len() + 7;
+        let mut temp = [0u8; 4096];
+        if name.is_empty() || len >= temp.len() {
+            return Err(if name.is_empty() { libc::EISDIR } else { libc::ENAMETOOLONG });
+        }
+        temp[..name_start].copy_from_slice(&path[..name_start]);
+        temp[name_start] = b'.';
+        temp[name_start + 1..len - 7].copy_from_slice(name);
+        temp[len - 7..len].copy_from_slice(b".XXXXXX");
+
+        let fd = make_temp(&mut temp[..len], false);
+        if fd < 0 {
+            return Err(errno());
+        }
+        let replacement = Replacement { path, temp, temp_len: len, fd, cleanup: Cleanup::remove(&temp[..len]) };
+
+        unsafe {
+            if exists {
+                // Only root may give the file away; otherwise it stays ours.
+                // The mode goes after, since chown clears set-id bits.
+                if st.st_uid != libc::geteuid() || st.st_gid != libc::getegid() {
+                    libc::fchown(fd, st.st_uid, st.st_gid);
+                }
+                libc::fchmod(fd, st.st_mode & 0o7777);
+            } else {
+                let umask = libc::umask(0);
+                libc::umask(umask);
+                libc::fchmod(fd, 0o666 & !umask);
+            }
+        }
+        Ok(replacement)
+    }
+
+    /// Where to write the new contents, or the `errno` of the failure to
+    /// open a path written through
+    pub fn fd(&mut self) -> Result<i32, i32> {
+        if self.fd < 0 {
+            self.fd = io::open(self.path, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC, 0o666);
+            if self.fd < 0 {
+                return Err(errno());
+            }
+        }
+        Ok(self.fd)
+    }
+
+    /// Puts the new contents in place, or returns the `errno` of the
+    /// failure, leaving the old file as it was
+    pub fn commit(mut self) -> Result<(), i32> {
+        let fd = core::mem::replace(&mut self.fd, -1);
+        if fd >= 0 && io::close(fd) < 0 {
+            return Err(errno());
+        }
+        if self.temp_len > 0 {
+            if io::rename(&self.temp[..self.temp_len], self.path) < 0 {
+                return Err(errno());
+            }
+            core::mem::replace(&mut self.cleanup, Cleanup::none()).disarm();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Replacement<'_> {
+    fn drop(&mut self) {
+        if self.fd >= 0 {
+            io::close(self.fd);
+        }
+    }
 }
@@ -140,6 +140,93 @@ proptest! {
     }
 }
 
+// =============================================================================
+// Option Parsing Tests
+// =============================================================================
+
+proptest! {
+    /// -n N, -nN, --lines=N, --lines N and -N should all mean the same thing,
+    /// wherever they appear relative to the operand
+    #[test]
+    fn head_option_spellings_agree(
+        lines in prop::collection::vec("[^\n]{1,50}", 1..100),
+        n in 1usize..50
+    ) {
+        let content = lines.join("\n") + "\n";
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(content.as_bytes()).unwrap();
+        let path = temp.path().to_str().unwrap();
+        let n = n.to_string();
+
+        let spellings: [Vec<String>; 5] = [
+            vec!["-n".into(), n.clone(), path.into()],
+            vec![format!("-n{}", n), path.into()],
+            vec![format!("--lines={}", n), path.into()],
+            vec![path.into(), "--lines".into(), n.clone()],
+            vec![format!("-{}", n), path.into()],
+        ];
+
+        let mut outputs = spellings.iter().map(|args| {
+            Command::new(armybox())
+                .arg("head")
+                .args(args)
+                .output()
+                .unwrap()
+                .stdout
+        });
+        let first = outputs.next().unwrap();
+        for other in outputs {
+            prop_assert_eq!(&first, &other);
+        }
+    }
+
+    /// An unknown option should be rejected with a diagnostic, not treated
+    /// as an operand
+    #[test]
+    fn unknown_option_rejected(c in "[ABD-Z]") {
+        let output = Command::new(armybox())
+            .args(["cut", &format!("-{}", c)])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        prop_assert_eq!(output.status.code(), Some(1));
+        prop_assert_eq!(
+            stderr.lines().next().unwrap_or(""),
+            format!("cut: invalid option -- '{}'", c)
+        );
+    }
+}
+
+/// cut rejects a bad LIST, or a missing or conflicting one, as a usage
+/// error instead of falling back to the first field
+#[test]
+fn cut_rejects_bad_lists() {
+    let mut cases: Vec<Vec<String>> = Vec::new();
+    for list in ["0", "1,,2", ",2", "2,", "", "-", "3-1", "a", "1-2-3", "+1", "99999999999999999999999"] {
+        cases.push(vec!["-f".into(), list.into()]);
+        cases.push(vec!["-c".into(), list.into()]);
+    }
+    cases.push(vec![]);
+    cases.push(vec!["-f1".into(), "-c1".into()]);
+    cases.push(vec!["-d".into(), "ab".into(), "-f1".into()]);
+    cases.push(vec!["-d:".into(), "-c1".into()]);
+    cases.push(vec!["-s".into(), "-b1".into()]);
+
+    for args in cases {
+        let output = Command::new(armybox())
+            .arg("cut")
+            .args(&args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1), "cut {:?}", args);
+        assert!(output.stdout.is_empty(), "cut {:?}", args);
+        assert!(output.stderr.starts_with(b"cut: "), "cut {:?}", args);
+    }
+}
+
 // =============================================================================
 // WC Tests
 // =============================================================================
@@ -351,147 +438,2071 @@ proptest! {
 }
 
 // =============================================================================
-// Rev/Tac Tests
+// Buffered Output Tests
 // =============================================================================
 
 proptest! {
-    /// rev should reverse each line
+    /// Output that spans many buffer flushes should arrive complete and in order
     #[test]
-    fn rev_reverses_lines(lines in prop::collection::vec("[a-zA-Z0-9]{1,50}", 1..20)) {
+    fn seq_output_survives_buffering(n in 1u64..50000) {
+        let output = Command::new(armybox())
+            .args(["seq", &n.to_string()])
+            .output()
+            .unwrap();
+
+        let expected: String = (1..=n).map(|i| format!("{}\n", i)).collect();
+        prop_assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+
+    /// tr writes one byte at a time into the buffer; nothing should be lost
+    #[test]
+    fn tr_preserves_length(content in prop::collection::vec(any::<u8>(), 0..20000)) {
+        let mut child = Command::new(armybox())
+            .args(["tr", "a-z", "A-Z"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        child.stdin.take().unwrap().write_all(&content).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        prop_assert_eq!(output.stdout.len(), content.len());
+    }
+}
+
+// =============================================================================
+// Long Line Tests
+// =============================================================================
+
+fn run_with_stdin(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(armybox())
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap().stdout
+}
+
+proptest! {
+    /// Lines far longer than the read buffer should come through whole
+    #[test]
+    fn grep_matches_long_lines(len in 4000usize..40000, tail in "[a-z]{1,20}") {
+        let line = "x".repeat(len) + "needle" + &tail + "\n";
+        let output = run_with_stdin(&["grep", "needle"], line.as_bytes());
+        prop_assert_eq!(output, line.into_bytes());
+    }
+
+    /// sed should substitute past the first 4 KiB of a line
+    #[test]
+    fn sed_substitutes_in_long_lines(len in 4000usize..40000) {
+        let line = "x".repeat(len) + "old\n";
+        let output = run_with_stdin(&["sed", "s/old/new/"], line.as_bytes());
+        prop_assert_eq!(output, ("x".repeat(len) + "new\n").into_bytes());
+    }
+
+    /// A missing final newline should not be invented
+    #[test]
+    fn missing_final_newline_preserved(lines in prop::collection::vec("[a-z]{1,20}", 1..10)) {
+        let content = lines.join("\n");
+        let output = run_with_stdin(&["sed", "s/q/q/"], content.as_bytes());
+        prop_assert_eq!(&output, content.as_bytes());
+
+        let output = run_with_stdin(&["rev"], content.as_bytes());
+        prop_assert_eq!(output.last() == Some(&b'\n'), false);
+    }
+
+    /// grep -z should select NUL-terminated records
+    #[test]
+    fn grep_null_data(records in prop::collection::vec("[a-c\n]{1,10}", 1..10)) {
+        let content: Vec<u8> = records.iter().flat_map(|r| r.bytes().chain([0])).collect();
+        let output = run_with_stdin(&["grep", "-z", "a"], &content);
+
+        let expected: Vec<u8> = records
+            .iter()
+            .filter(|r| r.contains('a'))
+            .flat_map(|r| r.bytes().chain([0]))
+            .collect();
+        prop_assert_eq!(output, expected);
+    }
+
+    /// grep selects the lines matching any of its -e patterns
+    #[test]
+    fn grep_matches_any_pattern(
+        lines in prop::collection::vec("[a-d]{1,8}", 1..20),
+        patterns in prop::collection::vec("[a-d]{1,3}", 1..4),
+    ) {
         let content = lines.join("\n") + "\n";
-        let mut temp = NamedTempFile::new().unwrap();
-        temp.write_all(content.as_bytes()).unwrap();
-        let path = temp.path().to_str().unwrap();
+        let mut expected = String::new();
+        for line in lines.iter().filter(|l| patterns.iter().any(|p| l.contains(p.as_str()))) {
+            expected += line;
+            expected += "\n";
+        }
+
+        for syntax in ["-G", "-E", "-F"] {
+            let mut args = vec!["grep", syntax];
+            for pattern in &patterns {
+                args.extend(["-e", pattern.as_str()]);
+            }
+            let output = run_with_stdin(&args, content.as_bytes());
+            prop_assert_eq!(String::from_utf8_lossy(&output), expected.as_str());
+        }
+        let output = run_with_stdin(&["grep", &patterns.join("\n")], content.as_bytes());
+        prop_assert_eq!(String::from_utf8_lossy(&output), expected.as_str());
+    }
+}
+
+// =============================================================================
+// Multi-file Input Tests
+// =============================================================================
+
+proptest! {
+    /// grep should search every file and prefix matches with the file name
+    #[test]
+    fn grep_searches_all_files(files in prop::collection::vec(prop::collection::vec("[a-c]{1,8}", 1..10), 2..5)) {
+        let temps: Vec<NamedTempFile> = files.iter().map(|lines| {
+            let mut temp = NamedTempFile::new().unwrap();
+            temp.write_all((lines.join("\n") + "\n").as_bytes()).unwrap();
+            temp
+        }).collect();
+        let paths: Vec<&str> = temps.iter().map(|t| t.path().to_str().unwrap()).collect();
 
         let output = Command::new(armybox())
-            .args(["rev", path])
+            .arg("grep")
+            .arg("a")
+            .args(&paths)
             .output()
             .unwrap();
 
-        let output_string = String::from_utf8_lossy(&output.stdout);
-        let output_lines: Vec<&str> = output_string.lines().collect();
-
-        for (orig, rev) in lines.iter().zip(output_lines.iter()) {
-            let expected: String = orig.chars().rev().collect();
-            prop_assert_eq!(*rev, expected.as_str());
+        let mut expected = String::new();
+        for (path, lines) in paths.iter().zip(&files) {
+            for line in lines.iter().filter(|l| l.contains('a')) {
+                expected += &format!("{}:{}\n", path, line);
+            }
         }
+        prop_assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
     }
 
-    /// tac should reverse line order
+    /// A missing file is reported, the rest are still read, and the
+    /// exit status records the failure
     #[test]
-    fn tac_reverses_line_order(lines in prop::collection::vec("[a-z]{1,20}", 1..50)) {
-        let content = lines.join("\n") + "\n";
+    fn missing_file_reported_and_skipped(content in "[a-z]{1,20}\n") {
         let mut temp = NamedTempFile::new().unwrap();
         temp.write_all(content.as_bytes()).unwrap();
         let path = temp.path().to_str().unwrap();
 
         let output = Command::new(armybox())
-            .args(["tac", path])
+            .args(["sed", "s/x/x/", "/nonexistent/file", path])
             .output()
             .unwrap();
 
-        let output_lines: Vec<_> = String::from_utf8_lossy(&output.stdout)
+        prop_assert_eq!(String::from_utf8_lossy(&output.stdout), content);
+        prop_assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "sed: /nonexistent/file: No such file or directory\n"
+        );
+        prop_assert!(!output.status.success());
+    }
+
+    /// wc should print a total matching the sum of the per-file counts
+    #[test]
+    fn wc_total_is_sum(a in "[a-z \n]{0,50}", b in "[a-z \n]{0,50}") {
+        let mut ta = NamedTempFile::new().unwrap();
+        ta.write_all(a.as_bytes()).unwrap();
+        let mut tb = NamedTempFile::new().unwrap();
+        tb.write_all(b.as_bytes()).unwrap();
+
+        let output = Command::new(armybox())
+            .args(["wc", "-c", ta.path().to_str().unwrap(), tb.path().to_str().unwrap()])
+            .output()
+            .unwrap();
+
+        let output_string = String::from_utf8_lossy(&output.stdout);
+        let total = output_string.lines().last().unwrap();
+        prop_assert_eq!(total, format!("{} total", a.len() + b.len()));
+    }
+}
+
+// =============================================================================
+// Applet Status Tests
+// =============================================================================
+
+proptest! {
+    /// Any applet listed as a stub should refuse to run rather than succeed
+    #[test]
+    fn stubs_report_not_implemented(index in any::<prop::sample::Index>()) {
+        let list = Command::new(armybox())
+            .args(["--list", "--status"])
+            .output()
+            .unwrap();
+        let list = String::from_utf8_lossy(&list.stdout);
+        let stubs: Vec<&str> = list
             .lines()
-            .map(String::from)
+            .filter_map(|line| line.strip_suffix("\tstub"))
             .collect();
+        prop_assume!(!stubs.is_empty());
 
-        let mut expected = lines.clone();
-        expected.reverse();
-        prop_assert_eq!(output_lines, expected);
+        let name = index.get(&stubs);
+        let output = Command::new(armybox())
+            .arg(name)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+
+        prop_assert_eq!(output.status.code(), Some(69));
+        prop_assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("{}: not implemented\n", name)
+        );
+    }
+}
+
+/// The applets `--list --status` gives, split into stubs and the rest
+fn applets_by_status() -> (Vec<String>, Vec<String>) {
+    let list = Command::new(armybox()).args(["--list", "--status"]).output().unwrap();
+    let list = String::from_utf8_lossy(&list.stdout).into_owned();
+    let (mut stubs, mut others) = (Vec::new(), Vec::new());
+    for line in list.lines() {
+        if let Some((name, status)) = line.split_once('\t') {
+            if status == "stub" { stubs.push(name.to_string()) } else { others.push(name.to_string()) }
+        }
     }
+    stubs.sort();
+    others.sort();
+    (stubs, others)
+}
+
+#[test]
+fn install_leaves_out_stubs() {
+    let (stubs, others) = applets_by_status();
+    assert!(!stubs.is_empty());
+    let links = |extra: &[&str]| {
+        let dir = tempfile::TempDir::new().unwrap();
+        let status = Command::new(armybox()).arg("--install").args(extra).arg(dir.path()).stdout(Stdio::null()).status().unwrap();
+        assert!(status.success());
+        let mut names: Vec<String> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    };
+    assert_eq!(links(&[]), others);
+    let mut all = [stubs, others].concat();
+    all.sort();
+    assert_eq!(links(&["--stubs"]), all);
+}
+
+/// A setuid-root copy of armybox with no `/etc/armybox.conf` drops to the
+/// caller's ids, saved ones included, before the applet runs
+#[test]
+fn setuid_applets_drop_privileges_without_a_policy() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::process::CommandExt;
+    if unsafe { libc::geteuid() } != 0 || std::path::Path::new("/etc/armybox.conf").exists() {
+        return;
+    }
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let copy = dir.path().join("armybox");
+    std::fs::copy(armybox(), &copy).unwrap();
+    std::fs::set_permissions(&copy, std::fs::Permissions::from_mode(0o4755)).unwrap();
+
+    let output = Command::new(&copy).args(["cat", "/proc/self/status"]).uid(65534).gid(65534).output().unwrap();
+    let status = String::from_utf8_lossy(&output.stdout);
+    let ids = |key: &str| status.lines().find_map(|line| line.strip_prefix(key)).map(|ids| ids.split_whitespace().collect::<Vec<_>>());
+    assert_eq!(ids("Uid:"), Some(vec!["65534"; 4]));
+    assert_eq!(ids("Gid:"), Some(vec!["65534"; 4]));
 }
 
 // =============================================================================
-// Checksum Tests
+// Applet Help Tests
 // =============================================================================
 
 proptest! {
-    /// checksums should be deterministic
+    /// `APPLET --help`, `armybox --help APPLET` and `help APPLET` agree
     #[test]
-    fn md5sum_deterministic(data in prop::collection::vec(any::<u8>(), 0..10000)) {
-        let mut temp = NamedTempFile::new().unwrap();
-        temp.write_all(&data).unwrap();
-        let path = temp.path().to_str().unwrap();
-
-        let output1 = Command::new(armybox())
-            .args(["md5sum", path])
+    fn help_forms_agree(index in any::<prop::sample::Index>()) {
+        let list = Command::new(armybox())
+            .args(["--list", "--status"])
             .output()
             .unwrap();
+        let list = String::from_utf8_lossy(&list.stdout);
+        let names: Vec<&str> = list
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .filter(|(_, status)| *status != "stub")
+            .map(|(name, _)| name)
+            .filter(|name| !matches!(*name, ":" | "[" | "echo" | "false" | "test" | "true"))
+            .collect();
+        prop_assume!(!names.is_empty());
 
-        let output2 = Command::new(armybox())
-            .args(["md5sum", path])
+        let name = *index.get(&names);
+        let direct = Command::new(armybox())
+            .args([name, "--help"])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        let via_option = Command::new(armybox())
+            .args(["--help", name])
+            .output()
+            .unwrap();
+        let via_applet = Command::new(armybox())
+            .args(["help", name])
             .output()
             .unwrap();
 
-        prop_assert_eq!(output1.stdout, output2.stdout);
+        prop_assert!(direct.status.success());
+        let usage = format!("Usage: {}", name);
+        prop_assert!(String::from_utf8_lossy(&direct.stdout).starts_with(&usage));
+        prop_assert_eq!(&direct.stdout, &via_option.stdout);
+        prop_assert_eq!(&direct.stdout, &via_applet.stdout);
     }
 
-    /// different data should produce different checksums (with high probability)
+    /// An invalid option prints the short usage line on stderr
     #[test]
-    fn md5sum_different_for_different_input(
-        data1 in prop::collection::vec(any::<u8>(), 10..1000),
-        data2 in prop::collection::vec(any::<u8>(), 10..1000)
-    ) {
-        prop_assume!(data1 != data2);
+    fn invalid_option_shows_usage(option in "--zz[a-z]{1,8}") {
+        let output = Command::new(armybox())
+            .args(["ls", option.as_str()])
+            .output()
+            .unwrap();
 
-        let mut temp1 = NamedTempFile::new().unwrap();
-        temp1.write_all(&data1).unwrap();
-        let path1 = temp1.path().to_str().unwrap();
+        prop_assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        prop_assert!(stderr.lines().any(|line| line.starts_with("Usage: ls ")));
+    }
+}
 
-        let mut temp2 = NamedTempFile::new().unwrap();
-        temp2.write_all(&data2).unwrap();
-        let path2 = temp2.path().to_str().unwrap();
+// =============================================================================
+// Diagnostic Tests
+// =============================================================================
 
-        let output1 = Command::new(armybox())
-            .args(["md5sum", path1])
+proptest! {
+    /// A missing file is reported as `applet: path: message` and the applet
+    /// fails
+    #[test]
+    fn missing_file_diagnostic(
+        applet in prop::sample::select(vec!["cat", "ls", "rm", "rmdir", "stat", "realpath"]),
+        name in "[a-z]{1,12}",
+    ) {
+        let path = format!("/nonexistent/{}", name);
+        let output = Command::new(armybox())
+            .args([applet, path.as_str()])
+            .stdin(Stdio::null())
             .output()
             .unwrap();
 
-        let output2 = Command::new(armybox())
-            .args(["md5sum", path2])
+        prop_assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("{}: {}: No such file or directory\n", applet, path)
+        );
+        prop_assert!(!output.status.success());
+    }
+
+    /// A malformed `test` expression is an error (2), not false (1)
+    #[test]
+    fn test_malformed_is_trouble(word in "[a-z]{1,8}") {
+        let output = Command::new(armybox())
+            .args(["test", "1", "-eq", word.as_str()])
             .output()
             .unwrap();
 
-        // Extract just the hash (first field)
-        let stdout1 = String::from_utf8_lossy(&output1.stdout);
-        let hash1 = stdout1.split_whitespace().next().unwrap_or("");
-        let stdout2 = String::from_utf8_lossy(&output2.stdout);
-        let hash2 = stdout2.split_whitespace().next().unwrap_or("");
+        prop_assert_eq!(output.status.code(), Some(2));
+        prop_assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("test: {}: integer expression expected\n", word)
+        );
+    }
 
-        prop_assert_ne!(hash1, hash2);
+    /// A command xargs can't find gives status 127
+    #[test]
+    fn xargs_missing_command(name in "[a-z]{8,12}") {
+        let mut child = Command::new(armybox())
+            .args(["xargs", format!("/nonexistent/{}", name).as_str()])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"x\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        prop_assert_eq!(output.status.code(), Some(127));
     }
 }
 
 // =============================================================================
-// Compression Roundtrip Tests
+// Command Runner Tests
 // =============================================================================
 
 proptest! {
-    /// gzip/gunzip roundtrip should preserve data
+    /// env passes NAME=VALUE on to the command it runs
     #[test]
-    fn gzip_roundtrip(data in prop::collection::vec(any::<u8>(), 1..10000)) {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let input_path = temp_dir.path().join("input");
-        std::fs::write(&input_path, &data).unwrap();
+    fn env_sets_variable(value in "[a-zA-Z0-9]{1,20}") {
+        let armybox = armybox();
+        let assignment = format!("ARMYBOX_TEST={}", value);
+        let output = Command::new(&armybox)
+            .args(["env", "-i", assignment.as_str(), armybox.as_str(), "printenv", "ARMYBOX_TEST"])
+            .output()
+            .unwrap();
 
-        // Compress
-        let status = Command::new(armybox())
-            .args(["gzip", "-k", input_path.to_str().unwrap()])
-            .status()
+        prop_assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{}\n", value));
+        prop_assert!(output.status.success());
+    }
+
+    /// timeout exits with the command's status when it finishes in time
+    #[test]
+    fn timeout_keeps_status(code in 0u8..100) {
+        let script = format!("exit {}", code);
+        let output = Command::new(armybox())
+            .args(["timeout", "10", "sh", "-c", script.as_str()])
+            .output()
             .unwrap();
 
-        if !status.success() {
-            return Ok(());
+        prop_assert_eq!(output.status.code(), Some(code as i32));
+    }
+}
+
+/// timeout exits with 124 when the command runs too long
+#[test]
+fn timeout_expires() {
+    let armybox = armybox();
+    let output = Command::new(&armybox)
+        .args(["timeout", "1", armybox.as_str(), "sleep", "10"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(124));
+}
+
+/// armybox built with the `arena` (1M, so it runs out quickly) and
+/// `prefer-applets` features, which the default build leaves out. Built
+/// once per run, into its own target dir.
+fn feature_build() -> &'static std::path::Path {
+    static BINARY: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+    BINARY.get_or_init(|| {
+        let target = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("target/feature-tests");
+        let output = Command::new(env!("CARGO"))
+            .args(["build", "--release", "--features", "arena,prefer-applets"])
+            .env("CARGO_TARGET_DIR", &target)
+            .env("ARMYBOX_ARENA_SIZE", "1M")
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        target.join("release/armybox")
+    })
+}
+
+/// With prefer-applets, a NOFORK applet run by xargs runs in the xargs
+/// process itself, and other applets replace the process that would have
+/// executed them, all without PATH
+#[test]
+fn preferred_applets_skip_fork_and_path() {
+    let armybox = feature_build();
+    let mut input = NamedTempFile::new().unwrap();
+    writeln!(input, "/proc/self").unwrap();
+    let script = "echo $$; exec \"$0\" xargs readlink <\"$1\"";
+    let pids = |prefer: &str| {
+        let output = Command::new("sh")
+            .args(["-c", script])
+            .arg(armybox)
+            .arg(input.path())
+            .env("ARMYBOX_PREFER_APPLETS", prefer)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout.lines().map(str::to_string).collect::<Vec<_>>()
+    };
+    let nofork = pids("1");
+    assert_eq!(nofork.len(), 2);
+    assert_eq!(nofork[0], nofork[1]);
+    let forked = pids("0");
+    assert_eq!(forked.len(), 2);
+    assert_ne!(forked[0], forked[1]);
+
+    let exe = Command::new(armybox)
+        .args(["env", "readlink", "/proc/self/exe"])
+        .env("PATH", "/nonexistent")
+        .output()
+        .unwrap();
+    assert!(exe.status.success());
+    assert_eq!(std::path::Path::new(String::from_utf8_lossy(&exe.stdout).trim_end()), armybox.canonicalize().unwrap());
+
+    let missing = Command::new(armybox)
+        .args(["env", "readlink", "/proc/self/exe"])
+        .env("PATH", "/nonexistent")
+        .env("ARMYBOX_PREFER_APPLETS", "0")
+        .output()
+        .unwrap();
+    assert_eq!(missing.status.code(), Some(127));
+}
+
+// =============================================================================
+// Allocator Tests
+// =============================================================================
+
+/// Input a bit bigger than the test build's 1M arena
+fn megabytes_of_lines() -> Vec<u8> {
+    (0..200_000u64).flat_map(|i| format!("{:08}\n", i * 7919 % 1_000_003).into_bytes()).collect()
+}
+
+/// An exhausted arena is reported and exits 1, where malloc copes
+#[test]
+fn arena_exhaustion_exits_1() {
+    let input = megabytes_of_lines();
+    let sort = |armybox: &std::path::Path, input: &[u8]| {
+        let mut child = Command::new(armybox)
+            .arg("sort")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // sort may exit before reading it all
+        let _ = child.stdin.take().unwrap().write_all(input);
+        child.wait_with_output().unwrap()
+    };
+
+    let arena = sort(feature_build(), &input);
+    assert_eq!(arena.status.code(), Some(1));
+    assert_eq!(arena.stderr, b"armybox: out of memory\n");
+
+    let small = sort(feature_build(), b"b\na\n");
+    assert!(small.status.success());
+    assert_eq!(small.stdout, b"a\nb\n");
+
+    let malloc = sort(std::path::Path::new(&armybox()), &input);
+    assert!(malloc.status.success());
+    assert_eq!(malloc.stdout.len(), input.len());
+}
+
+// =============================================================================
+// Regex Tests
+// =============================================================================
+
+proptest! {
+    /// grep -F and grep with the pattern's special characters escaped
+    /// select the same lines
+    #[test]
+    fn grep_escaped_matches_fixed(lines in prop::collection::vec("[a-c.*^$]{0,8}", 1..20), needle in "[a-c.*^$]{1,3}") {
+        let content = lines.join("\n") + "\n";
+        let escaped: String = needle.chars().flat_map(|c| {
+            if ".*^$".contains(c) { vec!['\\', c] } else { vec![c] }
+        }).collect();
+
+        let fixed = run_with_stdin(&["grep", "-F", needle.as_str()], content.as_bytes());
+        let basic = run_with_stdin(&["grep", escaped.as_str()], content.as_bytes());
+        prop_assert_eq!(fixed, basic);
+    }
+
+    /// sed 's/x*/-/g' puts one - between every pair of characters
+    #[test]
+    fn sed_empty_matches(line in "[a-w]{0,20}") {
+        let input = format!("{}\n", line);
+        let output = run_with_stdin(&["sed", "s/x*/-/g"], input.as_bytes());
+
+        let mut expected = String::from("-");
+        for c in line.chars() {
+            expected.push(c);
+            expected.push('-');
         }
+        expected.push('\n');
+        prop_assert_eq!(String::from_utf8_lossy(&output), expected);
+    }
 
-        let gz_path = format!("{}.gz", input_path.to_str().unwrap());
+    /// sed -E swaps two groups with back-references in the replacement
+    #[test]
+    fn sed_swaps_groups(a in "[a-z]{1,10}", b in "[0-9]{1,10}") {
+        let input = format!("{}={}\n", a, b);
+        let output = run_with_stdin(&["sed", "-E", "s/([a-z]+)=([0-9]+)/\\2=\\1/"], input.as_bytes());
+        prop_assert_eq!(String::from_utf8_lossy(&output), format!("{}={}\n", b, a));
+    }
 
-        // Decompress
+    /// expr STRING : REGEX prints the length of an anchored match
+    #[test]
+    fn expr_match_length(prefix in "[a-c]{0,10}", rest in "[x-z]{0,10}") {
+        let string = format!("{}{}", prefix, rest);
         let output = Command::new(armybox())
-            .args(["zcat", &gz_path])
+            .args(["expr", string.as_str(), ":", "[a-c]*"])
             .output()
             .unwrap();
 
-        prop_assert_eq!(&output.stdout, &data);
+        prop_assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{}\n", prefix.len()));
+        prop_assert_eq!(output.status.code(), Some(if prefix.is_empty() { 1 } else { 0 }));
+    }
+}
+
+/// sed runs a single s command and refuses anything else rather than
+/// passing the input through as if it had run
+#[test]
+fn sed_rejects_unsupported_scripts() {
+    for args in [
+        &["s/a/b/; s/c/d/"][..],
+        &["s/a/b/\ns/c/d/"],
+        &["p"],
+        &["d"],
+        &["1d"],
+        &["/a/p"],
+        &["$d"],
+        &["y/abc/xyz/"],
+        &["-n", "s/a/b/"],
+        &["-e", "s/a/b/", "-e", "s/c/d/"],
+    ] {
+        let mut child = Command::new(armybox())
+            .arg("sed")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let _ = child.stdin.take().unwrap().write_all(b"abc\n");
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(1), "sed {:?}", args);
+        assert!(output.stdout.is_empty(), "sed {:?}", args);
+        assert!(output.stderr.starts_with(b"sed: "), "sed {:?}", args);
     }
+
+    for (script, expected) in [("s/a/X/", "Xbc\n"), (" s/b/X/g ;", "aXc\n"), ("", "abc\n")] {
+        assert_eq!(run_with_stdin(&["sed", script], b"abc\n"), expected.as_bytes(), "sed {:?}", script);
+    }
+}
+
+/// A pattern that backtracking handles in exponential time runs quickly
+#[test]
+fn grep_nested_repeats_are_linear() {
+    let line = "a".repeat(5000) + "\n";
+    let start = std::time::Instant::now();
+    let output = run_with_stdin(&["grep", "-E", "(a|aa)*c"], line.as_bytes());
+
+    assert!(output.is_empty());
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+// =============================================================================
+// Wildcard Tests
+// =============================================================================
+
+/// The names among `names` that libc's fnmatch matches with `pattern`
+fn libc_fnmatch(pattern: &str, names: &std::collections::BTreeSet<String>, flags: i32) -> Vec<String> {
+    let pattern = std::ffi::CString::new(pattern).unwrap();
+    names
+        .iter()
+        .filter(|name| {
+            let name = std::ffi::CString::new(name.as_str()).unwrap();
+            unsafe { libc::fnmatch(pattern.as_ptr(), name.as_ptr(), flags) == 0 }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Base names of the files `find DIR ARGS...` prints, sorted
+fn find_names(dir: &std::path::Path, args: &[&str]) -> Vec<String> {
+    let output = Command::new(armybox())
+        .arg("find")
+        .arg(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    let mut names: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|path| path.rsplit('/').next().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+proptest! {
+    /// find -name selects the same names as libc's fnmatch
+    #[test]
+    fn find_name_matches_libc_fnmatch(
+        names in prop::collection::btree_set("[a-cA-C.]{1,6}", 1..20),
+        pattern in "([a-cA-C.*?]|\\[!?([a-c]|[a-c]-[a-c]|\\[:upper:\\])+\\]){1,5}",
+    ) {
+        let names: std::collections::BTreeSet<String> =
+            names.into_iter().filter(|n| n != "." && n != "..").collect();
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in &names {
+            std::fs::File::create(dir.path().join(name)).unwrap();
+        }
+
+        prop_assert_eq!(find_names(dir.path(), &["-name", &pattern]), libc_fnmatch(&pattern, &names, 0));
+        prop_assert_eq!(
+            find_names(dir.path(), &["-iname", &pattern]),
+            libc_fnmatch(&pattern, &names, libc::FNM_CASEFOLD)
+        );
+    }
+
+    /// find -path and -ipath select the same paths as libc's fnmatch, for
+    /// patterns with slashes, escapes, brackets and classes
+    #[test]
+    fn find_path_matches_libc_fnmatch(
+        files in prop::collection::btree_set("[a-bA-B]{1,2}/[a-bA-B.\\\\]{1,3}", 1..10),
+        pattern in "\\.(/|[aAbB.*?]|\\\\[aB./*?]|\\[!?([ab/.]|a-b|\\[:upper:\\])+\\]){1,8}",
+    ) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut paths = std::collections::BTreeSet::new();
+        for file in files.iter().filter(|f| !f.ends_with("/.") && !f.ends_with("/..")) {
+            let (parent, _) = file.split_once('/').unwrap();
+            std::fs::create_dir_all(dir.path().join(parent)).unwrap();
+            std::fs::File::create(dir.path().join(file)).unwrap();
+            paths.insert(format!("./{}", parent));
+            paths.insert(format!("./{}", file));
+        }
+
+        let find_paths = |test: &str| {
+            let output = Command::new(armybox())
+                .args(["find", ".", test, pattern.as_str()])
+                .current_dir(dir.path())
+                .output()
+                .unwrap();
+            let mut found: Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect();
+            found.sort();
+            found
+        };
+        prop_assert_eq!(find_paths("-path"), libc_fnmatch(&pattern, &paths, 0));
+        prop_assert_eq!(find_paths("-ipath"), libc_fnmatch(&pattern, &paths, libc::FNM_CASEFOLD));
+    }
+}
+
+// =============================================================================
+// UTF-8 Tests
+// =============================================================================
+
+/// Run armybox with `input` on stdin and `LC_ALL` set to `locale`
+fn run_in_locale(locale: &str, args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(armybox())
+        .args(args)
+        .env("LC_ALL", locale)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap().stdout
+}
+
+proptest! {
+    /// wc -m counts characters in a UTF-8 locale and bytes in the C locale
+    #[test]
+    fn wc_m_counts_characters(text in "[a-z \n\u{e9}\u{436}\u{65e5}\u{1f600}]{0,60}") {
+        let chars = String::from_utf8(run_in_locale("C.UTF-8", &["wc", "-m"], text.as_bytes())).unwrap();
+        prop_assert_eq!(chars.trim(), text.chars().count().to_string());
+        let bytes = String::from_utf8(run_in_locale("C", &["wc", "-m"], text.as_bytes())).unwrap();
+        prop_assert_eq!(bytes.trim(), text.len().to_string());
+    }
+
+    /// rev reverses characters, not bytes, and keeps invalid bytes
+    #[test]
+    fn rev_reverses_utf8(
+        line in "[a-z\u{e9}\u{436}\u{65e5}\u{1f600}]{0,30}",
+        junk in prop::collection::vec(any::<u8>().prop_filter("not a newline", |&b| b != b'\n'), 0..30),
+    ) {
+        let output = run_in_locale("C.UTF-8", &["rev"], format!("{}\n", line).as_bytes());
+        let expected: String = line.chars().rev().collect::<String>() + "\n";
+        prop_assert_eq!(String::from_utf8_lossy(&output), expected);
+
+        let output = run_in_locale("C.UTF-8", &["rev"], &junk);
+        let (mut sorted_out, mut sorted_in) = (output.clone(), junk.clone());
+        sorted_out.sort();
+        sorted_in.sort();
+        prop_assert_eq!(sorted_out, sorted_in);
+    }
+
+    /// fold never splits a character or lets a wide one run past WIDTH
+    #[test]
+    fn fold_counts_wide_characters(line in "[a\u{e9}\u{65e5}]{0,40}", width in 2usize..10) {
+        let output = run_in_locale("C.UTF-8", &["fold", "-w", &width.to_string()], format!("{}\n", line).as_bytes());
+        let output = String::from_utf8(output).unwrap();
+        for piece in output.lines() {
+            let columns: usize = piece.chars().map(|c| if c == '\u{65e5}' { 2 } else { 1 }).sum();
+            prop_assert!(columns <= width);
+        }
+        prop_assert_eq!(output.replace('\n', ""), line);
+    }
+}
+
+/// A `cut` LIST of up to four positions and ranges
+fn cut_list() -> impl Strategy<Value = String> {
+    let item = (1usize..8, 0usize..4, 0u8..4).prop_map(|(n, len, form)| match form {
+        0 => n.to_string(),
+        1 => format!("{}-{}", n, n + len),
+        2 => format!("{}-", n),
+        _ => format!("-{}", n),
+    });
+    prop::collection::vec(item, 1..5).prop_map(|items| items.join(","))
+}
+
+/// Run coreutils `cut` (from PATH) with `input` on stdin
+fn coreutils_cut(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new("cut")
+        .args(args)
+        .env("LC_ALL", "C.UTF-8")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap().stdout
+}
+
+proptest! {
+    /// cut -b and -f pick the same bytes and fields as coreutils, and -c
+    /// the same characters as -b would pick bytes in ASCII
+    #[test]
+    fn cut_lists_match_coreutils(
+        lines in prop::collection::vec("[a-c:\u{e9}\u{65e5}]{0,12}", 1..4),
+        list in cut_list(),
+    ) {
+        let input = lines.join("\n") + "\n";
+        let fields = format!("-f{}", list);
+        for args in [["-b", &list], ["-f", &list], ["-d:", &fields]] {
+            let mut cut = vec!["cut"];
+            cut.extend(args);
+            prop_assert_eq!(run_in_locale("C.UTF-8", &cut, input.as_bytes()), coreutils_cut(&args, input.as_bytes()));
+        }
+
+        let ascii = input.replace('\u{e9}', "e").replace('\u{65e5}', "d");
+        prop_assert_eq!(
+            run_in_locale("C.UTF-8", &["cut", "-c", &list], ascii.as_bytes()),
+            coreutils_cut(&["-c", &list], ascii.as_bytes())
+        );
+    }
+
+    /// cut -c counts characters, not bytes, in a UTF-8 locale
+    #[test]
+    fn cut_c_selects_characters(line in "[a-c\u{e9}\u{65e5}\u{1f600}]{0,12}", list in cut_list()) {
+        let selected = |n: usize| list.split(',').any(|item| {
+            let (first, last) = item.split_once('-').unwrap_or((item, item));
+            first.parse().unwrap_or(1) <= n && n <= last.parse().unwrap_or(usize::MAX)
+        });
+        let expected: String = line.chars().zip(1..).filter(|&(_, n)| selected(n)).map(|(c, _)| c).collect();
+
+        let output = run_in_locale("C.UTF-8", &["cut", "-c", &list], format!("{}\n", line).as_bytes());
+        prop_assert_eq!(String::from_utf8_lossy(&output), expected + "\n");
+    }
+}
+
+// =============================================================================
+// Account Tests
+// =============================================================================
+
+proptest! {
+    /// id looks users and their groups up in ARMYBOX_ROOT/etc
+    #[test]
+    fn id_resolves_users_and_groups(
+        uids in prop::collection::btree_set(1u32..60000, 1..8),
+        membership in prop::collection::vec(prop::collection::vec(any::<bool>(), 8), 1..6),
+    ) {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(root.path().join("etc")).unwrap();
+        let users: Vec<(String, u32)> = uids.iter().enumerate().map(|(i, &uid)| (format!("user{}", i), uid)).collect();
+        let passwd: String = users
+            .iter()
+            .map(|(name, uid)| format!("{}:x:{}:{}::/home/{}:/bin/sh\n", name, uid, uid, name))
+            .collect();
+        // Each user's primary group has its uid; group N has gid 70000+N
+        let mut group: String = users.iter().map(|(name, uid)| format!("{}:x:{}:\n", name, uid)).collect();
+        for (n, members) in membership.iter().enumerate() {
+            let names: Vec<&str> = users
+                .iter()
+                .zip(members)
+                .filter(|(_, member)| **member)
+                .map(|((name, _), _)| name.as_str())
+                .collect();
+            group += &format!("grp{}:x:{}:{}\n", n, 70000 + n, names.join(","));
+        }
+        std::fs::write(root.path().join("etc/passwd"), passwd).unwrap();
+        std::fs::write(root.path().join("etc/group"), group).unwrap();
+
+        for (i, (name, uid)) in users.iter().enumerate() {
+            let run = |args: &[&str]| {
+                let output = Command::new(armybox())
+                    .arg("id")
+                    .args(args)
+                    .arg(name)
+                    .env("ARMYBOX_ROOT", root.path())
+                    .output()
+                    .unwrap();
+                String::from_utf8(output.stdout).unwrap()
+            };
+            prop_assert_eq!(run(&["-u"]), format!("{}\n", uid));
+            prop_assert_eq!(run(&["-un"]), format!("{}\n", name));
+
+            let mut expected = vec![name.clone()];
+            for (n, members) in membership.iter().enumerate() {
+                if members[i] {
+                    expected.push(format!("grp{}", n));
+                }
+            }
+            prop_assert_eq!(run(&["-Gn"]), expected.join(" ") + "\n");
+        }
+    }
+}
+
+// =============================================================================
+// Date Tests
+// =============================================================================
+
+/// Run `date` with `TZ` set, and `TZDIR` if given
+fn run_date(tz: &str, tzdir: Option<&std::path::Path>, args: &[&str]) -> String {
+    let mut command = Command::new(armybox());
+    command.arg("date").args(args).env("TZ", tz);
+    if let Some(dir) = tzdir {
+        command.env("TZDIR", dir);
+    }
+    String::from_utf8(command.output().unwrap().stdout).unwrap()
+}
+
+/// A version 1 or 2 TZif file that starts in type AAA and switches between
+/// it and BBB at each of `transitions`
+fn tzif(version: u8, transitions: &[i64], offsets: [i32; 2]) -> Vec<u8> {
+    let block = |wide: bool| {
+        let mut data = b"TZif".to_vec();
+        data.push(version);
+        data.extend([0; 15]);
+        for count in [0, 0, 0, transitions.len() as u32, 2, 8] {
+            data.extend(count.to_be_bytes());
+        }
+        for &t in transitions {
+            if wide {
+                data.extend(t.to_be_bytes());
+            } else {
+                data.extend((t as i32).to_be_bytes());
+            }
+        }
+        data.extend((0..transitions.len()).map(|i| ((i + 1) % 2) as u8));
+        for (i, offset) in offsets.iter().enumerate() {
+            data.extend(offset.to_be_bytes());
+            data.extend([i as u8, 4 * i as u8]);
+        }
+        data.extend(b"AAA\0BBB\0");
+        data
+    };
+    let mut file = block(false);
+    if version >= b'2' {
+        file.extend(block(true));
+        file.extend(b"\n\n");
+    }
+    file
+}
+
+/// `+hhmm` for an offset in seconds
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("{}{:02}{:02}", sign, offset.abs() / 3600, offset.abs() / 60 % 60)
+}
+
+proptest! {
+    /// Dates printed in UTC read back as the same time, on the right day of
+    /// the week, for every year from 1 to 9999
+    #[test]
+    fn date_roundtrips_utc(t in -62135596800i64..253402300800) {
+        let printed = run_date("UTC", None, &["-d", &format!("@{}", t), "+%Y-%m-%d %H:%M:%S %u"]);
+        let (date, weekday) = printed.trim_end().rsplit_once(' ').unwrap();
+        // 1970-01-01 was a Thursday
+        prop_assert_eq!(weekday, ((t.div_euclid(86400) + 3).rem_euclid(7) + 1).to_string());
+        let read = run_date("UTC", None, &["-d", date, "+%s"]);
+        prop_assert_eq!(read.trim_end(), t.to_string());
+    }
+
+    /// Local times under a TZ string read back as a time that prints the
+    /// same, across the changes to and from daylight saving time
+    #[test]
+    fn date_roundtrips_local(t in 1_000_000_000i64..2_000_000_000) {
+        let tz = "CET-1CEST,M3.5.0,M10.5.0/3";
+        let printed = run_date(tz, None, &["-d", &format!("@{}", t), "+%F %T"]);
+        let read = run_date(tz, None, &["-d", printed.trim_end(), "+%s"]);
+        let read: i64 = read.trim_end().parse().unwrap();
+        // The hour repeated in October may read as its first occurrence
+        prop_assert!(read == t || read == t - 3600);
+        let reprinted = run_date(tz, None, &["-d", &format!("@{}", read), "+%F %T"]);
+        prop_assert_eq!(reprinted, printed);
+    }
+
+    /// TZif files are read: the type in force is that of the last
+    /// transition at or before the time, or the first before any
+    #[test]
+    fn date_reads_tzif(
+        transitions in prop::collection::btree_set(-2_000_000_000i64..2_000_000_000, 0..20),
+        minutes in [-840i32..840, -840i32..840],
+        times in prop::collection::vec(-2_000_000_000i64..2_000_000_000, 1..5),
+        wide in any::<bool>(),
+    ) {
+        let transitions: Vec<i64> = transitions.into_iter().collect();
+        let offsets = minutes.map(|m| m * 60);
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("Test"), tzif(if wide { b'2' } else { 0 }, &transitions, offsets)).unwrap();
+        for t in times.iter().copied().chain(transitions.iter().copied()) {
+            let count = transitions.iter().filter(|&&at| at <= t).count();
+            let (offset, name) = if count % 2 == 0 { (offsets[0], "AAA") } else { (offsets[1], "BBB") };
+            let printed = run_date("Test", Some(dir.path()), &["-d", &format!("@{}", t), "+%z %Z"]);
+            prop_assert_eq!(printed, format!("{} {}\n", format_offset(offset), name));
+        }
+    }
+
+    /// A zone file and the TZ string for its current rules agree
+    #[test]
+    fn date_zone_file_matches_rule(t in 1_300_000_000i64..2_100_000_000) {
+        if !std::path::Path::new("/usr/share/zoneinfo/Europe/Berlin").exists() {
+            return Ok(());
+        }
+        let args = ["-d", &format!("@{}", t), "+%F %T %z %Z"];
+        prop_assert_eq!(run_date("Europe/Berlin", None, &args), run_date("CET-1CEST,M3.5.0,M10.5.0/3", None, &args));
+    }
+
+    /// touch -d sets the modification time stat reports
+    #[test]
+    fn touch_sets_time(t in 0i64..4_000_000_000, nsec in 0u32..1_000_000_000) {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let status = Command::new(armybox())
+            .args(["touch", "-d", &format!("@{}.{:09}", t, nsec), path])
+            .status()
+            .unwrap();
+        prop_assert!(status.success());
+        let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+        prop_assert_eq!(modified, std::time::UNIX_EPOCH + std::time::Duration::new(t as u64, nsec));
+    }
+
+    /// cal starts each month on the weekday date gives its first day
+    #[test]
+    fn cal_agrees_with_date(month in 1u32..=12, year in 1753u32..=9999) {
+        let output = Command::new(armybox()).args(["cal", &month.to_string(), &year.to_string()]).output().unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        let first_week = output.lines().nth(2).unwrap();
+        let column = first_week.find(" 1").map_or(0, |i| i + 1) / 3;
+        let weekday = run_date("UTC", None, &["-d", &format!("{:04}-{:02}-01", year, month), "+%w"]);
+        prop_assert_eq!(column.to_string(), weekday.trim_end());
+    }
+}
+
+#[test]
+fn cal_skips_calendar_reform() {
+    let output = Command::new(armybox()).args(["cal", "9", "1752"]).output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "   September 1752\nSu Mo Tu We Th Fr Sa\n       1  2 14 15 16\n17 18 19 20 21 22 23\n24 25 26 27 28 29 30\n\n\n\n"
+    );
+}
+
+// =============================================================================
+// Process Tests
+// =============================================================================
+
+/// A process to put in a fake /proc
+#[derive(Debug, Clone)]
+struct FakeProcess {
+    ppid: u32,
+    comm: String,
+    args: Vec<String>,
+    uid: u32,
+    nice: i32,
+    vsize: u64,
+    state: char,
+}
+
+fn fake_process() -> impl Strategy<Value = FakeProcess> {
+    (
+        0u32..8,
+        "[a-z][a-z0-9]{0,9}",
+        prop::collection::vec("[a-z0-9=-]{1,8}", 0..4),
+        0u32..3,
+        -20i32..20,
+        0u64..1 << 40,
+        prop::sample::select(vec!['R', 'S', 'D', 'T', 'Z']),
+    )
+        .prop_map(|(ppid, comm, args, uid, nice, vsize, state)| FakeProcess { ppid, comm, args, uid, nice, vsize, state })
+}
+
+/// Writes a /proc with the processes at `pids` into a temporary directory
+fn proc_fixture(pids: &[u32], procs: &[FakeProcess]) -> tempfile::TempDir {
+    let root = tempfile::TempDir::new().unwrap();
+    std::fs::write(root.path().join("uptime"), "1000.00 2000.00\n").unwrap();
+    std::fs::write(root.path().join("meminfo"), "MemTotal:        1000000 kB\n").unwrap();
+    for (pid, p) in pids.iter().zip(procs) {
+        let dir = root.path().join(pid.to_string());
+        std::fs::create_dir(&dir).unwrap();
+        let stat = format!(
+            "{} ({}) {} {} {} {} 0 -1 4194560 0 0 0 0 10 5 0 0 20 {} 1 0 100 {} 0 18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 17 0 0 0 0\n",
+            pid, p.comm, p.state, p.ppid, pid, pid, p.nice, p.vsize,
+        );
+        std::fs::write(dir.join("stat"), stat).unwrap();
+        let status = format!("Name:\t{}\nUid:\t{u}\t{u}\t{u}\t{u}\nGid:\t{u}\t{u}\t{u}\t{u}\n", p.comm, u = p.uid);
+        std::fs::write(dir.join("status"), status).unwrap();
+        let mut cmdline = String::new();
+        for arg in std::iter::once(format!("/usr/bin/{}", p.comm)).chain(p.args.iter().cloned()) {
+            cmdline += &arg;
+            cmdline.push('\0');
+        }
+        std::fs::write(dir.join("cmdline"), cmdline).unwrap();
+        std::os::unix::fs::symlink(format!("/srv/{}", pid), dir.join("cwd")).unwrap();
+    }
+    root
+}
+
+/// Runs an applet against a fake /proc, returning its output's lines sorted
+fn run_with_proc(root: &tempfile::TempDir, args: &[&str]) -> Vec<String> {
+    let output = Command::new(armybox()).args(args).env("ARMYBOX_PROC", root.path()).output().unwrap();
+    let mut lines: Vec<String> = String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect();
+    lines.sort();
+    lines
+}
+
+proptest! {
+    /// ps reports each field of a process's stat and status
+    #[test]
+    fn ps_reads_proc_fixture(
+        pids in prop::collection::btree_set(1_000_000u32..4_000_000, 1..6),
+        procs in prop::collection::vec(fake_process(), 6),
+    ) {
+        let pids: Vec<u32> = pids.into_iter().collect();
+        let root = proc_fixture(&pids, &procs);
+        let fields = ["-o", "pid=", "-o", "ppid=", "-o", "uid=", "-o", "ni=", "-o", "s=", "-o", "vsz=", "-o", "time=", "-o", "args="];
+        let mut args = vec!["ps"];
+        args.extend(fields);
+        let mut expected: Vec<String> = pids
+            .iter()
+            .zip(&procs)
+            .map(|(pid, p)| {
+                let cmdline = std::iter::once(format!("/usr/bin/{}", p.comm)).chain(p.args.iter().cloned()).collect::<Vec<_>>().join(" ");
+                format!("{:>5} {:>5} {:>5} {:>3} {} {:>7} 00:00:00 {}", pid, p.ppid, p.uid, p.nice, p.state, p.vsize / 1024, cmdline)
+            })
+            .collect();
+        expected.sort();
+        prop_assert_eq!(run_with_proc(&root, &args), expected);
+
+        let mut args = vec!["pwdx"];
+        let pid_args: Vec<String> = pids.iter().map(u32::to_string).collect();
+        args.extend(pid_args.iter().map(String::as_str));
+        let mut expected: Vec<String> = pids.iter().map(|pid| format!("{}: /srv/{}", pid, pid)).collect();
+        expected.sort();
+        prop_assert_eq!(run_with_proc(&root, &args), expected);
+    }
+
+    /// pgrep and pidof select the same processes as their criteria do
+    #[test]
+    fn pgrep_selects_fixture_processes(
+        pids in prop::collection::btree_set(1_000_000u32..4_000_000, 1..8),
+        procs in prop::collection::vec(fake_process(), 8),
+        which in 0usize..8,
+    ) {
+        let pids: Vec<u32> = pids.into_iter().collect();
+        let root = proc_fixture(&pids, &procs);
+        let target = &procs[which % pids.len()];
+        let select = |keep: &dyn Fn(&FakeProcess) -> bool| -> Vec<String> {
+            let mut pids: Vec<String> = pids.iter().zip(&procs).filter(|(_, p)| keep(p)).map(|(pid, _)| pid.to_string()).collect();
+            pids.sort();
+            pids
+        };
+
+        let by_name = select(&|p| p.comm == target.comm);
+        prop_assert_eq!(run_with_proc(&root, &["pgrep", "-x", &target.comm]), by_name.clone());
+        let pidof = run_with_proc(&root, &["pidof", &target.comm]);
+        let mut pidof: Vec<String> = pidof.iter().flat_map(|line| line.split(' ')).map(String::from).collect();
+        pidof.sort();
+        prop_assert_eq!(pidof, by_name);
+
+        let ppid = target.ppid.to_string();
+        prop_assert_eq!(run_with_proc(&root, &["pgrep", "-P", &ppid]), select(&|p| p.ppid == target.ppid));
+        let uid = target.uid.to_string();
+        prop_assert_eq!(run_with_proc(&root, &["pgrep", "-U", &uid, "-v", &target.comm]), select(&|p| p.uid == target.uid && !p.comm.contains(&target.comm)));
+        let count = select(&|p| p.uid == target.uid).len();
+        prop_assert_eq!(run_with_proc(&root, &["pgrep", "-c", "-u", &uid]), vec![count.to_string()]);
+    }
+}
+
+// =============================================================================
+// Terminal Tests
+// =============================================================================
+
+/// Runs armybox on a new 24x80 pty. Once the first screen is drawn it
+/// types `input`, then sends `signal` if given. Returns how it exited and
+/// the terminal's local modes before and after.
+fn run_on_pty(args: &[&str], input: &[u8], signal: Option<i32>) -> (std::process::ExitStatus, u32, u32) {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::process::ExitStatusExt;
+
+    let (mut master, mut slave) = (0, 0);
+    let size = libc::winsize { ws_row: 24, ws_col: 80, ws_xpixel: 0, ws_ypixel: 0 };
+    let ret = unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &size) };
+    assert_eq!(ret, 0);
+    let lflag = |fd| {
+        let mut t: libc::termios = unsafe { std::mem::zeroed() };
+        unsafe { libc::tcgetattr(fd, &mut t) };
+        t.c_lflag
+    };
+    let before = lflag(slave);
+
+    let tty = unsafe { std::fs::File::from_raw_fd(slave) };
+    let mut child = Command::new(armybox())
+        .args(args)
+        .stdin(tty.try_clone().unwrap())
+        .stdout(tty.try_clone().unwrap())
+        .stderr(tty.try_clone().unwrap())
+        .spawn()
+        .unwrap();
+
+    // Drain the output, saying when the header is on screen
+    let mut reader = unsafe { std::fs::File::from_raw_fd(libc::dup(master)) };
+    let (drawn, on_screen) = std::sync::mpsc::channel();
+    let drain = std::thread::spawn(move || {
+        let (mut seen, mut buf) = (Vec::new(), [0u8; 4096]);
+        while let Ok(n) = reader.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            seen.extend_from_slice(&buf[..n]);
+            if seen.windows(7).any(|w| w == b"COMMAND") {
+                let _ = drawn.send(());
+            }
+        }
+    });
+    on_screen.recv_timeout(std::time::Duration::from_secs(10)).expect("nothing drawn");
+
+    let mut writer = unsafe { std::fs::File::from_raw_fd(master) };
+    writer.write_all(input).unwrap();
+    if let Some(signal) = signal {
+        unsafe { libc::kill(child.id() as i32, signal) };
+    }
+    let start = std::time::Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if start.elapsed() > std::time::Duration::from_secs(10) {
+            child.kill().unwrap();
+            break std::process::ExitStatus::from_raw(-1);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    let after = lflag(slave);
+    drop(tty);
+    drop(writer);
+    let _ = drain.join();
+    (status, before as u32, after as u32)
+}
+
+proptest! {
+    /// top on a terminal takes keys as they are typed, quits on q and
+    /// leaves the terminal in line mode
+    #[test]
+    fn top_quits_and_restores_terminal(
+        keys in prop::collection::vec(prop::sample::select(vec!["P", "M", "N", " ", "\x1b[A", "\x1b[6~", "\x1bOP"]), 0..4),
+    ) {
+        let input = format!("{}q", keys.concat());
+        let (status, before, after) = run_on_pty(&["top", "-d", "30"], input.as_bytes(), None);
+        prop_assert_eq!(status.code(), Some(0));
+        prop_assert!(before & libc::ICANON as u32 != 0);
+        prop_assert_eq!(after, before);
+    }
+
+    /// A signal that kills top still puts the terminal back
+    #[test]
+    fn top_restores_terminal_when_killed(signal in prop::sample::select(vec![libc::SIGTERM, libc::SIGHUP, libc::SIGINT, libc::SIGQUIT])) {
+        use std::os::unix::process::ExitStatusExt;
+        let (status, before, after) = run_on_pty(&["top", "-d", "30"], b"", Some(signal));
+        prop_assert_eq!(status.signal(), Some(signal));
+        prop_assert_eq!(after, before);
+    }
+}
+
+// =============================================================================
+// Login Record Tests
+// =============================================================================
+
+/// A utmp record in glibc's layout
+fn utmp_record(kind: i16, pid: i32, line: &str, user: &str, host: &str, time: u32) -> Vec<u8> {
+    let mut record = vec![0u8; 384];
+    let mut put = |at: usize, len: usize, s: &[u8]| record[at..at + s.len().min(len)].copy_from_slice(&s[..s.len().min(len)]);
+    put(0, 2, &kind.to_ne_bytes());
+    put(4, 4, &pid.to_ne_bytes());
+    put(8, 32, line.as_bytes());
+    put(40, 4, line[line.len().saturating_sub(4)..].as_bytes());
+    put(44, 32, user.as_bytes());
+    put(76, 256, host.as_bytes());
+    put(340, 4, &time.to_ne_bytes());
+    record
+}
+
+/// Writes `records` to `ROOT/var/run/utmp` or `ROOT/var/log/wtmp`
+fn write_utmp(root: &tempfile::TempDir, file: &str, records: &[Vec<u8>]) -> String {
+    let path = root.path().join(file);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, records.concat()).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn run_lines(args: &[&str], root: &tempfile::TempDir) -> Vec<String> {
+    let output = Command::new(armybox()).args(args).env("ARMYBOX_ROOT", root.path()).env("TZ", "UTC").output().unwrap();
+    String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect()
+}
+
+proptest! {
+    /// who, users and w list the users logged in, and not the other records
+    #[test]
+    fn who_lists_logged_in_users(names in prop::collection::vec("[a-z]{1,8}", 1..8), level in b'1'..b'6') {
+        let root = tempfile::tempdir().unwrap();
+        let mut records = vec![
+            utmp_record(2, 0, "~", "reboot", "", 1_700_000_000),
+            utmp_record(1, level as i32 + 256 * b'S' as i32, "~", "runlevel", "", 1_700_000_010),
+            utmp_record(6, 100, "tty9", "LOGIN", "", 1_700_000_020),
+            utmp_record(8, 101, "pts/99", "", "", 1_700_000_030),
+        ];
+        for (i, name) in names.iter().enumerate() {
+            records.push(utmp_record(7, 1000 + i as i32, &format!("pts/{}", i), name, "", 1_700_000_100 + i as u32));
+        }
+        let path = write_utmp(&root, "var/run/utmp", &records);
+
+        let who = run_lines(&["who", &path], &root);
+        let listed: Vec<(String, String)> = who.iter().map(|l| {
+            let mut words = l.split_whitespace();
+            (words.next().unwrap().to_string(), words.next().unwrap().to_string())
+        }).collect();
+        let expected: Vec<(String, String)> = names.iter().enumerate().map(|(i, n)| (n.clone(), format!("pts/{}", i))).collect();
+        prop_assert_eq!(listed, expected);
+
+        let mut sorted = names.clone();
+        sorted.sort();
+        prop_assert_eq!(run_lines(&["users", &path], &root), vec![sorted.join(" ")]);
+        prop_assert_eq!(run_lines(&["who", "-q", &path], &root), vec![names.join(" "), format!("# users={}", names.len())]);
+        let w: Vec<String> = run_lines(&["w", "-h", "-s"], &root).iter().map(|l| l.split_whitespace().next().unwrap().to_string()).collect();
+        prop_assert_eq!(w, names.clone());
+
+        let level = (level as char).to_string();
+        prop_assert_eq!(run_lines(&["runlevel", &path], &root), vec![format!("S {}", level)]);
+        let run_level = run_lines(&["who", "-r", &path], &root);
+        prop_assert_eq!(run_level.len(), 1);
+        let wanted = format!("run-level {}", level);
+        prop_assert!(run_level[0].contains(&wanted), "{}", run_level[0]);
+        prop_assert_eq!(run_lines(&["who", "-b", &path], &root).len(), 1);
+    }
+
+    /// last pairs each login with its logout, and ends sessions left open
+    /// at the next boot
+    #[test]
+    fn last_pairs_logins_with_logouts(sessions in prop::collection::vec((0u32..1000, 0u32..3000), 1..6)) {
+        let root = tempfile::tempdir().unwrap();
+        let base = 1_700_000_000u32;
+        let hhmm = |t: u32| format!("{:02}:{:02}", t / 3600 % 24, t / 60 % 60);
+        let duration = |secs: u32| match secs / 86400 {
+            0 => format!("({:02}:{:02})", secs / 3600, secs / 60 % 60),
+            days => format!("({}+{:02}:{:02})", days, secs / 3600 % 24, secs / 60 % 60),
+        };
+
+        // The last session is never logged out, and crashes at the reboot
+        let mut events = vec![(base, utmp_record(2, 0, "~", "reboot", "", base))];
+        let mut expected = Vec::new();
+        let mut end_of_all = base;
+        for (i, &(start, length)) in sessions.iter().enumerate() {
+            let (user, line) = (format!("u{}", i), format!("tty{}", i));
+            let login = base + 60 + start * 60 + i as u32;
+            let logout = login + length * 60;
+            events.push((login, utmp_record(7, 1000 + i as i32, &line, &user, "", login)));
+            if i + 1 < sessions.len() {
+                events.push((logout, utmp_record(8, 1000 + i as i32, &line, "", "", logout)));
+            }
+            end_of_all = end_of_all.max(logout);
+            expected.push((login, user, line, logout));
+        }
+        let reboot = end_of_all + 60;
+        events.push((reboot, utmp_record(2, 0, "~", "reboot", "", reboot)));
+        events.sort_by_key(|e| e.0);
+        let path = write_utmp(&root, "var/log/wtmp", &events.into_iter().map(|e| e.1).collect::<Vec<_>>());
+
+        expected.sort_by_key(|e| std::cmp::Reverse(e.0));
+        let lines: Vec<String> = run_lines(&["last", "-f", &path], &root).into_iter().filter(|l| l.starts_with('u')).collect();
+        prop_assert_eq!(lines.len(), expected.len());
+        for (line, (login, user, tty, logout)) in lines.iter().zip(&expected) {
+            let columns = format!("{:<9}{:<13}", user, tty);
+            prop_assert!(line.starts_with(&columns), "{}", line);
+            prop_assert!(line.contains(&hhmm(*login)), "{}", line);
+            let end = if *user == format!("u{}", sessions.len() - 1) {
+                format!("- crash  {}", duration(reboot - login))
+            } else {
+                format!("- {}  {}", hhmm(*logout), duration(logout - login))
+            };
+            prop_assert!(line.ends_with(&end), "{} does not end with {}", line, end);
+        }
+    }
+}
+
+// =============================================================================
+// Rev/Tac Tests
+// =============================================================================
+
+proptest! {
+    /// rev should reverse each line
+    #[test]
+    fn rev_reverses_lines(lines in prop::collection::vec("[a-zA-Z0-9]{1,50}", 1..20)) {
+        let content = lines.join("\n") + "\n";
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(content.as_bytes()).unwrap();
+        let path = temp.path().to_str().unwrap();
+
+        let output = Command::new(armybox())
+            .args(["rev", path])
+            .output()
+            .unwrap();
+
+        let output_string = String::from_utf8_lossy(&output.stdout);
+        let output_lines: Vec<&str> = output_string.lines().collect();
+
+        for (orig, rev) in lines.iter().zip(output_lines.iter()) {
+            let expected: String = orig.chars().rev().collect();
+            prop_assert_eq!(*rev, expected.as_str());
+        }
+    }
+
+    /// tac should reverse line order
+    #[test]
+    fn tac_reverses_line_order(lines in prop::collection::vec("[a-z]{1,20}", 1..50)) {
+        let content = lines.join("\n") + "\n";
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(content.as_bytes()).unwrap();
+        let path = temp.path().to_str().unwrap();
+
+        let output = Command::new(armybox())
+            .args(["tac", path])
+            .output()
+            .unwrap();
+
+        let output_lines: Vec<_> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let mut expected = lines.clone();
+        expected.reverse();
+        prop_assert_eq!(output_lines, expected);
+    }
+}
+
+// =============================================================================
+// Checksum Tests
+// =============================================================================
+
+proptest! {
+    /// checksums should be deterministic
+    #[test]
+    fn md5sum_deterministic(data in prop::collection::vec(any::<u8>(), 0..10000)) {
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(&data).unwrap();
+        let path = temp.path().to_str().unwrap();
+
+        let output1 = Command::new(armybox())
+            .args(["md5sum", path])
+            .output()
+            .unwrap();
+
+        if !output1.status.success() {
+            return Ok(());
+        }
+
+        let output2 = Command::new(armybox())
+            .args(["md5sum", path])
+            .output()
+            .unwrap();
+
+        prop_assert_eq!(output1.stdout, output2.stdout);
+    }
+
+    /// different data should produce different checksums (with high probability)
+    #[test]
+    fn md5sum_different_for_different_input(
+        data1 in prop::collection::vec(any::<u8>(), 10..1000),
+        data2 in prop::collection::vec(any::<u8>(), 10..1000)
+    ) {
+        prop_assume!(data1 != data2);
+
+        let mut temp1 = NamedTempFile::new().unwrap();
+        temp1.write_all(&data1).unwrap();
+        let path1 = temp1.path().to_str().unwrap();
+
+        let mut temp2 = NamedTempFile::new().unwrap();
+        temp2.write_all(&data2).unwrap();
+        let path2 = temp2.path().to_str().unwrap();
+
+        let output1 = Command::new(armybox())
+            .args(["md5sum", path1])
+            .output()
+            .unwrap();
+
+        if !output1.status.success() {
+            return Ok(());
+        }
+
+        let output2 = Command::new(armybox())
+            .args(["md5sum", path2])
+            .output()
+            .unwrap();
+
+        // Extract just the hash (first field)
+        let stdout1 = String::from_utf8_lossy(&output1.stdout);
+        let hash1 = stdout1.split_whitespace().next().unwrap_or("");
+        let stdout2 = String::from_utf8_lossy(&output2.stdout);
+        let hash2 = stdout2.split_whitespace().next().unwrap_or("");
+
+        prop_assert_ne!(hash1, hash2);
+    }
+}
+
+// =============================================================================
+// Compression Roundtrip Tests
+// =============================================================================
+
+proptest! {
+    /// gzip/gunzip roundtrip should preserve data
+    #[test]
+    fn gzip_roundtrip(data in prop::collection::vec(any::<u8>(), 1..10000)) {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input");
+        std::fs::write(&input_path, &data).unwrap();
+
+        // Compress
+        let status = Command::new(armybox())
+            .args(["gzip", "-k", input_path.to_str().unwrap()])
+            .status()
+            .unwrap();
+
+        if !status.success() {
+            return Ok(());
+        }
+
+        let gz_path = format!("{}.gz", input_path.to_str().unwrap());
+
+        // Decompress
+        let output = Command::new(armybox())
+            .args(["zcat", &gz_path])
+            .output()
+            .unwrap();
+
+        prop_assert_eq!(&output.stdout, &data);
+    }
+}
+
+// =============================================================================
+// JSON Output Tests
+// =============================================================================
+
+/// A parsed JSON value, with numbers kept as their text
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    Str(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().map(|(k, _)| k.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn items(&self) -> &[JsonValue] {
+        match self {
+            JsonValue::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        match self {
+            JsonValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn num(&self) -> Option<&str> {
+        match self {
+            JsonValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+/// Parses one strict JSON document, followed only by a newline
+fn parse_json(text: &str) -> Result<JsonValue, String> {
+    fn skip(s: &[u8], i: &mut usize) {
+        while *i < s.len() && matches!(s[*i], b' ' | b'\t' | b'\n' | b'\r') {
+            *i += 1;
+        }
+    }
+    fn expect(s: &[u8], i: &mut usize, c: u8) -> Result<(), String> {
+        skip(s, i);
+        if s.get(*i) != Some(&c) {
+            return Err(format!("expected {:?} at {}", c as char, i));
+        }
+        *i += 1;
+        Ok(())
+    }
+    fn string(s: &[u8], i: &mut usize) -> Result<String, String> {
+        expect(s, i, b'"')?;
+        let mut out = Vec::new();
+        loop {
+            match s.get(*i) {
+                None => return Err("unterminated string".into()),
+                Some(b'"') => break,
+                Some(&c) if c < 0x20 => return Err(format!("raw control byte at {}", i)),
+                Some(b'\\') => {
+                    *i += 1;
+                    let c = match s.get(*i) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let hex = s.get(*i + 1..*i + 5).ok_or("short \\u escape")?;
+                            let code = u32::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).map_err(|e| e.to_string())?;
+                            *i += 4;
+                            char::from_u32(code).ok_or("bad \\u escape")?
+                        }
+                        _ => return Err(format!("bad escape at {}", i)),
+                    };
+                    out.extend_from_slice(c.to_string().as_bytes());
+                }
+                Some(&c) => out.push(c),
+            }
+            *i += 1;
+        }
+        *i += 1;
+        String::from_utf8(out).map_err(|e| e.to_string())
+    }
+    fn value(s: &[u8], i: &mut usize) -> Result<JsonValue, String> {
+        skip(s, i);
+        match s.get(*i) {
+            Some(b'{') => {
+                *i += 1;
+                let mut fields = Vec::new();
+                skip(s, i);
+                if s.get(*i) == Some(&b'}') {
+                    *i += 1;
+                    return Ok(JsonValue::Object(fields));
+                }
+                loop {
+                    let key = string(s, i)?;
+                    expect(s, i, b':')?;
+                    fields.push((key, value(s, i)?));
+                    skip(s, i);
+                    match s.get(*i) {
+                        Some(b',') => *i += 1,
+                        Some(b'}') => {
+                            *i += 1;
+                            return Ok(JsonValue::Object(fields));
+                        }
+                        _ => return Err(format!("expected , or }} at {}", i)),
+                    }
+                    skip(s, i);
+                }
+            }
+            Some(b'[') => {
+                *i += 1;
+                let mut items = Vec::new();
+                skip(s, i);
+                if s.get(*i) == Some(&b']') {
+                    *i += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(value(s, i)?);
+                    skip(s, i);
+                    match s.get(*i) {
+                        Some(b',') => *i += 1,
+                        Some(b']') => {
+                            *i += 1;
+                            return Ok(JsonValue::Array(items));
+                        }
+                        _ => return Err(format!("expected , or ] at {}", i)),
+                    }
+                }
+            }
+            Some(b'"') => string(s, i).map(JsonValue::Str),
+            Some(b't') if s[*i..].starts_with(b"true") => {
+                *i += 4;
+                Ok(JsonValue::Bool(true))
+            }
+            Some(b'f') if s[*i..].starts_with(b"false") => {
+                *i += 5;
+                Ok(JsonValue::Bool(false))
+            }
+            Some(b'n') if s[*i..].starts_with(b"null") => {
+                *i += 4;
+                Ok(JsonValue::Null)
+            }
+            Some(&c) if c == b'-' || c.is_ascii_digit() => {
+                let start = *i;
+                *i += 1;
+                while *i < s.len() && (s[*i].is_ascii_digit() || s[*i] == b'.') {
+                    *i += 1;
+                }
+                let n = std::str::from_utf8(&s[start..*i]).unwrap();
+                if n.ends_with('.') || n.matches('.').count() > 1 || (n.len() > 1 && n.trim_start_matches('-').starts_with("0") && !n.trim_start_matches('-').starts_with("0.")) {
+                    return Err(format!("bad number {}", n));
+                }
+                Ok(JsonValue::Number(n.to_string()))
+            }
+            _ => Err(format!("unexpected input at {}", i)),
+        }
+    }
+
+    let s = text.as_bytes();
+    let mut i = 0;
+    let v = value(s, &mut i)?;
+    if &s[i..] != b"\n" {
+        return Err(format!("trailing input at {}", i));
+    }
+    Ok(v)
+}
+
+/// Runs armybox with `env`, parsing its output as one JSON document
+fn run_json(args: &[&str], env: &[(&str, &std::path::Path)]) -> Result<JsonValue, String> {
+    let mut cmd = Command::new(armybox());
+    cmd.args(args);
+    for (key, value) in env {
+        cmd.env(key, value);
+    }
+    let output = cmd.output().unwrap();
+    parse_json(&String::from_utf8(output.stdout).map_err(|e| e.to_string())?)
+}
+
+proptest! {
+    /// stat --json escapes any file name and replaces bytes that aren't UTF-8
+    #[test]
+    fn stat_json_escapes_names(
+        name in prop::collection::vec(prop_oneof![1u8..0x2f, 0x30u8..=0xff], 1..40),
+        size in 0usize..5000,
+    ) {
+        prop_assume!(name != b"." && name != b"..");
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(std::ffi::OsStr::from_bytes(&name));
+        std::fs::write(&path, vec![b'x'; size]).unwrap();
+        let output = Command::new(armybox()).args(["stat", "--json"]).arg(&path).output().unwrap();
+        let doc = parse_json(&String::from_utf8(output.stdout).unwrap()).unwrap();
+        let doc = &doc.items()[0];
+        let file = doc.get("file").and_then(JsonValue::str).unwrap_or("");
+        match std::str::from_utf8(path.as_os_str().as_bytes()) {
+            Ok(path) => prop_assert_eq!(file, path),
+            Err(_) => prop_assert!(file.contains(char::REPLACEMENT_CHARACTER)),
+        }
+        let size_text = size.to_string();
+        prop_assert_eq!(doc.get("size").and_then(JsonValue::num), Some(size_text.as_str()));
+        prop_assert_eq!(doc.get("type").and_then(JsonValue::str), Some(if size == 0 { "regular empty file" } else { "regular file" }));
+        prop_assert_eq!(doc.keys(), vec![
+            "file", "type", "size", "blocks", "io_block", "device", "inode", "links", "mode", "permissions",
+            "uid", "user", "gid", "group", "atime", "atime_nsec", "mtime", "mtime_nsec", "ctime", "ctime_nsec",
+        ]);
+    }
+
+    /// ps --json has one object per process, keyed by the -o names
+    #[test]
+    fn ps_json_reads_proc_fixture(
+        pids in prop::collection::btree_set(1_000_000u32..4_000_000, 1..6),
+        procs in prop::collection::vec(fake_process(), 6),
+    ) {
+        let pids: Vec<u32> = pids.into_iter().collect();
+        let root = proc_fixture(&pids, &procs);
+        let doc = run_json(&["ps", "--json", "-o", "pid,ppid,comm,vsz,args"], &[("ARMYBOX_PROC", root.path())]).unwrap();
+        let mut rows: Vec<(String, String, String, String, String)> = doc
+            .items()
+            .iter()
+            .map(|row| {
+                let field = |key| row.get(key).and_then(|v| v.num().or(v.str())).unwrap_or("?").to_string();
+                (field("pid"), field("ppid"), field("comm"), field("vsz"), field("args"))
+            })
+            .collect();
+        rows.sort();
+        let mut expected: Vec<(String, String, String, String, String)> = pids
+            .iter()
+            .zip(&procs)
+            .map(|(pid, p)| {
+                let args = std::iter::once(format!("/usr/bin/{}", p.comm)).chain(p.args.iter().cloned()).collect::<Vec<_>>().join(" ");
+                (pid.to_string(), p.ppid.to_string(), p.comm.clone(), p.vsize.to_string(), args)
+            })
+            .collect();
+        expected.sort();
+        prop_assert_eq!(rows, expected);
+    }
+
+    /// free and uptime --json report /proc/meminfo, uptime and loadavg in bytes and seconds
+    #[test]
+    fn free_and_uptime_json(
+        total in 1u64..1 << 30,
+        free_frac in 0u64..100,
+        buffers in 0u64..1 << 20,
+        swap in 0u64..1 << 30,
+        up in 0u64..100_000_000,
+        loads in prop::array::uniform3(0u64..100_000),
+    ) {
+        let root = tempfile::TempDir::new().unwrap();
+        let free = total * free_frac / 100;
+        let meminfo = format!(
+            "MemTotal: {} kB\nMemFree: {} kB\nMemAvailable: {} kB\nBuffers: {} kB\nCached: 0 kB\nShmem: 7 kB\nSwapTotal: {} kB\nSwapFree: {} kB\n",
+            total, free, free, buffers, swap, swap / 2,
+        );
+        std::fs::write(root.path().join("meminfo"), meminfo).unwrap();
+        std::fs::write(root.path().join("uptime"), format!("{}.{:02} 0.00\n", up / 100, up % 100)).unwrap();
+        let loadavg = format!("{}.{:02} {}.{:02} {}.{:02} 1/100 42\n", loads[0] / 100, loads[0] % 100, loads[1] / 100, loads[1] % 100, loads[2] / 100, loads[2] % 100);
+        std::fs::write(root.path().join("loadavg"), loadavg).unwrap();
+
+        let doc = run_json(&["free", "--json"], &[("ARMYBOX_PROC", root.path())]).unwrap();
+        let mem = doc.get("mem").unwrap();
+        prop_assert_eq!(mem.keys(), vec!["total", "used", "free", "shared", "buff_cache", "available"]);
+        let total_bytes = (total * 1024).to_string();
+        prop_assert_eq!(mem.get("total").and_then(JsonValue::num), Some(total_bytes.as_str()));
+        let used = (total.saturating_sub(free + buffers) * 1024).to_string();
+        prop_assert_eq!(mem.get("used").and_then(JsonValue::num), Some(used.as_str()));
+        prop_assert_eq!(mem.get("shared").and_then(JsonValue::num), Some("7168"));
+        let swap_used = ((swap - swap / 2) * 1024).to_string();
+        prop_assert_eq!(doc.get("swap").and_then(|s| s.get("used")).and_then(JsonValue::num), Some(swap_used.as_str()));
+
+        let doc = run_json(&["uptime", "--json"], &[("ARMYBOX_PROC", root.path())]).unwrap();
+        let uptime = format!("{}.{:02}", up / 100, up % 100);
+        prop_assert_eq!(doc.get("uptime").and_then(JsonValue::num), Some(uptime.as_str()));
+        let expected: Vec<String> = loads.iter().map(|n| format!("{}.{:02}", n / 100, n % 100)).collect();
+        let actual: Vec<String> = doc.get("load_average").unwrap().items().iter().filter_map(JsonValue::num).map(String::from).collect();
+        prop_assert_eq!(actual, expected);
+    }
+
+    /// lsmod and mount --json read /proc/modules and /proc/self/mounts
+    #[test]
+    fn lsmod_and_mount_json(
+        modules in prop::collection::vec(("[a-z_]{1,12}", 0u64..1 << 24, prop::collection::vec("[a-z_]{1,8}", 0..3)), 0..6),
+        mounts in prop::collection::vec(("[a-z/]{1,12}", "/[a-z ]{1,12}", "[a-z0-9]{1,8}", prop::collection::vec("[a-z]{1,6}", 1..4)), 1..6),
+    ) {
+        let root = tempfile::TempDir::new().unwrap();
+        let mut table = String::new();
+        for (name, size, users) in &modules {
+            let by = if users.is_empty() { "-".to_string() } else { users.join(",") + "," };
+            table += &format!("{} {} {} {} Live 0x0000000000000000\n", name, size, users.len(), by);
+        }
+        std::fs::write(root.path().join("modules"), table).unwrap();
+        std::fs::create_dir(root.path().join("self")).unwrap();
+        let mut table = String::new();
+        for (source, target, fstype, options) in &mounts {
+            table += &format!("{} {} {} {} 0 0\n", source, target.replace(' ', "\\040"), fstype, options.join(","));
+        }
+        std::fs::write(root.path().join("self/mounts"), table).unwrap();
+
+        let doc = run_json(&["lsmod", "--json"], &[("ARMYBOX_PROC", root.path())]).unwrap();
+        prop_assert_eq!(doc.items().len(), modules.len());
+        for (row, (name, size, users)) in doc.items().iter().zip(&modules) {
+            prop_assert_eq!(row.get("name").and_then(JsonValue::str), Some(name.as_str()));
+            let size = size.to_string();
+            prop_assert_eq!(row.get("size").and_then(JsonValue::num), Some(size.as_str()));
+            let used_by: Vec<&str> = row.get("used_by").unwrap().items().iter().filter_map(JsonValue::str).collect();
+            prop_assert_eq!(used_by, users.iter().map(String::as_str).collect::<Vec<_>>());
+        }
+
+        let doc = run_json(&["mount", "--json"], &[("ARMYBOX_PROC", root.path())]).unwrap();
+        prop_assert_eq!(doc.items().len(), mounts.len());
+        for (row, (source, target, fstype, options)) in doc.items().iter().zip(&mounts) {
+            prop_assert_eq!(row.keys(), vec!["source", "target", "fstype", "options"]);
+            prop_assert_eq!(row.get("source").and_then(JsonValue::str), Some(source.as_str()));
+            prop_assert_eq!(row.get("target").and_then(JsonValue::str), Some(target.as_str()));
+            prop_assert_eq!(row.get("fstype").and_then(JsonValue::str), Some(fstype.as_str()));
+            let listed: Vec<&str> = row.get("options").unwrap().items().iter().filter_map(JsonValue::str).collect();
+            prop_assert_eq!(listed, options.iter().map(String::as_str).collect::<Vec<_>>());
+        }
+    }
+
+    /// blkid --json reads an ext superblock's UUID and label
+    #[test]
+    fn blkid_json_reads_ext_superblock(
+        uuid in prop::array::uniform16(any::<u8>()),
+        label in "[A-Za-z0-9_-]{0,16}",
+        incompat in prop::sample::select(vec![0u32, 0x40, 0x2c2]),
+    ) {
+        let mut image = vec![0u8; 8192];
+        image[1024 + 56..1024 + 58].copy_from_slice(&[0x53, 0xef]);
+        image[1024 + 96..1024 + 100].copy_from_slice(&incompat.to_le_bytes());
+        image[1024 + 104..1024 + 120].copy_from_slice(&uuid);
+        image[1024 + 120..1024 + 120 + label.len()].copy_from_slice(label.as_bytes());
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&image).unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let doc = run_json(&["blkid", "--json", path], &[]).unwrap();
+        let row = &doc.items()[0];
+        prop_assert_eq!(row.get("device").and_then(JsonValue::str), Some(path));
+        prop_assert_eq!(row.get("type").and_then(JsonValue::str), Some(if incompat == 0 { "ext2" } else { "ext4" }));
+        let hex: String = uuid.iter().map(|b| format!("{:02x}", b)).collect();
+        let expected = format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]);
+        prop_assert_eq!(row.get("uuid").and_then(JsonValue::str), Some(expected.as_str()));
+        if label.is_empty() {
+            prop_assert_eq!(row.get("label"), Some(&JsonValue::Null));
+        } else {
+            prop_assert_eq!(row.get("label").and_then(JsonValue::str), Some(label.as_str()));
+        }
+    }
+}
+
+#[test]
+fn df_and_ip_json_are_valid() {
+    let doc = run_json(&["df", "--json", "/"], &[]).unwrap();
+    let row = &doc.items()[0];
+    assert_eq!(row.keys(), vec!["filesystem", "type", "size", "used", "available", "use_percent", "mounted_on"]);
+    assert_eq!(row.get("mounted_on").and_then(JsonValue::str), Some("/"));
+
+    let doc = run_json(&["ip", "-j", "link"], &[]).unwrap();
+    let lo = doc.items().iter().find(|link| link.get("ifname").and_then(JsonValue::str) == Some("lo"));
+    assert_eq!(lo.and_then(|lo| lo.get("link_type")).and_then(JsonValue::str), Some("loopback"));
+}
+
+// =============================================================================
+// Cleanup Tests
+// =============================================================================
+
+/// The names in `dir`, sorted
+fn dir_names(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    names.sort();
+    names
+}
+
+/// Waits up to 5 seconds for `done`
+fn wait_for(mut done: impl FnMut() -> bool) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < std::time::Duration::from_secs(5) {
+        if done() {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    false
+}
+
+proptest! {
+    /// sed -i leaves what sed would print, with the old mode and no
+    /// temporary file, and -iSUFFIX keeps the old contents
+    #[test]
+    fn sed_in_place_matches_output(
+        lines in prop::collection::vec("[abc ]{0,12}", 0..20),
+        mode in prop::sample::select(vec![0o600u32, 0o640, 0o755]),
+        backup in any::<bool>(),
+    ) {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("f");
+        let text = lines.join("\n");
+        std::fs::write(&path, &text).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        let path = path.to_str().unwrap();
+
+        let expected = Command::new(armybox()).args(["sed", "s/a/X/g", path]).output().unwrap().stdout;
+        let option = if backup { "-i.orig" } else { "-i" };
+        let status = Command::new(armybox()).args(["sed", option, "s/a/X/g", path]).status().unwrap();
+        prop_assert!(status.success());
+        prop_assert_eq!(std::fs::read(path).unwrap(), expected);
+        prop_assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o7777, mode);
+        if backup {
+            prop_assert_eq!(std::fs::read_to_string(format!("{}.orig", path)).unwrap(), text);
+            prop_assert_eq!(dir_names(dir.path()), vec!["f", "f.orig"]);
+        } else {
+            prop_assert_eq!(dir_names(dir.path()), vec!["f"]);
+        }
+    }
+
+    /// sort -o may name one of its inputs
+    #[test]
+    fn sort_output_replaces_input(lines in prop::collection::vec("[a-z0-9]{1,8}", 1..30)) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("f");
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let expected = Command::new(armybox()).args(["sort", path]).output().unwrap().stdout;
+        let status = Command::new(armybox()).args(["sort", "-o", path, path]).status().unwrap();
+        prop_assert!(status.success());
+        prop_assert_eq!(std::fs::read(path).unwrap(), expected);
+        prop_assert_eq!(dir_names(dir.path()), vec!["f"]);
+    }
+
+    /// sort -o writes through a symlink or FIFO instead of replacing it
+    #[test]
+    fn sort_output_keeps_symlinks_and_fifos(lines in prop::collection::vec("[a-z0-9]{1,8}", 1..30)) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("in");
+        std::fs::write(&input, lines.join("\n") + "\n").unwrap();
+        let input = input.to_str().unwrap();
+        let expected = Command::new(armybox()).args(["sort", input]).output().unwrap().stdout;
+
+        let target = dir.path().join("target");
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let status = Command::new(armybox()).args(["sort", "-o", link.to_str().unwrap(), input]).status().unwrap();
+        prop_assert!(status.success());
+        prop_assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        prop_assert_eq!(std::fs::read(&target).unwrap(), expected.clone());
+
+        let fifo = dir.path().join("fifo");
+        let fifo_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        prop_assert_eq!(unsafe { libc::mkfifo(fifo_path.as_ptr(), 0o644) }, 0);
+        let reader = std::thread::spawn({
+            let fifo = fifo.clone();
+            move || std::fs::read(fifo).unwrap()
+        });
+        let status = Command::new(armybox()).args(["sort", "-o", fifo.to_str().unwrap(), input]).status().unwrap();
+        prop_assert!(status.success());
+        prop_assert_eq!(reader.join().unwrap(), expected);
+        {
+            use std::os::unix::fs::FileTypeExt;
+            prop_assert!(std::fs::symlink_metadata(&fifo).unwrap().file_type().is_fifo());
+        }
+        prop_assert_eq!(dir_names(dir.path()), vec!["fifo", "in", "link", "target"]);
+    }
+
+    /// A signal that kills sort -o removes its temporary file, and the
+    /// process still dies by the signal
+    #[test]
+    fn interrupted_sort_removes_temp_file(signal in prop::sample::select(vec![libc::SIGTERM, libc::SIGHUP, libc::SIGINT])) {
+        use std::os::unix::process::ExitStatusExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out");
+        // stdin stays open, so sort is still reading when the signal comes
+        let mut child = Command::new(armybox())
+            .args(["sort", "-o", out.to_str().unwrap()])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        prop_assert!(wait_for(|| dir_names(dir.path()).iter().any(|name| name.starts_with(".out."))));
+        unsafe { libc::kill(child.id() as i32, signal) };
+        let status = child.wait().unwrap();
+        prop_assert_eq!(status.signal(), Some(signal));
+        prop_assert!(dir_names(dir.path()).is_empty());
+    }
+}
+
+#[test]
+fn killed_timeout_takes_its_command() {
+    use std::io::BufRead;
+    let mut child = Command::new(armybox())
+        .args(["timeout", "100", "/bin/sh", "-c", "echo $$; exec sleep 100"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    std::io::BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let pid: i32 = line.trim().parse().unwrap();
+    unsafe { libc::kill(child.id() as i32, libc::SIGTERM) };
+    child.wait().unwrap();
+    assert!(wait_for(|| unsafe { libc::kill(pid, 0) } < 0), "command outlived timeout");
 }